@@ -670,6 +670,15 @@ impl<T, const MUT: bool> GuestRet for Ptr<T, MUT> {
     }
 }
 
+impl GuestRet for GuestFunction {
+    fn from_regs(regs: &[u32]) -> Self {
+        GuestFunction(<ConstVoidPtr as GuestRet>::from_regs(regs))
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        <ConstVoidPtr as GuestRet>::to_regs(self.0, regs)
+    }
+}
+
 // GuestRet implementations for u64-like types
 
 impl GuestRet for u64 {
@@ -57,6 +57,7 @@ extern "C" {
 
     pub fn alcMakeContextCurrent(context: *mut ALCcontext) -> ALCboolean;
     pub fn alcGetCurrentContext() -> *mut ALCcontext;
+    pub fn alcGetContextsDevice(context: *mut ALCcontext) -> *mut ALCdevice;
 
     pub fn alcGetError(device: *mut ALCdevice) -> ALCenum;
 }
@@ -85,8 +86,14 @@ use al_types::*;
 
 pub const AL_NO_ERROR: ALenum = 0;
 
+pub const AL_POSITION: ALenum = 0x1004;
+pub const AL_DIRECTION: ALenum = 0x1005;
+pub const AL_VELOCITY: ALenum = 0x1006;
+
 pub const AL_MAX_GAIN: ALenum = 0x100E;
 
+pub const AL_ORIENTATION: ALenum = 0x100F;
+
 pub const AL_SOURCE_STATE: ALenum = 0x1010;
 
 pub const AL_INITIAL: ALenum = 0x1011;
@@ -105,30 +112,71 @@ pub const AL_FORMAT_STEREO16: ALenum = 0x1103;
 extern "C" {
     pub fn alGetError() -> ALenum;
 
+    pub fn alEnable(capability: ALenum);
+    pub fn alDisable(capability: ALenum);
+    pub fn alIsEnabled(capability: ALenum) -> ALboolean;
+
+    pub fn alGetBooleanv(param: ALenum, values: *mut ALboolean);
+    pub fn alGetIntegerv(param: ALenum, values: *mut ALint);
+    pub fn alGetFloatv(param: ALenum, values: *mut ALfloat);
+    pub fn alGetDoublev(param: ALenum, values: *mut ALdouble);
+
     pub fn alDistanceModel(value: ALenum);
 
     pub fn alListenerf(param: ALenum, value: ALfloat);
     pub fn alListenerfv(param: ALenum, values: *const ALfloat);
     pub fn alListener3f(param: ALenum, value1: ALfloat, value2: ALfloat, value3: ALfloat);
+    pub fn alListeneri(param: ALenum, value: ALint);
+    pub fn alGetListenerf(param: ALenum, value: *mut ALfloat);
+    pub fn alGetListenerfv(param: ALenum, values: *mut ALfloat);
+    pub fn alGetListener3f(
+        param: ALenum,
+        value1: *mut ALfloat,
+        value2: *mut ALfloat,
+        value3: *mut ALfloat,
+    );
+    pub fn alGetListeneri(param: ALenum, value: *mut ALint);
 
     pub fn alGenSources(n: ALsizei, sources: *mut ALuint);
     pub fn alDeleteSources(n: ALsizei, sources: *const ALuint);
+    pub fn alIsSource(source: ALuint) -> ALboolean;
 
     pub fn alSourcef(source: ALuint, param: ALenum, value: ALfloat);
     pub fn alSourcei(source: ALuint, param: ALenum, value: ALint);
     pub fn alSourcefv(source: ALuint, param: ALenum, values: *const ALfloat);
+    pub fn alSource3f(
+        source: ALuint,
+        param: ALenum,
+        value1: ALfloat,
+        value2: ALfloat,
+        value3: ALfloat,
+    );
     pub fn alGetSourcef(source: ALuint, param: ALenum, value: *mut ALfloat);
     pub fn alGetSourcei(source: ALuint, param: ALenum, value: *mut ALint);
+    pub fn alGetSourcefv(source: ALuint, param: ALenum, values: *mut ALfloat);
+    pub fn alGetSource3f(
+        source: ALuint,
+        param: ALenum,
+        value1: *mut ALfloat,
+        value2: *mut ALfloat,
+        value3: *mut ALfloat,
+    );
 
     pub fn alSourcePlay(source: ALuint);
     pub fn alSourcePause(source: ALuint);
     pub fn alSourceStop(source: ALuint);
+    pub fn alSourceRewind(source: ALuint);
+    pub fn alSourcePlayv(n: ALsizei, sources: *const ALuint);
+    pub fn alSourcePausev(n: ALsizei, sources: *const ALuint);
+    pub fn alSourceStopv(n: ALsizei, sources: *const ALuint);
+    pub fn alSourceRewindv(n: ALsizei, sources: *const ALuint);
 
     pub fn alSourceQueueBuffers(source: ALuint, nb: ALsizei, buffers: *const ALuint);
     pub fn alSourceUnqueueBuffers(source: ALuint, nb: ALsizei, buffers: *mut ALuint);
 
     pub fn alGenBuffers(n: ALsizei, buffers: *mut ALuint);
     pub fn alDeleteBuffers(n: ALsizei, buffers: *const ALuint);
+    pub fn alIsBuffer(buffer: ALuint) -> ALboolean;
 
     pub fn alBufferData(
         buffer: ALuint,
@@ -137,6 +185,8 @@ extern "C" {
         size: ALsizei,
         samplerate: ALsizei,
     );
+    pub fn alGetBufferf(buffer: ALuint, param: ALenum, value: *mut ALfloat);
+    pub fn alGetBufferi(buffer: ALuint, param: ALenum, value: *mut ALint);
 
     pub fn alDopplerFactor(dopplerFactor: ALfloat);
     pub fn alDopplerVelocity(dopplerVelocity: ALfloat);
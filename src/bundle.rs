@@ -105,6 +105,12 @@ impl Bundle {
             .join(self.plist["CFBundleExecutable"].as_string().unwrap())
     }
 
+    /// Path of the bundle describing the app's preferences for the Settings
+    /// app. Not every app has one.
+    pub fn settings_bundle_path(&self) -> GuestPathBuf {
+        self.path.join("Settings.bundle")
+    }
+
     pub fn launch_image_path(&self) -> GuestPathBuf {
         if let Some(base_name) = self.plist.get("UILaunchImageFile") {
             self.path
@@ -140,6 +146,39 @@ impl Bundle {
             })
     }
 
+    /// Whether the status bar should be hidden when the app starts.
+    pub fn status_bar_hidden(&self) -> bool {
+        self.plist
+            .get("UIStatusBarHidden")
+            .map_or(false, |v| v.as_boolean().unwrap())
+    }
+
+    /// The initial style of the status bar, e.g. `UIStatusBarStyleBlackOpaque`.
+    pub fn status_bar_style(&self) -> Option<&str> {
+        self.plist
+            .get("UIStatusBarStyle")
+            .map(|v| v.as_string().unwrap())
+    }
+
+    /// The initial interface orientation, e.g.
+    /// `UIInterfaceOrientationLandscapeRight`.
+    pub fn interface_orientation(&self) -> Option<&str> {
+        self.plist
+            .get("UIInterfaceOrientation")
+            .map(|v| v.as_string().unwrap())
+    }
+
+    /// The interface orientations the app supports, if it lists them.
+    pub fn supported_interface_orientations(&self) -> Option<Vec<&str>> {
+        self.plist.get("UISupportedInterfaceOrientations").map(|v| {
+            v.as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_string().unwrap())
+                .collect()
+        })
+    }
+
     pub fn main_nib_file_path(&self) -> Option<GuestPathBuf> {
         self.plist.get("NSMainNibFile").map(|filename| {
             let filename = filename.as_string().unwrap();
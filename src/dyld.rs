@@ -15,8 +15,8 @@
 //! touchHLE's own implementations of the frameworks, which are "host code"
 //! (i.e. not themselves running under emulation).
 //!
-//! This also does normal dynamic linking for libgcc and libstdc++. It might
-//! eventually support linking other things too.
+//! This also does normal dynamic linking for libgcc and libstdc++, and for any
+//! dynamic libraries bundled with the app.
 //!
 //! See [crate::mach_o] for resources.
 
@@ -26,13 +26,16 @@ mod function_lists;
 use crate::abi::{CallFromGuest, GuestFunction};
 use crate::cpu::Cpu;
 use crate::frameworks::foundation::ns_string;
-use crate::mach_o::{MachO, SectionType};
-use crate::mem::{ConstVoidPtr, GuestUSize, Mem, MutPtr, Ptr};
+use crate::libc::blocks;
+use crate::mach_o::{
+    MachO, SectionType, DYNAMIC_LOOKUP_ORDINAL, EXECUTABLE_ORDINAL, SELF_LIBRARY_ORDINAL,
+};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr, Ptr};
 use crate::objc::{nil, ObjC};
 use crate::Environment;
 use std::collections::HashMap;
 
-type HostFunction = &'static dyn CallFromGuest;
+pub type HostFunction = &'static dyn CallFromGuest;
 
 /// Type for lists of functions exported by host implementations of frameworks.
 ///
@@ -95,6 +98,10 @@ pub enum HostConstant {
     NSString(&'static str),
     NullPtr,
     Custom(fn(&mut Mem) -> ConstVoidPtr),
+    /// Like [HostConstant::Custom], but with access to the whole environment,
+    /// e.g. for constants containing function pointers created with
+    /// [Dyld::create_proc_address].
+    CustomWithEnv(fn(&mut Environment) -> ConstVoidPtr),
 }
 
 /// Type for lists of constants exported by host implementations of frameworks.
@@ -126,6 +133,43 @@ pub fn search_lists<T>(
         .find(|&(sym, _)| *sym == symbol)
 }
 
+/// Look up a symbol in the guest binary that `bins[bin_idx]` names as its
+/// source, if it uses a two-level namespace. Returns the index of that binary
+/// and the symbol's address.
+fn lookup_named_guest_symbol(bins: &[MachO], bin_idx: usize, symbol: &str) -> Option<(usize, u32)> {
+    let named_idx = match bins[bin_idx].library_ordinals.get(symbol) {
+        Some(&SELF_LIBRARY_ORDINAL) => Some(bin_idx),
+        Some(&EXECUTABLE_ORDINAL) => Some(0),
+        Some(&DYNAMIC_LOOKUP_ORDINAL) | None => None,
+        Some(&ordinal) => bins[bin_idx]
+            .dynamic_libraries
+            .get(usize::from(ordinal) - 1)
+            .and_then(|path| {
+                bins.iter()
+                    .position(|bin| bin.install_name.as_ref() == Some(path))
+            }),
+    };
+    let named_idx = named_idx?;
+    let &addr = bins[named_idx].exported_symbols.get(symbol)?;
+    Some((named_idx, addr))
+}
+
+/// Look up a symbol exported by one of the guest binaries, on behalf of
+/// `bins[bin_idx]`. Returns the index of the binary that has the symbol, and
+/// its address.
+///
+/// If the binary uses a two-level namespace, the library it names for the
+/// symbol is searched first. Otherwise, or if that fails, all the binaries are
+/// searched in order, like with a flat namespace.
+pub fn lookup_guest_symbol(bins: &[MachO], bin_idx: usize, symbol: &str) -> Option<(usize, u32)> {
+    if let Some(found) = lookup_named_guest_symbol(bins, bin_idx, symbol) {
+        return Some(found);
+    }
+    bins.iter()
+        .enumerate()
+        .find_map(|(idx, bin)| bin.exported_symbols.get(symbol).map(|&addr| (idx, addr)))
+}
+
 fn encode_a32_svc(imm: u32) -> u32 {
     assert!(imm & 0xff000000 == 0);
     imm | 0xef000000
@@ -203,22 +247,28 @@ impl Dyld {
             Some(write_return_to_host_routine(mem, Self::SVC_RETURN_TO_HOST));
         self.thread_exit_routine = Some(write_return_to_host_routine(mem, Self::SVC_THREAD_EXIT));
 
-        // Currently assuming only the app binary contains Objective-C things.
-
-        objc.register_bin_selectors(&bins[0], mem);
+        for bin in bins {
+            objc.register_bin_selectors(bin, mem);
+        }
         objc.register_host_selectors(mem);
 
-        for bin in bins {
-            self.setup_lazy_linking(bin, mem);
+        for bin_idx in 0..bins.len() {
+            self.setup_lazy_linking(&bins[bin_idx], mem);
             // Must happen before `register_bin_classes`, else superclass
             // pointers will be wrong.
-            self.do_non_lazy_linking(bin, bins, mem, objc);
+            self.do_non_lazy_linking(bin_idx, bins, mem, objc);
         }
 
-        objc.register_bin_classes(&bins[0], mem);
-        objc.register_bin_categories(&bins[0], mem);
+        // Libraries come after the libraries they depend on, and the app
+        // binary depends on all of them, so registering classes in this order
+        // means superclasses are registered before their subclasses, and
+        // classes before their categories.
+        for bin in bins[1..].iter().chain(&bins[..1]) {
+            objc.register_bin_classes(bin, mem);
+            objc.register_bin_categories(bin, mem);
 
-        ns_string::register_constant_strings(&bins[0], mem, objc);
+            ns_string::register_constant_strings(bin, mem, objc);
+        }
     }
 
     /// [Self::do_initial_linking] but for when this is the app picker's special
@@ -291,20 +341,35 @@ impl Dyld {
     /// this will spit out a warning to stderr for everything missing, so that
     /// there's at least some indication about why the emulator might crash.
     ///
-    /// `bin` is the binary to link non-lazy symbols for, `bins` is the set of
-    /// binaries symbols may be looked up in.
-    fn do_non_lazy_linking(&mut self, bin: &MachO, bins: &[MachO], mem: &mut Mem, objc: &mut ObjC) {
+    /// `bins[bin_idx]` is the binary to link non-lazy symbols for, `bins` is
+    /// the set of binaries symbols may be looked up in.
+    fn do_non_lazy_linking(
+        &mut self,
+        bin_idx: usize,
+        bins: &[MachO],
+        mem: &mut Mem,
+        objc: &mut ObjC,
+    ) {
+        let bin = &bins[bin_idx];
         let mut unhandled_relocations: HashMap<&str, Vec<u32>> = HashMap::new();
         for &(ptr_ptr, ref name) in &bin.external_relocations {
-            let ptr = if let Some(name) = name.strip_prefix("_OBJC_CLASS_$_") {
+            let ptr = if let Some((_, addr)) = lookup_guest_symbol(bins, bin_idx, name) {
+                // Classes defined by other binaries are also found here. The
+                // existing value is an addend, e.g. for a pointer into the
+                // middle of a struct.
+                let addend = mem.read(ConstPtr::<u32>::from_bits(ptr_ptr));
+                mem.write(MutPtr::<u32>::from_bits(ptr_ptr), addr.wrapping_add(addend));
+                continue;
+            } else if let Some(name) = name.strip_prefix("_OBJC_CLASS_$_") {
                 objc.link_class(name, /* is_metaclass: */ false, mem)
             } else if let Some(name) = name.strip_prefix("_OBJC_METACLASS_$_") {
                 objc.link_class(name, /* is_metaclass: */ true, mem)
+            } else if let Some(class_name) = blocks::class_for_symbol(name) {
+                objc.get_known_class(class_name, mem)
             } else if name == "___CFConstantStringClassReference" {
                 // See ns_string::register_constant_strings
                 nil
             } else {
-                // TODO: look up symbol, write pointer
                 unhandled_relocations.entry(name).or_default().push(ptr_ptr);
                 continue;
             };
@@ -341,11 +406,9 @@ impl Dyld {
 
             let ptr_ptr: MutPtr<ConstVoidPtr> = Ptr::from_bits(ptrs.addr + i * entry_size);
 
-            for other_bin in bins {
-                if let Some(&addr) = other_bin.exported_symbols.get(symbol) {
-                    mem.write(ptr_ptr, Ptr::from_bits(addr));
-                    continue 'ptr_loop;
-                }
+            if let Some((_, addr)) = lookup_guest_symbol(bins, bin_idx, symbol) {
+                mem.write(ptr_ptr, Ptr::from_bits(addr));
+                continue 'ptr_loop;
             }
 
             if let Some((_, template)) = search_lists(constant_lists::CONSTANT_LISTS, symbol) {
@@ -373,23 +436,44 @@ impl Dyld {
 
         let to_link = std::mem::take(&mut env.dyld.constants_to_link_later);
         for (symbol_ptr_ptr, template) in to_link {
-            let symbol_ptr: ConstVoidPtr = match template {
-                HostConstant::NSString(static_str) => {
-                    let string_ptr = ns_string::get_static_str(env, static_str);
-                    let string_ptr_ptr = env.mem.alloc_and_write(string_ptr);
-                    string_ptr_ptr.cast().cast_const()
-                }
-                HostConstant::NullPtr => {
-                    let null_ptr: ConstVoidPtr = Ptr::null();
-                    let null_ptr_ptr = env.mem.alloc_and_write(null_ptr);
-                    null_ptr_ptr.cast().cast_const()
-                }
-                HostConstant::Custom(f) => f(&mut env.mem),
-            };
+            let symbol_ptr = Self::create_constant(env, template);
             env.mem.write(symbol_ptr_ptr, symbol_ptr.cast());
         }
     }
 
+    /// Create a host constant in guest memory and return a pointer to it.
+    fn create_constant(env: &mut Environment, template: &'static HostConstant) -> ConstVoidPtr {
+        match template {
+            HostConstant::NSString(static_str) => {
+                let string_ptr = ns_string::get_static_str(env, static_str);
+                let string_ptr_ptr = env.mem.alloc_and_write(string_ptr);
+                string_ptr_ptr.cast().cast_const()
+            }
+            HostConstant::NullPtr => {
+                let null_ptr: ConstVoidPtr = Ptr::null();
+                let null_ptr_ptr = env.mem.alloc_and_write(null_ptr);
+                null_ptr_ptr.cast().cast_const()
+            }
+            HostConstant::Custom(f) => f(&mut env.mem),
+            HostConstant::CustomWithEnv(f) => f(env),
+        }
+    }
+
+    /// Like [Self::create_proc_address], but for constants: creates the host
+    /// constant named `symbol` in guest memory and returns a pointer to it.
+    /// This can be used to implement `dlsym()`. Again, no attempt is made to
+    /// deduplicate these.
+    ///
+    /// The name must be the mangled symbol name. Returns [Err] if there's no
+    /// such constant.
+    pub fn create_constant_address(
+        env: &mut Environment,
+        symbol: &str,
+    ) -> Result<ConstVoidPtr, ()> {
+        let (_, template) = search_lists(constant_lists::CONSTANT_LISTS, symbol).ok_or(())?;
+        Ok(Self::create_constant(env, template))
+    }
+
     /// Return a host function that can be called to handle an SVC instruction
     /// encountered during CPU emulation. If `None` is returned, the execution
     /// needs to resume at `svc_pc`.
@@ -424,10 +508,11 @@ impl Dyld {
         cpu: &mut Cpu,
         svc_pc: u32,
     ) -> Option<HostFunction> {
-        let stubs = bins
+        let (bin_idx, stubs) = bins
             .iter()
-            .flat_map(|bin| bin.get_section(SectionType::SymbolStubs))
-            .find(|stubs| (stubs.addr..(stubs.addr + stubs.size)).contains(&svc_pc))
+            .enumerate()
+            .flat_map(|(idx, bin)| Some((idx, bin.get_section(SectionType::SymbolStubs)?)))
+            .find(|(_, stubs)| (stubs.addr..(stubs.addr + stubs.size)).contains(&svc_pc))
             .unwrap();
 
         let info = stubs.dyld_indirect_symbol_info.as_ref().unwrap();
@@ -438,7 +523,15 @@ impl Dyld {
 
         let symbol = info.indirect_undef_symbols[idx].as_deref().unwrap();
 
-        if let Some(&(symbol, f)) = search_lists(function_lists::FUNCTION_LISTS, symbol) {
+        // A symbol in a guest library takes priority over a host function if
+        // the binary says that's where the symbol is supposed to come from.
+        let named_guest_symbol = lookup_named_guest_symbol(bins, bin_idx, symbol);
+        let host_function = match named_guest_symbol {
+            Some(_) => None,
+            None => search_lists(function_lists::FUNCTION_LISTS, symbol),
+        };
+
+        if let Some(&(symbol, f)) = host_function {
             // Allocate an SVC ID for this host function
             let idx: u32 = self.linked_host_functions.len().try_into().unwrap();
             let svc = idx + Self::SVC_LINKED_FUNCTIONS_BASE;
@@ -462,48 +555,49 @@ impl Dyld {
             return Some(f);
         }
 
-        for dylib in &bins[1..] {
-            if let Some(&addr) = dylib.exported_symbols.get(symbol) {
-                let original_instructions = match info.entry_size {
-                    12 => Self::SYMBOL_STUB_INSTRUCTIONS.as_slice(),
-                    16 => Self::PIC_SYMBOL_STUB_INSTRUCTIONS.as_slice(),
-                    _ => unreachable!(),
-                };
-                let instruction_count: GuestUSize = original_instructions.len().try_into().unwrap();
-
-                // Restore the original stub, which calls the __la_symbol_ptr
-                let stub_function_ptr: MutPtr<u32> = Ptr::from_bits(svc_pc);
-                for (i, &instr) in original_instructions.iter().enumerate() {
-                    mem.write(stub_function_ptr + i.try_into().unwrap(), instr)
-                }
-
-                cpu.invalidate_cache_range(stub_function_ptr.to_bits(), instruction_count * 4);
-
-                // Update the __la_symbol_ptr
-                let la_symbol_ptr: MutPtr<u32> = if info.entry_size == 12 {
-                    // Normal stub: absolute address
-                    let addr = mem.read(stub_function_ptr + instruction_count);
-                    Ptr::from_bits(addr)
-                } else {
-                    // The PIC (position-independent code) stub uses a
-                    // PC-relative offset rather than an absolute address.
-                    let offset = mem.read(stub_function_ptr + instruction_count);
-                    Ptr::from_bits(stub_function_ptr.to_bits() + offset + 12)
-                };
-                mem.write(la_symbol_ptr, addr);
-
-                log_dbg!(
-                    "Linked {} at {:?}/{:?} to {:#x} from {}",
-                    symbol,
-                    stub_function_ptr,
-                    la_symbol_ptr,
-                    addr,
-                    dylib.name
-                );
-
-                // Tell the caller it needs to restart execution at svc_pc.
-                return None;
+        let guest_symbol =
+            named_guest_symbol.or_else(|| lookup_guest_symbol(bins, bin_idx, symbol));
+        if let Some((dylib_idx, addr)) = guest_symbol {
+            let dylib = &bins[dylib_idx];
+            let original_instructions = match info.entry_size {
+                12 => Self::SYMBOL_STUB_INSTRUCTIONS.as_slice(),
+                16 => Self::PIC_SYMBOL_STUB_INSTRUCTIONS.as_slice(),
+                _ => unreachable!(),
+            };
+            let instruction_count: GuestUSize = original_instructions.len().try_into().unwrap();
+
+            // Restore the original stub, which calls the __la_symbol_ptr
+            let stub_function_ptr: MutPtr<u32> = Ptr::from_bits(svc_pc);
+            for (i, &instr) in original_instructions.iter().enumerate() {
+                mem.write(stub_function_ptr + i.try_into().unwrap(), instr)
             }
+
+            cpu.invalidate_cache_range(stub_function_ptr.to_bits(), instruction_count * 4);
+
+            // Update the __la_symbol_ptr
+            let la_symbol_ptr: MutPtr<u32> = if info.entry_size == 12 {
+                // Normal stub: absolute address
+                let addr = mem.read(stub_function_ptr + instruction_count);
+                Ptr::from_bits(addr)
+            } else {
+                // The PIC (position-independent code) stub uses a
+                // PC-relative offset rather than an absolute address.
+                let offset = mem.read(stub_function_ptr + instruction_count);
+                Ptr::from_bits(stub_function_ptr.to_bits() + offset + 12)
+            };
+            mem.write(la_symbol_ptr, addr);
+
+            log_dbg!(
+                "Linked {} at {:?}/{:?} to {:#x} from {}",
+                symbol,
+                stub_function_ptr,
+                la_symbol_ptr,
+                addr,
+                dylib.name
+            );
+
+            // Tell the caller it needs to restart execution at svc_pc.
+            return None;
         }
 
         panic!("Call to unimplemented function {}", symbol);
@@ -523,11 +617,24 @@ impl Dyld {
         symbol: &str,
     ) -> Result<GuestFunction, ()> {
         let &(symbol, f) = search_lists(function_lists::FUNCTION_LISTS, symbol).ok_or(())?;
+        Ok(self.create_guest_function(mem, cpu, symbol, f))
+    }
 
+    /// Creates a guest function that will call an arbitrary host function,
+    /// e.g. a host method implementation (see [crate::objc::HostIMP]). The
+    /// name is purely for debugging. Like with [Self::create_proc_address], no
+    /// attempt is made to deduplicate or deallocate these.
+    pub fn create_guest_function(
+        &mut self,
+        mem: &mut Mem,
+        cpu: &mut Cpu,
+        name: &'static str,
+        f: HostFunction,
+    ) -> GuestFunction {
         // Allocate an SVC ID for this host function
         let idx: u32 = self.linked_host_functions.len().try_into().unwrap();
         let svc = idx + Self::SVC_LINKED_FUNCTIONS_BASE;
-        self.linked_host_functions.push((symbol, f));
+        self.linked_host_functions.push((name, f));
 
         // Create guest function to call this host function
         let function_ptr = mem.alloc(8);
@@ -538,8 +645,6 @@ impl Dyld {
         // Just in case
         cpu.invalidate_cache_range(function_ptr.to_bits(), 4);
 
-        Ok(GuestFunction::from_addr_with_thumb_bit(
-            function_ptr.to_bits(),
-        ))
+        GuestFunction::from_addr_with_thumb_bit(function_ptr.to_bits())
     }
 }
@@ -6,17 +6,35 @@
 //! Separate module just for the constant lists, since this will probably be a
 //! very long and frequently-updated list.
 
-use crate::frameworks::{core_foundation, core_graphics, foundation, media_player, opengles};
+use crate::frameworks::{
+    core_animation, core_foundation, core_graphics, foundation, media_player, opengles, uikit,
+};
 use crate::libc;
 
 /// All the lists of constants that the linker should search through.
 pub const CONSTANT_LISTS: &[super::ConstantExports] = &[
+    libc::blocks::CONSTANTS,
     libc::ctype::CONSTANTS,
+    libc::dispatch::CONSTANTS,
+    core_animation::ca_animation::CONSTANTS,
+    core_animation::ca_layer::CONSTANTS,
+    core_animation::ca_transform_3d::CONSTANTS,
     core_foundation::cf_allocator::CONSTANTS,
+    core_foundation::cf_array::CONSTANTS,
+    core_foundation::cf_dictionary::CONSTANTS,
+    core_foundation::cf_number::CONSTANTS,
+    core_foundation::cf_preferences::CONSTANTS,
     core_foundation::cf_run_loop::CONSTANTS,
     core_graphics::cg_affine_transform::CONSTANTS,
     core_graphics::cg_color_space::CONSTANTS,
+    foundation::ns_error::CONSTANTS,
+    foundation::ns_file_manager::CONSTANTS,
+    foundation::ns_locale::CONSTANTS,
     foundation::ns_run_loop::CONSTANTS,
     media_player::movie_player::CONSTANTS,
     opengles::eagl::CONSTANTS,
+    uikit::ui_device::CONSTANTS,
+    uikit::ui_responder::CONSTANTS,
+    uikit::ui_view::ui_control::ui_text_field::CONSTANTS,
+    uikit::ui_view::ui_text_view::CONSTANTS,
 ];
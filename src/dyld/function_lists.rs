@@ -7,14 +7,17 @@
 //! very long and frequently-updated list.
 
 use crate::frameworks::{
-    audio_toolbox, core_foundation, core_graphics, foundation, openal, opengles, uikit,
+    audio_toolbox, core_animation, core_foundation, core_graphics, foundation, openal, opengles,
+    uikit,
 };
 use crate::libc;
 
 /// All the lists of functions that the linker should search through.
 pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
+    libc::blocks::FUNCTIONS,
     libc::ctype::FUNCTIONS,
     libc::cxxabi::FUNCTIONS,
+    libc::dispatch::FUNCTIONS,
     libc::dlfcn::FUNCTIONS,
     libc::errno::FUNCTIONS,
     libc::keymgr::FUNCTIONS,
@@ -42,9 +45,14 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     audio_toolbox::audio_queue::FUNCTIONS,
     audio_toolbox::audio_services::FUNCTIONS,
     audio_toolbox::audio_session::FUNCTIONS,
+    core_animation::ca_base::FUNCTIONS,
+    core_animation::ca_transform_3d::FUNCTIONS,
     core_foundation::cf_array::FUNCTIONS,
     core_foundation::cf_bundle::FUNCTIONS,
     core_foundation::cf_data::FUNCTIONS,
+    core_foundation::cf_dictionary::FUNCTIONS,
+    core_foundation::cf_number::FUNCTIONS,
+    core_foundation::cf_preferences::FUNCTIONS,
     core_foundation::cf_run_loop::FUNCTIONS,
     core_foundation::cf_string::FUNCTIONS,
     core_foundation::cf_type::FUNCTIONS,
@@ -52,13 +60,16 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     core_foundation::time::FUNCTIONS,
     core_graphics::cg_affine_transform::FUNCTIONS,
     core_graphics::cg_bitmap_context::FUNCTIONS,
+    core_graphics::cg_color::FUNCTIONS,
     core_graphics::cg_color_space::FUNCTIONS,
     core_graphics::cg_context::FUNCTIONS,
     core_graphics::cg_data_provider::FUNCTIONS,
     core_graphics::cg_image::FUNCTIONS,
+    core_graphics::cg_path::FUNCTIONS,
     foundation::ns_file_manager::FUNCTIONS,
     foundation::ns_log::FUNCTIONS,
     foundation::ns_objc_runtime::FUNCTIONS,
+    foundation::ns_thread::FUNCTIONS,
     openal::FUNCTIONS,
     opengles::FUNCTIONS,
     uikit::ui_application::FUNCTIONS,
@@ -8,6 +8,7 @@
 //! Unlike its siblings, this module should be considered private and only used
 //! via the re-exports one level up.
 
+mod clock;
 mod mutex;
 
 use crate::abi::GuestRet;
@@ -16,6 +17,8 @@ use crate::{
     abi, bundle, cpu, dyld, frameworks, fs, gdb, image, libc, mach_o, mem, objc, options, stack,
     window,
 };
+use clock::Clock;
+use std::collections::HashSet;
 use std::net::TcpListener;
 use std::time::{Duration, Instant};
 
@@ -74,8 +77,8 @@ impl Thread {
 /// The struct containing the entire emulator state. Methods are provided for
 /// execution and management of threads.
 pub struct Environment {
-    /// Reference point for various timing functions.
-    pub startup_time: Instant,
+    /// Source of guest-visible time, see [Clock].
+    pub clock: Clock,
     pub bundle: bundle::Bundle,
     pub fs: fs::Fs,
     /// The window is only absent when running in headless mode.
@@ -138,7 +141,7 @@ impl Environment {
         options: options::Options,
         env_for_salvage: Option<Environment>,
     ) -> Result<Environment, String> {
-        let startup_time = Instant::now();
+        let clock = Clock::new(&options);
 
         // Extract things to salvage from the old environment, and then drop it.
         // This needs to be done before creating a new window, because SDL2 only
@@ -189,35 +192,23 @@ impl Environment {
         } else {
             mem::Mem::new()
         };
+        mem.set_bounds_checking(options.check_memory_bounds);
 
         let executable = mach_o::MachO::load_from_file(bundle.executable_path(), &fs, &mut mem)
             .map_err(|e| format!("Could not load executable: {}", e))?;
 
         let mut dylibs = Vec::new();
-        for dylib in &executable.dynamic_libraries {
-            if dylib == "/usr/lib/libSystem.B.dylib" || dylib == "/usr/lib/libobjc.A.dylib" {
-                // We have host implementations of these
-                continue;
-            }
-
-            // There are some Free Software libraries bundled with touchHLE and
-            // exposed via the guest file system (see Fs::new()).
-            if fs.is_file(fs::GuestPath::new(dylib)) {
-                let dylib = mach_o::MachO::load_from_file(fs::GuestPath::new(dylib), &fs, &mut mem)
-                    .map_err(|e| format!("Could not load bundled dylib: {}", e))?;
-                dylibs.push(dylib);
-            } else {
-                // System frameworks will have host implementations.
-                // TODO: warn about unimplemented frameworks?
-                if !dylib.starts_with("/System/Library/Frameworks/") {
-                    log!(
-                        "Warning: app binary depends on unexpected dylib \"{}\"",
-                        dylib
-                    );
-                }
-                continue;
-            };
-        }
+        let executable_path = bundle.executable_path();
+        let executable_dir = executable_path.parent().unwrap();
+        load_dylibs(
+            &executable,
+            executable_dir,
+            executable_dir,
+            &fs,
+            &mut mem,
+            &mut HashSet::new(),
+            &mut dylibs,
+        )?;
 
         let entry_point_addr = executable.entry_point_pc.ok_or_else(|| {
             "Mach-O file does not specify an entry point PC, perhaps it is not an executable?"
@@ -251,7 +242,7 @@ impl Environment {
         };
 
         let mut env = Environment {
-            startup_time,
+            clock,
             bundle,
             fs,
             window,
@@ -305,15 +296,15 @@ impl Environment {
 
         echo!("CPU emulation begins now.");
 
+        // Like on a real device, +load methods must run before static
+        // initializers.
+        objc::ObjC::call_load_methods(&mut env);
+
         // Static initializers for libraries must be run before the initializer
-        // in the app binary.
-        // TODO: once we support more libraries, replace this hard-coded order
-        //       with e.g. a topological sort.
-        assert!(env.bins.len() <= 3);
-        for bin_idx in [1, 2, 0] {
-            let Some(bin) = env.bins.get(bin_idx) else {
-                continue;
-            };
+        // in the app binary. The libraries are already sorted so that each
+        // comes after the libraries it depends on.
+        for bin_idx in (1..env.bins.len()).chain([0]) {
+            let bin = &env.bins[bin_idx];
             let Some(section) = bin.get_section(mach_o::SectionType::ModInitFuncPointers) else {
                 continue;
             };
@@ -345,7 +336,7 @@ impl Environment {
         let bundle = bundle::Bundle::new_fake_bundle();
         let fs = fs::Fs::new_fake_fs();
 
-        let startup_time = Instant::now();
+        let clock = Clock::new_real();
 
         let icon = None;
         let launch_image = None;
@@ -359,6 +350,7 @@ impl Environment {
         ));
 
         let mut mem = mem::Mem::new();
+        mem.set_bounds_checking(options.check_memory_bounds);
 
         let bins = Vec::new();
 
@@ -383,7 +375,7 @@ impl Environment {
         };
 
         let mut env = Environment {
-            startup_time,
+            clock,
             bundle,
             fs,
             window,
@@ -541,7 +533,7 @@ impl Environment {
             self.current_thread,
             duration
         );
-        let until = Instant::now().checked_add(duration).unwrap();
+        let until = self.clock.now().checked_add(duration).unwrap();
         self.threads[self.current_thread].blocked_by = ThreadBlock::Sleeping(until);
         // For non tail-call sleeps (such as in NSRunLoop), we want to poll
         // other threads but can't return back to the run loop, since it would
@@ -829,7 +821,7 @@ impl Environment {
             // this until after we've done some amount of work on the guest
             // thread, lest every single callback call pay this cost.
             if let Some(ref mut window) = self.window {
-                window.poll_for_events(&self.options);
+                window.poll_for_events(&self.options, self.clock.uptime());
             }
 
             loop {
@@ -847,7 +839,7 @@ impl Environment {
                     }
                     match candidate.blocked_by {
                         ThreadBlock::Sleeping(sleeping_until) => {
-                            if sleeping_until <= Instant::now() {
+                            if sleeping_until <= self.clock.now() {
                                 log_dbg!("Thread {} finished sleeping.", i);
                                 candidate.blocked_by = ThreadBlock::NotBlocked;
                                 suitable_thread = Some(i);
@@ -915,9 +907,11 @@ impl Environment {
                 // All suitable threads are blocked and at least one is asleep.
                 // Sleep until one of them wakes up.
                 } else if let Some(next_awakening) = next_awakening {
-                    let duration = next_awakening.duration_since(Instant::now());
-                    log_dbg!("All threads blocked/asleep, sleeping for {:?}.", duration);
-                    std::thread::sleep(duration);
+                    log_dbg!(
+                        "All threads blocked/asleep, sleeping for {:?}.",
+                        next_awakening.saturating_duration_since(self.clock.now())
+                    );
+                    self.clock.wait_until(next_awakening);
                     // Try again, there should be some thread awake now (or
                     // there will be soon, since timing is approximate).
                     continue;
@@ -931,3 +925,63 @@ impl Environment {
         }
     }
 }
+
+/// Load the dynamic libraries that `bin` depends on, and the libraries those
+/// depend on in turn, adding them to `dylibs` so that each library comes after
+/// the libraries it depends on. `bin_dir` is the directory containing `bin`.
+/// `seen` contains the paths of libraries that have already been considered.
+fn load_dylibs(
+    bin: &mach_o::MachO,
+    bin_dir: &fs::GuestPath,
+    executable_dir: &fs::GuestPath,
+    fs: &fs::Fs,
+    mem: &mut mem::Mem,
+    seen: &mut HashSet<String>,
+    dylibs: &mut Vec<mach_o::MachO>,
+) -> Result<(), String> {
+    for dylib in &bin.dynamic_libraries {
+        if dylib == "/usr/lib/libSystem.B.dylib" || dylib == "/usr/lib/libobjc.A.dylib" {
+            // We have host implementations of these
+            continue;
+        }
+
+        // Libraries bundled with the app are found relative to the app binary
+        // or to the library that uses them.
+        let path = if let Some(rest) = dylib.strip_prefix("@executable_path/") {
+            executable_dir.join(rest)
+        } else if let Some(rest) = dylib.strip_prefix("@loader_path/") {
+            bin_dir.join(rest)
+        } else {
+            fs::GuestPathBuf::from(dylib.clone())
+        };
+        if !seen.insert(String::from(path.clone())) {
+            continue;
+        }
+
+        // There are also some Free Software libraries bundled with touchHLE
+        // and exposed via the guest file system (see Fs::new()).
+        if fs.is_file(&path) {
+            let loaded = mach_o::MachO::load_from_file(&path, fs, mem)
+                .map_err(|e| format!("Could not load dylib \"{}\": {}", dylib, e))?;
+            let loaded_dir = path.parent().unwrap();
+            load_dylibs(&loaded, loaded_dir, executable_dir, fs, mem, seen, dylibs)?;
+            log_dbg!("Loaded dylib {:?} (slide: {:#x})", path, loaded.slide);
+            dylibs.push(loaded);
+        } else if dylib.starts_with('@') {
+            log!(
+                "Warning: {} depends on dylib \"{}\", but it is missing from the bundle",
+                bin.name,
+                dylib
+            );
+        } else if !dylib.starts_with("/System/Library/Frameworks/") {
+            // System frameworks will have host implementations.
+            // TODO: warn about unimplemented frameworks?
+            log!(
+                "Warning: {} depends on unexpected dylib \"{}\"",
+                bin.name,
+                dylib
+            );
+        }
+    }
+    Ok(())
+}
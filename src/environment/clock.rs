@@ -0,0 +1,140 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Guest-visible time.
+//!
+//! Everything the app can observe about the passage of time (e.g.
+//! `mach_absolute_time()`, `gettimeofday()`, `CACurrentMediaTime()`, timers,
+//! animations) should be derived from [Clock], rather than from
+//! [Instant::now] or [SystemTime::now] directly. Normally the clock just
+//! follows the host's real time, but with the `--virtual-clock=` option it is
+//! replaced with a virtual clock that advances in fixed steps ("frames"), so
+//! that runs are reproducible.
+//!
+//! The virtual clock only advances when every guest thread is asleep
+//! (see [Clock::wait_until]), or when [Clock::step] is called. An app that
+//! busy-waits for time to pass will therefore hang.
+
+use crate::options::Options;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Wall-clock time that the virtual clock starts at: 2010-01-01 00:00:00 UTC.
+/// A fixed value is used so that dates seen by the app are also reproducible.
+const VIRTUAL_CLOCK_EPOCH: Duration = Duration::from_secs(1262304000);
+
+pub struct Clock {
+    /// Host time at startup. This is the reference point for uptime, and with
+    /// the virtual clock, the time the first frame starts at.
+    startup_instant: Instant,
+    /// Wall-clock time corresponding to [Self::startup_instant].
+    startup_system_time: SystemTime,
+    virtual_clock: Option<VirtualClock>,
+}
+
+struct VirtualClock {
+    /// Length of a frame, in nanoseconds.
+    step_nanos: u64,
+    /// Number of frames that have passed since startup.
+    frames: u64,
+    /// If set, touchHLE exits once this many frames have passed.
+    frame_limit: Option<u64>,
+}
+
+impl Clock {
+    /// Create a clock that follows the host's real time.
+    pub fn new_real() -> Clock {
+        Clock {
+            startup_instant: Instant::now(),
+            startup_system_time: SystemTime::now(),
+            virtual_clock: None,
+        }
+    }
+
+    /// Create a clock configured by the user's options, i.e. a virtual clock if
+    /// `--virtual-clock=` was used, or a real one otherwise.
+    pub fn new(options: &Options) -> Clock {
+        let Some(step) = options.virtual_clock_step else {
+            return Self::new_real();
+        };
+        log!("Using virtual clock, advancing by {:?} per frame.", step);
+        Clock {
+            startup_instant: Instant::now(),
+            startup_system_time: SystemTime::UNIX_EPOCH + VIRTUAL_CLOCK_EPOCH,
+            virtual_clock: Some(VirtualClock {
+                step_nanos: step.as_nanos().try_into().unwrap(),
+                frames: 0,
+                frame_limit: options.virtual_clock_frames,
+            }),
+        }
+    }
+
+    /// Get the current time, for use as a monotonic clock.
+    pub fn now(&self) -> Instant {
+        match self.virtual_clock {
+            None => Instant::now(),
+            Some(ref virtual_clock) => self
+                .startup_instant
+                .checked_add(virtual_clock.elapsed())
+                .unwrap(),
+        }
+    }
+
+    /// Get the current wall-clock time, e.g. for `time()`.
+    pub fn system_now(&self) -> SystemTime {
+        match self.virtual_clock {
+            None => SystemTime::now(),
+            Some(ref virtual_clock) => self
+                .startup_system_time
+                .checked_add(virtual_clock.elapsed())
+                .unwrap(),
+        }
+    }
+
+    /// Get the time that has passed since startup.
+    pub fn uptime(&self) -> Duration {
+        self.now().duration_since(self.startup_instant)
+    }
+
+    /// Advance the virtual clock by some number of frames. This is the means by
+    /// which a test harness can run the app for an exact amount of time.
+    ///
+    /// Panics if the virtual clock is not in use.
+    pub fn step(&mut self, frames: u64) {
+        let virtual_clock = self
+            .virtual_clock
+            .as_mut()
+            .expect("Virtual clock is not in use");
+        virtual_clock.frames = virtual_clock.frames.checked_add(frames).unwrap();
+        log_dbg!("Virtual clock is now at frame {}.", virtual_clock.frames);
+        if let Some(frame_limit) = virtual_clock.frame_limit {
+            if virtual_clock.frames >= frame_limit {
+                echo!("Virtual clock reached {} frames, exiting.", frame_limit);
+                std::process::exit(0);
+            }
+        }
+    }
+
+    /// For use by the thread scheduler when every thread is asleep: wait until
+    /// `until`. For the real clock, this puts the host thread to sleep. For the
+    /// virtual clock, this instead advances the clock to the first frame that
+    /// starts at or after `until`.
+    pub fn wait_until(&mut self, until: Instant) {
+        let duration = until.saturating_duration_since(self.now());
+        let Some(ref virtual_clock) = self.virtual_clock else {
+            std::thread::sleep(duration);
+            return;
+        };
+        let duration_nanos: u64 = duration.as_nanos().try_into().unwrap();
+        let step_nanos = virtual_clock.step_nanos;
+        let frames = (duration_nanos.saturating_add(step_nanos - 1) / step_nanos).max(1);
+        self.step(frames);
+    }
+}
+
+impl VirtualClock {
+    fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.step_nanos.checked_mul(self.frames).unwrap())
+    }
+}
@@ -89,6 +89,23 @@ impl Font {
         (v_metrics.ascent - v_metrics.descent, v_metrics.line_gap)
     }
 
+    /// Calculate the height of some number of lines of text.
+    pub fn height_of_lines(&self, font_size: f32, lines: usize) -> f32 {
+        let (line_height, line_gap) = self.line_height_and_gap(font_size);
+        line_height * (lines as f32) + line_gap * (lines.saturating_sub(1) as f32)
+    }
+
+    /// Calculate how many lines of text fit within a height. This is at least
+    /// one, since text that doesn't fit is still drawn, just clipped.
+    pub fn lines_fitting_height(&self, font_size: f32, height: f32) -> usize {
+        let (line_height, line_gap) = self.line_height_and_gap(font_size);
+        // The small extra amount allows for rounding errors when the height was
+        // calculated with [Self::height_of_lines].
+        let lines = ((height + line_gap) / (line_height + line_gap) + 1e-3).floor();
+        // This saturates for an infinite height.
+        (lines as usize).max(1)
+    }
+
     /// Calculate the width of a line. This does not handle newlines!
     fn calculate_line_width(&self, font_size: f32, line: &str) -> f32 {
         let mut line_bounds: Rect<i32> = Default::default();
@@ -103,12 +120,78 @@ impl Font {
         line_bounds.width() as f32
     }
 
-    /// Break text into lines with known widths.
+    /// Rasterize a single line of text (newlines are not handled) as a coverage
+    /// map, with values between 0.0 and 1.0 and top-to-bottom rows. Returns
+    /// the coverage map, its width and height, the position within it of the
+    /// start of the baseline, and the distance to advance the pen by
+    /// afterwards.
+    pub fn rasterize_line(
+        &self,
+        font_size: f32,
+        text: &str,
+    ) -> (Vec<f32>, (u32, u32), (i32, i32), f32) {
+        let glyphs: Vec<_> = self
+            .font
+            .layout(text, scale(font_size), Default::default())
+            .collect();
+        let advance = glyphs.last().map_or(0.0, |glyph| {
+            glyph.position().x + glyph.unpositioned().h_metrics().advance_width
+        });
+
+        let mut text_bounds: Option<Rect<i32>> = None;
+        for glyph in &glyphs {
+            let Some(glyph_bounds) = glyph.pixel_bounding_box() else {
+                continue;
+            };
+            match text_bounds {
+                Some(ref mut text_bounds) => update_bounds(text_bounds, &glyph_bounds),
+                None => text_bounds = Some(glyph_bounds),
+            }
+        }
+        let Some(text_bounds) = text_bounds else {
+            return (Vec::new(), (0, 0), (0, 0), advance);
+        };
+
+        let width = text_bounds.width() as u32;
+        let height = text_bounds.height() as u32;
+        let mut coverage = vec![0.0; (width * height) as usize];
+        for glyph in &glyphs {
+            let Some(glyph_bounds) = glyph.pixel_bounding_box() else {
+                continue;
+            };
+            let x_offset = (glyph_bounds.min.x - text_bounds.min.x) as u32;
+            let y_offset = (glyph_bounds.min.y - text_bounds.min.y) as u32;
+            glyph.draw(|x, y, glyph_coverage| {
+                let idx = ((y_offset + y) * width + x_offset + x) as usize;
+                // Glyphs can overlap slightly.
+                coverage[idx] = (coverage[idx] + glyph_coverage).min(1.0);
+            });
+        }
+        let baseline_origin = (-text_bounds.min.x, -text_bounds.min.y);
+        (coverage, (width, height), baseline_origin, advance)
+    }
+
+    /// Break text into lines with known widths. Lines after the first
+    /// `max_lines` lines, if specified, are discarded.
     fn break_lines<'a>(
         &self,
         font_size: f32,
         text: &'a str,
         wrap: Option<(f32, WrapMode)>,
+        max_lines: Option<usize>,
+    ) -> Vec<(f32, &'a str)> {
+        let mut lines = self.break_lines_inner(font_size, text, wrap);
+        if let Some(max_lines) = max_lines {
+            lines.truncate(max_lines);
+        }
+        lines
+    }
+
+    fn break_lines_inner<'a>(
+        &self,
+        font_size: f32,
+        text: &'a str,
+        wrap: Option<(f32, WrapMode)>,
     ) -> Vec<(f32, &'a str)> {
         let mut lines = Vec::new();
 
@@ -174,7 +257,7 @@ impl Font {
                             // Try to break the word.
                             let word_end = wrap_points[next_wrap_point_idx];
                             let word = &line[line_start..word_end];
-                            let broken_words = self.break_lines(
+                            let broken_words = self.break_lines_inner(
                                 font_size,
                                 word,
                                 Some((wrap_width, WrapMode::Char)),
@@ -209,14 +292,14 @@ impl Font {
         font_size: f32,
         text: &str,
         wrap: Option<(f32, WrapMode)>,
+        max_lines: Option<usize>,
     ) -> (f32, f32) {
-        let lines = self.break_lines(font_size, text, wrap);
+        let lines = self.break_lines(font_size, text, wrap, max_lines);
 
         let width = lines
             .iter()
             .fold(0f32, |widest, &(line_width, _line)| widest.max(line_width));
-        let (line_height, line_gap) = self.line_height_and_gap(font_size);
-        let height = line_height * (lines.len() as f32) + line_gap * ((lines.len() - 1) as f32);
+        let height = self.height_of_lines(font_size, lines.len());
 
         (width, height)
     }
@@ -230,13 +313,14 @@ impl Font {
         text: &str,
         origin: (f32, f32),
         wrap: Option<(f32, WrapMode)>,
+        max_lines: Option<usize>,
         alignment: TextAlignment,
         mut put_pixel: F,
     ) {
         // TODO: This code has gone through a rather traumatic series of y sign
         //       flips and might benefit from refactoring for clarity?
 
-        let lines = self.break_lines(font_size, text, wrap);
+        let lines = self.break_lines(font_size, text, wrap, max_lines);
 
         let mut line_y = self.font.v_metrics(scale(font_size)).ascent;
         let (line_height, line_gap) = self.line_height_and_gap(font_size);
@@ -216,6 +216,26 @@ fn AudioQueueSetParameter(
     0 // success
 }
 
+fn AudioQueueGetParameter(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_param_id: AudioQueueParameterID,
+    out_value: MutPtr<AudioQueueParameterValue>,
+) -> OSStatus {
+    return_if_null!(in_aq);
+
+    assert!(in_param_id == kAudioQueueParam_Volume); // others unimplemented
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get(&in_aq)
+        .unwrap();
+    let volume = host_object.volume;
+    env.mem.write(out_value, volume);
+
+    0 // success
+}
+
 fn AudioQueueAllocateBuffer(
     env: &mut Environment,
     in_aq: AudioQueueRef,
@@ -245,6 +265,35 @@ fn AudioQueueAllocateBuffer(
     0 // success
 }
 
+fn AudioQueueFreeBuffer(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_buffer: AudioQueueBufferRef,
+) -> OSStatus {
+    return_if_null!(in_aq);
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+
+    // TODO: Return error if buffer doesn't belong to audio queue
+    let idx = host_object
+        .buffers
+        .iter()
+        .position(|&buffer| buffer == in_buffer)
+        .unwrap();
+    // Apple's implementation refuses to free an enqueued buffer.
+    assert!(!host_object.buffer_queue.contains(&in_buffer));
+    host_object.buffers.remove(idx);
+
+    let buffer = env.mem.read(in_buffer);
+    env.mem.free(buffer.audio_data);
+    env.mem.free(in_buffer.cast());
+
+    0 // success
+}
+
 fn AudioQueueEnqueueBuffer(
     env: &mut Environment,
     in_aq: AudioQueueRef,
@@ -637,6 +686,42 @@ fn AudioQueueStop(env: &mut Environment, in_aq: AudioQueueRef, in_immediate: boo
     0 // success
 }
 
+fn AudioQueueFlush(_env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
+    return_if_null!(in_aq);
+
+    // Every enqueued buffer is always played in full, so there's nothing to
+    // flush.
+
+    0 // success
+}
+
+/// Discard all enqueued buffers of an audio queue, leaving it stopped.
+/// TODO: Apple's implementation invokes the callback for the discarded buffers.
+fn AudioQueueReset(env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
+    return_if_null!(in_aq);
+
+    let state = State::get(&mut env.framework_state);
+
+    let _context_manager = state.make_al_context_current();
+
+    let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
+    host_object.is_running = false;
+    host_object.buffer_queue.clear();
+
+    if let Some(al_source) = host_object.al_source {
+        unsafe {
+            // Stopping the source marks all its buffers as processed.
+            al::alSourceStop(al_source);
+            assert!(al::alGetError() == 0);
+        }
+        unqueue_buffers(al_source, |al_buffer| {
+            host_object.al_unused_buffers.push(al_buffer)
+        });
+    }
+
+    0 // success
+}
+
 fn AudioQueueDispose(env: &mut Environment, in_aq: AudioQueueRef, in_immediate: bool) -> OSStatus {
     return_if_null!(in_aq);
 
@@ -672,6 +757,7 @@ fn AudioQueueDispose(env: &mut Environment, in_aq: AudioQueueRef, in_immediate:
                 host_object.al_unused_buffers.len().try_into().unwrap(),
                 host_object.al_unused_buffers.as_ptr(),
             );
+            al::alDeleteSources(1, &al_source);
             assert!(al::alGetError() == 0);
         }
     }
@@ -684,7 +770,9 @@ fn AudioQueueDispose(env: &mut Environment, in_aq: AudioQueueRef, in_immediate:
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(AudioQueueNewOutput(_, _, _, _, _, _, _)),
     export_c_func!(AudioQueueSetParameter(_, _, _)),
+    export_c_func!(AudioQueueGetParameter(_, _, _)),
     export_c_func!(AudioQueueAllocateBuffer(_, _, _)),
+    export_c_func!(AudioQueueFreeBuffer(_, _)),
     export_c_func!(AudioQueueEnqueueBuffer(_, _, _, _)),
     export_c_func!(AudioQueueAddPropertyListener(_, _, _, _)),
     export_c_func!(AudioQueueRemovePropertyListener(_, _, _, _)),
@@ -692,5 +780,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(AudioQueueStart(_, _)),
     export_c_func!(AudioQueuePause(_)),
     export_c_func!(AudioQueueStop(_, _)),
+    export_c_func!(AudioQueueFlush(_)),
+    export_c_func!(AudioQueueReset(_)),
     export_c_func!(AudioQueueDispose(_, _)),
 ];
@@ -8,13 +8,21 @@
 //! Useful resources:
 //! - Apple's [Core Animation Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/CoreAnimation_guide/Introduction/Introduction.html)
 
+pub mod ca_animation;
+pub mod ca_base;
+pub mod ca_display_link;
 pub mod ca_eagl_layer;
+pub mod ca_gradient_layer;
 pub mod ca_layer;
+pub mod ca_shape_layer;
+pub mod ca_transaction;
+pub mod ca_transform_3d;
 
 mod composition;
-pub use composition::recomposite_if_necessary;
+pub use composition::{free_gl_resources, recomposite_if_necessary, texture_memory_in_use};
 
 #[derive(Default)]
 pub struct State {
+    ca_transaction: ca_transaction::State,
     composition: composition::State,
 }
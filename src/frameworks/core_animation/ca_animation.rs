@@ -0,0 +1,832 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CAAnimation` and its subclasses, `CAMediaTimingFunction`, and the
+//! evaluation of animations.
+//!
+//! Only `CABasicAnimation` is implemented so far, and only for a few
+//! properties. Like in the real Core Animation, animations never change the
+//! model values of a layer (what the app gets when it reads a property), only
+//! the presentation values used when compositing.
+//!
+//! Changing one of those properties also looks up and runs an action, which
+//! is how implicit animations happen (see [run_action_for_change]).
+//!
+//! Useful resources:
+//! - Apple's [Core Animation Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/CoreAnimation_guide/CreatingBasicAnimations/CreatingBasicAnimations.html)
+//! - [Apple's documentation of `CAMediaTimingFunction`](https://developer.apple.com/documentation/quartzcore/camediatimingfunction?language=objc)
+
+use super::ca_layer::CALayerHostObject;
+use super::ca_transaction;
+use super::ca_transform_3d::CATransform3D;
+use super::composition::set_needs_recomposite;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_foundation::time::CFTimeInterval;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr, ObjC,
+};
+use crate::Environment;
+use std::time::Instant;
+
+// Values might not be correct, but as these are linked symbol constants, it
+// shouldn't matter.
+pub const kCAMediaTimingFunctionLinear: &str = "linear";
+pub const kCAMediaTimingFunctionEaseIn: &str = "easeIn";
+pub const kCAMediaTimingFunctionEaseOut: &str = "easeOut";
+pub const kCAMediaTimingFunctionEaseInEaseOut: &str = "easeInEaseOut";
+pub const kCAMediaTimingFunctionDefault: &str = "default";
+
+pub const kCAFillModeForwards: &str = "forwards";
+pub const kCAFillModeBackwards: &str = "backwards";
+pub const kCAFillModeBoth: &str = "both";
+pub const kCAFillModeRemoved: &str = "removed";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCAMediaTimingFunctionLinear",
+        HostConstant::NSString(kCAMediaTimingFunctionLinear),
+    ),
+    (
+        "_kCAMediaTimingFunctionEaseIn",
+        HostConstant::NSString(kCAMediaTimingFunctionEaseIn),
+    ),
+    (
+        "_kCAMediaTimingFunctionEaseOut",
+        HostConstant::NSString(kCAMediaTimingFunctionEaseOut),
+    ),
+    (
+        "_kCAMediaTimingFunctionEaseInEaseOut",
+        HostConstant::NSString(kCAMediaTimingFunctionEaseInEaseOut),
+    ),
+    (
+        "_kCAMediaTimingFunctionDefault",
+        HostConstant::NSString(kCAMediaTimingFunctionDefault),
+    ),
+    (
+        "_kCAFillModeForwards",
+        HostConstant::NSString(kCAFillModeForwards),
+    ),
+    (
+        "_kCAFillModeBackwards",
+        HostConstant::NSString(kCAFillModeBackwards),
+    ),
+    ("_kCAFillModeBoth", HostConstant::NSString(kCAFillModeBoth)),
+    (
+        "_kCAFillModeRemoved",
+        HostConstant::NSString(kCAFillModeRemoved),
+    ),
+];
+
+/// Control points of a cubic Bézier timing curve, excluding the implicit first
+/// and last points (0, 0) and (1, 1).
+type ControlPoints = (f32, f32, f32, f32);
+
+const LINEAR_CONTROL_POINTS: ControlPoints = (0.0, 0.0, 1.0, 1.0);
+
+struct CAMediaTimingFunctionHostObject {
+    control_points: ControlPoints,
+}
+impl HostObject for CAMediaTimingFunctionHostObject {}
+
+/// Shared by `CAAnimation` and all its subclasses.
+struct CAAnimationHostObject {
+    duration: CFTimeInterval,
+    /// `CAMediaTimingFunction*`, possibly nil.
+    timing_function: id,
+    removed_on_completion: bool,
+    /// `NSString*`
+    fill_mode: id,
+    /// Possibly nil. Unusually, this is a strong reference.
+    delegate: id,
+    /// `NSString*`, for `CAPropertyAnimation` only.
+    key_path: id,
+    /// For `CABasicAnimation` only.
+    from_value: id,
+    /// For `CABasicAnimation` only.
+    to_value: id,
+}
+impl HostObject for CAAnimationHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CAMediaTimingFunction: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(CAMediaTimingFunctionHostObject {
+        control_points: LINEAR_CONTROL_POINTS,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)functionWithName:(id)name { // NSString*
+    // Control points are from Apple's documentation.
+    let control_points = match &*ns_string::to_rust_string(env, name) {
+        kCAMediaTimingFunctionLinear => LINEAR_CONTROL_POINTS,
+        kCAMediaTimingFunctionEaseIn => (0.42, 0.0, 1.0, 1.0),
+        kCAMediaTimingFunctionEaseOut => (0.0, 0.0, 0.58, 1.0),
+        kCAMediaTimingFunctionEaseInEaseOut => (0.42, 0.0, 0.58, 1.0),
+        kCAMediaTimingFunctionDefault => (0.25, 0.1, 0.25, 1.0),
+        other => panic!("Unknown timing function name {:?}", other),
+    };
+    let new: id = msg![env; this alloc];
+    env.objc.borrow_mut::<CAMediaTimingFunctionHostObject>(new).control_points = control_points;
+    autorelease(env, new)
+}
+
+@end
+
+@implementation CAAnimation: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let fill_mode = ns_string::get_static_str(env, kCAFillModeRemoved);
+    let host_object = Box::new(CAAnimationHostObject {
+        duration: 0.0,
+        timing_function: nil,
+        removed_on_completion: true,
+        fill_mode,
+        delegate: nil,
+        key_path: nil,
+        from_value: nil,
+        to_value: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)animation {
+    let new: id = msg![env; this new];
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let &CAAnimationHostObject {
+        timing_function,
+        fill_mode,
+        delegate,
+        key_path,
+        from_value,
+        to_value,
+        ..
+    } = env.objc.borrow(this);
+    release(env, timing_function);
+    release(env, fill_mode);
+    release(env, delegate);
+    release(env, key_path);
+    release(env, from_value);
+    release(env, to_value);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (CFTimeInterval)duration {
+    env.objc.borrow::<CAAnimationHostObject>(this).duration
+}
+- (())setDuration:(CFTimeInterval)duration {
+    env.objc.borrow_mut::<CAAnimationHostObject>(this).duration = duration;
+}
+
+- (id)timingFunction {
+    env.objc.borrow::<CAAnimationHostObject>(this).timing_function
+}
+- (())setTimingFunction:(id)timing_function {
+    retain(env, timing_function);
+    let host_object = env.objc.borrow_mut::<CAAnimationHostObject>(this);
+    let old = std::mem::replace(&mut host_object.timing_function, timing_function);
+    release(env, old);
+}
+
+- (bool)isRemovedOnCompletion {
+    env.objc.borrow::<CAAnimationHostObject>(this).removed_on_completion
+}
+- (())setRemovedOnCompletion:(bool)removed_on_completion {
+    env.objc.borrow_mut::<CAAnimationHostObject>(this).removed_on_completion = removed_on_completion;
+}
+
+- (id)fillMode {
+    env.objc.borrow::<CAAnimationHostObject>(this).fill_mode
+}
+- (())setFillMode:(id)fill_mode { // NSString*
+    retain(env, fill_mode);
+    let host_object = env.objc.borrow_mut::<CAAnimationHostObject>(this);
+    let old = std::mem::replace(&mut host_object.fill_mode, fill_mode);
+    release(env, old);
+}
+
+- (id)delegate {
+    env.objc.borrow::<CAAnimationHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate {
+    retain(env, delegate);
+    let host_object = env.objc.borrow_mut::<CAAnimationHostObject>(this);
+    let old = std::mem::replace(&mut host_object.delegate, delegate);
+    release(env, old);
+}
+
+// CAAction implementation
+- (())runActionForKey:(id)key // NSString*
+               object:(id)layer // CALayer*
+            arguments:(id)_arguments { // NSDictionary*
+    () = msg![env; layer addAnimation:this forKey:key];
+}
+
+@end
+
+@implementation CAPropertyAnimation: CAAnimation
+
++ (id)animationWithKeyPath:(id)key_path { // NSString*
+    let new: id = msg![env; this animation];
+    () = msg![env; new setKeyPath:key_path];
+    new
+}
+
+- (id)keyPath {
+    env.objc.borrow::<CAAnimationHostObject>(this).key_path
+}
+- (())setKeyPath:(id)key_path { // NSString*
+    retain(env, key_path);
+    let host_object = env.objc.borrow_mut::<CAAnimationHostObject>(this);
+    let old = std::mem::replace(&mut host_object.key_path, key_path);
+    release(env, old);
+}
+
+@end
+
+@implementation CABasicAnimation: CAPropertyAnimation
+
+- (id)fromValue {
+    env.objc.borrow::<CAAnimationHostObject>(this).from_value
+}
+- (())setFromValue:(id)from_value {
+    retain(env, from_value);
+    let host_object = env.objc.borrow_mut::<CAAnimationHostObject>(this);
+    let old = std::mem::replace(&mut host_object.from_value, from_value);
+    release(env, old);
+}
+
+- (id)toValue {
+    env.objc.borrow::<CAAnimationHostObject>(this).to_value
+}
+- (())setToValue:(id)to_value {
+    retain(env, to_value);
+    let host_object = env.objc.borrow_mut::<CAAnimationHostObject>(this);
+    let old = std::mem::replace(&mut host_object.to_value, to_value);
+    release(env, old);
+}
+
+// TODO: byValue
+
+@end
+
+};
+
+/// The layer properties that can currently be animated.
+#[derive(Copy, Clone, Debug)]
+pub(super) enum AnimatedProperty {
+    Opacity,
+    Position,
+    Bounds,
+    Transform,
+}
+impl AnimatedProperty {
+    fn key_path(self) -> &'static str {
+        match self {
+            AnimatedProperty::Opacity => "opacity",
+            AnimatedProperty::Position => "position",
+            AnimatedProperty::Bounds => "bounds",
+            AnimatedProperty::Transform => "transform",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(super) enum AnimatedValue {
+    Float(CGFloat),
+    Point(CGPoint),
+    Rect(CGRect),
+    Transform(CATransform3D),
+}
+
+/// Values of animated properties, as of the last time animations were
+/// evaluated. [None] means the model value should be used.
+#[derive(Default)]
+pub(super) struct PresentationValues {
+    opacity: Option<CGFloat>,
+    position: Option<CGPoint>,
+    bounds: Option<CGRect>,
+    transform: Option<CATransform3D>,
+}
+impl PresentationValues {
+    fn is_empty(&self) -> bool {
+        self.opacity.is_none()
+            && self.position.is_none()
+            && self.bounds.is_none()
+            && self.transform.is_none()
+    }
+
+    fn set(&mut self, value: AnimatedValue, property: AnimatedProperty) {
+        match (property, value) {
+            (AnimatedProperty::Opacity, AnimatedValue::Float(v)) => self.opacity = Some(v),
+            (AnimatedProperty::Position, AnimatedValue::Point(v)) => self.position = Some(v),
+            (AnimatedProperty::Bounds, AnimatedValue::Rect(v)) => self.bounds = Some(v),
+            (AnimatedProperty::Transform, AnimatedValue::Transform(v)) => self.transform = Some(v),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// An animation that has been added to a layer.
+pub(super) struct ActiveAnimation {
+    /// `NSString*`, possibly nil. This is a strong reference.
+    key: id,
+    /// `CAAnimation*`. This is a strong reference.
+    animation: id,
+    property: AnimatedProperty,
+    from: AnimatedValue,
+    /// If this is [None], the animation goes to the model value.
+    to: Option<AnimatedValue>,
+    start: Instant,
+    duration: CFTimeInterval,
+    control_points: ControlPoints,
+    removed_on_completion: bool,
+    fill_forwards: bool,
+    started: bool,
+    finished: bool,
+}
+
+impl CALayerHostObject {
+    pub(super) fn presentation_opacity(&self) -> CGFloat {
+        self.presentation.opacity.unwrap_or(self.opacity)
+    }
+    pub(super) fn presentation_position(&self) -> CGPoint {
+        self.presentation.position.unwrap_or(self.position)
+    }
+    pub(super) fn presentation_bounds(&self) -> CGRect {
+        self.presentation.bounds.unwrap_or(self.bounds)
+    }
+    pub(super) fn presentation_transform(&self) -> CATransform3D {
+        self.presentation.transform.unwrap_or(self.transform)
+    }
+
+    fn model_value(&self, property: AnimatedProperty) -> AnimatedValue {
+        match property {
+            AnimatedProperty::Opacity => AnimatedValue::Float(self.opacity),
+            AnimatedProperty::Position => AnimatedValue::Point(self.position),
+            AnimatedProperty::Bounds => AnimatedValue::Rect(self.bounds),
+            AnimatedProperty::Transform => AnimatedValue::Transform(self.transform),
+        }
+    }
+
+    pub(super) fn presentation_value(&self, property: AnimatedProperty) -> AnimatedValue {
+        match property {
+            AnimatedProperty::Opacity => AnimatedValue::Float(self.presentation_opacity()),
+            AnimatedProperty::Position => AnimatedValue::Point(self.presentation_position()),
+            AnimatedProperty::Bounds => AnimatedValue::Rect(self.presentation_bounds()),
+            AnimatedProperty::Transform => AnimatedValue::Transform(self.presentation_transform()),
+        }
+    }
+}
+
+fn read_value(env: &mut Environment, value: id, property: AnimatedProperty) -> AnimatedValue {
+    match property {
+        AnimatedProperty::Opacity => AnimatedValue::Float(msg![env; value floatValue]),
+        AnimatedProperty::Position => AnimatedValue::Point(msg![env; value CGPointValue]),
+        AnimatedProperty::Bounds => AnimatedValue::Rect(msg![env; value CGRectValue]),
+        AnimatedProperty::Transform => {
+            AnimatedValue::Transform(msg![env; value CATransform3DValue])
+        }
+    }
+}
+
+fn new_value_object(env: &mut Environment, value: AnimatedValue) -> id {
+    match value {
+        AnimatedValue::Float(v) => msg_class![env; NSNumber numberWithFloat:v],
+        AnimatedValue::Point(v) => msg_class![env; NSValue valueWithCGPoint:v],
+        AnimatedValue::Rect(v) => msg_class![env; NSValue valueWithCGRect:v],
+        AnimatedValue::Transform(v) => msg_class![env; NSValue valueWithCATransform3D:v],
+    }
+}
+
+fn interpolate(from: AnimatedValue, to: AnimatedValue, t: CGFloat) -> AnimatedValue {
+    fn lerp(from: CGFloat, to: CGFloat, t: CGFloat) -> CGFloat {
+        from + (to - from) * t
+    }
+    fn lerp_point(from: CGPoint, to: CGPoint, t: CGFloat) -> CGPoint {
+        CGPoint {
+            x: lerp(from.x, to.x, t),
+            y: lerp(from.y, to.y, t),
+        }
+    }
+    match (from, to) {
+        (AnimatedValue::Float(from), AnimatedValue::Float(to)) => {
+            AnimatedValue::Float(lerp(from, to, t))
+        }
+        (AnimatedValue::Point(from), AnimatedValue::Point(to)) => {
+            AnimatedValue::Point(lerp_point(from, to, t))
+        }
+        (AnimatedValue::Rect(from), AnimatedValue::Rect(to)) => AnimatedValue::Rect(CGRect {
+            origin: lerp_point(from.origin, to.origin, t),
+            size: CGSize {
+                width: lerp(from.size.width, to.size.width, t),
+                height: lerp(from.size.height, to.size.height, t),
+            },
+        }),
+        // TODO: Core Animation interpolates the decomposed transforms
+        // (translation, scale, rotation etc), so rotations don't shrink
+        // half-way through like they do with element-wise interpolation.
+        (AnimatedValue::Transform(from), AnimatedValue::Transform(to)) => {
+            let (from, to) = (from.to_array(), to.to_array());
+            AnimatedValue::Transform(CATransform3D::from_array(std::array::from_fn(|i| {
+                lerp(from[i], to[i], t)
+            })))
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Evaluate a cubic Bézier timing curve: map a fraction of the duration that
+/// has elapsed to a fraction of the animation's progress.
+fn evaluate_timing_curve(control_points: ControlPoints, x: f32) -> f32 {
+    fn bezier(p1: f32, p2: f32, t: f32) -> f32 {
+        let u = 1.0 - t;
+        3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t
+    }
+
+    if x <= 0.0 {
+        return 0.0;
+    } else if x >= 1.0 {
+        return 1.0;
+    }
+
+    let (x1, y1, x2, y2) = control_points;
+    // The x co-ordinate of the curve is monotonic, because the control points'
+    // x co-ordinates are within [0, 1], so bisection can be used to find t.
+    let mut low = 0.0;
+    let mut high = 1.0;
+    for _ in 0..32 {
+        let mid = (low + high) / 2.0;
+        if bezier(x1, x2, mid) < x {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    bezier(y1, y2, (low + high) / 2.0)
+}
+
+/// Implementation of `-[CALayer addAnimation:forKey:]`.
+pub(super) fn add_animation(env: &mut Environment, layer: id, animation: id, key: id) {
+    let &CAAnimationHostObject {
+        duration,
+        timing_function,
+        removed_on_completion,
+        fill_mode,
+        key_path,
+        from_value,
+        to_value,
+        ..
+    } = env.objc.borrow(animation);
+
+    if key_path == nil {
+        log!(
+            "Warning: animation {:?} has no key path, ignoring",
+            animation
+        );
+        return;
+    }
+    let key_path = ns_string::to_rust_string(env, key_path);
+    let property = match [
+        AnimatedProperty::Opacity,
+        AnimatedProperty::Position,
+        AnimatedProperty::Bounds,
+        AnimatedProperty::Transform,
+    ]
+    .into_iter()
+    .find(|property| property.key_path() == key_path)
+    {
+        Some(property) => property,
+        None => {
+            log!(
+                "TODO: animation of key path {:?} is not supported, ignoring animation {:?}",
+                key_path,
+                animation
+            );
+            return;
+        }
+    };
+
+    // The animation's values are read now, so later changes to the animation
+    // object won't affect it. This matches Core Animation, which copies the
+    // animation.
+    let from = if from_value != nil {
+        read_value(env, from_value, property)
+    } else {
+        env.objc
+            .borrow::<CALayerHostObject>(layer)
+            .presentation_value(property)
+    };
+    let to = if to_value != nil {
+        Some(read_value(env, to_value, property))
+    } else {
+        None
+    };
+    let control_points = if timing_function != nil {
+        env.objc
+            .borrow::<CAMediaTimingFunctionHostObject>(timing_function)
+            .control_points
+    } else {
+        LINEAR_CONTROL_POINTS
+    };
+    let fill_forwards = matches!(
+        &*ns_string::to_rust_string(env, fill_mode),
+        kCAFillModeForwards | kCAFillModeBoth
+    );
+
+    // An animation with the same key replaces the existing one.
+    if key != nil {
+        remove_animation(env, layer, Some(key));
+    }
+
+    retain(env, animation);
+    // TODO: copy the key?
+    retain(env, key);
+    env.objc
+        .borrow_mut::<CALayerHostObject>(layer)
+        .animations
+        .push(ActiveAnimation {
+            key,
+            animation,
+            property,
+            from,
+            to,
+            start: env.clock.now(),
+            // Zero means the default duration.
+            duration: if duration > 0.0 { duration } else { 0.25 },
+            control_points,
+            removed_on_completion,
+            fill_forwards,
+            started: false,
+            finished: false,
+        });
+    ca_transaction::animation_added(env, animation);
+    set_needs_recomposite(env);
+}
+
+/// For use by `CALayer`'s setters: run the action for a change to an animatable
+/// property, which by default is an implicit animation from `old_value` (the
+/// presentation value before the change) to the new value.
+///
+/// The layer's delegate gets the first chance to provide the action with
+/// `actionForLayer:forKey:`, and it can return `NSNull` to prevent it. This is
+/// how views stop their layers from being animated outside of animation
+/// blocks.
+pub(super) fn run_action_for_change(
+    env: &mut Environment,
+    layer: id,
+    property: AnimatedProperty,
+    old_value: AnimatedValue,
+) {
+    let Some((duration, timing_function)) = ca_transaction::animation_settings(env) else {
+        return;
+    };
+    // Like in the real Core Animation, layers that aren't part of a layer tree
+    // yet don't get implicit animations.
+    let &CALayerHostObject {
+        superlayer,
+        delegate,
+        ..
+    } = env.objc.borrow(layer);
+    if superlayer == nil {
+        return;
+    }
+
+    let key = ns_string::get_static_str(env, property.key_path());
+    let mut action = nil;
+    if delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, "actionForLayer:forKey:")
+    {
+        action = msg![env; delegate actionForLayer:layer forKey:key];
+        let null: id = msg_class![env; NSNull null];
+        if action == null {
+            return;
+        }
+    }
+    // TODO: the layer's actions dictionary, style and +defaultActionForKey:
+
+    let basic_animation_class = env.objc.get_known_class("CABasicAnimation", &mut env.mem);
+    if action == nil {
+        action = msg_class![env; CABasicAnimation animationWithKeyPath:key];
+        () = msg![env; action setDuration:duration];
+        let timing_function = if timing_function != nil {
+            timing_function
+        } else {
+            let name = ns_string::get_static_str(env, kCAMediaTimingFunctionDefault);
+            msg_class![env; CAMediaTimingFunction functionWithName:name]
+        };
+        () = msg![env; action setTimingFunction:timing_function];
+    }
+    if msg![env; action isKindOfClass:basic_animation_class] {
+        let from_value: id = msg![env; action fromValue];
+        // The presentation value is already the new value, so without this the
+        // animation wouldn't go anywhere.
+        if from_value == nil {
+            let from_value = new_value_object(env, old_value);
+            () = msg![env; action setFromValue:from_value];
+        }
+    }
+
+    if env
+        .objc
+        .object_has_method_named(&env.mem, action, "runActionForKey:object:arguments:")
+    {
+        () = msg![env; action runActionForKey:key object:layer arguments:nil];
+    } else {
+        log!(
+            "Warning: action {:?} for key {:?} of layer {:?} isn't a CAAction, ignoring",
+            action,
+            property.key_path(),
+            layer
+        );
+    }
+}
+
+/// Implementation of `-[CALayer animationForKey:]`.
+pub(super) fn animation_for_key(env: &mut Environment, layer: id, key: id) -> id {
+    let keys_and_animations: Vec<(id, id)> = env
+        .objc
+        .borrow::<CALayerHostObject>(layer)
+        .animations
+        .iter()
+        .map(|active| (active.key, active.animation))
+        .collect();
+    for (other_key, animation) in keys_and_animations {
+        if other_key != nil && msg![env; key isEqualToString:other_key] {
+            return animation;
+        }
+    }
+    nil
+}
+
+/// Implementation of `-[CALayer removeAnimationForKey:]` (`key` is [Some]) and
+/// `-[CALayer removeAllAnimations]` (`key` is [None]).
+pub(super) fn remove_animation(env: &mut Environment, layer: id, key: Option<id>) {
+    let animations =
+        std::mem::take(&mut env.objc.borrow_mut::<CALayerHostObject>(layer).animations);
+    let mut kept = Vec::new();
+    let mut removed = Vec::new();
+    for active in animations {
+        let matches = match key {
+            None => true,
+            Some(key) => active.key != nil && msg![env; key isEqualToString:(active.key)],
+        };
+        if matches {
+            removed.push(active);
+        } else {
+            kept.push(active);
+        }
+    }
+    // Animations might have been added while sending messages.
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(layer);
+    kept.append(&mut host_object.animations);
+    host_object.animations = kept;
+
+    if removed.is_empty() {
+        return;
+    }
+    stop_animations(env, removed.into_iter().map(|active| (active, false)));
+    set_needs_recomposite(env);
+}
+
+/// Tell animations' delegates they have stopped, then release them.
+fn stop_animations(env: &mut Environment, stopped: impl Iterator<Item = (ActiveAnimation, bool)>) {
+    for (active, finished) in stopped {
+        let delegate = env
+            .objc
+            .borrow::<CAAnimationHostObject>(active.animation)
+            .delegate;
+        if delegate != nil
+            && env
+                .objc
+                .object_has_method_named(&env.mem, delegate, "animationDidStop:finished:")
+        {
+            let animation = active.animation;
+            () = msg![env; delegate animationDidStop:animation finished:finished];
+        }
+        ca_transaction::animation_stopped(env, active.animation, finished);
+        release(env, active.animation);
+        release(env, active.key);
+    }
+}
+
+/// For use by `CALayer`'s `dealloc`: release a layer's animations without
+/// notifying their delegates.
+pub(super) fn release_animations(env: &mut Environment, animations: Vec<ActiveAnimation>) {
+    for active in animations {
+        ca_transaction::animation_stopped(env, active.animation, false);
+        release(env, active.animation);
+        release(env, active.key);
+    }
+}
+
+/// For use by the compositor: update the presentation values of every layer in
+/// the tree that has animations, and remove animations that have finished.
+pub(super) fn evaluate_animations(env: &mut Environment, root_layer: id) {
+    fn traverse(objc: &ObjC, layer: id, animated_layers: &mut Vec<id>) {
+        let host_obj = objc.borrow::<CALayerHostObject>(layer);
+        if !host_obj.animations.is_empty() || !host_obj.presentation.is_empty() {
+            animated_layers.push(layer);
+        }
+        for &layer in &host_obj.sublayers {
+            traverse(objc, layer, animated_layers);
+        }
+    }
+
+    let mut animated_layers = Vec::new();
+    traverse(&env.objc, root_layer, &mut animated_layers);
+    if animated_layers.is_empty() {
+        return;
+    }
+
+    let now = env.clock.now();
+    let mut any_running = false;
+    let mut started = Vec::new();
+    let mut stopped = Vec::new();
+    for layer in animated_layers {
+        let host_obj = env.objc.borrow_mut::<CALayerHostObject>(layer);
+
+        let model_values: Vec<AnimatedValue> = host_obj
+            .animations
+            .iter()
+            .map(|active| host_obj.model_value(active.property))
+            .collect();
+
+        let mut presentation = PresentationValues::default();
+        for (active, model_value) in host_obj.animations.iter_mut().zip(model_values) {
+            if !active.started {
+                active.started = true;
+                started.push(active.animation);
+            }
+
+            let progress = now.duration_since(active.start).as_secs_f64() / active.duration;
+            if progress >= 1.0 {
+                active.finished = true;
+                if active.removed_on_completion || !active.fill_forwards {
+                    continue;
+                }
+            } else {
+                any_running = true;
+            }
+
+            let t = evaluate_timing_curve(active.control_points, progress.min(1.0) as f32);
+            let to = active.to.unwrap_or(model_value);
+            presentation.set(interpolate(active.from, to, t), active.property);
+        }
+        host_obj.presentation = presentation;
+
+        let (finished, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut host_obj.animations)
+            .into_iter()
+            .partition(|active| active.finished && active.removed_on_completion);
+        host_obj.animations = kept;
+        stopped.extend(finished.into_iter().map(|active| (active, true)));
+    }
+
+    // The presentation values will change again next frame, or have reverted
+    // to the model values.
+    if any_running || !stopped.is_empty() {
+        set_needs_recomposite(env);
+    }
+
+    for animation in started {
+        let delegate = env.objc.borrow::<CAAnimationHostObject>(animation).delegate;
+        if delegate != nil
+            && env
+                .objc
+                .object_has_method_named(&env.mem, delegate, "animationDidStart:")
+        {
+            () = msg![env; delegate animationDidStart:animation];
+        }
+    }
+    stop_animations(env, stopped.into_iter());
+}
+
+#[cfg(test)]
+#[test]
+fn test_evaluate_timing_curve() {
+    let ease_in_ease_out = (0.42, 0.0, 0.58, 1.0);
+    for control_points in [LINEAR_CONTROL_POINTS, ease_in_ease_out] {
+        assert_eq!(evaluate_timing_curve(control_points, 0.0), 0.0);
+        assert_eq!(evaluate_timing_curve(control_points, 1.0), 1.0);
+        assert!((evaluate_timing_curve(control_points, 0.5) - 0.5).abs() < 0.001);
+    }
+    // Linear timing is the identity function.
+    assert!((evaluate_timing_curve(LINEAR_CONTROL_POINTS, 0.25) - 0.25).abs() < 0.001);
+    // Easing in is slower at the start.
+    assert!(evaluate_timing_curve(ease_in_ease_out, 0.25) < 0.2);
+    assert!(evaluate_timing_curve(ease_in_ease_out, 0.75) > 0.8);
+}
@@ -0,0 +1,17 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CABase.h`
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::time::CFTimeInterval;
+use crate::Environment;
+
+/// Like `mach_absolute_time()`, but in seconds.
+fn CACurrentMediaTime(env: &mut Environment) -> CFTimeInterval {
+    env.clock.uptime().as_secs_f64()
+}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(CACurrentMediaTime())];
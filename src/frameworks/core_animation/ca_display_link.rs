@@ -0,0 +1,210 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CADisplayLink`.
+//!
+//! Display links are handled by the run loop, at the same 60Hz rate as
+//! [super::recomposite_if_necessary].
+
+use crate::frameworks::core_foundation::time::CFTimeInterval;
+use crate::frameworks::foundation::{ns_run_loop, NSInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_send, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr, SEL,
+};
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+/// The refresh interval of the display.
+const FRAME_DURATION: CFTimeInterval = 1.0 / 60.0;
+
+struct CADisplayLinkHostObject {
+    /// Strong reference, released when the link is invalidated.
+    target: id,
+    selector: Option<SEL>,
+    /// Number of frames per firing.
+    frame_interval: NSInteger,
+    paused: bool,
+    /// `NSRunLoop*`s the link has been added to. Run loops are never
+    /// deallocated, so these don't need to be retained.
+    run_loops: Vec<id>,
+    /// Time of the last frame the link fired for.
+    timestamp: CFTimeInterval,
+    next_due: Option<Instant>,
+}
+impl HostObject for CADisplayLinkHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CADisplayLink: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(CADisplayLinkHostObject {
+        target: nil,
+        selector: None,
+        frame_interval: 1,
+        paused: false,
+        run_loops: Vec::new(),
+        timestamp: 0.0,
+        next_due: None,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)displayLinkWithTarget:(id)target
+                   selector:(SEL)selector {
+    retain(env, target);
+    let new: id = msg![env; this alloc];
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(new);
+    host_object.target = target;
+    host_object.selector = Some(selector);
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let target = env.objc.borrow::<CADisplayLinkHostObject>(this).target;
+    release(env, target);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())addToRunLoop:(id)run_loop // NSRunLoop*
+           forMode:(id)_mode { // NSString*
+    // TODO: handle run loop modes
+    let run_loops = &mut env.objc.borrow_mut::<CADisplayLinkHostObject>(this).run_loops;
+    if run_loops.contains(&run_loop) {
+        return;
+    }
+    run_loops.push(run_loop);
+    log_dbg!("Adding display link {:?} to run loop {:?}", this, run_loop);
+    ns_run_loop::add_display_link(env, run_loop, this);
+}
+- (())removeFromRunLoop:(id)run_loop // NSRunLoop*
+                forMode:(id)_mode { // NSString*
+    let run_loops = &mut env.objc.borrow_mut::<CADisplayLinkHostObject>(this).run_loops;
+    let Some(idx) = run_loops.iter().position(|&rl| rl == run_loop) else {
+        return;
+    };
+    run_loops.remove(idx);
+    ns_run_loop::remove_display_link(env, run_loop, this);
+}
+
+- (())invalidate {
+    log_dbg!("Invalidating display link {:?}", this);
+    // The run loops may hold the last references to the link.
+    retain(env, this);
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    let run_loops = std::mem::take(&mut host_object.run_loops);
+    let target = std::mem::take(&mut host_object.target);
+    host_object.selector = None;
+    host_object.next_due = None;
+    for run_loop in run_loops {
+        ns_run_loop::remove_display_link(env, run_loop, this);
+    }
+    release(env, target);
+    release(env, this);
+}
+
+- (NSInteger)frameInterval {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).frame_interval
+}
+- (())setFrameInterval:(NSInteger)frame_interval {
+    if frame_interval < 1 {
+        log!(
+            "Warning: Ignoring invalid frame interval {} for display link {:?}",
+            frame_interval,
+            this
+        );
+        return;
+    }
+    env.objc.borrow_mut::<CADisplayLinkHostObject>(this).frame_interval = frame_interval;
+}
+
+- (bool)isPaused {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).paused
+}
+- (())setPaused:(bool)paused {
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    host_object.paused = paused;
+    // A link that is resumed fires at the next frame, not for the frames it
+    // missed.
+    host_object.next_due = None;
+}
+
+- (CFTimeInterval)timestamp {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).timestamp
+}
+- (CFTimeInterval)duration {
+    FRAME_DURATION
+}
+
+@end
+
+};
+
+/// For use by `NSRunLoop`: fire a display link if a frame is due. Returns the
+/// time the next frame is due, if any.
+pub fn handle_display_link(env: &mut Environment, link: id) -> Option<Instant> {
+    let &CADisplayLinkHostObject {
+        target,
+        selector,
+        frame_interval,
+        paused,
+        next_due,
+        ..
+    } = env.objc.borrow(link);
+    // The link might have been invalidated by another link's target earlier in
+    // the same run loop iteration.
+    let Some(selector) = selector.filter(|_| !paused) else {
+        return None;
+    };
+
+    let now = env.clock.now();
+    let interval = FRAME_DURATION * frame_interval as CFTimeInterval;
+    let Some(next_due) = next_due else {
+        // The first firing is at the next frame.
+        let next_due = now.checked_add(Duration::from_secs_f64(interval)).unwrap();
+        env.objc
+            .borrow_mut::<CADisplayLinkHostObject>(link)
+            .next_due = Some(next_due);
+        return Some(next_due);
+    };
+    if next_due > now {
+        return Some(next_due);
+    }
+
+    // Like a repeating NSTimer, missed frames are skipped rather than caught
+    // up with.
+    let overdue_by = now.duration_since(next_due);
+    let advance_by = (overdue_by.as_secs_f64() / interval).max(1.0).ceil() as u32;
+    if advance_by > 1 {
+        log_dbg!(
+            "Warning: Display link {:?} is lagging, it has missed {} frame(s)!",
+            link,
+            advance_by - 1
+        );
+    }
+    let new_next_due = next_due
+        .checked_add(
+            Duration::from_secs_f64(interval)
+                .checked_mul(advance_by)
+                .unwrap(),
+        )
+        .unwrap();
+    let timestamp = env.clock.uptime().as_secs_f64();
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(link);
+    host_object.next_due = Some(new_next_due);
+    host_object.timestamp = timestamp;
+
+    // The target might invalidate the link.
+    retain(env, link);
+    retain(env, target);
+    () = msg_send(env, (target, selector, link));
+    release(env, target);
+    release(env, link);
+
+    Some(new_next_due)
+}
@@ -5,8 +5,11 @@
  */
 //! `CAEAGLLayer`.
 
-use super::ca_layer::CALayerHostObject;
+use super::ca_layer::{sublayers_in_drawing_order, CALayerHostObject, ContentsGravity};
+use super::ca_transform_3d::CATransform3DIdentity;
+use super::composition::set_needs_recomposite;
 use crate::frameworks::core_graphics::{CGPoint, CGRect};
+use crate::frameworks::uikit::ui_view::ui_window;
 use crate::objc::{id, msg, msg_class, nil, objc_classes, Class, ClassExports};
 use crate::Environment;
 
@@ -46,9 +49,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 /// and present it directly from the app's context. This function is used to
 /// determine when that will happen.
 pub fn find_fullscreen_eagl_layer(env: &mut Environment) -> id {
-    // Assumes the last window in the list is the one on top.
-    // TODO: this is not correct once we support zPosition.
-    let Some(&top_window) = env.framework_state.uikit.ui_view.ui_window.visible_windows.last() else {
+    let Some(top_window) = ui_window::top_visible_window(env) else {
         return nil;
     };
 
@@ -60,8 +61,7 @@ pub fn find_fullscreen_eagl_layer(env: &mut Environment) -> id {
     let mut layer: id = msg![env; top_window layer];
 
     // Descend through the hierarchy, looking only at the last layer in each
-    // list of children, since that should be the one on top.
-    // TODO: this is not correct once we support zPosition.
+    // list of children in drawing order, since that should be the one on top.
     loop {
         assert!(layer != nil);
 
@@ -78,20 +78,25 @@ pub fn find_fullscreen_eagl_layer(env: &mut Environment) -> id {
                     x: screen_bounds.size.width / 2.0,
                     y: screen_bounds.size.height / 2.0,
                 })
+            || layer_host_obj.transform != CATransform3DIdentity
             || layer_host_obj.hidden
             || layer_host_obj.opacity != 1.0
+            // Animations are only evaluated during composition.
+            || !layer_host_obj.animations.is_empty()
         {
             return nil;
         }
 
-        if let Some(&next) = layer_host_obj.sublayers.last() {
+        if let Some(&next) = sublayers_in_drawing_order(&env.objc, layer).last() {
             layer = next;
         } else {
             break;
         }
     }
 
-    if !env.objc.borrow::<CALayerHostObject>(layer).opaque {
+    let layer_host_obj = env.objc.borrow::<CALayerHostObject>(layer);
+    // Presenting directly always stretches the pixels to fill the screen.
+    if !layer_host_obj.opaque || layer_host_obj.contents_gravity != ContentsGravity::Resize {
         return nil;
     }
 
@@ -123,4 +128,5 @@ pub fn present_pixels(env: &mut Environment, layer: id, pixels: Vec<u8>, width:
     let host_obj = env.objc.borrow_mut::<CALayerHostObject>(layer);
     host_obj.presented_pixels = Some((pixels, width, height));
     host_obj.gles_texture_is_up_to_date = false;
+    set_needs_recomposite(env);
 }
@@ -0,0 +1,280 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CAGradientLayer`.
+//!
+//! Only axial (linear) gradients are supported. The compositor draws them with
+//! per-vertex colors: the layer's shape is cut into bands at each color stop,
+//! and OpenGL ES interpolates the colors within each band.
+
+use super::ca_layer::CALayerHostObject;
+use super::composition::{clip_polygon, set_needs_recomposite};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGSize};
+use crate::frameworks::foundation::NSUInteger;
+use crate::objc::{id, msg, msg_super, nil, objc_classes, release, ClassExports};
+use crate::Environment;
+
+/// Properties only used by `CAGradientLayer`.
+#[derive(Clone)]
+pub(super) struct CAGradientLayerProperties {
+    /// `NSArray*` of `CGColorRef`, possibly nil. This is an immutable copy.
+    colors: id,
+    /// The contents of `colors`, for use by the compositor.
+    pub(super) color_list: Vec<id>,
+    /// `NSArray*` of `NSNumber*`, possibly nil. This is an immutable copy.
+    locations: id,
+    /// The contents of `locations`, for use by the compositor.
+    pub(super) location_list: Option<Vec<CGFloat>>,
+    pub(super) start_point: CGPoint,
+    pub(super) end_point: CGPoint,
+}
+
+fn properties(env: &Environment, this: id) -> &CAGradientLayerProperties {
+    env.objc
+        .borrow::<CALayerHostObject>(this)
+        .gradient_properties
+        .as_ref()
+        .unwrap()
+}
+fn properties_mut(env: &mut Environment, this: id) -> &mut CAGradientLayerProperties {
+    env.objc
+        .borrow_mut::<CALayerHostObject>(this)
+        .gradient_properties
+        .as_mut()
+        .unwrap()
+}
+
+/// Get the elements of an `NSArray*`, which may be nil.
+fn array_elements(env: &mut Environment, array: id) -> Vec<id> {
+    if array == nil {
+        return Vec::new();
+    }
+    let count: NSUInteger = msg![env; array count];
+    (0..count)
+        .map(|i| msg![env; array objectAtIndex:i])
+        .collect()
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CAGradientLayer: CALayer
+
++ (id)alloc {
+    let host_object = Box::new(CALayerHostObject {
+        gradient_properties: Some(CAGradientLayerProperties {
+            colors: nil,
+            color_list: Vec::new(),
+            locations: nil,
+            location_list: None,
+            start_point: CGPoint { x: 0.5, y: 0.0 },
+            end_point: CGPoint { x: 0.5, y: 1.0 },
+        }),
+        ..Default::default()
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &CAGradientLayerProperties {
+        colors, locations, ..
+    } = properties(env, this);
+    release(env, colors);
+    release(env, locations);
+    msg_super![env; this dealloc]
+}
+
+- (id)colors {
+    properties(env, this).colors
+}
+- (())setColors:(id)new_colors { // NSArray*
+    let new_colors: id = msg![env; new_colors copy];
+    let color_list = array_elements(env, new_colors);
+    let props = properties_mut(env, this);
+    let old_colors = std::mem::replace(&mut props.colors, new_colors);
+    props.color_list = color_list;
+    release(env, old_colors);
+    set_needs_recomposite(env);
+}
+
+- (id)locations {
+    properties(env, this).locations
+}
+- (())setLocations:(id)new_locations { // NSArray*
+    let new_locations: id = msg![env; new_locations copy];
+    let location_list = if new_locations == nil {
+        None
+    } else {
+        let numbers = array_elements(env, new_locations);
+        Some(numbers.into_iter().map(|number| msg![env; number floatValue]).collect())
+    };
+    let props = properties_mut(env, this);
+    let old_locations = std::mem::replace(&mut props.locations, new_locations);
+    props.location_list = location_list;
+    release(env, old_locations);
+    set_needs_recomposite(env);
+}
+
+- (CGPoint)startPoint {
+    properties(env, this).start_point
+}
+- (())setStartPoint:(CGPoint)start_point {
+    properties_mut(env, this).start_point = start_point;
+    set_needs_recomposite(env);
+}
+
+- (CGPoint)endPoint {
+    properties(env, this).end_point
+}
+- (())setEndPoint:(CGPoint)end_point {
+    properties_mut(env, this).end_point = end_point;
+    set_needs_recomposite(env);
+}
+
+// TODO: type (radial gradients)
+
+@end
+
+};
+
+/// A location along the gradient axis and the r, g, b and a of the color
+/// there.
+pub(super) type GradientStop = (CGFloat, (f32, f32, f32, f32));
+
+/// A part of a gradient that can be drawn as a single triangle fan.
+#[derive(Debug, PartialEq)]
+pub(super) struct GradientBand {
+    /// x and y co-ordinates
+    pub(super) vertices: Vec<f32>,
+    /// r, g, b and a for each vertex
+    pub(super) colors: Vec<f32>,
+}
+
+/// Cut a convex polygon (in the layer's co-ordinate space, i.e. `(0, 0)` to
+/// `size`) into bands with interpolated colors. `start` and `end` are in the
+/// unit co-ordinate space, like `startPoint` and `endPoint`. `stops` must be
+/// sorted by location.
+pub(super) fn gradient_bands(
+    outline: &[CGPoint],
+    size: CGSize,
+    start: CGPoint,
+    end: CGPoint,
+    stops: &[GradientStop],
+) -> Vec<GradientBand> {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length_squared = dx * dx + dy * dy;
+    if stops.is_empty() || length_squared == 0.0 || size.width <= 0.0 || size.height <= 0.0 {
+        return Vec::new();
+    }
+    // The position along the gradient axis, where the start point is 0 and the
+    // end point is 1.
+    let axis_position = |p: CGPoint| {
+        let x = p.x / size.width - start.x;
+        let y = p.y / size.height - start.y;
+        (x * dx + y * dy) / length_squared
+    };
+    // Before the first stop and after the last one, the color is constant.
+    let &(first_location, first_color) = stops.first().unwrap();
+    let &(last_location, last_color) = stops.last().unwrap();
+    let mut ranges = vec![(
+        CGFloat::NEG_INFINITY,
+        first_color,
+        first_location,
+        first_color,
+    )];
+    for pair in stops.windows(2) {
+        let ((lo, lo_color), (hi, hi_color)) = (pair[0], pair[1]);
+        if hi > lo {
+            ranges.push((lo, lo_color, hi, hi_color));
+        }
+    }
+    ranges.push((last_location, last_color, CGFloat::INFINITY, last_color));
+
+    let mut bands = Vec::new();
+    for (lo, lo_color, hi, hi_color) in ranges {
+        let mut polygon = outline.to_vec();
+        if lo.is_finite() {
+            polygon = clip_polygon(polygon, |p| axis_position(p) - lo);
+        }
+        if hi.is_finite() {
+            polygon = clip_polygon(polygon, |p| hi - axis_position(p));
+        }
+        if polygon.len() < 3 {
+            continue;
+        }
+        let mut band = GradientBand {
+            vertices: Vec::with_capacity(polygon.len() * 2),
+            colors: Vec::with_capacity(polygon.len() * 4),
+        };
+        for point in polygon {
+            let t = if lo.is_finite() && hi.is_finite() {
+                ((axis_position(point) - lo) / (hi - lo)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let lerp = |a: f32, b: f32| a + (b - a) * t;
+            band.vertices.extend_from_slice(&[point.x, point.y]);
+            band.colors.extend_from_slice(&[
+                lerp(lo_color.0, hi_color.0),
+                lerp(lo_color.1, hi_color.1),
+                lerp(lo_color.2, hi_color.2),
+                lerp(lo_color.3, hi_color.3),
+            ]);
+        }
+        bands.push(band);
+    }
+    bands
+}
+
+#[cfg(test)]
+#[test]
+fn test_gradient_bands() {
+    fn p(x: CGFloat, y: CGFloat) -> CGPoint {
+        CGPoint { x, y }
+    }
+
+    let size = CGSize {
+        width: 10.0,
+        height: 20.0,
+    };
+    let outline = [p(0.0, 0.0), p(10.0, 0.0), p(10.0, 20.0), p(0.0, 20.0)];
+    let black = (0.0, 0.0, 0.0, 1.0);
+    let white = (1.0, 1.0, 1.0, 1.0);
+
+    // Default vertical gradient with two stops: a single band covering the
+    // whole layer, black at the top and white at the bottom.
+    let bands = gradient_bands(
+        &outline,
+        size,
+        p(0.5, 0.0),
+        p(0.5, 1.0),
+        &[(0.0, black), (1.0, white)],
+    );
+    assert_eq!(bands.len(), 1);
+    assert_eq!(
+        bands[0].vertices,
+        [0.0, 0.0, 10.0, 0.0, 10.0, 20.0, 0.0, 20.0]
+    );
+    assert_eq!(&bands[0].colors[..4], &[0.0, 0.0, 0.0, 1.0]);
+    assert_eq!(&bands[0].colors[8..12], &[1.0, 1.0, 1.0, 1.0]);
+
+    // Stops in the middle: solid black above, a gradient band, and solid white
+    // below.
+    let bands = gradient_bands(
+        &outline,
+        size,
+        p(0.5, 0.0),
+        p(0.5, 1.0),
+        &[(0.25, black), (0.75, white)],
+    );
+    assert_eq!(bands.len(), 3);
+    assert!(bands[0].colors.chunks(4).all(|c| c == [0.0, 0.0, 0.0, 1.0]));
+    assert!(bands[2].colors.chunks(4).all(|c| c == [1.0, 1.0, 1.0, 1.0]));
+    let middle_ys: Vec<f32> = bands[1].vertices.chunks(2).map(|xy| xy[1]).collect();
+    assert!(middle_ys
+        .iter()
+        .all(|&y| (y - 5.0).abs() < 0.001 || (y - 15.0).abs() < 0.001));
+}
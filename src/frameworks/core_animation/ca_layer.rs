@@ -5,7 +5,22 @@
  */
 //! `CALayer`.
 
+use super::ca_animation::{
+    add_animation, animation_for_key, release_animations, remove_animation, run_action_for_change,
+    ActiveAnimation, AnimatedProperty, PresentationValues,
+};
+use super::ca_gradient_layer::CAGradientLayerProperties;
+use super::ca_shape_layer::CAShapeLayerProperties;
+use super::ca_transform_3d::{
+    CATransform3D, CATransform3DGetAffineTransform, CATransform3DIdentity,
+    CATransform3DMakeAffineTransform,
+};
+use super::composition::{set_needs_recomposite, CompositorTexture};
+use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::core_foundation::{CFRelease, CFRetain};
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    CGAffineTransform, CGAffineTransformIdentity,
+};
 use crate::frameworks::core_graphics::cg_bitmap_context::{
     CGBitmapContextCreate, CGBitmapContextGetHeight, CGBitmapContextGetWidth,
 };
@@ -16,28 +31,148 @@ use crate::frameworks::core_graphics::cg_context::{
 use crate::frameworks::core_graphics::cg_image::{
     kCGImageAlphaPremultipliedLast, kCGImageByteOrder32Big,
 };
-use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string;
 use crate::mem::{GuestUSize, Ptr};
 use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, ObjC};
-use std::collections::HashMap;
+use crate::Environment;
+
+// Values might not be correct, but as these are linked symbol constants, it
+// shouldn't matter.
+pub const kCAGravityCenter: &str = "center";
+pub const kCAGravityTop: &str = "top";
+pub const kCAGravityBottom: &str = "bottom";
+pub const kCAGravityLeft: &str = "left";
+pub const kCAGravityRight: &str = "right";
+pub const kCAGravityTopLeft: &str = "topLeft";
+pub const kCAGravityTopRight: &str = "topRight";
+pub const kCAGravityBottomLeft: &str = "bottomLeft";
+pub const kCAGravityBottomRight: &str = "bottomRight";
+pub const kCAGravityResize: &str = "resize";
+pub const kCAGravityResizeAspect: &str = "resizeAspect";
+pub const kCAGravityResizeAspectFill: &str = "resizeAspectFill";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCAGravityCenter",
+        HostConstant::NSString(kCAGravityCenter),
+    ),
+    ("_kCAGravityTop", HostConstant::NSString(kCAGravityTop)),
+    (
+        "_kCAGravityBottom",
+        HostConstant::NSString(kCAGravityBottom),
+    ),
+    ("_kCAGravityLeft", HostConstant::NSString(kCAGravityLeft)),
+    ("_kCAGravityRight", HostConstant::NSString(kCAGravityRight)),
+    (
+        "_kCAGravityTopLeft",
+        HostConstant::NSString(kCAGravityTopLeft),
+    ),
+    (
+        "_kCAGravityTopRight",
+        HostConstant::NSString(kCAGravityTopRight),
+    ),
+    (
+        "_kCAGravityBottomLeft",
+        HostConstant::NSString(kCAGravityBottomLeft),
+    ),
+    (
+        "_kCAGravityBottomRight",
+        HostConstant::NSString(kCAGravityBottomRight),
+    ),
+    (
+        "_kCAGravityResize",
+        HostConstant::NSString(kCAGravityResize),
+    ),
+    (
+        "_kCAGravityResizeAspect",
+        HostConstant::NSString(kCAGravityResizeAspect),
+    ),
+    (
+        "_kCAGravityResizeAspectFill",
+        HostConstant::NSString(kCAGravityResizeAspectFill),
+    ),
+];
+
+/// Parsed value of `contentsGravity`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum ContentsGravity {
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Resize,
+    ResizeAspect,
+    ResizeAspectFill,
+}
+impl ContentsGravity {
+    const NAMES: &[(&'static str, ContentsGravity)] = &[
+        (kCAGravityCenter, ContentsGravity::Center),
+        (kCAGravityTop, ContentsGravity::Top),
+        (kCAGravityBottom, ContentsGravity::Bottom),
+        (kCAGravityLeft, ContentsGravity::Left),
+        (kCAGravityRight, ContentsGravity::Right),
+        (kCAGravityTopLeft, ContentsGravity::TopLeft),
+        (kCAGravityTopRight, ContentsGravity::TopRight),
+        (kCAGravityBottomLeft, ContentsGravity::BottomLeft),
+        (kCAGravityBottomRight, ContentsGravity::BottomRight),
+        (kCAGravityResize, ContentsGravity::Resize),
+        (kCAGravityResizeAspect, ContentsGravity::ResizeAspect),
+        (
+            kCAGravityResizeAspectFill,
+            ContentsGravity::ResizeAspectFill,
+        ),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|&&(n, _)| n == name)
+            .map(|&(_, gravity)| gravity)
+    }
+    fn name(self) -> &'static str {
+        Self::NAMES.iter().find(|&&(_, g)| g == self).unwrap().0
+    }
+}
 
 pub(super) struct CALayerHostObject {
     /// Possibly nil, usually a UIView. This is a weak reference.
-    delegate: id,
+    pub(super) delegate: id,
     /// Sublayers in back-to-front order. These are strong references.
     pub(super) sublayers: Vec<id>,
     /// The superlayer. This is a weak reference.
-    superlayer: id,
+    pub(super) superlayer: id,
     pub(super) bounds: CGRect,
     pub(super) position: CGPoint,
     pub(super) anchor_point: CGPoint,
+    pub(super) z_position: CGFloat,
+    pub(super) transform: CATransform3D,
     pub(super) hidden: bool,
     pub(super) opaque: bool,
     pub(super) opacity: f32,
+    pub(super) masks_to_bounds: bool,
+    pub(super) corner_radius: CGFloat,
     pub(super) background_color: id,
+    pub(super) border_width: CGFloat,
+    /// `CGColorRef`. nil means the default, opaque black.
+    pub(super) border_color: id,
+    /// `CGColorRef`. nil means the default, opaque black.
+    pub(super) shadow_color: id,
+    pub(super) shadow_opacity: f32,
+    pub(super) shadow_offset: CGSize,
+    pub(super) shadow_radius: CGFloat,
     pub(super) needs_display: bool,
     /// `CGImageRef*`
     pub(super) contents: id,
+    pub(super) contents_gravity: ContentsGravity,
+    /// Pixels per point of [Self::contents]. This doesn't affect the bitmap
+    /// used for `drawInContext:`, which always has one pixel per point.
+    pub(super) contents_scale: CGFloat,
     /// For CAEAGLLayer only
     pub(super) drawable_properties: id,
     /// For CAEAGLLayer only (internal state for compositor)
@@ -45,11 +180,177 @@ pub(super) struct CALayerHostObject {
     /// Internal, only exposed when calling `drawLayer:inContext:`
     pub(super) cg_context: Option<CGContextRef>,
     /// Internal state for compositor
-    pub(super) gles_texture: Option<crate::gles::gles11_raw::types::GLuint>,
+    pub(super) gles_texture: Option<CompositorTexture>,
+    /// Internal state for compositor (CAEAGLLayer only), the texture drawn
+    /// before [Self::gles_texture]
+    pub(super) gles_back_texture: Option<CompositorTexture>,
     /// Internal state for compositor
     pub(super) gles_texture_is_up_to_date: bool,
+    /// Animations added with `addAnimation:forKey:`, in the order they were
+    /// added.
+    pub(super) animations: Vec<ActiveAnimation>,
+    /// Internal state for compositor, see [super::ca_animation]
+    pub(super) presentation: PresentationValues,
+    /// For CAShapeLayer only
+    pub(super) shape_properties: Option<CAShapeLayerProperties>,
+    /// For CAGradientLayer only
+    pub(super) gradient_properties: Option<CAGradientLayerProperties>,
 }
 impl HostObject for CALayerHostObject {}
+impl Default for CALayerHostObject {
+    fn default() -> Self {
+        CALayerHostObject {
+            delegate: nil,
+            sublayers: Vec::new(),
+            superlayer: nil,
+            bounds: CGRect {
+                origin: CGPoint { x: 0.0, y: 0.0 },
+                size: CGSize {
+                    width: 0.0,
+                    height: 0.0,
+                },
+            },
+            position: CGPoint { x: 0.0, y: 0.0 },
+            anchor_point: CGPoint { x: 0.5, y: 0.5 },
+            z_position: 0.0,
+            transform: CATransform3DIdentity,
+            hidden: false,
+            opaque: false,
+            opacity: 1.0,
+            masks_to_bounds: false,
+            corner_radius: 0.0,
+            background_color: nil, // transparency
+            border_width: 0.0,
+            border_color: nil,
+            shadow_color: nil,
+            shadow_opacity: 0.0,
+            shadow_offset: CGSize {
+                width: 0.0,
+                height: -3.0,
+            },
+            shadow_radius: 3.0,
+            needs_display: true,
+            contents: nil,
+            contents_gravity: ContentsGravity::Resize,
+            contents_scale: 1.0,
+            drawable_properties: nil,
+            presented_pixels: None,
+            cg_context: None,
+            gles_texture: None,
+            gles_back_texture: None,
+            gles_texture_is_up_to_date: false,
+            animations: Vec::new(),
+            presentation: PresentationValues::default(),
+            shape_properties: None,
+            gradient_properties: None,
+        }
+    }
+}
+
+/// Get the sublayers of a layer in back-to-front drawing order. This is the
+/// order of the sublayers list, except that layers are sorted by zPosition.
+pub(super) fn sublayers_in_drawing_order(objc: &ObjC, layer: id) -> Vec<id> {
+    let mut sublayers = objc.borrow::<CALayerHostObject>(layer).sublayers.clone();
+    // This is a stable sort, so layers with the same zPosition stay in the
+    // order they have in the list.
+    sublayers.sort_by(|&a, &b| {
+        let a = objc.borrow::<CALayerHostObject>(a).z_position;
+        let b = objc.borrow::<CALayerHostObject>(b).z_position;
+        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    sublayers
+}
+
+/// Get the transform from a layer's co-ordinate space to its superlayer's,
+/// given the layer's geometry.
+fn transform_to_superlayer(
+    bounds: CGRect,
+    position: CGPoint,
+    anchor_point: CGPoint,
+    transform: CGAffineTransform,
+) -> CGAffineTransform {
+    // The layer's transform is applied around the anchor point, which is then
+    // placed at the position.
+    let anchor_x = bounds.origin.x + bounds.size.width * anchor_point.x;
+    let anchor_y = bounds.origin.y + bounds.size.height * anchor_point.y;
+    CGAffineTransform::translation(-anchor_x, -anchor_y)
+        .concat(transform)
+        .concat(CGAffineTransform::translation(position.x, position.y))
+}
+
+/// Get a layer and its ancestors, each with the transform from the layer's
+/// co-ordinate space to that ancestor's.
+fn ancestors_with_transforms(env: &mut Environment, layer: id) -> Vec<(id, CGAffineTransform)> {
+    let mut ancestors = vec![(layer, CGAffineTransformIdentity)];
+    let mut current = layer;
+    let mut transform = CGAffineTransformIdentity;
+    loop {
+        let &CALayerHostObject {
+            superlayer,
+            bounds,
+            position,
+            anchor_point,
+            transform: layer_transform,
+            ..
+        } = env.objc.borrow(current);
+        if superlayer == nil {
+            return ancestors;
+        }
+        let layer_transform = CATransform3DGetAffineTransform(env, layer_transform);
+        transform = transform.concat(transform_to_superlayer(
+            bounds,
+            position,
+            anchor_point,
+            layer_transform,
+        ));
+        ancestors.push((superlayer, transform));
+        current = superlayer;
+    }
+}
+
+/// Check whether it is worth calling `drawLayer:inContext:` on a layer's
+/// delegate (which may be nil).
+fn delegate_can_draw(env: &mut Environment, delegate: id) -> bool {
+    if delegate == nil {
+        return false;
+    }
+    let delegate_class = ObjC::read_isa(delegate, &env.mem);
+    if !env
+        .objc
+        .class_has_method_named(delegate_class, "drawLayer:inContext:")
+    {
+        return false;
+    }
+
+    // UIView has a method called drawRect: that subclasses override if they
+    // need custom drawing. touchHLE's UIView (a CALayerDelegate) provides
+    // an implementation of drawLayer:inContext: that calls drawRect:.
+    // This maintains a clean separation of UIView and CALayer, but it also
+    // means that CALayer has no idea which views actually need custom drawing,
+    // because they all have the inherited drawLayer:inContext: method.
+    // To avoid wasting space and time on unnecessary bitmaps, let's pierce the
+    // veil.
+    // (TODO: somehow do this optimization in UIView rather than CALayer.
+    // Apparently Apple do it that way: https://stackoverflow.com/q/4979192)
+    let ui_view_class = env.objc.get_known_class("UIView", &mut env.mem);
+    if env.objc.class_is_subclass_of(delegate_class, ui_view_class) {
+        let draw_rect_sel = env.objc.lookup_selector("drawRect:").unwrap();
+        let draw_layer_sel = env.objc.lookup_selector("drawLayer:inContext:").unwrap();
+        if !env.objc.class_overrides_method_of_superclass(
+            delegate_class,
+            draw_rect_sel,
+            ui_view_class,
+        ) && !env.objc.class_overrides_method_of_superclass(
+            delegate_class,
+            draw_layer_sel,
+            ui_view_class,
+        ) {
+            log_dbg!("Skipped render! {:?} does not override UIView's drawRect: or drawLayer:inContext: methods.", delegate_class);
+            return false;
+        }
+    }
+    true
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -58,28 +359,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation CALayer: NSObject
 
 + (id)alloc {
-    let host_object = Box::new(CALayerHostObject {
-        delegate: nil,
-        sublayers: Vec::new(),
-        superlayer: nil,
-        bounds: CGRect {
-            origin: CGPoint { x: 0.0, y: 0.0 },
-            size: CGSize { width: 0.0, height: 0.0 }
-        },
-        position: CGPoint { x: 0.0, y: 0.0 },
-        anchor_point: CGPoint { x: 0.5, y: 0.5 },
-        hidden: false,
-        opaque: false,
-        opacity: 1.0,
-        background_color: nil, // transparency
-        needs_display: true,
-        contents: nil,
-        drawable_properties: nil,
-        presented_pixels: None,
-        cg_context: None,
-        gles_texture: None,
-        gles_texture_is_up_to_date: false,
-    });
+    let host_object = Box::<CALayerHostObject>::default();
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
@@ -94,11 +374,17 @@ pub const CLASSES: ClassExports = objc_classes! {
         contents,
         superlayer,
         background_color,
+        border_color,
+        shadow_color,
         cg_context,
+        gles_texture,
+        gles_back_texture,
         ref mut sublayers,
+        ref mut animations,
         ..
     } = env.objc.borrow_mut(this);
     let sublayers = std::mem::take(sublayers);
+    let animations = std::mem::take(animations);
 
     if drawable_properties != nil {
         release(env, drawable_properties);
@@ -112,16 +398,31 @@ pub const CLASSES: ClassExports = objc_classes! {
         CFRelease(env, background_color);
     }
 
+    if border_color != nil {
+        CFRelease(env, border_color);
+    }
+
+    if shadow_color != nil {
+        CFRelease(env, shadow_color);
+    }
+
     if let Some(cg_context) = cg_context {
         CGContextRelease(env, cg_context);
     }
 
+    if gles_texture.is_some() || gles_back_texture.is_some() {
+        let textures = [gles_texture, gles_back_texture];
+        super::composition::forget_layer_textures(env, this, textures);
+    }
+
     assert!(superlayer == nil);
     for sublayer in sublayers {
         env.objc.borrow_mut::<CALayerHostObject>(sublayer).superlayer = nil;
         release(env, sublayer);
     }
 
+    release_animations(env, animations);
+
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -146,6 +447,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         env.objc.borrow_mut::<CALayerHostObject>(layer).superlayer = this;
         env.objc.borrow_mut::<CALayerHostObject>(this).sublayers.push(layer);
     }
+    set_needs_recomposite(env);
 }
 
 - (())removeFromSuperlayer {
@@ -160,25 +462,62 @@ pub const CLASSES: ClassExports = objc_classes! {
     let sublayer = sublayers.remove(idx);
     assert!(sublayer == this);
     release(env, this);
+    set_needs_recomposite(env);
 }
 
 - (CGRect)bounds {
     env.objc.borrow::<CALayerHostObject>(this).bounds
 }
 - (())setBounds:(CGRect)bounds {
-    env.objc.borrow_mut::<CALayerHostObject>(this).bounds = bounds;
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let old_value = host_object.presentation_value(AnimatedProperty::Bounds);
+    host_object.bounds = bounds;
+    run_action_for_change(env, this, AnimatedProperty::Bounds, old_value);
+    set_needs_recomposite(env);
 }
 - (CGPoint)position {
     env.objc.borrow::<CALayerHostObject>(this).position
 }
 - (())setPosition:(CGPoint)position {
-    env.objc.borrow_mut::<CALayerHostObject>(this).position = position;
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let old_value = host_object.presentation_value(AnimatedProperty::Position);
+    host_object.position = position;
+    run_action_for_change(env, this, AnimatedProperty::Position, old_value);
+    set_needs_recomposite(env);
 }
 - (CGPoint)anchorPoint {
     env.objc.borrow::<CALayerHostObject>(this).anchor_point
 }
 - (())setAnchorPoint:(CGPoint)anchor_point {
     env.objc.borrow_mut::<CALayerHostObject>(this).anchor_point = anchor_point;
+    set_needs_recomposite(env);
+}
+
+- (CGFloat)zPosition {
+    env.objc.borrow::<CALayerHostObject>(this).z_position
+}
+- (())setZPosition:(CGFloat)z_position {
+    env.objc.borrow_mut::<CALayerHostObject>(this).z_position = z_position;
+    set_needs_recomposite(env);
+}
+
+- (CATransform3D)transform {
+    env.objc.borrow::<CALayerHostObject>(this).transform
+}
+- (())setTransform:(CATransform3D)transform {
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let old_value = host_object.presentation_value(AnimatedProperty::Transform);
+    host_object.transform = transform;
+    run_action_for_change(env, this, AnimatedProperty::Transform, old_value);
+    set_needs_recomposite(env);
+}
+- (CGAffineTransform)affineTransform {
+    let transform = env.objc.borrow::<CALayerHostObject>(this).transform;
+    CATransform3DGetAffineTransform(env, transform)
+}
+- (())setAffineTransform:(CGAffineTransform)affine_transform {
+    let transform = CATransform3DMakeAffineTransform(env, affine_transform);
+    () = msg![env; this setTransform:transform];
 }
 
 - (CGRect)frame {
@@ -197,20 +536,18 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 - (())setFrame:(CGRect)frame {
-    let CALayerHostObject {
-        bounds,
-        position,
-        anchor_point,
-        ..
-    } = env.objc.borrow_mut(this);
-    *position = CGPoint {
+    let anchor_point = env.objc.borrow::<CALayerHostObject>(this).anchor_point;
+    let position = CGPoint {
         x: frame.origin.x + frame.size.width * anchor_point.x,
         y: frame.origin.y + frame.size.height * anchor_point.y,
     };
-    *bounds = CGRect {
+    let bounds = CGRect {
         origin: CGPoint { x: 0.0, y: 0.0 },
         size: frame.size,
     };
+    // The setters take care of implicit animations.
+    () = msg![env; this setPosition:position];
+    () = msg![env; this setBounds:bounds];
 }
 
 - (bool)isHidden {
@@ -218,6 +555,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 - (())setHidden:(bool)hidden {
     env.objc.borrow_mut::<CALayerHostObject>(this).hidden = hidden;
+    set_needs_recomposite(env);
 }
 
 - (bool)isOpaque {
@@ -225,20 +563,42 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 - (())setOpaque:(bool)opaque {
     env.objc.borrow_mut::<CALayerHostObject>(this).opaque = opaque;
+    set_needs_recomposite(env);
 }
 
 - (f32)opacity {
     env.objc.borrow::<CALayerHostObject>(this).opacity
 }
 - (())setOpacity:(f32)opacity {
-    env.objc.borrow_mut::<CALayerHostObject>(this).opacity = opacity;
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let old_value = host_object.presentation_value(AnimatedProperty::Opacity);
+    host_object.opacity = opacity;
+    run_action_for_change(env, this, AnimatedProperty::Opacity, old_value);
+    set_needs_recomposite(env);
 }
 
-// See remarks in ui_view.rs about the type of this property
+- (bool)masksToBounds {
+    env.objc.borrow::<CALayerHostObject>(this).masks_to_bounds
+}
+- (())setMasksToBounds:(bool)masks_to_bounds {
+    env.objc.borrow_mut::<CALayerHostObject>(this).masks_to_bounds = masks_to_bounds;
+    set_needs_recomposite(env);
+}
+
+- (CGFloat)cornerRadius {
+    env.objc.borrow::<CALayerHostObject>(this).corner_radius
+}
+- (())setCornerRadius:(CGFloat)corner_radius {
+    env.objc.borrow_mut::<CALayerHostObject>(this).corner_radius = corner_radius;
+    set_needs_recomposite(env);
+}
+
+// The compositor also accepts a UIColor* for the color properties, since some
+// apps seem to get away with passing one.
 - (id)backgroundColor {
     env.objc.borrow::<CALayerHostObject>(this).background_color
 }
-- (())setBackgroundColor:(id)new_color {
+- (())setBackgroundColor:(id)new_color { // CGColorRef
     let host_obj = env.objc.borrow_mut::<CALayerHostObject>(this);
     let old_color = std::mem::replace(&mut host_obj.background_color, new_color);
     if new_color != nil {
@@ -247,68 +607,121 @@ pub const CLASSES: ClassExports = objc_classes! {
     if old_color != nil {
         CFRelease(env, old_color); // CFRelease doesn't like nil
     }
+    set_needs_recomposite(env);
+}
+
+- (CGFloat)borderWidth {
+    env.objc.borrow::<CALayerHostObject>(this).border_width
+}
+- (())setBorderWidth:(CGFloat)border_width {
+    env.objc.borrow_mut::<CALayerHostObject>(this).border_width = border_width;
+    set_needs_recomposite(env);
+}
+
+- (id)borderColor {
+    // FIXME: This should return opaque black when no color has been set.
+    env.objc.borrow::<CALayerHostObject>(this).border_color
+}
+- (())setBorderColor:(id)new_color { // CGColorRef
+    let host_obj = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let old_color = std::mem::replace(&mut host_obj.border_color, new_color);
+    if new_color != nil {
+        CFRetain(env, new_color); // CFRetain doesn't like nil
+    }
+    if old_color != nil {
+        CFRelease(env, old_color); // CFRelease doesn't like nil
+    }
+    set_needs_recomposite(env);
+}
+
+- (id)shadowColor {
+    // FIXME: This should return opaque black when no color has been set.
+    env.objc.borrow::<CALayerHostObject>(this).shadow_color
+}
+- (())setShadowColor:(id)new_color { // CGColorRef
+    let host_obj = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let old_color = std::mem::replace(&mut host_obj.shadow_color, new_color);
+    if new_color != nil {
+        CFRetain(env, new_color); // CFRetain doesn't like nil
+    }
+    if old_color != nil {
+        CFRelease(env, old_color); // CFRelease doesn't like nil
+    }
+    set_needs_recomposite(env);
+}
+
+- (f32)shadowOpacity {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_opacity
+}
+- (())setShadowOpacity:(f32)shadow_opacity {
+    env.objc.borrow_mut::<CALayerHostObject>(this).shadow_opacity = shadow_opacity;
+    set_needs_recomposite(env);
 }
 
+- (CGSize)shadowOffset {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_offset
+}
+- (())setShadowOffset:(CGSize)shadow_offset {
+    env.objc.borrow_mut::<CALayerHostObject>(this).shadow_offset = shadow_offset;
+    set_needs_recomposite(env);
+}
+
+- (CGFloat)shadowRadius {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_radius
+}
+- (())setShadowRadius:(CGFloat)shadow_radius {
+    env.objc.borrow_mut::<CALayerHostObject>(this).shadow_radius = shadow_radius;
+    set_needs_recomposite(env);
+}
+
+// TODO: shadowPath
+
 - (bool)needsDisplay {
     env.objc.borrow::<CALayerHostObject>(this).needs_display
 }
 - (())setNeedsDisplay {
     env.objc.borrow_mut::<CALayerHostObject>(this).needs_display = true;
 }
-// TODO: support setNeedsDisplayInRect:
+- (())setNeedsDisplayInRect:(CGRect)_rect {
+    // The whole layer is redrawn. This is allowed, because drawing code has to
+    // be able to redraw any part of the layer anyway.
+    env.objc.borrow_mut::<CALayerHostObject>(this).needs_display = true;
+}
 - (())displayIfNeeded {
-    let &mut CALayerHostObject {
-        ref mut needs_display,
-        delegate,
-        ..
-    } = env.objc.borrow_mut(this);
+    let needs_display = &mut env.objc.borrow_mut::<CALayerHostObject>(this).needs_display;
     if !std::mem::take(needs_display) {
         return;
     }
+    () = msg![env; this display];
+}
 
-    if delegate == nil {
-        return;
-    }
-
-    let delegate_class = ObjC::read_isa(delegate, &env.mem);
+- (())display {
+    let delegate = env.objc.borrow::<CALayerHostObject>(this).delegate;
 
     // According to the Core Animation Programming Guide, a layer delegate must
     // provide either displayLayer: or drawLayer:inContext:, and the former is
     // called if both are defined.
-
-    if env.objc.class_has_method_named(delegate_class, "displayLayer:") {
-        () = msg![env; delegate displayLayer:this];
-        return;
-    }
-
-    // UIView has a method called drawRect: that subclasses override if they
-    // need custom drawing. touchHLE's UIView (a CALayerDelegate) provides
-    // an implementation of drawLayer:inContext: that calls drawRect:.
-    // This maintains a clean separation of UIView and CALayer, but it also
-    // means that CALayer has no idea which views actually need custom drawing,
-    // because they all have the inherited drawLayer:inContext: method.
-    // To avoid wasting space and time on unnecessary bitmaps, let's pierce the
-    // veil.
-    // (TODO: somehow do this optimization in UIView rather than CALayer.
-    // Apparently Apple do it that way: https://stackoverflow.com/q/4979192)
-    let ui_view_class = env.objc.get_known_class("UIView", &mut env.mem);
-    if env.objc.class_is_subclass_of(delegate_class, ui_view_class) {
-        let draw_rect_sel = env.objc.lookup_selector("drawRect:").unwrap();
-        let draw_layer_sel = env.objc.lookup_selector("drawLayer:inContext:").unwrap();
-        if !env.objc.class_overrides_method_of_superclass(
-            delegate_class,
-            draw_rect_sel,
-            ui_view_class
-        ) && !env.objc.class_overrides_method_of_superclass(
-            delegate_class,
-            draw_layer_sel,
-            ui_view_class
-        ) {
-            log_dbg!("Skipped render! {:?} does not override UIView's drawRect: or drawLayer:inContext: methods.", delegate_class);
+    if delegate != nil {
+        let delegate_class = ObjC::read_isa(delegate, &env.mem);
+        if env.objc.class_has_method_named(delegate_class, "displayLayer:") {
+            () = msg![env; delegate displayLayer:this];
             return;
         }
     }
 
+    // Layer subclasses can draw their contents by overriding drawInContext:.
+    // Otherwise, the default implementation asks the delegate to draw.
+    let this_class = ObjC::read_isa(this, &env.mem);
+    let ca_layer_class = env.objc.get_known_class("CALayer", &mut env.mem);
+    let draw_in_context_sel = env.objc.lookup_selector("drawInContext:").unwrap();
+    if !env.objc.class_overrides_method_of_superclass(
+        this_class,
+        draw_in_context_sel,
+        ca_layer_class
+    ) && !delegate_can_draw(env, delegate) {
+        return;
+    }
+
     let &mut CALayerHostObject {
         cg_context,
         ref mut gles_texture_is_up_to_date,
@@ -317,18 +730,23 @@ pub const CLASSES: ClassExports = objc_classes! {
     } = env.objc.borrow_mut(this);
 
     *gles_texture_is_up_to_date = false;
+    set_needs_recomposite(env);
 
+    // Devices emulated by touchHLE don't have Retina displays, so there is one
+    // pixel per point.
     // TODO: more correctly handle non-integer sizes?
     let int_width = size.width.round() as GuestUSize;
     let int_height = size.height.round() as GuestUSize;
 
+    // The context is kept between redraws, so that drawing code which doesn't
+    // clear it can draw on top of what was drawn before.
     let need_new_context = if let Some(cg_context) = cg_context {
         if CGBitmapContextGetWidth(env, cg_context) == int_width &&
            CGBitmapContextGetHeight(env, cg_context) == int_height {
-            true
+            false
         } else {
             CGContextRelease(env, cg_context);
-            false
+            true
         }
     } else {
         true
@@ -356,10 +774,17 @@ pub const CLASSES: ClassExports = objc_classes! {
     };
 
     CGContextTranslateCTM(env, cg_context, -origin.x, -origin.y);
-    () = msg![env; delegate drawLayer:this inContext:cg_context];
+    () = msg![env; this drawInContext:cg_context];
     CGContextTranslateCTM(env, cg_context, origin.x, origin.y);
 }
 
+- (())drawInContext:(CGContextRef)context {
+    let delegate = env.objc.borrow::<CALayerHostObject>(this).delegate;
+    if delegate_can_draw(env, delegate) {
+        () = msg![env; delegate drawLayer:this inContext:context];
+    }
+}
+
 // CGImageRef*
 - (id)contents {
     env.objc.borrow::<CALayerHostObject>(this).contents
@@ -370,6 +795,29 @@ pub const CLASSES: ClassExports = objc_classes! {
     let old_contents = std::mem::replace(&mut host_obj.contents, new_contents);
     retain(env, new_contents);
     release(env, old_contents);
+    set_needs_recomposite(env);
+}
+
+- (id)contentsGravity {
+    let gravity = env.objc.borrow::<CALayerHostObject>(this).contents_gravity;
+    ns_string::get_static_str(env, gravity.name())
+}
+- (())setContentsGravity:(id)gravity { // NSString*
+    let name = ns_string::to_rust_string(env, gravity);
+    let Some(gravity) = ContentsGravity::from_name(&name) else {
+        log!("Warning: ignoring unknown contentsGravity {:?}", name);
+        return;
+    };
+    env.objc.borrow_mut::<CALayerHostObject>(this).contents_gravity = gravity;
+    set_needs_recomposite(env);
+}
+
+- (CGFloat)contentsScale {
+    env.objc.borrow::<CALayerHostObject>(this).contents_scale
+}
+- (())setContentsScale:(CGFloat)scale {
+    env.objc.borrow_mut::<CALayerHostObject>(this).contents_scale = scale;
+    set_needs_recomposite(env);
 }
 
 - (bool)containsPoint:(CGPoint)point {
@@ -388,72 +836,30 @@ pub const CLASSES: ClassExports = objc_classes! {
         return point;
     }
 
-    // The two layers must have a common ancestor. Let's try to find it.
-    // The idea is to walk up each layer's superlayer chain, one at a time,
-    // alternating between layers until we find a match.
-
-    // Maps of layer pointers to origins in that layer's co-ordinate space.
-    let mut this_map = HashMap::from([(this, CGPoint { x: 0.0, y: 0.0 })]);
-    let mut other_map = HashMap::from([(other, CGPoint { x: 0.0, y: 0.0 })]);
-    // Current iteration state.
-    let mut this_superlayer = this;
-    let mut this_origin = CGPoint { x: 0.0, y: 0.0 };
-    let mut other_superlayer = other;
-    let mut other_origin = CGPoint { x: 0.0, y: 0.0 };
-    let (common_ancestor, this_origin, other_origin) = loop {
-        if this_superlayer != nil {
-            let next: id = msg![env; this_superlayer superlayer];
-            if next == nil {
-                this_superlayer = nil;
-            } else {
-                let bounds: CGRect = msg![env; this_superlayer bounds];
-                let frame: CGRect = msg![env; this_superlayer frame];
-                let next_origin = CGPoint {
-                    x: this_origin.x + frame.origin.x - bounds.origin.x,
-                    y: this_origin.y + frame.origin.y - bounds.origin.y,
-                };
-                if let Some(&other_origin) = other_map.get(&next) {
-                    break (next, next_origin, other_origin);
-                }
-                this_map.insert(next, next_origin);
-                this_superlayer = next;
-                this_origin = next_origin;
-            }
-        }
-
-        if other_superlayer != nil {
-            let next: id = msg![env; other_superlayer superlayer];
-            if next == nil {
-                other_superlayer = nil;
-            } else {
-                let bounds: CGRect = msg![env; other_superlayer bounds];
-                let frame: CGRect = msg![env; other_superlayer frame];
-                let next_origin = CGPoint {
-                    x: other_origin.x + frame.origin.x - bounds.origin.x,
-                    y: other_origin.y + frame.origin.y - bounds.origin.y,
-                };
-                if let Some(&this_origin) = this_map.get(&next) {
-                    break (next, this_origin, next_origin);
-                }
-                other_map.insert(next, next_origin);
-                other_superlayer = next;
-                other_origin = next_origin;
-            }
-        }
-
-        assert!(
-            this_superlayer != nil || other_superlayer != nil,
-            "Layers have no common ancestor!"
-        );
-    };
+    // The two layers must have a common ancestor. The point is converted up
+    // to that ancestor's co-ordinate space, then back down to this layer's.
+    // This takes the layers' transforms into account.
+    let this_ancestors = ancestors_with_transforms(env, this);
+    let other_ancestors = ancestors_with_transforms(env, other);
+    let (common_ancestor, this_to_ancestor, other_to_ancestor) = this_ancestors
+        .iter()
+        .find_map(|&(ancestor, this_to_ancestor)| {
+            other_ancestors
+                .iter()
+                .find(|&&(other_ancestor, _)| other_ancestor == ancestor)
+                .map(|&(_, other_to_ancestor)| (ancestor, this_to_ancestor, other_to_ancestor))
+        })
+        .expect("Layers have no common ancestor!");
 
     log_dbg!("{:?} and {:?}'s common ancestor: {:?}", this, other, common_ancestor);
-    log_dbg!("{:?}'s origin in common ancestor: {:?}", this, this_origin);
-    log_dbg!("{:?}'s origin in common ancestor: {:?}", other, other_origin);
-    let res = CGPoint {
-        x: point.x + other_origin.x - this_origin.x,
-        y: point.y + other_origin.y - this_origin.y,
+    let Some(ancestor_to_this) = this_to_ancestor.invert() else {
+        // The layer has been scaled to nothing, so no point is really in it.
+        log_dbg!("Can't convert {:?} to {:?}, transform isn't invertible", point, this);
+        return point;
     };
+    let res = other_to_ancestor
+        .concat(ancestor_to_this)
+        .apply_to_point(point);
     log_dbg!("Converted {:?} from {:?} to {:?}: {:?}", point, other, this, res);
     res
 }
@@ -465,8 +871,49 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; other convertPoint:point fromLayer:this]
 }
 
+- (())addAnimation:(id)animation // CAAnimation*
+            forKey:(id)key { // NSString*
+    add_animation(env, this, animation, key);
+}
+- (id)animationForKey:(id)key { // NSString*
+    animation_for_key(env, this, key)
+}
+- (())removeAnimationForKey:(id)key { // NSString*
+    remove_animation(env, this, Some(key));
+}
+- (())removeAllAnimations {
+    remove_animation(env, this, None);
+}
+
 // TODO: more
 
 @end
 
 };
+
+#[cfg(test)]
+#[test]
+fn test_transform_to_superlayer() {
+    let bounds = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: CGSize {
+            width: 100.0,
+            height: 50.0,
+        },
+    };
+    let position = CGPoint { x: 200.0, y: 300.0 };
+    let anchor_point = CGPoint { x: 0.5, y: 0.5 };
+
+    // Without a transform, this is just an offset by the frame's origin.
+    let identity =
+        transform_to_superlayer(bounds, position, anchor_point, CGAffineTransformIdentity);
+    let point = identity.apply_to_point(CGPoint { x: 10.0, y: 20.0 });
+    assert_eq!(point, CGPoint { x: 160.0, y: 295.0 });
+
+    // A rotation by 90° (clockwise in UIKit) is around the anchor point, so
+    // the top-left corner ends up at the top-right.
+    let rotation = CGAffineTransform::rotation(std::f32::consts::FRAC_PI_2);
+    let rotated = transform_to_superlayer(bounds, position, anchor_point, rotation);
+    let point = rotated.apply_to_point(CGPoint { x: 0.0, y: 0.0 });
+    assert!((point.x - 225.0).abs() < 0.001 && (point.y - 250.0).abs() < 0.001);
+}
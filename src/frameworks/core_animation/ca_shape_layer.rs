@@ -0,0 +1,412 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CAShapeLayer`.
+//!
+//! The path is turned into triangles by the compositor each time it is drawn.
+//! Filling ignores the fill rule: each subpath is filled separately, so holes
+//! aren't supported, and subpaths are assumed not to intersect themselves.
+//! Strokes have no joins or caps.
+
+use super::ca_layer::CALayerHostObject;
+use super::composition::set_needs_recomposite;
+use crate::frameworks::core_foundation::{CFRelease, CFRetain};
+use crate::frameworks::core_graphics::cg_color;
+use crate::frameworks::core_graphics::cg_path::{CGPathRelease, CGPathRetain, FlattenedSubpath};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint};
+use crate::objc::{id, msg_super, nil, objc_classes, ClassExports};
+use crate::Environment;
+
+/// Properties only used by `CAShapeLayer`.
+#[derive(Copy, Clone)]
+pub(super) struct CAShapeLayerProperties {
+    /// `CGPathRef`, possibly nil
+    pub(super) path: id,
+    /// `CGColorRef`, possibly nil
+    pub(super) fill_color: id,
+    /// `CGColorRef`, possibly nil
+    pub(super) stroke_color: id,
+    pub(super) line_width: CGFloat,
+    pub(super) stroke_start: CGFloat,
+    pub(super) stroke_end: CGFloat,
+}
+
+fn properties(env: &Environment, this: id) -> &CAShapeLayerProperties {
+    env.objc
+        .borrow::<CALayerHostObject>(this)
+        .shape_properties
+        .as_ref()
+        .unwrap()
+}
+fn properties_mut(env: &mut Environment, this: id) -> &mut CAShapeLayerProperties {
+    env.objc
+        .borrow_mut::<CALayerHostObject>(this)
+        .shape_properties
+        .as_mut()
+        .unwrap()
+}
+
+/// Replace a color property, retaining the new color and releasing the old.
+fn set_color(
+    env: &mut Environment,
+    this: id,
+    new_color: id,
+    field: fn(&mut CAShapeLayerProperties) -> &mut id,
+) {
+    if new_color != nil {
+        CFRetain(env, new_color); // CFRetain doesn't like nil
+    }
+    let old_color = std::mem::replace(field(properties_mut(env, this)), new_color);
+    if old_color != nil {
+        CFRelease(env, old_color); // CFRelease doesn't like nil
+    }
+    set_needs_recomposite(env);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CAShapeLayer: CALayer
+
++ (id)alloc {
+    // The default fill color is opaque black.
+    let fill_color = cg_color::new_rgba(env, (0.0, 0.0, 0.0, 1.0));
+    let host_object = Box::new(CALayerHostObject {
+        shape_properties: Some(CAShapeLayerProperties {
+            path: nil,
+            fill_color,
+            stroke_color: nil,
+            line_width: 1.0,
+            stroke_start: 0.0,
+            stroke_end: 1.0,
+        }),
+        ..Default::default()
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &CAShapeLayerProperties {
+        path,
+        fill_color,
+        stroke_color,
+        ..
+    } = properties(env, this);
+    CGPathRelease(env, path);
+    if fill_color != nil {
+        CFRelease(env, fill_color);
+    }
+    if stroke_color != nil {
+        CFRelease(env, stroke_color);
+    }
+    msg_super![env; this dealloc]
+}
+
+- (id)path {
+    properties(env, this).path
+}
+- (())setPath:(id)new_path { // CGPathRef
+    // TODO: Apple's implementation copies the path, so later changes to a
+    // mutable path don't affect the layer.
+    CGPathRetain(env, new_path);
+    let old_path = std::mem::replace(&mut properties_mut(env, this).path, new_path);
+    CGPathRelease(env, old_path);
+    set_needs_recomposite(env);
+}
+
+- (id)fillColor {
+    properties(env, this).fill_color
+}
+- (())setFillColor:(id)new_color { // CGColorRef
+    set_color(env, this, new_color, |props| &mut props.fill_color);
+}
+
+- (id)strokeColor {
+    properties(env, this).stroke_color
+}
+- (())setStrokeColor:(id)new_color { // CGColorRef
+    set_color(env, this, new_color, |props| &mut props.stroke_color);
+}
+
+- (CGFloat)lineWidth {
+    properties(env, this).line_width
+}
+- (())setLineWidth:(CGFloat)line_width {
+    properties_mut(env, this).line_width = line_width;
+    set_needs_recomposite(env);
+}
+
+- (CGFloat)strokeStart {
+    properties(env, this).stroke_start
+}
+- (())setStrokeStart:(CGFloat)stroke_start {
+    properties_mut(env, this).stroke_start = stroke_start;
+    set_needs_recomposite(env);
+}
+
+- (CGFloat)strokeEnd {
+    properties(env, this).stroke_end
+}
+- (())setStrokeEnd:(CGFloat)stroke_end {
+    properties_mut(env, this).stroke_end = stroke_end;
+    set_needs_recomposite(env);
+}
+
+// TODO: fillRule, lineCap, lineJoin, miterLimit, line dashes
+
+@end
+
+};
+
+fn signed_area(points: &[CGPoint]) -> CGFloat {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area / 2.0
+}
+
+/// Turn a simple polygon (no self-intersections) into triangles by ear
+/// clipping. The result is a list of x and y co-ordinates, three points per
+/// triangle.
+fn triangulate_polygon(points: &[CGPoint], triangles: &mut Vec<f32>) {
+    fn cross(o: CGPoint, a: CGPoint, b: CGPoint) -> CGFloat {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    }
+
+    // Repeated points (including the end of an explicitly closed path) would
+    // produce degenerate ears.
+    let mut remaining: Vec<CGPoint> = Vec::with_capacity(points.len());
+    for &point in points {
+        if remaining.last() != Some(&point) {
+            remaining.push(point);
+        }
+    }
+    while remaining.len() > 1 && remaining.first() == remaining.last() {
+        remaining.pop();
+    }
+    if remaining.len() < 3 {
+        return;
+    }
+    // Make the winding counter-clockwise (if y points up), so that convex
+    // vertices have a positive cross product.
+    if signed_area(&remaining) < 0.0 {
+        remaining.reverse();
+    }
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let prev = remaining[(i + n - 1) % n];
+            let curr = remaining[i];
+            let next = remaining[(i + 1) % n];
+            if cross(prev, curr, next) <= 0.0 {
+                return false;
+            }
+            // No other vertex may be inside the ear.
+            !remaining.iter().enumerate().any(|(j, &p)| {
+                j != i
+                    && j != (i + n - 1) % n
+                    && j != (i + 1) % n
+                    && cross(prev, curr, p) >= 0.0
+                    && cross(curr, next, p) >= 0.0
+                    && cross(next, prev, p) >= 0.0
+            })
+        });
+        // If there's no ear, the polygon isn't simple. Give up on this and do
+        // something that's at least the right size.
+        let Some(ear) = ear else {
+            break;
+        };
+        let prev = remaining[(ear + n - 1) % n];
+        let curr = remaining[ear];
+        let next = remaining[(ear + 1) % n];
+        triangles.extend_from_slice(&[prev.x, prev.y, curr.x, curr.y, next.x, next.y]);
+        remaining.remove(ear);
+    }
+    // Either a single triangle remains, or a fan is the fallback.
+    for i in 1..(remaining.len() - 1) {
+        let (a, b, c) = (remaining[0], remaining[i], remaining[i + 1]);
+        triangles.extend_from_slice(&[a.x, a.y, b.x, b.y, c.x, c.y]);
+    }
+}
+
+/// Get the triangles for filling a path. Open subpaths are implicitly closed.
+pub(super) fn fill_triangles(subpaths: &[FlattenedSubpath]) -> Vec<f32> {
+    let mut triangles = Vec::new();
+    for subpath in subpaths {
+        triangulate_polygon(&subpath.points, &mut triangles);
+    }
+    triangles
+}
+
+/// Get the part of the path between `stroke_start` and `stroke_end`, which are
+/// fractions of its total length, as a list of polylines.
+fn trim_polylines(
+    subpaths: &[FlattenedSubpath],
+    stroke_start: CGFloat,
+    stroke_end: CGFloat,
+) -> Vec<Vec<CGPoint>> {
+    fn distance(a: CGPoint, b: CGPoint) -> CGFloat {
+        ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt()
+    }
+
+    let polylines: Vec<Vec<CGPoint>> = subpaths
+        .iter()
+        .map(|subpath| {
+            let mut points = subpath.points.clone();
+            if subpath.closed {
+                points.push(points[0]);
+            }
+            points
+        })
+        .collect();
+
+    let stroke_start = stroke_start.clamp(0.0, 1.0);
+    let stroke_end = stroke_end.clamp(0.0, 1.0);
+    if stroke_start == 0.0 && stroke_end == 1.0 {
+        return polylines;
+    }
+    if stroke_start >= stroke_end {
+        return Vec::new();
+    }
+
+    let total_length: CGFloat = polylines
+        .iter()
+        .flat_map(|points| points.windows(2))
+        .map(|pair| distance(pair[0], pair[1]))
+        .sum();
+    let start = stroke_start * total_length;
+    let end = stroke_end * total_length;
+
+    let mut trimmed = Vec::new();
+    let mut travelled = 0.0;
+    for points in polylines {
+        let mut current = Vec::new();
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let length = distance(a, b);
+            let (seg_start, seg_end) = (travelled, travelled + length);
+            travelled = seg_end;
+            if seg_end <= start || seg_start >= end || length == 0.0 {
+                continue;
+            }
+            let point_at = |d: CGFloat| {
+                let t = ((d - seg_start) / length).clamp(0.0, 1.0);
+                CGPoint {
+                    x: a.x + (b.x - a.x) * t,
+                    y: a.y + (b.y - a.y) * t,
+                }
+            };
+            if current.is_empty() {
+                current.push(point_at(start));
+            }
+            current.push(point_at(end));
+        }
+        if current.len() > 1 {
+            trimmed.push(current);
+        }
+    }
+    trimmed
+}
+
+/// Get the triangles for stroking a path. Each line segment becomes a
+/// rectangle `line_width` wide.
+pub(super) fn stroke_triangles(
+    subpaths: &[FlattenedSubpath],
+    line_width: CGFloat,
+    stroke_start: CGFloat,
+    stroke_end: CGFloat,
+) -> Vec<f32> {
+    let mut triangles = Vec::new();
+    if line_width <= 0.0 {
+        return triangles;
+    }
+    for points in trim_polylines(subpaths, stroke_start, stroke_end) {
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let (dx, dy) = (b.x - a.x, b.y - a.y);
+            let length = (dx * dx + dy * dy).sqrt();
+            if length == 0.0 {
+                continue;
+            }
+            let (nx, ny) = (
+                -dy / length * line_width / 2.0,
+                dx / length * line_width / 2.0,
+            );
+            let corners = [
+                (a.x + nx, a.y + ny),
+                (b.x + nx, b.y + ny),
+                (b.x - nx, b.y - ny),
+                (a.x - nx, a.y - ny),
+            ];
+            for i in [0, 1, 2, 0, 2, 3] {
+                triangles.extend_from_slice(&[corners[i].0, corners[i].1]);
+            }
+        }
+    }
+    triangles
+}
+
+#[cfg(test)]
+fn triangles_area(triangles: &[f32]) -> CGFloat {
+    triangles
+        .chunks(6)
+        .map(|t| {
+            let points = [
+                CGPoint { x: t[0], y: t[1] },
+                CGPoint { x: t[2], y: t[3] },
+                CGPoint { x: t[4], y: t[5] },
+            ];
+            signed_area(&points).abs()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+#[test]
+fn test_fill_triangles() {
+    fn subpath(points: &[(CGFloat, CGFloat)]) -> FlattenedSubpath {
+        FlattenedSubpath {
+            points: points.iter().map(|&(x, y)| CGPoint { x, y }).collect(),
+            closed: true,
+        }
+    }
+
+    let square = subpath(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+    let triangles = fill_triangles(&[square]);
+    assert_eq!(triangles.len(), 2 * 6);
+    assert_eq!(triangles_area(&triangles), 100.0);
+
+    // A concave L shape, clockwise, with the start point repeated at the end.
+    let l_shape = subpath(&[
+        (0.0, 0.0),
+        (0.0, 20.0),
+        (20.0, 20.0),
+        (20.0, 10.0),
+        (10.0, 10.0),
+        (10.0, 0.0),
+        (0.0, 0.0),
+    ]);
+    let triangles = fill_triangles(&[l_shape]);
+    assert_eq!(triangles.len(), 4 * 6);
+    assert_eq!(triangles_area(&triangles), 300.0);
+}
+
+#[cfg(test)]
+#[test]
+fn test_stroke_triangles() {
+    let line = FlattenedSubpath {
+        points: vec![CGPoint { x: 0.0, y: 0.0 }, CGPoint { x: 100.0, y: 0.0 }],
+        closed: false,
+    };
+    let triangles = stroke_triangles(&[line.clone()], 2.0, 0.0, 1.0);
+    assert_eq!(triangles_area(&triangles), 200.0);
+    let triangles = stroke_triangles(&[line.clone()], 2.0, 0.25, 0.5);
+    assert_eq!(triangles_area(&triangles), 50.0);
+    assert!(stroke_triangles(&[line], 2.0, 0.5, 0.5).is_empty());
+}
@@ -0,0 +1,281 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CATransaction`.
+//!
+//! Changes to layers don't really get batched here, they always take effect
+//! immediately. What transactions provide is the settings for implicit
+//! animations (see [super::ca_animation::run_action_for_change]) and the
+//! completion blocks that run once the animations added during a transaction
+//! have finished.
+//!
+//! When no transaction has been begun explicitly, an implicit one is used. It
+//! is committed each time the main thread's run loop goes round.
+
+use crate::abi::CallFromHost;
+use crate::frameworks::core_foundation::time::CFTimeInterval;
+use crate::libc::blocks;
+use crate::objc::{id, nil, objc_classes, release, retain, ClassExports};
+use crate::Environment;
+
+/// Host code's equivalent of a completion block. The argument is whether all
+/// the animations finished, rather than being removed early.
+pub type CompletionHandler = Box<dyn FnOnce(&mut Environment, bool)>;
+
+enum Completion {
+    /// Strong reference to a copy of a block set with `setCompletionBlock:`.
+    Block(id),
+    Host(CompletionHandler),
+}
+
+struct Transaction {
+    animation_duration: CFTimeInterval,
+    /// `CAMediaTimingFunction*`, possibly nil. This is a strong reference.
+    animation_timing_function: id,
+    disable_actions: bool,
+    completion: Option<Completion>,
+    /// Animations added during this transaction that haven't stopped yet. These
+    /// are weak references, the layers own them.
+    animations: Vec<id>,
+    /// Whether all the animations that stopped so far had finished.
+    finished: bool,
+}
+
+/// A committed transaction's completion, waiting for its animations.
+struct PendingCompletion {
+    completion: Completion,
+    animations: Vec<id>,
+    finished: bool,
+}
+
+#[derive(Default)]
+pub struct State {
+    /// Explicitly begun transactions, innermost last.
+    stack: Vec<Transaction>,
+    implicit: Option<Transaction>,
+    pending: Vec<PendingCompletion>,
+}
+
+/// Get the innermost transaction, beginning an implicit one if necessary.
+fn current(env: &mut Environment) -> &mut Transaction {
+    let state = &mut env.framework_state.core_animation.ca_transaction;
+    if !state.stack.is_empty() {
+        return state.stack.last_mut().unwrap();
+    }
+    state.implicit.get_or_insert_with(|| Transaction {
+        animation_duration: 0.25,
+        animation_timing_function: nil,
+        disable_actions: false,
+        completion: None,
+        animations: Vec::new(),
+        finished: true,
+    })
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CATransaction: NSObject
+
++ (())begin {
+    // Nested transactions inherit the settings of the enclosing one.
+    let &mut Transaction {
+        animation_duration,
+        animation_timing_function,
+        disable_actions,
+        ..
+    } = current(env);
+    retain(env, animation_timing_function);
+    env.framework_state.core_animation.ca_transaction.stack.push(Transaction {
+        animation_duration,
+        animation_timing_function,
+        disable_actions,
+        completion: None,
+        animations: Vec::new(),
+        finished: true,
+    });
+}
+
++ (())commit {
+    let Some(transaction) = env.framework_state.core_animation.ca_transaction.stack.pop() else {
+        log!("Warning: [CATransaction commit] without matching begin, ignoring");
+        return;
+    };
+    commit(env, transaction);
+}
+
++ (())flush {
+    commit_implicit_transaction(env);
+}
+
++ (())lock {
+    // Only the main thread should be touching layers anyway.
+}
++ (())unlock {
+}
+
++ (CFTimeInterval)animationDuration {
+    current(env).animation_duration
+}
++ (())setAnimationDuration:(CFTimeInterval)duration {
+    current(env).animation_duration = duration;
+}
+
++ (id)animationTimingFunction {
+    current(env).animation_timing_function
+}
++ (())setAnimationTimingFunction:(id)function { // CAMediaTimingFunction*
+    retain(env, function);
+    let old = std::mem::replace(&mut current(env).animation_timing_function, function);
+    release(env, old);
+}
+
++ (bool)disableActions {
+    current(env).disable_actions
+}
++ (())setDisableActions:(bool)disable {
+    current(env).disable_actions = disable;
+}
+
++ (id)completionBlock {
+    match current(env).completion {
+        Some(Completion::Block(block)) => block,
+        _ => nil,
+    }
+}
++ (())setCompletionBlock:(id)block {
+    let block = if block != nil {
+        Some(Completion::Block(blocks::copy_block(env, block)))
+    } else {
+        None
+    };
+    set_completion(env, block);
+}
+
+// TODO: valueForKey:/setValue:forKey:
+
+@end
+
+};
+
+fn set_completion(env: &mut Environment, completion: Option<Completion>) {
+    let old = std::mem::replace(&mut current(env).completion, completion);
+    if let Some(Completion::Block(block)) = old {
+        blocks::release_block(env, block);
+    }
+}
+
+/// Set the completion of the innermost transaction from host code, like
+/// `[CATransaction setCompletionBlock:]`.
+pub fn set_completion_handler(env: &mut Environment, handler: CompletionHandler) {
+    set_completion(env, Some(Completion::Host(handler)));
+}
+
+fn run_completion(env: &mut Environment, completion: Completion, finished: bool) {
+    match completion {
+        Completion::Block(block) => {
+            let invoke = blocks::get_invoke(env, block);
+            let _: () = invoke.call_from_host(env, (block,));
+            blocks::release_block(env, block);
+        }
+        Completion::Host(handler) => handler(env, finished),
+    }
+}
+
+fn commit(env: &mut Environment, transaction: Transaction) {
+    let Transaction {
+        animation_timing_function,
+        completion,
+        animations,
+        finished,
+        ..
+    } = transaction;
+    release(env, animation_timing_function);
+
+    let Some(completion) = completion else {
+        return;
+    };
+    if animations.is_empty() {
+        run_completion(env, completion, finished);
+    } else {
+        env.framework_state
+            .core_animation
+            .ca_transaction
+            .pending
+            .push(PendingCompletion {
+                completion,
+                animations,
+                finished,
+            });
+    }
+}
+
+/// For use by `NSRunLoop` on the main thread: commit the implicit transaction,
+/// if there is one.
+pub fn commit_implicit_transaction(env: &mut Environment) {
+    if let Some(transaction) = env
+        .framework_state
+        .core_animation
+        .ca_transaction
+        .implicit
+        .take()
+    {
+        commit(env, transaction);
+    }
+}
+
+/// Get the settings for implicit animations: the duration and the timing
+/// function (possibly nil), or [None] if actions are disabled.
+pub(super) fn animation_settings(env: &mut Environment) -> Option<(CFTimeInterval, id)> {
+    let transaction = current(env);
+    if transaction.disable_actions {
+        None
+    } else {
+        Some((
+            transaction.animation_duration,
+            transaction.animation_timing_function,
+        ))
+    }
+}
+
+/// For use by [super::ca_animation]: an animation was added to a layer.
+pub(super) fn animation_added(env: &mut Environment, animation: id) {
+    current(env).animations.push(animation);
+}
+
+/// For use by [super::ca_animation]: an animation stopped, so completions that
+/// were waiting for it might be able to run now.
+pub(super) fn animation_stopped(env: &mut Environment, animation: id, finished: bool) {
+    let state = &mut env.framework_state.core_animation.ca_transaction;
+    for transaction in state.stack.iter_mut().chain(state.implicit.iter_mut()) {
+        if let Some(idx) = transaction.animations.iter().position(|&a| a == animation) {
+            transaction.animations.remove(idx);
+            transaction.finished &= finished;
+        }
+    }
+
+    let pending = std::mem::take(&mut state.pending);
+    let (ready, waiting): (Vec<_>, Vec<_>) = pending
+        .into_iter()
+        .map(|mut pending| {
+            if let Some(idx) = pending.animations.iter().position(|&a| a == animation) {
+                pending.animations.remove(idx);
+                pending.finished &= finished;
+            }
+            pending
+        })
+        .partition(|pending| pending.animations.is_empty());
+    state.pending = waiting;
+
+    for PendingCompletion {
+        completion,
+        finished,
+        ..
+    } in ready
+    {
+        run_completion(env, completion, finished);
+    }
+}
@@ -0,0 +1,313 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CATransform3D.h`
+//!
+//! Like `CGAffineTransform`, these matrices use the row vector convention: a
+//! point is transformed by multiplying it on the left of the matrix, so
+//! `CATransform3DConcat(a, b)` means applying `a` and then `b`.
+
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::core_graphics::cg_affine_transform::CGAffineTransform;
+use crate::frameworks::core_graphics::CGFloat;
+use crate::mem::SafeRead;
+use crate::Environment;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C, packed)]
+pub struct CATransform3D {
+    pub m11: CGFloat,
+    pub m12: CGFloat,
+    pub m13: CGFloat,
+    pub m14: CGFloat,
+    pub m21: CGFloat,
+    pub m22: CGFloat,
+    pub m23: CGFloat,
+    pub m24: CGFloat,
+    pub m31: CGFloat,
+    pub m32: CGFloat,
+    pub m33: CGFloat,
+    pub m34: CGFloat,
+    pub m41: CGFloat,
+    pub m42: CGFloat,
+    pub m43: CGFloat,
+    pub m44: CGFloat,
+}
+unsafe impl SafeRead for CATransform3D {}
+impl_GuestRet_for_large_struct!(CATransform3D);
+impl GuestArg for CATransform3D {
+    const REG_COUNT: usize = 16;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        let mut m = [0.0; 16];
+        for (value, reg) in m.iter_mut().zip(regs) {
+            *value = GuestArg::from_regs(std::slice::from_ref(reg));
+        }
+        CATransform3D::from_array(m)
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        for (value, reg) in self.to_array().into_iter().zip(regs) {
+            value.to_regs(std::slice::from_mut(reg));
+        }
+    }
+}
+
+impl CATransform3D {
+    /// The elements in row-major order. Because of the row vector convention,
+    /// this is also the column-major order OpenGL expects.
+    pub fn to_array(self) -> [CGFloat; 16] {
+        [
+            self.m11, self.m12, self.m13, self.m14, self.m21, self.m22, self.m23, self.m24,
+            self.m31, self.m32, self.m33, self.m34, self.m41, self.m42, self.m43, self.m44,
+        ]
+    }
+    pub fn from_array(m: [CGFloat; 16]) -> Self {
+        CATransform3D {
+            m11: m[0],
+            m12: m[1],
+            m13: m[2],
+            m14: m[3],
+            m21: m[4],
+            m22: m[5],
+            m23: m[6],
+            m24: m[7],
+            m31: m[8],
+            m32: m[9],
+            m33: m[10],
+            m34: m[11],
+            m41: m[12],
+            m42: m[13],
+            m43: m[14],
+            m44: m[15],
+        }
+    }
+
+    /// Matrix multiplication, i.e. the transform that applies `self` and then
+    /// `other`.
+    pub fn concat(self, other: CATransform3D) -> Self {
+        let a = self.to_array();
+        let b = other.to_array();
+        let mut res = [0.0; 16];
+        for row in 0..4 {
+            for col in 0..4 {
+                res[row * 4 + col] = (0..4).map(|i| a[row * 4 + i] * b[i * 4 + col]).sum();
+            }
+        }
+        CATransform3D::from_array(res)
+    }
+
+    pub fn translation(tx: CGFloat, ty: CGFloat, tz: CGFloat) -> Self {
+        CATransform3D {
+            m41: tx,
+            m42: ty,
+            m43: tz,
+            ..CATransform3DIdentity
+        }
+    }
+
+    /// Transform a 2D point, including the perspective divide.
+    pub fn apply_to_point(self, x: CGFloat, y: CGFloat) -> (CGFloat, CGFloat) {
+        let tx = x * self.m11 + y * self.m21 + self.m41;
+        let ty = x * self.m12 + y * self.m22 + self.m42;
+        let tw = x * self.m14 + y * self.m24 + self.m44;
+        (tx / tw, ty / tw)
+    }
+
+    /// Whether this transform only translates and scales in the x and y
+    /// dimensions, so that rectangles stay axis-aligned rectangles.
+    pub fn is_axis_aligned_2d(self) -> bool {
+        self.m12 == 0.0 && self.m14 == 0.0 && self.m21 == 0.0 && self.m24 == 0.0 && self.m44 == 1.0
+    }
+}
+
+#[rustfmt::skip]
+pub const CATransform3DIdentity: CATransform3D = CATransform3D {
+    m11: 1.0, m12: 0.0, m13: 0.0, m14: 0.0,
+    m21: 0.0, m22: 1.0, m23: 0.0, m24: 0.0,
+    m31: 0.0, m32: 0.0, m33: 1.0, m34: 0.0,
+    m41: 0.0, m42: 0.0, m43: 0.0, m44: 1.0,
+};
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_CATransform3DIdentity",
+    HostConstant::Custom(|mem| {
+        mem.alloc_and_write(CATransform3DIdentity)
+            .cast()
+            .cast_const()
+    }),
+)];
+
+fn CATransform3DIsIdentity(_env: &mut Environment, t: CATransform3D) -> bool {
+    t == CATransform3DIdentity
+}
+fn CATransform3DEqualToTransform(
+    _env: &mut Environment,
+    a: CATransform3D,
+    b: CATransform3D,
+) -> bool {
+    a == b
+}
+
+fn CATransform3DMakeTranslation(
+    _env: &mut Environment,
+    tx: CGFloat,
+    ty: CGFloat,
+    tz: CGFloat,
+) -> CATransform3D {
+    CATransform3D::translation(tx, ty, tz)
+}
+fn CATransform3DMakeScale(
+    _env: &mut Environment,
+    sx: CGFloat,
+    sy: CGFloat,
+    sz: CGFloat,
+) -> CATransform3D {
+    CATransform3D {
+        m11: sx,
+        m22: sy,
+        m33: sz,
+        ..CATransform3DIdentity
+    }
+}
+fn make_rotation(angle: CGFloat, x: CGFloat, y: CGFloat, z: CGFloat) -> CATransform3D {
+    let length = (x * x + y * y + z * z).sqrt();
+    if length == 0.0 {
+        return CATransform3DIdentity;
+    }
+    let (x, y, z) = (x / length, y / length, z / length);
+    let (s, c) = angle.sin_cos();
+    let t = 1.0 - c;
+    // This is the transpose of the usual column vector rotation matrix.
+    CATransform3D {
+        m11: c + x * x * t,
+        m12: x * y * t + z * s,
+        m13: x * z * t - y * s,
+        m21: y * x * t - z * s,
+        m22: c + y * y * t,
+        m23: y * z * t + x * s,
+        m31: z * x * t + y * s,
+        m32: z * y * t - x * s,
+        m33: c + z * z * t,
+        ..CATransform3DIdentity
+    }
+}
+fn CATransform3DMakeRotation(
+    _env: &mut Environment,
+    angle: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+    z: CGFloat,
+) -> CATransform3D {
+    make_rotation(angle, x, y, z)
+}
+
+// The Translate/Scale/Rotate functions apply the new transform before the
+// existing one.
+fn CATransform3DTranslate(
+    env: &mut Environment,
+    t: CATransform3D,
+    tx: CGFloat,
+    ty: CGFloat,
+    tz: CGFloat,
+) -> CATransform3D {
+    CATransform3DMakeTranslation(env, tx, ty, tz).concat(t)
+}
+fn CATransform3DScale(
+    env: &mut Environment,
+    t: CATransform3D,
+    sx: CGFloat,
+    sy: CGFloat,
+    sz: CGFloat,
+) -> CATransform3D {
+    CATransform3DMakeScale(env, sx, sy, sz).concat(t)
+}
+fn CATransform3DRotate(
+    _env: &mut Environment,
+    t: CATransform3D,
+    angle: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+    z: CGFloat,
+) -> CATransform3D {
+    make_rotation(angle, x, y, z).concat(t)
+}
+fn CATransform3DConcat(
+    _env: &mut Environment,
+    a: CATransform3D,
+    b: CATransform3D,
+) -> CATransform3D {
+    a.concat(b)
+}
+
+pub fn CATransform3DMakeAffineTransform(
+    _env: &mut Environment,
+    m: CGAffineTransform,
+) -> CATransform3D {
+    CATransform3D {
+        m11: m.a,
+        m12: m.b,
+        m21: m.c,
+        m22: m.d,
+        m41: m.tx,
+        m42: m.ty,
+        ..CATransform3DIdentity
+    }
+}
+pub fn CATransform3DIsAffine(_env: &mut Environment, t: CATransform3D) -> bool {
+    t.m13 == 0.0
+        && t.m14 == 0.0
+        && t.m23 == 0.0
+        && t.m24 == 0.0
+        && t.m31 == 0.0
+        && t.m32 == 0.0
+        && t.m33 == 1.0
+        && t.m34 == 0.0
+        && t.m43 == 0.0
+        && t.m44 == 1.0
+}
+pub fn CATransform3DGetAffineTransform(
+    _env: &mut Environment,
+    t: CATransform3D,
+) -> CGAffineTransform {
+    CGAffineTransform {
+        a: t.m11,
+        b: t.m12,
+        c: t.m21,
+        d: t.m22,
+        tx: t.m41,
+        ty: t.m42,
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CATransform3DIsIdentity(_)),
+    export_c_func!(CATransform3DEqualToTransform(_, _)),
+    export_c_func!(CATransform3DMakeTranslation(_, _, _)),
+    export_c_func!(CATransform3DMakeScale(_, _, _)),
+    export_c_func!(CATransform3DMakeRotation(_, _, _, _)),
+    export_c_func!(CATransform3DTranslate(_, _, _, _)),
+    export_c_func!(CATransform3DScale(_, _, _, _)),
+    export_c_func!(CATransform3DRotate(_, _, _, _, _)),
+    export_c_func!(CATransform3DConcat(_, _)),
+    export_c_func!(CATransform3DMakeAffineTransform(_)),
+    export_c_func!(CATransform3DIsAffine(_)),
+    export_c_func!(CATransform3DGetAffineTransform(_)),
+];
+
+#[cfg(test)]
+#[test]
+fn test_rotate_then_translate() {
+    let rotation = make_rotation(std::f32::consts::FRAC_PI_2, 0.0, 0.0, 1.0);
+    let translation = CATransform3D::translation(10.0, 0.0, 0.0);
+
+    // Row vector convention: rotating (1, 0) by 90° gives (0, 1), and then
+    // translating gives (10, 1).
+    let (x, y) = rotation.concat(translation).apply_to_point(1.0, 0.0);
+    assert!((x - 10.0).abs() < 0.0001 && (y - 1.0).abs() < 0.0001);
+    // The other way around: (11, 0) rotated is (0, 11).
+    let (x, y) = translation.concat(rotation).apply_to_point(1.0, 0.0);
+    assert!(x.abs() < 0.0001 && (y - 11.0).abs() < 0.0001);
+}
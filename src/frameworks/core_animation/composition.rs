@@ -9,25 +9,100 @@
 //! I haven't attempted to reverse-engineer the details. As such, it probably
 //! diverges wildly from what the real iPhone OS does.
 
+use super::ca_animation::evaluate_animations;
 use super::ca_eagl_layer::find_fullscreen_eagl_layer;
-use super::ca_layer::CALayerHostObject;
+use super::ca_gradient_layer;
+use super::ca_layer::{sublayers_in_drawing_order, CALayerHostObject, ContentsGravity};
+use super::ca_shape_layer;
+use super::ca_transform_3d::CATransform3D;
+use crate::font::{Font, TextAlignment};
 use crate::frameworks::core_graphics::{
-    cg_bitmap_context, cg_image, CGFloat, CGPoint, CGRect, CGSize,
+    cg_bitmap_context, cg_image, cg_path, CGFloat, CGPoint, CGRect, CGSize,
+};
+use crate::frameworks::opengles;
+use crate::frameworks::uikit::ui_application::{
+    status_bar_orientation, visible_status_bar_style, UIInterfaceOrientation,
+    UIInterfaceOrientationLandscapeLeft, UIInterfaceOrientationLandscapeRight, UIStatusBarStyle,
+    UIStatusBarStyleBlackOpaque, UIStatusBarStyleBlackTranslucent,
 };
 use crate::frameworks::uikit::ui_color;
+use crate::frameworks::uikit::ui_view::ui_window;
+use crate::frameworks::uikit::ui_view_controller::status_bar_layout_offset;
+use crate::gles::debug_overlay::FrameStats;
 use crate::gles::gles11_raw as gles11; // constants only
 use crate::gles::gles11_raw::types::*;
-use crate::gles::present::present_frame;
+use crate::gles::present::{present_frame, save_screenshot};
+use crate::gles::texture_memory::TextureMemory;
 use crate::gles::GLES;
 use crate::mem::Mem;
 use crate::objc::{id, msg, msg_class, nil, ObjC};
 use crate::Environment;
-use std::time::{Duration, Instant};
+use std::collections::HashSet;
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Default)]
 pub(super) struct State {
-    texture_framebuffer: Option<(GLuint, GLuint)>,
+    /// Texture and framebuffer used for rendering, and their size.
+    texture_framebuffer: Option<(GLuint, GLuint, (u32, u32))>,
     recomposite_next: Option<Instant>,
+    /// Set when something that affects the composited output has changed. See
+    /// [set_needs_recomposite].
+    needs_recomposite: bool,
+    /// Things that affect the composited output, but aren't layer properties,
+    /// as of the last composition: the top window, the offset of its contents
+    /// for the status bar, and the status bar style, text and orientation (if
+    /// the status bar is visible).
+    last_composited: Option<(id, CGPoint, Option<StatusBarContents>)>,
+    status_bar: Option<StatusBar>,
+    /// Sizes and use of the layers' textures, for eviction when
+    /// `--texture-memory-budget=` is exceeded.
+    texture_memory: TextureMemory<id>,
+    /// Textures of deallocated layers. See [forget_layer_textures].
+    textures_to_delete: Vec<GLuint>,
+}
+
+/// A texture used by the compositor, and the size of its storage, if it has
+/// been allocated. Knowing the size means new contents of the same size can be
+/// uploaded with `glTexSubImage2D()`, which doesn't reallocate anything.
+#[derive(Copy, Clone)]
+pub(super) struct CompositorTexture {
+    pub(super) name: GLuint,
+    size: Option<(u32, u32)>,
+}
+impl CompositorTexture {
+    unsafe fn new(gles: &mut dyn GLES) -> Self {
+        let mut name = 0;
+        gles.GenTextures(1, &mut name);
+        CompositorTexture { name, size: None }
+    }
+
+    fn bytes(&self) -> usize {
+        self.size
+            .map_or(0, |(width, height)| width as usize * height as usize * 4)
+    }
+}
+
+/// Internal state for drawing the status bar.
+struct StatusBar {
+    font: Font,
+    texture: CompositorTexture,
+    /// The contents last drawn to the texture, if any.
+    drawn: Option<StatusBarContents>,
+}
+
+type StatusBarContents = (UIStatusBarStyle, String, UIInterfaceOrientation);
+
+/// Height of the status bar in points.
+const STATUS_BAR_HEIGHT: CGFloat = 20.0;
+
+/// Tell the compositor that a layer property affecting the composited output
+/// has changed. Unless this is called, the compositor will assume it can reuse
+/// the previous frame.
+pub(super) fn set_needs_recomposite(env: &mut Environment) {
+    env.framework_state
+        .core_animation
+        .composition
+        .needs_recomposite = true;
 }
 
 /// For use by `NSRunLoop`: call this 60 times per second. Composites the app's
@@ -36,11 +111,9 @@ pub(super) struct State {
 ///
 /// Returns the time a recomposite is due, if any.
 pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
-    // Assumes the last window in the list is the one on top.
-    // TODO: this is not correct once we support zPosition.
     // TODO: can there be windows smaller than the screen? If so we need to draw
-    //       all of them.
-    let Some(&top_window) = env.framework_state.uikit.ui_view.ui_window.visible_windows.last() else {
+    //       all of them, sorted by window level.
+    let Some(top_window) = ui_window::top_visible_window(env) else {
         log_dbg!("No visible window, skipping composition");
         return None;
     };
@@ -48,10 +121,13 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
     if find_fullscreen_eagl_layer(env) != nil {
         // No composition done, EAGLContext will present directly.
         log_dbg!("Using CAEAGLLayer fast path, skipping composition");
+        // The previous frame can't be reused once the fast path stops being
+        // used, because it will be out of date.
+        set_needs_recomposite(env);
         return None;
     }
 
-    let now = Instant::now();
+    let now = env.clock.now();
     let interval = 1.0 / 60.0; // 60Hz
     let new_recomposite_next = if let Some(recomposite_next) = env
         .framework_state
@@ -91,6 +167,10 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
     // Ensure layer bitmaps are up to date.
     display_layers(env, root_layer);
 
+    // Update the presentation values of animated layers. This can request
+    // another recomposite.
+    evaluate_animations(env, root_layer);
+
     let screen_bounds: CGRect = {
         let screen: id = msg_class![env; UIScreen mainScreen];
         msg![env; screen bounds]
@@ -102,26 +182,105 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
         env.window().viewport(),
         env.window().output_rotation_matrix(),
         env.window().virtual_cursor_visible_at(),
+        env.window().present_filter(),
     );
+    let take_screenshot = env.window_mut().take_screenshot_request();
+    // The app may have drawn to offscreen renderbuffers that are composited.
+    let mut frame_stats = FrameStats {
+        draw_calls: opengles::take_draw_call_count(env),
+        ..Default::default()
+    };
+    let app_texture_memory = opengles::app_texture_memory(env);
 
-    // TODO: draw status bar if it's not hidden
+    let status_bar = visible_status_bar_style(env).map(|style| {
+        (
+            style,
+            status_bar_clock_text(env),
+            status_bar_orientation(env),
+        )
+    });
+
+    let layout_offset = status_bar_layout_offset(env, top_window);
+
+    let state = &mut env.framework_state.core_animation.composition;
+    let composited = (top_window, layout_offset, status_bar.clone());
+    let needs_recomposite = std::mem::take(&mut state.needs_recomposite)
+        || state.texture_framebuffer.map(|(_, _, size)| size) != Some((fb_width, fb_height))
+        || state.last_composited.as_ref() != Some(&composited);
+    state.last_composited = Some(composited);
 
     // Initial state for layer tree traversal (see composite_layer_recursive)
-    let origin = CGPoint { x: 0.0, y: 0.0 };
+    let parent_transform = CATransform3D::translation(layout_offset.x, layout_offset.y, 0.0);
     let clip_to = CGRect {
-        origin,
+        origin: CGPoint { x: 0.0, y: 0.0 },
         size: screen_bounds.size,
     };
     let opacity = 1.0;
 
     let window = env.window.as_mut().unwrap();
     window.make_internal_gl_ctx_current();
-    let gles = window.get_internal_gl_ctx();
+    let (gles, debug_overlay) = window.get_internal_gl_ctx_and_debug_overlay();
+
+    let state = &mut env.framework_state.core_animation.composition;
+    unsafe {
+        delete_textures(gles, &std::mem::take(&mut state.textures_to_delete));
+    }
+    state.texture_memory.next_frame();
+
+    // The texture needs to be re-created if the size has changed, e.g. because
+    // the screen was rotated.
+    if let Some((texture, framebuffer, size)) = state.texture_framebuffer {
+        if size != (fb_width, fb_height) {
+            log_dbg!(
+                "Framebuffer size changed from {:?} to {:?}, recreating texture",
+                size,
+                (fb_width, fb_height)
+            );
+            unsafe {
+                delete_texture_framebuffer(gles, texture, framebuffer);
+            }
+            state.texture_framebuffer = None;
+        }
+    }
+
+    // If nothing has changed, the previous frame can be presented again. This
+    // still has to be done rather than doing nothing, because the virtual
+    // cursor may have moved.
+    if !needs_recomposite {
+        log_dbg!("Nothing changed, re-presenting previous frame");
+        let state = &env.framework_state.core_animation.composition;
+        let (texture, _framebuffer, _size) = state.texture_framebuffer.unwrap();
+        frame_stats.texture_memory = app_texture_memory + state.texture_memory.bytes();
+        unsafe {
+            gles.BindTexture(gles11::TEXTURE_2D, texture);
+            gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, 0);
+            present_frame(
+                gles,
+                present_frame_args.0,
+                present_frame_args.1,
+                present_frame_args.2,
+                present_frame_args.3,
+            );
+            if take_screenshot {
+                save_screenshot(gles, present_frame_args.0);
+            }
+            if let Some(debug_overlay) = debug_overlay {
+                debug_overlay.record_frame(Instant::now(), frame_stats);
+                debug_overlay.draw(gles, present_frame_args.0);
+            }
+        }
+        env.window().swap_window();
+        return new_recomposite_next;
+    }
+
+    // This uses real time rather than the app's clock, since it's measuring
+    // touchHLE's performance.
+    let composite_start = Instant::now();
 
     // Set up GL objects needed for render-to-texture. We could draw directly
     // to the screen instead, but this way we can reuse the code for scaling and
     // rotating the screen and drawing the virtual cursor.
-    let texture = if let Some((texture, framebuffer)) = env
+    let texture = if let Some((texture, framebuffer, _size)) = env
         .framework_state
         .core_animation
         .composition
@@ -148,16 +307,9 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
                 gles11::UNSIGNED_BYTE,
                 std::ptr::null(),
             );
-            gles.TexParameteri(
-                gles11::TEXTURE_2D,
-                gles11::TEXTURE_MIN_FILTER,
-                gles11::LINEAR as _,
-            );
-            gles.TexParameteri(
-                gles11::TEXTURE_2D,
-                gles11::TEXTURE_MAG_FILTER,
-                gles11::LINEAR as _,
-            );
+            let filter = present_frame_args.3;
+            gles.TexParameteri(gles11::TEXTURE_2D, gles11::TEXTURE_MIN_FILTER, filter as _);
+            gles.TexParameteri(gles11::TEXTURE_2D, gles11::TEXTURE_MAG_FILTER, filter as _);
 
             gles.GenFramebuffersOES(1, &mut framebuffer);
             gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, framebuffer);
@@ -177,7 +329,7 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
         env.framework_state
             .core_animation
             .composition
-            .texture_framebuffer = Some((texture, framebuffer));
+            .texture_framebuffer = Some((texture, framebuffer, (fb_width, fb_height)));
         texture
     };
 
@@ -189,6 +341,35 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
         gles.Enable(gles11::SCISSOR_TEST);
         gles.Scissor(0, 0, fb_width as _, fb_height as _);
         gles.Color4f(1.0, 1.0, 1.0, 1.0);
+        // Layers are drawn in unscaled screen co-ordinates, with y pointing
+        // down. The depth range is generous so that 3D transforms don't get
+        // clipped.
+        gles.MatrixMode(gles11::PROJECTION);
+        gles.LoadIdentity();
+        gles.Orthof(
+            0.0,
+            screen_bounds.size.width,
+            screen_bounds.size.height,
+            0.0,
+            -1000.0,
+            1000.0,
+        );
+    }
+
+    let mut occluded = HashSet::new();
+    if env.options.occlusion_culling {
+        find_occluded_layers(
+            &env.objc,
+            root_layer,
+            parent_transform,
+            clip_to,
+            opacity,
+            scale_hack,
+            fb_height,
+            &mut Vec::new(),
+            &mut occluded,
+        );
+        log_dbg!("Skipping {} occluded layer(s)", occluded.len());
     }
 
     // Here's where the actual drawing happens
@@ -197,15 +378,76 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
             gles,
             &mut env.objc,
             &env.mem,
+            &occluded,
             root_layer,
-            origin,
+            parent_transform,
             clip_to,
             opacity,
             scale_hack,
             fb_height,
+            &mut env
+                .framework_state
+                .core_animation
+                .composition
+                .texture_memory,
+            &mut frame_stats,
         );
     }
 
+    // Textures are only evicted once the frame has been drawn, and textures
+    // used for it are kept, so none of them have to be re-uploaded for the
+    // next frame unless something has changed. The app's textures can't be
+    // evicted, but they still count towards the budget.
+    let state = &mut env.framework_state.core_animation.composition;
+    if let Some(budget) = env.options.texture_memory_budget {
+        let evicted = state
+            .texture_memory
+            .evict_to_fit(budget.saturating_sub(app_texture_memory));
+        if !evicted.is_empty() {
+            log_dbg!("Evicting {} layer texture(s)", evicted.len());
+        }
+        for layer in evicted {
+            let host_obj = env.objc.borrow_mut::<CALayerHostObject>(layer);
+            let textures: Vec<GLuint> = [
+                host_obj.gles_texture.take(),
+                host_obj.gles_back_texture.take(),
+            ]
+            .iter()
+            .flatten()
+            .map(|texture| texture.name)
+            .collect();
+            host_obj.gles_texture_is_up_to_date = false;
+            unsafe {
+                delete_textures(gles, &textures);
+            }
+        }
+    }
+    frame_stats.texture_memory = app_texture_memory + state.texture_memory.bytes();
+
+    // The status bar and present_frame() expect identity matrices.
+    unsafe {
+        gles.MatrixMode(gles11::PROJECTION);
+        gles.LoadIdentity();
+        gles.MatrixMode(gles11::MODELVIEW);
+        gles.LoadIdentity();
+    }
+
+    // The status bar is drawn on top of the app's content.
+    if let Some(status_bar_contents) = status_bar {
+        let state = &mut env.framework_state.core_animation.composition;
+        unsafe {
+            draw_status_bar(
+                gles,
+                &mut state.status_bar,
+                status_bar_contents,
+                screen_bounds.size,
+                scale_hack,
+                fb_height,
+            );
+        }
+        frame_stats.draw_calls += 1;
+    }
+
     // Clean up some GL state
     unsafe {
         gles.Viewport(0, 0, fb_width as _, fb_height as _);
@@ -214,6 +456,7 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
         gles.Disable(gles11::BLEND);
         assert_eq!(gles.GetError(), 0);
     }
+    frame_stats.composite_time = Some(composite_start.elapsed());
 
     // Present our rendered frame (bound to TEXTURE_2D). This copies it to the
     // default framebuffer (0) so we need to unbind our internal framebuffer.
@@ -225,13 +468,79 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
             present_frame_args.0,
             present_frame_args.1,
             present_frame_args.2,
+            present_frame_args.3,
         );
+        if take_screenshot {
+            save_screenshot(gles, present_frame_args.0);
+        }
+        if let Some(debug_overlay) = debug_overlay {
+            debug_overlay.record_frame(Instant::now(), frame_stats);
+            debug_overlay.draw(gles, present_frame_args.0);
+        }
     }
     env.window().swap_window();
 
     new_recomposite_next
 }
 
+unsafe fn delete_texture_framebuffer(gles: &mut dyn GLES, texture: GLuint, framebuffer: GLuint) {
+    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, 0);
+    gles.DeleteFramebuffersOES(1, &framebuffer);
+    gles.DeleteTextures(1, &texture);
+}
+
+unsafe fn delete_textures(gles: &mut dyn GLES, textures: &[GLuint]) {
+    if !textures.is_empty() {
+        gles.DeleteTextures(textures.len() as _, textures.as_ptr());
+    }
+}
+
+/// For use by `CALayer`'s `dealloc`: delete the layer's textures. The internal
+/// GL context may not be current, so this is done when the next frame is
+/// composited.
+pub(super) fn forget_layer_textures(
+    env: &mut Environment,
+    layer: id,
+    textures: [Option<CompositorTexture>; 2],
+) {
+    let state = &mut env.framework_state.core_animation.composition;
+    state.texture_memory.forget(layer);
+    state
+        .textures_to_delete
+        .extend(textures.iter().flatten().map(|texture| texture.name));
+}
+
+/// Get the estimated amount of memory in bytes used by textures, both the
+/// app's and those of the compositor.
+pub fn texture_memory_in_use(env: &Environment) -> usize {
+    let state = &env.framework_state.core_animation.composition;
+    opengles::app_texture_memory(env) + state.texture_memory.bytes()
+}
+
+/// Delete the GL objects used by the compositor. For use when the app is
+/// exiting.
+pub fn free_gl_resources(env: &mut Environment) {
+    let state = &mut env.framework_state.core_animation.composition;
+    let texture_framebuffer = state.texture_framebuffer.take();
+    let status_bar = state.status_bar.take();
+    let textures_to_delete = std::mem::take(&mut state.textures_to_delete);
+    // In headless mode there's no window and no GL objects to delete.
+    let Some(window) = env.window.as_mut() else {
+        return;
+    };
+    window.make_internal_gl_ctx_current();
+    let gles = window.get_internal_gl_ctx();
+    unsafe {
+        if let Some((texture, framebuffer, _size)) = texture_framebuffer {
+            delete_texture_framebuffer(gles, texture, framebuffer);
+        }
+        if let Some(StatusBar { texture, .. }) = status_bar {
+            gles.DeleteTextures(1, &texture.name);
+        }
+        delete_textures(gles, &textures_to_delete);
+    }
+}
+
 /// Call `displayIfNeeded` on all relevant layers in the tree, so their bitmaps
 /// are up to date before compositing.
 fn display_layers(env: &mut Environment, root_layer: id) {
@@ -258,21 +567,151 @@ fn display_layers(env: &mut Environment, root_layer: id) {
     }
 }
 
+/// The position of a layer on the screen and related values, computed from the
+/// layer's properties and those of its superlayers. See
+/// [composite_layer_recursive].
+struct LayerGeometry {
+    /// The layer's opacity multiplied by that of its superlayers.
+    opacity: CGFloat,
+    bounds: CGRect,
+    /// Maps from the layer's rectangle, with (0, 0) at the top-left corner, to
+    /// the screen.
+    layer_transform: CATransform3D,
+    /// The part of the layer's frame that is visible. Like `clip_to`, this is
+    /// in unscaled absolute co-ordinates; scale_hack is only applied when
+    /// converting to a GL rect. If the layer is rotated, the frame is its
+    /// bounding box.
+    absolute_frame_clipped: CGRect,
+    is_axis_aligned: bool,
+    /// Maps from the layer's bounds co-ordinate space to the screen.
+    sublayer_transform: CATransform3D,
+    /// Sublayers are clipped to this layer's frame if masksToBounds is set.
+    sublayer_clip_to: CGRect,
+}
+
+fn layer_geometry(
+    host_obj: &CALayerHostObject,
+    parent_transform: CATransform3D,
+    clip_to: CGRect,
+    opacity: CGFloat,
+) -> LayerGeometry {
+    let opacity = opacity * host_obj.presentation_opacity();
+    let bounds = host_obj.presentation_bounds();
+    let position = host_obj.presentation_position();
+    let anchor_point = host_obj.anchor_point;
+    // The layer's transform is applied around its anchor point.
+    let layer_transform = CATransform3D::translation(
+        -bounds.size.width * anchor_point.x,
+        -bounds.size.height * anchor_point.y,
+        0.0,
+    )
+    .concat(host_obj.presentation_transform())
+    .concat(CATransform3D::translation(position.x, position.y, 0.0))
+    .concat(parent_transform);
+    let absolute_frame = transformed_bounding_box(layer_transform, bounds.size);
+    let absolute_frame_clipped = clip_rects(clip_to, absolute_frame);
+    LayerGeometry {
+        opacity,
+        bounds,
+        layer_transform,
+        absolute_frame_clipped,
+        is_axis_aligned: layer_transform.is_axis_aligned_2d(),
+        sublayer_transform: CATransform3D::translation(-bounds.origin.x, -bounds.origin.y, 0.0)
+            .concat(layer_transform),
+        sublayer_clip_to: if host_obj.masks_to_bounds {
+            absolute_frame_clipped
+        } else {
+            clip_to
+        },
+    }
+}
+
+/// Find the layers that don't need to be drawn because they would be
+/// completely covered by opaque layers drawn after them. This traverses the
+/// layer tree front-to-back, keeping a list of the screen rectangles covered so
+/// far. Only layers that are certain to replace everything beneath them count,
+/// so transparency is unaffected.
+#[allow(clippy::too_many_arguments)]
+fn find_occluded_layers(
+    objc: &ObjC,
+    layer: id,
+    parent_transform: CATransform3D,
+    clip_to: CGRect,
+    opacity: CGFloat,
+    scale_hack: u32,
+    fb_height: u32,
+    covered: &mut Vec<(GLint, GLint, GLint, GLint)>,
+    occluded: &mut HashSet<id>,
+) {
+    let host_obj = objc.borrow::<CALayerHostObject>(layer);
+    if host_obj.hidden {
+        return;
+    }
+    let geometry = layer_geometry(host_obj, parent_transform, clip_to, opacity);
+
+    for &child_layer in sublayers_in_drawing_order(objc, layer).iter().rev() {
+        find_occluded_layers(
+            objc,
+            child_layer,
+            geometry.sublayer_transform,
+            geometry.sublayer_clip_to,
+            geometry.opacity,
+            scale_hack,
+            fb_height,
+            covered,
+            occluded,
+        );
+    }
+
+    // Comparing the GL rects rather than the CGRects accounts for rounding.
+    let gl_rect = gl_rect_from_cg_rect(geometry.absolute_frame_clipped, scale_hack, fb_height);
+
+    // Shadows and shape layer paths can be drawn outside the layer's frame.
+    let draws_outside_frame = host_obj.shadow_opacity > 0.0
+        || host_obj
+            .shape_properties
+            .map_or(false, |shape| shape.path != nil);
+    if !draws_outside_frame && covered.iter().any(|&rect| gl_rect_contains(rect, gl_rect)) {
+        occluded.insert(layer);
+        return;
+    }
+
+    // These conditions should match the ones in composite_layer_recursive
+    // where drawing is done without blending.
+    if !geometry.is_axis_aligned || host_obj.corner_radius > 0.0 || geometry.opacity != 1.0 {
+        return;
+    }
+    let opaque_background = host_obj.background_color != nil
+        && ui_color::get_rgba(objc, host_obj.background_color).3 == 1.0;
+    let opaque_contents = host_obj.opaque
+        && (host_obj.presented_pixels.is_some()
+            || host_obj.contents != nil
+            || host_obj.cg_context.is_some())
+        && host_obj.contents_gravity == ContentsGravity::Resize
+        && host_obj.gradient_properties.is_none();
+    if opaque_background || opaque_contents {
+        covered.push(gl_rect);
+    }
+}
+
 /// Traverses the layer tree and draws each layer.
 unsafe fn composite_layer_recursive(
     gles: &mut dyn GLES,
     objc: &mut ObjC,
     mem: &Mem,
+    occluded: &HashSet<id>,
     layer: id,
-    origin: CGPoint,
+    parent_transform: CATransform3D,
     clip_to: CGRect,
     opacity: CGFloat,
     scale_hack: u32,
     fb_height: u32,
+    texture_memory: &mut TextureMemory<id>,
+    stats: &mut FrameStats,
 ) {
-    // TODO: this can't handle zPosition, non-AABB layer transforms, rounded
-    // corners, and many other things, but none of these are supported yet :)
-    // TODO: back-to-front drawing is not efficient, could we use front-to-back?
+    // TODO: this can't handle masking sublayers to rounded corners or to
+    // non-AABB frames, and many other things, but none of these are supported
+    // yet :)
 
     let host_obj = objc.borrow::<CALayerHostObject>(layer);
 
@@ -280,36 +719,185 @@ unsafe fn composite_layer_recursive(
         return;
     }
 
-    let opacity = opacity * host_obj.opacity;
-    let bounds = host_obj.bounds;
-    let absolute_frame = {
-        let position = host_obj.position;
-        let anchor_point = host_obj.anchor_point;
-        CGRect {
-            origin: CGPoint {
-                x: origin.x + position.x - bounds.size.width * anchor_point.x,
-                y: origin.y + position.y - bounds.size.height * anchor_point.y,
-            },
-            size: bounds.size,
+    let LayerGeometry {
+        opacity,
+        bounds,
+        layer_transform,
+        absolute_frame_clipped,
+        is_axis_aligned,
+        sublayer_transform,
+        sublayer_clip_to,
+    } = layer_geometry(host_obj, parent_transform, clip_to, opacity);
+
+    // Drawing is back-to-front, so layers that would be completely covered by
+    // layers in front of them can be skipped. Their sublayers still need to be
+    // considered, since they are in front.
+    if occluded.contains(&layer) {
+        // This is a copy, so the borrow isn't held while recursing.
+        let sublayers = sublayers_in_drawing_order(objc, layer);
+        for child_layer in sublayers {
+            composite_layer_recursive(
+                gles,
+                objc,
+                mem,
+                occluded,
+                child_layer,
+                /* parent_transform: */ sublayer_transform,
+                /* clip_to: */ sublayer_clip_to,
+                opacity,
+                scale_hack,
+                fb_height,
+                texture_memory,
+                stats,
+            )
         }
+        return;
+    }
+    stats.layers_drawn += 1;
+
+    gles.MatrixMode(gles11::MODELVIEW);
+    gles.LoadMatrixf(layer_transform.to_array().as_ptr());
+    let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
+    gles.Scissor(x, y, w, h);
+
+    // Draw shadow, if any. It's beneath the layer, and it isn't clipped to the
+    // layer's frame, so it has its own scissor rect. Rather than the shadow
+    // matching the layer's contents, it's always the shape of the layer.
+    let shadow_color = if host_obj.shadow_opacity > 0.0 {
+        let (r, g, b, a) = if host_obj.shadow_color == nil {
+            (0.0, 0.0, 0.0, 1.0)
+        } else {
+            ui_color::get_rgba(objc, host_obj.shadow_color)
+        };
+        Some((r, g, b, a * host_obj.shadow_opacity * opacity))
+    } else {
+        None
     };
-    let absolute_frame_clipped = clip_rects(clip_to, absolute_frame);
+    if let Some((r, g, b, a)) = shadow_color.filter(|&(_, _, _, a)| a > 0.0) {
+        let (x, y, w, h) = gl_rect_from_cg_rect(clip_to, scale_hack, fb_height);
+        gles.Scissor(x, y, w, h);
+        gles.Enable(gles11::BLEND);
+        gles.BlendFunc(gles11::SRC_ALPHA, gles11::ONE_MINUS_SRC_ALPHA);
+        gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+        gles.EnableClientState(gles11::VERTEX_ARRAY);
+        gles.DisableClientState(gles11::TEXTURE_COORD_ARRAY);
+        gles.Disable(gles11::TEXTURE_2D);
+        let offset = host_obj.shadow_offset;
+        for (outset, step_alpha) in shadow_steps(host_obj.shadow_radius, a) {
+            let size = CGSize {
+                width: bounds.size.width + outset * 2.0,
+                height: bounds.size.height + outset * 2.0,
+            };
+            if size.width <= 0.0 || size.height <= 0.0 {
+                continue;
+            }
+            let vertices = rounded_rect_vertices(size, host_obj.corner_radius + outset);
+            let shadow_transform =
+                CATransform3D::translation(offset.width - outset, offset.height - outset, 0.0)
+                    .concat(layer_transform);
+            gles.LoadMatrixf(shadow_transform.to_array().as_ptr());
+            gles.Color4f(r, g, b, step_alpha);
+            gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+            gles.DrawArrays(gles11::TRIANGLE_FAN, 0, (vertices.len() / 2) as _);
+            stats.draw_calls += 1;
+        }
+        gles.LoadMatrixf(layer_transform.to_array().as_ptr());
+        let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
+        gles.Scissor(x, y, w, h);
+    }
+
+    // The shape of the layer, in the co-ordinate space of layer_transform.
+    let vertices = rounded_rect_vertices(bounds.size, host_obj.corner_radius);
+    let is_rounded = host_obj.corner_radius > 0.0;
+    // The same shape as a polygon. The fan's center and closing vertex aren't
+    // part of the outline.
+    let outline: Vec<CGPoint> = if is_rounded {
+        &vertices[2..vertices.len() - 2]
+    } else {
+        &vertices[..]
+    }
+    .chunks(2)
+    .map(|xy| CGPoint { x: xy[0], y: xy[1] })
+    .collect();
 
     // Draw background color, if any
     let have_background = if host_obj.background_color == nil {
         false
     } else {
         let (r, g, b, a) = ui_color::get_rgba(objc, host_obj.background_color);
-        // TODO: fully support alpha transparency for backgrounds
+        let a = a * opacity;
         if a == 0.0 {
             false
-        } else {
-            gles.ClearColor(r * opacity, g * opacity, b * opacity, a * opacity);
-            let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
-            gles.Scissor(x, y, w, h);
+        } else if a == 1.0 && !is_rounded && is_axis_aligned {
+            // Fast path: an opaque rectangle can simply replace whatever is
+            // beneath it. The scissor rect is already the right shape.
+            gles.ClearColor(r, g, b, a);
             gles.Clear(gles11::COLOR_BUFFER_BIT);
             true
+        } else {
+            // Clearing ignores what's already in the framebuffer, so anything
+            // transparent or not rectangular has to be drawn with blending.
+            gles.Color4f(r, g, b, a);
+            gles.Enable(gles11::BLEND);
+            gles.BlendFunc(gles11::SRC_ALPHA, gles11::ONE_MINUS_SRC_ALPHA);
+
+            gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+            gles.EnableClientState(gles11::VERTEX_ARRAY);
+            gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+            gles.DisableClientState(gles11::TEXTURE_COORD_ARRAY);
+            gles.Disable(gles11::TEXTURE_2D);
+            gles.DrawArrays(gles11::TRIANGLE_FAN, 0, (vertices.len() / 2) as _);
+            stats.draw_calls += 1;
+            true
+        }
+    };
+
+    // Draw CAGradientLayer gradient, if any
+    let have_gradient = if let Some(gradient) = &host_obj.gradient_properties {
+        let mut stops: Vec<_> = gradient
+            .color_list
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| {
+                let location = match gradient.location_list {
+                    Some(ref locations) if locations.len() == gradient.color_list.len() => {
+                        locations[i]
+                    }
+                    // Stops are spread evenly by default.
+                    _ => i as CGFloat / (gradient.color_list.len() - 1).max(1) as CGFloat,
+                };
+                let (r, g, b, a) = ui_color::get_rgba(objc, color);
+                (location, (r, g, b, a * opacity))
+            })
+            .collect();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let bands = ca_gradient_layer::gradient_bands(
+            &outline,
+            bounds.size,
+            gradient.start_point,
+            gradient.end_point,
+            &stops,
+        );
+
+        gles.Color4f(1.0, 1.0, 1.0, 1.0);
+        gles.Enable(gles11::BLEND);
+        gles.BlendFunc(gles11::SRC_ALPHA, gles11::ONE_MINUS_SRC_ALPHA);
+        gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+        gles.EnableClientState(gles11::VERTEX_ARRAY);
+        gles.EnableClientState(gles11::COLOR_ARRAY);
+        gles.DisableClientState(gles11::TEXTURE_COORD_ARRAY);
+        gles.Disable(gles11::TEXTURE_2D);
+        for band in &bands {
+            gles.VertexPointer(2, gles11::FLOAT, 0, band.vertices.as_ptr() as *const GLvoid);
+            gles.ColorPointer(4, gles11::FLOAT, 0, band.colors.as_ptr() as *const GLvoid);
+            gles.DrawArrays(gles11::TRIANGLE_FAN, 0, (band.vertices.len() / 2) as _);
+            stats.draw_calls += 1;
         }
+        gles.DisableClientState(gles11::COLOR_ARRAY);
+        !bands.is_empty()
+    } else {
+        false
     };
 
     // re-borrow mutably
@@ -320,24 +908,36 @@ unsafe fn composite_layer_recursive(
         || host_obj.cg_context.is_some();
     let need_update = need_texture && !host_obj.gles_texture_is_up_to_date;
 
+    // CAEAGLLayer pixels can change every frame, so they're double-buffered:
+    // each new frame is uploaded to the texture that wasn't drawn last time,
+    // so the upload doesn't have to wait for that draw to finish. This is a
+    // copy, which is written back below.
+    let mut texture = None;
     if need_texture {
-        if let Some(texture) = host_obj.gles_texture {
-            gles.BindTexture(gles11::TEXTURE_2D, texture);
-        } else {
-            assert!(!host_obj.gles_texture_is_up_to_date);
-            let mut texture = 0;
-            gles.GenTextures(1, &mut texture);
-            gles.BindTexture(gles11::TEXTURE_2D, texture);
-            host_obj.gles_texture = Some(texture);
+        texture_memory.record_use(layer);
+        if need_update && host_obj.presented_pixels.is_some() && host_obj.gles_texture.is_some() {
+            std::mem::swap(&mut host_obj.gles_texture, &mut host_obj.gles_back_texture);
         }
+        let layer_texture = match host_obj.gles_texture {
+            Some(layer_texture) => layer_texture,
+            None => {
+                assert!(need_update);
+                let layer_texture = CompositorTexture::new(gles);
+                host_obj.gles_texture = Some(layer_texture);
+                layer_texture
+            }
+        };
+        gles.BindTexture(gles11::TEXTURE_2D, layer_texture.name);
+        texture = Some(layer_texture);
     }
 
     // Update texture with CAEAGLLayer pixels (slow path), if any
     if need_update {
         if let Some((ref mut pixels, width, height)) = host_obj.presented_pixels {
             // The pixels are always RGBA, but if the layer is opaque then the
-            // alpha channel is meant to be ignored. glTexImage2D() has no
-            // option to ignore it, so let's manually set them to 255.
+            // alpha channel is meant to be ignored. glTexImage2D() and
+            // glTexSubImage2D() have no option to ignore it, so let's manually
+            // set them to 255.
             if host_obj.opaque {
                 let mut i = 3;
                 while i < pixels.len() {
@@ -346,13 +946,25 @@ unsafe fn composite_layer_recursive(
                 }
             }
 
-            upload_rgba8_pixels(gles, pixels, (width, height));
+            upload_rgba8_pixels(gles, texture.as_mut().unwrap(), pixels, (width, height));
         }
     }
 
     // re-borrow immutably
     let host_obj = objc.borrow::<CALayerHostObject>(layer);
 
+    // The size of the texture in pixels, if any
+    let texture_size = if let Some((_, width, height)) = host_obj.presented_pixels {
+        Some((width, height))
+    } else if host_obj.contents != nil {
+        Some(cg_image::borrow_image(objc, host_obj.contents).dimensions())
+    } else {
+        host_obj.cg_context.map(|cg_context| {
+            let (width, height, _) = cg_bitmap_context::get_data(objc, cg_context);
+            (width, height)
+        })
+    };
+
     // Update texture with CGImageRef or CGContextRef pixels, if any
     if need_update {
         if host_obj.contents != nil {
@@ -360,14 +972,15 @@ unsafe fn composite_layer_recursive(
 
             // No special handling for opacity is needed here: the alpha channel
             // on an image is meaningful and won't be ignored.
-            upload_rgba8_pixels(gles, image.pixels(), image.dimensions());
+            let dimensions = image.dimensions();
+            upload_rgba8_pixels(gles, texture.as_mut().unwrap(), image.pixels(), dimensions);
         } else if let Some(cg_context) = host_obj.cg_context {
             // Make sure this is in sync with the code in ca_layer.rs that
             // sets up the context!
             let (width, height, data) = cg_bitmap_context::get_data(objc, cg_context);
             let size = width * height * 4;
             let pixels = mem.bytes_at(data.cast(), size);
-            upload_rgba8_pixels(gles, pixels, (width, height));
+            upload_rgba8_pixels(gles, texture.as_mut().unwrap(), pixels, (width, height));
         }
     }
 
@@ -375,67 +988,407 @@ unsafe fn composite_layer_recursive(
     let host_obj = objc.borrow_mut::<CALayerHostObject>(layer);
 
     if need_update {
+        host_obj.gles_texture = texture;
         host_obj.gles_texture_is_up_to_date = true;
+        let bytes = [host_obj.gles_texture, host_obj.gles_back_texture]
+            .iter()
+            .flatten()
+            .map(CompositorTexture::bytes)
+            .sum();
+        texture_memory.record_upload(layer, bytes);
     }
 
     // Draw texture, if any
-    if need_texture {
+    if let Some((texture_width, texture_height)) = texture_size {
+        // The rectangle the texture is drawn to, which is cropped to the
+        // layer's shape.
+        let contents_scale = if host_obj.presented_pixels.is_none() && host_obj.contents != nil {
+            host_obj.contents_scale
+        } else {
+            1.0
+        };
+        let contents_rect = contents_rect(
+            host_obj.contents_gravity,
+            CGSize {
+                width: texture_width as CGFloat / contents_scale,
+                height: texture_height as CGFloat / contents_scale,
+            },
+            bounds.size,
+        );
+        let CGRect {
+            origin: CGPoint { x: rx, y: ry },
+            size: CGSize {
+                width: rw,
+                height: rh,
+            },
+        } = contents_rect;
+        let mut polygon = outline.clone();
+        polygon = clip_polygon(polygon, |p| p.x - rx);
+        polygon = clip_polygon(polygon, |p| rx + rw - p.x);
+        polygon = clip_polygon(polygon, |p| p.y - ry);
+        polygon = clip_polygon(polygon, |p| ry + rh - p.y);
+        let vertices: Vec<f32> = polygon.iter().flat_map(|p| [p.x, p.y]).collect();
+
         gles.Color4f(opacity, opacity, opacity, opacity);
-        if opacity == 1.0 && host_obj.opaque && !have_background {
+        if opacity == 1.0 && host_obj.opaque && !have_background && !have_gradient && !is_rounded {
             gles.Disable(gles11::BLEND);
         } else {
             gles.Enable(gles11::BLEND);
             gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
         }
 
-        let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
-        gles.Scissor(x, y, w, h);
-        gles.Viewport(x, y, w, h);
-
         gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
-        let vertices: [f32; 12] = [
-            -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0,
-        ];
         gles.EnableClientState(gles11::VERTEX_ARRAY);
         gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
 
         // Normal images will have top-to-bottom row order, but OpenGL ES
         // expects bottom-to-top, so flip the UVs in that case.
-        let tex_coords: [f32; 12] = if host_obj.contents != nil {
-            [0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0]
-        } else {
-            [0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0]
-        };
+        let flip = host_obj.contents != nil;
+        let tex_coords: Vec<f32> = vertices
+            .chunks(2)
+            .flat_map(|xy| {
+                let u = (xy[0] - rx) / rw;
+                let v = (xy[1] - ry) / rh;
+                [u, if flip { v } else { 1.0 - v }]
+            })
+            .collect();
         gles.EnableClientState(gles11::TEXTURE_COORD_ARRAY);
         gles.TexCoordPointer(2, gles11::FLOAT, 0, tex_coords.as_ptr() as *const GLvoid);
         gles.Enable(gles11::TEXTURE_2D);
-        gles.DrawArrays(gles11::TRIANGLES, 0, 6);
+        gles.DrawArrays(gles11::TRIANGLE_FAN, 0, (vertices.len() / 2) as _);
+        stats.draw_calls += 1;
     }
 
-    // avoid holding mutable borrow while recursing
-    let sublayers = std::mem::take(&mut host_obj.sublayers);
-    for &child_layer in &sublayers {
+    // Copy the remaining properties so the borrow isn't held while calling
+    // into other objects.
+    let shape_properties = host_obj.shape_properties;
+    let border_width = host_obj.border_width;
+    let border_color = host_obj.border_color;
+    let corner_radius = host_obj.corner_radius;
+
+    // Draw CAShapeLayer path, if any. Unlike the background and contents, the
+    // path can extend outside the layer's frame, so the scissor rect is
+    // changed. masksToBounds applies to it too.
+    if let Some(shape) = shape_properties {
+        if shape.path != nil {
+            // scale_hack means there are more pixels per point.
+            let flatness = cg_path::DEFAULT_FLATNESS / scale_hack as CGFloat;
+            let subpaths = cg_path::flatten_path(objc, shape.path, flatness);
+            let mut parts = Vec::new();
+            if shape.fill_color != nil {
+                let triangles = ca_shape_layer::fill_triangles(&subpaths);
+                parts.push((shape.fill_color, triangles));
+            }
+            // The stroke is drawn on top of the fill.
+            if shape.stroke_color != nil {
+                let triangles = ca_shape_layer::stroke_triangles(
+                    &subpaths,
+                    shape.line_width,
+                    shape.stroke_start,
+                    shape.stroke_end,
+                );
+                parts.push((shape.stroke_color, triangles));
+            }
+
+            // The path is in the bounds co-ordinate space.
+            gles.LoadMatrixf(sublayer_transform.to_array().as_ptr());
+            let (x, y, w, h) = gl_rect_from_cg_rect(sublayer_clip_to, scale_hack, fb_height);
+            gles.Scissor(x, y, w, h);
+            gles.Enable(gles11::BLEND);
+            gles.BlendFunc(gles11::SRC_ALPHA, gles11::ONE_MINUS_SRC_ALPHA);
+            gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+            gles.EnableClientState(gles11::VERTEX_ARRAY);
+            gles.DisableClientState(gles11::TEXTURE_COORD_ARRAY);
+            gles.Disable(gles11::TEXTURE_2D);
+
+            for (color, vertices) in parts {
+                let (r, g, b, a) = ui_color::get_rgba(objc, color);
+                let a = a * opacity;
+                if a == 0.0 {
+                    continue;
+                }
+                gles.Color4f(r, g, b, a);
+                gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+                gles.DrawArrays(gles11::TRIANGLES, 0, (vertices.len() / 2) as _);
+                stats.draw_calls += 1;
+            }
+        }
+    }
+
+    // This is a copy, so the borrow isn't held while recursing.
+    let sublayers = sublayers_in_drawing_order(objc, layer);
+    for child_layer in sublayers {
         composite_layer_recursive(
             gles,
             objc,
             mem,
+            occluded,
             child_layer,
-            /* origin: */
-            CGPoint {
-                x: absolute_frame.origin.x - bounds.origin.x,
-                y: absolute_frame.origin.y - bounds.origin.y,
-            },
-            // TODO: clipping goes here (when masksToBounds is implemented)
-            clip_to,
+            /* parent_transform: */ sublayer_transform,
+            /* clip_to: */ sublayer_clip_to,
             opacity,
             scale_hack,
             fb_height,
+            texture_memory,
+            stats,
         )
     }
-    objc.borrow_mut::<CALayerHostObject>(layer).sublayers = sublayers;
+
+    // Draw border, if any. This goes on top of the sublayers.
+    if border_width > 0.0 {
+        let (r, g, b, a) = if border_color == nil {
+            (0.0, 0.0, 0.0, 1.0)
+        } else {
+            ui_color::get_rgba(objc, border_color)
+        };
+        let a = a * opacity;
+        if a != 0.0 {
+            let vertices = border_vertices(bounds.size, corner_radius, border_width);
+
+            gles.MatrixMode(gles11::MODELVIEW);
+            gles.LoadMatrixf(layer_transform.to_array().as_ptr());
+            let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
+            gles.Scissor(x, y, w, h);
+            gles.Color4f(r, g, b, a);
+            gles.Enable(gles11::BLEND);
+            gles.BlendFunc(gles11::SRC_ALPHA, gles11::ONE_MINUS_SRC_ALPHA);
+            gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+            gles.EnableClientState(gles11::VERTEX_ARRAY);
+            gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+            gles.DisableClientState(gles11::TEXTURE_COORD_ARRAY);
+            gles.Disable(gles11::TEXTURE_2D);
+            gles.DrawArrays(gles11::TRIANGLE_STRIP, 0, (vertices.len() / 2) as _);
+            stats.draw_calls += 1;
+        }
+    }
+}
+
+/// Get the axis-aligned bounding box of a layer's rectangle on the screen.
+fn transformed_bounding_box(transform: CATransform3D, size: CGSize) -> CGRect {
+    let corners = [
+        (0.0, 0.0),
+        (size.width, 0.0),
+        (size.width, size.height),
+        (0.0, size.height),
+    ];
+    let (mut min_x, mut min_y) = (CGFloat::INFINITY, CGFloat::INFINITY);
+    let (mut max_x, mut max_y) = (CGFloat::NEG_INFINITY, CGFloat::NEG_INFINITY);
+    for (x, y) in corners {
+        let (x, y) = transform.apply_to_point(x, y);
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+    CGRect {
+        origin: CGPoint { x: min_x, y: min_y },
+        size: CGSize {
+            width: max_x - min_x,
+            height: max_y - min_y,
+        },
+    }
+}
+
+/// Draw a simple status bar (background and clock) along the top edge of the
+/// interface, which is a side of the screen in landscape orientations.
+///
+/// TODO: Draw other status bar items, like the battery indicator?
+unsafe fn draw_status_bar(
+    gles: &mut dyn GLES,
+    status_bar: &mut Option<StatusBar>,
+    contents: StatusBarContents,
+    screen_size: CGSize,
+    scale_hack: u32,
+    fb_height: u32,
+) {
+    let orientation = contents.2;
+    let is_landscape = matches!(
+        orientation,
+        UIInterfaceOrientationLandscapeLeft | UIInterfaceOrientationLandscapeRight
+    );
+    // The length of the status bar, i.e. the width of the interface.
+    let length = if is_landscape {
+        screen_size.height
+    } else {
+        screen_size.width
+    };
+
+    let status_bar = status_bar.get_or_insert_with(|| StatusBar {
+        font: Font::sans_bold(),
+        texture: CompositorTexture::new(gles),
+        drawn: None,
+    });
+    gles.BindTexture(gles11::TEXTURE_2D, status_bar.texture.name);
+
+    // There's no need to redraw the texture unless the clock has changed.
+    if status_bar.drawn.as_ref() != Some(&contents) {
+        let width = length as u32 * scale_hack;
+        let height = STATUS_BAR_HEIGHT as u32 * scale_hack;
+        let pixels = draw_status_bar_pixels(
+            &status_bar.font,
+            contents.0,
+            &contents.1,
+            (width, height),
+            scale_hack,
+        );
+        upload_rgba8_pixels(gles, &mut status_bar.texture, &pixels, (width, height));
+        status_bar.drawn = Some(contents);
+    }
+
+    // The texture is drawn rotated so its top points towards the top of the
+    // interface. The texture co-ordinates are for the corners of the rect in
+    // the order bottom-left, bottom-right, top-right, top-left (as seen in the
+    // portrait framebuffer).
+    let (rect, tex_coords): (CGRect, [f32; 8]) = match orientation {
+        UIInterfaceOrientationLandscapeLeft => (
+            CGRect {
+                origin: CGPoint { x: 0.0, y: 0.0 },
+                size: CGSize {
+                    width: STATUS_BAR_HEIGHT,
+                    height: length,
+                },
+            },
+            [0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0],
+        ),
+        UIInterfaceOrientationLandscapeRight => (
+            CGRect {
+                origin: CGPoint {
+                    x: screen_size.width - STATUS_BAR_HEIGHT,
+                    y: 0.0,
+                },
+                size: CGSize {
+                    width: STATUS_BAR_HEIGHT,
+                    height: length,
+                },
+            },
+            [1.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0],
+        ),
+        // TODO: upside-down portrait
+        _ => (
+            CGRect {
+                origin: CGPoint { x: 0.0, y: 0.0 },
+                size: CGSize {
+                    width: length,
+                    height: STATUS_BAR_HEIGHT,
+                },
+            },
+            [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0],
+        ),
+    };
+    let (x, y, w, h) = gl_rect_from_cg_rect(rect, scale_hack, fb_height);
+    gles.Scissor(x, y, w, h);
+    gles.Viewport(x, y, w, h);
+
+    gles.Color4f(1.0, 1.0, 1.0, 1.0);
+    gles.Enable(gles11::BLEND);
+    gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
+
+    gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+    let vertices: [f32; 8] = [-1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0];
+    gles.EnableClientState(gles11::VERTEX_ARRAY);
+    gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+    gles.EnableClientState(gles11::TEXTURE_COORD_ARRAY);
+    gles.TexCoordPointer(2, gles11::FLOAT, 0, tex_coords.as_ptr() as *const GLvoid);
+    gles.Enable(gles11::TEXTURE_2D);
+    gles.DrawArrays(gles11::TRIANGLE_FAN, 0, 4);
+}
+
+/// Get the time to display in the status bar, e.g. "9:41 AM".
+fn status_bar_clock_text(env: &Environment) -> String {
+    // TODO: don't assume local time is UTC? (The same assumption is made by
+    // our localtime().)
+    let timestamp = env
+        .clock
+        .system_now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let minute_of_day = (timestamp / 60) % (24 * 60);
+    let (hour, minute) = (minute_of_day / 60, minute_of_day % 60);
+    let (hour, am_pm) = match hour {
+        0 => (12, "AM"),
+        1..=11 => (hour, "AM"),
+        12 => (12, "PM"),
+        _ => (hour - 12, "PM"),
+    };
+    format!("{}:{:02} {}", hour, minute, am_pm)
 }
 
-unsafe fn upload_rgba8_pixels(gles: &mut dyn GLES, pixels: &[u8], dimensions: (u32, u32)) {
+/// Draw the status bar into a buffer of premultiplied RGBA pixels, with rows in
+/// bottom-to-top order.
+fn draw_status_bar_pixels(
+    font: &Font,
+    style: UIStatusBarStyle,
+    clock_text: &str,
+    (width, height): (u32, u32),
+    scale_hack: u32,
+) -> Vec<u8> {
+    let (background, foreground): ([f32; 4], [f32; 4]) = match style {
+        UIStatusBarStyleBlackOpaque => ([0.0, 0.0, 0.0, 1.0], [1.0; 4]),
+        UIStatusBarStyleBlackTranslucent => ([0.0, 0.0, 0.0, 0.5], [1.0; 4]),
+        // UIStatusBarStyleDefault is grey with black text
+        _ => ([0.69, 0.72, 0.76, 1.0], [0.0, 0.0, 0.0, 1.0]),
+    };
+    let [r, g, b, a] = background;
+    let background = [r * a, g * a, b * a, a];
+
+    let mut pixels: Vec<f32> = background
+        .iter()
+        .copied()
+        .cycle()
+        .take((width * height * 4) as usize)
+        .collect();
+
+    let font_size = 14.0 * scale_hack as f32;
+    let (_, text_height) = font.calculate_text_size(font_size, clock_text, None, None);
+    font.draw(
+        font_size,
+        clock_text,
+        (width as f32 / 2.0, (height as f32 - text_height) / 2.0),
+        None,
+        None,
+        TextAlignment::Center,
+        |(x, y), coverage| {
+            if x < 0 || y < 0 || x as u32 >= width || y as u32 >= height {
+                return;
+            }
+            // The font code's y co-ordinates point downwards.
+            let row = height - 1 - y as u32;
+            let idx = ((row * width + x as u32) * 4) as usize;
+            for (pixel, fg) in pixels[idx..idx + 4].iter_mut().zip(foreground) {
+                *pixel = fg * coverage + *pixel * (1.0 - coverage);
+            }
+        },
+    );
+
+    pixels
+        .into_iter()
+        .map(|c| (c * 255.0).round() as u8)
+        .collect()
+}
+
+/// Upload pixels to a texture, which must be bound.
+unsafe fn upload_rgba8_pixels(
+    gles: &mut dyn GLES,
+    texture: &mut CompositorTexture,
+    pixels: &[u8],
+    dimensions: (u32, u32),
+) {
+    if texture.size == Some(dimensions) {
+        gles.TexSubImage2D(
+            gles11::TEXTURE_2D,
+            0,
+            0,
+            0,
+            dimensions.0 as _,
+            dimensions.1 as _,
+            gles11::RGBA,
+            gles11::UNSIGNED_BYTE,
+            pixels.as_ptr() as *const _,
+        );
+        return;
+    }
+    texture.size = Some(dimensions);
     gles.TexImage2D(
         gles11::TEXTURE_2D,
         0,
@@ -459,6 +1412,176 @@ unsafe fn upload_rgba8_pixels(gles: &mut dyn GLES, pixels: &[u8], dimensions: (u
     );
 }
 
+/// Generate the vertices of a rectangle with rounded corners as a triangle fan.
+/// The rectangle has its top-left corner at (0, 0), with y pointing down.
+/// The radius is clamped so the result is at most a capsule shape.
+/// Number of line segments used to approximate each rounded corner.
+const SEGMENTS_PER_CORNER: u32 = 8;
+
+fn rounded_rect_vertices(size: CGSize, corner_radius: CGFloat) -> Vec<f32> {
+    let CGSize { width, height } = size;
+    let radius = corner_radius.min(width / 2.0).min(height / 2.0);
+    if radius <= 0.0 || radius.is_nan() {
+        return vec![0.0, 0.0, width, 0.0, width, height, 0.0, height];
+    }
+
+    // The fan starts at the center and goes clockwise (on screen) around the
+    // corners, starting at the bottom right.
+    let mut vertices = vec![width / 2.0, height / 2.0];
+    let corner_centers = [
+        (width - radius, height - radius),
+        (radius, height - radius),
+        (radius, radius),
+        (width - radius, radius),
+    ];
+    for (i, (cx, cy)) in corner_centers.into_iter().enumerate() {
+        let start_angle = i as f32 * std::f32::consts::FRAC_PI_2;
+        for j in 0..=SEGMENTS_PER_CORNER {
+            let angle =
+                start_angle + std::f32::consts::FRAC_PI_2 * (j as f32 / SEGMENTS_PER_CORNER as f32);
+            vertices.push(cx + radius * angle.cos());
+            vertices.push(cy + radius * angle.sin());
+        }
+    }
+    // Close the fan.
+    vertices.push(vertices[2]);
+    vertices.push(vertices[3]);
+    vertices
+}
+
+/// Get the rectangle, in the layer's co-ordinate space, that contents of a
+/// certain size should be drawn to.
+fn contents_rect(gravity: ContentsGravity, contents_size: CGSize, bounds_size: CGSize) -> CGRect {
+    let resize = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: bounds_size,
+    };
+    if contents_size.width <= 0.0 || contents_size.height <= 0.0 {
+        return resize;
+    }
+
+    let size = match gravity {
+        ContentsGravity::Resize => return resize,
+        ContentsGravity::ResizeAspect | ContentsGravity::ResizeAspectFill => {
+            let scale_x = bounds_size.width / contents_size.width;
+            let scale_y = bounds_size.height / contents_size.height;
+            let scale = if gravity == ContentsGravity::ResizeAspect {
+                scale_x.min(scale_y)
+            } else {
+                scale_x.max(scale_y)
+            };
+            CGSize {
+                width: contents_size.width * scale,
+                height: contents_size.height * scale,
+            }
+        }
+        _ => contents_size,
+    };
+
+    let x = match gravity {
+        ContentsGravity::Left | ContentsGravity::TopLeft | ContentsGravity::BottomLeft => 0.0,
+        ContentsGravity::Right | ContentsGravity::TopRight | ContentsGravity::BottomRight => {
+            bounds_size.width - size.width
+        }
+        _ => (bounds_size.width - size.width) / 2.0,
+    };
+    // The gravity is defined as if y pointed up, like on macOS, so on iPhone OS
+    // "top" is at the bottom of the screen.
+    let y = match gravity {
+        ContentsGravity::Top | ContentsGravity::TopLeft | ContentsGravity::TopRight => {
+            bounds_size.height - size.height
+        }
+        ContentsGravity::Bottom | ContentsGravity::BottomLeft | ContentsGravity::BottomRight => 0.0,
+        _ => (bounds_size.height - size.height) / 2.0,
+    };
+    CGRect {
+        origin: CGPoint { x, y },
+        size,
+    }
+}
+
+/// Clip a convex polygon to the half-plane where `distance` is not negative
+/// (Sutherland-Hodgman). `distance` must be linear.
+pub(super) fn clip_polygon(
+    polygon: Vec<CGPoint>,
+    distance: impl Fn(CGPoint) -> CGFloat,
+) -> Vec<CGPoint> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let (da, db) = (distance(a), distance(b));
+        if da >= 0.0 {
+            clipped.push(a);
+        }
+        if (da > 0.0 && db < 0.0) || (da < 0.0 && db > 0.0) {
+            let t = da / (da - db);
+            clipped.push(CGPoint {
+                x: a.x + (b.x - a.x) * t,
+                y: a.y + (b.y - a.y) * t,
+            });
+        }
+    }
+    clipped
+}
+
+/// Number of overlapping shapes used to approximate a blurred shadow.
+const SHADOW_STEPS: u32 = 4;
+
+/// Approximate a shadow blur by drawing the shape several times, each grown by
+/// a different amount and drawn with a low alpha, so the edge fades out. Each
+/// step is a pair of the amount to grow the shape by on each side and the alpha
+/// to draw it with. Where all the steps overlap, the final alpha is `alpha`.
+fn shadow_steps(radius: CGFloat, alpha: f32) -> Vec<(CGFloat, f32)> {
+    if radius <= 0.0 {
+        return vec![(0.0, alpha)];
+    }
+    // Alpha blending n times with alpha x gives a total of 1 - (1 - x)^n.
+    let step_alpha = 1.0 - (1.0 - alpha).powf(1.0 / SHADOW_STEPS as f32);
+    (0..SHADOW_STEPS)
+        .map(|i| {
+            let outset = radius * ((2 * i + 1) as CGFloat / SHADOW_STEPS as CGFloat - 1.0);
+            (outset, step_alpha)
+        })
+        .collect()
+}
+
+/// Get the vertices for drawing a border of a certain width just inside the
+/// edges of a (possibly rounded) rectangle, as a triangle strip.
+fn border_vertices(size: CGSize, corner_radius: CGFloat, border_width: CGFloat) -> Vec<f32> {
+    let CGSize { width, height } = size;
+    let border_width = border_width.min(width / 2.0).min(height / 2.0);
+    let radius = corner_radius.min(width / 2.0).min(height / 2.0).max(0.0);
+    // A corner that is less rounded than the border is wide has a sharp inner
+    // edge, so all its inner vertices are in the same place.
+    let inner_radius = (radius - border_width).max(0.0);
+
+    // The strip alternates between the outer and inner edges, going around
+    // the corners in the same order as rounded_rect_vertices.
+    let mut vertices = Vec::new();
+    let corner_centers = [
+        (width - radius, height - radius),
+        (radius, height - radius),
+        (radius, radius),
+        (width - radius, radius),
+    ];
+    for (i, (cx, cy)) in corner_centers.into_iter().enumerate() {
+        let inner_cx = cx.clamp(border_width, width - border_width);
+        let inner_cy = cy.clamp(border_width, height - border_width);
+        let start_angle = i as f32 * std::f32::consts::FRAC_PI_2;
+        for j in 0..=SEGMENTS_PER_CORNER {
+            let angle =
+                start_angle + std::f32::consts::FRAC_PI_2 * (j as f32 / SEGMENTS_PER_CORNER as f32);
+            let (sin, cos) = angle.sin_cos();
+            vertices.extend_from_slice(&[cx + radius * cos, cy + radius * sin]);
+            vertices
+                .extend_from_slice(&[inner_cx + inner_radius * cos, inner_cy + inner_radius * sin]);
+        }
+    }
+    // Close the strip.
+    vertices.extend_from_within(0..4);
+    vertices
+}
+
 fn clip_rects(a_clip: CGRect, b_clip: CGRect) -> CGRect {
     let a_x1 = a_clip.origin.x;
     let a_y1 = a_clip.origin.y;
@@ -483,6 +1606,15 @@ fn clip_rects(a_clip: CGRect, b_clip: CGRect) -> CGRect {
     }
 }
 
+fn gl_rect_contains(
+    outer: (GLint, GLint, GLint, GLint),
+    inner: (GLint, GLint, GLint, GLint),
+) -> bool {
+    let (ox, oy, ow, oh) = outer;
+    let (ix, iy, iw, ih) = inner;
+    ix >= ox && iy >= oy && ix + iw <= ox + ow && iy + ih <= oy + oh
+}
+
 fn gl_rect_from_cg_rect(
     rect: CGRect,
     scale_hack: u32,
@@ -495,3 +1627,257 @@ fn gl_rect_from_cg_rect(
     // y points up in OpenGL ES, but down in UIKit and Core Animation
     (x, fb_height as GLint - h - y, w, h)
 }
+
+#[cfg(test)]
+#[test]
+fn test_clip_rects() {
+    fn rect(x: CGFloat, y: CGFloat, width: CGFloat, height: CGFloat) -> CGRect {
+        CGRect {
+            origin: CGPoint { x, y },
+            size: CGSize { width, height },
+        }
+    }
+
+    // Overlapping rects of different sizes: the extent of each rect must come
+    // from its own size.
+    assert_eq!(
+        clip_rects(rect(0.0, 0.0, 100.0, 50.0), rect(20.0, 10.0, 30.0, 200.0)),
+        rect(20.0, 10.0, 30.0, 40.0)
+    );
+    assert_eq!(
+        clip_rects(rect(20.0, 10.0, 30.0, 200.0), rect(0.0, 0.0, 100.0, 50.0)),
+        rect(20.0, 10.0, 30.0, 40.0)
+    );
+    // Disjoint rects
+    assert_eq!(
+        clip_rects(rect(0.0, 0.0, 10.0, 10.0), rect(20.0, 20.0, 10.0, 10.0)).size,
+        CGSize {
+            width: 0.0,
+            height: 0.0
+        }
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_contents_rect() {
+    fn rect(x: CGFloat, y: CGFloat, width: CGFloat, height: CGFloat) -> CGRect {
+        CGRect {
+            origin: CGPoint { x, y },
+            size: CGSize { width, height },
+        }
+    }
+
+    let contents = CGSize {
+        width: 100.0,
+        height: 50.0,
+    };
+    let bounds = CGSize {
+        width: 200.0,
+        height: 200.0,
+    };
+    assert_eq!(
+        contents_rect(ContentsGravity::Resize, contents, bounds),
+        rect(0.0, 0.0, 200.0, 200.0)
+    );
+    // Letterboxed
+    assert_eq!(
+        contents_rect(ContentsGravity::ResizeAspect, contents, bounds),
+        rect(0.0, 50.0, 200.0, 100.0)
+    );
+    // Cropped
+    assert_eq!(
+        contents_rect(ContentsGravity::ResizeAspectFill, contents, bounds),
+        rect(-100.0, 0.0, 400.0, 200.0)
+    );
+    assert_eq!(
+        contents_rect(ContentsGravity::Center, contents, bounds),
+        rect(50.0, 75.0, 100.0, 50.0)
+    );
+    assert_eq!(
+        contents_rect(ContentsGravity::TopLeft, contents, bounds),
+        rect(0.0, 150.0, 100.0, 50.0)
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_shadow_steps() {
+    assert_eq!(shadow_steps(0.0, 0.5), vec![(0.0, 0.5)]);
+
+    let steps = shadow_steps(4.0, 0.5);
+    assert_eq!(steps.len(), SHADOW_STEPS as usize);
+    // The blur extends equally inwards and outwards.
+    assert_eq!(steps.first().unwrap().0, -steps.last().unwrap().0);
+    let total = 1.0 - steps.iter().map(|&(_, a)| 1.0 - a).product::<f32>();
+    assert!((total - 0.5).abs() < 0.0001);
+}
+
+#[cfg(test)]
+#[test]
+fn test_border_vertices() {
+    let size = CGSize {
+        width: 100.0,
+        height: 50.0,
+    };
+    // Square corners: the outer vertices are the rectangle's corners and the
+    // inner vertices are inset by the border width.
+    let vertices = border_vertices(size, 0.0, 2.0);
+    let per_corner = (SEGMENTS_PER_CORNER as usize + 1) * 4;
+    assert_eq!(vertices.len(), per_corner * 4 + 4);
+    assert_eq!(&vertices[..4], &[100.0, 50.0, 98.0, 48.0]);
+    assert_eq!(
+        &vertices[per_corner * 2..per_corner * 2 + 4],
+        &[0.0, 0.0, 2.0, 2.0]
+    );
+    // The strip ends where it started.
+    assert_eq!(&vertices[vertices.len() - 4..], &vertices[..4]);
+
+    // Rounded corners: every vertex is inside the rectangle, with the inner
+    // ones nearer the middle.
+    let vertices = border_vertices(size, 10.0, 2.0);
+    for pair in vertices.chunks(4) {
+        let (outer, inner) = ((pair[0], pair[1]), (pair[2], pair[3]));
+        assert!(outer.0 >= 0.0 && outer.0 <= 100.0 && outer.1 >= 0.0 && outer.1 <= 50.0);
+        // The tolerance is for vertices at the ends of a corner's arc, where
+        // the inner and outer vertices are level.
+        assert!((inner.0 - 50.0).abs() <= (outer.0 - 50.0).abs() + 1e-4);
+        assert!((inner.1 - 25.0).abs() <= (outer.1 - 25.0).abs() + 1e-4);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_occlusion_culling_fill() {
+    use super::ca_transform_3d::CATransform3DIdentity;
+
+    const WIDTH: CGFloat = 320.0;
+    const HEIGHT: CGFloat = 480.0;
+    const DEPTH: usize = 10;
+
+    let mut mem = Mem::new();
+    let mut objc = ObjC::new();
+    objc.register_host_selectors(&mut mem);
+    let class = objc.get_known_class("CALayer", &mut mem);
+
+    let mut new_layer = |objc: &mut ObjC, superlayer: id, frame: CGRect, opaque: bool| {
+        let layer = objc.alloc_object(
+            class,
+            Box::new(CALayerHostObject {
+                bounds: CGRect {
+                    origin: CGPoint { x: 0.0, y: 0.0 },
+                    size: frame.size,
+                },
+                position: CGPoint {
+                    x: frame.origin.x + frame.size.width / 2.0,
+                    y: frame.origin.y + frame.size.height / 2.0,
+                },
+                opaque,
+                presented_pixels: Some((Vec::new(), 1, 1)),
+                superlayer,
+                ..Default::default()
+            }),
+            &mut mem,
+        );
+        if superlayer != nil {
+            objc.borrow_mut::<CALayerHostObject>(superlayer)
+                .sublayers
+                .push(layer);
+        }
+        layer
+    };
+
+    let screen = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: CGSize {
+            width: WIDTH,
+            height: HEIGHT,
+        },
+    };
+    let inset = CGRect {
+        origin: CGPoint { x: 10.0, y: 10.0 },
+        size: CGSize {
+            width: 100.0,
+            height: 100.0,
+        },
+    };
+
+    // A stack of full-screen opaque layers, like nested views that each draw
+    // a background, with a transparent overlay and a small opaque layer on
+    // top.
+    let root = new_layer(&mut objc, nil, screen, true);
+    let mut chain = vec![root];
+    for _ in 0..DEPTH {
+        let layer = new_layer(&mut objc, *chain.last().unwrap(), screen, true);
+        chain.push(layer);
+    }
+    let deepest = *chain.last().unwrap();
+    let overlay = new_layer(&mut objc, deepest, screen, false);
+    let small = new_layer(&mut objc, deepest, inset, true);
+
+    let mut occluded = HashSet::new();
+    find_occluded_layers(
+        &objc,
+        root,
+        CATransform3DIdentity,
+        screen,
+        1.0,
+        1,
+        HEIGHT as u32,
+        &mut Vec::new(),
+        &mut occluded,
+    );
+
+    // Everything below the deepest full-screen opaque layer is covered by it.
+    for &layer in &chain[..DEPTH] {
+        assert!(occluded.contains(&layer));
+    }
+    assert!(!occluded.contains(&deepest));
+    assert!(!occluded.contains(&overlay));
+    assert!(!occluded.contains(&small));
+
+    /// Count the layers drawn and the pixels they fill, like
+    /// [composite_layer_recursive] does.
+    fn fill(
+        objc: &ObjC,
+        occluded: &HashSet<id>,
+        layer: id,
+        parent_transform: CATransform3D,
+        clip_to: CGRect,
+    ) -> (u32, i64) {
+        let host_obj = objc.borrow::<CALayerHostObject>(layer);
+        let geometry = layer_geometry(host_obj, parent_transform, clip_to, 1.0);
+        let mut totals = if occluded.contains(&layer) {
+            (0, 0)
+        } else {
+            let (_, _, w, h) =
+                gl_rect_from_cg_rect(geometry.absolute_frame_clipped, 1, HEIGHT as u32);
+            (1, w as i64 * h as i64)
+        };
+        for child_layer in sublayers_in_drawing_order(objc, layer) {
+            let (layers, pixels) = fill(
+                objc,
+                occluded,
+                child_layer,
+                geometry.sublayer_transform,
+                geometry.sublayer_clip_to,
+            );
+            totals.0 += layers;
+            totals.1 += pixels;
+        }
+        totals
+    }
+
+    let (layers_unculled, pixels_unculled) =
+        fill(&objc, &HashSet::new(), root, CATransform3DIdentity, screen);
+    let (layers_culled, pixels_culled) =
+        fill(&objc, &occluded, root, CATransform3DIdentity, screen);
+    let screen_pixels = (WIDTH * HEIGHT) as i64;
+    assert_eq!(layers_unculled, DEPTH as u32 + 3);
+    assert_eq!(
+        pixels_unculled,
+        (DEPTH as i64 + 2) * screen_pixels + 100 * 100
+    );
+    assert_eq!(layers_culled, 3);
+    assert_eq!(pixels_culled, 2 * screen_pixels + 100 * 100);
+}
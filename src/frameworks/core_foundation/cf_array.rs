@@ -9,28 +9,73 @@
 //! implementation. Here they are the same types.
 
 use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_type::standard_callbacks;
 use super::CFIndex;
-use crate::dyld::{export_c_func, FunctionExports};
+use crate::abi::GuestFunction;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
 use crate::frameworks::foundation::NSUInteger;
-use crate::mem::ConstVoidPtr;
+use crate::mem::{ConstPtr, ConstVoidPtr, SafeRead};
 use crate::objc::{id, msg, msg_class};
 use crate::Environment;
 
-#[allow(dead_code)]
 pub type CFArrayRef = super::CFTypeRef;
 pub type CFMutableArrayRef = super::CFTypeRef;
 
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct CFArrayCallBacks {
+    version: CFIndex,
+    retain: GuestFunction,
+    release: GuestFunction,
+    copy_description: GuestFunction,
+    equal: GuestFunction,
+}
+unsafe impl SafeRead for CFArrayCallBacks {}
+
+fn kCFTypeArrayCallBacks(env: &mut Environment) -> ConstVoidPtr {
+    let [retain, release, equal, _hash] = standard_callbacks(env);
+    env.mem
+        .alloc_and_write(CFArrayCallBacks {
+            version: 0,
+            retain,
+            release,
+            copy_description: GuestFunction::from_addr_with_thumb_bit(0), // TODO
+            equal,
+        })
+        .cast()
+        .cast_const()
+}
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_kCFTypeArrayCallBacks",
+    HostConstant::CustomWithEnv(kCFTypeArrayCallBacks),
+)];
+
 fn CFArrayCreateMutable(
     env: &mut Environment,
     allocator: CFAllocatorRef,
     capacity: CFIndex,
-    callbacks: ConstVoidPtr, // TODO, should be `const CFArrayCallBacks*`
+    callbacks: ConstPtr<CFArrayCallBacks>,
 ) -> CFMutableArrayRef {
     assert!(allocator == kCFAllocatorDefault); // unimplemented
     assert!(capacity == 0); // TODO: fixed capacity support
-    assert!(callbacks.is_null()); // TODO: support retaining etc
 
-    msg_class![env; _touchHLE_NSMutableArray_non_retaining new]
+    // NULL callbacks, or callbacks without retain and release functions, mean
+    // the values aren't necessarily Objective-C objects and must not be
+    // retained.
+    let retains = !callbacks.is_null() && {
+        let CFArrayCallBacks {
+            retain, release, ..
+        } = env.mem.read(callbacks);
+        retain.addr_with_thumb_bit() != 0 || release.addr_with_thumb_bit() != 0
+    };
+    if retains {
+        // TODO: support custom callbacks. For now they are assumed to behave
+        // like kCFTypeArrayCallBacks, which is by far the most common case.
+        msg_class![env; _touchHLE_NSMutableArray new]
+    } else {
+        msg_class![env; _touchHLE_NSMutableArray_non_retaining new]
+    }
 }
 
 fn CFArrayGetCount(env: &mut Environment, array: CFArrayRef) -> CFIndex {
@@ -35,6 +35,7 @@ fn CFBundleCopyResourceURL(
     let url: CFURLRef = msg![env; bundle URLForResource:resource_name
                                           withExtension:resource_type
                                            subdirectory:sub_dir_name];
+    // This is nil if the resource doesn't exist, and so is the copy.
     msg![env; url copy]
 }
 
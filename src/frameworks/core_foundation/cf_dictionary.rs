@@ -0,0 +1,164 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFDictionary` and `CFMutableDictionary`.
+//!
+//! These are toll-free bridged to `NSDictionary` and `NSMutableDictionary` in
+//! Apple's implementation. Here they are the same types.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_type::standard_callbacks;
+use super::CFIndex;
+use crate::abi::GuestFunction;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::{ns_dictionary, NSUInteger};
+use crate::mem::{ConstPtr, ConstVoidPtr, SafeRead};
+use crate::objc::{id, msg, msg_class};
+use crate::Environment;
+
+pub type CFDictionaryRef = super::CFTypeRef;
+pub type CFMutableDictionaryRef = super::CFTypeRef;
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct CFDictionaryKeyCallBacks {
+    version: CFIndex,
+    retain: GuestFunction,
+    release: GuestFunction,
+    copy_description: GuestFunction,
+    equal: GuestFunction,
+    hash: GuestFunction,
+}
+unsafe impl SafeRead for CFDictionaryKeyCallBacks {}
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct CFDictionaryValueCallBacks {
+    version: CFIndex,
+    retain: GuestFunction,
+    release: GuestFunction,
+    copy_description: GuestFunction,
+    equal: GuestFunction,
+}
+unsafe impl SafeRead for CFDictionaryValueCallBacks {}
+
+fn kCFTypeDictionaryKeyCallBacks(env: &mut Environment) -> ConstVoidPtr {
+    let [retain, release, equal, hash] = standard_callbacks(env);
+    env.mem
+        .alloc_and_write(CFDictionaryKeyCallBacks {
+            version: 0,
+            retain,
+            release,
+            copy_description: GuestFunction::from_addr_with_thumb_bit(0), // TODO
+            equal,
+            hash,
+        })
+        .cast()
+        .cast_const()
+}
+fn kCFTypeDictionaryValueCallBacks(env: &mut Environment) -> ConstVoidPtr {
+    let [retain, release, equal, _hash] = standard_callbacks(env);
+    env.mem
+        .alloc_and_write(CFDictionaryValueCallBacks {
+            version: 0,
+            retain,
+            release,
+            copy_description: GuestFunction::from_addr_with_thumb_bit(0), // TODO
+            equal,
+        })
+        .cast()
+        .cast_const()
+}
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFTypeDictionaryKeyCallBacks",
+        HostConstant::CustomWithEnv(kCFTypeDictionaryKeyCallBacks),
+    ),
+    (
+        "_kCFTypeDictionaryValueCallBacks",
+        HostConstant::CustomWithEnv(kCFTypeDictionaryValueCallBacks),
+    ),
+];
+
+fn CFDictionaryCreateMutable(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    _capacity: CFIndex, // TODO: fixed capacity support
+    key_callbacks: ConstPtr<CFDictionaryKeyCallBacks>,
+    value_callbacks: ConstPtr<CFDictionaryValueCallBacks>,
+) -> CFMutableDictionaryRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+
+    // NULL callbacks, or callbacks without retain and hash functions, mean the
+    // keys and values aren't necessarily Objective-C objects and must not be
+    // retained, copied or sent hash/isEqual: messages.
+    let keys_are_objects = !key_callbacks.is_null() && {
+        let CFDictionaryKeyCallBacks { retain, hash, .. } = env.mem.read(key_callbacks);
+        retain.addr_with_thumb_bit() != 0 && hash.addr_with_thumb_bit() != 0
+    };
+    let values_are_objects = !value_callbacks.is_null() && {
+        let CFDictionaryValueCallBacks { retain, .. } = env.mem.read(value_callbacks);
+        retain.addr_with_thumb_bit() != 0
+    };
+    if keys_are_objects && values_are_objects {
+        // TODO: support custom callbacks. For now they are assumed to behave
+        // like kCFTypeDictionaryKeyCallBacks and
+        // kCFTypeDictionaryValueCallBacks, which is by far the most common
+        // case.
+        msg_class![env; _touchHLE_NSMutableDictionary new]
+    } else {
+        if keys_are_objects || values_are_objects {
+            // TODO: retain only the keys or only the values.
+            log!("TODO: CFDictionaryCreateMutable() with object keys or values but not both, neither will be retained");
+        }
+        msg_class![env; _touchHLE_NSMutableDictionary_non_retaining new]
+    }
+}
+
+fn CFDictionaryGetCount(env: &mut Environment, dict: CFDictionaryRef) -> CFIndex {
+    let count: NSUInteger = msg![env; dict count];
+    count.try_into().unwrap()
+}
+
+fn CFDictionaryGetValue(
+    env: &mut Environment,
+    dict: CFDictionaryRef,
+    key: ConstVoidPtr,
+) -> ConstVoidPtr {
+    let key: id = key.cast().cast_mut();
+    let value: id = msg![env; dict objectForKey:key];
+    value.cast().cast_const()
+}
+
+fn CFDictionarySetValue(
+    env: &mut Environment,
+    dict: CFMutableDictionaryRef,
+    key: ConstVoidPtr,
+    value: ConstVoidPtr,
+) {
+    // Unlike NSMutableDictionary, kCFTypeDictionaryKeyCallBacks only retains
+    // the key.
+    ns_dictionary::set_object_for_key(
+        env,
+        dict,
+        value.cast().cast_mut(),
+        key.cast().cast_mut(),
+        /* copy_key: */ false,
+    );
+}
+
+fn CFDictionaryRemoveValue(env: &mut Environment, dict: CFMutableDictionaryRef, key: ConstVoidPtr) {
+    let key: id = key.cast().cast_mut();
+    msg![env; dict removeObjectForKey:key]
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFDictionaryCreateMutable(_, _, _, _)),
+    export_c_func!(CFDictionaryGetCount(_)),
+    export_c_func!(CFDictionaryGetValue(_, _)),
+    export_c_func!(CFDictionarySetValue(_, _, _)),
+    export_c_func!(CFDictionaryRemoveValue(_, _)),
+];
@@ -0,0 +1,235 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFNumber` and `CFBoolean`.
+//!
+//! These are toll-free bridged to `NSNumber` in Apple's implementation. Here
+//! they are the same type.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::CFIndex;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::ns_value::{self, number_value, NSNumberHostObject};
+use crate::mem::{ConstVoidPtr, MutVoidPtr};
+use crate::objc::{id, msg, msg_class, nil};
+use crate::Environment;
+
+pub type CFNumberRef = super::CFTypeRef;
+pub type CFBooleanRef = super::CFTypeRef;
+
+pub type CFNumberType = CFIndex;
+pub const kCFNumberSInt8Type: CFNumberType = 1;
+pub const kCFNumberSInt16Type: CFNumberType = 2;
+pub const kCFNumberSInt32Type: CFNumberType = 3;
+pub const kCFNumberSInt64Type: CFNumberType = 4;
+pub const kCFNumberFloat32Type: CFNumberType = 5;
+pub const kCFNumberFloat64Type: CFNumberType = 6;
+pub const kCFNumberCharType: CFNumberType = 7;
+pub const kCFNumberShortType: CFNumberType = 8;
+pub const kCFNumberIntType: CFNumberType = 9;
+pub const kCFNumberLongType: CFNumberType = 10;
+pub const kCFNumberLongLongType: CFNumberType = 11;
+pub const kCFNumberFloatType: CFNumberType = 12;
+pub const kCFNumberDoubleType: CFNumberType = 13;
+pub const kCFNumberCFIndexType: CFNumberType = 14;
+pub const kCFNumberNSIntegerType: CFNumberType = 15;
+pub const kCFNumberCGFloatType: CFNumberType = 16;
+
+/// The C types that the `CFNumberType` codes correspond to on iPhone OS.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum NumberKind {
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+impl NumberKind {
+    /// Returns [None] (and logs a warning) for an invalid type.
+    fn from_type(type_: CFNumberType) -> Option<NumberKind> {
+        Some(match type_ {
+            kCFNumberSInt8Type | kCFNumberCharType => NumberKind::I8,
+            kCFNumberSInt16Type | kCFNumberShortType => NumberKind::I16,
+            kCFNumberSInt32Type
+            | kCFNumberIntType
+            | kCFNumberLongType
+            | kCFNumberCFIndexType
+            | kCFNumberNSIntegerType => NumberKind::I32,
+            kCFNumberSInt64Type | kCFNumberLongLongType => NumberKind::I64,
+            kCFNumberFloat32Type | kCFNumberFloatType | kCFNumberCGFloatType => NumberKind::F32,
+            kCFNumberFloat64Type | kCFNumberDoubleType => NumberKind::F64,
+            _ => {
+                log!("Warning: Invalid CFNumberType {}", type_);
+                return None;
+            }
+        })
+    }
+}
+
+/// The values of `kCFBooleanTrue` and `kCFBooleanFalse`. These are the same
+/// objects as the boolean `NSNumber`s.
+fn new_boolean(env: &mut Environment, value: bool) -> ConstVoidPtr {
+    let number = ns_value::shared_boolean(env, value);
+    env.mem.alloc_and_write(number).cast().cast_const()
+}
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFBooleanTrue",
+        HostConstant::CustomWithEnv(|env| new_boolean(env, true)),
+    ),
+    (
+        "_kCFBooleanFalse",
+        HostConstant::CustomWithEnv(|env| new_boolean(env, false)),
+    ),
+];
+
+fn CFBooleanGetValue(env: &mut Environment, boolean: CFBooleanRef) -> bool {
+    msg![env; boolean boolValue]
+}
+
+fn CFNumberCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    type_: CFNumberType,
+    value_ptr: ConstVoidPtr,
+) -> CFNumberRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+
+    let Some(kind) = NumberKind::from_type(type_) else {
+        return nil;
+    };
+    let number: id = msg_class![env; NSNumber alloc];
+    match kind {
+        NumberKind::I8 => {
+            let value: i64 = env.mem.read(value_ptr.cast::<i8>()).into();
+            msg![env; number initWithLongLong:value]
+        }
+        NumberKind::I16 => {
+            let value: i64 = env.mem.read(value_ptr.cast::<i16>()).into();
+            msg![env; number initWithLongLong:value]
+        }
+        NumberKind::I32 => {
+            let value: i64 = env.mem.read(value_ptr.cast::<i32>()).into();
+            msg![env; number initWithLongLong:value]
+        }
+        NumberKind::I64 => {
+            let value: i64 = env.mem.read(value_ptr.cast());
+            msg![env; number initWithLongLong:value]
+        }
+        NumberKind::F32 => {
+            let value: f64 = env.mem.read(value_ptr.cast::<f32>()).into();
+            msg![env; number initWithDouble:value]
+        }
+        NumberKind::F64 => {
+            let value: f64 = env.mem.read(value_ptr.cast());
+            msg![env; number initWithDouble:value]
+        }
+    }
+}
+
+fn CFNumberGetType(env: &mut Environment, number: CFNumberRef) -> CFNumberType {
+    match number_value(env, number) {
+        // Apple's implementation reports booleans as chars.
+        NSNumberHostObject::Bool(_) => kCFNumberCharType,
+        NSNumberHostObject::LongLong(_) | NSNumberHostObject::UnsignedLongLong(_) => {
+            kCFNumberSInt64Type
+        }
+        NSNumberHostObject::Double(_) => kCFNumberFloat64Type,
+    }
+}
+
+fn CFNumberIsFloatType(env: &mut Environment, number: CFNumberRef) -> bool {
+    matches!(number_value(env, number), NSNumberHostObject::Double(_))
+}
+
+/// Convert a number to the C type for a `CFNumberType`, with the usual C
+/// conversion rules. The `bool` is [false] if the conversion was lossy.
+fn convert(value: NSNumberHostObject, kind: NumberKind) -> (f64, i64, bool) {
+    let float = value.as_f64();
+    match kind {
+        NumberKind::F32 | NumberKind::F64 => {
+            let converted = if kind == NumberKind::F32 {
+                float as f32 as f64
+            } else {
+                float
+            };
+            // Integers can be too large to be represented exactly, even as
+            // doubles.
+            let exact = match value.as_i128() {
+                Some(int) if !matches!(value, NSNumberHostObject::Double(_)) => {
+                    converted as i128 == int
+                }
+                _ => converted == float || float.is_nan(),
+            };
+            (converted, 0, exact)
+        }
+        NumberKind::I8 | NumberKind::I16 | NumberKind::I32 | NumberKind::I64 => {
+            let truncated: i128 = match value {
+                // Saturating, like C conversion on Arm.
+                NSNumberHostObject::Double(float) => float as i64 as i128,
+                _ => value.as_i128().unwrap(),
+            };
+            let converted: i64 = match kind {
+                NumberKind::I8 => truncated as i8 as i64,
+                NumberKind::I16 => truncated as i16 as i64,
+                NumberKind::I32 => truncated as i32 as i64,
+                _ => truncated as i64,
+            };
+            let exact = value.as_i128() == Some(converted as i128);
+            (0.0, converted, exact)
+        }
+    }
+}
+
+fn CFNumberGetValue(
+    env: &mut Environment,
+    number: CFNumberRef,
+    type_: CFNumberType,
+    value_ptr: MutVoidPtr,
+) -> bool {
+    let Some(kind) = NumberKind::from_type(type_) else {
+        return false;
+    };
+    let (float, int, exact) = convert(number_value(env, number), kind);
+    match kind {
+        NumberKind::I8 => env.mem.write(value_ptr.cast(), int as i8),
+        NumberKind::I16 => env.mem.write(value_ptr.cast(), int as i16),
+        NumberKind::I32 => env.mem.write(value_ptr.cast(), int as i32),
+        NumberKind::I64 => env.mem.write(value_ptr.cast(), int),
+        NumberKind::F32 => env.mem.write(value_ptr.cast(), float as f32),
+        NumberKind::F64 => env.mem.write(value_ptr.cast(), float),
+    }
+    exact
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFBooleanGetValue(_)),
+    export_c_func!(CFNumberCreate(_, _, _)),
+    export_c_func!(CFNumberGetType(_)),
+    export_c_func!(CFNumberIsFloatType(_)),
+    export_c_func!(CFNumberGetValue(_, _, _)),
+];
+
+#[cfg(test)]
+#[test]
+fn test_convert() {
+    use NSNumberHostObject::*;
+
+    assert_eq!(convert(LongLong(300), NumberKind::I32), (0.0, 300, true));
+    // Narrowing
+    assert_eq!(convert(LongLong(300), NumberKind::I8), (0.0, 44, false));
+    assert_eq!(convert(Double(2.5), NumberKind::I32), (0.0, 2, false));
+    assert_eq!(convert(Double(-3.0), NumberKind::I16), (0.0, -3, true));
+    assert_eq!(convert(Bool(true), NumberKind::I8), (0.0, 1, true));
+    // Widening
+    assert_eq!(convert(LongLong(7), NumberKind::F64), (7.0, 0, true));
+    assert_eq!(convert(Double(0.5), NumberKind::F32), (0.5, 0, true));
+    // Precision loss
+    assert!(!convert(Double(0.1), NumberKind::F32).2);
+    assert!(!convert(LongLong(i64::MAX), NumberKind::F64).2);
+    assert!(!convert(UnsignedLongLong(u64::MAX), NumberKind::I64).2);
+}
@@ -0,0 +1,139 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFPreferences`.
+//!
+//! Only the current application's preferences are supported. They are stored
+//! in the same place as `NSUserDefaults`' persistent domain, so values set
+//! through either API are visible through the other.
+
+use super::cf_string::CFStringRef;
+use super::{CFIndex, CFTypeRef};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::{ns_string, ns_user_defaults};
+use crate::mem::MutPtr;
+use crate::objc::{id, msg, msg_class, nil, retain, Class};
+use crate::Environment;
+
+pub type CFPropertyListRef = CFTypeRef;
+
+pub const kCFPreferencesCurrentApplication: &str = "kCFPreferencesCurrentApplication";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_kCFPreferencesCurrentApplication",
+    HostConstant::NSString(kCFPreferencesCurrentApplication),
+)];
+
+/// Returns [true] if the application ID refers to the current application.
+fn is_current_application(env: &mut Environment, application_id: CFStringRef) -> bool {
+    let current = ns_string::get_static_str(env, kCFPreferencesCurrentApplication);
+    if msg![env; application_id isEqualToString:current] {
+        return true;
+    }
+    let application_id = ns_string::to_rust_string(env, application_id);
+    if application_id == env.bundle.bundle_identifier() {
+        return true;
+    }
+    log!(
+        "TODO: CFPreferences for other application {:?}, ignoring",
+        application_id
+    );
+    false
+}
+
+fn CFPreferencesCopyAppValue(
+    env: &mut Environment,
+    key: CFStringRef,
+    application_id: CFStringRef,
+) -> CFPropertyListRef {
+    if !is_current_application(env, application_id) {
+        return nil;
+    }
+    let dict = ns_user_defaults::app_domain(env);
+    let value: id = msg![env; dict objectForKey:key];
+    log_dbg!("CFPreferencesCopyAppValue({:?}) => {:?}", key, value);
+    retain(env, value)
+}
+
+fn CFPreferencesSetAppValue(
+    env: &mut Environment,
+    key: CFStringRef,
+    value: CFPropertyListRef,
+    application_id: CFStringRef,
+) {
+    if !is_current_application(env, application_id) {
+        return;
+    }
+    log_dbg!("CFPreferencesSetAppValue({:?}, {:?})", key, value);
+    let dict = ns_user_defaults::app_domain(env);
+    // NULL means the key should be removed.
+    if value == nil {
+        msg![env; dict removeObjectForKey:key]
+    } else {
+        msg![env; dict setObject:value forKey:key]
+    }
+}
+
+fn CFPreferencesAppSynchronize(env: &mut Environment, application_id: CFStringRef) -> bool {
+    if !is_current_application(env, application_id) {
+        return false;
+    }
+    ns_user_defaults::synchronize_app_domain(env)
+}
+
+/// Look up a value for `CFPreferencesGetApp*Value`, returning nil if it isn't
+/// a number. Strings are not supported (TODO).
+fn get_app_number(
+    env: &mut Environment,
+    key: CFStringRef,
+    application_id: CFStringRef,
+    key_exists_and_has_valid_format: MutPtr<u8>,
+) -> id {
+    let value = if is_current_application(env, application_id) {
+        let dict = ns_user_defaults::app_domain(env);
+        msg![env; dict objectForKey:key]
+    } else {
+        nil
+    };
+    let number_class: Class = msg_class![env; NSNumber class];
+    let value = if msg![env; value isKindOfClass:number_class] {
+        value
+    } else {
+        nil
+    };
+    if !key_exists_and_has_valid_format.is_null() {
+        env.mem
+            .write(key_exists_and_has_valid_format, (value != nil).into());
+    }
+    value
+}
+
+fn CFPreferencesGetAppBooleanValue(
+    env: &mut Environment,
+    key: CFStringRef,
+    application_id: CFStringRef,
+    key_exists_and_has_valid_format: MutPtr<u8>, // Boolean*
+) -> bool {
+    let number = get_app_number(env, key, application_id, key_exists_and_has_valid_format);
+    msg![env; number boolValue]
+}
+
+fn CFPreferencesGetAppIntegerValue(
+    env: &mut Environment,
+    key: CFStringRef,
+    application_id: CFStringRef,
+    key_exists_and_has_valid_format: MutPtr<u8>, // Boolean*
+) -> CFIndex {
+    let number = get_app_number(env, key, application_id, key_exists_and_has_valid_format);
+    msg![env; number intValue]
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFPreferencesCopyAppValue(_, _)),
+    export_c_func!(CFPreferencesSetAppValue(_, _, _)),
+    export_c_func!(CFPreferencesAppSynchronize(_)),
+    export_c_func!(CFPreferencesGetAppBooleanValue(_, _, _)),
+    export_c_func!(CFPreferencesGetAppIntegerValue(_, _, _)),
+];
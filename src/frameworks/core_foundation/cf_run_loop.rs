@@ -6,16 +6,100 @@
 //! `CFRunLoop`.
 //!
 //! This is not even toll-free bridged to `NSRunLoop` in Apple's implementation,
-//! but here it is the same type.
+//! but here it is the same type. `CFRunLoopTimer` is toll-free bridged to
+//! `NSTimer`, and here it is the same type too.
+//!
+//! Only version 0 (custom, manually signalled) run loop sources are supported.
 
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::time::{CFAbsoluteTime, CFAbsoluteTimeGetCurrent, CFTimeInterval};
+use super::CFIndex;
+use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
-use crate::objc::msg_class;
+use crate::frameworks::foundation::{ns_run_loop, ns_string, ns_timer};
+use crate::mem::{ConstPtr, ConstVoidPtr, MutVoidPtr, SafeRead};
+use crate::objc::{id, msg, msg_class, objc_classes, ClassExports, HostObject};
 use crate::Environment;
 
 pub type CFRunLoopRef = super::CFTypeRef;
 pub type CFRunLoopMode = super::cf_string::CFStringRef;
+pub type CFRunLoopTimerRef = super::CFTypeRef;
+pub type CFRunLoopSourceRef = super::CFTypeRef;
+
+pub type CFOptionFlags = u32;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFRunLoopSource is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CFRunLoopSource: NSObject
+
+- (())dealloc {
+    let &CFRunLoopSourceHostObject { context, .. } = env.objc.borrow(this);
+    // The context is packed, so its fields must be copied out before use.
+    let CFRunLoopSourceContext { release, info, .. } = context;
+    if release.addr_with_thumb_bit() != 0 {
+        let _: () = release.call_from_host(env, (info.cast_const(),));
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct CFRunLoopTimerContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    /// `const void *(*retain)(const void *info)`, may be NULL
+    retain: GuestFunction,
+    /// `void (*release)(const void *info)`, may be NULL
+    release: GuestFunction,
+    copy_description: GuestFunction,
+}
+unsafe impl SafeRead for CFRunLoopTimerContext {}
+
+/// The version 0 variant of `CFRunLoopSourceContext`.
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct CFRunLoopSourceContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    /// `const void *(*retain)(const void *info)`, may be NULL
+    retain: GuestFunction,
+    /// `void (*release)(const void *info)`, may be NULL
+    release: GuestFunction,
+    copy_description: GuestFunction,
+    equal: GuestFunction,
+    hash: GuestFunction,
+    /// `void (*schedule)(void *info, CFRunLoopRef rl, CFRunLoopMode mode)`,
+    /// may be NULL
+    schedule: GuestFunction,
+    /// `void (*cancel)(void *info, CFRunLoopRef rl, CFRunLoopMode mode)`, may
+    /// be NULL
+    cancel: GuestFunction,
+    /// `void (*perform)(void *info)`
+    perform: GuestFunction,
+}
+unsafe impl SafeRead for CFRunLoopSourceContext {}
+
+struct CFRunLoopSourceHostObject {
+    context: CFRunLoopSourceContext,
+    signaled: bool,
+    valid: bool,
+    /// Weak references to the run loops the source has been added to.
+    run_loops: Vec<CFRunLoopRef>,
+}
+impl HostObject for CFRunLoopSourceHostObject {}
 
 fn CFRunLoopGetCurrent(env: &mut Environment) -> CFRunLoopRef {
+    // There's only one thread with a run loop for now, so this is always the
+    // main run loop.
     msg_class![env; NSRunLoop currentRunLoop]
 }
 
@@ -37,7 +121,219 @@ pub const CONSTANTS: ConstantExports = &[
     ),
 ];
 
+fn CFRunLoopRun(env: &mut Environment) {
+    let run_loop = CFRunLoopGetCurrent(env);
+    ns_run_loop::run_run_loop_until_stopped(env, run_loop);
+}
+
+fn CFRunLoopStop(env: &mut Environment, run_loop: CFRunLoopRef) {
+    ns_run_loop::stop(env, run_loop);
+}
+
+fn CFRunLoopWakeUp(_env: &mut Environment, _run_loop: CFRunLoopRef) {
+    // The run loop never sleeps for long, so there's nothing to do.
+}
+
+fn CFRunLoopTimerCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    fire_date: CFAbsoluteTime,
+    interval: CFTimeInterval,
+    _flags: CFOptionFlags,  // reserved
+    _order: CFIndex,        // TODO: ordering
+    callout: GuestFunction, // CFRunLoopTimerCallBack
+    context: ConstPtr<CFRunLoopTimerContext>,
+) -> CFRunLoopTimerRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+
+    let (info, info_release) = if context.is_null() {
+        (
+            MutVoidPtr::null(),
+            GuestFunction::from_addr_with_thumb_bit(0),
+        )
+    } else {
+        let CFRunLoopTimerContext {
+            version,
+            info,
+            retain,
+            release,
+            ..
+        } = env.mem.read(context);
+        assert!(version == 0);
+        let info = retain_info(env, retain, info);
+        (info, release)
+    };
+
+    let fire_after = fire_date - CFAbsoluteTimeGetCurrent(env);
+    ns_timer::new_with_callout(env, fire_after, interval, callout, info, info_release)
+}
+
+fn CFRunLoopAddTimer(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    timer: CFRunLoopTimerRef,
+    mode: CFRunLoopMode,
+) {
+    msg![env; run_loop addTimer:timer forMode:mode]
+}
+
+fn CFRunLoopTimerInvalidate(env: &mut Environment, timer: CFRunLoopTimerRef) {
+    msg![env; timer invalidate]
+}
+
+fn CFRunLoopTimerIsValid(env: &mut Environment, timer: CFRunLoopTimerRef) -> bool {
+    msg![env; timer isValid]
+}
+
+/// Call a context's `retain` callback, if it has one.
+fn retain_info(env: &mut Environment, retain: GuestFunction, info: MutVoidPtr) -> MutVoidPtr {
+    if retain.addr_with_thumb_bit() == 0 {
+        return info;
+    }
+    let info: ConstVoidPtr = retain.call_from_host(env, (info.cast_const(),));
+    info.cast_mut()
+}
+
+fn CFRunLoopSourceCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    _order: CFIndex, // TODO: ordering
+    context: ConstPtr<CFRunLoopSourceContext>,
+) -> CFRunLoopSourceRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+
+    let mut context = env.mem.read(context);
+    // TODO: version 1 (Mach port) sources
+    assert!(context.version == 0);
+    context.info = retain_info(env, context.retain, context.info);
+
+    let host_object = Box::new(CFRunLoopSourceHostObject {
+        context,
+        signaled: false,
+        valid: true,
+        run_loops: Vec::new(),
+    });
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CFRunLoopSource", &mut env.mem);
+    let source = env.objc.alloc_object(isa, host_object, &mut env.mem);
+    log_dbg!("CFRunLoopSourceCreate() => {:?}", source);
+    source
+}
+
+/// Call a source's `schedule` or `cancel` callback, if it has one.
+fn call_schedule_or_cancel(
+    env: &mut Environment,
+    callback: GuestFunction,
+    info: MutVoidPtr,
+    run_loop: CFRunLoopRef,
+) {
+    if callback.addr_with_thumb_bit() == 0 {
+        return;
+    }
+    // TODO: handle run loop modes
+    let mode = ns_string::get_static_str(env, kCFRunLoopCommonModes);
+    let _: () = callback.call_from_host(env, (info, run_loop, mode));
+}
+
+fn CFRunLoopAddSource(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    source: CFRunLoopSourceRef,
+    _mode: CFRunLoopMode, // TODO: handle run loop modes
+) {
+    if !env.objc.borrow::<CFRunLoopSourceHostObject>(source).valid
+        || !ns_run_loop::add_source(env, run_loop, source)
+    {
+        return;
+    }
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    host_object.run_loops.push(run_loop);
+    let CFRunLoopSourceContext { info, schedule, .. } = host_object.context;
+    call_schedule_or_cancel(env, schedule, info, run_loop);
+}
+
+fn CFRunLoopRemoveSource(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    source: CFRunLoopSourceRef,
+    _mode: CFRunLoopMode, // TODO: handle run loop modes
+) {
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    let Some(idx) = host_object.run_loops.iter().position(|&rl| rl == run_loop) else {
+        return;
+    };
+    host_object.run_loops.swap_remove(idx);
+    let CFRunLoopSourceContext { info, cancel, .. } = host_object.context;
+    call_schedule_or_cancel(env, cancel, info, run_loop);
+    // This may release the source, so it has to be done last.
+    ns_run_loop::remove_source(env, run_loop, source);
+}
+
+fn CFRunLoopSourceInvalidate(env: &mut Environment, source: CFRunLoopSourceRef) {
+    let run_loops = std::mem::take(
+        &mut env
+            .objc
+            .borrow_mut::<CFRunLoopSourceHostObject>(source)
+            .run_loops,
+    );
+    env.objc
+        .borrow_mut::<CFRunLoopSourceHostObject>(source)
+        .valid = false;
+    // Keep the source alive while removing it from its run loops.
+    crate::objc::retain(env, source);
+    for run_loop in run_loops {
+        let CFRunLoopSourceContext { info, cancel, .. } =
+            env.objc.borrow::<CFRunLoopSourceHostObject>(source).context;
+        call_schedule_or_cancel(env, cancel, info, run_loop);
+        ns_run_loop::remove_source(env, run_loop, source);
+    }
+    crate::objc::release(env, source);
+}
+
+fn CFRunLoopSourceIsValid(env: &mut Environment, source: CFRunLoopSourceRef) -> bool {
+    env.objc.borrow::<CFRunLoopSourceHostObject>(source).valid
+}
+
+fn CFRunLoopSourceSignal(env: &mut Environment, source: CFRunLoopSourceRef) {
+    env.objc
+        .borrow_mut::<CFRunLoopSourceHostObject>(source)
+        .signaled = true;
+}
+
+/// For use by `NSRunLoop`: call the source's `perform` callback if it has been
+/// signalled.
+pub fn handle_source(env: &mut Environment, source: CFRunLoopSourceRef) {
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    if !host_object.valid || !std::mem::take(&mut host_object.signaled) {
+        return;
+    }
+    let CFRunLoopSourceContext { info, perform, .. } = host_object.context;
+
+    log_dbg!("Run loop source {:?} fired, calling {:?}", source, perform);
+
+    // The source might be removed from the run loop by its callback.
+    crate::objc::retain(env, source);
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let _: () = perform.call_from_host(env, (info,));
+    crate::objc::release(env, pool);
+    crate::objc::release(env, source);
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFRunLoopGetCurrent()),
     export_c_func!(CFRunLoopGetMain()),
+    export_c_func!(CFRunLoopRun()),
+    export_c_func!(CFRunLoopStop(_)),
+    export_c_func!(CFRunLoopWakeUp(_)),
+    export_c_func!(CFRunLoopTimerCreate(_, _, _, _, _, _, _)),
+    export_c_func!(CFRunLoopAddTimer(_, _, _)),
+    export_c_func!(CFRunLoopTimerInvalidate(_)),
+    export_c_func!(CFRunLoopTimerIsValid(_)),
+    export_c_func!(CFRunLoopSourceCreate(_, _, _)),
+    export_c_func!(CFRunLoopAddSource(_, _, _)),
+    export_c_func!(CFRunLoopRemoveSource(_, _, _)),
+    export_c_func!(CFRunLoopSourceInvalidate(_)),
+    export_c_func!(CFRunLoopSourceIsValid(_)),
+    export_c_func!(CFRunLoopSourceSignal(_)),
 ];
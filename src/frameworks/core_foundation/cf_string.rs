@@ -9,14 +9,24 @@
 //! is the same type.
 
 use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::CFIndex;
+use crate::abi::DotDotDot;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::foundation::ns_string;
-use crate::mem::ConstPtr;
+use crate::frameworks::foundation::{ns_string, NSUInteger};
+use crate::mem::{ConstPtr, MutPtr};
 use crate::objc::{id, msg, msg_class};
 use crate::Environment;
 
 pub type CFStringRef = super::CFTypeRef;
 
+pub type CFComparisonResult = CFIndex;
+pub const kCFCompareLessThan: CFComparisonResult = -1;
+pub const kCFCompareEqualTo: CFComparisonResult = 0;
+pub const kCFCompareGreaterThan: CFComparisonResult = 1;
+
+pub type CFStringCompareFlags = u32;
+pub const kCFCompareCaseInsensitive: CFStringCompareFlags = 1;
+
 pub type CFStringEncoding = u32;
 pub const kCFStringEncodingASCII: CFStringEncoding = 0x600;
 pub const kCFStringEncodingUTF8: CFStringEncoding = 0x8000100;
@@ -63,8 +73,87 @@ fn CFStringCreateWithCString(
     msg![env; ns_string initWithCString:c_string encoding:encoding]
 }
 
+fn CFStringCreateWithFormat(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    _format_options: super::CFTypeRef, // CFDictionaryRef, ignored
+    format: CFStringRef,
+    args: DotDotDot,
+) -> CFStringRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+
+    // TODO: avoid copy
+    let format_string = ns_string::to_rust_string(env, format);
+
+    log_dbg!(
+        "CFStringCreateWithFormat({:?} ({:?}), ...)",
+        format,
+        format_string
+    );
+
+    let res = crate::libc::stdio::printf::printf_inner::<true, _>(
+        env,
+        |_, idx| {
+            if idx as usize == format_string.len() {
+                b'\0'
+            } else {
+                format_string.as_bytes()[idx as usize]
+            }
+        },
+        args.start(),
+    );
+    // TODO: what if it's not valid UTF-8?
+    // This is a Create function, so the caller owns the result.
+    ns_string::from_rust_string(env, String::from_utf8(res).unwrap())
+}
+
+fn CFStringGetLength(env: &mut Environment, string: CFStringRef) -> CFIndex {
+    let length: NSUInteger = msg![env; string length];
+    length.try_into().unwrap()
+}
+
+fn CFStringGetCString(
+    env: &mut Environment,
+    string: CFStringRef,
+    buffer: MutPtr<u8>,
+    buffer_size: CFIndex,
+    encoding: CFStringEncoding,
+) -> bool {
+    let encoding = CFStringConvertEncodingToNSStringEncoding(env, encoding);
+    let buffer_size: NSUInteger = buffer_size.try_into().unwrap();
+    msg![env; string getCString:buffer
+                      maxLength:buffer_size
+                       encoding:encoding]
+}
+
+fn CFStringCompare(
+    env: &mut Environment,
+    a: CFStringRef,
+    b: CFStringRef,
+    flags: CFStringCompareFlags,
+) -> CFComparisonResult {
+    if flags == 0 {
+        // NSComparisonResult and CFComparisonResult have the same values.
+        return msg![env; a compare:b];
+    }
+    // TODO: other flags
+    assert!(flags == kCFCompareCaseInsensitive);
+    // TODO: avoid copying
+    let a = ns_string::to_rust_string(env, a).to_lowercase();
+    let b = ns_string::to_rust_string(env, b).to_lowercase();
+    match a.encode_utf16().cmp(b.encode_utf16()) {
+        std::cmp::Ordering::Less => kCFCompareLessThan,
+        std::cmp::Ordering::Equal => kCFCompareEqualTo,
+        std::cmp::Ordering::Greater => kCFCompareGreaterThan,
+    }
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFStringConvertEncodingToNSStringEncoding(_)),
     export_c_func!(CFStringConvertNSStringEncodingToEncoding(_)),
     export_c_func!(CFStringCreateWithCString(_, _, _)),
+    export_c_func!(CFStringCreateWithFormat(_, _, _, _)),
+    export_c_func!(CFStringGetLength(_)),
+    export_c_func!(CFStringGetCString(_, _, _, _)),
+    export_c_func!(CFStringCompare(_, _, _)),
 ];
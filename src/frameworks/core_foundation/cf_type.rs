@@ -5,12 +5,17 @@
  */
 //! `CFType` (type-generic functions etc).
 
+use super::cf_allocator::CFAllocatorRef;
+use crate::abi::GuestFunction;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::objc;
+use crate::mem::ConstVoidPtr;
+use crate::objc::{self, msg};
 use crate::Environment;
 
 pub type CFTypeRef = objc::id;
 
+pub type CFHashCode = u32;
+
 pub fn CFRetain(env: &mut Environment, object: CFTypeRef) -> CFTypeRef {
     assert!(!object.is_null()); // not allowed, unlike for normal objc objects
     objc::retain(env, object)
@@ -19,4 +24,56 @@ pub fn CFRelease(env: &mut Environment, object: CFTypeRef) {
     objc::release(env, object);
 }
 
-pub const FUNCTIONS: FunctionExports = &[export_c_func!(CFRetain(_)), export_c_func!(CFRelease(_))];
+// These are private functions used to fill in the standard collection
+// callbacks (e.g. `kCFTypeArrayCallBacks`), so that the function pointers in
+// them do the right thing if the app calls them itself.
+
+fn _touchHLE_CFTypeRetainCallBack(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    value: ConstVoidPtr,
+) -> ConstVoidPtr {
+    CFRetain(env, value.cast().cast_mut()).cast().cast_const()
+}
+fn _touchHLE_CFTypeReleaseCallBack(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    value: ConstVoidPtr,
+) {
+    CFRelease(env, value.cast().cast_mut())
+}
+fn _touchHLE_CFTypeEqualCallBack(env: &mut Environment, a: ConstVoidPtr, b: ConstVoidPtr) -> bool {
+    let a: CFTypeRef = a.cast().cast_mut();
+    let b: CFTypeRef = b.cast().cast_mut();
+    // Our Foundation collections use isEqualTo:, so this does the same.
+    msg![env; a isEqualTo:b]
+}
+fn _touchHLE_CFTypeHashCallBack(env: &mut Environment, value: ConstVoidPtr) -> CFHashCode {
+    let value: CFTypeRef = value.cast().cast_mut();
+    msg![env; value hash]
+}
+
+/// Get guest function pointers for the standard retain, release, equal and
+/// hash callbacks, in that order.
+pub(super) fn standard_callbacks(env: &mut Environment) -> [GuestFunction; 4] {
+    [
+        "__touchHLE_CFTypeRetainCallBack",
+        "__touchHLE_CFTypeReleaseCallBack",
+        "__touchHLE_CFTypeEqualCallBack",
+        "__touchHLE_CFTypeHashCallBack",
+    ]
+    .map(|symbol| {
+        env.dyld
+            .create_proc_address(&mut env.mem, &mut env.cpu, symbol)
+            .unwrap()
+    })
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFRetain(_)),
+    export_c_func!(CFRelease(_)),
+    export_c_func!(_touchHLE_CFTypeRetainCallBack(_, _)),
+    export_c_func!(_touchHLE_CFTypeReleaseCallBack(_, _)),
+    export_c_func!(_touchHLE_CFTypeEqualCallBack(_, _)),
+    export_c_func!(_touchHLE_CFTypeHashCallBack(_)),
+];
@@ -11,9 +11,9 @@ use crate::objc::msg_class;
 use crate::Environment;
 
 pub type CFTimeInterval = NSTimeInterval;
-type CFAbsoluteTime = CFTimeInterval;
+pub type CFAbsoluteTime = CFTimeInterval;
 
-fn CFAbsoluteTimeGetCurrent(env: &mut Environment) -> CFAbsoluteTime {
+pub fn CFAbsoluteTimeGetCurrent(env: &mut Environment) -> CFAbsoluteTime {
     let time: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
     time
 }
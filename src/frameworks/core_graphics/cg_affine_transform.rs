@@ -4,9 +4,13 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `CGAffineTransform.h`
+//!
+//! These matrices use the row vector convention: a point is transformed by
+//! multiplying it on the left of the matrix, so `CGAffineTransformConcat(a, b)`
+//! means applying `a` and then `b`.
 
-use super::CGFloat;
-use crate::abi::GuestArg;
+use super::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
 use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
 use crate::mem::SafeRead;
 use crate::Environment;
@@ -24,6 +28,7 @@ pub struct CGAffineTransform {
     pub ty: CGFloat,
 }
 unsafe impl SafeRead for CGAffineTransform {}
+impl_GuestRet_for_large_struct!(CGAffineTransform);
 impl GuestArg for CGAffineTransform {
     const REG_COUNT: usize = 6;
 
@@ -47,6 +52,80 @@ impl GuestArg for CGAffineTransform {
     }
 }
 
+impl CGAffineTransform {
+    /// Matrix multiplication, i.e. the transform that applies `self` and then
+    /// `other`.
+    pub fn concat(self, other: CGAffineTransform) -> Self {
+        let CGAffineTransform { a, b, c, d, tx, ty } = self;
+        let o = other;
+        CGAffineTransform {
+            a: a * o.a + b * o.c,
+            b: a * o.b + b * o.d,
+            c: c * o.a + d * o.c,
+            d: c * o.b + d * o.d,
+            tx: tx * o.a + ty * o.c + o.tx,
+            ty: tx * o.b + ty * o.d + o.ty,
+        }
+    }
+
+    /// Returns [None] if the matrix can't be inverted.
+    pub fn invert(self) -> Option<Self> {
+        let CGAffineTransform { a, b, c, d, tx, ty } = self;
+        let determinant = a * d - b * c;
+        if determinant == 0.0 {
+            return None;
+        }
+        let (a, b, c, d) = (
+            d / determinant,
+            -b / determinant,
+            -c / determinant,
+            a / determinant,
+        );
+        Some(CGAffineTransform {
+            a,
+            b,
+            c,
+            d,
+            tx: -(tx * a + ty * c),
+            ty: -(tx * b + ty * d),
+        })
+    }
+
+    pub fn apply_to_point(self, point: CGPoint) -> CGPoint {
+        CGPoint {
+            x: self.a * point.x + self.c * point.y + self.tx,
+            y: self.b * point.x + self.d * point.y + self.ty,
+        }
+    }
+
+    pub fn translation(tx: CGFloat, ty: CGFloat) -> Self {
+        CGAffineTransform {
+            tx,
+            ty,
+            ..CGAffineTransformIdentity
+        }
+    }
+    pub fn scale(sx: CGFloat, sy: CGFloat) -> Self {
+        CGAffineTransform {
+            a: sx,
+            d: sy,
+            ..CGAffineTransformIdentity
+        }
+    }
+    /// Positive angles are counter-clockwise in a co-ordinate space where y
+    /// points up (so clockwise in UIKit's co-ordinate space).
+    pub fn rotation(angle: CGFloat) -> Self {
+        let (s, c) = angle.sin_cos();
+        CGAffineTransform {
+            a: c,
+            b: s,
+            c: -s,
+            d: c,
+            ..CGAffineTransformIdentity
+        }
+    }
+}
+
 #[rustfmt::skip]
 pub const CGAffineTransformIdentity: CGAffineTransform = CGAffineTransform {
     a: 1.0, c: 0.0, tx: 0.0,
@@ -66,5 +145,177 @@ pub const CONSTANTS: ConstantExports = &[(
 fn CGAffineTransformIsIdentity(_env: &mut Environment, transform: CGAffineTransform) -> bool {
     transform == CGAffineTransformIdentity
 }
+fn CGAffineTransformEqualToTransform(
+    _env: &mut Environment,
+    a: CGAffineTransform,
+    b: CGAffineTransform,
+) -> bool {
+    a == b
+}
+
+fn CGAffineTransformMake(
+    _env: &mut Environment,
+    a: CGFloat,
+    b: CGFloat,
+    c: CGFloat,
+    d: CGFloat,
+    tx: CGFloat,
+    ty: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransform { a, b, c, d, tx, ty }
+}
+fn CGAffineTransformMakeTranslation(
+    _env: &mut Environment,
+    tx: CGFloat,
+    ty: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransform::translation(tx, ty)
+}
+fn CGAffineTransformMakeScale(
+    _env: &mut Environment,
+    sx: CGFloat,
+    sy: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransform::scale(sx, sy)
+}
+fn CGAffineTransformMakeRotation(_env: &mut Environment, angle: CGFloat) -> CGAffineTransform {
+    CGAffineTransform::rotation(angle)
+}
+
+// The Translate/Scale/Rotate functions apply the new transform before the
+// existing one.
+fn CGAffineTransformTranslate(
+    _env: &mut Environment,
+    t: CGAffineTransform,
+    tx: CGFloat,
+    ty: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransform::translation(tx, ty).concat(t)
+}
+fn CGAffineTransformScale(
+    _env: &mut Environment,
+    t: CGAffineTransform,
+    sx: CGFloat,
+    sy: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransform::scale(sx, sy).concat(t)
+}
+fn CGAffineTransformRotate(
+    _env: &mut Environment,
+    t: CGAffineTransform,
+    angle: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransform::rotation(angle).concat(t)
+}
+fn CGAffineTransformConcat(
+    _env: &mut Environment,
+    a: CGAffineTransform,
+    b: CGAffineTransform,
+) -> CGAffineTransform {
+    a.concat(b)
+}
+fn CGAffineTransformInvert(_env: &mut Environment, t: CGAffineTransform) -> CGAffineTransform {
+    // Apple's documentation says the original is returned if it can't be
+    // inverted.
+    t.invert().unwrap_or(t)
+}
 
-pub const FUNCTIONS: FunctionExports = &[export_c_func!(CGAffineTransformIsIdentity(_))];
+fn CGPointApplyAffineTransform(
+    _env: &mut Environment,
+    point: CGPoint,
+    t: CGAffineTransform,
+) -> CGPoint {
+    t.apply_to_point(point)
+}
+fn CGSizeApplyAffineTransform(
+    _env: &mut Environment,
+    size: CGSize,
+    t: CGAffineTransform,
+) -> CGSize {
+    // Sizes are vectors, so the translation doesn't apply.
+    CGSize {
+        width: t.a * size.width + t.c * size.height,
+        height: t.b * size.width + t.d * size.height,
+    }
+}
+/// Returns the bounding box of the transformed rectangle.
+fn CGRectApplyAffineTransform(
+    _env: &mut Environment,
+    rect: CGRect,
+    t: CGAffineTransform,
+) -> CGRect {
+    let CGRect { origin, size } = rect;
+    let corners = [
+        (origin.x, origin.y),
+        (origin.x + size.width, origin.y),
+        (origin.x, origin.y + size.height),
+        (origin.x + size.width, origin.y + size.height),
+    ]
+    .map(|(x, y)| t.apply_to_point(CGPoint { x, y }));
+    let (mut min, mut max) = (corners[0], corners[0]);
+    for corner in &corners[1..] {
+        min = CGPoint {
+            x: min.x.min(corner.x),
+            y: min.y.min(corner.y),
+        };
+        max = CGPoint {
+            x: max.x.max(corner.x),
+            y: max.y.max(corner.y),
+        };
+    }
+    CGRect {
+        origin: min,
+        size: CGSize {
+            width: max.x - min.x,
+            height: max.y - min.y,
+        },
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGAffineTransformIsIdentity(_)),
+    export_c_func!(CGAffineTransformEqualToTransform(_, _)),
+    export_c_func!(CGAffineTransformMake(_, _, _, _, _, _)),
+    export_c_func!(CGAffineTransformMakeTranslation(_, _)),
+    export_c_func!(CGAffineTransformMakeScale(_, _)),
+    export_c_func!(CGAffineTransformMakeRotation(_)),
+    export_c_func!(CGAffineTransformTranslate(_, _, _)),
+    export_c_func!(CGAffineTransformScale(_, _, _)),
+    export_c_func!(CGAffineTransformRotate(_, _)),
+    export_c_func!(CGAffineTransformConcat(_, _)),
+    export_c_func!(CGAffineTransformInvert(_)),
+    export_c_func!(CGPointApplyAffineTransform(_, _)),
+    export_c_func!(CGSizeApplyAffineTransform(_, _)),
+    export_c_func!(CGRectApplyAffineTransform(_, _)),
+];
+
+#[cfg(test)]
+#[test]
+fn test_rotate_then_translate() {
+    fn approx_eq(point: CGPoint, x: CGFloat, y: CGFloat) -> bool {
+        (point.x - x).abs() < 0.0001 && (point.y - y).abs() < 0.0001
+    }
+
+    let rotation = CGAffineTransform::rotation(std::f32::consts::FRAC_PI_2);
+    let translation = CGAffineTransform::translation(10.0, 0.0);
+    let point = CGPoint { x: 1.0, y: 0.0 };
+
+    // Row vector convention: rotating (1, 0) by 90° gives (0, 1), and then
+    // translating gives (10, 1).
+    let rotate_then_translate = rotation.concat(translation);
+    let result = rotate_then_translate.apply_to_point(point);
+    assert!(approx_eq(result, 10.0, 1.0));
+    // The other way around: (11, 0) rotated is (0, 11).
+    let result = translation.concat(rotation).apply_to_point(point);
+    assert!(approx_eq(result, 0.0, 11.0));
+    // CGAffineTransformTranslate(rotation, ...) translates first.
+    let translated = CGAffineTransform::translation(10.0, 0.0).concat(rotation);
+    assert!(approx_eq(translated.apply_to_point(point), 0.0, 11.0));
+
+    let inverse = rotate_then_translate.invert().unwrap();
+    let identity = rotate_then_translate.concat(inverse);
+    assert!(approx_eq(identity.apply_to_point(point), 1.0, 0.0));
+    let result = inverse.apply_to_point(CGPoint { x: 10.0, y: 1.0 });
+    assert!(approx_eq(result, 1.0, 0.0));
+    assert!(CGAffineTransform::scale(0.0, 1.0).invert().is_none());
+}
@@ -5,6 +5,7 @@
  */
 //! `CGBitmapContext.h`
 
+use super::cg_affine_transform::CGAffineTransform;
 use super::cg_color_space::{kCGColorSpaceGenericRGB, CGColorSpaceHostObject, CGColorSpaceRef};
 use super::cg_context::{CGContextHostObject, CGContextRef, CGContextSubclass};
 use super::cg_image::{
@@ -13,9 +14,9 @@ use super::cg_image::{
     kCGImageAlphaPremultipliedFirst, kCGImageAlphaPremultipliedLast, kCGImageByteOrder32Big,
     kCGImageByteOrderDefault, CGBitmapInfo, CGImageAlphaInfo, CGImageRef,
 };
-use super::{CGFloat, CGRect};
+use super::{CGFloat, CGPoint, CGRect};
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::image::{gamma_decode, gamma_encode};
+use crate::image::{gamma_decode, gamma_encode, Image};
 use crate::mem::{GuestUSize, Mem, MutVoidPtr};
 use crate::objc::ObjC;
 use crate::Environment;
@@ -64,8 +65,8 @@ pub fn CGBitmapContextCreate(
         (data, false, bytes_per_row)
     };
 
-    let host_object = CGContextHostObject {
-        subclass: CGContextSubclass::CGBitmapContext(CGBitmapContextData {
+    let host_object =
+        CGContextHostObject::new(CGContextSubclass::CGBitmapContext(CGBitmapContextData {
             data,
             data_is_owned,
             width,
@@ -74,11 +75,7 @@ pub fn CGBitmapContextCreate(
             bytes_per_row,
             color_space: kCGColorSpaceGenericRGB,
             alpha_info: bitmap_info & kCGBitmapAlphaInfoMask,
-        }),
-        // TODO: is this the correct default?
-        rgb_fill_color: (0.0, 0.0, 0.0, 0.0),
-        translation: (0.0, 0.0),
-    };
+        }));
     let isa = env
         .objc
         .get_known_class("_touchHLE_CGContext", &mut env.mem);
@@ -104,6 +101,46 @@ pub fn CGBitmapContextGetHeight(env: &mut Environment, context: CGContextRef) ->
     bitmap_data.height
 }
 
+fn CGBitmapContextGetBytesPerRow(env: &mut Environment, context: CGContextRef) -> GuestUSize {
+    let host_obj = env.objc.borrow::<CGContextHostObject>(context);
+    let CGContextSubclass::CGBitmapContext(bitmap_data) = host_obj.subclass;
+    bitmap_data.bytes_per_row
+}
+
+fn CGBitmapContextGetBitsPerComponent(env: &mut Environment, context: CGContextRef) -> GuestUSize {
+    let host_obj = env.objc.borrow::<CGContextHostObject>(context);
+    let CGContextSubclass::CGBitmapContext(bitmap_data) = host_obj.subclass;
+    bitmap_data.bits_per_component
+}
+
+fn CGBitmapContextGetAlphaInfo(env: &mut Environment, context: CGContextRef) -> CGImageAlphaInfo {
+    let host_obj = env.objc.borrow::<CGContextHostObject>(context);
+    let CGContextSubclass::CGBitmapContext(bitmap_data) = host_obj.subclass;
+    bitmap_data.alpha_info
+}
+
+/// Creates an image from a snapshot of the context's pixels. Later drawing in
+/// the context doesn't affect the image.
+fn CGBitmapContextCreateImage(env: &mut Environment, context: CGContextRef) -> CGImageRef {
+    let host_obj = env.objc.borrow::<CGContextHostObject>(context);
+    let CGContextSubclass::CGBitmapContext(bitmap_data) = host_obj.subclass;
+    let pixel_size = bytes_per_pixel(&bitmap_data) as usize;
+    let pixels = get_pixels(&bitmap_data, &mut env.mem);
+
+    let (width, height) = (bitmap_data.width as usize, bitmap_data.height as usize);
+    let mut image_pixels = Vec::with_capacity(width * height * 4);
+    // Rows are already in top-to-bottom order, see put_pixel().
+    for y in 0..height {
+        let row = &pixels[y * bitmap_data.bytes_per_row as usize..][..width * pixel_size];
+        for pixel in row.chunks_exact(pixel_size) {
+            image_pixels.extend_from_slice(&to_premultiplied_rgba(bitmap_data.alpha_info, pixel));
+        }
+    }
+
+    let image = Image::from_pixels(image_pixels, (bitmap_data.width, bitmap_data.height));
+    cg_image::from_image(env, image)
+}
+
 fn components_for_rgb(bitmap_info: CGBitmapInfo) -> Result<GuestUSize, ()> {
     let byte_order = bitmap_info & kCGBitmapByteOrderMask;
     if byte_order != kCGImageByteOrderDefault && byte_order != kCGImageByteOrder32Big {
@@ -139,6 +176,30 @@ fn bytes_per_pixel(data: &CGBitmapContextData) -> GuestUSize {
     components_for_rgb(alpha_info).unwrap()
 }
 
+/// Convert a pixel in a bitmap context's format to the format used by [Image]
+/// (sRGB RGBA with premultiplied alpha).
+fn to_premultiplied_rgba(alpha_info: CGImageAlphaInfo, pixel: &[u8]) -> [u8; 4] {
+    let premultiply = |[r, g, b, a]: [u8; 4]| {
+        let multiply_by = a as f32 / 255.0;
+        [
+            (r as f32 * multiply_by) as u8,
+            (g as f32 * multiply_by) as u8,
+            (b as f32 * multiply_by) as u8,
+            a,
+        ]
+    };
+    match alpha_info {
+        kCGImageAlphaNone | kCGImageAlphaNoneSkipLast => [pixel[0], pixel[1], pixel[2], 255],
+        kCGImageAlphaNoneSkipFirst => [pixel[1], pixel[2], pixel[3], 255],
+        kCGImageAlphaPremultipliedLast => [pixel[0], pixel[1], pixel[2], pixel[3]],
+        kCGImageAlphaPremultipliedFirst => [pixel[1], pixel[2], pixel[3], pixel[0]],
+        kCGImageAlphaLast => premultiply([pixel[0], pixel[1], pixel[2], pixel[3]]),
+        kCGImageAlphaFirst => premultiply([pixel[1], pixel[2], pixel[3], pixel[0]]),
+        kCGImageAlphaOnly => [0, 0, 0, pixel[0]],
+        _ => unreachable!(), // checked by bytes_per_pixel
+    }
+}
+
 fn get_pixels<'a>(data: &CGBitmapContextData, mem: &'a mut Mem) -> &'a mut [u8] {
     let pixel_data_size = data.height.checked_mul(data.bytes_per_row).unwrap();
     mem.bytes_at_mut(data.data.cast(), pixel_data_size)
@@ -322,6 +383,7 @@ impl CGBitmapContextDrawer<'_> {
             subclass: CGContextSubclass::CGBitmapContext(bitmap_info),
             rgb_fill_color,
             translation,
+            ..
         } = objc.borrow(context);
 
         let pixels = get_pixels(&bitmap_info, mem);
@@ -447,6 +509,61 @@ pub(super) fn draw_image(
     // let _ = std::fs::write(format!("bitmap-{:?}-{:?}-after.data", (image as *const _ as *const ()), (drawer.width(), drawer.height())), &drawer.pixels);
 }
 
+/// Implementation of `CGContextShowText` for `CGBitmapContext`: fill the
+/// pixels covered by text, given a coverage map with top-to-bottom rows (see
+/// [crate::font::Font::rasterize_line]). `transform` maps from co-ordinates
+/// within the coverage map, in pixels, to user space.
+pub(super) fn draw_coverage(
+    env: &mut Environment,
+    context: CGContextRef,
+    coverage: &[f32],
+    size: (u32, u32),
+    transform: CGAffineTransform,
+) {
+    let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
+
+    let translation = drawer.translation();
+    let transform = transform.concat(CGAffineTransform::translation(translation.0, translation.1));
+    let Some(inverse) = transform.invert() else {
+        return;
+    };
+
+    let (width, height) = (size.0 as CGFloat, size.1 as CGFloat);
+    // Find the bounding box of the transformed coverage map.
+    let (mut x_min, mut y_min) = (CGFloat::INFINITY, CGFloat::INFINITY);
+    let (mut x_max, mut y_max) = (CGFloat::NEG_INFINITY, CGFloat::NEG_INFINITY);
+    for (x, y) in [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)] {
+        let corner = transform.apply_to_point(CGPoint { x, y });
+        (x_min, x_max) = (x_min.min(corner.x), x_max.max(corner.x));
+        (y_min, y_max) = (y_min.min(corner.y), y_max.max(corner.y));
+    }
+    let x_start = x_min.floor().max(0.0) as i32;
+    let y_start = y_min.floor().max(0.0) as i32;
+    let x_end = x_max.ceil().min(drawer.width() as CGFloat) as i32;
+    let y_end = y_max.ceil().min(drawer.height() as CGFloat) as i32;
+
+    let (r, g, b, a) = drawer.rgb_fill_color();
+    // TODO: non-nearest-neighbour filtering when the text matrix scales or
+    // rotates the text?
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            let texel = inverse.apply_to_point(CGPoint {
+                x: x as CGFloat + 0.5,
+                y: y as CGFloat + 0.5,
+            });
+            if texel.x < 0.0 || texel.y < 0.0 || texel.x >= width || texel.y >= height {
+                continue;
+            }
+            let idx = texel.y as usize * size.0 as usize + texel.x as usize;
+            let coverage = coverage[idx];
+            if coverage > 0.0 {
+                let color = (r * coverage, g * coverage, b * coverage, a * coverage);
+                drawer.put_pixel((x, y), color);
+            }
+        }
+    }
+}
+
 /// Shortcut for [crate::frameworks::core_animation::composition]. This is a
 /// workaround for not having a `&mut Environment` that should eventually be
 /// removed somehow (TODO).
@@ -461,4 +578,38 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGBitmapContextGetData(_)),
     export_c_func!(CGBitmapContextGetWidth(_)),
     export_c_func!(CGBitmapContextGetHeight(_)),
+    export_c_func!(CGBitmapContextGetBytesPerRow(_)),
+    export_c_func!(CGBitmapContextGetBitsPerComponent(_)),
+    export_c_func!(CGBitmapContextGetAlphaInfo(_)),
+    export_c_func!(CGBitmapContextCreateImage(_)),
 ];
+
+#[cfg(test)]
+#[test]
+fn test_to_premultiplied_rgba() {
+    let pixel = [0x10, 0x20, 0x30, 0x80];
+    assert_eq!(
+        to_premultiplied_rgba(kCGImageAlphaPremultipliedLast, &pixel),
+        [0x10, 0x20, 0x30, 0x80]
+    );
+    assert_eq!(
+        to_premultiplied_rgba(kCGImageAlphaPremultipliedFirst, &pixel),
+        [0x20, 0x30, 0x80, 0x10]
+    );
+    assert_eq!(
+        to_premultiplied_rgba(kCGImageAlphaNoneSkipFirst, &pixel),
+        [0x20, 0x30, 0x80, 0xFF]
+    );
+    assert_eq!(
+        to_premultiplied_rgba(kCGImageAlphaNone, &pixel[..3]),
+        [0x10, 0x20, 0x30, 0xFF]
+    );
+    assert_eq!(
+        to_premultiplied_rgba(kCGImageAlphaLast, &[0xFF, 0x80, 0x00, 0x80]),
+        [0x80, 0x40, 0x00, 0x80]
+    );
+    assert_eq!(
+        to_premultiplied_rgba(kCGImageAlphaOnly, &pixel[..1]),
+        [0, 0, 0, 0x10]
+    );
+}
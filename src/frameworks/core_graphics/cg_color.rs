@@ -0,0 +1,179 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGColor.h`
+//!
+//! Only RGB colors are supported, since those are the only color spaces we
+//! have (see [super::cg_color_space]).
+
+use super::cg_color_space::{CGColorSpaceCreateDeviceRGB, CGColorSpaceRef, CGColorSpaceRetain};
+use super::CGFloat;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, MutPtr};
+use crate::objc::{id, objc_classes, release, ClassExports, HostObject, ObjC};
+use crate::Environment;
+
+pub type CGColorRef = CFTypeRef;
+
+/// Red, green, blue and alpha, in that order.
+const COMPONENT_COUNT: GuestUSize = 4;
+
+struct CGColorHostObject {
+    rgba: (CGFloat, CGFloat, CGFloat, CGFloat),
+    /// Strong reference.
+    color_space: CGColorSpaceRef,
+    /// Copy of the components in guest memory, for [CGColorGetComponents].
+    components: MutPtr<CGFloat>,
+}
+impl HostObject for CGColorHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGColor seems to be a CFType-based type, but in our implementation those
+// are just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGColor: NSObject
+
+- (())dealloc {
+    let &CGColorHostObject {
+        color_space,
+        components,
+        ..
+    } = env.objc.borrow(this);
+    release(env, color_space);
+    env.mem.free(components.cast());
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+fn new_color(
+    env: &mut Environment,
+    color_space: CGColorSpaceRef,
+    rgba: (CGFloat, CGFloat, CGFloat, CGFloat),
+) -> CGColorRef {
+    let (r, g, b, a) = rgba;
+    let components: MutPtr<CGFloat> = env
+        .mem
+        .alloc(guest_size_of::<CGFloat>() * COMPONENT_COUNT)
+        .cast();
+    for (i, component) in (0..COMPONENT_COUNT).zip([r, g, b, a]) {
+        env.mem.write(components + i, component);
+    }
+
+    let host_object = Box::new(CGColorHostObject {
+        rgba,
+        color_space,
+        components,
+    });
+    let class = env.objc.get_known_class("_touchHLE_CGColor", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+/// Shortcut for host code: create a color in the device RGB color space. The
+/// result is not autoreleased.
+pub fn new_rgba(env: &mut Environment, rgba: (CGFloat, CGFloat, CGFloat, CGFloat)) -> CGColorRef {
+    let color_space = CGColorSpaceCreateDeviceRGB(env);
+    new_color(env, color_space, rgba)
+}
+
+/// Get the RGBA components of a color, or [None] if the object isn't a
+/// `CGColorRef`.
+pub fn get_rgba(objc: &ObjC, color: id) -> Option<(CGFloat, CGFloat, CGFloat, CGFloat)> {
+    objc.try_borrow::<CGColorHostObject>(color)
+        .map(|host_object| host_object.rgba)
+}
+
+pub fn CGColorCreate(
+    env: &mut Environment,
+    space: CGColorSpaceRef,
+    components: ConstPtr<CGFloat>,
+) -> CGColorRef {
+    let r = env.mem.read(components);
+    let g = env.mem.read(components + 1);
+    let b = env.mem.read(components + 2);
+    let a = env.mem.read(components + 3);
+    let space = CGColorSpaceRetain(env, space);
+    new_color(env, space, (r, g, b, a))
+}
+
+fn CGColorCreateCopy(env: &mut Environment, color: CGColorRef) -> CGColorRef {
+    // Colors are immutable, so there's no need for a real copy.
+    CGColorRetain(env, color)
+}
+
+fn CGColorCreateCopyWithAlpha(
+    env: &mut Environment,
+    color: CGColorRef,
+    alpha: CGFloat,
+) -> CGColorRef {
+    let &CGColorHostObject {
+        rgba: (r, g, b, _),
+        color_space,
+        ..
+    } = env.objc.borrow(color);
+    let color_space = CGColorSpaceRetain(env, color_space);
+    new_color(env, color_space, (r, g, b, alpha))
+}
+
+pub fn CGColorRelease(env: &mut Environment, color: CGColorRef) {
+    if !color.is_null() {
+        CFRelease(env, color);
+    }
+}
+pub fn CGColorRetain(env: &mut Environment, color: CGColorRef) -> CGColorRef {
+    if !color.is_null() {
+        CFRetain(env, color)
+    } else {
+        color
+    }
+}
+
+fn CGColorEqualToColor(env: &mut Environment, color1: CGColorRef, color2: CGColorRef) -> bool {
+    if color1 == color2 {
+        return true;
+    }
+    if color1.is_null() || color2.is_null() {
+        return false;
+    }
+    get_rgba(&env.objc, color1) == get_rgba(&env.objc, color2)
+}
+
+fn CGColorGetNumberOfComponents(_env: &mut Environment, _color: CGColorRef) -> GuestUSize {
+    COMPONENT_COUNT
+}
+
+fn CGColorGetComponents(env: &mut Environment, color: CGColorRef) -> ConstPtr<CGFloat> {
+    env.objc
+        .borrow::<CGColorHostObject>(color)
+        .components
+        .cast_const()
+}
+
+fn CGColorGetAlpha(env: &mut Environment, color: CGColorRef) -> CGFloat {
+    env.objc.borrow::<CGColorHostObject>(color).rgba.3
+}
+
+fn CGColorGetColorSpace(env: &mut Environment, color: CGColorRef) -> CGColorSpaceRef {
+    env.objc.borrow::<CGColorHostObject>(color).color_space
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGColorCreate(_, _)),
+    export_c_func!(CGColorCreateCopy(_)),
+    export_c_func!(CGColorCreateCopyWithAlpha(_, _)),
+    export_c_func!(CGColorRelease(_)),
+    export_c_func!(CGColorRetain(_)),
+    export_c_func!(CGColorEqualToColor(_, _)),
+    export_c_func!(CGColorGetNumberOfComponents(_)),
+    export_c_func!(CGColorGetComponents(_)),
+    export_c_func!(CGColorGetAlpha(_)),
+    export_c_func!(CGColorGetColorSpace(_)),
+];
@@ -5,10 +5,14 @@
  */
 //! `CGContext.h`
 
+use super::cg_affine_transform::{CGAffineTransform, CGAffineTransformIdentity};
+use super::cg_color::CGColorRef;
 use super::cg_image::CGImageRef;
-use super::{cg_bitmap_context, CGFloat, CGRect};
+use super::{cg_bitmap_context, CGFloat, CGPoint, CGRect};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::frameworks::uikit::{ui_color, ui_font};
+use crate::mem::{ConstPtr, GuestUSize};
 use crate::objc::{objc_classes, ClassExports, HostObject};
 use crate::Environment;
 
@@ -40,8 +44,27 @@ pub(super) struct CGContextHostObject {
     pub(super) rgb_fill_color: (CGFloat, CGFloat, CGFloat, CGFloat),
     /// Current translation. TODO: replace this with a transformation matrix.
     pub(super) translation: (CGFloat, CGFloat),
+    /// Name of the font selected with `CGContextSelectFont`, if any.
+    pub(super) font_name: Option<String>,
+    pub(super) font_size: CGFloat,
+    /// Maps from text space to user space. The translation part is the text
+    /// position.
+    pub(super) text_matrix: CGAffineTransform,
 }
 impl HostObject for CGContextHostObject {}
+impl CGContextHostObject {
+    pub(super) fn new(subclass: CGContextSubclass) -> Self {
+        CGContextHostObject {
+            subclass,
+            // TODO: is this the correct default?
+            rgb_fill_color: (0.0, 0.0, 0.0, 0.0),
+            translation: (0.0, 0.0),
+            font_name: None,
+            font_size: 0.0,
+            text_matrix: CGAffineTransformIdentity,
+        }
+    }
+}
 
 pub(super) enum CGContextSubclass {
     CGBitmapContext(cg_bitmap_context::CGBitmapContextData),
@@ -76,7 +99,12 @@ pub fn CGContextSetRGBFillColor(
         .rgb_fill_color = color;
 }
 
-fn CGContextFillRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+fn CGContextSetFillColorWithColor(env: &mut Environment, context: CGContextRef, color: CGColorRef) {
+    let (r, g, b, a) = ui_color::get_rgba(&env.objc, color);
+    CGContextSetRGBFillColor(env, context, r, g, b, a);
+}
+
+pub fn CGContextFillRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
     cg_bitmap_context::fill_rect(env, context, rect, /* clear: */ false);
 }
 
@@ -104,12 +132,188 @@ fn CGContextDrawImage(
     cg_bitmap_context::draw_image(env, context, rect, image);
 }
 
+pub type CGTextEncoding = i32;
+#[allow(dead_code)]
+pub const kCGEncodingFontSpecific: CGTextEncoding = 0;
+pub const kCGEncodingMacRoman: CGTextEncoding = 1;
+
+pub type CGTextDrawingMode = i32;
+pub const kCGTextFill: CGTextDrawingMode = 0;
+
+fn CGContextSelectFont(
+    env: &mut Environment,
+    context: CGContextRef,
+    name: ConstPtr<u8>,
+    size: CGFloat,
+    text_encoding: CGTextEncoding,
+) {
+    let name = String::from_utf8_lossy(env.mem.cstr_at(name)).into_owned();
+    // Only the MacRoman encoding is supported. Font-specific encodings are
+    // treated the same way, which is right for ASCII text at least.
+    if text_encoding != kCGEncodingMacRoman {
+        log!(
+            "TODO: text encoding {} for font {:?}, treating as MacRoman",
+            text_encoding,
+            name
+        );
+    }
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    host_obj.font_name = Some(name);
+    host_obj.font_size = size;
+}
+
+fn CGContextSetFontSize(env: &mut Environment, context: CGContextRef, size: CGFloat) {
+    env.objc
+        .borrow_mut::<CGContextHostObject>(context)
+        .font_size = size;
+}
+
+fn CGContextSetTextDrawingMode(
+    _env: &mut Environment,
+    _context: CGContextRef,
+    mode: CGTextDrawingMode,
+) {
+    if mode != kCGTextFill {
+        log!("TODO: text drawing mode {}, text will be filled", mode);
+    }
+}
+
+fn CGContextSetTextMatrix(env: &mut Environment, context: CGContextRef, t: CGAffineTransform) {
+    env.objc
+        .borrow_mut::<CGContextHostObject>(context)
+        .text_matrix = t;
+}
+fn CGContextGetTextMatrix(env: &mut Environment, context: CGContextRef) -> CGAffineTransform {
+    env.objc.borrow::<CGContextHostObject>(context).text_matrix
+}
+
+fn CGContextSetTextPosition(env: &mut Environment, context: CGContextRef, x: CGFloat, y: CGFloat) {
+    let text_matrix = &mut env
+        .objc
+        .borrow_mut::<CGContextHostObject>(context)
+        .text_matrix;
+    text_matrix.tx = x;
+    text_matrix.ty = y;
+}
+fn CGContextGetTextPosition(env: &mut Environment, context: CGContextRef) -> CGPoint {
+    let text_matrix = env.objc.borrow::<CGContextHostObject>(context).text_matrix;
+    CGPoint {
+        x: text_matrix.tx,
+        y: text_matrix.ty,
+    }
+}
+
+fn CGContextShowText(
+    env: &mut Environment,
+    context: CGContextRef,
+    string: ConstPtr<u8>,
+    length: GuestUSize,
+) {
+    let text = decode_mac_roman(env.mem.bytes_at(string, length));
+    let host_obj = env.objc.borrow::<CGContextHostObject>(context);
+    let Some(font_name) = host_obj.font_name.clone() else {
+        log!("Warning: No font selected, not drawing text {:?}", text);
+        return;
+    };
+    let font_size = host_obj.font_size;
+    let text_matrix = host_obj.text_matrix;
+
+    let font = ui_font::font_for_name(env, &font_name, &text);
+    let (coverage, size, (baseline_x, baseline_y), advance) = font.rasterize_line(font_size, &text);
+
+    // The coverage map's rows go downwards, but text space's y axis points
+    // upwards, and its origin is the start of the baseline.
+    let coverage_to_text_space = CGAffineTransform {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: -1.0,
+        tx: -baseline_x as CGFloat,
+        ty: baseline_y as CGFloat,
+    };
+    cg_bitmap_context::draw_coverage(
+        env,
+        context,
+        &coverage,
+        size,
+        coverage_to_text_space.concat(text_matrix),
+    );
+
+    // The next text is drawn after this text.
+    let text_matrix = &mut env
+        .objc
+        .borrow_mut::<CGContextHostObject>(context)
+        .text_matrix;
+    text_matrix.tx += text_matrix.a * advance;
+    text_matrix.ty += text_matrix.b * advance;
+}
+
+fn CGContextShowTextAtPoint(
+    env: &mut Environment,
+    context: CGContextRef,
+    x: CGFloat,
+    y: CGFloat,
+    string: ConstPtr<u8>,
+    length: GuestUSize,
+) {
+    CGContextSetTextPosition(env, context, x, y);
+    CGContextShowText(env, context, string, length);
+}
+
+/// Characters 0x80 to 0xFF of the MacRoman encoding. The rest is ASCII.
+#[rustfmt::skip]
+const MAC_ROMAN_HIGH_HALF: [char; 128] = [
+    'Ä', 'Å', 'Ç', 'É', 'Ñ', 'Ö', 'Ü', 'á', 'à', 'â', 'ä', 'ã', 'å', 'ç', 'é', 'è',
+    'ê', 'ë', 'í', 'ì', 'î', 'ï', 'ñ', 'ó', 'ò', 'ô', 'ö', 'õ', 'ú', 'ù', 'û', 'ü',
+    '†', '°', '¢', '£', '§', '•', '¶', 'ß', '®', '©', '™', '´', '¨', '≠', 'Æ', 'Ø',
+    '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑', '∏', 'π', '∫', 'ª', 'º', 'Ω', 'æ', 'ø',
+    '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«', '»', '…', '\u{A0}', 'À', 'Ã', 'Õ', 'Œ', 'œ',
+    '–', '—', '“', '”', '‘', '’', '÷', '◊', 'ÿ', 'Ÿ', '⁄', '€', '‹', '›', 'ﬁ', 'ﬂ',
+    '‡', '·', '‚', '„', '‰', 'Â', 'Ê', 'Á', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', 'Ó', 'Ô',
+    '\u{F8FF}', 'Ò', 'Ú', 'Û', 'Ù', 'ı', 'ˆ', '˜', '¯', '˘', '˙', '˚', '¸', '˝', '˛', 'ˇ',
+];
+
+fn decode_mac_roman(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| {
+            if byte < 0x80 {
+                byte as char
+            } else {
+                MAC_ROMAN_HIGH_HALF[byte as usize - 0x80]
+            }
+        })
+        .collect()
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGContextRetain(_)),
     export_c_func!(CGContextRelease(_)),
     export_c_func!(CGContextSetRGBFillColor(_, _, _, _, _)),
+    export_c_func!(CGContextSetFillColorWithColor(_, _)),
     export_c_func!(CGContextFillRect(_, _)),
     export_c_func!(CGContextClearRect(_, _)),
     export_c_func!(CGContextTranslateCTM(_, _, _)),
     export_c_func!(CGContextDrawImage(_, _, _)),
+    export_c_func!(CGContextSelectFont(_, _, _, _)),
+    export_c_func!(CGContextSetFontSize(_, _)),
+    export_c_func!(CGContextSetTextDrawingMode(_, _)),
+    export_c_func!(CGContextSetTextMatrix(_, _)),
+    export_c_func!(CGContextGetTextMatrix(_)),
+    export_c_func!(CGContextSetTextPosition(_, _, _)),
+    export_c_func!(CGContextGetTextPosition(_)),
+    export_c_func!(CGContextShowText(_, _, _)),
+    export_c_func!(CGContextShowTextAtPoint(_, _, _, _, _)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_mac_roman() {
+        assert_eq!(decode_mac_roman(b"Score: 10"), "Score: 10");
+        assert_eq!(decode_mac_roman(&[0x43, 0x61, 0x66, 0x8E]), "Café");
+        assert_eq!(decode_mac_roman(&[0xA9, 0x20, 0xD2, 0xDB, 0xD3]), "© “€”");
+    }
+}
@@ -89,24 +89,51 @@ pub fn borrow_image(objc: &ObjC, image: CGImageRef) -> &Image {
 
 // TODO: More create methods.
 
-fn CGImageCreateWithPNGDataProvider(
+/// Shared implementation of `CGImageCreateWithPNGDataProvider` and
+/// `CGImageCreateWithJPEGDataProvider`. [Image] detects the format itself.
+fn create_with_data_provider(
     env: &mut Environment,
     source: CGDataProviderRef,
     decode: ConstPtr<CGFloat>,
-    _should_interpolate: bool, // TODO
-    _intent: i32,              // TODO (should be CGColorRenderingIntent)
 ) -> CGImageRef {
     assert!(decode.is_null()); // TODO
 
     let bytes = cg_data_provider::borrow_bytes(env, source);
-    let Ok(image) = Image::from_bytes(bytes) else {
-        // Docs don't say what happens on failure, but this would make sense.
-        return nil;
+    let image = match Image::from_bytes(bytes) {
+        Ok(image) => image,
+        Err(reason) => {
+            // Docs don't say what happens on failure, but this would make sense.
+            log!(
+                "Warning: couldn't decode image ({}), returning NULL",
+                reason
+            );
+            return nil;
+        }
     };
 
     from_image(env, image)
 }
 
+fn CGImageCreateWithPNGDataProvider(
+    env: &mut Environment,
+    source: CGDataProviderRef,
+    decode: ConstPtr<CGFloat>,
+    _should_interpolate: bool, // TODO
+    _intent: i32,              // TODO (should be CGColorRenderingIntent)
+) -> CGImageRef {
+    create_with_data_provider(env, source, decode)
+}
+
+fn CGImageCreateWithJPEGDataProvider(
+    env: &mut Environment,
+    source: CGDataProviderRef,
+    decode: ConstPtr<CGFloat>,
+    _should_interpolate: bool, // TODO
+    _intent: i32,              // TODO (should be CGColorRenderingIntent)
+) -> CGImageRef {
+    create_with_data_provider(env, source, decode)
+}
+
 fn CGImageGetAlphaInfo(_env: &mut Environment, _image: CGImageRef) -> CGImageAlphaInfo {
     // our Image type always returns premultiplied RGBA
     // (the premultiplied part must match what the real UIImage does, but
@@ -139,6 +166,16 @@ fn CGImageGetHeight(env: &mut Environment, image: CGImageRef) -> GuestUSize {
     height
 }
 
+fn CGImageGetBitsPerComponent(_env: &mut Environment, _image: CGImageRef) -> GuestUSize {
+    8 // see Image::pixels()
+}
+fn CGImageGetBitsPerPixel(_env: &mut Environment, _image: CGImageRef) -> GuestUSize {
+    32
+}
+fn CGImageGetBytesPerRow(env: &mut Environment, image: CGImageRef) -> GuestUSize {
+    CGImageGetWidth(env, image).checked_mul(4).unwrap()
+}
+
 fn CGImageGetDataProvider(env: &mut Environment, image: CGImageRef) -> CGDataProviderRef {
     // CGImageGetDataProvider() seems to be intended to return the underlying
     // data provider that is retained by the CGImage. That's not how CGImage is
@@ -155,9 +192,13 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGImageRelease(_)),
     export_c_func!(CGImageRetain(_)),
     export_c_func!(CGImageCreateWithPNGDataProvider(_, _, _, _)),
+    export_c_func!(CGImageCreateWithJPEGDataProvider(_, _, _, _)),
     export_c_func!(CGImageGetAlphaInfo(_)),
     export_c_func!(CGImageGetColorSpace(_)),
     export_c_func!(CGImageGetWidth(_)),
     export_c_func!(CGImageGetHeight(_)),
+    export_c_func!(CGImageGetBitsPerComponent(_)),
+    export_c_func!(CGImageGetBitsPerPixel(_)),
+    export_c_func!(CGImageGetBytesPerRow(_)),
     export_c_func!(CGImageGetDataProvider(_)),
 ];
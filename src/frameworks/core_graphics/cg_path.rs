@@ -0,0 +1,523 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGPath.h`
+
+use super::cg_affine_transform::CGAffineTransform;
+use super::{CGFloat, CGPoint, CGRect};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::mem::{guest_size_of, ConstPtr, MutPtr, MutVoidPtr, SafeRead};
+use crate::objc::{objc_classes, ClassExports, HostObject, ObjC};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGPath seems to be a CFType-based type, but in our implementation those
+// are just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGPath: NSObject
+@end
+
+};
+
+/// An element of a path. The points have already had any transform applied.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathElement {
+    MoveToPoint(CGPoint),
+    AddLineToPoint(CGPoint),
+    AddQuadCurveToPoint(CGPoint, CGPoint),
+    AddCurveToPoint(CGPoint, CGPoint, CGPoint),
+    CloseSubpath,
+}
+
+struct CGPathHostObject {
+    elements: Vec<PathElement>,
+}
+impl HostObject for CGPathHostObject {}
+
+pub type CGPathRef = CFTypeRef;
+pub type CGMutablePathRef = CFTypeRef;
+
+pub fn CGPathCreateMutable(env: &mut Environment) -> CGMutablePathRef {
+    let isa = env.objc.get_known_class("_touchHLE_CGPath", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CGPathHostObject {
+            elements: Vec::new(),
+        }),
+        &mut env.mem,
+    )
+}
+
+pub fn CGPathRelease(env: &mut Environment, path: CGPathRef) {
+    if !path.is_null() {
+        CFRelease(env, path);
+    }
+}
+pub fn CGPathRetain(env: &mut Environment, path: CGPathRef) -> CGPathRef {
+    if !path.is_null() {
+        CFRetain(env, path)
+    } else {
+        path
+    }
+}
+
+/// Apply the optional transform that the path construction functions take.
+fn transform_point(
+    env: &Environment,
+    transform: ConstPtr<CGAffineTransform>,
+    point: CGPoint,
+) -> CGPoint {
+    if transform.is_null() {
+        return point;
+    }
+    env.mem.read(transform).apply_to_point(point)
+}
+
+fn add_element(env: &mut Environment, path: CGMutablePathRef, element: PathElement) {
+    env.objc
+        .borrow_mut::<CGPathHostObject>(path)
+        .elements
+        .push(element);
+}
+
+pub fn CGPathMoveToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    transform: ConstPtr<CGAffineTransform>,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let point = transform_point(env, transform, CGPoint { x, y });
+    add_element(env, path, PathElement::MoveToPoint(point));
+}
+
+pub fn CGPathAddLineToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    transform: ConstPtr<CGAffineTransform>,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let point = transform_point(env, transform, CGPoint { x, y });
+    add_element(env, path, PathElement::AddLineToPoint(point));
+}
+
+pub fn CGPathAddQuadCurveToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    transform: ConstPtr<CGAffineTransform>,
+    cpx: CGFloat,
+    cpy: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let control = transform_point(env, transform, CGPoint { x: cpx, y: cpy });
+    let point = transform_point(env, transform, CGPoint { x, y });
+    add_element(env, path, PathElement::AddQuadCurveToPoint(control, point));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn CGPathAddCurveToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    transform: ConstPtr<CGAffineTransform>,
+    cp1x: CGFloat,
+    cp1y: CGFloat,
+    cp2x: CGFloat,
+    cp2y: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let control1 = transform_point(env, transform, CGPoint { x: cp1x, y: cp1y });
+    let control2 = transform_point(env, transform, CGPoint { x: cp2x, y: cp2y });
+    let point = transform_point(env, transform, CGPoint { x, y });
+    add_element(
+        env,
+        path,
+        PathElement::AddCurveToPoint(control1, control2, point),
+    );
+}
+
+pub fn CGPathCloseSubpath(env: &mut Environment, path: CGMutablePathRef) {
+    add_element(env, path, PathElement::CloseSubpath);
+}
+
+pub fn CGPathAddRect(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    transform: ConstPtr<CGAffineTransform>,
+    rect: CGRect,
+) {
+    let (x, y) = (rect.origin.x, rect.origin.y);
+    let (width, height) = (rect.size.width, rect.size.height);
+    CGPathMoveToPoint(env, path, transform, x, y);
+    CGPathAddLineToPoint(env, path, transform, x + width, y);
+    CGPathAddLineToPoint(env, path, transform, x + width, y + height);
+    CGPathAddLineToPoint(env, path, transform, x, y + height);
+    CGPathCloseSubpath(env, path);
+}
+
+pub fn CGPathAddEllipseInRect(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    transform: ConstPtr<CGAffineTransform>,
+    rect: CGRect,
+) {
+    // Distance of the control points from the ends of each quarter, as a
+    // fraction of the radius, for the usual cubic approximation of a circle.
+    const KAPPA: CGFloat = 0.552_284_8;
+
+    let (rx, ry) = (rect.size.width / 2.0, rect.size.height / 2.0);
+    let (cx, cy) = (rect.origin.x + rx, rect.origin.y + ry);
+    let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+    // Like Apple's implementation, this starts on the right and goes
+    // counter-clockwise (in a co-ordinate space where y points up).
+    CGPathMoveToPoint(env, path, transform, cx + rx, cy);
+    #[rustfmt::skip]
+    let quarters = [
+        [cx + rx, cy + ky, cx + kx, cy + ry, cx, cy + ry],
+        [cx - kx, cy + ry, cx - rx, cy + ky, cx - rx, cy],
+        [cx - rx, cy - ky, cx - kx, cy - ry, cx, cy - ry],
+        [cx + kx, cy - ry, cx + rx, cy - ky, cx + rx, cy],
+    ];
+    for [cp1x, cp1y, cp2x, cp2y, x, y] in quarters {
+        CGPathAddCurveToPoint(env, path, transform, cp1x, cp1y, cp2x, cp2y, x, y);
+    }
+    CGPathCloseSubpath(env, path);
+}
+
+pub fn CGPathIsEmpty(env: &mut Environment, path: CGPathRef) -> bool {
+    path.is_null()
+        || env
+            .objc
+            .borrow::<CGPathHostObject>(path)
+            .elements
+            .is_empty()
+}
+
+pub type CGPathElementType = i32;
+pub const kCGPathElementMoveToPoint: CGPathElementType = 0;
+pub const kCGPathElementAddLineToPoint: CGPathElementType = 1;
+pub const kCGPathElementAddQuadCurveToPoint: CGPathElementType = 2;
+pub const kCGPathElementAddCurveToPoint: CGPathElementType = 3;
+pub const kCGPathElementCloseSubpath: CGPathElementType = 4;
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+struct CGPathElement {
+    type_: CGPathElementType,
+    points: MutPtr<CGPoint>,
+}
+unsafe impl SafeRead for CGPathElement {}
+
+/// `void (*function)(void *info, const CGPathElement *element)`
+type CGPathApplierFunction = GuestFunction;
+
+fn CGPathApply(
+    env: &mut Environment,
+    path: CGPathRef,
+    info: MutVoidPtr,
+    function: CGPathApplierFunction,
+) {
+    if path.is_null() {
+        return;
+    }
+    // Copied so the path can be modified by the callback.
+    let elements = env.objc.borrow::<CGPathHostObject>(path).elements.clone();
+
+    // Curves have up to three points.
+    let points: MutPtr<CGPoint> = env.mem.alloc(guest_size_of::<CGPoint>() * 3).cast();
+    let element_ptr: MutPtr<CGPathElement> =
+        env.mem.alloc_and_write(CGPathElement { type_: 0, points });
+    for element in elements {
+        let (type_, element_points) = match element {
+            PathElement::MoveToPoint(point) => (kCGPathElementMoveToPoint, vec![point]),
+            PathElement::AddLineToPoint(point) => (kCGPathElementAddLineToPoint, vec![point]),
+            PathElement::AddQuadCurveToPoint(control, point) => {
+                (kCGPathElementAddQuadCurveToPoint, vec![control, point])
+            }
+            PathElement::AddCurveToPoint(control1, control2, point) => (
+                kCGPathElementAddCurveToPoint,
+                vec![control1, control2, point],
+            ),
+            PathElement::CloseSubpath => (kCGPathElementCloseSubpath, vec![]),
+        };
+        for (i, &point) in element_points.iter().enumerate() {
+            env.mem.write(points + i as u32, point);
+        }
+        env.mem.write(element_ptr, CGPathElement { type_, points });
+        let _: () = function.call_from_host(env, (info, element_ptr.cast_const()));
+    }
+    env.mem.free(element_ptr.cast());
+    env.mem.free(points.cast());
+}
+
+/// Default flatness (maximum distance between a curve and its approximation
+/// as line segments) for flattening paths, in device pixels.
+pub const DEFAULT_FLATNESS: CGFloat = 0.5;
+
+fn CGPathContainsPoint(
+    env: &mut Environment,
+    path: CGPathRef,
+    transform: ConstPtr<CGAffineTransform>,
+    point: CGPoint,
+    even_odd: bool,
+) -> bool {
+    if path.is_null() {
+        return false;
+    }
+    let mut subpaths = flatten_path(&env.objc, path, DEFAULT_FLATNESS);
+    for subpath in subpaths.iter_mut() {
+        for point in subpath.points.iter_mut() {
+            *point = transform_point(env, transform, *point);
+        }
+    }
+    contains_point(&subpaths, point, even_odd)
+}
+
+/// Check if a point is inside the filled area of a flattened path, using
+/// either the even-odd or the non-zero winding rule. All subpaths are treated
+/// as closed, like when filling.
+pub fn contains_point(subpaths: &[FlattenedSubpath], point: CGPoint, even_odd: bool) -> bool {
+    let mut winding: i32 = 0;
+    for subpath in subpaths {
+        let points = &subpath.points;
+        for (i, &a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            // Count crossings of a ray going in the positive x direction.
+            if (a.y <= point.y) == (b.y <= point.y) {
+                continue;
+            }
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if x > point.x {
+                winding += if b.y > a.y { 1 } else { -1 };
+            }
+        }
+    }
+    if even_odd {
+        winding % 2 != 0
+    } else {
+        winding != 0
+    }
+}
+
+/// A subpath that has been converted to a sequence of straight lines.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlattenedSubpath {
+    pub points: Vec<CGPoint>,
+    pub closed: bool,
+}
+
+/// Shortcut for use by the compositor: convert a path to a list of subpaths
+/// made only of straight lines. Curves are split into enough segments that
+/// they stray no further than `flatness` from the true curve.
+pub fn flatten_path(objc: &ObjC, path: CGPathRef, flatness: CGFloat) -> Vec<FlattenedSubpath> {
+    flatten_elements(&objc.borrow::<CGPathHostObject>(path).elements, flatness)
+}
+
+fn flatten_elements(elements: &[PathElement], flatness: CGFloat) -> Vec<FlattenedSubpath> {
+    fn lerp(a: CGPoint, b: CGPoint, t: CGFloat) -> CGPoint {
+        CGPoint {
+            x: a.x + (b.x - a.x) * t,
+            y: a.y + (b.y - a.y) * t,
+        }
+    }
+    /// Length of the second difference `a - 2b + c`, which bounds how far a
+    /// curve deviates from a straight line.
+    fn second_difference(a: CGPoint, b: CGPoint, c: CGPoint) -> CGFloat {
+        let x = a.x - 2.0 * b.x + c.x;
+        let y = a.y - 2.0 * b.y + c.y;
+        (x * x + y * y).sqrt()
+    }
+    /// Splitting a curve with maximum second derivative `d` into `n` equal
+    /// steps gives an error of at most `d / (8 * n * n)`.
+    fn segment_count(max_second_derivative: CGFloat, flatness: CGFloat) -> u32 {
+        let count = (max_second_derivative / (8.0 * flatness)).sqrt().ceil();
+        // Also avoids problems with NaN and infinity.
+        count.clamp(1.0, 256.0) as u32
+    }
+
+    let mut subpaths = Vec::new();
+    let mut current = FlattenedSubpath {
+        points: Vec::new(),
+        closed: false,
+    };
+    for &element in elements {
+        // Adding to a path without an explicit starting point starts from the
+        // origin.
+        let last = current
+            .points
+            .last()
+            .copied()
+            .unwrap_or(CGPoint { x: 0.0, y: 0.0 });
+        match element {
+            PathElement::MoveToPoint(point) => {
+                let finished = std::mem::replace(
+                    &mut current,
+                    FlattenedSubpath {
+                        points: vec![point],
+                        closed: false,
+                    },
+                );
+                if finished.points.len() > 1 {
+                    subpaths.push(finished);
+                }
+            }
+            PathElement::AddLineToPoint(point) => {
+                if current.points.is_empty() {
+                    current.points.push(last);
+                }
+                current.points.push(point);
+            }
+            PathElement::AddQuadCurveToPoint(control, point) => {
+                if current.points.is_empty() {
+                    current.points.push(last);
+                }
+                let segments =
+                    segment_count(2.0 * second_difference(last, control, point), flatness);
+                for i in 1..=segments {
+                    let t = i as CGFloat / segments as CGFloat;
+                    current
+                        .points
+                        .push(lerp(lerp(last, control, t), lerp(control, point, t), t));
+                }
+            }
+            PathElement::AddCurveToPoint(control1, control2, point) => {
+                if current.points.is_empty() {
+                    current.points.push(last);
+                }
+                let segments = segment_count(
+                    6.0 * second_difference(last, control1, control2)
+                        .max(second_difference(control1, control2, point)),
+                    flatness,
+                );
+                for i in 1..=segments {
+                    let t = i as CGFloat / segments as CGFloat;
+                    let a = lerp(last, control1, t);
+                    let b = lerp(control1, control2, t);
+                    let c = lerp(control2, point, t);
+                    current.points.push(lerp(lerp(a, b, t), lerp(b, c, t), t));
+                }
+            }
+            PathElement::CloseSubpath => {
+                let Some(&start) = current.points.first() else {
+                    continue;
+                };
+                current.closed = true;
+                // A new subpath implicitly starts where the closed one did.
+                let finished = std::mem::replace(
+                    &mut current,
+                    FlattenedSubpath {
+                        points: vec![start],
+                        closed: false,
+                    },
+                );
+                if finished.points.len() > 1 {
+                    subpaths.push(finished);
+                }
+            }
+        }
+    }
+    if current.points.len() > 1 {
+        subpaths.push(current);
+    }
+    subpaths
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGPathCreateMutable()),
+    export_c_func!(CGPathRelease(_)),
+    export_c_func!(CGPathRetain(_)),
+    export_c_func!(CGPathMoveToPoint(_, _, _, _)),
+    export_c_func!(CGPathAddLineToPoint(_, _, _, _)),
+    export_c_func!(CGPathAddQuadCurveToPoint(_, _, _, _, _, _)),
+    export_c_func!(CGPathAddCurveToPoint(_, _, _, _, _, _, _, _)),
+    export_c_func!(CGPathCloseSubpath(_)),
+    export_c_func!(CGPathAddRect(_, _, _)),
+    export_c_func!(CGPathAddEllipseInRect(_, _, _)),
+    export_c_func!(CGPathIsEmpty(_)),
+    export_c_func!(CGPathApply(_, _, _)),
+    export_c_func!(CGPathContainsPoint(_, _, _, _)),
+];
+
+#[cfg(test)]
+#[test]
+fn test_flatten_elements() {
+    fn p(x: CGFloat, y: CGFloat) -> CGPoint {
+        CGPoint { x, y }
+    }
+
+    let triangle = [
+        PathElement::MoveToPoint(p(0.0, 0.0)),
+        PathElement::AddLineToPoint(p(10.0, 0.0)),
+        PathElement::AddLineToPoint(p(10.0, 10.0)),
+        PathElement::CloseSubpath,
+        PathElement::MoveToPoint(p(20.0, 20.0)),
+        PathElement::AddCurveToPoint(p(20.0, 30.0), p(30.0, 30.0), p(30.0, 20.0)),
+    ];
+    let subpaths = flatten_elements(&triangle, 0.2);
+    assert_eq!(subpaths.len(), 2);
+    assert_eq!(
+        subpaths[0],
+        FlattenedSubpath {
+            points: vec![p(0.0, 0.0), p(10.0, 0.0), p(10.0, 10.0)],
+            closed: true,
+        }
+    );
+    assert!(!subpaths[1].closed);
+    // The second differences are both 10*sqrt(2), so 6*10*sqrt(2)/(8*0.2)
+    // rounds up to 8 segments.
+    assert_eq!(subpaths[1].points.len(), 1 + 8);
+    assert_eq!(subpaths[1].points.last(), Some(&p(30.0, 20.0)));
+    // The curve is symmetric, so its middle point should be at the middle.
+    let middle = subpaths[1].points[4];
+    assert!((middle.x - 25.0).abs() < 0.001 && (middle.y - 27.5).abs() < 0.001);
+    // A tighter tolerance gives more segments.
+    let subpaths = flatten_elements(&triangle, 0.01);
+    assert!(subpaths[1].points.len() > 1 + 8);
+}
+
+#[cfg(test)]
+#[test]
+fn test_contains_point() {
+    fn p(x: CGFloat, y: CGFloat) -> CGPoint {
+        CGPoint { x, y }
+    }
+    fn square(x: CGFloat, y: CGFloat, size: CGFloat, clockwise: bool) -> FlattenedSubpath {
+        let mut points = vec![
+            p(x, y),
+            p(x + size, y),
+            p(x + size, y + size),
+            p(x, y + size),
+        ];
+        if clockwise {
+            points.reverse();
+        }
+        FlattenedSubpath {
+            points,
+            closed: true,
+        }
+    }
+
+    let outer = square(0.0, 0.0, 10.0, false);
+    assert!(contains_point(&[outer.clone()], p(5.0, 5.0), false));
+    assert!(!contains_point(&[outer.clone()], p(15.0, 5.0), false));
+    assert!(!contains_point(&[outer.clone()], p(5.0, -1.0), true));
+
+    // A hole made with the same direction is only a hole for even-odd.
+    let same = [outer.clone(), square(2.0, 2.0, 6.0, false)];
+    assert!(contains_point(&same, p(5.0, 5.0), false));
+    assert!(!contains_point(&same, p(5.0, 5.0), true));
+    assert!(contains_point(&same, p(1.0, 5.0), true));
+    // A hole made with the opposite direction is a hole for both rules.
+    let opposite = [outer, square(2.0, 2.0, 6.0, true)];
+    assert!(!contains_point(&opposite, p(5.0, 5.0), false));
+    assert!(!contains_point(&opposite, p(5.0, 5.0), true));
+}
@@ -17,25 +17,36 @@ pub mod ns_bundle;
 pub mod ns_character_set;
 pub mod ns_coder;
 pub mod ns_data;
+pub mod ns_date;
+pub mod ns_date_formatter;
 pub mod ns_dictionary;
 pub mod ns_enumerator;
+pub mod ns_error;
 pub mod ns_file_manager;
+pub mod ns_invocation;
 pub mod ns_keyed_unarchiver;
 pub mod ns_locale;
 pub mod ns_log;
+pub mod ns_method_signature;
 pub mod ns_notification;
 pub mod ns_notification_center;
 pub mod ns_null;
+pub mod ns_number_formatter;
 pub mod ns_objc_runtime;
 pub mod ns_object;
+pub mod ns_predicate;
 pub mod ns_process_info;
 pub mod ns_property_list_serialization;
 pub mod ns_run_loop;
 pub mod ns_set;
 pub mod ns_string;
 pub mod ns_thread;
+pub mod ns_time_zone;
 pub mod ns_timer;
 pub mod ns_url;
+pub mod ns_url_connection;
+pub mod ns_url_request;
+pub mod ns_url_response;
 pub mod ns_user_defaults;
 pub mod ns_value;
 
@@ -49,12 +60,17 @@ pub struct State {
     ns_null: ns_null::State,
     ns_run_loop: ns_run_loop::State,
     ns_string: ns_string::State,
+    ns_thread: ns_thread::State,
     ns_user_defaults: ns_user_defaults::State,
+    ns_value: ns_value::State,
 }
 
 pub type NSInteger = i32;
 pub type NSUInteger = u32;
 
+/// Returned by search methods when nothing was found.
+pub const NSNotFound: NSInteger = NSInteger::MAX;
+
 #[derive(Debug)]
 #[repr(C, packed)]
 pub struct NSRange {
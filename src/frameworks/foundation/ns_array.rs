@@ -6,10 +6,17 @@
 //! The `NSArray` class cluster, including `NSMutableArray`.
 
 use super::ns_property_list_serialization::deserialize_plist_from_file;
-use super::{ns_keyed_unarchiver, ns_string, ns_url, NSUInteger};
+use super::{
+    ns_keyed_unarchiver, ns_string, ns_url, NSComparisonResult, NSInteger, NSNotFound,
+    NSOrderedDescending, NSUInteger,
+};
+use crate::abi::CallFromHost;
 use crate::fs::GuestPath;
+use crate::libc::blocks;
+use crate::mem::MutPtr;
 use crate::objc::{
-    autorelease, id, msg_class, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+    autorelease, id, msg, msg_class, msg_send, objc_classes, release, retain, ClassExports,
+    HostObject, NSZonePtr, SEL,
 };
 use crate::Environment;
 
@@ -75,6 +82,69 @@ pub const CLASSES: ClassExports = objc_classes! {
     retain(env, this)
 }
 
+- (NSUInteger)indexOfObject:(id)object {
+    // Objects are compared with isEqual:, not by identity.
+    let count: NSUInteger = msg![env; this count];
+    for i in 0..count {
+        let current: id = msg![env; this objectAtIndex:i];
+        if msg![env; current isEqual:object] {
+            return i;
+        }
+    }
+    NSNotFound as NSUInteger
+}
+
+- (bool)containsObject:(id)object {
+    let index: NSUInteger = msg![env; this indexOfObject:object];
+    index != NSNotFound as NSUInteger
+}
+
+- (id)sortedArrayUsingSelector:(SEL)selector {
+    let mut objects = to_vec(env, this);
+    sort_using_selector(env, &mut objects, selector);
+    for &object in &objects {
+        retain(env, object);
+    }
+    let new = from_vec(env, objects);
+    autorelease(env, new)
+}
+
+- (id)sortedArrayUsingComparator:(id)comparator { // NSComparator (block)
+    let mut objects = to_vec(env, this);
+    sort_using_comparator(env, &mut objects, comparator);
+    for &object in &objects {
+        retain(env, object);
+    }
+    let new = from_vec(env, objects);
+    autorelease(env, new)
+}
+
+- (())enumerateObjectsUsingBlock:(id)block { // void (^)(id, NSUInteger, BOOL*)
+    let invoke = blocks::get_invoke(env, block);
+    let objects = to_vec(env, this);
+    let stop_ptr: MutPtr<u8> = env.mem.alloc_and_write(0);
+    for (i, object) in objects.into_iter().enumerate() {
+        let i: NSUInteger = i.try_into().unwrap();
+        let _: () = invoke.call_from_host(env, (block, object, i, stop_ptr));
+        if env.mem.read(stop_ptr) != 0 {
+            break;
+        }
+    }
+    env.mem.free(stop_ptr.cast());
+}
+
+- (id)filteredArrayUsingPredicate:(id)predicate { // NSPredicate*
+    let objects = to_vec(env, this);
+    let mut filtered = Vec::new();
+    for object in objects {
+        if msg![env; predicate evaluateWithObject:object] {
+            filtered.push(retain(env, object));
+        }
+    }
+    let new = from_vec(env, filtered);
+    autorelease(env, new)
+}
+
 @end
 
 // NSMutableArray is an abstract class. A subclass must provide everything
@@ -211,6 +281,19 @@ pub const CLASSES: ClassExports = objc_classes! {
     release(env, object)
 }
 
+- (())sortUsingSelector:(SEL)selector {
+    // The array is copied so that the comparisons can safely look at it.
+    let mut objects = env.objc.borrow::<ArrayHostObject>(this).array.clone();
+    sort_using_selector(env, &mut objects, selector);
+    env.objc.borrow_mut::<ArrayHostObject>(this).array = objects;
+}
+
+- (())sortUsingComparator:(id)comparator { // NSComparator (block)
+    let mut objects = env.objc.borrow::<ArrayHostObject>(this).array.clone();
+    sort_using_comparator(env, &mut objects, comparator);
+    env.objc.borrow_mut::<ArrayHostObject>(this).array = objects;
+}
+
 @end
 
 // Special variant for use by CFArray with NULL callbacks: objects aren't
@@ -241,3 +324,91 @@ pub fn from_vec(env: &mut Environment, objects: Vec<id>) -> id {
     env.objc.borrow_mut::<ArrayHostObject>(array).array = objects;
     array
 }
+
+/// Shortcut for host code: get the objects in an array. They are not retained.
+fn to_vec(env: &mut Environment, array: id) -> Vec<id> {
+    let count: NSUInteger = msg![env; array count];
+    (0..count)
+        .map(|i| msg![env; array objectAtIndex:i])
+        .collect()
+}
+
+/// Sort objects using a comparison method, e.g. `compare:`, which is sent to
+/// each object with another object as the argument.
+fn sort_using_selector(env: &mut Environment, objects: &mut [id], selector: SEL) {
+    stable_sort(objects, |a, b| {
+        let res: NSComparisonResult = msg_send(env, (a, selector, b));
+        res
+    });
+}
+
+/// Sort objects using an `NSComparator` block.
+fn sort_using_comparator(env: &mut Environment, objects: &mut [id], comparator: id) {
+    let invoke = blocks::get_invoke(env, comparator);
+    stable_sort(objects, |a, b| {
+        let res: NSComparisonResult = invoke.call_from_host(env, (comparator, a, b));
+        res
+    });
+}
+
+/// Stable merge sort. The comparison function returns an [NSComparisonResult].
+///
+/// This is used rather than [slice::sort_by] because the guest's comparison
+/// function may not be a total order, and that must not cause a panic.
+fn stable_sort<T: Copy>(items: &mut [T], mut compare: impl FnMut(T, T) -> NSInteger) {
+    fn merge_sort<T: Copy>(
+        items: &mut [T],
+        scratch: &mut Vec<T>,
+        compare: &mut impl FnMut(T, T) -> NSInteger,
+    ) {
+        if items.len() <= 1 {
+            return;
+        }
+        let mid = items.len() / 2;
+        merge_sort(&mut items[..mid], scratch, compare);
+        merge_sort(&mut items[mid..], scratch, compare);
+
+        scratch.clear();
+        let (mut i, mut j) = (0, mid);
+        while i < mid && j < items.len() {
+            // Only take from the right half if it is strictly smaller, so
+            // that equal items keep their order.
+            if compare(items[i], items[j]) == NSOrderedDescending {
+                scratch.push(items[j]);
+                j += 1;
+            } else {
+                scratch.push(items[i]);
+                i += 1;
+            }
+        }
+        scratch.extend_from_slice(&items[i..mid]);
+        scratch.extend_from_slice(&items[j..]);
+        items.copy_from_slice(scratch);
+    }
+    let mut scratch = Vec::with_capacity(items.len());
+    merge_sort(items, &mut scratch, &mut compare);
+}
+
+#[cfg(test)]
+#[test]
+fn test_stable_sort() {
+    use super::{NSOrderedAscending, NSOrderedSame};
+    let compare = |a: (i32, char), b: (i32, char)| match a.0.cmp(&b.0) {
+        std::cmp::Ordering::Less => NSOrderedAscending,
+        std::cmp::Ordering::Equal => NSOrderedSame,
+        std::cmp::Ordering::Greater => NSOrderedDescending,
+    };
+
+    let mut items = [(3, 'a'), (1, 'b'), (2, 'c'), (1, 'd'), (3, 'e'), (0, 'f')];
+    stable_sort(&mut items, compare);
+    assert_eq!(
+        items,
+        [(0, 'f'), (1, 'b'), (1, 'd'), (2, 'c'), (3, 'a'), (3, 'e')]
+    );
+
+    // An inconsistent comparison function must not cause a panic.
+    let mut items = [5, 4, 3, 2, 1];
+    stable_sort(&mut items, |_, _| NSOrderedDescending);
+    let mut empty: [i32; 0] = [];
+    stable_sort(&mut empty, |_, _| unreachable!());
+}
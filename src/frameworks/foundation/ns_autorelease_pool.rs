@@ -5,21 +5,32 @@
  */
 //! `NSAutoreleasePool`.
 
-use crate::objc::{id, msg, objc_classes, release, ClassExports, HostObject, NSZonePtr};
-use crate::Environment;
+use crate::objc::{id, msg, objc_classes, release, ClassExports, HostObject, NSZonePtr, ObjC};
+use crate::{Environment, ThreadId};
+use std::collections::HashMap;
 
 #[derive(Default)]
 pub struct State {
-    pool_stack: Vec<id>,
+    /// Each thread has its own stack of pools. The last pool in a stack is the
+    /// one objects are added to.
+    pool_stacks: HashMap<ThreadId, Vec<id>>,
 }
 impl State {
     fn get(env: &mut Environment) -> &mut Self {
         &mut env.framework_state.foundation.ns_autorelease_pool
     }
+    fn current_stack(env: &mut Environment) -> &mut Vec<id> {
+        let current_thread = env.current_thread;
+        State::get(env)
+            .pool_stacks
+            .entry(current_thread)
+            .or_default()
+    }
 }
 
 struct NSAutoreleasePoolHostObject {
-    /// This is allowed to contain duplicates, which get released several times!
+    /// Objects in the order they were added. This is allowed to contain
+    /// duplicates, which get released several times!
     objects: Vec<id>,
 }
 impl HostObject for NSAutoreleasePoolHostObject {}
@@ -38,17 +49,22 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 + (())addObject:(id)obj {
-    if let Some(current_pool) = State::get(env).pool_stack.last().copied() {
+    if let Some(current_pool) = State::current_stack(env).last().copied() {
         msg![env; current_pool addObject:obj]
     } else {
-        log_dbg!("Warning: no active NSAutoreleasePool, leaking {:?}", obj);
+        let class = ObjC::read_isa(obj, &env.mem);
+        log!(
+            "Warning: Object {:?} of class {} autoreleased with no pool in place on thread {}, just leaking",
+            obj,
+            env.objc.get_class_name(class),
+            env.current_thread,
+        );
     }
 }
 
 - (id)init {
-    assert!(env.current_thread == 0); // TODO: per-thread stacks
-    State::get(env).pool_stack.push(this);
-    log_dbg!("New pool: {:?}", this);
+    State::current_stack(env).push(this);
+    log_dbg!("New pool: {:?} (thread {})", this, env.current_thread);
     this
 }
 
@@ -70,15 +86,38 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (())dealloc {
+    // Pools can only be drained on the thread that created them, and draining
+    // a pool also drains any pools created after it that are still in place.
+    let Some(index) = State::current_stack(env).iter().position(|&pool| pool == this) else {
+        panic!("Pool {:?} is not in place on thread {}!", this, env.current_thread);
+    };
+    while let Some(&inner_pool) = State::current_stack(env).get(index + 1) {
+        log!(
+            "Warning: Pool {:?} drained while pool {:?} was still in place, draining that first",
+            this,
+            inner_pool,
+        );
+        // This pops the inner pool.
+        release(env, inner_pool);
+    }
+
     log_dbg!("Draining pool: {:?}", this);
-    let pop_res = State::get(env).pool_stack.pop();
+    // The pool stays in place while its objects are released, so objects
+    // autoreleased by their dealloc methods get released too.
+    loop {
+        let host_obj: &mut NSAutoreleasePoolHostObject = env.objc.borrow_mut(this);
+        let objects = std::mem::take(&mut host_obj.objects);
+        if objects.is_empty() {
+            break;
+        }
+        for object in objects {
+            release(env, object);
+        }
+    }
+
+    let pop_res = State::current_stack(env).pop();
     assert!(pop_res == Some(this));
-    let host_obj: &mut NSAutoreleasePoolHostObject = env.objc.borrow_mut(this);
-    let objects = std::mem::take(&mut host_obj.objects);
     env.objc.dealloc_object(this, &mut env.mem);
-    for object in objects {
-        release(env, object);
-    }
 }
 
 @end
@@ -5,8 +5,9 @@
  */
 //! `NSBundle`.
 
-use super::ns_string;
+use super::{ns_string, NSUInteger};
 use crate::bundle::Bundle;
+use crate::fs::GuestPath;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
 };
@@ -105,16 +106,23 @@ pub const CLASSES: ClassExports = objc_classes! {
     assert!(name != nil); // TODO
 
     // FIXME: localized resource handling?
-    // FIXME: return nil if path does not exist
 
     let mut path: id = msg![env; this resourcePath];
     if directory != nil {
         path = msg![env; path stringByAppendingPathComponent:directory];
     }
     path = msg![env; path stringByAppendingPathComponent:name];
-    if extension != nil {
+    // An empty extension is treated the same as no extension.
+    let extension_length: NSUInteger = msg![env; extension length];
+    if extension_length != 0 {
         path = msg![env; path stringByAppendingPathExtension:extension];
     }
+
+    let path_string = ns_string::to_rust_string(env, path); // TODO: avoid copy
+    if !env.fs.exists(GuestPath::new(&path_string)) {
+        log_dbg!("pathForResource: {:?} does not exist", path_string);
+        return nil;
+    }
     path
 }
 - (id)pathForResource:(id)name // NSString*
@@ -127,6 +135,9 @@ pub const CLASSES: ClassExports = objc_classes! {
    let path_string: id = msg![env; this pathForResource:name
                                                  ofType:extension
                                             inDirectory:subpath];
+   if path_string == nil {
+       return nil;
+   }
    let path_url: id = msg_class![env; NSURL alloc];
    let path_url: id = msg![env; path_url initFileURLWithPath:path_string];
    autorelease(env, path_url)
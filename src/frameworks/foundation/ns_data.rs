@@ -10,7 +10,8 @@ use super::{NSRange, NSUInteger};
 use crate::fs::GuestPath;
 use crate::mem::{ConstVoidPtr, MutPtr, MutVoidPtr, Ptr};
 use crate::objc::{
-    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
 };
 use crate::Environment;
 
@@ -49,6 +50,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)dataWithContentsOfFile:(id)path { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithContentsOfFile:path];
+    autorelease(env, new)
+}
+
 // Calling the standard `init` is also allowed, in which case we just get data
 // of size 0.
 
@@ -126,6 +133,13 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<NSDataHostObject>(this).length
 }
 
+- (())getBytes:(MutPtr<u8>)buffer length:(NSUInteger)length {
+    let range = NSRange {
+        location: 0,
+        length: length.min(env.objc.borrow::<NSDataHostObject>(this).length),
+    };
+    msg![env; this getBytes:buffer range:range]
+}
 - (())getBytes:(MutPtr<u8>)buffer range:(NSRange)range {
     if range.length == 0 {
         return;
@@ -140,6 +154,18 @@ pub const CLASSES: ClassExports = objc_classes! {
     );
 }
 
+- (id)subdataWithRange:(NSRange)range {
+    let &NSDataHostObject { bytes, length, .. } = env.objc.borrow(this);
+    // TODO: throw NSRangeException if out-of-range instead of panic?
+    assert!(range.location <= length && range.length <= length - range.location);
+    if range.length == 0 {
+        let new: id = msg_class![env; NSData new];
+        return autorelease(env, new);
+    }
+    let bytes = bytes + range.location;
+    msg_class![env; NSData dataWithBytes:bytes length:(range.length)]
+}
+
 @end
 
 };
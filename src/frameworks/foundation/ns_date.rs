@@ -0,0 +1,243 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSDate`.
+
+use super::ns_date_formatter::format_date;
+use super::{
+    ns_string, NSComparisonResult, NSOrderedAscending, NSOrderedDescending, NSOrderedSame,
+    NSTimeInterval, NSUInteger,
+};
+use crate::objc::{
+    autorelease, id, msg, msg_class, objc_classes, retain, Class, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Number of seconds between the Unix epoch (1970-01-01 00:00:00 UTC) and
+/// Apple's reference date (2001-01-01 00:00:00 UTC).
+pub const NSTimeIntervalSince1970: NSTimeInterval = 978307200.0;
+
+/// Value used by `distantPast` and `distantFuture`: the number of seconds in
+/// about 2000 years, so these are the years 1 and 4001.
+const DISTANT_INTERVAL: NSTimeInterval = 63113904000.0;
+
+struct NSDateHostObject {
+    time_interval_since_reference_date: NSTimeInterval,
+}
+impl HostObject for NSDateHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSDate: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSDateHostObject {
+        time_interval_since_reference_date: 0.0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)date {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new init];
+    autorelease(env, new)
+}
+
++ (id)dateWithTimeIntervalSinceNow:(NSTimeInterval)interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeIntervalSinceNow:interval];
+    autorelease(env, new)
+}
+
++ (id)dateWithTimeIntervalSinceReferenceDate:(NSTimeInterval)interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeIntervalSinceReferenceDate:interval];
+    autorelease(env, new)
+}
+
++ (id)dateWithTimeIntervalSince1970:(NSTimeInterval)interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeIntervalSince1970:interval];
+    autorelease(env, new)
+}
+
++ (id)dateWithTimeInterval:(NSTimeInterval)interval
+                 sinceDate:(id)date { // NSDate*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeInterval:interval sinceDate:date];
+    autorelease(env, new)
+}
+
++ (id)distantPast {
+    msg![env; this dateWithTimeIntervalSinceReferenceDate:(-DISTANT_INTERVAL)]
+}
++ (id)distantFuture {
+    msg![env; this dateWithTimeIntervalSinceReferenceDate:DISTANT_INTERVAL]
+}
+
++ (NSTimeInterval)timeIntervalSinceReferenceDate {
+    now_since_reference_date(env)
+}
+
+- (id)init {
+    msg![env; this initWithTimeIntervalSinceNow:0.0]
+}
+
+- (id)initWithTimeIntervalSinceNow:(NSTimeInterval)interval {
+    let interval = now_since_reference_date(env) + interval;
+    msg![env; this initWithTimeIntervalSinceReferenceDate:interval]
+}
+
+- (id)initWithTimeIntervalSinceReferenceDate:(NSTimeInterval)interval {
+    env.objc
+        .borrow_mut::<NSDateHostObject>(this)
+        .time_interval_since_reference_date = interval;
+    this
+}
+
+- (id)initWithTimeIntervalSince1970:(NSTimeInterval)interval {
+    let interval = interval - NSTimeIntervalSince1970;
+    msg![env; this initWithTimeIntervalSinceReferenceDate:interval]
+}
+
+- (id)initWithTimeInterval:(NSTimeInterval)interval
+                 sinceDate:(id)date { // NSDate*
+    let since: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    msg![env; this initWithTimeIntervalSinceReferenceDate:(since + interval)]
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+- (NSTimeInterval)timeIntervalSinceReferenceDate {
+    env.objc.borrow::<NSDateHostObject>(this).time_interval_since_reference_date
+}
+
+- (NSTimeInterval)timeIntervalSinceNow {
+    let interval = env.objc.borrow::<NSDateHostObject>(this).time_interval_since_reference_date;
+    interval - now_since_reference_date(env)
+}
+
+- (NSTimeInterval)timeIntervalSince1970 {
+    let interval = env.objc.borrow::<NSDateHostObject>(this).time_interval_since_reference_date;
+    interval + NSTimeIntervalSince1970
+}
+
+- (NSTimeInterval)timeIntervalSinceDate:(id)other { // NSDate*
+    let interval = env.objc.borrow::<NSDateHostObject>(this).time_interval_since_reference_date;
+    let other: NSTimeInterval = msg![env; other timeIntervalSinceReferenceDate];
+    interval - other
+}
+
+- (id)dateByAddingTimeInterval:(NSTimeInterval)interval {
+    let class: Class = msg![env; this class];
+    msg![env; class dateWithTimeInterval:interval sinceDate:this]
+}
+// Deprecated equivalent of the above, from older versions of iPhone OS.
+- (id)addTimeInterval:(NSTimeInterval)interval {
+    msg![env; this dateByAddingTimeInterval:interval]
+}
+
+- (NSComparisonResult)compare:(id)other { // NSDate*
+    let interval = env.objc.borrow::<NSDateHostObject>(this).time_interval_since_reference_date;
+    let other: NSTimeInterval = msg![env; other timeIntervalSinceReferenceDate];
+    if interval < other {
+        NSOrderedAscending
+    } else if interval > other {
+        NSOrderedDescending
+    } else {
+        NSOrderedSame
+    }
+}
+
+- (id)earlierDate:(id)other { // NSDate*
+    let order: NSComparisonResult = msg![env; this compare:other];
+    if order == NSOrderedDescending {
+        other
+    } else {
+        this
+    }
+}
+- (id)laterDate:(id)other { // NSDate*
+    let order: NSComparisonResult = msg![env; this compare:other];
+    if order == NSOrderedAscending {
+        other
+    } else {
+        this
+    }
+}
+
+- (bool)isEqualToDate:(id)other { // NSDate*
+    if other == this {
+        return true;
+    }
+    let order: NSComparisonResult = msg![env; this compare:other];
+    order == NSOrderedSame
+}
+- (bool)isEqual:(id)other {
+    if other == this {
+        return true;
+    }
+    let class: Class = msg_class![env; NSDate class];
+    if !msg![env; other isKindOfClass:class] {
+        return false;
+    }
+    msg![env; this isEqualToDate:other]
+}
+- (NSUInteger)hash {
+    let interval = env.objc.borrow::<NSDateHostObject>(this).time_interval_since_reference_date;
+    super::hash_helper(&interval.to_bits())
+}
+
+- (id)description {
+    // Always in UTC, like on a real device.
+    let interval = env.objc.borrow::<NSDateHostObject>(this).time_interval_since_reference_date;
+    let description = format_date(interval, "yyyy-MM-dd HH:mm:ss Z", 0);
+    let description = ns_string::from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+@end
+
+};
+
+/// Get the current time as an interval since the reference date.
+pub fn now_since_reference_date(env: &Environment) -> NSTimeInterval {
+    system_time_since_reference_date(env.clock.system_now())
+}
+
+/// Convert a host time to an interval since the reference date.
+pub fn system_time_since_reference_date(time: SystemTime) -> NSTimeInterval {
+    let since_1970 = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    };
+    since_1970 - NSTimeIntervalSince1970
+}
+
+/// Convert an interval since the reference date to a host time.
+pub fn time_interval_since_reference_date_to_system_time(interval: NSTimeInterval) -> SystemTime {
+    let since_1970 = interval + NSTimeIntervalSince1970;
+    if since_1970 >= 0.0 {
+        UNIX_EPOCH + Duration::from_secs_f64(since_1970)
+    } else {
+        UNIX_EPOCH - Duration::from_secs_f64(-since_1970)
+    }
+}
+
+/// Shortcut for host code: create a new date, which is not autoreleased.
+pub fn from_time_interval_since_reference_date(
+    env: &mut Environment,
+    interval: NSTimeInterval,
+) -> id {
+    let date: id = msg_class![env; NSDate alloc];
+    msg![env; date initWithTimeIntervalSinceReferenceDate:interval]
+}
@@ -0,0 +1,673 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSFormatter` and `NSDateFormatter`.
+//!
+//! Date formats use the [Unicode Technical Standard #35](https://unicode.org/reports/tr35/tr35-dates.html#Date_Field_Symbol_Table)
+//! patterns, like `yyyy-MM-dd HH:mm:ss`. Only the common fields and the
+//! Gregorian calendar are supported, and names are always in English.
+
+use super::ns_date::NSTimeIntervalSince1970;
+use super::{ns_string, ns_time_zone, NSTimeInterval, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+pub type NSDateFormatterStyle = NSUInteger;
+pub const NSDateFormatterNoStyle: NSDateFormatterStyle = 0;
+pub const NSDateFormatterShortStyle: NSDateFormatterStyle = 1;
+pub const NSDateFormatterMediumStyle: NSDateFormatterStyle = 2;
+pub const NSDateFormatterLongStyle: NSDateFormatterStyle = 3;
+pub const NSDateFormatterFullStyle: NSDateFormatterStyle = 4;
+
+struct NSDateFormatterHostObject {
+    /// `NSString*`, may be [nil], in which case the styles are used.
+    date_format: id,
+    date_style: NSDateFormatterStyle,
+    time_style: NSDateFormatterStyle,
+    /// `NSTimeZone*`, may be [nil], in which case the system time zone is used.
+    time_zone: id,
+    /// `NSLocale*`, may be [nil]. This doesn't currently affect anything.
+    locale: id,
+}
+impl HostObject for NSDateFormatterHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSFormatter: NSObject
+@end
+
+@implementation NSDateFormatter: NSFormatter
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSDateFormatterHostObject {
+        date_format: nil,
+        date_style: NSDateFormatterNoStyle,
+        time_style: NSDateFormatterNoStyle,
+        time_zone: nil,
+        locale: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)localizedStringFromDate:(id)date // NSDate*
+                    dateStyle:(NSDateFormatterStyle)date_style
+                    timeStyle:(NSDateFormatterStyle)time_style {
+    let formatter: id = msg![env; this new];
+    () = msg![env; formatter setDateStyle:date_style];
+    () = msg![env; formatter setTimeStyle:time_style];
+    let string: id = msg![env; formatter stringFromDate:date];
+    release(env, formatter);
+    string
+}
+
+- (())dealloc {
+    let &NSDateFormatterHostObject {
+        date_format,
+        time_zone,
+        locale,
+        ..
+    } = env.objc.borrow(this);
+    release(env, date_format);
+    release(env, time_zone);
+    release(env, locale);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)dateFormat {
+    env.objc.borrow::<NSDateFormatterHostObject>(this).date_format
+}
+- (())setDateFormat:(id)format { // NSString*
+    let format: id = msg![env; format copy];
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.date_format, format);
+    release(env, old);
+}
+
+- (NSDateFormatterStyle)dateStyle {
+    env.objc.borrow::<NSDateFormatterHostObject>(this).date_style
+}
+- (())setDateStyle:(NSDateFormatterStyle)style {
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    host_object.date_style = style;
+    // Setting a style replaces any explicit format.
+    let old = std::mem::replace(&mut host_object.date_format, nil);
+    release(env, old);
+}
+- (NSDateFormatterStyle)timeStyle {
+    env.objc.borrow::<NSDateFormatterHostObject>(this).time_style
+}
+- (())setTimeStyle:(NSDateFormatterStyle)style {
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    host_object.time_style = style;
+    let old = std::mem::replace(&mut host_object.date_format, nil);
+    release(env, old);
+}
+
+- (id)timeZone {
+    let time_zone = env.objc.borrow::<NSDateFormatterHostObject>(this).time_zone;
+    if time_zone == nil {
+        msg_class![env; NSTimeZone systemTimeZone]
+    } else {
+        time_zone
+    }
+}
+- (())setTimeZone:(id)time_zone { // NSTimeZone*
+    retain(env, time_zone);
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.time_zone, time_zone);
+    release(env, old);
+}
+
+- (id)locale {
+    env.objc.borrow::<NSDateFormatterHostObject>(this).locale
+}
+- (())setLocale:(id)locale { // NSLocale*
+    retain(env, locale);
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.locale, locale);
+    release(env, old);
+}
+
+- (id)stringFromDate:(id)date { // NSDate*
+    if date == nil {
+        return nil;
+    }
+    let (format, seconds_from_gmt) = format_and_offset(env, this);
+    let interval: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    let string = format_date(interval, &format, seconds_from_gmt);
+    let string = ns_string::from_rust_string(env, string);
+    autorelease(env, string)
+}
+
+- (id)dateFromString:(id)string { // NSString*
+    if string == nil {
+        return nil;
+    }
+    let (format, seconds_from_gmt) = format_and_offset(env, this);
+    let string = ns_string::to_rust_string(env, string);
+    let Some(interval) = parse_date(&string, &format, seconds_from_gmt) else {
+        log_dbg!("Couldn't parse date {:?} with format {:?}", string, format);
+        return nil;
+    };
+    msg_class![env; NSDate dateWithTimeIntervalSinceReferenceDate:interval]
+}
+
+@end
+
+};
+
+/// Get the pattern and time zone offset a formatter uses.
+fn format_and_offset(env: &mut Environment, formatter: id) -> (String, i32) {
+    let &NSDateFormatterHostObject {
+        date_format,
+        date_style,
+        time_style,
+        time_zone,
+        ..
+    } = env.objc.borrow(formatter);
+    let format = if date_format != nil {
+        ns_string::to_rust_string(env, date_format).into_owned()
+    } else {
+        format_for_styles(date_style, time_style)
+    };
+    (format, ns_time_zone::seconds_from_gmt(env, time_zone))
+}
+
+/// Get the pattern the `en_US` locale uses for some combination of styles.
+fn format_for_styles(date_style: NSDateFormatterStyle, time_style: NSDateFormatterStyle) -> String {
+    let date = match date_style {
+        NSDateFormatterShortStyle => "M/d/yy",
+        NSDateFormatterMediumStyle => "MMM d, yyyy",
+        NSDateFormatterLongStyle => "MMMM d, yyyy",
+        NSDateFormatterFullStyle => "EEEE, MMMM d, yyyy",
+        _ => "",
+    };
+    let time = match time_style {
+        NSDateFormatterShortStyle => "h:mm a",
+        NSDateFormatterMediumStyle => "h:mm:ss a",
+        NSDateFormatterLongStyle | NSDateFormatterFullStyle => "h:mm:ss a z",
+        _ => "",
+    };
+    match (date.is_empty(), time.is_empty()) {
+        (false, false) => format!("{} {}", date, time),
+        _ => format!("{}{}", date, time),
+    }
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+/// Get the number of days since 1970-01-01 for a date in the proleptic
+/// Gregorian calendar. `month` and `day` count from 1.
+///
+/// This is Howard Hinnant's `days_from_civil` algorithm, see
+/// <https://howardhinnant.github.io/date_algorithms.html>.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The inverse of [days_from_civil]: returns the year, month and day.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// A pattern letter and how many times it is repeated.
+    Field(char, usize),
+    Literal(String),
+}
+
+fn tokenize(format: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = format.chars().peekable();
+    let mut literal = String::new();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            // '' is a literal quote, otherwise everything up to the next quote
+            // is literal text.
+            if chars.peek() == Some(&'\'') {
+                chars.next();
+                literal.push('\'');
+                continue;
+            }
+            while let Some(c) = chars.next() {
+                if c == '\'' {
+                    if chars.peek() == Some(&'\'') {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                literal.push(c);
+            }
+        } else if c.is_ascii_alphabetic() {
+            if !literal.is_empty() {
+                tokens.push(Token::Literal(std::mem::take(&mut literal)));
+            }
+            let mut count = 1;
+            while chars.peek() == Some(&c) {
+                chars.next();
+                count += 1;
+            }
+            tokens.push(Token::Field(c, count));
+        } else {
+            literal.push(c);
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    tokens
+}
+
+/// Format an offset from GMT like `+0200`, or `+02:00` if `colon` is set.
+fn format_offset(seconds_from_gmt: i32, colon: bool) -> String {
+    let sign = if seconds_from_gmt < 0 { '-' } else { '+' };
+    let minutes = seconds_from_gmt.unsigned_abs() / 60;
+    let separator = if colon { ":" } else { "" };
+    format!(
+        "{}{:02}{}{:02}",
+        sign,
+        minutes / 60,
+        separator,
+        minutes % 60
+    )
+}
+
+/// Format a date (an interval since the reference date) with a UTS #35
+/// pattern, in the time zone with the given offset.
+pub fn format_date(interval: NSTimeInterval, format: &str, seconds_from_gmt: i32) -> String {
+    let local = interval + NSTimeIntervalSince1970 + seconds_from_gmt as f64;
+    let whole_seconds = local.floor();
+    let fraction = local - whole_seconds;
+    let whole_seconds = whole_seconds as i64;
+    let days = whole_seconds.div_euclid(DAY_SECONDS);
+    let second_in_day = whole_seconds.rem_euclid(DAY_SECONDS);
+    let (year, month, day) = civil_from_days(days);
+    let hour = second_in_day / 3600;
+    let minute = (second_in_day / 60) % 60;
+    let second = second_in_day % 60;
+    // 1970-01-01 was a Thursday.
+    let weekday = (days + 4).rem_euclid(7) as usize;
+    let day_of_year = days - days_from_civil(year, 1, 1) + 1;
+
+    let mut result = String::new();
+    for token in tokenize(format) {
+        let (letter, count) = match token {
+            Token::Literal(literal) => {
+                result.push_str(&literal);
+                continue;
+            }
+            Token::Field(letter, count) => (letter, count),
+        };
+        let number = |value: i64| format!("{:01$}", value, count);
+        let text = match letter {
+            'G' => "AD".to_string(),
+            'y' | 'Y' | 'u' if count == 2 => format!("{:02}", year.rem_euclid(100)),
+            'y' | 'Y' | 'u' => number(year),
+            'M' | 'L' if count >= 4 => MONTH_NAMES[month as usize - 1].to_string(),
+            'M' | 'L' if count == 3 => MONTH_NAMES[month as usize - 1][..3].to_string(),
+            'M' | 'L' => number(month.into()),
+            'd' => number(day.into()),
+            'D' => number(day_of_year),
+            'E' if count >= 4 => WEEKDAY_NAMES[weekday].to_string(),
+            'E' => WEEKDAY_NAMES[weekday][..3].to_string(),
+            'a' => if hour < 12 { "AM" } else { "PM" }.to_string(),
+            'H' => number(hour),
+            'k' => number(if hour == 0 { 24 } else { hour }),
+            'K' => number(hour % 12),
+            'h' => number(if hour % 12 == 0 { 12 } else { hour % 12 }),
+            'm' => number(minute),
+            's' => number(second),
+            'S' => {
+                // Fractional seconds are truncated, not rounded.
+                let digits = count.min(9) as i32;
+                let value = (fraction * 10f64.powi(digits)) as i64;
+                format!("{:01$}", value, count.min(9))
+            }
+            'Z' if count == 4 => format!("GMT{}", format_offset(seconds_from_gmt, true)),
+            'Z' if count >= 5 && seconds_from_gmt == 0 => "Z".to_string(),
+            'Z' if count >= 5 => format_offset(seconds_from_gmt, true),
+            'Z' => format_offset(seconds_from_gmt, false),
+            'z' | 'v' | 'V' | 'O' if seconds_from_gmt == 0 => "GMT".to_string(),
+            'z' | 'v' | 'V' | 'O' => format!("GMT{}", format_offset(seconds_from_gmt, true)),
+            _ => {
+                log!(
+                    "TODO: Date format field {:?} in {:?} is not supported",
+                    letter.to_string().repeat(count),
+                    format
+                );
+                String::new()
+            }
+        };
+        result.push_str(&text);
+    }
+    result
+}
+
+/// Parse a date formatted with a UTS #35 pattern, assuming it's in the time
+/// zone with the given offset unless it says otherwise. Returns the interval
+/// since the reference date, or [None] if the string doesn't match.
+pub fn parse_date(string: &str, format: &str, seconds_from_gmt: i32) -> Option<NSTimeInterval> {
+    let tokens = tokenize(format);
+
+    // Fields that aren't in the string default to 1970-01-01 00:00:00.
+    let mut year: i64 = 1970;
+    let mut month: u32 = 1;
+    let mut day: u32 = 1;
+    let mut hour: u32 = 0;
+    let mut hour_is_12: bool = false;
+    let mut pm: Option<bool> = None;
+    let mut minute: u32 = 0;
+    let mut second: u32 = 0;
+    let mut fraction: f64 = 0.0;
+    let mut offset = seconds_from_gmt;
+
+    let mut rest = string;
+    for (i, token) in tokens.iter().enumerate() {
+        let (letter, count) = match token {
+            Token::Literal(literal) => {
+                rest = rest.strip_prefix(literal.as_str())?;
+                continue;
+            }
+            &Token::Field(letter, count) => (letter, count),
+        };
+
+        // Without a separator before the next number, e.g. in "yyyyMMdd", the
+        // width of each number has to be taken from the pattern.
+        let next_is_number = matches!(
+            tokens.get(i + 1),
+            Some(Token::Field(next, next_count))
+                if "yYuMLdDHkKhmsS".contains(*next) && (*next_count < 3 || !"ML".contains(*next))
+        );
+        let width = |max_digits: usize| if next_is_number { count } else { max_digits };
+
+        match letter {
+            'y' | 'Y' | 'u' => {
+                let (value, digits) =
+                    take_number(&mut rest, width(if count == 2 { 2 } else { 4.max(count) }))?;
+                year = if count == 2 && digits == 2 {
+                    // Two-digit years are taken to be between 1950 and 2049.
+                    // Real devices base this on the current date, but a fixed
+                    // range is more predictable.
+                    if value < 50 {
+                        2000 + value
+                    } else {
+                        1900 + value
+                    }
+                } else {
+                    value
+                };
+            }
+            'M' | 'L' if count >= 3 => {
+                let index = MONTH_NAMES.iter().position(|name| {
+                    let name = if count == 3 { &name[..3] } else { name };
+                    starts_with_ignore_case(rest, name)
+                })?;
+                let name_len = if count == 3 {
+                    3
+                } else {
+                    MONTH_NAMES[index].len()
+                };
+                rest = &rest[name_len..];
+                month = index as u32 + 1;
+            }
+            'M' | 'L' => month = take_number(&mut rest, width(2))?.0.try_into().ok()?,
+            'd' => day = take_number(&mut rest, width(2))?.0.try_into().ok()?,
+            'E' => {
+                let name_len = WEEKDAY_NAMES.iter().find_map(|name| {
+                    let name = if count >= 4 { name } else { &name[..3] };
+                    starts_with_ignore_case(rest, name).then_some(name.len())
+                })?;
+                // The weekday doesn't affect the result.
+                rest = &rest[name_len..];
+            }
+            'a' => {
+                if starts_with_ignore_case(rest, "AM") {
+                    pm = Some(false);
+                } else if starts_with_ignore_case(rest, "PM") {
+                    pm = Some(true);
+                } else {
+                    return None;
+                }
+                rest = &rest[2..];
+            }
+            'H' | 'k' | 'K' | 'h' => {
+                hour = take_number(&mut rest, width(2))?.0.try_into().ok()?;
+                if letter == 'k' && hour == 24 {
+                    hour = 0;
+                }
+                hour_is_12 = matches!(letter, 'K' | 'h');
+                if hour_is_12 && hour > 12 {
+                    return None;
+                }
+            }
+            'm' => minute = take_number(&mut rest, width(2))?.0.try_into().ok()?,
+            's' => second = take_number(&mut rest, width(2))?.0.try_into().ok()?,
+            'S' => {
+                let (value, digits) = take_number(&mut rest, width(9))?;
+                fraction = value as f64 / 10f64.powi(digits as i32);
+            }
+            'Z' | 'z' | 'v' | 'V' | 'O' | 'X' | 'x' => {
+                let (parsed_offset, len) = parse_offset(rest)?;
+                offset = parsed_offset;
+                rest = &rest[len..];
+            }
+            _ => {
+                log!(
+                    "TODO: Date format field {:?} in {:?} is not supported",
+                    letter.to_string().repeat(count),
+                    format
+                );
+                return None;
+            }
+        }
+    }
+    if !rest.is_empty() {
+        return None;
+    }
+
+    if hour_is_12 {
+        hour %= 12;
+    }
+    if pm == Some(true) {
+        if hour >= 12 {
+            return None;
+        }
+        hour += 12;
+    }
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * DAY_SECONDS + (hour * 3600 + minute * 60 + second) as i64;
+    let seconds = seconds - offset as i64;
+    Some(seconds as f64 + fraction - NSTimeIntervalSince1970)
+}
+
+/// Take a number with up to `max_digits` digits from the start of a string.
+/// Returns the number and how many digits it had.
+fn take_number(string: &mut &str, max_digits: usize) -> Option<(i64, usize)> {
+    let digits = string
+        .bytes()
+        .take(max_digits)
+        .take_while(u8::is_ascii_digit)
+        .count();
+    if digits == 0 {
+        return None;
+    }
+    let value = string[..digits].parse().ok()?;
+    *string = &string[digits..];
+    Some((value, digits))
+}
+
+fn starts_with_ignore_case(string: &str, prefix: &str) -> bool {
+    matches!(string.get(..prefix.len()), Some(start) if start.eq_ignore_ascii_case(prefix))
+}
+
+/// Parse a time zone offset like `Z`, `GMT`, `+0200`, `+02:00` or
+/// `GMT+02:00`. Returns the offset in seconds and the length of the text.
+fn parse_offset(string: &str) -> Option<(i32, usize)> {
+    if string.starts_with('Z') {
+        return Some((0, 1));
+    }
+    let (prefix_len, rest) = if let Some(rest) = string
+        .strip_prefix("GMT")
+        .or_else(|| string.strip_prefix("UTC"))
+    {
+        if !rest.starts_with(['+', '-']) {
+            return Some((0, 3));
+        }
+        (3, rest)
+    } else {
+        (0, string)
+    };
+    let sign = match rest.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let bytes = rest.as_bytes();
+    let digit = |i: usize| -> Option<i32> {
+        bytes
+            .get(i)
+            .filter(|b| b.is_ascii_digit())
+            .map(|b| (b - b'0') as i32)
+    };
+    let hours = digit(1)? * 10 + digit(2)?;
+    let (minutes, len) = if bytes.get(3) == Some(&b':') {
+        (digit(4)? * 10 + digit(5)?, 6)
+    } else if let (Some(a), Some(b)) = (digit(3), digit(4)) {
+        (a * 10 + b, 5)
+    } else {
+        (0, 3)
+    };
+    Some((sign * (hours * 3600 + minutes * 60), prefix_len + len))
+}
+
+#[cfg(test)]
+#[test]
+fn test_format_and_parse_date() {
+    // 2010-01-02 03:04:05.25 UTC
+    let date = 1262401445.25 - NSTimeIntervalSince1970;
+
+    assert_eq!(
+        format_date(date, "yyyy-MM-dd HH:mm:ss", 0),
+        "2010-01-02 03:04:05"
+    );
+    assert_eq!(
+        format_date(date, "EEE, d MMM yy h:mm:ss.SS a Z", 0),
+        "Sat, 2 Jan 10 3:04:05.25 AM +0000"
+    );
+    assert_eq!(
+        format_date(date, "yyyy-MM-dd'T'HH:mmZZZZZ", -5 * 3600),
+        "2010-01-01T22:04-05:00"
+    );
+    assert_eq!(
+        format_date(date, format_for_styles(2, 1).as_str(), 0),
+        "Jan 2, 2010 3:04 AM"
+    );
+    assert_eq!(format_date(date, "'o''clock' h", 0), "o'clock 3");
+
+    assert_eq!(
+        parse_date("2010-01-02 03:04:05", "yyyy-MM-dd HH:mm:ss", 0),
+        Some(date - 0.25)
+    );
+    assert_eq!(
+        parse_date("20100102 0304", "yyyyMMdd HHmm", 3600),
+        Some(date - 3600.0 - 5.25)
+    );
+    assert_eq!(
+        parse_date(
+            "January 2, 2010 3:04:05 AM GMT",
+            "MMMM d, yyyy h:mm:ss a z",
+            3600
+        ),
+        Some(date - 0.25)
+    );
+    assert_eq!(
+        parse_date("2010-01-01T22:04:05-05:00", "yyyy-MM-dd'T'HH:mm:ssZZZZZ", 0),
+        Some(date - 0.25)
+    );
+    // Invalid dates and trailing text are rejected.
+    assert_eq!(parse_date("2010-02-30", "yyyy-MM-dd", 0), None);
+    assert_eq!(parse_date("2010-01-02 extra", "yyyy-MM-dd", 0), None);
+
+    // Dates before 1970 and far in the future work too.
+    for days in [-719162, -1, 0, 11323, 2932896] {
+        let (year, month, day) = civil_from_days(days);
+        assert_eq!(days_from_civil(year, month, day), days);
+    }
+    assert_eq!(civil_from_days(-719162), (1, 1, 1));
+}
@@ -6,7 +6,7 @@
 //! The `NSDictionary` class cluster, including `NSMutableDictionary`.
 
 use super::ns_property_list_serialization::deserialize_plist_from_file;
-use super::{ns_string, ns_url, NSUInteger};
+use super::{ns_keyed_unarchiver, ns_string, ns_url, NSUInteger};
 use crate::fs::GuestPath;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
@@ -29,30 +29,51 @@ pub(super) struct DictionaryHostObject {
     /// where the keys have the same hash value.
     map: HashMap<Hash, Vec<(id, id)>>,
     pub(super) count: NSUInteger,
+    /// Set for `CFDictionary`s with NULL callbacks: the keys and values aren't
+    /// necessarily Objective-C objects, so they are compared and hashed by
+    /// identity and never copied, retained or released.
+    pub(super) non_retaining: bool,
 }
 impl HostObject for DictionaryHostObject {}
 impl DictionaryHostObject {
+    fn hash_key(&self, env: &mut Environment, key: id) -> Hash {
+        if self.non_retaining {
+            key.to_bits()
+        } else {
+            msg![env; key hash]
+        }
+    }
+    fn keys_equal(env: &mut Environment, non_retaining: bool, candidate_key: id, key: id) -> bool {
+        candidate_key == key || (!non_retaining && msg![env; candidate_key isEqualTo:key])
+    }
     pub(super) fn lookup(&self, env: &mut Environment, key: id) -> id {
-        let hash: Hash = msg![env; key hash];
+        let hash = self.hash_key(env, key);
         let Some(collisions) = self.map.get(&hash) else {
             return nil;
         };
         for &(candidate_key, value) in collisions {
-            if candidate_key == key || msg![env; candidate_key isEqualTo:key] {
+            if Self::keys_equal(env, self.non_retaining, candidate_key, key) {
                 return value;
             }
         }
         nil
     }
     pub(super) fn insert(&mut self, env: &mut Environment, key: id, value: id, copy_key: bool) {
-        let key: id = if copy_key {
+        let non_retaining = self.non_retaining;
+        let key: id = if non_retaining {
+            key
+        } else if copy_key {
             msg![env; key copy]
         } else {
             retain(env, key)
         };
-        let hash: Hash = msg![env; key hash];
+        let hash = self.hash_key(env, key);
 
-        let value = retain(env, value);
+        let value = if non_retaining {
+            value
+        } else {
+            retain(env, value)
+        };
 
         let Some(collisions) = self.map.get_mut(&hash) else {
             self.map.insert(hash, vec![(key, value)]);
@@ -60,8 +81,10 @@ impl DictionaryHostObject {
             return;
         };
         for &mut (candidate_key, ref mut existing_value) in collisions.iter_mut() {
-            if candidate_key == key || msg![env; candidate_key isEqualTo:key] {
-                release(env, *existing_value);
+            if Self::keys_equal(env, non_retaining, candidate_key, key) {
+                if !non_retaining {
+                    release(env, *existing_value);
+                }
                 *existing_value = value;
                 return;
             }
@@ -69,7 +92,32 @@ impl DictionaryHostObject {
         collisions.push((key, value));
         self.count += 1;
     }
+    pub(super) fn remove(&mut self, env: &mut Environment, key: id) {
+        let non_retaining = self.non_retaining;
+        let hash = self.hash_key(env, key);
+        let Some(collisions) = self.map.get_mut(&hash) else {
+            return;
+        };
+        for i in 0..collisions.len() {
+            let candidate_key = collisions[i].0;
+            if Self::keys_equal(env, non_retaining, candidate_key, key) {
+                let (existing_key, existing_value) = collisions.remove(i);
+                if collisions.is_empty() {
+                    self.map.remove(&hash);
+                }
+                self.count -= 1;
+                if !non_retaining {
+                    release(env, existing_key);
+                    release(env, existing_value);
+                }
+                return;
+            }
+        }
+    }
     pub(super) fn release(&mut self, env: &mut Environment) {
+        if self.non_retaining {
+            return;
+        }
         for collisions in self.map.values() {
             for &(key, value) in collisions {
                 release(env, key);
@@ -158,10 +206,51 @@ pub const CLASSES: ClassExports = objc_classes! {
     retain(env, this)
 }
 
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(NSZonePtr)zone {
+    let keys_and_objects = keys_and_objects(env, this);
+    let new: id = msg_class![env; _touchHLE_NSMutableDictionary allocWithZone:zone];
+    let new: id = msg![env; new init];
+    for (key, object) in keys_and_objects {
+        set_object_for_key(env, new, object, key, /* copy_key: */ true);
+    }
+    new
+}
+
 // TODO
 
 @end
 
+// NSMutableDictionary is an abstract class. A subclass must provide everything
+// NSDictionary provides, plus:
+// - (void)setObject:(id)object forKey:(id)key;
+// - (void)removeObjectForKey:(id)key;
+// Note that it inherits from NSDictionary, so we must ensure we override any
+// default methods that would be inappropriate for mutability.
+@implementation NSMutableDictionary: NSDictionary
+
++ (id)allocWithZone:(NSZonePtr)zone {
+    // NSMutableDictionary might be subclassed by something which needs
+    // allocWithZone: to have the normal behaviour. Unimplemented: call
+    // superclass alloc then.
+    assert!(this == env.objc.get_known_class("NSMutableDictionary", &mut env.mem));
+    msg_class![env; _touchHLE_NSMutableDictionary allocWithZone:zone]
+}
+
++ (id)dictionaryWithCapacity:(NSUInteger)capacity {
+    let new_dict: id = msg![env; this alloc];
+    let new_dict: id = msg![env; new_dict initWithCapacity:capacity];
+    autorelease(env, new_dict)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let keys_and_objects = keys_and_objects(env, this);
+    dict_from_keys_and_objects(env, &keys_and_objects)
+}
+
+@end
+
 // Our private subclass that is the single implementation of NSDictionary for
 // the time being.
 @implementation _touchHLE_NSDictionary: NSDictionary
@@ -177,6 +266,11 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    init_with_coder(env, this, coder)
+}
+
 - (id)initWithObjectsAndKeys:(id)first_object, ...dots {
     let mut va_args = dots.start();
     let first_key: id = va_args.next(env);
@@ -219,8 +313,103 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @end
 
+// Our private subclass that is the single implementation of
+// NSMutableDictionary for the time being.
+@implementation _touchHLE_NSMutableDictionary: NSMutableDictionary
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<DictionaryHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    std::mem::take(env.objc.borrow_mut::<DictionaryHostObject>(this)).release(env);
+
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)init {
+    *env.objc.borrow_mut(this) = <DictionaryHostObject as Default>::default();
+    this
+}
+- (id)initWithCapacity:(NSUInteger)_capacity {
+    msg![env; this init]
+}
+
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    init_with_coder(env, this, coder)
+}
+
+// TODO: enumeration, more init methods, etc
+
+- (NSUInteger)count {
+    env.objc.borrow::<DictionaryHostObject>(this).count
+}
+- (id)objectForKey:(id)key {
+    let host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let res = host_obj.lookup(env, key);
+    *env.objc.borrow_mut(this) = host_obj;
+    res
+}
+
+- (())setObject:(id)object forKey:(id)key {
+    set_object_for_key(env, this, object, key, /* copy_key: */ true);
+}
+- (())removeObjectForKey:(id)key {
+    let mut host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    host_obj.remove(env, key);
+    *env.objc.borrow_mut(this) = host_obj;
+}
+
+@end
+
+// Special variant for use by CFDictionary with NULL callbacks: keys and values
+// aren't necessarily Objective-C objects and won't be retained/released.
+@implementation _touchHLE_NSMutableDictionary_non_retaining: _touchHLE_NSMutableDictionary
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(DictionaryHostObject {
+        non_retaining: true,
+        ..Default::default()
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)init {
+    *env.objc.borrow_mut(this) = DictionaryHostObject {
+        non_retaining: true,
+        ..Default::default()
+    };
+    this
+}
+
+@end
+
 };
 
+/// Shared `initWithCoder:` implementation for both private subclasses.
+/// FIXME: What if it's not an NSKeyedUnarchiver?
+fn init_with_coder(env: &mut Environment, dict: id, coder: id) -> id {
+    let keys_and_objects = ns_keyed_unarchiver::decode_current_dictionary(env, coder);
+    let mut host_object = <DictionaryHostObject as Default>::default();
+    for (key, object) in keys_and_objects {
+        host_object.insert(env, key, object, /* copy_key: */ true);
+    }
+    *env.objc.borrow_mut(dict) = host_object;
+    dict
+}
+
+/// Shortcut for host code, like `[dict setObject:object forKey:key]` but with
+/// control over whether the key is copied or only retained (`CFDictionary`
+/// generally only retains keys).
+pub fn set_object_for_key(env: &mut Environment, dict: id, object: id, key: id, copy_key: bool) {
+    assert!(key != nil); // TODO: raise proper exception
+    let mut host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(dict));
+    host_obj.insert(env, key, object, copy_key);
+    *env.objc.borrow_mut(dict) = host_obj;
+}
+
 /// Direct constructor for use by host code, similar to
 /// `[[NSDictionary alloc] initWithObjectsAndKeys:]` but without variadics and
 /// with a more intuitive argument order. Unlike [super::ns_array::from_vec],
@@ -236,3 +425,16 @@ pub fn dict_from_keys_and_objects(env: &mut Environment, keys_and_objects: &[(id
 
     dict
 }
+
+/// Shortcut for host code: get all the key-value pairs of a dictionary, in no
+/// particular order. They are not retained.
+pub fn keys_and_objects(env: &mut Environment, dict: id) -> Vec<(id, id)> {
+    let host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(dict));
+    let keys: Vec<id> = host_obj.iter_keys().collect();
+    let keys_and_objects = keys
+        .into_iter()
+        .map(|key| (key, host_obj.lookup(env, key)))
+        .collect();
+    *env.objc.borrow_mut(dict) = host_obj;
+    keys_and_objects
+}
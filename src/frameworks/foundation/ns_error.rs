@@ -0,0 +1,238 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSError`.
+//!
+//! Host code that reports failures through an `NSError**` out-parameter should
+//! use [set_error], so that apps which inspect the error get something useful.
+
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::{ns_string, NSInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::mem::MutPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+/// `NSErrorDomain` values.
+pub const NSCocoaErrorDomain: &str = "NSCocoaErrorDomain";
+pub const NSPOSIXErrorDomain: &str = "NSPOSIXErrorDomain";
+pub const NSOSStatusErrorDomain: &str = "NSOSStatusErrorDomain";
+pub const NSMachErrorDomain: &str = "NSMachErrorDomain";
+pub const NSURLErrorDomain: &str = "NSURLErrorDomain";
+
+/// `NSErrorUserInfoKey` values.
+pub const NSLocalizedDescriptionKey: &str = "NSLocalizedDescription";
+pub const NSLocalizedFailureReasonErrorKey: &str = "NSLocalizedFailureReason";
+pub const NSUnderlyingErrorKey: &str = "NSUnderlyingError";
+pub const NSFilePathErrorKey: &str = "NSFilePath";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSCocoaErrorDomain",
+        HostConstant::NSString(NSCocoaErrorDomain),
+    ),
+    (
+        "_NSPOSIXErrorDomain",
+        HostConstant::NSString(NSPOSIXErrorDomain),
+    ),
+    (
+        "_NSOSStatusErrorDomain",
+        HostConstant::NSString(NSOSStatusErrorDomain),
+    ),
+    (
+        "_NSMachErrorDomain",
+        HostConstant::NSString(NSMachErrorDomain),
+    ),
+    (
+        "_NSURLErrorDomain",
+        HostConstant::NSString(NSURLErrorDomain),
+    ),
+    (
+        "_NSLocalizedDescriptionKey",
+        HostConstant::NSString(NSLocalizedDescriptionKey),
+    ),
+    (
+        "_NSLocalizedFailureReasonErrorKey",
+        HostConstant::NSString(NSLocalizedFailureReasonErrorKey),
+    ),
+    (
+        "_NSUnderlyingErrorKey",
+        HostConstant::NSString(NSUnderlyingErrorKey),
+    ),
+    (
+        "_NSFilePathErrorKey",
+        HostConstant::NSString(NSFilePathErrorKey),
+    ),
+];
+
+// Error codes in NSCocoaErrorDomain (FoundationErrors.h).
+#[allow(dead_code)]
+pub const NSFileNoSuchFileError: NSInteger = 4;
+pub const NSFileReadUnknownError: NSInteger = 256;
+pub const NSFileReadNoSuchFileError: NSInteger = 260;
+#[allow(dead_code)]
+pub const NSFileReadInapplicableStringEncodingError: NSInteger = 261;
+pub const NSFileWriteUnknownError: NSInteger = 512;
+pub const NSFileWriteFileExistsError: NSInteger = 516;
+pub const NSPropertyListReadCorruptError: NSInteger = 3840;
+pub const NSPropertyListWriteStreamError: NSInteger = 3851;
+
+// Error codes in NSURLErrorDomain (NSURLError.h).
+pub const NSURLErrorUnknown: NSInteger = -1;
+
+struct NSErrorHostObject {
+    /// `NSString*`
+    domain: id,
+    code: NSInteger,
+    /// `NSDictionary*`, possibly nil
+    user_info: id,
+}
+impl HostObject for NSErrorHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSError: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSErrorHostObject {
+        domain: nil,
+        code: 0,
+        user_info: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)errorWithDomain:(id)domain // NSString*
+                 code:(NSInteger)code
+             userInfo:(id)user_info { // NSDictionary*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithDomain:domain code:code userInfo:user_info];
+    autorelease(env, new)
+}
+
+- (id)initWithDomain:(id)domain // NSString*
+                code:(NSInteger)code
+            userInfo:(id)user_info { // NSDictionary*
+    assert!(domain != nil); // TODO: raise exception
+    let domain: id = msg![env; domain copy];
+    let user_info: id = msg![env; user_info copy];
+    *env.objc.borrow_mut(this) = NSErrorHostObject { domain, code, user_info };
+    this
+}
+
+- (())dealloc {
+    let &NSErrorHostObject { domain, user_info, .. } = env.objc.borrow(this);
+    release(env, domain);
+    release(env, user_info);
+    env.objc.dealloc_object(this, &mut env.mem);
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    // NSError is immutable.
+    retain(env, this)
+}
+
+- (id)domain {
+    env.objc.borrow::<NSErrorHostObject>(this).domain
+}
+- (NSInteger)code {
+    env.objc.borrow::<NSErrorHostObject>(this).code
+}
+- (id)userInfo {
+    env.objc.borrow::<NSErrorHostObject>(this).user_info
+}
+
+- (id)localizedDescription {
+    let &NSErrorHostObject { domain, code, user_info } = env.objc.borrow(this);
+    if user_info != nil {
+        let key = ns_string::get_static_str(env, NSLocalizedDescriptionKey);
+        let description: id = msg![env; user_info objectForKey:key];
+        if description != nil {
+            return description;
+        }
+    }
+    let domain = ns_string::to_rust_string(env, domain);
+    let description = format!(
+        "The operation couldn\u{2019}t be completed. ({} error {}.)",
+        domain, code
+    );
+    let description = ns_string::from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+- (id)localizedFailureReason {
+    let user_info = env.objc.borrow::<NSErrorHostObject>(this).user_info;
+    if user_info == nil {
+        return nil;
+    }
+    let key = ns_string::get_static_str(env, NSLocalizedFailureReasonErrorKey);
+    msg![env; user_info objectForKey:key]
+}
+
+- (id)description {
+    let &NSErrorHostObject { domain, code, .. } = env.objc.borrow(this);
+    let domain = ns_string::to_rust_string(env, domain);
+    let localized_description: id = msg![env; this localizedDescription];
+    let localized_description = ns_string::to_rust_string(env, localized_description);
+    let description = format!(
+        "Error Domain={} Code={} \"{}\"",
+        domain, code, localized_description
+    );
+    let description = ns_string::from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+@end
+
+};
+
+/// Create a new `NSError*` (not autoreleased) for use by host code, with an
+/// optional localized description.
+pub fn new_error(
+    env: &mut Environment,
+    domain: &'static str,
+    code: NSInteger,
+    description: Option<String>,
+) -> id {
+    let domain = ns_string::get_static_str(env, domain);
+    let user_info = description.map(|description| {
+        let key = ns_string::get_static_str(env, NSLocalizedDescriptionKey);
+        let description = ns_string::from_rust_string(env, description);
+        let user_info = dict_from_keys_and_objects(env, &[(key, description)]);
+        release(env, description);
+        user_info
+    });
+    let error: id = msg_class![env; NSError alloc];
+    let error: id = msg![env; error initWithDomain:domain
+                                              code:code
+                                          userInfo:(user_info.unwrap_or(nil))];
+    if let Some(user_info) = user_info {
+        release(env, user_info);
+    }
+    error
+}
+
+/// Report a failure to the caller through an `NSError**` out-parameter, if
+/// the caller provided one. The error is autoreleased.
+pub fn set_error(
+    env: &mut Environment,
+    error_ptr: MutPtr<id>,
+    domain: &'static str,
+    code: NSInteger,
+    description: Option<String>,
+) {
+    if error_ptr.is_null() {
+        return;
+    }
+    let error = new_error(env, domain, code, description);
+    let error = autorelease(env, error);
+    env.mem.write(error_ptr, error);
+}
@@ -5,9 +5,15 @@
  */
 //! `NSFileManager` etc.
 
-use super::{ns_array, ns_string, NSUInteger};
-use crate::dyld::{export_c_func, FunctionExports};
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_error::{
+    set_error, NSCocoaErrorDomain, NSFileReadNoSuchFileError, NSFileReadUnknownError,
+    NSFileWriteFileExistsError, NSFileWriteUnknownError,
+};
+use super::{ns_array, ns_date, ns_string, NSUInteger};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
 use crate::fs::{GuestPath, GuestPathBuf};
+use crate::mem::MutPtr;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
 };
@@ -54,6 +60,32 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(NSSearchPathForDirectoriesInDomains(_, _, _)),
 ];
 
+// File attribute keys and values. Values might not be correct, but as these
+// are linked symbol constants, it shouldn't matter.
+pub const NSFileSize: &str = "NSFileSize";
+pub const NSFileModificationDate: &str = "NSFileModificationDate";
+pub const NSFileType: &str = "NSFileType";
+pub const NSFileTypeRegular: &str = "NSFileTypeRegular";
+pub const NSFileTypeDirectory: &str = "NSFileTypeDirectory";
+// TODO: more attributes
+
+pub const CONSTANTS: ConstantExports = &[
+    ("_NSFileSize", HostConstant::NSString(NSFileSize)),
+    (
+        "_NSFileModificationDate",
+        HostConstant::NSString(NSFileModificationDate),
+    ),
+    ("_NSFileType", HostConstant::NSString(NSFileType)),
+    (
+        "_NSFileTypeRegular",
+        HostConstant::NSString(NSFileTypeRegular),
+    ),
+    (
+        "_NSFileTypeDirectory",
+        HostConstant::NSString(NSFileTypeDirectory),
+    ),
+];
+
 #[derive(Default)]
 pub struct State {
     default_manager: Option<id>,
@@ -81,23 +113,162 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (bool)fileExistsAtPath:(id)path { // NSString*
-    let path = ns_string::to_rust_string(env, path); // TODO: avoid copy
+    let path = to_guest_path(env, path);
     // fileExistsAtPath: will return true for directories, hence Fs::exists()
     // rather than Fs::is_file() is appropriate.
-    let res = env.fs.exists(GuestPath::new(&path));
+    let res = env.fs.exists(&path);
     log_dbg!("fileExistsAtPath:{:?} => {}", path, res);
     res
 }
 
+- (bool)fileExistsAtPath:(id)path // NSString*
+             isDirectory:(MutPtr<u8>)is_dir { // BOOL*
+    let path = to_guest_path(env, path);
+    let res = env.fs.exists(&path);
+    let res_is_dir = env.fs.is_dir(&path);
+    log_dbg!("fileExistsAtPath:{:?} isDirectory:{:?} => {} ({})", path, is_dir, res, res_is_dir);
+    if !is_dir.is_null() && res {
+        env.mem.write(is_dir, res_is_dir.into());
+    }
+    res
+}
+
+- (id)contentsOfDirectoryAtPath:(id)path // NSString*
+                          error:(MutPtr<id>)error { // NSError**
+    let path = to_guest_path(env, path);
+    let Ok(names) = env.fs.enumerate(&path) else {
+        log!("Warning: contentsOfDirectoryAtPath:{:?} failed", path);
+        let code = if env.fs.exists(&path) {
+            NSFileReadUnknownError
+        } else {
+            NSFileReadNoSuchFileError
+        };
+        set_error(env, error, NSCocoaErrorDomain, code, None);
+        return nil;
+    };
+    // The order is not specified, but sorting makes it deterministic.
+    let mut names: Vec<String> = names.map(String::from).collect();
+    names.sort();
+    log_dbg!("contentsOfDirectoryAtPath:{:?} => {:?}", path, names);
+    let names = names
+        .into_iter()
+        .map(|name| ns_string::from_rust_string(env, name))
+        .collect();
+    let array = ns_array::from_vec(env, names);
+    autorelease(env, array)
+}
+
+// Deprecated equivalent of contentsOfDirectoryAtPath:error:
+- (id)directoryContentsAtPath:(id)path { // NSString*
+    msg![env; this contentsOfDirectoryAtPath:path error:(MutPtr::<id>::null())]
+}
+
+// Deprecated in favor of the next method, but that's only available from
+// iPhone OS 2.0 onwards.
+- (bool)createDirectoryAtPath:(id)path // NSString*
+                   attributes:(id)attributes { // NSDictionary*
+    msg![env; this createDirectoryAtPath:path
+             withIntermediateDirectories:false
+                              attributes:attributes
+                                   error:(MutPtr::<id>::null())]
+}
+
+- (bool)createDirectoryAtPath:(id)path // NSString*
+  withIntermediateDirectories:(bool)with_intermediates
+                   attributes:(id)attributes // NSDictionary*
+                        error:(MutPtr<id>)error { // NSError**
+    if attributes != nil {
+        log!("TODO: createDirectoryAtPath:{:?} attributes:{:?} (ignored)", path, attributes);
+    }
+    let path = to_guest_path(env, path);
+
+    let res = if with_intermediates {
+        // Create each missing ancestor in turn. It's not an error if the
+        // directory already exists in this case.
+        let mut ancestors = Vec::new();
+        let mut current: &GuestPath = &path;
+        while !env.fs.exists(current) {
+            ancestors.push(current.to_owned());
+            // A relative path with one component is in the current directory.
+            current = current.parent().unwrap_or(GuestPath::new("."));
+        }
+        env.fs.is_dir(current)
+            && ancestors
+                .into_iter()
+                .rev()
+                .all(|ancestor| env.fs.create_dir(ancestor).is_ok())
+    } else {
+        env.fs.create_dir(&path).is_ok()
+    };
+    log_dbg!(
+        "createDirectoryAtPath:{:?} withIntermediateDirectories:{} => {}",
+        path,
+        with_intermediates,
+        res
+    );
+    if !res {
+        let code = if !with_intermediates && env.fs.exists(&path) {
+            NSFileWriteFileExistsError
+        } else {
+            NSFileWriteUnknownError
+        };
+        set_error(env, error, NSCocoaErrorDomain, code, None);
+    }
+    res
+}
+
+- (id)attributesOfItemAtPath:(id)path // NSString*
+                       error:(MutPtr<id>)error { // NSError**
+    let path = to_guest_path(env, path);
+    let Ok(metadata) = env.fs.metadata(&path) else {
+        log!("Warning: attributesOfItemAtPath:{:?} failed", path);
+        set_error(env, error, NSCocoaErrorDomain, NSFileReadNoSuchFileError, None);
+        return nil;
+    };
+    log_dbg!("attributesOfItemAtPath:{:?} => {:?}", path, metadata);
+
+    let size_key = ns_string::get_static_str(env, NSFileSize);
+    let size: id = msg_class![env; NSNumber numberWithUnsignedLongLong:(metadata.size)];
+    let type_key = ns_string::get_static_str(env, NSFileType);
+    let type_ = ns_string::get_static_str(
+        env,
+        if metadata.is_dir {
+            NSFileTypeDirectory
+        } else {
+            NSFileTypeRegular
+        },
+    );
+    let mut keys_and_objects = vec![(size_key, size), (type_key, type_)];
+    let date = metadata.modified.map(|modified| {
+        let interval = ns_date::system_time_since_reference_date(modified);
+        ns_date::from_time_interval_since_reference_date(env, interval)
+    });
+    if let Some(date) = date {
+        let date_key = ns_string::get_static_str(env, NSFileModificationDate);
+        keys_and_objects.push((date_key, date));
+    }
+    let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+    if let Some(date) = date {
+        release(env, date);
+    }
+    autorelease(env, dict)
+}
+
+// Deprecated equivalent of attributesOfItemAtPath:error:
+- (id)fileAttributesAtPath:(id)path // NSString*
+              traverseLink:(bool)_traverse_link { // there are no symlinks
+    msg![env; this attributesOfItemAtPath:path error:(MutPtr::<id>::null())]
+}
+
 - (bool)createFileAtPath:(id)path // NSString*
                 contents:(id)data // NSData*
               attributes:(id)attributes { // NSDictionary*
     assert!(attributes == nil); // TODO
 
-    let path_str = ns_string::to_rust_string(env, path); // TODO: avoid copy
+    let path_str = to_guest_path(env, path);
     // createFileAtPath: returns true if there's already a file at a given path.
     // If there's a directory, that's an error, though.
-    if env.fs.is_file(GuestPath::new(&path_str)) {
+    if env.fs.is_file(&path_str) {
         return true;
     }
 
@@ -112,8 +283,8 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (id)enumeratorAtPath:(id)path { // NSString*
-    let path = ns_string::to_rust_string(env, path); // TODO: avoid copy
-    let Ok(paths) = env.fs.enumerate_recursive(GuestPath::new(&path)) else {
+    let path = to_guest_path(env, path);
+    let Ok(paths) = env.fs.enumerate_recursive(&path) else {
         return nil;
     };
     let host_object = Box::new(NSDirectoryEnumeratorHostObject {
@@ -136,3 +307,20 @@ pub const CLASSES: ClassExports = objc_classes! {
 @end
 
 };
+
+/// Convert an `NSString*` path to a guest path. A leading `~` refers to the
+/// app's sandboxed home directory, like in `stringByExpandingTildeInPath`.
+fn to_guest_path(env: &mut Environment, path: id) -> GuestPathBuf {
+    let path = ns_string::to_rust_string(env, path);
+    // Trailing slashes are allowed but don't mean anything here.
+    let path = match path.trim_end_matches('/') {
+        "" if path.starts_with('/') => "/",
+        trimmed => trimmed,
+    };
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            GuestPathBuf::from(format!("{}{}", env.fs.home_directory().as_str(), rest))
+        }
+        _ => GuestPathBuf::from(path.to_string()),
+    }
+}
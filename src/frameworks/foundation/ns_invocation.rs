@@ -0,0 +1,266 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSInvocation`.
+//!
+//! We don't have type information for most methods, so arguments and return
+//! values are stored as raw 32-bit words, as they would be passed in registers
+//! and on the stack. This works for objects, pointers and 32-bit scalars.
+//! Return values can also be 64-bit scalars.
+
+use super::ns_method_signature;
+use super::{NSInteger, NSUInteger};
+use crate::mem::{ConstVoidPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_send_with_raw_args, nil, objc_classes, release, retain, ClassExports,
+    HostObject, SEL,
+};
+use crate::Environment;
+
+struct NSInvocationHostObject {
+    /// `NSMethodSignature*`
+    signature: id,
+    target: id,
+    selector: Option<SEL>,
+    /// The arguments after `self` and `_cmd`.
+    arguments: Vec<u32>,
+    /// The contents of `r0` and `r1` after the method returns.
+    return_value: [u32; 2],
+    arguments_retained: bool,
+}
+impl HostObject for NSInvocationHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSInvocation: NSObject
+
++ (id)invocationWithMethodSignature:(id)signature { // NSMethodSignature*
+    let arg_count: NSUInteger = msg![env; signature numberOfArguments];
+    let signature = retain(env, signature);
+    let new = new_with_signature(env, signature, arg_count - 2);
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let &NSInvocationHostObject {
+        signature,
+        target,
+        arguments_retained,
+        ..
+    } = env.objc.borrow(this);
+    if arguments_retained {
+        release(env, target);
+        for object in retained_arguments(env, this) {
+            release(env, object);
+        }
+    }
+    release(env, signature);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)methodSignature {
+    env.objc.borrow::<NSInvocationHostObject>(this).signature
+}
+
+- (())retainArguments {
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(this);
+    if host_object.arguments_retained {
+        return;
+    }
+    host_object.arguments_retained = true;
+    let target = host_object.target;
+    retain(env, target);
+    for object in retained_arguments(env, this) {
+        retain(env, object);
+    }
+}
+- (bool)argumentsRetained {
+    env.objc.borrow::<NSInvocationHostObject>(this).arguments_retained
+}
+
+- (id)target {
+    env.objc.borrow::<NSInvocationHostObject>(this).target
+}
+- (())setTarget:(id)target {
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(this);
+    let old_target = std::mem::replace(&mut host_object.target, target);
+    if host_object.arguments_retained {
+        retain(env, target);
+        release(env, old_target);
+    }
+}
+
+- (SEL)selector {
+    env.objc.borrow::<NSInvocationHostObject>(this).selector.unwrap()
+}
+- (())setSelector:(SEL)selector {
+    env.objc.borrow_mut::<NSInvocationHostObject>(this).selector = Some(selector);
+}
+
+- (())getArgument:(MutVoidPtr)buffer
+          atIndex:(NSInteger)index {
+    match index {
+        0 => {
+            let target = env.objc.borrow::<NSInvocationHostObject>(this).target;
+            env.mem.write(buffer.cast(), target);
+        }
+        1 => {
+            let selector = env.objc.borrow::<NSInvocationHostObject>(this).selector.unwrap();
+            env.mem.write(buffer.cast(), selector);
+        }
+        _ => {
+            let index: usize = (index - 2).try_into().unwrap();
+            let word = env.objc.borrow::<NSInvocationHostObject>(this).arguments[index];
+            env.mem.write(buffer.cast(), word);
+        }
+    }
+}
+- (())setArgument:(ConstVoidPtr)buffer
+          atIndex:(NSInteger)index {
+    match index {
+        0 => {
+            let target: id = env.mem.read(buffer.cast());
+            msg![env; this setTarget:target]
+        }
+        1 => {
+            let selector: SEL = env.mem.read(buffer.cast());
+            msg![env; this setSelector:selector]
+        }
+        _ => {
+            // TODO: arguments larger than 32 bits
+            let index: usize = (index - 2).try_into().unwrap();
+            let word: u32 = env.mem.read(buffer.cast());
+            let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(this);
+            let old_word = std::mem::replace(&mut host_object.arguments[index], word);
+            let (signature, arguments_retained) =
+                (host_object.signature, host_object.arguments_retained);
+            if arguments_retained && is_object_type(env, signature, index + 2) {
+                retain(env, id::from_bits(word));
+                release(env, id::from_bits(old_word));
+            }
+        }
+    }
+}
+
+- (())getReturnValue:(MutVoidPtr)buffer {
+    let &NSInvocationHostObject {
+        signature,
+        return_value,
+        ..
+    } = env.objc.borrow(this);
+    let size = return_value_size(env, signature);
+    if size == 0 {
+        return;
+    }
+    let bytes: Vec<u8> = return_value.iter().flat_map(|word| word.to_le_bytes()).collect();
+    env.mem
+        .bytes_at_mut(buffer.cast(), size)
+        .copy_from_slice(&bytes[..size as usize]);
+}
+- (())setReturnValue:(ConstVoidPtr)buffer {
+    let signature = env.objc.borrow::<NSInvocationHostObject>(this).signature;
+    let size = return_value_size(env, signature);
+    if size == 0 {
+        return;
+    }
+    let mut bytes = [0u8; 8];
+    bytes[..size as usize].copy_from_slice(env.mem.bytes_at(buffer.cast(), size));
+    env.objc.borrow_mut::<NSInvocationHostObject>(this).return_value = [
+        u32::from_le_bytes(bytes[..4].try_into().unwrap()),
+        u32::from_le_bytes(bytes[4..].try_into().unwrap()),
+    ];
+}
+
+- (())invoke {
+    let target = env.objc.borrow::<NSInvocationHostObject>(this).target;
+    invoke_with_target(env, this, target)
+}
+- (())invokeWithTarget:(id)target {
+    invoke_with_target(env, this, target)
+}
+
+@end
+
+};
+
+fn new_with_signature(env: &mut Environment, signature: id, arg_count: NSUInteger) -> id {
+    let host_object = Box::new(NSInvocationHostObject {
+        signature,
+        target: nil,
+        selector: None,
+        arguments: vec![0; arg_count as usize],
+        return_value: [0; 2],
+        arguments_retained: false,
+    });
+    let class = env.objc.get_known_class("NSInvocation", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+/// Check if the argument at an index (counting `self` and `_cmd`) is an object
+/// according to the signature.
+fn is_object_type(env: &Environment, signature: id, index: usize) -> bool {
+    let type_ = ns_method_signature::type_at(env, signature, Some(index));
+    type_.trim_start_matches(['r', 'n', 'N', 'o', 'O', 'R', 'V']) == "@"
+}
+
+/// Get the arguments (excluding the target) that are retained by
+/// `retainArguments`.
+fn retained_arguments(env: &Environment, invocation: id) -> Vec<id> {
+    let host_object = env.objc.borrow::<NSInvocationHostObject>(invocation);
+    host_object
+        .arguments
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| is_object_type(env, host_object.signature, i + 2))
+        .map(|(_, &word)| id::from_bits(word))
+        .collect()
+}
+
+fn return_value_size(env: &Environment, signature: id) -> u32 {
+    let size = ns_method_signature::type_size(ns_method_signature::type_at(env, signature, None));
+    assert!(size <= 8); // TODO: struct returns
+    size
+}
+
+fn invoke_with_target(env: &mut Environment, invocation: id, target: id) {
+    let host_object = env.objc.borrow::<NSInvocationHostObject>(invocation);
+    let selector = host_object.selector.unwrap();
+    let arguments = host_object.arguments.clone();
+    let return_value = msg_send_with_raw_args(env, target, selector, &arguments);
+    env.objc
+        .borrow_mut::<NSInvocationHostObject>(invocation)
+        .return_value = return_value;
+}
+
+/// For use by the Objective-C runtime: create an invocation for a message that
+/// is being forwarded. `arguments` are the raw words after `self` and `_cmd`.
+pub fn new_for_forwarding(
+    env: &mut Environment,
+    target: id,
+    selector: SEL,
+    arguments: Vec<u32>,
+) -> id {
+    let signature = ns_method_signature::new_for_selector(env, selector);
+    let invocation = new_with_signature(env, signature, arguments.len() as NSUInteger);
+    let host_object = env.objc.borrow_mut::<NSInvocationHostObject>(invocation);
+    host_object.target = target;
+    host_object.selector = Some(selector);
+    host_object.arguments = arguments;
+    invocation
+}
+
+/// For use by the Objective-C runtime: get the raw return value (`r0` and
+/// `r1`) of an invocation, so it can be returned to the original caller of a
+/// forwarded message.
+pub fn raw_return_value(env: &Environment, invocation: id) -> [u32; 2] {
+    if invocation == nil {
+        return [0; 2];
+    }
+    env.objc
+        .borrow::<NSInvocationHostObject>(invocation)
+        .return_value
+}
@@ -11,13 +11,15 @@
 //!   plists, e.g. `plutil -p` or `println!("{:#?}", plist::Value::...);`.
 //! - Apple's [Archives and Serializations Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Archiving/Articles/archives.html)
 
-use super::ns_string::{from_rust_string, to_rust_string};
+use super::ns_string::{from_rust_string, get_static_str, to_rust_string};
+use super::{ns_data, ns_property_list_serialization, NSInteger};
 use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
 use crate::frameworks::uikit::ui_geometry::{
     CGPointFromString, CGRectFromString, CGSizeFromString,
 };
 use crate::objc::{
-    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
 };
 use crate::Environment;
 use plist::{Dictionary, Uid, Value};
@@ -46,8 +48,36 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.alloc_object(this, unarchiver, &mut env.mem)
 }
 
-// TODO: real init methods. This is currently only initialized by the shortcut
-// function below.
++ (id)unarchiveObjectWithData:(id)data { // NSData*
+    let unarchiver: id = msg![env; this alloc];
+    let unarchiver: id = msg![env; unarchiver initForReadingWithData:data];
+    // The root object of an archive made with archiveRootObject: or
+    // archivedDataWithRootObject: is always stored under this key.
+    let key = get_static_str(env, "root");
+    let object: id = msg![env; unarchiver decodeObjectForKey:key];
+    release(env, unarchiver);
+    object
+}
+
++ (id)unarchiveObjectWithFile:(id)path { // NSString*
+    let data: id = msg_class![env; NSData dataWithContentsOfFile:path];
+    if data == nil {
+        return nil;
+    }
+    msg![env; this unarchiveObjectWithData:data]
+}
+
+- (id)initForReadingWithData:(id)data { // NSData*
+    // TODO: raise an exception rather than panicking if the data isn't a
+    // keyed archive
+    let bytes = ns_data::to_rust_slice(env, data).to_vec();
+    init_for_reading_with_data(env, this, &bytes);
+    this
+}
+
+- (())finishDecoding {
+    // Nothing to do, there are no delegates to notify.
+}
 
 - (())dealloc {
     let host_obj = borrow_host_obj(env, this);
@@ -87,6 +117,30 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, object)
 }
 
+- (bool)containsValueForKey:(id)key { // NSString*
+    get_value_to_decode_for_key(env, this, key).is_some()
+}
+
+- (i32)decodeIntForKey:(id)key { // NSString*
+    decode_integer_for_key(env, this, key) as i32
+}
+- (i32)decodeInt32ForKey:(id)key { // NSString*
+    decode_integer_for_key(env, this, key) as i32
+}
+- (i64)decodeInt64ForKey:(id)key { // NSString*
+    decode_integer_for_key(env, this, key)
+}
+- (NSInteger)decodeIntegerForKey:(id)key { // NSString*
+    decode_integer_for_key(env, this, key) as NSInteger
+}
+
+- (f32)decodeFloatForKey:(id)key { // NSString*
+    decode_real_for_key(env, this, key) as f32
+}
+- (f64)decodeDoubleForKey:(id)key { // NSString*
+    decode_real_for_key(env, this, key)
+}
+
 // TODO: add more decode methods
 
 // These come from a category in UIKit's UIGeometry.h
@@ -125,6 +179,27 @@ fn get_value_to_decode_for_key(env: &mut Environment, unarchiver: id, key: id) -
     scope.get(&key)
 }
 
+/// Integers are stored as integers, but Apple's implementation also accepts
+/// reals here, truncating them.
+fn decode_integer_for_key(env: &mut Environment, unarchiver: id, key: id) -> i64 {
+    match get_value_to_decode_for_key(env, unarchiver, key) {
+        None => 0,
+        Some(Value::Real(real)) => *real as i64,
+        Some(value) => value
+            .as_signed_integer()
+            .or_else(|| value.as_unsigned_integer().map(|int| int as i64))
+            .unwrap(),
+    }
+}
+
+fn decode_real_for_key(env: &mut Environment, unarchiver: id, key: id) -> f64 {
+    match get_value_to_decode_for_key(env, unarchiver, key) {
+        None => 0.0,
+        Some(Value::Real(real)) => *real,
+        Some(value) => value.as_signed_integer().unwrap() as f64,
+    }
+}
+
 /// Shortcut for use by [crate::frameworks::uikit::ui_nib::load_main_nib_file].
 ///
 /// This is equivalent to calling `initForReadingWithData:` in the proper API.
@@ -202,10 +277,34 @@ fn unarchive_key(env: &mut Environment, unarchiver: id, key: Uid) -> id {
 
             new_object
         }
+        // The first object in every archive is this placeholder for nil.
+        Value::String(s) if s == "$null" => nil,
         Value::String(s) => {
             let s = s.to_string();
             from_rust_string(env, s)
         }
+        // Numbers and data are stored directly rather than with a class.
+        &Value::Boolean(b) => {
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithBool:b]
+        }
+        &Value::Real(r) => {
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithDouble:r]
+        }
+        &Value::Integer(i) => {
+            let number: id = msg_class![env; NSNumber alloc];
+            if let Some(i) = i.as_signed() {
+                msg![env; number initWithLongLong:i]
+            } else {
+                let i = i.as_unsigned().unwrap();
+                msg![env; number initWithUnsignedLongLong:i]
+            }
+        }
+        Value::Data(bytes) => {
+            let bytes = bytes.clone();
+            ns_property_list_serialization::new_data(env, &bytes)
+        }
         _ => unimplemented!("Unarchive: {:#?}", item),
     };
 
@@ -238,3 +337,39 @@ pub fn decode_current_array(env: &mut Environment, unarchiver: id) -> Vec<id> {
         })
         .collect()
 }
+
+/// Shortcut for use by `[_touchHLE_NSDictionary initWithCoder:]`.
+///
+/// Dictionaries look much like arrays (see [decode_current_array]), but with
+/// an `NS.keys` array that matches the `NS.objects` array. The keys and
+/// objects are retained only by the unarchiver.
+pub fn decode_current_dictionary(env: &mut Environment, unarchiver: id) -> Vec<(id, id)> {
+    let uids: Vec<(Uid, Uid)> = {
+        let host_obj = borrow_host_obj(env, unarchiver);
+        let objects = host_obj.plist["$objects"].as_array().unwrap();
+        let item = objects[host_obj.current_key.unwrap().get() as usize]
+            .as_dictionary()
+            .unwrap();
+        let keys = item["NS.keys"].as_array().unwrap();
+        let objects = item["NS.objects"].as_array().unwrap();
+        assert!(keys.len() == objects.len());
+        keys.iter()
+            .zip(objects.iter())
+            .map(|(key, object)| {
+                (
+                    key.as_uid().copied().unwrap(),
+                    object.as_uid().copied().unwrap(),
+                )
+            })
+            .collect()
+    };
+
+    uids.into_iter()
+        .map(|(key, object)| {
+            (
+                unarchive_key(env, unarchiver, key),
+                unarchive_key(env, unarchiver, object),
+            )
+        })
+        .collect()
+}
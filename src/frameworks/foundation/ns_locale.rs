@@ -4,14 +4,178 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `NSLocale`.
+//!
+//! The current locale is `en_US` unless the user picks another one with the
+//! `--locale=` option, so that apps format numbers the same way on every host.
+//! Only a handful of locales have their own data (see [LocaleData]), the
+//! rest behave like `en_US`.
 
 use super::{ns_array, ns_string};
-use crate::objc::{id, objc_classes, ClassExports};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
 use crate::Environment;
 
+/// `NSLocaleKey` values.
+pub const NSLocaleIdentifier: &str = "NSLocaleIdentifier";
+pub const NSLocaleLanguageCode: &str = "NSLocaleLanguageCode";
+pub const NSLocaleCountryCode: &str = "NSLocaleCountryCode";
+pub const NSLocaleDecimalSeparator: &str = "NSLocaleDecimalSeparator";
+pub const NSLocaleGroupingSeparator: &str = "NSLocaleGroupingSeparator";
+pub const NSLocaleCurrencySymbol: &str = "NSLocaleCurrencySymbol";
+pub const NSLocaleCurrencyCode: &str = "NSLocaleCurrencyCode";
+pub const NSLocaleUsesMetricSystem: &str = "NSLocaleUsesMetricSystem";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSLocaleIdentifier",
+        HostConstant::NSString(NSLocaleIdentifier),
+    ),
+    (
+        "_NSLocaleLanguageCode",
+        HostConstant::NSString(NSLocaleLanguageCode),
+    ),
+    (
+        "_NSLocaleCountryCode",
+        HostConstant::NSString(NSLocaleCountryCode),
+    ),
+    (
+        "_NSLocaleDecimalSeparator",
+        HostConstant::NSString(NSLocaleDecimalSeparator),
+    ),
+    (
+        "_NSLocaleGroupingSeparator",
+        HostConstant::NSString(NSLocaleGroupingSeparator),
+    ),
+    (
+        "_NSLocaleCurrencySymbol",
+        HostConstant::NSString(NSLocaleCurrencySymbol),
+    ),
+    (
+        "_NSLocaleCurrencyCode",
+        HostConstant::NSString(NSLocaleCurrencyCode),
+    ),
+    (
+        "_NSLocaleUsesMetricSystem",
+        HostConstant::NSString(NSLocaleUsesMetricSystem),
+    ),
+];
+
+/// Number formatting conventions of a locale.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LocaleData {
+    pub decimal_separator: &'static str,
+    pub grouping_separator: &'static str,
+    pub currency_symbol: &'static str,
+    pub currency_code: &'static str,
+    /// Number of digits after the decimal separator in currency amounts.
+    pub currency_fraction_digits: u32,
+    /// Where the currency symbol (`¤`) goes relative to the number (`#`).
+    pub currency_pattern: &'static str,
+    /// Where the percent sign goes relative to the number (`#`).
+    pub percent_pattern: &'static str,
+    pub uses_metric_system: bool,
+}
+
+const NBSP: &str = "\u{a0}";
+
+const EN_US: LocaleData = LocaleData {
+    decimal_separator: ".",
+    grouping_separator: ",",
+    currency_symbol: "$",
+    currency_code: "USD",
+    currency_fraction_digits: 2,
+    currency_pattern: "¤#",
+    percent_pattern: "#%",
+    uses_metric_system: false,
+};
+/// Conventions shared by many of the countries that use the euro.
+const EURO: LocaleData = LocaleData {
+    decimal_separator: ",",
+    grouping_separator: ".",
+    currency_symbol: "€",
+    currency_code: "EUR",
+    currency_fraction_digits: 2,
+    currency_pattern: "#\u{a0}¤",
+    percent_pattern: "#\u{a0}%",
+    uses_metric_system: true,
+};
+
+impl LocaleData {
+    /// Get the data for a locale identifier like `en_US`. Locales without
+    /// their own data use the data of the main country for the language, or
+    /// failing that, `en_US`.
+    pub fn for_identifier(identifier: &str) -> LocaleData {
+        let (language, country) = split_identifier(identifier);
+        match (language, country.unwrap_or("")) {
+            ("en", "GB") => LocaleData {
+                currency_symbol: "£",
+                currency_code: "GBP",
+                uses_metric_system: true,
+                ..EN_US
+            },
+            ("en", "AU") => LocaleData {
+                currency_code: "AUD",
+                uses_metric_system: true,
+                ..EN_US
+            },
+            ("en", "CA") => LocaleData {
+                currency_code: "CAD",
+                uses_metric_system: true,
+                ..EN_US
+            },
+            ("fr", _) => LocaleData {
+                grouping_separator: NBSP,
+                ..EURO
+            },
+            ("de" | "es" | "it", _) => EURO,
+            ("nl", _) => LocaleData {
+                currency_pattern: "¤\u{a0}#",
+                percent_pattern: "#%",
+                ..EURO
+            },
+            ("pt", "BR") | ("pt", "") => LocaleData {
+                currency_symbol: "R$",
+                currency_code: "BRL",
+                currency_pattern: "¤\u{a0}#",
+                percent_pattern: "#%",
+                ..EURO
+            },
+            ("sv", _) => LocaleData {
+                grouping_separator: NBSP,
+                currency_symbol: "kr",
+                currency_code: "SEK",
+                ..EURO
+            },
+            ("ja", _) => LocaleData {
+                currency_symbol: "¥",
+                currency_code: "JPY",
+                currency_fraction_digits: 0,
+                uses_metric_system: true,
+                ..EN_US
+            },
+            _ => EN_US,
+        }
+    }
+}
+
+/// Split a locale identifier like `en_US` into its language and country
+/// codes.
+fn split_identifier(identifier: &str) -> (&str, Option<&str>) {
+    let mut parts = identifier.split(['_', '-']);
+    let language = parts.next().unwrap_or("");
+    // Skip any script code, e.g. in "zh_Hant_TW".
+    let country = parts.find(|part| part.len() == 2 || part.len() == 3);
+    (language, country)
+}
+
 #[derive(Default)]
 pub struct State {
     preferred_languages: Option<id>,
+    /// `NSLocale*`
+    current_locale: Option<id>,
 }
 impl State {
     fn get(env: &mut Environment) -> &mut State {
@@ -19,12 +183,67 @@ impl State {
     }
 }
 
+struct NSLocaleHostObject {
+    identifier: String,
+}
+impl HostObject for NSLocaleHostObject {}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
 @implementation NSLocale: NSObject
 
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSLocaleHostObject {
+        identifier: String::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)localeWithLocaleIdentifier:(id)identifier { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithLocaleIdentifier:identifier];
+    autorelease(env, new)
+}
+
++ (id)currentLocale {
+    if let Some(existing) = State::get(env).current_locale {
+        return existing;
+    }
+    let identifier = env.options.locale.clone();
+    log!(
+        "The app requested the current locale. {:?} will be reported.",
+        identifier
+    );
+    let identifier = ns_string::from_rust_string(env, identifier);
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithLocaleIdentifier:identifier];
+    release(env, identifier);
+    State::get(env).current_locale = Some(new);
+    new
+}
++ (id)autoupdatingCurrentLocale {
+    // The locale never changes while the app is running.
+    msg![env; this currentLocale]
+}
++ (id)systemLocale {
+    let identifier = ns_string::get_static_str(env, "");
+    msg![env; this localeWithLocaleIdentifier:identifier]
+}
+
++ (id)availableLocaleIdentifiers {
+    let identifiers = [
+        "en_US", "en_GB", "en_AU", "en_CA", "fr_FR", "de_DE", "es_ES", "it_IT", "nl_NL", "pt_BR",
+        "sv_SE", "ja_JP",
+    ]
+    .into_iter()
+    .map(|identifier| ns_string::from_rust_string(env, identifier.to_string()))
+    .collect();
+    let array = ns_array::from_vec(env, identifiers);
+    autorelease(env, array)
+}
+
 // The documentation isn't clear about what the format of the strings should be,
 // but Super Monkey Ball does `isEqualToString:` against "fr", "es", "de", "it"
 // and "ja", and its locale detection works properly, so presumably they do not
@@ -53,8 +272,79 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
-// TODO: constructors, more accessors
+- (id)initWithLocaleIdentifier:(id)identifier { // NSString*
+    let identifier = ns_string::to_rust_string(env, identifier).into_owned();
+    env.objc.borrow_mut::<NSLocaleHostObject>(this).identifier = identifier;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    // Locales are immutable.
+    retain(env, this)
+}
+
+- (id)localeIdentifier {
+    let identifier = env.objc.borrow::<NSLocaleHostObject>(this).identifier.clone();
+    let identifier = ns_string::from_rust_string(env, identifier);
+    autorelease(env, identifier)
+}
+
+- (id)objectForKey:(id)key { // NSString*
+    let key = ns_string::to_rust_string(env, key);
+    let identifier = env.objc.borrow::<NSLocaleHostObject>(this).identifier.clone();
+    let data = LocaleData::for_identifier(&identifier);
+    let (language, country) = split_identifier(&identifier);
+    let value = match &*key {
+        NSLocaleIdentifier => identifier.clone(),
+        NSLocaleLanguageCode => language.to_string(),
+        NSLocaleCountryCode => match country {
+            Some(country) => country.to_string(),
+            None => return nil,
+        },
+        NSLocaleDecimalSeparator => data.decimal_separator.to_string(),
+        NSLocaleGroupingSeparator => data.grouping_separator.to_string(),
+        NSLocaleCurrencySymbol => data.currency_symbol.to_string(),
+        NSLocaleCurrencyCode => data.currency_code.to_string(),
+        NSLocaleUsesMetricSystem => {
+            let number: id = msg_class![env; NSNumber numberWithBool:(data.uses_metric_system)];
+            return number;
+        }
+        _ => {
+            log!("TODO: [NSLocale objectForKey:{:?}], returning nil", key);
+            return nil;
+        }
+    };
+    let value = ns_string::from_rust_string(env, value);
+    autorelease(env, value)
+}
+
+- (id)description {
+    msg![env; this localeIdentifier]
+}
 
 @end
 
 };
+
+/// Get the locale data for an `NSLocale*`, or for the current locale if it is
+/// [nil].
+pub fn locale_data(env: &mut Environment, locale: id) -> LocaleData {
+    if locale == nil {
+        LocaleData::for_identifier(&env.options.locale)
+    } else {
+        LocaleData::for_identifier(&env.objc.borrow::<NSLocaleHostObject>(locale).identifier)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_locale_data() {
+    assert_eq!(split_identifier("en_US"), ("en", Some("US")));
+    assert_eq!(split_identifier("zh-Hant-TW"), ("zh", Some("TW")));
+    assert_eq!(split_identifier("fr"), ("fr", None));
+    assert_eq!(LocaleData::for_identifier("en_US"), EN_US);
+    assert_eq!(LocaleData::for_identifier("de_AT"), EURO);
+    assert_eq!(LocaleData::for_identifier("ja_JP").currency_symbol, "¥");
+    assert_eq!(LocaleData::for_identifier("xx_YY"), EN_US);
+}
@@ -0,0 +1,172 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSMethodSignature`.
+//!
+//! The runtime doesn't keep type strings for methods (TODO), so signatures
+//! created for a selector assume that every argument and the return value is
+//! an object. Signatures created from a type string are accurate.
+
+use super::NSUInteger;
+use crate::mem::ConstPtr;
+use crate::objc::{autorelease, id, objc_classes, ClassExports, HostObject, SEL};
+use crate::Environment;
+
+struct NSMethodSignatureHostObject {
+    /// Type encodings for the return value, followed by each argument
+    /// (including `self` and `_cmd`).
+    types: Vec<String>,
+}
+impl HostObject for NSMethodSignatureHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSMethodSignature: NSObject
+
++ (id)signatureWithObjCTypes:(ConstPtr<u8>)types {
+    let types = env.mem.cstr_at_utf8(types).unwrap();
+    let types = split_objc_types(types);
+    assert!(types.len() >= 3); // return type, self and _cmd
+    let new = new_with_types(env, types);
+    autorelease(env, new)
+}
+
+- (NSUInteger)numberOfArguments {
+    let count = env.objc.borrow::<NSMethodSignatureHostObject>(this).types.len() - 1;
+    count.try_into().unwrap()
+}
+
+- (NSUInteger)methodReturnLength {
+    let types = &env.objc.borrow::<NSMethodSignatureHostObject>(this).types;
+    type_size(&types[0])
+}
+
+@end
+
+};
+
+fn new_with_types(env: &mut Environment, types: Vec<String>) -> id {
+    let host_object = Box::new(NSMethodSignatureHostObject { types });
+    let class = env.objc.get_known_class("NSMethodSignature", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+/// Create a signature for a selector, guessing the types (see the module
+/// documentation). The result is not autoreleased.
+pub fn new_for_selector(env: &mut Environment, selector: SEL) -> id {
+    let arg_count = selector.as_str(&env.mem).matches(':').count();
+    let mut types = vec!["@".to_string(), "@".to_string(), ":".to_string()];
+    types.extend((0..arg_count).map(|_| "@".to_string()));
+    new_with_types(env, types)
+}
+
+/// Get the type encoding of the return value (`index` is [None]) or of an
+/// argument.
+pub fn type_at(env: &Environment, signature: id, index: Option<usize>) -> &str {
+    let types = &env
+        .objc
+        .borrow::<NSMethodSignatureHostObject>(signature)
+        .types;
+    &types[index.map_or(0, |index| index + 1)]
+}
+
+/// Get the size in bytes of a value with a particular type encoding.
+/// Structs and unions are not supported (TODO).
+pub fn type_size(type_: &str) -> NSUInteger {
+    let type_ = type_.trim_start_matches(['r', 'n', 'N', 'o', 'O', 'R', 'V']);
+    match type_.as_bytes()[0] {
+        b'v' => 0,
+        b'c' | b'C' | b'B' => 1,
+        b's' | b'S' => 2,
+        b'q' | b'Q' | b'd' => 8,
+        b'{' | b'(' | b'[' => unimplemented!("Size of type {:?}", type_),
+        _ => 4,
+    }
+}
+
+/// Split an Objective-C method type string (e.g. `v12@0:4i8`) into the type
+/// encodings of each value, discarding the offsets.
+fn split_objc_types(types: &str) -> Vec<String> {
+    fn skip_type(bytes: &[u8], mut i: usize) -> usize {
+        while i < bytes.len() && b"rnNoORV".contains(&bytes[i]) {
+            i += 1;
+        }
+        match bytes[i] {
+            b'^' => skip_type(bytes, i + 1),
+            open @ (b'{' | b'(' | b'[') => {
+                let close = match open {
+                    b'{' => b'}',
+                    b'(' => b')',
+                    _ => b']',
+                };
+                let mut depth = 0;
+                loop {
+                    if bytes[i] == open {
+                        depth += 1;
+                    } else if bytes[i] == close {
+                        depth -= 1;
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            b'@' => {
+                i += 1;
+                if bytes.get(i) == Some(&b'?') {
+                    i + 1
+                } else if bytes.get(i) == Some(&b'"') {
+                    // Class name, e.g. @"NSString"
+                    i + 1 + bytes[i + 1..].iter().position(|&b| b == b'"').unwrap() + 1
+                } else {
+                    i
+                }
+            }
+            b'b' => {
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+                i
+            }
+            _ => i + 1,
+        }
+    }
+
+    let bytes = types.as_bytes();
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        i = skip_type(bytes, i);
+        result.push(types[start..i].to_string());
+        // Skip the offset
+        while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'-') {
+            i += 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+#[test]
+fn test_split_objc_types() {
+    assert_eq!(split_objc_types("v12@0:4i8"), ["v", "@", ":", "i"]);
+    assert_eq!(split_objc_types("v@:"), ["v", "@", ":"]);
+    assert_eq!(
+        split_objc_types("{CGPoint=ff}16@0:4^{CGSize=ff}8Vv12"),
+        ["{CGPoint=ff}", "@", ":", "^{CGSize=ff}", "Vv"]
+    );
+    assert_eq!(
+        split_objc_types("@\"NSString\"12@0:4@?8"),
+        ["@\"NSString\"", "@", ":", "@?"]
+    );
+    assert_eq!(type_size("Q"), 8);
+    assert_eq!(type_size("rc"), 1);
+    assert_eq!(type_size("^{CGSize=ff}"), 4);
+}
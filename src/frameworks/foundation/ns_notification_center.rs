@@ -9,11 +9,10 @@ use super::ns_notification::NSNotificationName;
 use super::ns_string;
 
 use crate::objc::{
-    id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports, HostObject,
-    NSZonePtr, SEL,
+    id, msg, msg_class, msg_send, nil, objc_classes, release, ClassExports, HostObject, NSZonePtr,
+    SEL,
 };
 use std::borrow::Cow;
-use std::collections::HashMap;
 
 #[derive(Default)]
 pub struct State {
@@ -22,13 +21,23 @@ pub struct State {
 
 #[derive(Clone)]
 struct Observer {
+    /// Unique for each registration, so that registrations removed while a
+    /// notification is being posted can be skipped.
+    registration_id: u64,
     observer: id,
     selector: SEL,
+    /// Only notifications with this name are observed, or any if [None].
+    name: Option<Cow<'static, str>>,
+    /// Only notifications posted by this object are observed, or any if nil.
     object: id,
 }
 
+#[derive(Default)]
 struct NSNotificationCenterHostObject {
-    observers: HashMap<Cow<'static, str>, Vec<Observer>>,
+    /// Registrations in the order they were added. Like in Apple's
+    /// implementation, neither observers nor objects are retained.
+    observers: Vec<Observer>,
+    next_registration_id: u64,
 }
 impl HostObject for NSNotificationCenterHostObject {}
 
@@ -39,9 +48,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation NSNotificationCenter: NSObject
 
 + (id)allocWithZone:(NSZonePtr)_zone {
-    let host_object = Box::new(NSNotificationCenterHostObject {
-        observers: HashMap::new(),
-    });
+    let host_object = Box::<NSNotificationCenterHostObject>::default();
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
@@ -55,23 +62,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
-- (())dealloc {
-    let host_obj = env.objc.borrow_mut::<NSNotificationCenterHostObject>(this);
-    let observers = std::mem::take(&mut host_obj.observers);
-    for observer in observers.values().flatten() {
-        release(env, observer.observer);
-        release(env, observer.object);
-    }
-    env.objc.dealloc_object(this, &mut env.mem);
-}
-
 - (())addObserver:(id)observer
          selector:(SEL)selector
              name:(NSNotificationName)name
            object:(id)object {
-    // TODO: handle case where name is nil
     // Usually a static string, so no real copy will happen
-    let name = ns_string::to_rust_string(env, name);
+    let name = (name != nil).then(|| ns_string::to_rust_string(env, name));
 
     log_dbg!(
         "[(NSNotificationCenter*){:?} addObserver:{:?} selector:{:?} name:{:?} object:{:?}",
@@ -82,25 +78,27 @@ pub const CLASSES: ClassExports = objc_classes! {
         object,
     );
 
-    retain(env, observer);
-    retain(env, object); // TODO: is it correct that this is retained?
-
     let host_obj = env.objc.borrow_mut::<NSNotificationCenterHostObject>(this);
-    host_obj.observers.entry(name).or_default().push(Observer {
+    let registration_id = host_obj.next_registration_id;
+    host_obj.next_registration_id += 1;
+    host_obj.observers.push(Observer {
+        registration_id,
         observer,
         selector,
+        name,
         object,
     });
 }
 
+- (())removeObserver:(id)observer {
+    msg![env; this removeObserver:observer name:nil object:nil]
+}
+
 - (())removeObserver:(id)observer
                 name:(NSNotificationName)name
               object:(id)object {
-    assert!(observer != nil); // TODO
-
-    // TODO: handle case where name is nil
     // Usually a static string, so no real copy will happen
-    let name = ns_string::to_rust_string(env, name);
+    let name = (name != nil).then(|| ns_string::to_rust_string(env, name));
 
     log_dbg!(
         "[(NSNotificationCenter*){:?} removeObserver:{:?} name:{:?} object:{:?}",
@@ -110,28 +108,14 @@ pub const CLASSES: ClassExports = objc_classes! {
         object,
     );
 
+    // A nil name or object means registrations with any name or object are
+    // removed.
     let host_obj = env.objc.borrow_mut::<NSNotificationCenterHostObject>(this);
-    let Some(observers) = host_obj.observers.get_mut(&name) else {
-        return;
-    };
-
-    // TODO: is this the correct behaviour, can an observer be registered
-    // several times?
-    let mut removed_observers = Vec::new();
-
-    let mut i = 0;
-    while i < observers.len() {
-        if observers[i].observer == observer && (object == nil || object == observers[i].object) {
-            removed_observers.push(observers.swap_remove(i));
-        } else {
-            i += 1;
-        }
-    }
-
-    for removed_observer in removed_observers {
-        release(env, removed_observer.observer);
-        release(env, removed_observer.object);
-    }
+    host_obj.observers.retain(|registration| {
+        !(registration.observer == observer
+            && (name.is_none() || registration.name == name)
+            && (object == nil || registration.object == object))
+    });
 }
 
 - (())postNotification:(id)notification {
@@ -149,17 +133,25 @@ pub const CLASSES: ClassExports = objc_classes! {
 
     log_dbg!("Notification is a {:?} posted by {:?}", name, notification_poster);
 
-    let host_obj = env.objc.borrow_mut::<NSNotificationCenterHostObject>(this);
-    let Some(observers) = host_obj.observers.get(&name).cloned() else {
-        return;
-    };
-    for Observer { observer, selector, object } in observers {
-        // The object argument is a filter for which notification sources the
-        // observer is interested in.
-        if object != nil && notification_poster != object {
+    // Observers may add or remove registrations when they receive the
+    // notification, so a copy of the list is used. New registrations don't
+    // receive this notification, but removed ones must be skipped.
+    let observers = env.objc.borrow::<NSNotificationCenterHostObject>(this).observers.clone();
+    for registration in observers {
+        if !matches_notification(&registration, &name, notification_poster) {
+            continue;
+        }
+        if !env
+            .objc
+            .borrow::<NSNotificationCenterHostObject>(this)
+            .observers
+            .iter()
+            .any(|other| other.registration_id == registration.registration_id)
+        {
             continue;
         }
 
+        let Observer { observer, selector, .. } = registration;
         log_dbg!(
             "Notification {:?} observed, sending {:?} message to {:?}",
             notification,
@@ -191,3 +183,9 @@ pub const CLASSES: ClassExports = objc_classes! {
 @end
 
 };
+
+/// Check if a registration's name and object filters match a notification.
+fn matches_notification(registration: &Observer, name: &str, poster: id) -> bool {
+    registration.name.as_deref().map_or(true, |n| n == name)
+        && (registration.object == nil || registration.object == poster)
+}
@@ -0,0 +1,536 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSNumberFormatter`.
+//!
+//! Separators and currency symbols come from the formatter's locale (see
+//! [super::ns_locale]). Format strings (`setPositiveFormat:` etc) are not
+//! supported.
+
+use super::ns_locale::{locale_data, LocaleData};
+use super::{ns_string, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+pub type NSNumberFormatterStyle = NSUInteger;
+pub const NSNumberFormatterNoStyle: NSNumberFormatterStyle = 0;
+pub const NSNumberFormatterDecimalStyle: NSNumberFormatterStyle = 1;
+pub const NSNumberFormatterCurrencyStyle: NSNumberFormatterStyle = 2;
+pub const NSNumberFormatterPercentStyle: NSNumberFormatterStyle = 3;
+
+pub type NSNumberFormatterRoundingMode = NSUInteger;
+pub const NSNumberFormatterRoundCeiling: NSNumberFormatterRoundingMode = 0;
+pub const NSNumberFormatterRoundFloor: NSNumberFormatterRoundingMode = 1;
+pub const NSNumberFormatterRoundDown: NSNumberFormatterRoundingMode = 2;
+pub const NSNumberFormatterRoundUp: NSNumberFormatterRoundingMode = 3;
+pub const NSNumberFormatterRoundHalfEven: NSNumberFormatterRoundingMode = 4;
+pub const NSNumberFormatterRoundHalfDown: NSNumberFormatterRoundingMode = 5;
+pub const NSNumberFormatterRoundHalfUp: NSNumberFormatterRoundingMode = 6;
+
+struct NSNumberFormatterHostObject {
+    number_style: NSNumberFormatterStyle,
+    /// `NSLocale*`, may be [nil], in which case the current locale is used.
+    locale: id,
+    uses_grouping_separator: bool,
+    minimum_integer_digits: NSUInteger,
+    minimum_fraction_digits: NSUInteger,
+    maximum_fraction_digits: NSUInteger,
+    rounding_mode: NSNumberFormatterRoundingMode,
+    /// `NSString*`s overriding the locale's symbols. These may be [nil].
+    decimal_separator: id,
+    grouping_separator: id,
+    currency_symbol: id,
+}
+impl HostObject for NSNumberFormatterHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSNumberFormatter: NSFormatter
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSNumberFormatterHostObject {
+        number_style: NSNumberFormatterNoStyle,
+        locale: nil,
+        uses_grouping_separator: false,
+        minimum_integer_digits: 1,
+        minimum_fraction_digits: 0,
+        maximum_fraction_digits: 0,
+        rounding_mode: NSNumberFormatterRoundHalfEven,
+        decimal_separator: nil,
+        grouping_separator: nil,
+        currency_symbol: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)localizedStringFromNumber:(id)number // NSNumber*
+                    numberStyle:(NSNumberFormatterStyle)style {
+    let formatter: id = msg![env; this new];
+    () = msg![env; formatter setNumberStyle:style];
+    let string: id = msg![env; formatter stringFromNumber:number];
+    release(env, formatter);
+    string
+}
+
+- (())dealloc {
+    let &NSNumberFormatterHostObject {
+        locale,
+        decimal_separator,
+        grouping_separator,
+        currency_symbol,
+        ..
+    } = env.objc.borrow(this);
+    release(env, locale);
+    release(env, decimal_separator);
+    release(env, grouping_separator);
+    release(env, currency_symbol);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())setFormatterBehavior:(NSUInteger)_behavior {
+    // Only the 10.4+ behavior is supported, which is the default anyway.
+}
+
+- (NSNumberFormatterStyle)numberStyle {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).number_style
+}
+- (())setNumberStyle:(NSNumberFormatterStyle)style {
+    let locale = env.objc.borrow::<NSNumberFormatterHostObject>(this).locale;
+    let data = locale_data(env, locale);
+    let (style, grouping, min_fraction, max_fraction) = match style {
+        NSNumberFormatterNoStyle => (style, false, 0, 0),
+        NSNumberFormatterDecimalStyle => (style, true, 0, 3),
+        NSNumberFormatterCurrencyStyle => (
+            style,
+            true,
+            data.currency_fraction_digits,
+            data.currency_fraction_digits,
+        ),
+        NSNumberFormatterPercentStyle => (style, true, 0, 0),
+        // Scientific and spell-out styles aren't supported.
+        _ => {
+            log!(
+                "TODO: NSNumberFormatterStyle {}, using NSNumberFormatterNoStyle instead",
+                style
+            );
+            (NSNumberFormatterNoStyle, false, 0, 0)
+        }
+    };
+    let host_object = env.objc.borrow_mut::<NSNumberFormatterHostObject>(this);
+    host_object.number_style = style;
+    host_object.uses_grouping_separator = grouping;
+    host_object.minimum_fraction_digits = min_fraction;
+    host_object.maximum_fraction_digits = max_fraction;
+}
+
+- (id)locale {
+    let locale = env.objc.borrow::<NSNumberFormatterHostObject>(this).locale;
+    if locale == nil {
+        msg_class![env; NSLocale currentLocale]
+    } else {
+        locale
+    }
+}
+- (())setLocale:(id)locale { // NSLocale*
+    retain(env, locale);
+    let host_object = env.objc.borrow_mut::<NSNumberFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.locale, locale);
+    release(env, old);
+}
+
+- (bool)usesGroupingSeparator {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).uses_grouping_separator
+}
+- (())setUsesGroupingSeparator:(bool)uses {
+    env.objc.borrow_mut::<NSNumberFormatterHostObject>(this).uses_grouping_separator = uses;
+}
+
+- (NSUInteger)minimumIntegerDigits {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).minimum_integer_digits
+}
+- (())setMinimumIntegerDigits:(NSUInteger)digits {
+    env.objc.borrow_mut::<NSNumberFormatterHostObject>(this).minimum_integer_digits = digits;
+}
+- (NSUInteger)minimumFractionDigits {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).minimum_fraction_digits
+}
+- (())setMinimumFractionDigits:(NSUInteger)digits {
+    let host_object = env.objc.borrow_mut::<NSNumberFormatterHostObject>(this);
+    host_object.minimum_fraction_digits = digits;
+    host_object.maximum_fraction_digits = host_object.maximum_fraction_digits.max(digits);
+}
+- (NSUInteger)maximumFractionDigits {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).maximum_fraction_digits
+}
+- (())setMaximumFractionDigits:(NSUInteger)digits {
+    let host_object = env.objc.borrow_mut::<NSNumberFormatterHostObject>(this);
+    host_object.maximum_fraction_digits = digits;
+    host_object.minimum_fraction_digits = host_object.minimum_fraction_digits.min(digits);
+}
+
+- (NSNumberFormatterRoundingMode)roundingMode {
+    env.objc.borrow::<NSNumberFormatterHostObject>(this).rounding_mode
+}
+- (())setRoundingMode:(NSNumberFormatterRoundingMode)mode {
+    assert!(mode <= NSNumberFormatterRoundHalfUp);
+    env.objc.borrow_mut::<NSNumberFormatterHostObject>(this).rounding_mode = mode;
+}
+
+- (id)decimalSeparator {
+    let format = number_format(env, this);
+    let string = ns_string::from_rust_string(env, format.decimal_separator);
+    autorelease(env, string)
+}
+- (())setDecimalSeparator:(id)separator { // NSString*
+    let separator: id = msg![env; separator copy];
+    let host_object = env.objc.borrow_mut::<NSNumberFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.decimal_separator, separator);
+    release(env, old);
+}
+- (id)groupingSeparator {
+    let format = number_format(env, this);
+    let string = ns_string::from_rust_string(env, format.grouping_separator);
+    autorelease(env, string)
+}
+- (())setGroupingSeparator:(id)separator { // NSString*
+    let separator: id = msg![env; separator copy];
+    let host_object = env.objc.borrow_mut::<NSNumberFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.grouping_separator, separator);
+    release(env, old);
+}
+- (id)currencySymbol {
+    let format = number_format(env, this);
+    let string = ns_string::from_rust_string(env, format.currency_symbol);
+    autorelease(env, string)
+}
+- (())setCurrencySymbol:(id)symbol { // NSString*
+    let symbol: id = msg![env; symbol copy];
+    let host_object = env.objc.borrow_mut::<NSNumberFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.currency_symbol, symbol);
+    release(env, old);
+}
+- (id)currencyCode {
+    let locale = env.objc.borrow::<NSNumberFormatterHostObject>(this).locale;
+    let code = locale_data(env, locale).currency_code;
+    ns_string::get_static_str(env, code)
+}
+
+- (id)stringFromNumber:(id)number { // NSNumber*
+    if number == nil {
+        return nil;
+    }
+    let value: f64 = msg![env; number doubleValue];
+    let format = number_format(env, this);
+    let string = format_number(value, &format);
+    let string = ns_string::from_rust_string(env, string);
+    autorelease(env, string)
+}
+
+- (id)numberFromString:(id)string { // NSString*
+    if string == nil {
+        return nil;
+    }
+    let format = number_format(env, this);
+    let string = ns_string::to_rust_string(env, string);
+    let Some(value) = parse_number(&string, &format) else {
+        log_dbg!("Couldn't parse number {:?}", string);
+        return nil;
+    };
+    // Whole numbers are given an integer type, like on a real device.
+    if value.fract() == 0.0 && value.abs() < (1u64 << 53) as f64 {
+        msg_class![env; NSNumber numberWithLongLong:(value as i64)]
+    } else {
+        msg_class![env; NSNumber numberWithDouble:value]
+    }
+}
+
+@end
+
+};
+
+/// The settings of a formatter, after applying its locale.
+#[derive(Debug, Clone)]
+struct NumberFormat {
+    style: NSNumberFormatterStyle,
+    decimal_separator: String,
+    /// This is used even if grouping is off, for `groupingSeparator`.
+    grouping_separator: String,
+    uses_grouping_separator: bool,
+    currency_symbol: String,
+    minimum_integer_digits: usize,
+    minimum_fraction_digits: usize,
+    maximum_fraction_digits: usize,
+    rounding_mode: NSNumberFormatterRoundingMode,
+    locale_data: LocaleData,
+}
+
+fn number_format(env: &mut Environment, formatter: id) -> NumberFormat {
+    let &NSNumberFormatterHostObject {
+        number_style,
+        locale,
+        uses_grouping_separator,
+        minimum_integer_digits,
+        minimum_fraction_digits,
+        maximum_fraction_digits,
+        rounding_mode,
+        decimal_separator,
+        grouping_separator,
+        currency_symbol,
+    } = env.objc.borrow(formatter);
+    let data = locale_data(env, locale);
+    let mut symbol = |string: id, default: &str| {
+        if string == nil {
+            default.to_string()
+        } else {
+            ns_string::to_rust_string(env, string).into_owned()
+        }
+    };
+    NumberFormat {
+        style: number_style,
+        decimal_separator: symbol(decimal_separator, data.decimal_separator),
+        grouping_separator: symbol(grouping_separator, data.grouping_separator),
+        uses_grouping_separator,
+        currency_symbol: symbol(currency_symbol, data.currency_symbol),
+        minimum_integer_digits: minimum_integer_digits as usize,
+        minimum_fraction_digits: minimum_fraction_digits as usize,
+        maximum_fraction_digits: maximum_fraction_digits as usize,
+        rounding_mode,
+        locale_data: data,
+    }
+}
+
+/// Get the text that goes around the number for a style, e.g. a currency
+/// symbol.
+fn affixes(format: &NumberFormat) -> (String, String) {
+    let pattern = match format.style {
+        NSNumberFormatterCurrencyStyle => format.locale_data.currency_pattern,
+        NSNumberFormatterPercentStyle => format.locale_data.percent_pattern,
+        _ => "#",
+    };
+    let pattern = pattern.replace('¤', &format.currency_symbol);
+    let (prefix, suffix) = pattern.split_once('#').unwrap();
+    (prefix.to_string(), suffix.to_string())
+}
+
+/// Round a non-negative decimal number, given as its integer and fractional
+/// digits, to at most `max_fraction_digits` fractional digits.
+fn round_digits(
+    integer: &str,
+    fraction: &str,
+    max_fraction_digits: usize,
+    mode: NSNumberFormatterRoundingMode,
+    negative: bool,
+) -> (String, String) {
+    if fraction.len() <= max_fraction_digits {
+        return (integer.to_string(), fraction.to_string());
+    }
+    let (kept, dropped) = fraction.split_at(max_fraction_digits);
+    let first_dropped = dropped.as_bytes()[0] - b'0';
+    let rest_nonzero = dropped[1..].bytes().any(|b| b != b'0');
+    let dropped_nonzero = first_dropped != 0 || rest_nonzero;
+    let last_kept = kept.bytes().last().or_else(|| integer.bytes().last());
+    let last_kept_odd = matches!(last_kept, Some(b) if (b - b'0') % 2 == 1);
+    let round_up = match mode {
+        NSNumberFormatterRoundCeiling => !negative && dropped_nonzero,
+        NSNumberFormatterRoundFloor => negative && dropped_nonzero,
+        NSNumberFormatterRoundDown => false,
+        NSNumberFormatterRoundUp => dropped_nonzero,
+        NSNumberFormatterRoundHalfEven => {
+            first_dropped > 5 || (first_dropped == 5 && (rest_nonzero || last_kept_odd))
+        }
+        NSNumberFormatterRoundHalfDown => first_dropped > 5 || (first_dropped == 5 && rest_nonzero),
+        NSNumberFormatterRoundHalfUp => first_dropped >= 5,
+        _ => unreachable!(),
+    };
+
+    let mut digits: Vec<u8> = integer.bytes().chain(kept.bytes()).collect();
+    if round_up {
+        let mut i = digits.len();
+        loop {
+            if i == 0 {
+                digits.insert(0, b'1');
+                break;
+            }
+            i -= 1;
+            if digits[i] == b'9' {
+                digits[i] = b'0';
+            } else {
+                digits[i] += 1;
+                break;
+            }
+        }
+    }
+    let split = digits.len() - kept.len();
+    let digits = String::from_utf8(digits).unwrap();
+    let (integer, fraction) = digits.split_at(split);
+    (integer.to_string(), fraction.to_string())
+}
+
+/// Format a number like a formatter with these settings would.
+fn format_number(value: f64, format: &NumberFormat) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    let negative = value.is_sign_negative();
+    let (prefix, suffix) = affixes(format);
+    if value.is_infinite() {
+        let sign = if negative { "-" } else { "" };
+        return format!("{}{}\u{221e}{}", sign, prefix, suffix);
+    }
+
+    let value = if format.style == NSNumberFormatterPercentStyle {
+        value * 100.0
+    } else {
+        value
+    };
+    // Rust prints the shortest decimal representation that round-trips, which
+    // is what a human would expect to be rounded, e.g. 2.675 is rounded as if
+    // it were exactly 2.675, not 2.67499999999999982236431605997495353221893.
+    let digits = format!("{}", value.abs());
+    let (integer, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    let (integer, fraction) = round_digits(
+        integer,
+        fraction,
+        format.maximum_fraction_digits,
+        format.rounding_mode,
+        negative,
+    );
+    let fraction = fraction.trim_end_matches('0');
+    let fraction = format!(
+        "{:0<1$}",
+        fraction,
+        format.minimum_fraction_digits.max(fraction.len())
+    );
+    let integer = integer.trim_start_matches('0');
+    let integer = format!("{:0>1$}", integer, format.minimum_integer_digits);
+
+    let mut result = String::new();
+    // If everything was rounded away, there's no point in a minus sign.
+    let is_zero = integer.bytes().chain(fraction.bytes()).all(|b| b == b'0');
+    if negative && !is_zero {
+        result.push('-');
+    }
+    result.push_str(&prefix);
+    for (i, digit) in integer.chars().enumerate() {
+        if format.uses_grouping_separator && i != 0 && (integer.len() - i) % 3 == 0 {
+            result.push_str(&format.grouping_separator);
+        }
+        result.push(digit);
+    }
+    if !fraction.is_empty() {
+        result.push_str(&format.decimal_separator);
+        result.push_str(&fraction);
+    }
+    result.push_str(&suffix);
+    result
+}
+
+/// Parse a number formatted like a formatter with these settings would format
+/// it. The grouping separators are optional.
+fn parse_number(string: &str, format: &NumberFormat) -> Option<f64> {
+    let (prefix, suffix) = affixes(format);
+    let (negative, string) = match string.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, string),
+    };
+    // Spaces around the symbols are optional, since they are easy to get
+    // wrong when typing.
+    let string = string.strip_prefix(prefix.trim_end_matches('\u{a0}'))?;
+    let string = string.trim_start_matches('\u{a0}');
+    let string = string.strip_suffix(suffix.trim_start_matches('\u{a0}'))?;
+    let string = string.trim_end_matches('\u{a0}');
+    if string.is_empty() {
+        return None;
+    }
+
+    let mut number = String::with_capacity(string.len());
+    let mut rest = string;
+    let mut seen_decimal_separator = false;
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            rest = &rest[1..];
+        } else if !seen_decimal_separator && rest.starts_with(&format.decimal_separator) {
+            seen_decimal_separator = true;
+            number.push('.');
+            rest = &rest[format.decimal_separator.len()..];
+        } else if !seen_decimal_separator && rest.starts_with(&format.grouping_separator) {
+            rest = &rest[format.grouping_separator.len()..];
+        } else {
+            return None;
+        }
+    }
+    let value: f64 = number.parse().ok()?;
+    let value = if format.style == NSNumberFormatterPercentStyle {
+        value / 100.0
+    } else {
+        value
+    };
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+#[test]
+fn test_format_and_parse_number() {
+    let format_for = |style, identifier| {
+        let data = LocaleData::for_identifier(identifier);
+        let (min, max) = match style {
+            NSNumberFormatterDecimalStyle => (0, 3),
+            NSNumberFormatterCurrencyStyle => (2, 2),
+            _ => (0, 0),
+        };
+        NumberFormat {
+            style,
+            decimal_separator: data.decimal_separator.to_string(),
+            grouping_separator: data.grouping_separator.to_string(),
+            uses_grouping_separator: style != NSNumberFormatterNoStyle,
+            currency_symbol: data.currency_symbol.to_string(),
+            minimum_integer_digits: 1,
+            minimum_fraction_digits: min,
+            maximum_fraction_digits: max,
+            rounding_mode: NSNumberFormatterRoundHalfEven,
+            locale_data: data,
+        }
+    };
+
+    let decimal = format_for(NSNumberFormatterDecimalStyle, "en_US");
+    assert_eq!(format_number(1234567.891, &decimal), "1,234,567.891");
+    assert_eq!(format_number(-1234.5, &decimal), "-1,234.5");
+    assert_eq!(format_number(0.0, &decimal), "0");
+    assert_eq!(format_number(-0.0001, &decimal), "0");
+    assert_eq!(format_number(0.0005, &decimal), "0");
+    assert_eq!(format_number(0.0015, &decimal), "0.002");
+    assert_eq!(format_number(999.9999, &decimal), "1,000");
+
+    let currency = format_for(NSNumberFormatterCurrencyStyle, "en_US");
+    assert_eq!(format_number(1234.5, &currency), "$1,234.50");
+    assert_eq!(format_number(-2.675, &currency), "-$2.68");
+    assert_eq!(format_number(0.0, &currency), "$0.00");
+    let euro = format_for(NSNumberFormatterCurrencyStyle, "fr_FR");
+    assert_eq!(format_number(1234.5, &euro), "1\u{a0}234,50\u{a0}€");
+
+    let percent = format_for(NSNumberFormatterPercentStyle, "en_US");
+    assert_eq!(format_number(0.256, &percent), "26%");
+    assert_eq!(format_number(0.07, &percent), "7%");
+
+    let plain = format_for(NSNumberFormatterNoStyle, "en_US");
+    assert_eq!(format_number(1234.5, &plain), "1234");
+    assert_eq!(format_number(1235.5, &plain), "1236");
+
+    assert_eq!(parse_number("1,234.5", &decimal), Some(1234.5));
+    assert_eq!(parse_number("-1234", &decimal), Some(-1234.0));
+    assert_eq!(parse_number("1.2.3", &decimal), None);
+    assert_eq!(parse_number("abc", &decimal), None);
+    assert_eq!(parse_number("$1,234.50", &currency), Some(1234.5));
+    assert_eq!(parse_number("1234.50", &currency), None);
+    assert_eq!(parse_number("1\u{a0}234,50 €", &euro), None);
+    assert_eq!(parse_number("1\u{a0}234,50\u{a0}€", &euro), Some(1234.5));
+    assert_eq!(parse_number("26%", &percent), Some(0.26));
+}
@@ -14,12 +14,15 @@
 //!
 //! See also: [crate::objc], especially the `objects` module.
 
+use super::ns_run_loop::NSDefaultRunLoopMode;
 use super::ns_string::to_rust_string;
-use super::NSUInteger;
+use super::{ns_method_signature, ns_run_loop, ns_string, ns_thread, ns_timer};
+use super::{NSTimeInterval, NSUInteger};
 use crate::mem::MutVoidPtr;
 use crate::objc::{
-    id, msg, msg_class, msg_send, objc_classes, Class, ClassExports, NSZonePtr, ObjC,
-    TrivialHostObject, SEL,
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release,
+    release_orphaned_associated_objects, Class, ClassExports, NSZonePtr, ObjC, TrivialHostObject,
+    SEL,
 };
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -60,6 +63,29 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.class_has_method(this, selector)
 }
 
++ (bool)conformsToProtocol:(id)protocol { // Protocol*
+    env.objc.class_conforms_to_protocol(this, protocol, &env.mem)
+}
+
++ (id)instanceMethodSignatureForSelector:(SEL)selector {
+    if !env.objc.class_has_method(this, selector) {
+        return nil;
+    }
+    let signature = ns_method_signature::new_for_selector(env, selector);
+    autorelease(env, signature)
+}
+
++ (())cancelPreviousPerformRequestsWithTarget:(id)target {
+    let run_loop: id = msg_class![env; NSRunLoop mainRunLoop];
+    ns_run_loop::cancel_perform_requests(env, run_loop, target, None);
+}
++ (())cancelPreviousPerformRequestsWithTarget:(id)target
+                                     selector:(SEL)selector
+                                       object:(id)object {
+    let run_loop: id = msg_class![env; NSRunLoop mainRunLoop];
+    ns_run_loop::cancel_perform_requests(env, run_loop, target, Some((selector, object)));
+}
+
 - (id)init {
     this
 }
@@ -74,6 +100,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     log_dbg!("[{:?} release]", this);
     if env.objc.decrement_refcount(this) {
         () = msg![env; this dealloc];
+        release_orphaned_associated_objects(env);
     }
 }
 - (id)autorelease {
@@ -115,6 +142,10 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (id)copy {
     msg![env; this copyWithZone:(MutVoidPtr::null())]
 }
+// Helper for NSMutableCopying
+- (id)mutableCopy {
+    msg![env; this mutableCopyWithZone:(MutVoidPtr::null())]
+}
 
 
 // NSKeyValueCoding
@@ -149,8 +180,66 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (bool)respondsToSelector:(SEL)selector {
+    // If the receiver is a class, this gets its metaclass, so class methods
+    // are checked.
+    env.objc.object_has_method(&env.mem, this, selector)
+}
+
+- (bool)conformsToProtocol:(id)protocol { // Protocol*
     let class = msg![env; this class];
-    env.objc.class_has_method(class, selector)
+    env.objc.class_conforms_to_protocol(class, protocol, &env.mem)
+}
+
+- (id)methodSignatureForSelector:(SEL)selector {
+    if !env.objc.object_has_method(&env.mem, this, selector) {
+        return nil;
+    }
+    let signature = ns_method_signature::new_for_selector(env, selector);
+    autorelease(env, signature)
+}
+
+- (())performSelector:(SEL)selector
+             withObject:(id)object
+             afterDelay:(NSTimeInterval)delay {
+    let timer = ns_timer::new_for_perform_request(env, delay, this, selector, object);
+    let run_loop: id = msg_class![env; NSRunLoop currentRunLoop];
+    let mode: id = ns_string::get_static_str(env, NSDefaultRunLoopMode);
+    let _: () = msg![env; run_loop addTimer:timer forMode:mode];
+    // The run loop now owns the timer.
+    release(env, timer);
+}
+
+- (())performSelectorInBackground:(SEL)selector
+                       withObject:(id)object {
+    msg_class![env; NSThread detachNewThreadSelector:selector
+                                            toTarget:this
+                                          withObject:object]
+}
+
+- (())performSelectorOnMainThread:(SEL)selector
+                       withObject:(id)object
+                    waitUntilDone:(bool)wait {
+    ns_thread::perform_selector_on_main_thread(env, this, selector, object, wait)
+}
+
+// Message forwarding. The runtime uses these when an object doesn't respond to
+// a selector (see objc_msgSend_inner).
+- (id)forwardingTargetForSelector:(SEL)_selector {
+    nil
+}
+- (())forwardInvocation:(id)invocation { // NSInvocation*
+    let selector: SEL = msg![env; invocation selector];
+    msg![env; this doesNotRecognizeSelector:selector]
+}
+- (())doesNotRecognizeSelector:(SEL)selector {
+    // TODO: raise NSInvalidArgumentException instead
+    let class = ObjC::read_isa(this, &env.mem);
+    panic!(
+        "-[{} {}]: unrecognized selector sent to instance {:?}",
+        env.objc.get_class_name(class),
+        selector.as_str(&env.mem),
+        this,
+    );
 }
 
 
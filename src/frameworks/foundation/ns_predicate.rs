@@ -0,0 +1,43 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSPredicate`.
+//!
+//! Only constant predicates are supported so far. Apps can still subclass
+//! `NSPredicate` and override `evaluateWithObject:`.
+
+use crate::objc::{autorelease, id, msg, objc_classes, ClassExports, HostObject, NSZonePtr};
+
+struct NSPredicateHostObject {
+    /// TODO: support predicates other than constant ones, e.g. via
+    /// `predicateWithFormat:`.
+    value: bool,
+}
+impl HostObject for NSPredicateHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSPredicate: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSPredicateHostObject { value: false });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)predicateWithValue:(bool)value {
+    let new: id = msg![env; this alloc];
+    env.objc.borrow_mut::<NSPredicateHostObject>(new).value = value;
+    autorelease(env, new)
+}
+
+- (bool)evaluateWithObject:(id)_object {
+    env.objc.borrow::<NSPredicateHostObject>(this).value
+}
+
+@end
+
+};
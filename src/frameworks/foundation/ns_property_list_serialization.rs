@@ -1,19 +1,198 @@
 //! `NSPropertyListSerialization`.
+//!
+//! The actual parsing and writing of property lists, in both the XML and
+//! binary (`bplist00`) formats, is done by the `plist` crate. This module
+//! converts between its representation and Foundation objects.
 
-use super::{ns_array, ns_dictionary, ns_string, NSUInteger};
+use super::ns_error::{
+    set_error, NSCocoaErrorDomain, NSPropertyListReadCorruptError, NSPropertyListWriteStreamError,
+};
+use super::ns_value::{number_value, NSNumberHostObject};
+use super::{ns_array, ns_data, ns_date, ns_dictionary, ns_string, NSTimeInterval, NSUInteger};
 use crate::fs::GuestPath;
 use crate::mem::MutPtr;
-use crate::objc::{id, msg, msg_class, nil, release};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, Class, ClassExports,
+};
 use crate::Environment;
 use plist::Value;
 use std::io::Cursor;
 
-// TODO: Implement reading of property lists other than Info.plist.
-// [NSDictionary contentsOfFile:] and [NSArray contentsOfFile:] in particular.
+pub type NSPropertyListFormat = NSUInteger;
+pub const NSPropertyListOpenStepFormat: NSPropertyListFormat = 1;
+pub const NSPropertyListXMLFormat_v1_0: NSPropertyListFormat = 100;
+pub const NSPropertyListBinaryFormat_v1_0: NSPropertyListFormat = 200;
+
+pub type NSPropertyListMutabilityOptions = NSUInteger;
+pub const NSPropertyListImmutable: NSPropertyListMutabilityOptions = 0;
+pub const NSPropertyListMutableContainers: NSPropertyListMutabilityOptions = 1;
+pub const NSPropertyListMutableContainersAndLeaves: NSPropertyListMutabilityOptions = 2;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSPropertyListSerialization: NSObject
+
++ (id)propertyListFromData:(id)data // NSData*
+          mutabilityOption:(NSPropertyListMutabilityOptions)options
+                    format:(MutPtr<NSPropertyListFormat>)format
+          errorDescription:(MutPtr<id>)error_string { // NSString**
+    let res = deserialize_plist_from_data(env, data, options, format);
+    if res.is_none() && !error_string.is_null() {
+        // The caller is responsible for releasing this string.
+        let description = ns_string::from_rust_string(
+            env,
+            "Couldn't parse property list".to_string()
+        );
+        env.mem.write(error_string, description);
+    }
+    res.map_or(nil, |res| autorelease(env, res))
+}
++ (id)propertyListWithData:(id)data // NSData*
+                   options:(NSPropertyListMutabilityOptions)options
+                    format:(MutPtr<NSPropertyListFormat>)format
+                     error:(MutPtr<id>)error { // NSError**
+    let res = deserialize_plist_from_data(env, data, options, format);
+    if res.is_none() {
+        set_error(
+            env,
+            error,
+            NSCocoaErrorDomain,
+            NSPropertyListReadCorruptError,
+            Some("Couldn't parse property list".to_string())
+        );
+    }
+    res.map_or(nil, |res| autorelease(env, res))
+}
+
++ (id)dataFromPropertyList:(id)plist
+                    format:(NSPropertyListFormat)format
+          errorDescription:(MutPtr<id>)error_string { // NSString**
+    let res = serialize_plist_to_data(env, plist, format);
+    if res == nil && !error_string.is_null() {
+        // The caller is responsible for releasing this string.
+        let description = ns_string::from_rust_string(
+            env,
+            "Couldn't serialize property list".to_string()
+        );
+        env.mem.write(error_string, description);
+    }
+    res
+}
++ (id)dataWithPropertyList:(id)plist
+                    format:(NSPropertyListFormat)format
+                   options:(NSUInteger)_options // reserved
+                     error:(MutPtr<id>)error { // NSError**
+    let res = serialize_plist_to_data(env, plist, format);
+    if res == nil {
+        set_error(
+            env,
+            error,
+            NSCocoaErrorDomain,
+            NSPropertyListWriteStreamError,
+            Some("Couldn't serialize property list".to_string())
+        );
+    }
+    res
+}
+
++ (bool)propertyList:(id)plist
+    isValidForFormat:(NSPropertyListFormat)format {
+    format != NSPropertyListOpenStepFormat && serialize_plist(env, plist).is_some()
+}
+
+@end
+
+};
+
+/// Guess the format of a serialized property list from its first bytes.
+fn detect_format(bytes: &[u8]) -> NSPropertyListFormat {
+    if bytes.starts_with(b"bplist") {
+        return NSPropertyListBinaryFormat_v1_0;
+    }
+    let text = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match text.iter().find(|byte| !byte.is_ascii_whitespace()) {
+        Some(b'<') => NSPropertyListXMLFormat_v1_0,
+        _ => NSPropertyListOpenStepFormat,
+    }
+}
+
+/// Internals of the `propertyList...` methods. The result is not autoreleased.
+fn deserialize_plist_from_data(
+    env: &mut Environment,
+    data: id,
+    options: NSPropertyListMutabilityOptions,
+    format: MutPtr<NSPropertyListFormat>,
+) -> Option<id> {
+    let bytes = ns_data::to_rust_slice(env, data);
+    let detected_format = detect_format(bytes);
+    let Ok(root) = Value::from_reader(Cursor::new(bytes)) else {
+        log!(
+            "Warning: couldn't parse plist from {:?}, returning nil.",
+            data
+        );
+        return None;
+    };
+    if !format.is_null() {
+        env.mem.write(format, detected_format);
+    }
+    let mutable = match options {
+        NSPropertyListImmutable => false,
+        NSPropertyListMutableContainers => true,
+        NSPropertyListMutableContainersAndLeaves => {
+            log!("TODO: mutable property list leaves, only containers will be mutable");
+            true
+        }
+        _ => {
+            log!(
+                "Warning: Unknown NSPropertyListMutabilityOptions {}, using immutable",
+                options
+            );
+            false
+        }
+    };
+    Some(deserialize_plist(env, &root, mutable))
+}
+
+/// Internals of the `dataFromPropertyList:` methods. The result is
+/// autoreleased, or `nil` on failure.
+fn serialize_plist_to_data(env: &mut Environment, plist: id, format: NSPropertyListFormat) -> id {
+    let Some(root) = serialize_plist(env, plist) else {
+        log!(
+            "Warning: couldn't serialize plist {:?}, returning nil.",
+            plist
+        );
+        return nil;
+    };
+    let mut bytes = Vec::new();
+    let res = match format {
+        NSPropertyListXMLFormat_v1_0 => root.to_writer_xml(&mut bytes),
+        NSPropertyListBinaryFormat_v1_0 => root.to_writer_binary(&mut bytes),
+        // Apple's implementation doesn't support writing this format either.
+        NSPropertyListOpenStepFormat => return nil,
+        _ => {
+            log!(
+                "Warning: Unknown NSPropertyListFormat {}, returning nil.",
+                format
+            );
+            return nil;
+        }
+    };
+    if res.is_err() {
+        log!(
+            "Warning: couldn't serialize plist {:?}, returning nil.",
+            plist
+        );
+        return nil;
+    }
+    let data = new_data(env, &bytes);
+    autorelease(env, data)
+}
 
 /// Internals of `initWithContentsOfFile:` on `NSArray` and `NSDictionary`.
 /// Returns `nil` on failure.
-pub(super) fn deserialize_plist_from_file(
+pub fn deserialize_plist_from_file(
     env: &mut Environment,
     path: &GuestPath,
     array_expected: bool,
@@ -38,17 +217,27 @@ pub(super) fn deserialize_plist_from_file(
         return nil;
     }
 
-    deserialize_plist(env, &root)
+    deserialize_plist(env, &root, /* mutable: */ false)
 }
 
-fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
+/// Convert a property list to Foundation objects. If `mutable` is [true],
+/// arrays and dictionaries will be mutable.
+pub(super) fn deserialize_plist(env: &mut Environment, value: &Value, mutable: bool) -> id {
     match value {
         Value::Array(array) => {
-            let array = array
+            let array: Vec<id> = array
                 .iter()
-                .map(|value| deserialize_plist(env, value))
+                .map(|value| deserialize_plist(env, value, mutable))
                 .collect();
-            ns_array::from_vec(env, array)
+            if !mutable {
+                return ns_array::from_vec(env, array);
+            }
+            let ns_array: id = msg_class![env; NSMutableArray new];
+            for object in array {
+                () = msg![env; ns_array addObject:object];
+                release(env, object);
+            }
+            ns_array
         }
         Value::Dictionary(dict) => {
             let pairs: Vec<_> = dict
@@ -56,13 +245,21 @@ fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
                 .map(|(key, value)| {
                     (
                         ns_string::from_rust_string(env, key.clone()),
-                        deserialize_plist(env, value),
+                        deserialize_plist(env, value, mutable),
                     )
                 })
                 .collect();
             // Unlike ns_array::from_vec and ns_string::from_rust_string,
-            // this will retain the keys and values!
-            let ns_dict = ns_dictionary::dict_from_keys_and_objects(env, &pairs);
+            // these will retain the keys and values!
+            let ns_dict = if mutable {
+                let ns_dict: id = msg_class![env; NSMutableDictionary new];
+                for &(key, value) in &pairs {
+                    () = msg![env; ns_dict setObject:value forKey:key];
+                }
+                ns_dict
+            } else {
+                ns_dictionary::dict_from_keys_and_objects(env, &pairs)
+            };
             // ...so they need to be released.
             for (key, value) in pairs {
                 release(env, key);
@@ -75,15 +272,11 @@ fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
             let b: bool = *b;
             msg![env; number initWithBool:b]
         }
-        Value::Data(d) => {
-            let length: NSUInteger = d.len().try_into().unwrap();
-            let alloc: MutPtr<u8> = env.mem.alloc(length).cast();
-            env.mem.bytes_at_mut(alloc, length).copy_from_slice(d);
-            let data: id = msg_class![env; NSData alloc];
-            msg![env; data initWithBytesNoCopy:alloc length:length]
-        }
-        Value::Date(_) => {
-            todo!("deserialize plist value: {:?}", value); // TODO
+        Value::Data(d) => new_data(env, d),
+        Value::Date(date) => {
+            let time = std::time::SystemTime::from(*date);
+            let interval = ns_date::system_time_since_reference_date(time);
+            ns_date::from_time_interval_since_reference_date(env, interval)
         }
         Value::Integer(int) => {
             let number: id = msg_class![env; NSNumber alloc];
@@ -114,3 +307,101 @@ fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
         }
     }
 }
+
+/// Create a new `NSData` with a copy of some bytes. The result is not
+/// autoreleased.
+pub(super) fn new_data(env: &mut Environment, bytes: &[u8]) -> id {
+    let length: NSUInteger = bytes.len().try_into().unwrap();
+    let alloc: MutPtr<u8> = env.mem.alloc(length).cast();
+    env.mem.bytes_at_mut(alloc, length).copy_from_slice(bytes);
+    let data: id = msg_class![env; NSData alloc];
+    msg![env; data initWithBytesNoCopy:alloc length:length]
+}
+
+/// Write a property list (made of `NSDictionary`, `NSArray`, `NSString`,
+/// `NSNumber`, `NSData` and `NSDate` objects) to a file in XML format. Returns
+/// [false] on failure.
+pub fn serialize_plist_to_file(env: &mut Environment, path: &GuestPath, root: id) -> bool {
+    log_dbg!("Writing plist to {:?}.", path);
+    let Some(root) = serialize_plist(env, root) else {
+        log!("Warning: couldn't serialize plist for {:?}", path);
+        return false;
+    };
+    let mut bytes = Vec::new();
+    if root.to_writer_xml(&mut bytes).is_err() {
+        log!("Warning: couldn't serialize plist for {:?}", path);
+        return false;
+    }
+    if env.fs.write(path, &bytes).is_err() {
+        log!("Warning: couldn't write plist to {:?}", path);
+        return false;
+    }
+    true
+}
+
+/// Returns [None] if the object isn't a property list object.
+fn serialize_plist(env: &mut Environment, object: id) -> Option<Value> {
+    if is_kind_of(env, object, "NSString") {
+        Some(Value::String(
+            ns_string::to_rust_string(env, object).into_owned(),
+        ))
+    } else if is_kind_of(env, object, "NSNumber") {
+        Some(match number_value(env, object) {
+            NSNumberHostObject::Bool(b) => Value::Boolean(b),
+            NSNumberHostObject::LongLong(int) => Value::Integer(int.into()),
+            NSNumberHostObject::UnsignedLongLong(int) => Value::Integer(int.into()),
+            NSNumberHostObject::Double(real) => Value::Real(real),
+        })
+    } else if is_kind_of(env, object, "NSData") {
+        Some(Value::Data(ns_data::to_rust_slice(env, object).to_vec()))
+    } else if is_kind_of(env, object, "NSDate") {
+        let interval: NSTimeInterval = msg![env; object timeIntervalSinceReferenceDate];
+        let time = ns_date::time_interval_since_reference_date_to_system_time(interval);
+        Some(Value::Date(time.into()))
+    } else if is_kind_of(env, object, "NSArray") {
+        let count: NSUInteger = msg![env; object count];
+        let mut array = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let element: id = msg![env; object objectAtIndex:i];
+            array.push(serialize_plist(env, element)?);
+        }
+        Some(Value::Array(array))
+    } else if is_kind_of(env, object, "NSDictionary") {
+        let mut dict = plist::Dictionary::new();
+        for (key, value) in ns_dictionary::keys_and_objects(env, object) {
+            // Property list keys must be strings.
+            if !is_kind_of(env, key, "NSString") {
+                return None;
+            }
+            let key = ns_string::to_rust_string(env, key).into_owned();
+            dict.insert(key, serialize_plist(env, value)?);
+        }
+        Some(Value::Dictionary(dict))
+    } else {
+        None
+    }
+}
+
+fn is_kind_of(env: &mut Environment, object: id, class_name: &str) -> bool {
+    let class: Class = env.objc.get_known_class(class_name, &mut env.mem);
+    msg![env; object isKindOfClass:class]
+}
+
+#[cfg(test)]
+#[test]
+fn test_detect_format() {
+    assert_eq!(
+        detect_format(b"bplist00\xd1\x01\x02"),
+        NSPropertyListBinaryFormat_v1_0
+    );
+    assert_eq!(
+        detect_format(b"<?xml version=\"1.0\"?>"),
+        NSPropertyListXMLFormat_v1_0
+    );
+    assert_eq!(
+        detect_format(b"\xEF\xBB\xBF\n  <plist>"),
+        NSPropertyListXMLFormat_v1_0
+    );
+    assert_eq!(detect_format(b"{ a = b; }"), NSPropertyListOpenStepFormat);
+    assert_eq!(detect_format(b""), NSPropertyListOpenStepFormat);
+}
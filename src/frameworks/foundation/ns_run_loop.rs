@@ -8,15 +8,18 @@
 //! Resources:
 //! - Apple's [Threading Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Multithreading/Introduction/Introduction.html)
 
-use super::{ns_string, ns_timer};
+use super::{ns_string, ns_timer, ns_url_connection};
 use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::audio_toolbox::audio_queue::{handle_audio_queue, AudioQueueRef};
+use crate::frameworks::core_animation::ca_display_link;
 use crate::frameworks::core_foundation::cf_run_loop::{
-    kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoopRef,
+    self, kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoopRef, CFRunLoopSourceRef,
 };
 use crate::frameworks::{core_animation, media_player, uikit};
-use crate::objc::{id, msg, objc_classes, release, retain, ClassExports, HostObject};
-use crate::Environment;
+use crate::libc::dispatch;
+use crate::objc::{id, msg, objc_classes, release, retain, ClassExports, HostObject, SEL};
+use crate::{Environment, ThreadId};
+use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
 /// `NSString*`
@@ -39,6 +42,9 @@ pub const CONSTANTS: ConstantExports = &[
 #[derive(Default)]
 pub struct State {
     main_thread_run_loop: Option<id>,
+    /// Run loops for threads other than the main thread, created on demand.
+    /// TODO: release these when their thread exits
+    secondary_thread_run_loops: HashMap<ThreadId, id>,
 }
 
 struct NSRunLoopHostObject {
@@ -48,6 +54,16 @@ struct NSRunLoopHostObject {
     /// Strong references to `NSTimer*` in no particular order. Timers are owned
     /// by the run loop. The timer must remove itself when invalidated.
     timers: Vec<id>,
+    /// Strong references to `CFRunLoopSourceRef`s in no particular order.
+    sources: Vec<CFRunLoopSourceRef>,
+    /// Strong references to started `NSURLConnection*`s in no particular
+    /// order. The connection must remove itself when finished or cancelled.
+    url_connections: Vec<id>,
+    /// Strong references to `CADisplayLink*`s in no particular order. The
+    /// display link must remove itself when invalidated.
+    display_links: Vec<id>,
+    /// Set by `CFRunLoopStop`.
+    stop_requested: bool,
 }
 impl HostObject for NSRunLoopHostObject {}
 
@@ -61,19 +77,30 @@ pub const CLASSES: ClassExports = objc_classes! {
     if let Some(rl) = env.framework_state.foundation.ns_run_loop.main_thread_run_loop {
         rl
     } else {
-        let host_object = Box::new(NSRunLoopHostObject {
-            audio_queues: Vec::new(),
-            timers: Vec::new(),
-        });
-        let new = env.objc.alloc_static_object(this, host_object, &mut env.mem);
+        let new = new_run_loop(env);
         env.framework_state.foundation.ns_run_loop.main_thread_run_loop = Some(new);
         new
     }
 }
 
 + (id)currentRunLoop {
-    assert!(env.current_thread == 0);
-    msg![env; this mainRunLoop]
+    let current_thread = env.current_thread;
+    if current_thread == 0 {
+        return msg![env; this mainRunLoop];
+    }
+    let run_loops = &env.framework_state.foundation.ns_run_loop.secondary_thread_run_loops;
+    if let Some(&rl) = run_loops.get(&current_thread) {
+        rl
+    } else {
+        let new = new_run_loop(env);
+        log_dbg!("Created run loop {:?} for thread {}", new, current_thread);
+        env.framework_state
+            .foundation
+            .ns_run_loop
+            .secondary_thread_run_loops
+            .insert(current_thread, new);
+        new
+    }
 }
 
 // TODO: more accessors
@@ -90,8 +117,12 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())addTimer:(id)timer // NSTimer*
        forMode:(NSRunLoopMode)mode {
     let default_mode = ns_string::get_static_str(env, NSDefaultRunLoopMode);
+    let common_modes = ns_string::get_static_str(env, NSRunLoopCommonModes);
     // TODO: handle other modes
-    assert!(msg![env; mode isEqualToString:default_mode]);
+    assert!(
+        msg![env; mode isEqualToString:default_mode] ||
+        msg![env; mode isEqualToString:common_modes]
+    );
 
     log_dbg!("Adding timer {:?} to run loop {:?}", timer, this);
 
@@ -112,6 +143,20 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+fn new_run_loop(env: &mut Environment) -> id {
+    let host_object = Box::new(NSRunLoopHostObject {
+        audio_queues: Vec::new(),
+        timers: Vec::new(),
+        sources: Vec::new(),
+        url_connections: Vec::new(),
+        display_links: Vec::new(),
+        stop_requested: false,
+    });
+    let class = env.objc.get_known_class("NSRunLoop", &mut env.mem);
+    env.objc
+        .alloc_static_object(class, host_object, &mut env.mem)
+}
+
 /// For use by Audio Toolbox.
 /// TODO: Maybe replace this with a `CFRunLoopObserver` or some other generic
 /// mechanism?
@@ -133,6 +178,82 @@ pub fn remove_audio_queue(env: &mut Environment, run_loop: id, queue: AudioQueue
     queues.remove(queue_idx);
 }
 
+/// For use by `CFRunLoopAddSource`. Returns [false] if the source was already
+/// added.
+/// TODO: Handle run loop modes. Currently assumes the common modes.
+pub fn add_source(env: &mut Environment, run_loop: id, source: CFRunLoopSourceRef) -> bool {
+    let sources = &mut env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop).sources;
+    if sources.contains(&source) {
+        return false;
+    }
+    sources.push(source);
+    retain(env, source);
+    true
+}
+
+/// For use by `CFRunLoopRemoveSource`. Returns [false] if the source wasn't
+/// added.
+pub fn remove_source(env: &mut Environment, run_loop: id, source: CFRunLoopSourceRef) -> bool {
+    let sources = &mut env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop).sources;
+    let Some(source_idx) = sources.iter().position(|&item| item == source) else {
+        return false;
+    };
+    sources.swap_remove(source_idx);
+    release(env, source);
+    true
+}
+
+/// For use by `NSURLConnection`, once it has started.
+pub(super) fn add_url_connection(env: &mut Environment, run_loop: id, connection: id) {
+    retain(env, connection);
+    env.objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .url_connections
+        .push(connection);
+}
+
+/// For use by `NSURLConnection`, once it has finished or been cancelled.
+pub(super) fn remove_url_connection(env: &mut Environment, run_loop: id, connection: id) {
+    let connections = &mut env
+        .objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .url_connections;
+    let connection_idx = connections
+        .iter()
+        .position(|&item| item == connection)
+        .unwrap();
+    connections.swap_remove(connection_idx);
+    release(env, connection);
+}
+
+/// For use by `CADisplayLink`.
+/// TODO: Handle run loop modes. Currently assumes the common modes.
+pub fn add_display_link(env: &mut Environment, run_loop: id, link: id) {
+    retain(env, link);
+    env.objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .display_links
+        .push(link);
+}
+
+/// For use by `CADisplayLink`, when it is removed or invalidated.
+pub fn remove_display_link(env: &mut Environment, run_loop: id, link: id) {
+    let links = &mut env
+        .objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .display_links;
+    let link_idx = links.iter().position(|&item| item == link).unwrap();
+    links.swap_remove(link_idx);
+    release(env, link);
+}
+
+/// For use by `CFRunLoopStop`.
+pub fn stop(env: &mut Environment, run_loop: id) {
+    env.objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .stop_requested = true;
+}
+
 /// For use by NSTimer so it can remove itself once it's invalidated.
 pub(super) fn remove_timer(env: &mut Environment, run_loop: id, timer: id) {
     let NSRunLoopHostObject { timers, .. } = env.objc.borrow_mut(run_loop);
@@ -153,6 +274,28 @@ pub(super) fn remove_timer(env: &mut Environment, run_loop: id, timer: id) {
     }
 }
 
+/// For use by `cancelPreviousPerformRequestsWithTarget:`: invalidate the
+/// timers for matching `performSelector:withObject:afterDelay:` requests. See
+/// [ns_timer::is_matching_perform_request].
+pub(super) fn cancel_perform_requests(
+    env: &mut Environment,
+    run_loop: id,
+    target: id,
+    selector_and_object: Option<(SEL, id)>,
+) {
+    let timers = env
+        .objc
+        .borrow::<NSRunLoopHostObject>(run_loop)
+        .timers
+        .clone();
+    for timer in timers {
+        if ns_timer::is_matching_perform_request(env, timer, target, selector_and_object) {
+            log_dbg!("Cancelling perform request timer {:?}", timer);
+            let _: () = msg![env; timer invalidate];
+        }
+    }
+}
+
 /// Run the run loop for just a single iteration. This is a special mode just
 /// for the app picker, since we don't have `runMode:beforeDate:` or
 /// `runUntilDate:` yet. (TODO: implement those to replace this.)
@@ -160,17 +303,38 @@ pub fn run_run_loop_single_iteration(env: &mut Environment, run_loop: id) {
     run_run_loop(env, run_loop, /* single_iteration: */ true)
 }
 
+/// Run the run loop until `CFRunLoopStop` is called on it. For use by
+/// `CFRunLoopRun`. Unlike `[NSRunLoop run]`, this can be stopped.
+pub fn run_run_loop_until_stopped(env: &mut Environment, run_loop: id) {
+    log_dbg!("Entering run loop {:?} (until stopped)", run_loop);
+    run_run_loop_inner(
+        env, run_loop, /* single_iteration: */ false, /* stoppable: */ true,
+    );
+    log_dbg!("Exited run loop {:?}", run_loop);
+}
+
 fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
     if single_iteration {
         log_dbg!("Entering run loop {:?} (single iteration)", run_loop);
     } else {
         log_dbg!("Entering run loop {:?} (indefinitely)", run_loop);
     }
+    run_run_loop_inner(env, run_loop, single_iteration, /* stoppable: */ false);
+}
 
+fn run_run_loop_inner(
+    env: &mut Environment,
+    run_loop: id,
+    single_iteration: bool,
+    stoppable: bool,
+) {
     // Temporary vectors used to track things without needing a reference to the
     // environment or to lock the object. Re-used each iteration for efficiency.
     let mut timers_tmp = Vec::new();
     let mut audio_queues_tmp = Vec::new();
+    let mut sources_tmp = Vec::new();
+    let mut url_connections_tmp = Vec::new();
+    let mut display_links_tmp = Vec::new();
 
     fn limit_sleep_time(current: &mut Option<Instant>, new: Option<Instant>) {
         if let Some(new) = new {
@@ -178,19 +342,43 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
         }
     }
 
+    // Only the main thread's run loop handles input and drawing.
+    let is_main_thread = env.current_thread == 0;
+
     loop {
         let mut sleep_until = None;
 
-        env.window
-            .as_mut()
-            .expect("NSRunLoop not supported in headless mode")
-            .poll_for_events(&env.options);
+        if is_main_thread {
+            env.window
+                .as_mut()
+                .expect("NSRunLoop not supported in headless mode")
+                .poll_for_events(&env.options, env.clock.uptime());
 
-        let next_due = uikit::handle_events(env);
-        limit_sleep_time(&mut sleep_until, next_due);
+            let next_due = uikit::handle_events(env);
+            limit_sleep_time(&mut sleep_until, next_due);
+
+            let next_due = dispatch::handle_main_queue(env);
+            limit_sleep_time(&mut sleep_until, next_due);
+
+            core_animation::ca_transaction::commit_implicit_transaction(env);
 
-        let next_due = core_animation::recomposite_if_necessary(env);
-        limit_sleep_time(&mut sleep_until, next_due);
+            let next_due = core_animation::recomposite_if_necessary(env);
+            limit_sleep_time(&mut sleep_until, next_due);
+        } else {
+            // Like Apple's implementation, a run loop exits once it has no
+            // inputs or timers. The main thread's run loop always has input
+            // and drawing to handle, so this only applies to other threads.
+            let host_object = env.objc.borrow::<NSRunLoopHostObject>(run_loop);
+            if host_object.timers.is_empty()
+                && host_object.audio_queues.is_empty()
+                && host_object.sources.is_empty()
+                && host_object.url_connections.is_empty()
+                && host_object.display_links.is_empty()
+            {
+                log_dbg!("Run loop {:?} has no inputs or timers, exiting", run_loop);
+                break;
+            }
+        }
 
         assert!(timers_tmp.is_empty());
         timers_tmp.extend_from_slice(&env.objc.borrow::<NSRunLoopHostObject>(run_loop).timers);
@@ -211,7 +399,39 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
             handle_audio_queue(env, audio_queue);
         }
 
-        media_player::handle_players(env);
+        assert!(sources_tmp.is_empty());
+        sources_tmp.extend_from_slice(&env.objc.borrow::<NSRunLoopHostObject>(run_loop).sources);
+
+        for source in sources_tmp.drain(..) {
+            cf_run_loop::handle_source(env, source);
+        }
+
+        assert!(url_connections_tmp.is_empty());
+        url_connections_tmp.extend_from_slice(
+            &env.objc
+                .borrow::<NSRunLoopHostObject>(run_loop)
+                .url_connections,
+        );
+
+        for connection in url_connections_tmp.drain(..) {
+            ns_url_connection::handle_connection(env, connection);
+        }
+
+        assert!(display_links_tmp.is_empty());
+        display_links_tmp.extend_from_slice(
+            &env.objc
+                .borrow::<NSRunLoopHostObject>(run_loop)
+                .display_links,
+        );
+
+        for link in display_links_tmp.drain(..) {
+            let next_due = ca_display_link::handle_display_link(env, link);
+            limit_sleep_time(&mut sleep_until, next_due);
+        }
+
+        if is_main_thread {
+            media_player::handle_players(env);
+        }
 
         // Unfortunately, touchHLE has to poll for certain things repeatedly;
         // it can't just wait until the next event appears.
@@ -228,12 +448,21 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
         // or until the next scheduled event, whichever is sooner. iPhone OS
         // apps can't do more than 60fps so this should be fine.
         let limit = Duration::from_millis(1000 / 60);
+        let now = env.clock.now();
         env.sleep(
-            sleep_until.map_or(limit, |i| i.duration_since(Instant::now()).min(limit)),
+            sleep_until.map_or(limit, |i| i.duration_since(now).min(limit)),
             false,
         );
 
-        if single_iteration {
+        // Stopping a run loop that can't be stopped (e.g. the one run by
+        // UIApplicationMain) only ends the current iteration.
+        let stop_requested = std::mem::take(
+            &mut env
+                .objc
+                .borrow_mut::<NSRunLoopHostObject>(run_loop)
+                .stop_requested,
+        );
+        if single_iteration || (stoppable && stop_requested) {
             break;
         }
     }
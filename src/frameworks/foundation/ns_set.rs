@@ -13,6 +13,7 @@ use crate::mem::MutPtr;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, retain, ClassExports, HostObject, NSZonePtr,
 };
+use crate::Environment;
 
 /// Belongs to _touchHLE_NSSet
 struct SetHostObject {
@@ -83,7 +84,11 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 // TODO: more init methods, etc
 
-// TODO: accessors
+// TODO: more accessors
+
+- (NSUInteger)count {
+    env.objc.borrow::<SetHostObject>(this).dict.count
+}
 
 - (id)anyObject {
     let object_or_none = env.objc.borrow_mut::<SetHostObject>(this).dict.iter_keys().next();
@@ -102,42 +107,56 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
                                   objects:(MutPtr<id>)stackbuf
                                     count:(NSUInteger)len {
-    let host_object = env.objc.borrow::<SetHostObject>(this);
-
-    if host_object.dict.count == 0 {
-        return 0;
-    }
-
-    // TODO: handle size > 1
-    assert!(host_object.dict.count == 1);
-    assert!(len >= host_object.dict.count);
-
     let NSFastEnumerationState {
-        state: is_first_round,
+        state: start_index,
         ..
     } = env.mem.read(state);
 
-    match is_first_round {
-        0 => {
-            let object = host_object.dict.iter_keys().next().unwrap();
-            env.mem.write(stackbuf, object);
-            env.mem.write(state, NSFastEnumerationState {
-                state: 1,
-                items_ptr: stackbuf,
-                // can be anything as long as it's dereferenceable and the same
-                // each iteration
-                mutations_ptr: stackbuf.cast(),
-                extra: Default::default(),
-            });
-            1 // returned object count
-        },
-        1 => {
-            0 // end of iteration
-        },
-        _ => panic!(), // app failed to initialize the buffer?
+    let objects: Vec<id> = env.objc.borrow::<SetHostObject>(this).dict.iter_keys().collect();
+    let start_index: usize = start_index.try_into().unwrap();
+    if start_index >= objects.len() {
+        return 0; // end of iteration
+    }
+
+    let batch = &objects[start_index..objects.len().min(start_index + len as usize)];
+    for (i, &object) in batch.iter().enumerate() {
+        env.mem.write(stackbuf + i.try_into().unwrap(), object);
     }
+    env.mem.write(state, NSFastEnumerationState {
+        state: (start_index + batch.len()).try_into().unwrap(),
+        items_ptr: stackbuf,
+        // can be anything as long as it's dereferenceable and the same
+        // each iteration
+        mutations_ptr: stackbuf.cast(),
+        extra: Default::default(),
+    });
+    batch.len().try_into().unwrap() // returned object count
 }
 
 @end
 
 };
+
+/// Create a new set from a [Vec] of objects, retaining each of them. The
+/// result is not autoreleased. Duplicate objects are only stored once.
+pub fn from_vec(env: &mut Environment, objects: Vec<id>) -> id {
+    let null: id = msg_class![env; NSNull null];
+
+    let mut dict = <DictionaryHostObject as Default>::default();
+    for object in objects {
+        dict.insert(env, object, null, /* copy_key: */ false);
+    }
+
+    let set: id = msg_class![env; _touchHLE_NSSet alloc];
+    env.objc.borrow_mut::<SetHostObject>(set).dict = dict;
+    set
+}
+
+/// Get the objects in a set as a [Vec]. They are not retained.
+pub fn to_vec(env: &mut Environment, set: id) -> Vec<id> {
+    env.objc
+        .borrow::<SetHostObject>(set)
+        .dict
+        .iter_keys()
+        .collect()
+}
@@ -6,21 +6,26 @@
 //! The `NSString` class cluster, including `NSMutableString`.
 
 mod path_algorithms;
+mod search_algorithms;
 
 use super::ns_array;
+use super::ns_error::{set_error, NSCocoaErrorDomain, NSFileReadNoSuchFileError};
 use super::{
-    NSComparisonResult, NSOrderedAscending, NSOrderedDescending, NSOrderedSame, NSUInteger,
+    NSComparisonResult, NSNotFound, NSOrderedAscending, NSOrderedDescending, NSOrderedSame,
+    NSRange, NSUInteger,
 };
+use crate::abi::VaList;
 use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::frameworks::uikit::ui_font::{
     self, UILineBreakMode, UILineBreakModeWordWrap, UITextAlignment, UITextAlignmentLeft,
 };
 use crate::fs::GuestPath;
+use crate::libc::stdio::printf::printf_inner;
 use crate::mach_o::MachO;
 use crate::mem::{guest_size_of, ConstPtr, Mem, MutPtr, Ptr, SafeRead};
 use crate::objc::{
-    autorelease, id, msg, msg_class, nil, objc_classes, retain, Class, ClassExports, HostObject,
-    NSZonePtr, ObjC,
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject, NSZonePtr, ObjC,
 };
 use crate::Environment;
 use std::borrow::Cow;
@@ -35,6 +40,12 @@ pub const NSUTF16StringEncoding: NSUInteger = NSUnicodeStringEncoding;
 pub const NSUTF16BigEndianStringEncoding: NSUInteger = 0x90000100;
 pub const NSUTF16LittleEndianStringEncoding: NSUInteger = 0x94000100;
 
+pub type NSStringCompareOptions = NSUInteger;
+pub const NSCaseInsensitiveSearch: NSStringCompareOptions = 1;
+pub const NSBackwardsSearch: NSStringCompareOptions = 4;
+pub const NSAnchoredSearch: NSStringCompareOptions = 8;
+pub const NSNumericSearch: NSStringCompareOptions = 64;
+
 /// Encodings that C strings (null-terminated byte strings) can use.
 const C_STRING_FRIENDLY_ENCODINGS: &[NSStringEncoding] =
     &[NSASCIIStringEncoding, NSUTF8StringEncoding];
@@ -248,25 +259,42 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 + (id)stringWithFormat:(id)format, // NSString*
                        ...args {
-    // TODO: avoid copy
-    let format_string = to_rust_string(env, format);
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithFormat:format arguments:(args.start())];
+    autorelease(env, new)
+}
 
-    log_dbg!("[NSString stringWithFormat:{:?} ({:?}), ...]", format, format_string);
++ (id)stringWithString:(id)string { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithString:string];
+    autorelease(env, new)
+}
 
-    let res = crate::libc::stdio::printf::printf_inner::<true, _>(
-        env,
-        |_, idx| {
-            if idx as usize == format_string.len() {
-                b'\0'
-            } else {
-                format_string.as_bytes()[idx as usize]
-            }
-        },
-        args.start(),
-    );
-    // TODO: what if it's not valid UTF-8?
-    let res = from_rust_string(env, String::from_utf8(res).unwrap());
-    autorelease(env, res)
+// These init methods are implemented in terms of the ones subclasses provide.
+
+- (id)initWithUTF8String:(ConstPtr<u8>)utf8_string {
+    msg![env; this initWithCString:utf8_string encoding:NSUTF8StringEncoding]
+}
+
+- (id)initWithCString:(ConstPtr<u8>)c_string {
+    // This is a deprecated method nobody should use, but unfortunately, it is
+    // used. The encoding it should use is [NSString defaultCStringEncoding]
+    // but I don't want to figure out what that is on all platforms, and the use
+    // I've seen of this method was on ASCII strings, so let's just hardcode
+    // UTF-8 and hope that works.
+    msg![env; this initWithCString:c_string encoding:NSUTF8StringEncoding]
+}
+
+- (id)initWithCString:(ConstPtr<u8>)c_string
+             encoding:(NSStringEncoding)encoding {
+    assert!(C_STRING_FRIENDLY_ENCODINGS.contains(&encoding));
+    let len: NSUInteger = env.mem.cstr_at(c_string).len().try_into().unwrap();
+    msg![env; this initWithBytes:c_string length:len encoding:encoding]
+}
+
+- (id)initWithFormat:(id)format, // NSString*
+                    ...args {
+    msg![env; this initWithFormat:format arguments:(args.start())]
 }
 
 // These are the two methods that have to be overridden by subclasses, so these
@@ -312,6 +340,9 @@ pub const CLASSES: ClassExports = objc_classes! {
     // TODO: avoid copying
     super::hash_helper(&to_rust_string(env, this))
 }
+- (bool)isEqual:(id)other {
+    msg![env; this isEqualTo:other]
+}
 - (bool)isEqualTo:(id)other {
     if this == other {
         return true;
@@ -332,23 +363,82 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (NSComparisonResult)compare:(id)other { // NSString*
-    // TODO: support foreign subclasses (perhaps via a helper function that
-    // copies the string first)
-    let a_iter = env.objc.borrow::<StringHostObject>(this).iter_code_units();
-    let b_iter = env.objc.borrow::<StringHostObject>(other).iter_code_units();
-    match a_iter.cmp(b_iter) {
+    msg![env; this compare:other options:0u32]
+}
+- (NSComparisonResult)caseInsensitiveCompare:(id)other { // NSString*
+    msg![env; this compare:other options:NSCaseInsensitiveSearch]
+}
+- (NSComparisonResult)compare:(id)other // NSString*
+                      options:(NSStringCompareOptions)options {
+    assert!(other != nil); // TODO: raise exception
+
+    let a = to_utf16(env, this);
+    let b = to_utf16(env, other);
+    match search_algorithms::compare(&a, &b, options) {
         std::cmp::Ordering::Less => NSOrderedAscending,
         std::cmp::Ordering::Equal => NSOrderedSame,
         std::cmp::Ordering::Greater => NSOrderedDescending,
     }
 }
 
+- (bool)hasPrefix:(id)prefix { // NSString*
+    // An empty prefix never matches.
+    let prefix = to_utf16(env, prefix);
+    !prefix.is_empty() && to_utf16(env, this).starts_with(&prefix)
+}
+- (bool)hasSuffix:(id)suffix { // NSString*
+    // An empty suffix never matches.
+    let suffix = to_utf16(env, suffix);
+    !suffix.is_empty() && to_utf16(env, this).ends_with(&suffix)
+}
+
+- (NSRange)rangeOfString:(id)search_string { // NSString*
+    msg![env; this rangeOfString:search_string options:0u32]
+}
+- (NSRange)rangeOfString:(id)search_string // NSString*
+                 options:(NSStringCompareOptions)options {
+    assert!(search_string != nil); // TODO: raise exception
+
+    let haystack = to_utf16(env, this);
+    let needle = to_utf16(env, search_string);
+    match search_algorithms::find(&haystack, &needle, options) {
+        Some((location, length)) => NSRange {
+            location: location.try_into().unwrap(),
+            length: length.try_into().unwrap(),
+        },
+        None => NSRange {
+            location: NSNotFound as NSUInteger,
+            length: 0,
+        },
+    }
+}
+
+- (id)uppercaseString {
+    // TODO: avoid copying
+    let string = String::from_utf16_lossy(&to_utf16(env, this));
+    let res = from_rust_string(env, string.to_uppercase());
+    autorelease(env, res)
+}
+- (id)lowercaseString {
+    // TODO: avoid copying
+    let string = String::from_utf16_lossy(&to_utf16(env, this));
+    let res = from_rust_string(env, string.to_lowercase());
+    autorelease(env, res)
+}
+
 // NSCopying implementation
 - (id)copyWithZone:(NSZonePtr)_zone {
-    // TODO: override this once we have NSMutableString!
+    // Immutable, so there's no need to actually copy. NSMutableString overrides
+    // this.
     retain(env, this)
 }
 
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(NSZonePtr)zone {
+    let new: id = msg_class![env; _touchHLE_NSMutableString allocWithZone:zone];
+    msg![env; new initWithString:this]
+}
+
 - (bool)getCString:(MutPtr<u8>)buffer
          maxLength:(NSUInteger)buffer_size
           encoding:(NSStringEncoding)encoding {
@@ -393,8 +483,13 @@ pub const CLASSES: ClassExports = objc_classes! {
     let sep_iter = env.objc.borrow::<StringHostObject>(separator)
         .iter_code_units();
 
-    // TODO: zero-length separator support
-    assert!(sep_iter.clone().next().is_some());
+    // A zero-length separator never matches, so the result is just the
+    // original string.
+    if sep_iter.clone().next().is_none() {
+        let copy: id = msg![env; this copy];
+        let array = ns_array::from_vec(env, vec![copy]);
+        return autorelease(env, array);
+    }
 
     let mut components = Vec::<Utf16String>::new();
     let mut current_component: Utf16String = Vec::new();
@@ -503,8 +598,11 @@ pub const CLASSES: ClassExports = objc_classes! {
     let replacement_iter = env.objc.borrow::<StringHostObject>(replacement)
         .iter_code_units();
 
-    // TODO: zero-length target support?
-    assert!(target_iter.clone().next().is_some());
+    // A zero-length target never matches.
+    if target_iter.clone().next().is_none() {
+        let copy: id = msg![env; this copy];
+        return autorelease(env, copy);
+    }
 
     let mut result: Utf16String = Vec::new();
     loop {
@@ -673,6 +771,37 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @end
 
+@implementation NSMutableString: NSString
+
++ (id)allocWithZone:(NSZonePtr)zone {
+    // NSMutableString might be subclassed by something which needs
+    // allocWithZone: to have the normal behaviour. Unimplemented: call
+    // superclass alloc then.
+    assert!(this == env.objc.get_known_class("NSMutableString", &mut env.mem));
+    msg_class![env; _touchHLE_NSMutableString allocWithZone:zone]
+}
+
++ (id)string {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new init];
+    autorelease(env, new)
+}
+
++ (id)stringWithCapacity:(NSUInteger)capacity {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCapacity:capacity];
+    autorelease(env, new)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)zone {
+    // The copy must be immutable and not affected by later mutations.
+    let new: id = msg_class![env; _touchHLE_NSString allocWithZone:zone];
+    msg![env; new initWithString:this]
+}
+
+@end
+
 // Our private subclass that is the single implementation of NSString for the
 // time being.
 @implementation _touchHLE_NSString: NSString
@@ -696,35 +825,33 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
-- (id)initWithUTF8String:(ConstPtr<u8>)utf8_string {
-    msg![env; this initWithCString:utf8_string encoding:NSUTF8StringEncoding]
-}
-
-- (id)initWithCString:(ConstPtr<u8>)c_string {
-    // This is a deprecated method nobody should use, but unfortunately, it is
-    // used. The encoding it should use is [NSString defaultCStringEncoding]
-    // but I don't want to figure out what that is on all platforms, and the use
-    // I've seen of this method was on ASCII strings, so let's just hardcode
-    // UTF-8 and hope that works.
-    msg![env; this initWithCString:c_string encoding:NSUTF8StringEncoding]
+- (id)initWithFormat:(id)format // NSString*
+           arguments:(VaList)args {
+    let res = with_format(env, format, args);
+    *env.objc.borrow_mut(this) = StringHostObject::Utf8(Cow::Owned(res));
+    this
 }
 
-- (id)initWithCString:(ConstPtr<u8>)c_string
-             encoding:(NSStringEncoding)encoding {
-    assert!(C_STRING_FRIENDLY_ENCODINGS.contains(&encoding));
-    let len: NSUInteger = env.mem.cstr_at(c_string).len().try_into().unwrap();
-    msg![env; this initWithBytes:c_string length:len encoding:encoding]
+- (id)initWithString:(id)string { // NSString*
+    assert!(string != nil); // TODO: raise exception
+    let utf16 = to_utf16(env, string);
+    *env.objc.borrow_mut(this) = StringHostObject::Utf16(utf16);
+    this
 }
 
 - (id)initWithContentsOfFile:(id)path // NSString*
                     encoding:(NSStringEncoding)encoding
                        error:(MutPtr<id>)error { // NSError**
-    assert!(error.is_null()); // TODO: error handling
-
     // TODO: avoid copy?
     let path = to_rust_string(env, path);
-    let bytes = env.fs.read(GuestPath::new(&path)).unwrap();
+    let Ok(bytes) = env.fs.read(GuestPath::new(&path)) else {
+        log!("Warning: initWithContentsOfFile:{:?} failed", path);
+        set_error(env, error, NSCocoaErrorDomain, NSFileReadNoSuchFileError, None);
+        release(env, this);
+        return nil;
+    };
 
+    // TODO: report decoding errors too
     let host_object = StringHostObject::decode(Cow::Owned(bytes), encoding);
 
     *env.objc.borrow_mut(this) = host_object;
@@ -734,6 +861,90 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @end
 
+// Our private subclass that is the single implementation of NSMutableString
+// for the time being. It uses the same host object as _touchHLE_NSString, but
+// always in UTF-16 so that the edits can use UTF-16 code unit indices.
+@implementation _touchHLE_NSMutableString: NSMutableString
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(StringHostObject::Utf16(Vec::new()));
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)init {
+    this
+}
+
+- (id)initWithCapacity:(NSUInteger)_capacity {
+    this
+}
+
+- (id)initWithBytes:(ConstPtr<u8>)bytes
+             length:(NSUInteger)len
+           encoding:(NSStringEncoding)encoding {
+    // TODO: error handling
+    let slice = env.mem.bytes_at(bytes, len);
+    let mut host_object = StringHostObject::decode(Cow::Borrowed(slice), encoding);
+    host_object.convert_to_utf16_inplace();
+
+    *env.objc.borrow_mut(this) = host_object;
+
+    this
+}
+
+- (id)initWithFormat:(id)format // NSString*
+           arguments:(VaList)args {
+    let res = with_format(env, format, args);
+    *env.objc.borrow_mut(this) = StringHostObject::Utf16(res.encode_utf16().collect());
+    this
+}
+
+- (id)initWithString:(id)string { // NSString*
+    let _: () = msg![env; this setString:string];
+    this
+}
+
+- (())setString:(id)string { // NSString*
+    assert!(string != nil); // TODO: raise exception
+    let utf16 = to_utf16(env, string);
+    *env.objc.borrow_mut(this) = StringHostObject::Utf16(utf16);
+}
+
+- (())appendString:(id)string { // NSString*
+    assert!(string != nil); // TODO: raise exception
+    let utf16 = to_utf16(env, string);
+    let length: NSUInteger = msg![env; this length];
+    replace_code_units(env, this, NSRange { location: length, length: 0 }, utf16);
+}
+
+- (())appendFormat:(id)format, // NSString*
+                   ...args {
+    let res = with_format(env, format, args.start());
+    let length: NSUInteger = msg![env; this length];
+    let range = NSRange { location: length, length: 0 };
+    replace_code_units(env, this, range, res.encode_utf16().collect());
+}
+
+- (())insertString:(id)string // NSString*
+           atIndex:(NSUInteger)index {
+    assert!(string != nil); // TODO: raise exception
+    let utf16 = to_utf16(env, string);
+    replace_code_units(env, this, NSRange { location: index, length: 0 }, utf16);
+}
+
+- (())deleteCharactersInRange:(NSRange)range {
+    replace_code_units(env, this, range, Vec::new());
+}
+
+- (())replaceCharactersInRange:(NSRange)range
+                    withString:(id)string { // NSString*
+    assert!(string != nil); // TODO: raise exception
+    let utf16 = to_utf16(env, string);
+    replace_code_units(env, this, range, utf16);
+}
+
+@end
+
 // Specialised subclass for static-lifetime strings.
 // See `get_static_str`.
 @implementation _touchHLE_NSString_Static: _touchHLE_NSString
@@ -829,6 +1040,29 @@ pub fn get_static_str(env: &mut Environment, from: &'static str) -> id {
     }
 }
 
+/// Shortcut for host code: format a string like `[NSString stringWithFormat:]`
+/// does, see [printf_inner].
+pub fn with_format(env: &mut Environment, format: id, args: VaList) -> String {
+    // TODO: avoid copy
+    let format_string = to_rust_string(env, format);
+
+    log_dbg!("Formatting {:?} ({:?})", format, format_string);
+
+    let res = printf_inner::<true, _>(
+        env,
+        |_, idx| {
+            if idx as usize == format_string.len() {
+                b'\0'
+            } else {
+                format_string.as_bytes()[idx as usize]
+            }
+        },
+        args,
+    );
+    // TODO: what if it's not valid UTF-8?
+    String::from_utf8(res).unwrap()
+}
+
 /// Shortcut for host code, roughly equivalent to
 /// `[[NSString alloc] initWithUTF8String:]` in the proper API.
 pub fn from_rust_string(env: &mut Environment, from: String) -> id {
@@ -852,6 +1086,33 @@ pub fn to_rust_string(env: &mut Environment, string: id) -> Cow<'static, str> {
         .unwrap()
 }
 
+/// Shortcut for host code, copies a string's UTF-16 code units.
+fn to_utf16(env: &mut Environment, string: id) -> Utf16String {
+    let mut utf16 = Vec::new();
+    for_each_code_unit(env, string, |_idx, c| utf16.push(c));
+    utf16
+}
+
+/// For use by `NSMutableString`: replace the code units in `range` with
+/// `replacement`.
+fn replace_code_units(env: &mut Environment, string: id, range: NSRange, replacement: Utf16String) {
+    let (utf16, _) = env
+        .objc
+        .borrow_mut::<StringHostObject>(string)
+        .convert_to_utf16_inplace();
+    let start = range.location as usize;
+    let end = start.checked_add(range.length as usize).unwrap();
+    // TODO: raise NSRangeException instead of panicking
+    assert!(
+        end <= utf16.len(),
+        "Range {{{}, {}}} out of bounds for string of length {}",
+        start,
+        end - start,
+        utf16.len()
+    );
+    utf16.splice(start..end, replacement);
+}
+
 /// Shortcut for host code, calls a callback once for each UTF-16 code-unit in a
 /// string. This is equivalent to a for loop using the `length` and
 /// `characterAtIndex:` methods, but much more efficient.
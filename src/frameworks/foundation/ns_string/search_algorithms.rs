@@ -0,0 +1,216 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Implementations of the string comparison and searching algorithms used by
+//! NSString methods.
+//!
+//! Strings are given as UTF-16 code units, and ranges are returned in UTF-16
+//! code units, because that is what NSString's API uses. Case-insensitive
+//! operations work on whole code points instead, with unpaired surrogates
+//! treated as U+FFFD. Case folding is done one code point at a time, so e.g.
+//! "ß" does not match "SS".
+
+use super::{
+    NSAnchoredSearch, NSBackwardsSearch, NSCaseInsensitiveSearch, NSNumericSearch,
+    NSStringCompareOptions,
+};
+use std::cmp::Ordering;
+
+/// Split UTF-16 into code points, each paired with the index of its first code
+/// unit.
+fn decode(utf16: &[u16]) -> Vec<(usize, char)> {
+    let mut idx = 0;
+    char::decode_utf16(utf16.iter().copied())
+        .map(|c| {
+            let start = idx;
+            idx += match c {
+                Ok(c) => c.len_utf16(),
+                Err(_) => 1,
+            };
+            (start, c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        })
+        .collect()
+}
+
+fn compare_chars(a: char, b: char, case_insensitive: bool) -> Ordering {
+    if case_insensitive {
+        a.to_lowercase().cmp(b.to_lowercase())
+    } else {
+        a.cmp(&b)
+    }
+}
+
+/// Compare two strings, like `[NSString compare:options:]`. Only the
+/// [NSCaseInsensitiveSearch] and [NSNumericSearch] options affect the result.
+/// Without them, strings are compared code unit by code unit.
+pub fn compare(a: &[u16], b: &[u16], options: NSStringCompareOptions) -> Ordering {
+    let case_insensitive = options & NSCaseInsensitiveSearch != 0;
+    let numeric = options & NSNumericSearch != 0;
+    if !case_insensitive && !numeric {
+        return a.cmp(b);
+    }
+
+    let a = decode(a);
+    let b = decode(b);
+    let is_digit = |c: char| c.is_ascii_digit();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if numeric && is_digit(a[i].1) && is_digit(b[j].1) {
+            // Compare runs of digits by their numeric value, so "9" < "10".
+            let a_end = a[i..].iter().position(|&(_, c)| !is_digit(c));
+            let a_end = a_end.map_or(a.len(), |len| i + len);
+            let b_end = b[j..].iter().position(|&(_, c)| !is_digit(c));
+            let b_end = b_end.map_or(b.len(), |len| j + len);
+            let a_digits: Vec<char> = a[i..a_end]
+                .iter()
+                .map(|&(_, c)| c)
+                .skip_while(|&c| c == '0')
+                .collect();
+            let b_digits: Vec<char> = b[j..b_end]
+                .iter()
+                .map(|&(_, c)| c)
+                .skip_while(|&c| c == '0')
+                .collect();
+            let ordering = a_digits
+                .len()
+                .cmp(&b_digits.len())
+                .then_with(|| a_digits.cmp(&b_digits));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            i = a_end;
+            j = b_end;
+            continue;
+        }
+        let ordering = compare_chars(a[i].1, b[j].1, case_insensitive);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        i += 1;
+        j += 1;
+    }
+    (a.len() - i).cmp(&(b.len() - j))
+}
+
+/// Find the first match of `needle` in `haystack`, given as `(index, unit)`
+/// pairs. `haystack_len` is the length in code units, used as the end index of
+/// a match at the very end.
+fn find_units<T: Copy>(
+    haystack: &[(usize, T)],
+    haystack_len: usize,
+    needle: &[T],
+    options: NSStringCompareOptions,
+    eq: impl Fn(T, T) -> bool,
+) -> Option<(usize, usize)> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+    let last_start = haystack.len() - needle.len();
+    let matches_at = |start: usize| {
+        haystack[start..start + needle.len()]
+            .iter()
+            .zip(needle)
+            .all(|(&(_, a), &b)| eq(a, b))
+    };
+    let backwards = options & NSBackwardsSearch != 0;
+    let start = if options & NSAnchoredSearch != 0 {
+        // Anchored searches only look at the start of the string, or at the
+        // end if searching backwards.
+        let start = if backwards { last_start } else { 0 };
+        Some(start).filter(|&start| matches_at(start))
+    } else if backwards {
+        (0..=last_start).rev().find(|&start| matches_at(start))
+    } else {
+        (0..=last_start).find(|&start| matches_at(start))
+    }?;
+    let location = haystack[start].0;
+    let end = haystack
+        .get(start + needle.len())
+        .map_or(haystack_len, |&(idx, _)| idx);
+    Some((location, end - location))
+}
+
+/// Search for `needle` in `haystack`, like `[NSString rangeOfString:options:]`.
+/// Returns the location and length of the match, or [None] if there is no
+/// match or `needle` is empty.
+pub fn find(
+    haystack: &[u16],
+    needle: &[u16],
+    options: NSStringCompareOptions,
+) -> Option<(usize, usize)> {
+    if options & NSCaseInsensitiveSearch != 0 {
+        let needle: Vec<char> = decode(needle).into_iter().map(|(_, c)| c).collect();
+        find_units(
+            &decode(haystack),
+            haystack.len(),
+            &needle,
+            options,
+            |a, b| compare_chars(a, b, true) == Ordering::Equal,
+        )
+    } else {
+        let haystack: Vec<(usize, u16)> = haystack.iter().copied().enumerate().collect();
+        find_units(&haystack, haystack.len(), needle, options, |a, b| a == b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    fn test_compare() {
+        let cmp = |a: &str, b: &str, options| compare(&utf16(a), &utf16(b), options);
+
+        assert_eq!(cmp("abc", "abc", 0), Ordering::Equal);
+        assert_eq!(cmp("abc", "abd", 0), Ordering::Less);
+        assert_eq!(cmp("abc", "ab", 0), Ordering::Greater);
+        assert_eq!(cmp("", "a", 0), Ordering::Less);
+        assert_eq!(cmp("ABC", "abc", 0), Ordering::Less);
+
+        let ci = NSCaseInsensitiveSearch;
+        assert_eq!(cmp("ABC", "abc", ci), Ordering::Equal);
+        assert_eq!(cmp("Straße", "STRASSE", ci), Ordering::Greater);
+        assert_eq!(cmp("ÉCOLE", "école", ci), Ordering::Equal);
+        assert_eq!(cmp("apple", "Banana", ci), Ordering::Less);
+
+        let num = NSNumericSearch;
+        assert_eq!(cmp("file9", "file10", 0), Ordering::Greater);
+        assert_eq!(cmp("file9", "file10", num), Ordering::Less);
+        assert_eq!(cmp("file010", "file10", num), Ordering::Equal);
+        assert_eq!(cmp("v1.10", "V1.9", num | ci), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_find() {
+        let search = |a: &str, b: &str, options| find(&utf16(a), &utf16(b), options);
+
+        assert_eq!(search("hello world", "o", 0), Some((4, 1)));
+        assert_eq!(search("hello world", "o", NSBackwardsSearch), Some((7, 1)));
+        assert_eq!(search("hello world", "world", 0), Some((6, 5)));
+        assert_eq!(search("hello world", "World", 0), None);
+        assert_eq!(search("hello world", "", 0), None);
+        assert_eq!(search("", "a", 0), None);
+        assert_eq!(search("aaa", "aa", 0), Some((0, 2)));
+        assert_eq!(search("aaa", "aa", NSBackwardsSearch), Some((1, 2)));
+
+        let ci = NSCaseInsensitiveSearch;
+        assert_eq!(search("Hello World", "WORLD", ci), Some((6, 5)));
+        // Code points outside the BMP take two code units.
+        assert_eq!(search("😀Ab😀", "aB", ci), Some((2, 2)));
+        assert_eq!(search("😀Ab😀", "😀", ci | NSBackwardsSearch), Some((4, 2)));
+
+        let anchored = NSAnchoredSearch;
+        assert_eq!(search("hello", "he", anchored), Some((0, 2)));
+        assert_eq!(search("hello", "lo", anchored), None);
+        assert_eq!(
+            search("hello", "lo", anchored | NSBackwardsSearch),
+            Some((3, 2))
+        );
+    }
+}
@@ -4,8 +4,55 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `NSThread`.
+//!
+//! `NSThread`s are built on the same guest threads as `pthread_create`. Like
+//! all guest threads, they are scheduled cooperatively on a single host thread:
+//! only one guest thread executes at a time, and switches only happen when the
+//! current thread blocks or yields (sleeping, waiting on a mutex, joining,
+//! running a run loop, etc). This means host code has exclusive access to
+//! the [Environment] (including [crate::objc::ObjC] and [crate::mem::Mem])
+//! for as long as it doesn't call back into guest code, so no locking is
+//! needed on the host side. Guest code must still synchronize with its own
+//! threads as it would on a real device, e.g. with pthread mutexes.
+//!
+//! Each thread gets its own `NSRunLoop` on demand, see `[NSRunLoop
+//! currentRunLoop]`.
 
-use crate::objc::{id, objc_classes, ClassExports};
+use super::ns_run_loop::NSDefaultRunLoopMode;
+use super::ns_timer;
+use super::{ns_string, NSTimeInterval};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    id, msg, msg_class, msg_send, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr, SEL,
+};
+use crate::{Environment, ThreadId};
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct State {
+    /// `NSThread*` for each guest thread that has one. The main thread's
+    /// object is never released, and other threads' objects are owned by the
+    /// thread while it is executing.
+    threads: HashMap<ThreadId, id>,
+}
+
+#[derive(Default)]
+struct NSThreadHostObject {
+    target: id,
+    selector: Option<SEL>,
+    object: id,
+    /// `NSString*`
+    name: id,
+    /// Set once the thread has been started.
+    thread_id: Option<ThreadId>,
+    finished: bool,
+    cancelled: bool,
+    priority: f64,
+}
+impl HostObject for NSThreadHostObject {}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -13,25 +60,242 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation NSThread: NSObject
 
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSThreadHostObject {
+        priority: 0.5,
+        ..Default::default()
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (())detachNewThreadSelector:(SEL)selector
+                     toTarget:(id)target
+                   withObject:(id)object {
+    let thread: id = msg![env; this alloc];
+    let thread: id = msg![env; thread initWithTarget:target selector:selector object:object];
+    let _: () = msg![env; thread start];
+    release(env, thread);
+}
+
++ (id)currentThread {
+    current_thread(env)
+}
+
++ (id)mainThread {
+    thread_object(env, 0)
+}
+
++ (bool)isMainThread {
+    env.current_thread == 0
+}
+
++ (bool)isMultiThreaded {
+    env.threads.len() > 1
+}
+
 + (f64)threadPriority {
-    log!("TODO: [NSThread threadPriority] (not implemented yet)");
-    1.0
+    let thread = current_thread(env);
+    msg![env; thread threadPriority]
 }
 
 + (bool)setThreadPriority:(f64)priority {
-    log!("TODO: [NSThread setThreadPriority:{:?}] (ignored)", priority);
-    true
+    let thread = current_thread(env);
+    msg![env; thread setThreadPriority:priority]
 }
 
-+ (id)currentThread {
-    // Simple hack to make the `setThreadPriority:` work as an instance method
-    // (it's both a class and an instance method). Must be replaced if we ever
-    // need to support other methods.
++ (())sleepForTimeInterval:(NSTimeInterval)interval {
+    log_dbg!("[NSThread sleepForTimeInterval:{}]", interval);
+    env.sleep(Duration::from_secs_f64(interval.max(0.0)), /* tail_call: */ true);
+}
+
+- (id)initWithTarget:(id)target
+            selector:(SEL)selector
+              object:(id)object {
+    retain(env, target);
+    retain(env, object);
+    let host_object = env.objc.borrow_mut::<NSThreadHostObject>(this);
+    host_object.target = target;
+    host_object.selector = Some(selector);
+    host_object.object = object;
     this
 }
 
-// TODO: construction etc
+- (())dealloc {
+    let &NSThreadHostObject {
+        target,
+        object,
+        name,
+        ..
+    } = env.objc.borrow(this);
+    release(env, target);
+    release(env, object);
+    release(env, name);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())start {
+    let host_object = env.objc.borrow::<NSThreadHostObject>(this);
+    assert!(host_object.thread_id.is_none()); // TODO: raise exception
+
+    // The thread owns its object while it executes.
+    retain(env, this);
+    let helper = env
+        .dyld
+        .create_proc_address(&mut env.mem, &mut env.cpu, "__touchHLE_NSThreadInvocationHelper")
+        .unwrap();
+    let thread_id = env.new_thread(helper, this.cast());
+    log_dbg!("Started NSThread {:?} as thread {}", this, thread_id);
+
+    env.objc.borrow_mut::<NSThreadHostObject>(this).thread_id = Some(thread_id);
+    let threads = &mut env.framework_state.foundation.ns_thread.threads;
+    assert!(!threads.contains_key(&thread_id));
+    threads.insert(thread_id, this);
+}
+
+- (())main {
+    let &NSThreadHostObject {
+        target,
+        selector,
+        object,
+        ..
+    } = env.objc.borrow(this);
+    if let Some(selector) = selector {
+        let _: () = msg_send(env, (target, selector, object));
+    }
+}
+
+- (())cancel {
+    env.objc.borrow_mut::<NSThreadHostObject>(this).cancelled = true;
+}
+
+- (bool)isExecuting {
+    let host_object = env.objc.borrow::<NSThreadHostObject>(this);
+    host_object.thread_id.is_some() && !host_object.finished
+}
+- (bool)isFinished {
+    env.objc.borrow::<NSThreadHostObject>(this).finished
+}
+- (bool)isCancelled {
+    env.objc.borrow::<NSThreadHostObject>(this).cancelled
+}
+
+- (bool)isMainThread {
+    env.objc.borrow::<NSThreadHostObject>(this).thread_id == Some(0)
+}
+
+- (id)name {
+    env.objc.borrow::<NSThreadHostObject>(this).name
+}
+- (())setName:(id)name { // NSString*
+    let name: id = msg![env; name copy];
+    let old = std::mem::replace(&mut env.objc.borrow_mut::<NSThreadHostObject>(this).name, name);
+    release(env, old);
+}
+
+- (f64)threadPriority {
+    env.objc.borrow::<NSThreadHostObject>(this).priority
+}
+- (bool)setThreadPriority:(f64)priority {
+    log!("TODO: [{:?} setThreadPriority:{:?}] (ignored)", this, priority);
+    env.objc.borrow_mut::<NSThreadHostObject>(this).priority = priority;
+    true
+}
+
+// TODO: stack size, thread dictionary, etc
 
 @end
 
 };
+
+/// Get the `NSThread*` for a guest thread, creating it if necessary (e.g. for
+/// the main thread or threads created with `pthread_create`).
+fn thread_object(env: &mut Environment, thread_id: ThreadId) -> id {
+    if let Some(&thread) = env
+        .framework_state
+        .foundation
+        .ns_thread
+        .threads
+        .get(&thread_id)
+    {
+        return thread;
+    }
+    let host_object = Box::new(NSThreadHostObject {
+        thread_id: Some(thread_id),
+        priority: 0.5,
+        ..Default::default()
+    });
+    let class = env.objc.get_known_class("NSThread", &mut env.mem);
+    // TODO: release objects for threads not created by NSThread once they exit
+    let thread = env
+        .objc
+        .alloc_static_object(class, host_object, &mut env.mem);
+    env.framework_state
+        .foundation
+        .ns_thread
+        .threads
+        .insert(thread_id, thread);
+    thread
+}
+
+fn current_thread(env: &mut Environment) -> id {
+    let current_thread = env.current_thread;
+    thread_object(env, current_thread)
+}
+
+/// For use by `performSelectorOnMainThread:withObject:waitUntilDone:`.
+pub(super) fn perform_selector_on_main_thread(
+    env: &mut Environment,
+    target: id,
+    selector: SEL,
+    object: id,
+    wait: bool,
+) {
+    if wait && env.current_thread == 0 {
+        let _: () = msg_send(env, (target, selector, object));
+        return;
+    }
+
+    log_dbg!(
+        "Thread {} scheduling [{:?} {}] on main thread (wait: {})",
+        env.current_thread,
+        target,
+        selector.as_str(&env.mem),
+        wait,
+    );
+    let timer = ns_timer::new_for_perform_request(env, 0.0, target, selector, object);
+    let run_loop: id = msg_class![env; NSRunLoop mainRunLoop];
+    let mode: id = ns_string::get_static_str(env, NSDefaultRunLoopMode);
+    let _: () = msg![env; run_loop addTimer:timer forMode:mode];
+
+    if wait {
+        // Let other threads (including the main thread) run until the timer
+        // has fired, which invalidates it.
+        while msg![env; timer isValid] {
+            env.sleep(Duration::from_millis(1), /* tail_call: */ false);
+        }
+    }
+
+    // The run loop owns the timer until it fires.
+    release(env, timer);
+}
+
+/// The start routine for threads created by `NSThread`.
+fn _touchHLE_NSThreadInvocationHelper(env: &mut Environment, thread: id) -> MutVoidPtr {
+    let _: () = msg![env; thread main];
+    log_dbg!(
+        "NSThread {:?} (thread {}) finished",
+        thread,
+        env.current_thread
+    );
+    env.objc.borrow_mut::<NSThreadHostObject>(thread).finished = true;
+    let current_thread = env.current_thread;
+    env.framework_state
+        .foundation
+        .ns_thread
+        .threads
+        .remove(&current_thread);
+    release(env, thread);
+    MutVoidPtr::null()
+}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(_touchHLE_NSThreadInvocationHelper(_))];
@@ -0,0 +1,194 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSTimeZone`.
+//!
+//! Only time zones with a fixed offset from GMT are supported, i.e. there is
+//! no daylight saving time. The system time zone is UTC unless the user picks
+//! another offset with the `--time-zone-offset=` option, so that apps see the
+//! same dates on every host.
+
+use super::{ns_string, NSInteger, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, retain, Class, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+struct NSTimeZoneHostObject {
+    seconds_from_gmt: i32,
+}
+impl HostObject for NSTimeZoneHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSTimeZone: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSTimeZoneHostObject {
+        seconds_from_gmt: 0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)timeZoneForSecondsFromGMT:(NSInteger)seconds {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new _touchHLE_initWithSecondsFromGMT:seconds];
+    autorelease(env, new)
+}
+
++ (id)timeZoneWithName:(id)name { // NSString*
+    let name_string = ns_string::to_rust_string(env, name);
+    let Some(seconds) = seconds_from_gmt_for_name(&name_string) else {
+        log!(
+            "TODO: Time zone {:?} is not supported, returning nil",
+            name_string
+        );
+        return nil;
+    };
+    msg![env; this timeZoneForSecondsFromGMT:seconds]
+}
++ (id)timeZoneWithAbbreviation:(id)abbreviation { // NSString*
+    msg![env; this timeZoneWithName:abbreviation]
+}
+
++ (id)systemTimeZone {
+    let seconds = system_seconds_from_gmt(env);
+    msg![env; this timeZoneForSecondsFromGMT:seconds]
+}
++ (id)localTimeZone {
+    msg![env; this systemTimeZone]
+}
++ (id)defaultTimeZone {
+    msg![env; this systemTimeZone]
+}
+
+- (id)_touchHLE_initWithSecondsFromGMT:(NSInteger)seconds {
+    env.objc
+        .borrow_mut::<NSTimeZoneHostObject>(this)
+        .seconds_from_gmt = seconds;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    // Time zones are immutable.
+    retain(env, this)
+}
+
+- (NSInteger)secondsFromGMT {
+    env.objc.borrow::<NSTimeZoneHostObject>(this).seconds_from_gmt
+}
+- (NSInteger)secondsFromGMTForDate:(id)_date { // NSDate*
+    msg![env; this secondsFromGMT]
+}
+- (bool)isDaylightSavingTime {
+    false
+}
+
+- (id)name {
+    let seconds = env.objc.borrow::<NSTimeZoneHostObject>(this).seconds_from_gmt;
+    let name = name_for_seconds_from_gmt(seconds);
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+- (id)abbreviation {
+    msg![env; this name]
+}
+- (id)description {
+    msg![env; this name]
+}
+
+- (bool)isEqualToTimeZone:(id)other { // NSTimeZone*
+    let a: NSInteger = msg![env; this secondsFromGMT];
+    let b: NSInteger = msg![env; other secondsFromGMT];
+    a == b
+}
+- (bool)isEqual:(id)other {
+    if this == other {
+        return true;
+    }
+    let class: Class = msg_class![env; NSTimeZone class];
+    if !msg![env; other isKindOfClass:class] {
+        return false;
+    }
+    msg![env; this isEqualToTimeZone:other]
+}
+- (NSUInteger)hash {
+    let seconds = env.objc.borrow::<NSTimeZoneHostObject>(this).seconds_from_gmt;
+    super::hash_helper(&seconds)
+}
+
+@end
+
+};
+
+/// Get the offset from GMT of the time zone the device is set to.
+pub fn system_seconds_from_gmt(env: &Environment) -> i32 {
+    env.options.time_zone_offset
+}
+
+/// Get the offset from GMT of an `NSTimeZone*`, or of the system time zone if
+/// it is [nil].
+pub fn seconds_from_gmt(env: &mut Environment, time_zone: id) -> i32 {
+    if time_zone == nil {
+        system_seconds_from_gmt(env)
+    } else {
+        msg![env; time_zone secondsFromGMT]
+    }
+}
+
+/// Make a name like `GMT+02:00` for a fixed offset.
+fn name_for_seconds_from_gmt(seconds: i32) -> String {
+    if seconds == 0 {
+        return "GMT".to_string();
+    }
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.unsigned_abs() / 60;
+    format!("GMT{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// Parse a time zone name, which can be one of the names used for UTC, or a
+/// fixed offset like `GMT+02:00`, `GMT+2` or `GMT+0200`.
+fn seconds_from_gmt_for_name(name: &str) -> Option<i32> {
+    if matches!(name, "UTC" | "GMT" | "Etc/UTC" | "Etc/GMT" | "Z") {
+        return Some(0);
+    }
+    let offset = name
+        .strip_prefix("GMT")
+        .or_else(|| name.strip_prefix("UTC"))?;
+    let (sign, offset) = if let Some(offset) = offset.strip_prefix('+') {
+        (1, offset)
+    } else {
+        (-1, offset.strip_prefix('-')?)
+    };
+    let (hours, minutes) = if let Some((hours, minutes)) = offset.split_once(':') {
+        (hours, minutes)
+    } else if offset.len() == 4 {
+        offset.split_at(2)
+    } else {
+        (offset, "0")
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if !(0..=23).contains(&hours) || !(0..=59).contains(&minutes) {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes) * 60)
+}
+
+#[cfg(test)]
+#[test]
+fn test_time_zone_names() {
+    assert_eq!(seconds_from_gmt_for_name("UTC"), Some(0));
+    assert_eq!(seconds_from_gmt_for_name("GMT+02:00"), Some(7200));
+    assert_eq!(seconds_from_gmt_for_name("GMT-0530"), Some(-19800));
+    assert_eq!(seconds_from_gmt_for_name("GMT+9"), Some(32400));
+    assert_eq!(seconds_from_gmt_for_name("Europe/Paris"), None);
+    assert_eq!(name_for_seconds_from_gmt(0), "GMT");
+    assert_eq!(name_for_seconds_from_gmt(-19800), "GMT-05:30");
+}
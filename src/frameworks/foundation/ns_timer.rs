@@ -7,7 +7,9 @@
 
 use super::ns_run_loop::NSDefaultRunLoopMode;
 use super::NSTimeInterval;
-use super::{ns_run_loop, ns_string};
+use super::{ns_date, ns_run_loop, ns_string};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::mem::MutVoidPtr;
 use crate::objc::{
     autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
     HostObject, SEL,
@@ -19,18 +21,36 @@ struct NSTimerHostObject {
     ns_interval: NSTimeInterval,
     /// Copy of `ns_interval` in Rust's type for time intervals. Keep in sync!
     rust_interval: Duration,
-    /// Strong reference
+    /// Strong reference, released when the timer is invalidated.
     target: id,
     selector: SEL,
-    /// Strong reference
+    /// Strong reference, released when the timer is invalidated.
     user_info: id,
     repeats: bool,
+    /// [None] once the timer is invalidated.
     due_by: Option<Instant>,
+    /// Only stored, since touchHLE always tries to fire timers on time.
+    tolerance: NSTimeInterval,
     /// Weak reference
     run_loop: id,
+    /// Used instead of `target` and `selector` by timers created with
+    /// `CFRunLoopTimerCreate`.
+    callout: Option<TimerCallout>,
+    /// Set for timers created by `performSelector:withObject:afterDelay:`,
+    /// which send `user_info` rather than the timer as the argument.
+    perform_request: bool,
 }
 impl HostObject for NSTimerHostObject {}
 
+#[derive(Copy, Clone)]
+struct TimerCallout {
+    /// `void (*callout)(CFRunLoopTimerRef timer, void *info)`
+    callout: GuestFunction,
+    info: MutVoidPtr,
+    /// `void (*release)(const void *info)`, may be NULL
+    release: GuestFunction,
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -56,8 +76,11 @@ pub const CLASSES: ClassExports = objc_classes! {
         selector,
         user_info,
         repeats,
-        due_by: Some(Instant::now().checked_add(rust_interval).unwrap()),
+        due_by: Some(env.clock.now().checked_add(rust_interval).unwrap()),
+        tolerance: 0.0,
         run_loop: nil,
+        callout: None,
+        perform_request: false,
     });
     let new = env.objc.alloc_object(this, host_object, &mut env.mem);
 
@@ -96,10 +119,21 @@ pub const CLASSES: ClassExports = objc_classes! {
     let &NSTimerHostObject {
         target,
         user_info,
+        callout,
         ..
     } = env.objc.borrow(this);
     release(env, target);
     release(env, user_info);
+    if let Some(TimerCallout {
+        info,
+        release: info_release,
+        ..
+    }) = callout
+    {
+        if info_release.addr_with_thumb_bit() != 0 {
+            let _: () = info_release.call_from_host(env, (info.cast_const(),));
+        }
+    }
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -118,12 +152,60 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<NSTimerHostObject>(this).due_by.is_some()
 }
 
+- (id)fireDate {
+    // TODO: return the last fire date for invalidated timers?
+    let Some(due_by) = env.objc.borrow::<NSTimerHostObject>(this).due_by else {
+        return nil;
+    };
+    let now = env.clock.now();
+    let offset = if due_by > now {
+        due_by.duration_since(now).as_secs_f64()
+    } else {
+        -now.duration_since(due_by).as_secs_f64()
+    };
+    let interval = ns_date::now_since_reference_date(env) + offset;
+    let date = ns_date::from_time_interval_since_reference_date(env, interval);
+    autorelease(env, date)
+}
+- (())setFireDate:(id)date { // NSDate*
+    let offset: NSTimeInterval = msg![env; date timeIntervalSinceNow];
+    let host_object = env.objc.borrow_mut::<NSTimerHostObject>(this);
+    // Setting the fire date of an invalidated timer does nothing.
+    if host_object.due_by.is_some() {
+        let offset = Duration::from_secs_f64(offset.max(0.0));
+        host_object.due_by = Some(env.clock.now().checked_add(offset).unwrap());
+    }
+}
+
+- (NSTimeInterval)tolerance {
+    env.objc.borrow::<NSTimerHostObject>(this).tolerance
+}
+- (())setTolerance:(NSTimeInterval)tolerance {
+    env.objc.borrow_mut::<NSTimerHostObject>(this).tolerance = tolerance.max(0.0);
+}
+
+- (())fire {
+    // Firing manually doesn't change when a repeating timer will next fire.
+    if env.objc.borrow::<NSTimerHostObject>(this).due_by.is_none() {
+        return;
+    }
+    let repeats = env.objc.borrow::<NSTimerHostObject>(this).repeats;
+    fire_timer(env, this);
+    if !repeats {
+        let _: () = msg![env; this invalidate];
+    }
+}
+
 -(())invalidate {
-    // Timer might already be invalid, don't try to remove it twice.
-    if env.objc.borrow_mut::<NSTimerHostObject>(this).due_by.take().is_some() {
-        let run_loop: id = msg_class![env; NSRunLoop currentRunLoop];
+    let host_object = env.objc.borrow_mut::<NSTimerHostObject>(this);
+    let was_valid = host_object.due_by.take().is_some();
+    let run_loop = host_object.run_loop;
+    // Timer might already be invalid, don't try to remove it twice. It also
+    // might not have been added to a run loop yet.
+    if was_valid && run_loop != nil {
         ns_run_loop::remove_timer(env, run_loop, this);
     }
+    release_target_and_user_info(env, this);
 }
 
 // TODO: more constructors
@@ -133,6 +215,135 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// For use by `CFRunLoopTimerCreate`: create a timer that calls a C function
+/// rather than sending a message. The caller owns the result.
+pub fn new_with_callout(
+    env: &mut Environment,
+    fire_after: NSTimeInterval,
+    ns_interval: NSTimeInterval,
+    callout: GuestFunction,
+    info: MutVoidPtr,
+    info_release: GuestFunction,
+) -> id {
+    // A zero or negative interval means a single-use timer.
+    let repeats = ns_interval > 0.0;
+    let ns_interval = ns_interval.max(0.0001);
+    let rust_interval = Duration::from_secs_f64(ns_interval);
+    let fire_after = Duration::from_secs_f64(fire_after.max(0.0));
+
+    let host_object = Box::new(NSTimerHostObject {
+        ns_interval,
+        rust_interval,
+        target: nil,
+        // Unused, since there is a callout.
+        selector: env
+            .objc
+            .register_host_selector("fire".to_string(), &mut env.mem),
+        user_info: nil,
+        repeats,
+        due_by: Some(env.clock.now().checked_add(fire_after).unwrap()),
+        tolerance: 0.0,
+        run_loop: nil,
+        callout: Some(TimerCallout {
+            callout,
+            info,
+            release: info_release,
+        }),
+        perform_request: false,
+    });
+    let class = env.objc.get_known_class("NSTimer", &mut env.mem);
+    let new = env.objc.alloc_object(class, host_object, &mut env.mem);
+
+    log_dbg!(
+        "New {} CFRunLoopTimer {:?}, interval {}s, callout {:?}, info {:?}",
+        if repeats { "repeating" } else { "single-use" },
+        new,
+        ns_interval,
+        callout,
+        info,
+    );
+
+    new
+}
+
+/// For use by `performSelector:withObject:afterDelay:`: create a single-use
+/// timer that sends `selector` to `target` with `object` as the argument. The
+/// target and object are retained until the timer is invalidated. The caller
+/// owns the result.
+pub(super) fn new_for_perform_request(
+    env: &mut Environment,
+    delay: NSTimeInterval,
+    target: id,
+    selector: SEL,
+    object: id,
+) -> id {
+    let ns_interval = 0.0001;
+    let rust_interval = Duration::from_secs_f64(ns_interval);
+    let delay = Duration::from_secs_f64(delay.max(0.0));
+
+    retain(env, target);
+    retain(env, object);
+
+    let host_object = Box::new(NSTimerHostObject {
+        ns_interval,
+        rust_interval,
+        target,
+        selector,
+        user_info: object,
+        repeats: false,
+        due_by: Some(env.clock.now().checked_add(delay).unwrap()),
+        tolerance: 0.0,
+        run_loop: nil,
+        callout: None,
+        perform_request: true,
+    });
+    let class = env.objc.get_known_class("NSTimer", &mut env.mem);
+    let new = env.objc.alloc_object(class, host_object, &mut env.mem);
+
+    log_dbg!(
+        "New perform request timer {:?}, delay {}s, target [{:?} {}], object {:?}",
+        new,
+        delay.as_secs_f64(),
+        target,
+        selector.as_str(&env.mem),
+        object,
+    );
+
+    new
+}
+
+/// For use by `cancelPreviousPerformRequestsWithTarget:`: check if a timer was
+/// created by `performSelector:withObject:afterDelay:` with this target and, if
+/// provided, this selector and object. Objects are compared with `isEqual:`.
+pub(super) fn is_matching_perform_request(
+    env: &mut Environment,
+    timer: id,
+    target: id,
+    selector_and_object: Option<(SEL, id)>,
+) -> bool {
+    let &NSTimerHostObject {
+        target: timer_target,
+        selector: timer_selector,
+        user_info: timer_object,
+        perform_request,
+        ..
+    } = env.objc.borrow(timer);
+    if !perform_request || timer_target != target {
+        return false;
+    }
+    let Some((selector, object)) = selector_and_object else {
+        return true;
+    };
+    if timer_selector != selector {
+        return false;
+    }
+    if timer_object == nil || object == nil {
+        timer_object == object
+    } else {
+        msg![env; timer_object isEqual:object]
+    }
+}
+
 /// For use by `NSRunLoop`
 pub(super) fn set_run_loop(env: &mut Environment, timer: id, run_loop: id) {
     let host_object = env.objc.borrow_mut::<NSTimerHostObject>(timer);
@@ -148,18 +359,20 @@ pub(super) fn handle_timer(env: &mut Environment, timer: id) -> Option<Instant>
     let &NSTimerHostObject {
         ns_interval,
         rust_interval,
-        target,
-        selector,
         repeats,
         due_by,
         run_loop,
         ..
     } = env.objc.borrow(timer);
 
-    // invalidated timers should have already been removed from the run loop
-    let due_by = due_by.unwrap();
+    // Invalidated timers are removed from the run loop, but the timer might
+    // have been invalidated by another timer's target earlier in the same
+    // run loop iteration. It must not fire again.
+    let Some(due_by) = due_by else {
+        return None;
+    };
 
-    let now = Instant::now();
+    let now = env.clock.now();
 
     if due_by > now {
         return Some(due_by);
@@ -201,20 +414,80 @@ pub(super) fn handle_timer(env: &mut Environment, timer: id) -> Option<Instant>
     };
     env.objc.borrow_mut::<NSTimerHostObject>(timer).due_by = new_due_by;
 
-    log_dbg!(
-        "Timer {:?} fired, sending {:?} message to {:?}",
-        timer,
-        selector.as_str(&env.mem),
-        target
-    );
+    fire_timer(env, timer);
+
+    // A single-use timer is invalidated once it fires.
+    if !repeats {
+        release_target_and_user_info(env, timer);
+    }
+
+    release(env, timer);
+
+    new_due_by
+}
+
+/// Send a timer's message (or call its callout), without affecting when it is
+/// next due.
+fn fire_timer(env: &mut Environment, timer: id) {
+    let &NSTimerHostObject {
+        target,
+        selector,
+        user_info,
+        callout,
+        perform_request,
+        ..
+    } = env.objc.borrow(timer);
+
+    // The target might invalidate the timer, which releases the target and
+    // user info, so keep them around until the message has been sent.
+    retain(env, timer);
+    retain(env, target);
+    retain(env, user_info);
 
     let pool: id = msg_class![env; NSAutoreleasePool new];
 
-    // Signature should be `- (void)timerDidFire:(NSTimer *)which`.
-    let _: () = msg_send(env, (target, selector, timer));
+    if let Some(TimerCallout { callout, info, .. }) = callout {
+        log_dbg!(
+            "Timer {:?} fired, calling {:?} with info {:?}",
+            timer,
+            callout,
+            info
+        );
+        let _: () = callout.call_from_host(env, (timer, info));
+    } else if perform_request {
+        log_dbg!(
+            "Perform request timer {:?} fired, sending {:?} message to {:?} with object {:?}",
+            timer,
+            selector.as_str(&env.mem),
+            target,
+            user_info
+        );
+        let _: () = msg_send(env, (target, selector, user_info));
+    } else {
+        log_dbg!(
+            "Timer {:?} fired, sending {:?} message to {:?}",
+            timer,
+            selector.as_str(&env.mem),
+            target
+        );
+
+        // Signature should be `- (void)timerDidFire:(NSTimer *)which`.
+        let _: () = msg_send(env, (target, selector, timer));
+    }
 
-    release(env, timer);
     release(env, pool);
 
-    new_due_by
+    release(env, user_info);
+    release(env, target);
+    release(env, timer);
+}
+
+/// Like in Apple's implementation, the target and user info are released as
+/// soon as a timer is invalidated, not when it is deallocated.
+fn release_target_and_user_info(env: &mut Environment, timer: id) {
+    let host_object = env.objc.borrow_mut::<NSTimerHostObject>(timer);
+    let target = std::mem::take(&mut host_object.target);
+    let user_info = std::mem::take(&mut host_object.user_info);
+    release(env, target);
+    release(env, user_info);
 }
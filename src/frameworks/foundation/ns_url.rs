@@ -5,7 +5,7 @@
  */
 //! `NSURL`.
 
-use super::ns_string::{to_rust_string, NSUTF8StringEncoding};
+use super::ns_string::{from_rust_string, to_rust_string, NSUTF8StringEncoding};
 use super::NSUInteger;
 use crate::fs::GuestPath;
 use crate::mem::MutPtr;
@@ -94,6 +94,19 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
+- (id)absoluteString {
+    match *env.objc.borrow(this) {
+        NSURLHostObject::FileURL { ns_string } => {
+            // FIXME: This should do escaping.
+            let path = to_rust_string(env, ns_string);
+            let string = from_rust_string(env, format!("file://{}", path));
+            autorelease(env, string)
+        }
+        // FIXME: don't assume URL is already absolute
+        NSURLHostObject::OtherURL { ns_string } => ns_string,
+    }
+}
+
 - (id)absoluteURL {
     // FIXME: don't assume URL is already absolute
     let &NSURLHostObject::OtherURL { ns_string } = env.objc.borrow(this) else {
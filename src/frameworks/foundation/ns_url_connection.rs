@@ -0,0 +1,543 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLConnection`.
+//!
+//! Requests are made by a small HTTP/1.0 client on the host, which only
+//! supports plain `http:` URLs (TODO: HTTPS). Network access is disabled unless
+//! the user passes `--allow-network-access`; otherwise every request fails as
+//! if the device were offline, so that runs are deterministic.
+//!
+//! Asynchronous requests are performed on a host thread, and the delegate is
+//! notified from the run loop the connection is scheduled in. The response
+//! body is delivered in a single `connection:didReceiveData:` message.
+
+use super::ns_error::{new_error, set_error, NSURLErrorDomain, NSURLErrorUnknown};
+use super::ns_property_list_serialization::new_data;
+use super::ns_run_loop::{self, NSRunLoopMode};
+use super::{ns_data, ns_string, ns_url_request, ns_url_response, NSTimeInterval};
+use crate::mem::MutPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+type RequestResult = Result<HttpResponse, String>;
+
+#[derive(Default)]
+struct NSURLConnectionHostObject {
+    /// `NSURLRequest*`
+    request: id,
+    /// Strong reference, released once the request has finished or been
+    /// cancelled.
+    delegate: id,
+    /// The run loop the delegate will be notified from. This is not a strong
+    /// reference while the connection isn't started, but the run loop holds
+    /// a strong reference to the connection while it is.
+    run_loop: Option<id>,
+    started: bool,
+    /// Receives the result from the host thread while the request is running.
+    receiver: Option<Receiver<RequestResult>>,
+}
+impl HostObject for NSURLConnectionHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLConnection: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSURLConnectionHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)sendSynchronousRequest:(id)request // NSURLRequest*
+           returningResponse:(MutPtr<id>)response_ptr // NSURLResponse**
+                       error:(MutPtr<id>)error_ptr { // NSError**
+    let http_request = http_request_from_ns_url_request(env, request);
+    log_dbg!(
+        "[NSURLConnection sendSynchronousRequest:{:?}] {} {:?}",
+        request,
+        http_request.method,
+        http_request.url,
+    );
+    let result = if env.options.network_access {
+        perform_request(&http_request)
+    } else {
+        Err("Network access is disabled".to_string())
+    };
+    match result {
+        Ok(http_response) => {
+            let url: id = msg![env; request URL];
+            let response = ns_url_response::new_http_response(
+                env,
+                url,
+                http_response.status_code,
+                http_response.headers,
+            );
+            let response = autorelease(env, response);
+            if !response_ptr.is_null() {
+                env.mem.write(response_ptr, response);
+            }
+            if !error_ptr.is_null() {
+                env.mem.write(error_ptr, nil);
+            }
+            let data = new_data(env, &http_response.body);
+            autorelease(env, data)
+        }
+        Err(error) => {
+            log!("Synchronous request for {:?} failed: {}", http_request.url, error);
+            if !response_ptr.is_null() {
+                env.mem.write(response_ptr, nil);
+            }
+            set_error(env, error_ptr, NSURLErrorDomain, NSURLErrorUnknown, Some(error));
+            nil
+        }
+    }
+}
+
++ (id)connectionWithRequest:(id)request // NSURLRequest*
+                   delegate:(id)delegate {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithRequest:request delegate:delegate];
+    autorelease(env, new)
+}
+
+- (id)initWithRequest:(id)request // NSURLRequest*
+             delegate:(id)delegate {
+    msg![env; this initWithRequest:request delegate:delegate startImmediately:true]
+}
+
+- (id)initWithRequest:(id)request // NSURLRequest*
+             delegate:(id)delegate
+     startImmediately:(bool)start_immediately {
+    let request: id = msg![env; request copy];
+    retain(env, delegate);
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(this);
+    host_object.request = request;
+    host_object.delegate = delegate;
+    if start_immediately {
+        let _: () = msg![env; this start];
+    }
+    this
+}
+
+- (())dealloc {
+    let &NSURLConnectionHostObject {
+        request, delegate, ..
+    } = env.objc.borrow(this);
+    release(env, request);
+    release(env, delegate);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())scheduleInRunLoop:(id)run_loop // NSRunLoop*
+                forMode:(NSRunLoopMode)_mode { // TODO: handle modes
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(this);
+    // TODO: scheduling in several run loops, or after starting
+    assert!(!host_object.started && host_object.run_loop.is_none());
+    host_object.run_loop = Some(run_loop);
+}
+
+- (())unscheduleFromRunLoop:(id)run_loop // NSRunLoop*
+                    forMode:(NSRunLoopMode)_mode {
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(this);
+    assert!(!host_object.started); // TODO
+    if host_object.run_loop == Some(run_loop) {
+        host_object.run_loop = None;
+    }
+}
+
+- (())start {
+    if env.objc.borrow::<NSURLConnectionHostObject>(this).started {
+        return;
+    }
+
+    let request = env.objc.borrow::<NSURLConnectionHostObject>(this).request;
+    let http_request = http_request_from_ns_url_request(env, request);
+    log_dbg!(
+        "Starting connection {:?} for {} {:?}",
+        this,
+        http_request.method,
+        http_request.url,
+    );
+
+    let (sender, receiver) = mpsc::channel();
+    if env.options.network_access {
+        std::thread::spawn(move || {
+            // The connection may have been cancelled in the meantime, in which
+            // case nobody is listening anymore.
+            let _ = sender.send(perform_request(&http_request));
+        });
+    } else {
+        sender
+            .send(Err("Network access is disabled".to_string()))
+            .unwrap();
+    }
+
+    let run_loop = match env.objc.borrow::<NSURLConnectionHostObject>(this).run_loop {
+        Some(run_loop) => run_loop,
+        None => msg_class![env; NSRunLoop currentRunLoop],
+    };
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(this);
+    host_object.started = true;
+    host_object.run_loop = Some(run_loop);
+    host_object.receiver = Some(receiver);
+    ns_run_loop::add_url_connection(env, run_loop, this);
+}
+
+- (())cancel {
+    log_dbg!("Cancelling connection {:?}", this);
+    finish(env, this);
+}
+
+// TODO: more accessors
+
+@end
+
+};
+
+/// For use by [ns_run_loop]: check if an asynchronous request has finished, and
+/// notify the delegate if so.
+pub(super) fn handle_connection(env: &mut Environment, connection: id) {
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(connection);
+    let Some(receiver) = &host_object.receiver else {
+        return;
+    };
+    let result = match receiver.try_recv() {
+        Ok(result) => result,
+        Err(TryRecvError::Empty) => return,
+        Err(TryRecvError::Disconnected) => Err("Request thread panicked".to_string()),
+    };
+    let &mut NSURLConnectionHostObject {
+        request, delegate, ..
+    } = host_object;
+
+    // Keep the connection and its delegate alive until the delegate has been
+    // notified, even though the connection is removed from the run loop.
+    retain(env, connection);
+    retain(env, delegate);
+    finish(env, connection);
+
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+
+    match result {
+        Ok(http_response) => {
+            log_dbg!(
+                "Connection {:?} finished with status {}",
+                connection,
+                http_response.status_code
+            );
+            let url: id = msg![env; request URL];
+            let response = ns_url_response::new_http_response(
+                env,
+                url,
+                http_response.status_code,
+                http_response.headers,
+            );
+            autorelease(env, response);
+            if env.objc.object_has_method_named(
+                &env.mem,
+                delegate,
+                "connection:didReceiveResponse:",
+            ) {
+                let _: () = msg![env; delegate connection:connection didReceiveResponse:response];
+            }
+            if !http_response.body.is_empty()
+                && env.objc.object_has_method_named(
+                    &env.mem,
+                    delegate,
+                    "connection:didReceiveData:",
+                )
+            {
+                let data = new_data(env, &http_response.body);
+                autorelease(env, data);
+                let _: () = msg![env; delegate connection:connection didReceiveData:data];
+            }
+            if env
+                .objc
+                .object_has_method_named(&env.mem, delegate, "connectionDidFinishLoading:")
+            {
+                let _: () = msg![env; delegate connectionDidFinishLoading:connection];
+            }
+        }
+        Err(error) => {
+            log!("Connection {:?} failed: {}", connection, error);
+            if env
+                .objc
+                .object_has_method_named(&env.mem, delegate, "connection:didFailWithError:")
+            {
+                let error = new_error(env, NSURLErrorDomain, NSURLErrorUnknown, Some(error));
+                let _: () = msg![env; delegate connection:connection didFailWithError:error];
+                release(env, error);
+            }
+        }
+    }
+
+    release(env, pool);
+    release(env, delegate);
+    release(env, connection);
+}
+
+/// Stop any request in progress, remove the connection from its run loop and
+/// release the delegate. This may deallocate the connection.
+fn finish(env: &mut Environment, connection: id) {
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(connection);
+    // Dropping the receiver means the host thread's result will be discarded.
+    let was_running = host_object.receiver.take().is_some();
+    let delegate = std::mem::take(&mut host_object.delegate);
+    let run_loop = host_object.run_loop;
+    release(env, delegate);
+    if was_running {
+        ns_run_loop::remove_url_connection(env, run_loop.unwrap(), connection);
+    }
+}
+
+#[derive(Debug)]
+struct HttpRequest {
+    url: String,
+    method: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    timeout: Duration,
+}
+
+#[derive(Debug, PartialEq)]
+struct HttpResponse {
+    status_code: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+fn http_request_from_ns_url_request(env: &mut Environment, request: id) -> HttpRequest {
+    let url: id = msg![env; request URL];
+    let url: id = msg![env; url absoluteString];
+    let url = ns_string::to_rust_string(env, url).into_owned();
+    let method: id = msg![env; request HTTPMethod];
+    let method = ns_string::to_rust_string(env, method).into_owned();
+    let body: id = msg![env; request HTTPBody];
+    let body = if body == nil {
+        Vec::new()
+    } else {
+        ns_data::to_rust_slice(env, body).to_vec()
+    };
+    let timeout: NSTimeInterval = msg![env; request timeoutInterval];
+    let timeout = if timeout > 0.0 {
+        Duration::from_secs_f64(timeout)
+    } else {
+        Duration::from_secs(60)
+    };
+    HttpRequest {
+        url,
+        method,
+        headers: ns_url_request::headers(env, request).to_vec(),
+        body,
+        timeout,
+    }
+}
+
+/// Split an `http:` URL into the host, port and path (including any query).
+fn parse_http_url(url: &str) -> Result<(&str, u16, String), String> {
+    let Some(rest) = url
+        .get(..7)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("http://"))
+        .map(|_| &url[7..])
+    else {
+        return Err(format!(
+            "Unsupported URL (only http: is supported): {:?}",
+            url
+        ));
+    };
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(idx) if rest.as_bytes()[idx] == b'?' => (&rest[..idx], format!("/{}", &rest[idx..])),
+        Some(idx) => (&rest[..idx], rest[idx..].to_string()),
+        None => (rest, "/".to_string()),
+    };
+    // Credentials in the URL are not supported.
+    let authority = authority.rsplit('@').next().unwrap();
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (
+            host,
+            port.parse()
+                .map_err(|_| format!("Invalid port in URL: {:?}", url))?,
+        ),
+        _ => (authority, 80),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(format!("No host in URL: {:?}", url));
+    }
+    Ok((host, port, path))
+}
+
+/// Perform an HTTP request, blocking until it completes or fails.
+fn perform_request(request: &HttpRequest) -> RequestResult {
+    let (host, port, path) = parse_http_url(&request.url)?;
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Could not resolve {:?}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("Could not resolve {:?}", host))?;
+    let mut stream = TcpStream::connect_timeout(&addr, request.timeout)
+        .map_err(|e| format!("Could not connect to {}: {}", addr, e))?;
+    stream.set_read_timeout(Some(request.timeout)).unwrap();
+    stream.set_write_timeout(Some(request.timeout)).unwrap();
+
+    // HTTP/1.0 is used so that the server will close the connection after
+    // the response and won't use chunked encoding, which keeps this simple.
+    let mut head = format!("{} {} HTTP/1.0\r\n", request.method, path);
+    if port == 80 {
+        head.push_str(&format!("Host: {}\r\n", host));
+    } else {
+        head.push_str(&format!("Host: {}:{}\r\n", host, port));
+    }
+    for (name, value) in &request.headers {
+        if ["Host", "Content-Length", "Connection"]
+            .iter()
+            .any(|skipped| name.eq_ignore_ascii_case(skipped))
+        {
+            continue;
+        }
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !request.body.is_empty() || request.method != "GET" {
+        head.push_str(&format!("Content-Length: {}\r\n", request.body.len()));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+
+    let io_error = |e: std::io::Error| format!("Error communicating with {}: {}", addr, e);
+    stream.write_all(head.as_bytes()).map_err(io_error)?;
+    stream.write_all(&request.body).map_err(io_error)?;
+    let mut raw_response = Vec::new();
+    stream.read_to_end(&mut raw_response).map_err(io_error)?;
+
+    parse_http_response(&raw_response)
+}
+
+fn parse_http_response(raw: &[u8]) -> RequestResult {
+    let head_end = raw
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or("Incomplete HTTP response")?;
+    let head = String::from_utf8_lossy(&raw[..head_end]);
+    let mut lines = head.split("\r\n");
+
+    let status_line = lines.next().unwrap();
+    let mut status_parts = status_line.split(' ');
+    let status_code = match (status_parts.next(), status_parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/") => code.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| format!("Invalid HTTP status line: {:?}", status_line))?;
+
+    let mut headers = Vec::new();
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(format!("Invalid HTTP header line: {:?}", line));
+        };
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(candidate, _)| candidate.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+
+    let mut body = &raw[head_end + 4..];
+    if let Some(length) = header("Content-Length").and_then(|length| length.parse().ok()) {
+        body = &body[..body.len().min(length)];
+    }
+    let body = if header("Transfer-Encoding")
+        .map_or(false, |encoding| encoding.eq_ignore_ascii_case("chunked"))
+    {
+        // Servers shouldn't do this in response to an HTTP/1.0 request, but
+        // some do anyway.
+        decode_chunked(body)?
+    } else {
+        body.to_vec()
+    };
+
+    Ok(HttpResponse {
+        status_code,
+        headers,
+        body,
+    })
+}
+
+fn decode_chunked(mut raw: &[u8]) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = raw
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or("Incomplete chunked HTTP response")?;
+        let size = String::from_utf8_lossy(&raw[..line_end]);
+        // Ignore chunk extensions.
+        let size = size.split(';').next().unwrap().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| format!("Invalid chunk size in HTTP response: {:?}", size))?;
+        raw = &raw[line_end + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        if raw.len() < size {
+            return Err("Incomplete chunked HTTP response".to_string());
+        }
+        body.extend_from_slice(&raw[..size]);
+        raw = raw[size..].strip_prefix(b"\r\n").unwrap_or(&raw[size..]);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_http() {
+    assert_eq!(
+        parse_http_url("http://example.com/scores?id=1"),
+        Ok(("example.com", 80, "/scores?id=1".to_string()))
+    );
+    assert_eq!(
+        parse_http_url("HTTP://127.0.0.1:8080"),
+        Ok(("127.0.0.1", 8080, "/".to_string()))
+    );
+    assert_eq!(
+        parse_http_url("http://[::1]:81/"),
+        Ok(("::1", 81, "/".to_string()))
+    );
+    assert_eq!(
+        parse_http_url("http://a.b?x"),
+        Ok(("a.b", 80, "/?x".to_string()))
+    );
+    assert!(parse_http_url("https://example.com/").is_err());
+    assert!(parse_http_url("http:///").is_err());
+
+    assert_eq!(
+        parse_http_response(b"HTTP/1.1 404 Not Found\r\nContent-Length: 2\r\nX-A: b c\r\n\r\nhi!!"),
+        Ok(HttpResponse {
+            status_code: 404,
+            headers: vec![
+                ("Content-Length".to_string(), "2".to_string()),
+                ("X-A".to_string(), "b c".to_string())
+            ],
+            body: b"hi".to_vec(),
+        })
+    );
+    assert_eq!(
+        parse_http_response(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2;x=y\r\nde\r\n0\r\n\r\n"
+        )
+        .unwrap()
+        .body,
+        b"abcde"
+    );
+    assert!(parse_http_response(b"HTTP/1.1 200 OK\r\n").is_err());
+    assert!(parse_http_response(b"garbage\r\n\r\n").is_err());
+}
@@ -0,0 +1,256 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLRequest` and `NSMutableURLRequest`.
+
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::{ns_string, NSTimeInterval, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, Class, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+pub type NSURLRequestCachePolicy = NSUInteger;
+
+const DEFAULT_TIMEOUT_INTERVAL: NSTimeInterval = 60.0;
+
+/// Belongs to `NSURLRequest` and `NSMutableURLRequest`.
+#[derive(Default)]
+struct NSURLRequestHostObject {
+    /// `NSURL*`
+    url: id,
+    /// `NSString*`, or nil for the default (`GET`).
+    http_method: id,
+    /// `NSData*`, may be nil.
+    http_body: id,
+    /// Header fields in the order they were set. Names are compared
+    /// case-insensitively.
+    headers: Vec<(String, String)>,
+    timeout_interval: NSTimeInterval,
+}
+impl HostObject for NSURLRequestHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLRequest: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSURLRequestHostObject {
+        timeout_interval: DEFAULT_TIMEOUT_INTERVAL,
+        ..Default::default()
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)requestWithURL:(id)url { // NSURL*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithURL:url];
+    autorelease(env, new)
+}
+
++ (id)requestWithURL:(id)url // NSURL*
+         cachePolicy:(NSURLRequestCachePolicy)cache_policy
+     timeoutInterval:(NSTimeInterval)timeout_interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithURL:url
+                                cachePolicy:cache_policy
+                            timeoutInterval:timeout_interval];
+    autorelease(env, new)
+}
+
+- (id)initWithURL:(id)url { // NSURL*
+    let url: id = msg![env; url copy];
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).url = url;
+    this
+}
+
+- (id)initWithURL:(id)url // NSURL*
+      cachePolicy:(NSURLRequestCachePolicy)_cache_policy // TODO: caching
+  timeoutInterval:(NSTimeInterval)timeout_interval {
+    let this: id = msg![env; this initWithURL:url];
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).timeout_interval = timeout_interval;
+    this
+}
+
+- (())dealloc {
+    let &NSURLRequestHostObject {
+        url,
+        http_method,
+        http_body,
+        ..
+    } = env.objc.borrow(this);
+    release(env, url);
+    release(env, http_method);
+    release(env, http_body);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let class = env.objc.get_known_class("NSURLRequest", &mut env.mem);
+    copy_request(env, this, class)
+}
+
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(NSZonePtr)_zone {
+    let class = env.objc.get_known_class("NSMutableURLRequest", &mut env.mem);
+    copy_request(env, this, class)
+}
+
+- (id)URL {
+    env.objc.borrow::<NSURLRequestHostObject>(this).url
+}
+
+- (id)HTTPMethod {
+    let http_method = env.objc.borrow::<NSURLRequestHostObject>(this).http_method;
+    if http_method == nil {
+        ns_string::get_static_str(env, "GET")
+    } else {
+        http_method
+    }
+}
+
+- (id)HTTPBody {
+    env.objc.borrow::<NSURLRequestHostObject>(this).http_body
+}
+
+- (NSTimeInterval)timeoutInterval {
+    env.objc.borrow::<NSURLRequestHostObject>(this).timeout_interval
+}
+
+- (id)allHTTPHeaderFields {
+    let headers = env.objc.borrow::<NSURLRequestHostObject>(this).headers.clone();
+    let dict = headers_to_dict(env, headers);
+    autorelease(env, dict)
+}
+
+- (id)valueForHTTPHeaderField:(id)field { // NSString*
+    let field = ns_string::to_rust_string(env, field);
+    let value = env
+        .objc
+        .borrow::<NSURLRequestHostObject>(this)
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(&field))
+        .map(|(_, value)| value.clone());
+    match value {
+        Some(value) => {
+            let value = ns_string::from_rust_string(env, value);
+            autorelease(env, value)
+        }
+        None => nil,
+    }
+}
+
+// TODO: more accessors
+
+@end
+
+@implementation NSMutableURLRequest: NSURLRequest
+
+- (())setURL:(id)url { // NSURL*
+    let url: id = msg![env; url copy];
+    let old = std::mem::replace(&mut env.objc.borrow_mut::<NSURLRequestHostObject>(this).url, url);
+    release(env, old);
+}
+
+- (())setHTTPMethod:(id)method { // NSString*
+    let method: id = msg![env; method copy];
+    let host_object = env.objc.borrow_mut::<NSURLRequestHostObject>(this);
+    let old = std::mem::replace(&mut host_object.http_method, method);
+    release(env, old);
+}
+
+- (())setHTTPBody:(id)body { // NSData*
+    let body: id = msg![env; body copy];
+    let host_object = env.objc.borrow_mut::<NSURLRequestHostObject>(this);
+    let old = std::mem::replace(&mut host_object.http_body, body);
+    release(env, old);
+}
+
+- (())setTimeoutInterval:(NSTimeInterval)timeout_interval {
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).timeout_interval = timeout_interval;
+}
+
+- (())setValue:(id)value // NSString*
+forHTTPHeaderField:(id)field { // NSString*
+    let field = ns_string::to_rust_string(env, field).into_owned();
+    let value = (value != nil).then(|| ns_string::to_rust_string(env, value).into_owned());
+    let headers = &mut env.objc.borrow_mut::<NSURLRequestHostObject>(this).headers;
+    headers.retain(|(name, _)| !name.eq_ignore_ascii_case(&field));
+    if let Some(value) = value {
+        headers.push((field, value));
+    }
+}
+
+- (())addValue:(id)value // NSString*
+forHTTPHeaderField:(id)field { // NSString*
+    let field = ns_string::to_rust_string(env, field).into_owned();
+    let value = ns_string::to_rust_string(env, value).into_owned();
+    let headers = &mut env.objc.borrow_mut::<NSURLRequestHostObject>(this).headers;
+    if let Some((_, existing)) = headers
+        .iter_mut()
+        .find(|(name, _)| name.eq_ignore_ascii_case(&field))
+    {
+        existing.push(',');
+        existing.push_str(&value);
+    } else {
+        headers.push((field, value));
+    }
+}
+
+// TODO: more setters
+
+@end
+
+};
+
+fn copy_request(env: &mut Environment, request: id, class: Class) -> id {
+    let &NSURLRequestHostObject {
+        url,
+        http_method,
+        http_body,
+        ref headers,
+        timeout_interval,
+    } = env.objc.borrow(request);
+    let host_object = Box::new(NSURLRequestHostObject {
+        url,
+        http_method,
+        http_body,
+        headers: headers.clone(),
+        timeout_interval,
+    });
+    retain(env, url);
+    retain(env, http_method);
+    retain(env, http_body);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+/// For use by `NSURLConnection`: get the header fields of a request.
+pub(super) fn headers(env: &Environment, request: id) -> &[(String, String)] {
+    &env.objc.borrow::<NSURLRequestHostObject>(request).headers
+}
+
+/// Create an `NSDictionary` of `NSString*` from HTTP header fields. The result
+/// is not autoreleased.
+pub(super) fn headers_to_dict(env: &mut Environment, headers: Vec<(String, String)>) -> id {
+    let keys_and_objects: Vec<(id, id)> = headers
+        .into_iter()
+        .map(|(name, value)| {
+            let name = ns_string::from_rust_string(env, name);
+            let value = ns_string::from_rust_string(env, value);
+            (name, value)
+        })
+        .collect();
+    let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+    for (name, value) in keys_and_objects {
+        release(env, name);
+        release(env, value);
+    }
+    dict
+}
@@ -0,0 +1,115 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLResponse` and `NSHTTPURLResponse`.
+
+use super::ns_url_request::headers_to_dict;
+use super::{ns_string, NSInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+/// Value of `expectedContentLength` if the length is not known.
+pub const NSURLResponseUnknownLength: i64 = -1;
+
+/// Belongs to `NSURLResponse` and `NSHTTPURLResponse`.
+#[derive(Default)]
+struct NSURLResponseHostObject {
+    /// `NSURL*`
+    url: id,
+    /// `NSString*`, may be nil.
+    mime_type: id,
+    expected_content_length: i64,
+    /// Only meaningful for `NSHTTPURLResponse`.
+    status_code: NSInteger,
+    /// Only meaningful for `NSHTTPURLResponse`.
+    headers: Vec<(String, String)>,
+}
+impl HostObject for NSURLResponseHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLResponse: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSURLResponseHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &NSURLResponseHostObject { url, mime_type, .. } = env.objc.borrow(this);
+    release(env, url);
+    release(env, mime_type);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)URL {
+    env.objc.borrow::<NSURLResponseHostObject>(this).url
+}
+
+- (id)MIMEType {
+    env.objc.borrow::<NSURLResponseHostObject>(this).mime_type
+}
+
+- (i64)expectedContentLength {
+    env.objc.borrow::<NSURLResponseHostObject>(this).expected_content_length
+}
+
+// TODO: more accessors
+
+@end
+
+@implementation NSHTTPURLResponse: NSURLResponse
+
+- (NSInteger)statusCode {
+    env.objc.borrow::<NSURLResponseHostObject>(this).status_code
+}
+
+- (id)allHeaderFields {
+    let headers = env.objc.borrow::<NSURLResponseHostObject>(this).headers.clone();
+    let dict = headers_to_dict(env, headers);
+    autorelease(env, dict)
+}
+
+@end
+
+};
+
+/// For use by `NSURLConnection`: create an `NSHTTPURLResponse` for a request
+/// to `url` (`NSURL*`). The result is not autoreleased.
+pub(super) fn new_http_response(
+    env: &mut Environment,
+    url: id,
+    status_code: u16,
+    headers: Vec<(String, String)>,
+) -> id {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(candidate, _)| candidate.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    let expected_content_length = header("Content-Length")
+        .and_then(|length| length.trim().parse().ok())
+        .unwrap_or(NSURLResponseUnknownLength);
+    let mime_type = header("Content-Type")
+        .map(|type_| type_.split(';').next().unwrap().trim().to_string())
+        .map_or(nil, |type_| ns_string::from_rust_string(env, type_));
+
+    let url: id = msg![env; url copy];
+    let response: id = msg_class![env; NSHTTPURLResponse alloc];
+    *env.objc.borrow_mut(response) = NSURLResponseHostObject {
+        url,
+        mime_type,
+        expected_content_length,
+        status_code: status_code.into(),
+        headers,
+    };
+    response
+}
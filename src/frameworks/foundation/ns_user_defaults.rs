@@ -5,18 +5,38 @@
  */
 //! `NSUserDefaults`.
 //!
+//! Only the app's persistent domain (see [app_domain]) and the registration
+//! domain are supported. Values set by the app are looked up in the former,
+//! falling back to the latter, which has the values from `registerDefaults:`
+//! and some system defaults.
+//!
 //! References:
 //! - Apple's [Preferences and Settings Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/UserDefaults/AboutPreferenceDomains/AboutPreferenceDomains.html).
 
-use super::ns_dictionary::dict_from_keys_and_objects;
-use super::ns_string;
-use crate::objc::{id, msg_class, objc_classes, ClassExports};
+use super::ns_dictionary::keys_and_objects;
+use super::ns_property_list_serialization::{
+    deserialize_plist, deserialize_plist_from_file, serialize_plist_to_file,
+};
+use super::ns_value::number_value;
+use super::{ns_string, NSInteger};
+use crate::fs::GuestPathBuf;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, Class, ClassExports,
+};
 use crate::Environment;
+use plist::Value;
+use std::io::Cursor;
 
 #[derive(Default)]
 pub struct State {
-    /// `NSDictionary*`
+    /// `NSUserDefaults*`
     standard_defaults: Option<id>,
+    /// `NSMutableDictionary*` with the registration domain. See
+    /// [registration_domain].
+    registration_domain: Option<id>,
+    /// `NSMutableDictionary*` with the app's persistent preferences. See
+    /// [app_domain].
+    app_domain: Option<id>,
 }
 impl State {
     fn get(env: &mut Environment) -> &mut State {
@@ -34,17 +54,369 @@ pub const CLASSES: ClassExports = objc_classes! {
     if let Some(existing) = State::get(env).standard_defaults {
         existing
     } else {
-        // TODO: Are there other default keys we need to set?
-        let langs_value: id = msg_class![env; NSLocale preferredLanguages];
-        let langs_key: id = ns_string::get_static_str(env, "AppleLanguages");
-        let new = dict_from_keys_and_objects(env, &[(langs_key, langs_value)]);
+        let new: id = msg![env; this new];
         State::get(env).standard_defaults = Some(new);
         new
     }
 }
 
-// TODO: plist methods etc
+- (id)objectForKey:(id)key { // NSString*
+    let app_domain = app_domain(env);
+    let value: id = msg![env; app_domain objectForKey:key];
+    if value != nil {
+        return value;
+    }
+    let registration_domain = registration_domain(env);
+    msg![env; registration_domain objectForKey:key]
+}
+- (())setObject:(id)object
+         forKey:(id)key { // NSString*
+    if object == nil {
+        return msg![env; this removeObjectForKey:key];
+    }
+    // The value is copied so that later changes to a mutable object don't
+    // affect the stored value.
+    let object: id = msg![env; object copy];
+    let app_domain = app_domain(env);
+    () = msg![env; app_domain setObject:object forKey:key];
+    release(env, object);
+}
+- (())removeObjectForKey:(id)key { // NSString*
+    // This doesn't affect the registration domain.
+    let app_domain = app_domain(env);
+    msg![env; app_domain removeObjectForKey:key]
+}
+
+- (())registerDefaults:(id)defaults { // NSDictionary*
+    let registration_domain = registration_domain(env);
+    for (key, value) in keys_and_objects(env, defaults) {
+        () = msg![env; registration_domain setObject:value forKey:key];
+    }
+}
+
+- (id)dictionaryRepresentation {
+    let registration_domain = registration_domain(env);
+    let dict: id = msg![env; registration_domain mutableCopy];
+    let app_domain = app_domain(env);
+    for (key, value) in keys_and_objects(env, app_domain) {
+        () = msg![env; dict setObject:value forKey:key];
+    }
+    autorelease(env, dict)
+}
+
+- (id)stringForKey:(id)key { // NSString*
+    let value: id = msg![env; this objectForKey:key];
+    if is_kind_of(env, value, "NSString") {
+        value
+    } else if is_kind_of(env, value, "NSNumber") {
+        // Numbers are converted to strings.
+        let string = match number_value(env, value).as_i128() {
+            Some(integer) => integer.to_string(),
+            None => number_value(env, value).as_f64().to_string(),
+        };
+        let string = ns_string::from_rust_string(env, string);
+        autorelease(env, string)
+    } else {
+        nil
+    }
+}
+- (id)arrayForKey:(id)key { // NSString*
+    let value: id = msg![env; this objectForKey:key];
+    if is_kind_of(env, value, "NSArray") {
+        value
+    } else {
+        nil
+    }
+}
+- (id)dictionaryForKey:(id)key { // NSString*
+    let value: id = msg![env; this objectForKey:key];
+    if is_kind_of(env, value, "NSDictionary") {
+        value
+    } else {
+        nil
+    }
+}
+- (id)dataForKey:(id)key { // NSString*
+    let value: id = msg![env; this objectForKey:key];
+    if is_kind_of(env, value, "NSData") {
+        value
+    } else {
+        nil
+    }
+}
+
+- (NSInteger)integerForKey:(id)key { // NSString*
+    number_for_key(env, this, key).map_or(0, |number| number as NSInteger)
+}
+- (f32)floatForKey:(id)key { // NSString*
+    number_for_key(env, this, key).map_or(0.0, |number| number as f32)
+}
+- (f64)doubleForKey:(id)key { // NSString*
+    number_for_key(env, this, key).unwrap_or(0.0)
+}
+- (bool)boolForKey:(id)key { // NSString*
+    let value: id = msg![env; this objectForKey:key];
+    if is_kind_of(env, value, "NSString") {
+        string_bool_value(&ns_string::to_rust_string(env, value))
+    } else {
+        number_for_key(env, this, key).map_or(false, |number| number != 0.0)
+    }
+}
+
+- (())setInteger:(NSInteger)value
+          forKey:(id)key { // NSString*
+    let number: id = msg_class![env; NSNumber numberWithInteger:value];
+    msg![env; this setObject:number forKey:key]
+}
+- (())setFloat:(f32)value
+        forKey:(id)key { // NSString*
+    let number: id = msg_class![env; NSNumber numberWithFloat:value];
+    msg![env; this setObject:number forKey:key]
+}
+- (())setDouble:(f64)value
+         forKey:(id)key { // NSString*
+    let number: id = msg_class![env; NSNumber numberWithDouble:value];
+    msg![env; this setObject:number forKey:key]
+}
+- (())setBool:(bool)value
+       forKey:(id)key { // NSString*
+    let number: id = msg_class![env; NSNumber numberWithBool:value];
+    msg![env; this setObject:number forKey:key]
+}
+
+- (bool)synchronize {
+    synchronize_app_domain(env)
+}
 
 @end
 
 };
+
+fn is_kind_of(env: &mut Environment, object: id, class_name: &str) -> bool {
+    let class: Class = env.objc.get_known_class(class_name, &mut env.mem);
+    msg![env; object isKindOfClass:class]
+}
+
+/// Get a value as a number, for the typed accessors like `integerForKey:`.
+/// Strings are parsed, ignoring anything after the number.
+fn number_for_key(env: &mut Environment, defaults: id, key: id) -> Option<f64> {
+    let value: id = msg![env; defaults objectForKey:key];
+    if is_kind_of(env, value, "NSNumber") {
+        Some(msg![env; value doubleValue])
+    } else if is_kind_of(env, value, "NSString") {
+        parse_number_prefix(&ns_string::to_rust_string(env, value))
+    } else {
+        None
+    }
+}
+
+fn parse_number_prefix(string: &str) -> Option<f64> {
+    let string = string.trim_start();
+    (1..=string.len())
+        .rev()
+        .find_map(|len| string.get(..len)?.parse().ok())
+}
+
+/// Like `NSString`'s `boolValue`: strings starting with "Y", "T" or a non-zero
+/// digit are true.
+fn string_bool_value(string: &str) -> bool {
+    let string = string.trim_start().trim_start_matches(['+', '-', '0']);
+    matches!(
+        string.chars().next(),
+        Some('Y' | 'y' | 'T' | 't' | '1'..='9')
+    )
+}
+
+/// Get the `NSMutableDictionary*` with the registration domain, creating it
+/// with the system defaults and those from the app's `Settings.bundle` the
+/// first time.
+fn registration_domain(env: &mut Environment) -> id {
+    if let Some(existing) = State::get(env).registration_domain {
+        return existing;
+    }
+
+    let dict: id = msg_class![env; NSMutableDictionary new];
+    // TODO: Are there other default keys we need to set?
+    let langs_value: id = msg_class![env; NSLocale preferredLanguages];
+    let langs_key: id = ns_string::get_static_str(env, "AppleLanguages");
+    () = msg![env; dict setObject:langs_value forKey:langs_key];
+
+    for (key, value) in settings_bundle_defaults(env) {
+        let key = ns_string::from_rust_string(env, key);
+        let value = deserialize_plist(env, &value, /* mutable: */ false);
+        () = msg![env; dict setObject:value forKey:key];
+        release(env, key);
+        release(env, value);
+    }
+
+    State::get(env).registration_domain = Some(dict);
+    dict
+}
+
+/// Read the default values of the preferences declared in the app's
+/// `Settings.bundle`. On a real device, the Settings app only stores these
+/// once the user looks at the app's settings, but many apps assume that has
+/// happened and would otherwise read zeroes on first launch, so they are
+/// put in the registration domain.
+fn settings_bundle_defaults(env: &mut Environment) -> Vec<(String, Value)> {
+    let settings_bundle_path = env.bundle.settings_bundle_path();
+    let mut defaults = Vec::new();
+    let mut pages = vec!["Root".to_string()];
+    let mut visited_pages = Vec::new();
+    while let Some(page) = pages.pop() {
+        // Guard against pages that link to each other.
+        if visited_pages.contains(&page) {
+            continue;
+        }
+        let path = settings_bundle_path.join(format!("{}.plist", page));
+        visited_pages.push(page);
+        let Ok(bytes) = env.fs.read(&path) else {
+            log_dbg!("No settings page at {:?}", path);
+            continue;
+        };
+        let Ok(plist) = Value::from_reader(Cursor::new(bytes)) else {
+            log!("Warning: Couldn't parse settings page {:?}, ignoring", path);
+            continue;
+        };
+        parse_settings_page(&plist, &mut defaults, &mut pages);
+    }
+    if !defaults.is_empty() {
+        log!(
+            "Using the default values of {} preferences from the app's Settings.bundle",
+            defaults.len()
+        );
+    }
+    defaults
+}
+
+/// Collect the default values of the preferences on a settings page, and the
+/// names of the pages it links to.
+fn parse_settings_page(
+    page: &Value,
+    defaults: &mut Vec<(String, Value)>,
+    child_pages: &mut Vec<String>,
+) {
+    let Some(specifiers) = page
+        .as_dictionary()
+        .and_then(|page| page.get("PreferenceSpecifiers"))
+        .and_then(Value::as_array)
+    else {
+        return;
+    };
+    for specifier in specifiers.iter().filter_map(Value::as_dictionary) {
+        let get_string = |key| specifier.get(key).and_then(Value::as_string);
+        if get_string("Type") == Some("PSChildPaneSpecifier") {
+            if let Some(file) = get_string("File") {
+                child_pages.push(file.to_string());
+            }
+        } else if let (Some(key), Some(value)) = (get_string("Key"), specifier.get("DefaultValue"))
+        {
+            defaults.push((key.to_string(), value.clone()));
+        }
+    }
+}
+
+/// Guest path of the file the app's persistent preferences are stored in.
+fn app_domain_path(env: &Environment) -> GuestPathBuf {
+    env.fs.home_directory().join(format!(
+        "Library/Preferences/{}.plist",
+        env.bundle.bundle_identifier()
+    ))
+}
+
+/// Get the `NSMutableDictionary*` with the app's persistent preferences,
+/// loading it from disk the first time. This is the backing store shared by
+/// `NSUserDefaults` and `CFPreferences`.
+pub fn app_domain(env: &mut Environment) -> id {
+    if let Some(existing) = State::get(env).app_domain {
+        return existing;
+    }
+
+    let path = app_domain_path(env);
+    let loaded = if env.fs.exists(&path) {
+        deserialize_plist_from_file(env, &path, /* array_expected: */ false)
+    } else {
+        nil
+    };
+    let dict: id = if loaded == nil {
+        log_dbg!("No preferences at {:?}, starting empty", path);
+        msg_class![env; NSMutableDictionary new]
+    } else {
+        log_dbg!("Loaded preferences from {:?}", path);
+        let dict: id = msg![env; loaded mutableCopy];
+        release(env, loaded);
+        dict
+    };
+    State::get(env).app_domain = Some(dict);
+    dict
+}
+
+/// Write the app's persistent preferences to disk, if they have been loaded.
+/// Returns [false] on failure.
+pub fn synchronize_app_domain(env: &mut Environment) -> bool {
+    let Some(dict) = State::get(env).app_domain else {
+        return true;
+    };
+    let path = app_domain_path(env);
+    serialize_plist_to_file(env, &path, dict)
+}
+
+#[cfg(test)]
+#[test]
+fn test_number_parsing() {
+    assert_eq!(parse_number_prefix(" 42"), Some(42.0));
+    assert_eq!(parse_number_prefix("-1.5 lives"), Some(-1.5));
+    assert_eq!(parse_number_prefix("high"), None);
+    assert!(string_bool_value("YES"));
+    assert!(string_bool_value(" true"));
+    assert!(string_bool_value("-007"));
+    assert!(!string_bool_value("NO"));
+    assert!(!string_bool_value("0"));
+    assert!(!string_bool_value(""));
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_settings_page() {
+    use plist::Dictionary;
+
+    let specifier = |pairs: &[(&str, Value)]| {
+        let mut dict = Dictionary::new();
+        for (key, value) in pairs {
+            dict.insert(key.to_string(), value.clone());
+        }
+        Value::Dictionary(dict)
+    };
+    let page = specifier(&[(
+        "PreferenceSpecifiers",
+        Value::Array(vec![
+            specifier(&[("Type", "PSGroupSpecifier".into())]),
+            specifier(&[
+                ("Type", "PSToggleSwitchSpecifier".into()),
+                ("Key", "sound_enabled".into()),
+                ("DefaultValue", true.into()),
+            ]),
+            specifier(&[
+                ("Type", "PSSliderSpecifier".into()),
+                ("Key", "volume".into()),
+                ("DefaultValue", 0.5.into()),
+            ]),
+            specifier(&[
+                ("Type", "PSChildPaneSpecifier".into()),
+                ("File", "Advanced".into()),
+            ]),
+        ]),
+    )]);
+
+    let mut defaults = Vec::new();
+    let mut child_pages = Vec::new();
+    parse_settings_page(&page, &mut defaults, &mut child_pages);
+    assert_eq!(
+        defaults,
+        vec![
+            ("sound_enabled".to_string(), Value::Boolean(true)),
+            ("volume".to_string(), Value::Real(0.5)),
+        ]
+    );
+    assert_eq!(child_pages, vec!["Advanced".to_string()]);
+}
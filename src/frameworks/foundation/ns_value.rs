@@ -5,28 +5,127 @@
  */
 //! The `NSValue` class cluster, including `NSNumber`.
 
-use super::NSUInteger;
+use super::{NSInteger, NSUInteger};
+use crate::frameworks::core_animation::ca_transform_3d::CATransform3D;
+use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
 use crate::objc::{
-    autorelease, id, msg, msg_class, objc_classes, retain, Class, ClassExports, HostObject,
-    NSZonePtr,
+    autorelease, id, msg, msg_class, objc_classes, release, retain, Class, ClassExports,
+    HostObject, NSZonePtr, ObjC,
 };
+use crate::Environment;
 
-enum NSNumberHostObject {
+#[derive(Default)]
+pub struct State {
+    /// The shared `NSNumber`s for `NO` and `YES`, see [shared_boolean].
+    booleans: [Option<id>; 2],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NSNumberHostObject {
     Bool(bool),
     UnsignedLongLong(u64),
     LongLong(i64),
     Double(f64),
 }
 impl HostObject for NSNumberHostObject {}
+impl NSNumberHostObject {
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            NSNumberHostObject::Bool(value) => value as u8 as f64,
+            NSNumberHostObject::UnsignedLongLong(value) => value as f64,
+            NSNumberHostObject::LongLong(value) => value as f64,
+            NSNumberHostObject::Double(value) => value,
+        }
+    }
+    /// Get the value as an integer, if it is one. Every `u64` and `i64` fits
+    /// in an `i128`, so this is lossless.
+    pub fn as_i128(&self) -> Option<i128> {
+        match *self {
+            NSNumberHostObject::Bool(value) => Some(value as i128),
+            NSNumberHostObject::UnsignedLongLong(value) => Some(value as i128),
+            NSNumberHostObject::LongLong(value) => Some(value as i128),
+            NSNumberHostObject::Double(value) => {
+                if value.fract() == 0.0 && value.abs() < 2f64.powi(127) {
+                    Some(value as i128)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+    /// Integer value, with the usual truncating C conversion for doubles.
+    fn as_i64(&self) -> i64 {
+        match *self {
+            NSNumberHostObject::Double(value) => value as i64,
+            _ => self.as_i128().unwrap() as i64,
+        }
+    }
+}
+
+/// Host object for `NSValue` instances that wrap a struct. Only the structs
+/// from UIKit's and Core Animation's `NSValue` additions are supported.
+enum NSValueHostObject {
+    CGPoint(CGPoint),
+    CGSize(CGSize),
+    CGRect(CGRect),
+    CATransform3D(CATransform3D),
+}
+impl HostObject for NSValueHostObject {}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
-// NSValue is an abstract class. None of the things it should provide are
-// implemented here yet (TODO).
+// NSValue is an abstract class. Only the UIKit additions for wrapping geometry
+// structs are implemented here so far (TODO).
 @implementation NSValue: NSObject
 
++ (id)valueWithCGPoint:(CGPoint)point {
+    let host_object = Box::new(NSValueHostObject::CGPoint(point));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
++ (id)valueWithCGSize:(CGSize)size {
+    let host_object = Box::new(NSValueHostObject::CGSize(size));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
++ (id)valueWithCGRect:(CGRect)rect {
+    let host_object = Box::new(NSValueHostObject::CGRect(rect));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
++ (id)valueWithCATransform3D:(CATransform3D)transform {
+    let host_object = Box::new(NSValueHostObject::CATransform3D(transform));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
+
+- (CGPoint)CGPointValue {
+    let &NSValueHostObject::CGPoint(point) = env.objc.borrow(this) else {
+        panic!("{:?} is not a CGPoint value", this);
+    };
+    point
+}
+- (CGSize)CGSizeValue {
+    let &NSValueHostObject::CGSize(size) = env.objc.borrow(this) else {
+        panic!("{:?} is not a CGSize value", this);
+    };
+    size
+}
+- (CGRect)CGRectValue {
+    let &NSValueHostObject::CGRect(rect) = env.objc.borrow(this) else {
+        panic!("{:?} is not a CGRect value", this);
+    };
+    rect
+}
+- (CATransform3D)CATransform3DValue {
+    let &NSValueHostObject::CATransform3D(transform) = env.objc.borrow(this) else {
+        panic!("{:?} is not a CATransform3D value", this);
+    };
+    transform
+}
+
 // NSCopying implementation
 - (id)copyWithZone:(NSZonePtr)_zone {
     retain(env, this)
@@ -43,7 +142,9 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 + (id)numberWithBool:(bool)value {
-    // TODO: for greater efficiency we could return a static-lifetime value
+    if this == env.objc.get_known_class("NSNumber", &mut env.mem) {
+        return shared_boolean(env, value);
+    }
 
     let new: id = msg![env; this alloc];
     let new: id = msg![env; new initWithBool:value];
@@ -74,9 +175,30 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
-// TODO: types other than booleans and long longs
++ (id)numberWithInt:(i32)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithInt:value];
+    autorelease(env, new)
+}
++ (id)numberWithInteger:(NSInteger)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithInteger:value];
+    autorelease(env, new)
+}
++ (id)numberWithFloat:(f32)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithFloat:value];
+    autorelease(env, new)
+}
+
+// TODO: more types
 
 - (id)initWithBool:(bool)value {
+    if ObjC::read_isa(this, &env.mem) == env.objc.get_known_class("NSNumber", &mut env.mem) {
+        release(env, this);
+        let shared = shared_boolean(env, value);
+        return retain(env, shared);
+    }
     *env.objc.borrow_mut(this) = NSNumberHostObject::Bool(value);
     this
 }
@@ -96,11 +218,30 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithInt:(i32)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::LongLong(value.into());
+    this
+}
+- (id)initWithInteger:(NSInteger)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::LongLong(value.into());
+    this
+}
+- (id)initWithFloat:(f32)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::Double(value.into());
+    this
+}
+
 - (NSUInteger)hash {
-    let &NSNumberHostObject::Bool(value) = env.objc.borrow(this) else {
-        todo!();
-    };
-    super::hash_helper(&value)
+    // Numbers that compare equal must have the same hash, regardless of type.
+    let value = env.objc.borrow::<NSNumberHostObject>(this);
+    if let Some(value) = value.as_i128() {
+        super::hash_helper(&value)
+    } else {
+        super::hash_helper(&value.as_f64().to_bits())
+    }
+}
+- (bool)isEqual:(id)other {
+    msg![env; this isEqualTo:other]
 }
 - (bool)isEqualTo:(id)other {
     if this == other {
@@ -110,17 +251,56 @@ pub const CLASSES: ClassExports = objc_classes! {
     if !msg![env; other isKindOfClass:class] {
         return false;
     }
-    let &NSNumberHostObject::Bool(a) = env.objc.borrow(this) else {
-        todo!();
-    };
-    let &NSNumberHostObject::Bool(b) = env.objc.borrow(other) else {
-        todo!();
-    };
-    a == b
+    let a = *env.objc.borrow::<NSNumberHostObject>(this);
+    let b = *env.objc.borrow::<NSNumberHostObject>(other);
+    match (a.as_i128(), b.as_i128()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.as_f64() == b.as_f64(),
+    }
+}
+
+- (f64)doubleValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_f64()
+}
+- (f32)floatValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_f64() as f32
+}
+- (bool)boolValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_f64() != 0.0
+}
+- (i32)intValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as i32
+}
+- (NSInteger)integerValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as NSInteger
+}
+- (i64)longLongValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64()
 }
 
-// TODO: accessors etc
+// TODO: more accessors etc
 
 @end
 
 };
+
+/// Get the shared `NSNumber` for a boolean value. Like in Apple's
+/// implementation, `numberWithBool:` and `initWithBool:` always return one of
+/// these two objects, which are also `kCFBooleanFalse` and `kCFBooleanTrue`.
+/// They are never deallocated, and the result is not retained.
+pub fn shared_boolean(env: &mut Environment, value: bool) -> id {
+    let index = usize::from(value);
+    if let Some(number) = env.framework_state.foundation.ns_value.booleans[index] {
+        return number;
+    }
+    let class = env.objc.get_known_class("NSNumber", &mut env.mem);
+    let host_object = Box::new(NSNumberHostObject::Bool(value));
+    let number = env.objc.alloc_object(class, host_object, &mut env.mem);
+    env.framework_state.foundation.ns_value.booleans[index] = Some(number);
+    number
+}
+
+/// For use by `CFNumber`: get the value of an `NSNumber` in its original type.
+pub fn number_value(env: &Environment, number: id) -> NSNumberHostObject {
+    *env.objc.borrow::<NSNumberHostObject>(number)
+}
@@ -341,178 +341,235 @@ fn alSourcefv(env: &mut Environment, source: ALuint, param: ALenum, values: Cons
     unsafe { al::alSourcefv(source, param, values) };
 }
 
-// TODO: more functions
-
-// Note: For some reasons Wolf3d registers many OpenAl functions, but actually uses only few ones.
-// To workaround this, we just provide stubs
+/// Number of values a vector getter or setter reads or writes for a parameter.
+fn param_value_count(param: ALenum) -> GuestUSize {
+    match param {
+        al::AL_POSITION | al::AL_DIRECTION | al::AL_VELOCITY => 3,
+        al::AL_ORIENTATION => 6,
+        _ => 1,
+    }
+}
 
 fn alcGetContextsDevice(
-    _env: &mut Environment,
-    _context: MutPtr<GuestALCcontext>,
+    env: &mut Environment,
+    context: MutPtr<GuestALCcontext>,
 ) -> MutPtr<GuestALCdevice> {
-    todo!();
-}
-fn alcGetCurrentContext(_env: &mut Environment) -> MutPtr<GuestALCcontext> {
-    todo!();
-}
-fn alcGetEnumValue(
-    _env: &mut Environment,
-    _device: MutPtr<GuestALCdevice>,
-    _enumName: ConstPtr<u8>,
-) -> ALenum {
-    todo!();
+    let state = State::get(env);
+    let &host_context = state.contexts.get(&context).unwrap();
+    let host_device = unsafe { al::alcGetContextsDevice(host_context) };
+    let (&guest_device, _) = state
+        .devices
+        .iter()
+        .find(|&(_, &device)| device == host_device)
+        .unwrap();
+    guest_device
+}
+fn alcGetCurrentContext(env: &mut Environment) -> MutPtr<GuestALCcontext> {
+    let host_context = unsafe { al::alcGetCurrentContext() };
+    if host_context.is_null() {
+        return Ptr::null();
+    }
+    let (&guest_context, _) = State::get(env)
+        .contexts
+        .iter()
+        .find(|&(_, &context)| context == host_context)
+        .unwrap();
+    guest_context
 }
-fn alcGetIntegerv(
-    _env: &mut Environment,
-    _device: MutPtr<GuestALCdevice>,
-    _param: ALenum,
-    _size: ALCsizei,
-    _values: MutPtr<ALCint>,
-) {
-    todo!();
+
+fn alEnable(_env: &mut Environment, capability: ALenum) {
+    unsafe { al::alEnable(capability) };
 }
-fn alcGetString(
-    _env: &mut Environment,
-    _device: MutPtr<GuestALCdevice>,
-    _param: ALenum,
-) -> ConstPtr<u8> {
-    todo!();
+fn alDisable(_env: &mut Environment, capability: ALenum) {
+    unsafe { al::alDisable(capability) };
 }
-fn alcIsExtensionPresent(
-    _env: &mut Environment,
-    _device: MutPtr<GuestALCdevice>,
-    _extName: ConstPtr<u8>,
-) -> ALCboolean {
-    0
+fn alIsEnabled(_env: &mut Environment, capability: ALenum) -> ALboolean {
+    unsafe { al::alIsEnabled(capability) }
 }
-fn alcProcessContext(_env: &mut Environment, _context: MutPtr<GuestALCcontext>) {
-    todo!();
+
+fn alGetBoolean(_env: &mut Environment, param: ALenum) -> ALboolean {
+    let mut value = 0;
+    unsafe { al::alGetBooleanv(param, &mut value) };
+    value
 }
-fn alcSuspendContext(_env: &mut Environment, _context: MutPtr<GuestALCcontext>) {
-    todo!();
+fn alGetBooleanv(env: &mut Environment, param: ALenum, values: MutPtr<ALboolean>) {
+    let values = env.mem.ptr_at_mut(values, param_value_count(param));
+    unsafe { al::alGetBooleanv(param, values) };
 }
-fn alIsBuffer(_env: &mut Environment, _buffer: ALuint) -> ALboolean {
-    todo!();
+fn alGetDouble(_env: &mut Environment, param: ALenum) -> ALdouble {
+    let mut value = 0.0;
+    unsafe { al::alGetDoublev(param, &mut value) };
+    value
 }
-fn alGetBufferf(_env: &mut Environment, _buffer: ALuint, _param: ALenum, _value: MutPtr<ALfloat>) {
-    todo!();
+fn alGetDoublev(env: &mut Environment, param: ALenum, values: MutPtr<ALdouble>) {
+    let values = env.mem.ptr_at_mut(values, param_value_count(param));
+    unsafe { al::alGetDoublev(param, values) };
 }
-fn alGetBufferi(_env: &mut Environment, _buffer: ALuint, _param: ALenum, _value: MutPtr<ALint>) {
-    todo!();
+fn alGetFloat(_env: &mut Environment, param: ALenum) -> ALfloat {
+    let mut value = 0.0;
+    unsafe { al::alGetFloatv(param, &mut value) };
+    value
 }
-fn alEnable(_env: &mut Environment, _capability: ALenum) {
-    todo!();
+fn alGetFloatv(env: &mut Environment, param: ALenum, values: MutPtr<ALfloat>) {
+    let values = env.mem.ptr_at_mut(values, param_value_count(param));
+    unsafe { al::alGetFloatv(param, values) };
 }
-fn alDisable(_env: &mut Environment, _capability: ALenum) {
-    todo!();
+fn alGetInteger(_env: &mut Environment, param: ALenum) -> ALint {
+    let mut value = 0;
+    unsafe { al::alGetIntegerv(param, &mut value) };
+    value
 }
-fn alGetBoolean(_env: &mut Environment, _param: ALenum) -> ALboolean {
-    todo!();
+fn alGetIntegerv(env: &mut Environment, param: ALenum, values: MutPtr<ALint>) {
+    let values = env.mem.ptr_at_mut(values, param_value_count(param));
+    unsafe { al::alGetIntegerv(param, values) };
 }
-fn alGetBooleanv(_env: &mut Environment, _param: ALenum, _values: MutPtr<ALboolean>) {
-    todo!();
+
+fn alListeneri(_env: &mut Environment, param: ALenum, value: ALint) {
+    unsafe { al::alListeneri(param, value) };
 }
-fn alGetDouble(_env: &mut Environment, _param: ALenum) -> ALdouble {
-    todo!();
+fn alGetListenerf(env: &mut Environment, param: ALenum, value: MutPtr<ALfloat>) {
+    unsafe { al::alGetListenerf(param, env.mem.ptr_at_mut(value, 1)) };
 }
-fn alGetDoublev(_env: &mut Environment, _param: ALenum, _values: MutPtr<ALdouble>) {
-    todo!();
+fn alGetListener3f(
+    env: &mut Environment,
+    param: ALenum,
+    value1: MutPtr<ALfloat>,
+    value2: MutPtr<ALfloat>,
+    value3: MutPtr<ALfloat>,
+) {
+    let (mut v1, mut v2, mut v3) = (0.0, 0.0, 0.0);
+    unsafe { al::alGetListener3f(param, &mut v1, &mut v2, &mut v3) };
+    env.mem.write(value1, v1);
+    env.mem.write(value2, v2);
+    env.mem.write(value3, v3);
 }
-fn alGetFloat(_env: &mut Environment, _param: ALenum) -> ALfloat {
-    todo!();
+fn alGetListenerfv(env: &mut Environment, param: ALenum, values: MutPtr<ALfloat>) {
+    let values = env.mem.ptr_at_mut(values, param_value_count(param));
+    unsafe { al::alGetListenerfv(param, values) };
 }
-fn alGetFloatv(_env: &mut Environment, _param: ALenum, _values: MutPtr<ALfloat>) {
-    todo!();
+fn alGetListeneri(env: &mut Environment, param: ALenum, value: MutPtr<ALint>) {
+    unsafe { al::alGetListeneri(param, env.mem.ptr_at_mut(value, 1)) };
 }
-fn alGetInteger(_env: &mut Environment, _param: ALenum) -> ALint {
-    todo!();
+
+fn alIsSource(_env: &mut Environment, source: ALuint) -> ALboolean {
+    unsafe { al::alIsSource(source) }
 }
-fn alGetIntegerv(_env: &mut Environment, _param: ALenum, _values: MutPtr<ALint>) {
-    todo!();
+fn alSource3f(
+    _env: &mut Environment,
+    source: ALuint,
+    param: ALenum,
+    value1: ALfloat,
+    value2: ALfloat,
+    value3: ALfloat,
+) {
+    unsafe { al::alSource3f(source, param, value1, value2, value3) };
 }
-fn alGetEnumValue(_env: &mut Environment, _enumName: ConstPtr<u8>) -> ALenum {
-    todo!();
+fn alGetSource3f(
+    env: &mut Environment,
+    source: ALuint,
+    param: ALenum,
+    value1: MutPtr<ALfloat>,
+    value2: MutPtr<ALfloat>,
+    value3: MutPtr<ALfloat>,
+) {
+    let (mut v1, mut v2, mut v3) = (0.0, 0.0, 0.0);
+    unsafe { al::alGetSource3f(source, param, &mut v1, &mut v2, &mut v3) };
+    env.mem.write(value1, v1);
+    env.mem.write(value2, v2);
+    env.mem.write(value3, v3);
 }
-fn alGetProcAddress(_env: &mut Environment, _funcName: ConstPtr<u8>) -> MutVoidPtr {
-    todo!();
+fn alGetSourcefv(env: &mut Environment, source: ALuint, param: ALenum, values: MutPtr<ALfloat>) {
+    let values = env.mem.ptr_at_mut(values, param_value_count(param));
+    unsafe { al::alGetSourcefv(source, param, values) };
 }
-fn alGetString(_env: &mut Environment, _param: ALenum) -> ConstPtr<u8> {
-    todo!();
+
+fn alSourceRewind(_env: &mut Environment, source: ALuint) {
+    unsafe { al::alSourceRewind(source) };
 }
-fn alIsExtensionPresent(_env: &mut Environment, _extName: ConstPtr<u8>) -> ALboolean {
-    todo!();
+fn alSourcePlayv(env: &mut Environment, nsources: ALsizei, sources: ConstPtr<ALuint>) {
+    let n_usize: GuestUSize = nsources.try_into().unwrap();
+    let sources = env.mem.ptr_at(sources, n_usize);
+    unsafe { al::alSourcePlayv(nsources, sources) };
 }
-fn alIsEnabled(_env: &mut Environment, _capability: ALenum) -> ALboolean {
-    todo!();
+fn alSourcePausev(env: &mut Environment, nsources: ALsizei, sources: ConstPtr<ALuint>) {
+    let n_usize: GuestUSize = nsources.try_into().unwrap();
+    let sources = env.mem.ptr_at(sources, n_usize);
+    unsafe { al::alSourcePausev(nsources, sources) };
 }
-fn alListeneri(_env: &mut Environment, _param: ALenum, _value: ALint) {
-    todo!();
+fn alSourceStopv(env: &mut Environment, nsources: ALsizei, sources: ConstPtr<ALuint>) {
+    let n_usize: GuestUSize = nsources.try_into().unwrap();
+    let sources = env.mem.ptr_at(sources, n_usize);
+    unsafe { al::alSourceStopv(nsources, sources) };
 }
-fn alGetListenerf(_env: &mut Environment, _param: ALenum, _value: MutPtr<ALfloat>) {
-    todo!();
+fn alSourceRewindv(env: &mut Environment, nsources: ALsizei, sources: ConstPtr<ALuint>) {
+    let n_usize: GuestUSize = nsources.try_into().unwrap();
+    let sources = env.mem.ptr_at(sources, n_usize);
+    unsafe { al::alSourceRewindv(nsources, sources) };
 }
-fn alGetListener3f(
-    _env: &mut Environment,
-    _param: ALenum,
-    _value1: MutPtr<ALfloat>,
-    _value2: MutPtr<ALfloat>,
-    _value3: MutPtr<ALfloat>,
-) {
-    todo!();
+
+fn alIsBuffer(_env: &mut Environment, buffer: ALuint) -> ALboolean {
+    unsafe { al::alIsBuffer(buffer) }
 }
-fn alGetListenerfv(_env: &mut Environment, _param: ALenum, _values: MutPtr<ALfloat>) {
-    todo!();
+fn alGetBufferf(env: &mut Environment, buffer: ALuint, param: ALenum, value: MutPtr<ALfloat>) {
+    unsafe { al::alGetBufferf(buffer, param, env.mem.ptr_at_mut(value, 1)) };
 }
-fn alGetListeneri(_env: &mut Environment, _param: ALenum, _value: MutPtr<ALint>) {
-    todo!();
+fn alGetBufferi(env: &mut Environment, buffer: ALuint, param: ALenum, value: MutPtr<ALint>) {
+    unsafe { al::alGetBufferi(buffer, param, env.mem.ptr_at_mut(value, 1)) };
 }
-fn alIsSource(_env: &mut Environment, _source: ALuint) -> ALboolean {
+
+// TODO: more functions
+
+// Note: For some reasons Wolf3d registers many OpenAl functions, but actually uses only few ones.
+// To workaround this, we just provide stubs
+
+fn alcGetEnumValue(
+    _env: &mut Environment,
+    _device: MutPtr<GuestALCdevice>,
+    _enumName: ConstPtr<u8>,
+) -> ALenum {
     todo!();
 }
-fn alSource3f(
+fn alcGetIntegerv(
     _env: &mut Environment,
-    _source: ALuint,
+    _device: MutPtr<GuestALCdevice>,
     _param: ALenum,
-    _value1: ALfloat,
-    _value2: ALfloat,
-    _value3: ALfloat,
+    _size: ALCsizei,
+    _values: MutPtr<ALCint>,
 ) {
     todo!();
 }
-fn alGetSource3f(
+fn alcGetString(
     _env: &mut Environment,
-    _source: ALuint,
+    _device: MutPtr<GuestALCdevice>,
     _param: ALenum,
-    _value1: MutPtr<ALfloat>,
-    _value2: MutPtr<ALfloat>,
-    _value3: MutPtr<ALfloat>,
-) {
+) -> ConstPtr<u8> {
     todo!();
 }
-fn alGetSourcefv(
+fn alcIsExtensionPresent(
     _env: &mut Environment,
-    _source: ALuint,
-    _param: ALenum,
-    _values: MutPtr<ALfloat>,
-) {
+    _device: MutPtr<GuestALCdevice>,
+    _extName: ConstPtr<u8>,
+) -> ALCboolean {
+    0
+}
+fn alcProcessContext(_env: &mut Environment, _context: MutPtr<GuestALCcontext>) {
     todo!();
 }
-fn alSourcePlayv(_env: &mut Environment, _nsources: ALsizei, _sources: ConstPtr<ALuint>) {
+fn alcSuspendContext(_env: &mut Environment, _context: MutPtr<GuestALCcontext>) {
     todo!();
 }
-fn alSourcePausev(_env: &mut Environment, _nsources: ALsizei, _sources: ConstPtr<ALuint>) {
+fn alGetEnumValue(_env: &mut Environment, _enumName: ConstPtr<u8>) -> ALenum {
     todo!();
 }
-fn alSourceStopv(_env: &mut Environment, _nsources: ALsizei, _sources: ConstPtr<ALuint>) {
+fn alGetProcAddress(_env: &mut Environment, _funcName: ConstPtr<u8>) -> MutVoidPtr {
     todo!();
 }
-fn alSourceRewind(_env: &mut Environment, _source: ALuint) {
+fn alGetString(_env: &mut Environment, _param: ALenum) -> ConstPtr<u8> {
     todo!();
 }
-fn alSourceRewindv(_env: &mut Environment, _nsources: ALsizei, _sources: ConstPtr<ALuint>) {
+fn alIsExtensionPresent(_env: &mut Environment, _extName: ConstPtr<u8>) -> ALboolean {
     todo!();
 }
-
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(alcOpenDevice(_)),
     export_c_func!(alcCloseDevice(_)),
@@ -581,7 +638,6 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(alGetSource3f(_, _, _, _, _)),
     export_c_func!(alGetSourcefv(_, _, _)),
     export_c_func!(alSourcePlayv(_, _)),
-    export_c_func!(alSourcePause(_)),
     export_c_func!(alSourcePausev(_, _)),
     export_c_func!(alSourceStopv(_, _)),
     export_c_func!(alSourceRewind(_)),
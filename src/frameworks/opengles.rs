@@ -11,21 +11,91 @@
 
 pub mod eagl;
 mod gles_guest;
+pub mod profile;
 
 pub use gles_guest::FUNCTIONS;
 
+use crate::gles::gles11_raw::types::{GLenum, GLint, GLubyte, GLuint};
+use crate::mem::ConstPtr;
+use crate::objc::id;
+use std::collections::HashMap;
+
 #[derive(Default)]
 pub struct State {
     /// Current EAGLContext for each thread
     current_ctxs: std::collections::HashMap<crate::ThreadId, Option<crate::objc::id>>,
     /// Which thread's EAGLContext is currently active
     current_ctx_thread: Option<crate::ThreadId>,
+    /// Number of draw calls made by the app since the last call to
+    /// [take_draw_call_count].
+    draw_calls: u32,
+    /// Strings returned by `glGetString()`, which must remain valid, keyed by
+    /// whether the context is OpenGL ES 2.0 and the name.
+    strings: HashMap<(bool, GLenum), ConstPtr<GLubyte>>,
+    /// Estimated size in bytes of each level of each texture the app has
+    /// allocated, keyed by EAGLContext and texture name. See
+    /// [app_texture_memory].
+    app_textures: HashMap<(id, GLuint), HashMap<GLint, usize>>,
+    /// Sum of the sizes in [Self::app_textures].
+    app_texture_bytes: usize,
 }
 impl State {
     fn current_ctx_for_thread(&mut self, thread: crate::ThreadId) -> &mut Option<crate::objc::id> {
         self.current_ctxs.entry(thread).or_insert(None);
         self.current_ctxs.get_mut(&thread).unwrap()
     }
+
+    fn record_app_texture_image(&mut self, ctx: id, texture: GLuint, level: GLint, bytes: usize) {
+        let levels = self.app_textures.entry((ctx, texture)).or_default();
+        if let Some(old_bytes) = levels.insert(level, bytes) {
+            self.app_texture_bytes -= old_bytes;
+        }
+        self.app_texture_bytes += bytes;
+    }
+
+    fn forget_app_texture(&mut self, ctx: id, texture: GLuint) {
+        if let Some(levels) = self.app_textures.remove(&(ctx, texture)) {
+            self.app_texture_bytes -= levels.values().sum::<usize>();
+        }
+    }
+
+    /// For use when an EAGLContext is destroyed, which destroys its textures.
+    fn forget_app_textures_for_context(&mut self, ctx: id) {
+        let textures: Vec<GLuint> = self
+            .app_textures
+            .keys()
+            .filter(|&&(texture_ctx, _)| texture_ctx == ctx)
+            .map(|&(_, texture)| texture)
+            .collect();
+        for texture in textures {
+            self.forget_app_texture(ctx, texture);
+        }
+    }
+}
+
+/// Get the estimated amount of memory in bytes used by textures the app has
+/// allocated. Unlike the compositor's textures, these can't be evicted.
+pub fn app_texture_memory(env: &crate::Environment) -> usize {
+    env.framework_state.opengles.app_texture_bytes
+}
+
+/// Get the number of draw calls (`glDrawArrays()` and `glDrawElements()`) the
+/// app has made since the last call, for the debugging overlay.
+pub fn take_draw_call_count(env: &mut crate::Environment) -> u32 {
+    std::mem::take(&mut env.framework_state.opengles.draw_calls)
+}
+
+/// Returns [true] if the current thread's EAGLContext is an OpenGL ES 2.0
+/// context.
+fn current_ctx_is_gles2(env: &mut crate::Environment) -> bool {
+    let current_ctx = env
+        .framework_state
+        .opengles
+        .current_ctx_for_thread(env.current_thread)
+        .unwrap();
+    env.objc
+        .borrow::<eagl::EAGLContextHostObject>(current_ctx)
+        .is_gles2()
 }
 
 fn sync_context<'a>(
@@ -6,15 +6,18 @@
 //! EAGL.
 
 use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_animation;
 use crate::frameworks::core_animation::ca_eagl_layer::{
     find_fullscreen_eagl_layer, get_pixels_vec_for_presenting, present_pixels,
 };
 use crate::frameworks::foundation::ns_string::get_static_str;
 use crate::frameworks::foundation::NSUInteger;
+use crate::gles::debug_overlay::FrameStats;
 use crate::gles::gles11_raw as gles11; // constants only
 use crate::gles::gles11_raw::types::*;
-use crate::gles::present::present_frame;
-use crate::gles::{create_gles1_ctx, gles1_on_gl2, GLES};
+use crate::gles::gles20_raw as gles20; // constants only
+use crate::gles::present::{present_frame, save_screenshot};
+use crate::gles::{create_gles1_ctx, create_gles2_ctx, gles1_on_gl2, GLES};
 use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, HostObject};
 use crate::window::Window;
 use std::collections::HashMap;
@@ -49,18 +52,24 @@ pub const CONSTANTS: ConstantExports = &[
 
 type EAGLRenderingAPI = u32;
 const kEAGLRenderingAPIOpenGLES1: EAGLRenderingAPI = 1;
-#[allow(dead_code)]
 const kEAGLRenderingAPIOpenGLES2: EAGLRenderingAPI = 2;
 #[allow(dead_code)]
 const kEAGLRenderingAPIOpenGLES3: EAGLRenderingAPI = 3;
 
 pub(super) struct EAGLContextHostObject {
     pub(super) gles_ctx: Option<Box<dyn GLES>>,
+    /// Meaningless until `initWithAPI:` is called.
+    api: EAGLRenderingAPI,
     /// Mapping of OpenGL ES renderbuffer names to `EAGLDrawable` instances
     /// (always `CAEAGLLayer*`). Retains the instance so it won't dangle.
     renderbuffer_drawable_bindings: HashMap<GLuint, id>,
 }
 impl HostObject for EAGLContextHostObject {}
+impl EAGLContextHostObject {
+    pub(super) fn is_gles2(&self) -> bool {
+        self.api == kEAGLRenderingAPIOpenGLES2
+    }
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -71,6 +80,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)alloc {
     let host_object = Box::new(EAGLContextHostObject {
         gles_ctx: None,
+        api: 0,
         renderbuffer_drawable_bindings: HashMap::new(),
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
@@ -106,31 +116,44 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (id)initWithAPI:(EAGLRenderingAPI)api {
-    assert!(api == kEAGLRenderingAPIOpenGLES1);
-
     let window = env.window.as_mut().expect("OpenGL ES is not supported in headless mode");
-    let gles1_ctx = create_gles1_ctx(window, &env.options);
+    let gles_ctx = match api {
+        kEAGLRenderingAPIOpenGLES1 => create_gles1_ctx(window, &env.options),
+        kEAGLRenderingAPIOpenGLES2 => create_gles2_ctx(window),
+        _ => {
+            log!("[EAGLContext initWithAPI:{}] Unsupported API, returning nil", api);
+            release(env, this);
+            return nil;
+        }
+    };
 
     // Make the context current so we can get driver info from it.
     // initWithAPI: is not supposed to make the new context current (the app
     // must call setCurrentContext: for that), so we need to hide this from the
     // app. Setting current_ctx_thread to None should cause sync_context to
     // switch back to the right context if the app makes an OpenGL ES call.
-    gles1_ctx.make_current(window);
+    gles_ctx.make_current(window);
     env.framework_state.opengles.current_ctx_thread = None;
-    log!("Driver info: {}", unsafe { gles1_ctx.driver_description() });
+    log!("Driver info: {}", unsafe { gles_ctx.driver_description() });
 
-    env.objc.borrow_mut::<EAGLContextHostObject>(this).gles_ctx = Some(gles1_ctx);
+    let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(this);
+    host_obj.gles_ctx = Some(gles_ctx);
+    host_obj.api = api;
 
     this
 }
 
+- (EAGLRenderingAPI)API {
+    env.objc.borrow::<EAGLContextHostObject>(this).api
+}
+
 - (())dealloc {
     let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(this);
     let bindings = std::mem::take(&mut host_obj.renderbuffer_drawable_bindings);
     for (_renderbuffer, drawable) in bindings {
         release(env, drawable);
     }
+    env.framework_state.opengles.forget_app_textures_for_context(this);
     env.objc.dealloc_object(this, &mut env.mem);
 }
 
@@ -218,10 +241,17 @@ pub const CLASSES: ClassExports = objc_classes! {
             drawable,
             renderbuffer,
         );
+        let is_gles2 = env.objc.borrow::<EAGLContextHostObject>(this).api == kEAGLRenderingAPIOpenGLES2;
+        // Nothing is composited on this path.
+        let frame_stats = FrameStats {
+            draw_calls: super::take_draw_call_count(env),
+            texture_memory: core_animation::texture_memory_in_use(env),
+            ..Default::default()
+        };
         // re-borrow
         let gles = super::sync_context(&mut env.framework_state.opengles, &mut env.objc, env.window.as_mut().unwrap(), env.current_thread);
         unsafe {
-            present_renderbuffer(gles, env.window.as_mut().unwrap());
+            present_renderbuffer(gles, env.window.as_mut().unwrap(), is_gles2, frame_stats);
         }
     } else {
         if fullscreen_layer != nil {
@@ -374,10 +404,18 @@ unsafe fn read_renderbuffer(gles: &mut dyn GLES, mut pixel_buffer: Vec<u8>) -> (
 /// Copies the pixels in a renderbuffer bound to `GL_RENDERBUFFER_BINDING_OES`
 /// (which should be provided by the app) to a texture and presents it with
 /// [present_frame], trying to avoid noticeably modifying OpenGL ES state while
-/// doing so. The front and back buffers are then swapped.
+/// doing so. The debugging overlay is drawn on top if it's visible, using
+/// `frame_stats`. The front and back buffers are then swapped.
 ///
-/// The provided context must be current.
-unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
+/// The provided context must be current. If it is an OpenGL ES 2.0 context,
+/// `is_gles2` must be [true], so the app's shader program can be unbound
+/// while the fixed-function pipeline is used.
+unsafe fn present_renderbuffer(
+    gles: &mut dyn GLES,
+    window: &mut Window,
+    is_gles2: bool,
+    frame_stats: FrameStats,
+) {
     // We can't directly copy the content of the renderbuffer to the default
     // framebuffer (the window), but if we attach it to a framebuffer object, we
     // can use glCopyTexImage2D() to copy it to a texture, which we can then
@@ -391,6 +429,13 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
     // state changes we make.
     let old_framebuffer: GLuint = get_int(gles, gles11::FRAMEBUFFER_BINDING_OES) as _;
     let old_texture_2d: GLuint = get_int(gles, gles11::TEXTURE_BINDING_2D) as _;
+    let old_program: GLuint = if is_gles2 {
+        let old_program = get_int(gles, gles20::CURRENT_PROGRAM) as _;
+        gles.UseProgram(0);
+        old_program
+    } else {
+        0
+    };
 
     // Create a framebuffer we can use to read from the renderbuffer
     let mut src_framebuffer = 0;
@@ -417,13 +462,6 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
         height,
         0,
     );
-    // The texture will not have any mip levels so we must ensure the filter
-    // does not use them, else rendering will fail.
-    gles.TexParameteri(
-        gles11::TEXTURE_2D,
-        gles11::TEXTURE_MIN_FILTER,
-        gles11::LINEAR as _,
-    );
 
     // Clean up the framebuffer object since we no longer need it.
     // This also sets the framebuffer bindings back to zero, so rendering
@@ -490,7 +528,16 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
         window.viewport(),
         window.output_rotation_matrix(),
         window.virtual_cursor_visible_at(),
+        window.present_filter(),
     );
+    if window.take_screenshot_request() {
+        save_screenshot(gles, window.viewport());
+    }
+    let viewport = window.viewport();
+    if let Some(debug_overlay) = window.debug_overlay_mut() {
+        debug_overlay.record_frame(Instant::now(), frame_stats);
+        debug_overlay.draw(gles, viewport);
+    }
 
     // Clean up the texture
     gles.DeleteTextures(1, &texture);
@@ -557,6 +604,9 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
     // Restore the other bindings
     gles.BindTexture(gles11::TEXTURE_2D, old_texture_2d);
     gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, old_framebuffer);
+    if is_gles2 {
+        gles.UseProgram(old_program);
+    }
 
     //{ let err = gl21::GetError(); if err != 0 { panic!("{:#x}", err); } }
 }
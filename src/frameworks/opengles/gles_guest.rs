@@ -12,17 +12,19 @@
 //! depending on the value of `pname`, using the upper bound (4 in this case)
 //! every time is never going to cause a problem in practice.
 
+use super::profile::{self, GLProfile};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::gles::gles11_raw as gles11; // constants only
 use crate::gles::GLES;
-use crate::mem::{ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, Mem, MutPtr};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, Mem, MutPtr, Ptr};
+use crate::objc::id;
 use crate::Environment;
 use core::ffi::CStr;
 
 // These types are the same size in guest code (32-bit) and host code (64-bit).
 use crate::gles::gles11_raw::types::{
-    GLbitfield, GLboolean, GLclampf, GLclampx, GLenum, GLfixed, GLfloat, GLint, GLsizei, GLubyte,
-    GLuint, GLvoid,
+    GLbitfield, GLboolean, GLchar, GLclampf, GLclampx, GLenum, GLfixed, GLfloat, GLint, GLsizei,
+    GLubyte, GLuint, GLvoid,
 };
 // These types have different sizes, so some care is needed.
 use crate::gles::gles11_raw::types::GLsizeiptr as HostGLsizeiptr;
@@ -113,24 +115,75 @@ fn glGetFloatv(env: &mut Environment, pname: GLenum, params: MutPtr<GLfloat>) {
     });
 }
 fn glGetIntegerv(env: &mut Environment, pname: GLenum, params: MutPtr<GLint>) {
+    let is_gles2 = super::current_ctx_is_gles2(env);
+    let profile = env.options.gl_profile;
+    if profile != GLProfile::Host {
+        if pname == gles11::NUM_COMPRESSED_TEXTURE_FORMATS {
+            let count = profile::COMPRESSED_TEXTURE_FORMATS.len();
+            env.mem.write(params, count as GLint);
+            return;
+        } else if pname == gles11::COMPRESSED_TEXTURE_FORMATS {
+            for (i, &format) in profile::COMPRESSED_TEXTURE_FORMATS.iter().enumerate() {
+                env.mem.write(params + i as GuestUSize, format as GLint);
+            }
+            return;
+        }
+    }
+    let max = profile.max_integer(is_gles2, pname);
     with_ctx_and_mem(env, |gles, mem| {
         let params = mem.ptr_at_mut(params, 16 /* upper bound */);
-        unsafe { gles.GetIntegerv(pname, params) };
+        unsafe {
+            gles.GetIntegerv(pname, params);
+            if let Some(max) = max {
+                *params = (*params).min(max);
+            }
+        };
     });
 }
 fn glHint(env: &mut Environment, target: GLenum, mode: GLenum) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Hint(target, mode) })
 }
 fn glGetString(env: &mut Environment, name: GLenum) -> ConstPtr<GLubyte> {
-    with_ctx_and_mem(env, |gles, mem| {
-        let s = unsafe { CStr::from_ptr(gles.GetString(name).cast()) };
-        log!(
-            "TODO: glGetString({}) does not match real device and leaks memory",
+    let is_gles2 = super::current_ctx_is_gles2(env);
+    if let Some(&string) = env.framework_state.opengles.strings.get(&(is_gles2, name)) {
+        return string;
+    }
+
+    let options = &env.options;
+    let reported = options
+        .gl_profile
+        .get_string(is_gles2, name, options.gl_extensions.as_deref());
+    let string = with_ctx_and_mem(env, |gles, mem| {
+        let bytes = if let Some(reported) = reported {
+            reported.into_bytes()
+        } else {
+            let host_string = unsafe { gles.GetString(name) };
+            if host_string.is_null() {
+                log!("Warning: glGetString({:#x}) failed", name);
+                return Ptr::null();
+            }
+            log!(
+                "TODO: glGetString({:#x}) is using the host's value, which does not match a real device",
+                name,
+            );
+            unsafe { CStr::from_ptr(host_string.cast()) }
+                .to_bytes()
+                .to_vec()
+        };
+        log_dbg!(
+            "glGetString({:#x}) => {:?}",
             name,
+            String::from_utf8_lossy(&bytes)
         );
-        log_dbg!("glGetString({}) => {:?}", name, s);
-        mem.alloc_and_write_cstr(s.to_bytes()).cast_const()
-    })
+        mem.alloc_and_write_cstr(&bytes).cast_const()
+    });
+    if !string.is_null() {
+        env.framework_state
+            .opengles
+            .strings
+            .insert((is_gles2, name), string);
+    }
+    string
 }
 
 // Other state manipulation
@@ -382,6 +435,7 @@ fn glVertexPointer(
 
 // Drawing
 fn glDrawArrays(env: &mut Environment, mode: GLenum, first: GLint, count: GLsizei) {
+    env.framework_state.opengles.draw_calls += 1;
     with_ctx_and_mem(env, |gles, _mem| unsafe {
         gles.DrawArrays(mode, first, count)
     })
@@ -393,6 +447,7 @@ fn glDrawElements(
     type_: GLenum,
     indices: ConstVoidPtr,
 ) {
+    env.framework_state.opengles.draw_calls += 1;
     with_ctx_and_mem(env, |gles, mem| unsafe {
         let indices =
             translate_pointer_or_offset(gles, mem, indices, gles11::ELEMENT_ARRAY_BUFFER_BINDING);
@@ -576,11 +631,17 @@ fn glGenTextures(env: &mut Environment, n: GLsizei, textures: MutPtr<GLuint>) {
     })
 }
 fn glDeleteTextures(env: &mut Environment, n: GLsizei, textures: ConstPtr<GLuint>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    let deleted = with_ctx_and_mem(env, |gles, mem| {
         let n_usize: GuestUSize = n.try_into().unwrap();
         let textures = mem.ptr_at(textures, n_usize);
-        unsafe { gles.DeleteTextures(n, textures) }
-    })
+        unsafe { gles.DeleteTextures(n, textures) };
+        unsafe { std::slice::from_raw_parts(textures, n_usize as usize) }.to_vec()
+    });
+    let state = &mut env.framework_state.opengles;
+    let ctx = state.current_ctx_for_thread(env.current_thread).unwrap();
+    for texture in deleted {
+        state.forget_app_texture(ctx, texture);
+    }
 }
 fn glActiveTexture(env: &mut Environment, texture: GLenum) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.ActiveTexture(texture) })
@@ -675,6 +736,34 @@ fn image_size_estimate(pixel_count: GuestUSize, format: GLenum, type_: GLenum) -
     // This is approximate, it doesn't account for alignment.
     pixel_count.checked_mul(bytes_per_pixel).unwrap()
 }
+/// Get the current EAGLContext and the texture bound to `GL_TEXTURE_2D`, for
+/// texture memory accounting (see [super::app_texture_memory]).
+fn bound_texture_2d(env: &mut Environment) -> (id, GLuint) {
+    let texture = with_ctx_and_mem(env, |gles, _mem| {
+        let mut texture = 0;
+        unsafe { gles.GetIntegerv(gles11::TEXTURE_BINDING_2D, &mut texture) };
+        texture as GLuint
+    });
+    let state = &mut env.framework_state.opengles;
+    let ctx = state.current_ctx_for_thread(env.current_thread).unwrap();
+    (ctx, texture)
+}
+/// Record the estimated size of a texture image the app has just specified.
+fn record_texture_image(env: &mut Environment, target: GLenum, level: GLint, bytes: usize) {
+    if target != gles11::TEXTURE_2D {
+        return;
+    }
+    let (ctx, texture) = bound_texture_2d(env);
+    env.framework_state
+        .opengles
+        .record_app_texture_image(ctx, texture, level, bytes);
+}
+/// Estimated size of a texture image on the host, which may not match the
+/// size of the data the app provides, e.g. because the host stores RGB as RGBA
+/// or because the data is decompressed. This is only used for accounting.
+fn host_image_size_estimate(width: GLsizei, height: GLsizei) -> usize {
+    (width.max(0) as usize) * (height.max(0) as usize) * 4
+}
 fn glTexImage2D(
     env: &mut Environment,
     target: GLenum,
@@ -706,7 +795,9 @@ fn glTexImage2D(
             type_,
             pixels,
         )
-    })
+    });
+    let bytes = host_image_size_estimate(width, height);
+    record_texture_image(env, target, level, bytes);
 }
 fn glTexSubImage2D(
     env: &mut Environment,
@@ -754,7 +845,10 @@ fn glCompressedTexImage2D(
             image_size,
             data,
         )
-    })
+    });
+    // touchHLE decompresses PVRTC and paletted textures.
+    let bytes = host_image_size_estimate(width, height);
+    record_texture_image(env, target, level, bytes);
 }
 fn glCopyTexImage2D(
     env: &mut Environment,
@@ -769,7 +863,9 @@ fn glCopyTexImage2D(
 ) {
     with_ctx_and_mem(env, |gles, _mem| unsafe {
         gles.CopyTexImage2D(target, level, internalformat, x, y, width, height, border)
-    })
+    });
+    let bytes = host_image_size_estimate(width, height);
+    record_texture_image(env, target, level, bytes);
 }
 fn glTexEnvf(env: &mut Environment, target: GLenum, pname: GLenum, param: GLfloat) {
     with_ctx_and_mem(env, |gles, _mem| unsafe {
@@ -910,7 +1006,381 @@ fn glDeleteRenderbuffersOES(env: &mut Environment, n: GLsizei, renderbuffers: Co
     })
 }
 fn glGenerateMipmapOES(env: &mut Environment, target: GLenum) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.GenerateMipmapOES(target) })
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.GenerateMipmapOES(target) });
+    if target != gles11::TEXTURE_2D {
+        return;
+    }
+    // The other levels add up to about a third of the size of the first one.
+    // They're recorded as if they were all level 1.
+    let (ctx, texture) = bound_texture_2d(env);
+    let state = &mut env.framework_state.opengles;
+    let first_level_bytes = state
+        .app_textures
+        .get(&(ctx, texture))
+        .and_then(|levels| levels.get(&0))
+        .copied()
+        .unwrap_or(0);
+    state.record_app_texture_image(ctx, texture, 1, first_level_bytes / 3);
+}
+
+// OpenGL ES 2.0
+
+/// Get a host pointer to a NUL-terminated string.
+fn cstr_ptr(mem: &Mem, string: ConstPtr<GLchar>) -> *const GLchar {
+    let len: GuestUSize = mem.cstr_at(string.cast::<u8>()).len().try_into().unwrap();
+    mem.ptr_at(string, len + 1)
+}
+
+fn glCreateShader(env: &mut Environment, type_: GLenum) -> GLuint {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.CreateShader(type_) })
+}
+fn glDeleteShader(env: &mut Environment, shader: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.DeleteShader(shader) })
+}
+fn glShaderSource(
+    env: &mut Environment,
+    shader: GLuint,
+    count: GLsizei,
+    string: ConstPtr<ConstPtr<GLchar>>,
+    length: ConstPtr<GLint>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let count_usize: GuestUSize = count.try_into().unwrap();
+        let mut strings = Vec::with_capacity(count_usize as usize);
+        let mut lengths = Vec::with_capacity(count_usize as usize);
+        for i in 0..count_usize {
+            let string = mem.read(string + i);
+            let length = if length.is_null() {
+                -1
+            } else {
+                mem.read(length + i)
+            };
+            // A negative length means the string is NUL-terminated.
+            if length < 0 {
+                strings.push(cstr_ptr(mem, string));
+            } else {
+                strings.push(mem.ptr_at(string, length as GuestUSize));
+            }
+            lengths.push(length);
+        }
+        unsafe { gles.ShaderSource(shader, count, strings.as_ptr(), lengths.as_ptr()) }
+    })
+}
+fn glCompileShader(env: &mut Environment, shader: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.CompileShader(shader) })
+}
+fn glGetShaderiv(env: &mut Environment, shader: GLuint, pname: GLenum, params: MutPtr<GLint>) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { gles.GetShaderiv(shader, pname, params) }
+    })
+}
+fn glGetShaderInfoLog(
+    env: &mut Environment,
+    shader: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    info_log: MutPtr<GLchar>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let length = if length.is_null() {
+            std::ptr::null_mut()
+        } else {
+            mem.ptr_at_mut(length, 1)
+        };
+        let info_log = mem.ptr_at_mut(info_log, buf_size.try_into().unwrap());
+        unsafe { gles.GetShaderInfoLog(shader, buf_size, length, info_log) }
+    })
+}
+fn glCreateProgram(env: &mut Environment) -> GLuint {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.CreateProgram() })
+}
+fn glDeleteProgram(env: &mut Environment, program: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.DeleteProgram(program) })
+}
+fn glAttachShader(env: &mut Environment, program: GLuint, shader: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.AttachShader(program, shader)
+    })
+}
+fn glDetachShader(env: &mut Environment, program: GLuint, shader: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.DetachShader(program, shader)
+    })
+}
+fn glBindAttribLocation(
+    env: &mut Environment,
+    program: GLuint,
+    index: GLuint,
+    name: ConstPtr<GLchar>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let name = cstr_ptr(mem, name);
+        unsafe { gles.BindAttribLocation(program, index, name) }
+    })
+}
+fn glLinkProgram(env: &mut Environment, program: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.LinkProgram(program) })
+}
+fn glValidateProgram(env: &mut Environment, program: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.ValidateProgram(program) })
+}
+fn glUseProgram(env: &mut Environment, program: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.UseProgram(program) })
+}
+fn glGetProgramiv(env: &mut Environment, program: GLuint, pname: GLenum, params: MutPtr<GLint>) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { gles.GetProgramiv(program, pname, params) }
+    })
+}
+fn glGetProgramInfoLog(
+    env: &mut Environment,
+    program: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    info_log: MutPtr<GLchar>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let length = if length.is_null() {
+            std::ptr::null_mut()
+        } else {
+            mem.ptr_at_mut(length, 1)
+        };
+        let info_log = mem.ptr_at_mut(info_log, buf_size.try_into().unwrap());
+        unsafe { gles.GetProgramInfoLog(program, buf_size, length, info_log) }
+    })
+}
+fn glGetAttribLocation(env: &mut Environment, program: GLuint, name: ConstPtr<GLchar>) -> GLint {
+    with_ctx_and_mem(env, |gles, mem| {
+        let name = cstr_ptr(mem, name);
+        unsafe { gles.GetAttribLocation(program, name) }
+    })
+}
+fn glGetUniformLocation(env: &mut Environment, program: GLuint, name: ConstPtr<GLchar>) -> GLint {
+    with_ctx_and_mem(env, |gles, mem| {
+        let name = cstr_ptr(mem, name);
+        unsafe { gles.GetUniformLocation(program, name) }
+    })
+}
+fn glUniform1i(env: &mut Environment, location: GLint, v0: GLint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Uniform1i(location, v0) })
+}
+fn glUniform1f(env: &mut Environment, location: GLint, v0: GLfloat) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Uniform1f(location, v0) })
+}
+fn glUniform2f(env: &mut Environment, location: GLint, v0: GLfloat, v1: GLfloat) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.Uniform2f(location, v0, v1)
+    })
+}
+fn glUniform3f(env: &mut Environment, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.Uniform3f(location, v0, v1, v2)
+    })
+}
+fn glUniform4f(
+    env: &mut Environment,
+    location: GLint,
+    v0: GLfloat,
+    v1: GLfloat,
+    v2: GLfloat,
+    v3: GLfloat,
+) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.Uniform4f(location, v0, v1, v2, v3)
+    })
+}
+fn uniformiv(
+    env: &mut Environment,
+    size: GLint,
+    location: GLint,
+    count: GLsizei,
+    value: ConstPtr<GLint>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let value = mem.ptr_at(value, (size * count).try_into().unwrap());
+        unsafe { gles.Uniformiv(size, location, count, value) }
+    })
+}
+fn glUniform1iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    uniformiv(env, 1, location, count, value)
+}
+fn glUniform2iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    uniformiv(env, 2, location, count, value)
+}
+fn glUniform3iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    uniformiv(env, 3, location, count, value)
+}
+fn glUniform4iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    uniformiv(env, 4, location, count, value)
+}
+fn uniformfv(
+    env: &mut Environment,
+    size: GLint,
+    location: GLint,
+    count: GLsizei,
+    value: ConstPtr<GLfloat>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let value = mem.ptr_at(value, (size * count).try_into().unwrap());
+        unsafe { gles.Uniformfv(size, location, count, value) }
+    })
+}
+fn glUniform1fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    uniformfv(env, 1, location, count, value)
+}
+fn glUniform2fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    uniformfv(env, 2, location, count, value)
+}
+fn glUniform3fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    uniformfv(env, 3, location, count, value)
+}
+fn glUniform4fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    uniformfv(env, 4, location, count, value)
+}
+fn uniform_matrixfv(
+    env: &mut Environment,
+    size: GLint,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let value = mem.ptr_at(value, (size * size * count).try_into().unwrap());
+        unsafe { gles.UniformMatrixfv(size, location, count, transpose, value) }
+    })
+}
+fn glUniformMatrix2fv(
+    env: &mut Environment,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    uniform_matrixfv(env, 2, location, count, transpose, value)
+}
+fn glUniformMatrix3fv(
+    env: &mut Environment,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    uniform_matrixfv(env, 3, location, count, transpose, value)
+}
+fn glUniformMatrix4fv(
+    env: &mut Environment,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    uniform_matrixfv(env, 4, location, count, transpose, value)
+}
+fn glVertexAttribPointer(
+    env: &mut Environment,
+    index: GLuint,
+    size: GLint,
+    type_: GLenum,
+    normalized: GLboolean,
+    stride: GLsizei,
+    pointer: ConstVoidPtr,
+) {
+    with_ctx_and_mem(env, |gles, mem| unsafe {
+        let pointer = translate_pointer_or_offset(gles, mem, pointer, gles11::ARRAY_BUFFER_BINDING);
+        gles.VertexAttribPointer(index, size, type_, normalized, stride, pointer)
+    })
+}
+fn glEnableVertexAttribArray(env: &mut Environment, index: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.EnableVertexAttribArray(index)
+    })
+}
+fn glDisableVertexAttribArray(env: &mut Environment, index: GLuint) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.DisableVertexAttribArray(index)
+    })
+}
+fn glVertexAttrib4f(
+    env: &mut Environment,
+    index: GLuint,
+    x: GLfloat,
+    y: GLfloat,
+    z: GLfloat,
+    w: GLfloat,
+) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.VertexAttrib4f(index, x, y, z, w)
+    })
+}
+fn glVertexAttrib4fv(env: &mut Environment, index: GLuint, v: ConstPtr<GLfloat>) {
+    let [x, y, z, w] = [0, 1, 2, 3].map(|i| env.mem.read(v + i));
+    glVertexAttrib4f(env, index, x, y, z, w)
+}
+
+// OpenGL ES 2.0 has framebuffer objects in the core API, with the same
+// behavior and enum values as OES_framebuffer_object.
+fn glGenFramebuffers(env: &mut Environment, n: GLsizei, framebuffers: MutPtr<GLuint>) {
+    glGenFramebuffersOES(env, n, framebuffers)
+}
+fn glGenRenderbuffers(env: &mut Environment, n: GLsizei, renderbuffers: MutPtr<GLuint>) {
+    glGenRenderbuffersOES(env, n, renderbuffers)
+}
+fn glBindFramebuffer(env: &mut Environment, target: GLenum, framebuffer: GLuint) {
+    glBindFramebufferOES(env, target, framebuffer)
+}
+fn glBindRenderbuffer(env: &mut Environment, target: GLenum, renderbuffer: GLuint) {
+    glBindRenderbufferOES(env, target, renderbuffer)
+}
+fn glRenderbufferStorage(
+    env: &mut Environment,
+    target: GLenum,
+    internalformat: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    glRenderbufferStorageOES(env, target, internalformat, width, height)
+}
+fn glFramebufferRenderbuffer(
+    env: &mut Environment,
+    target: GLenum,
+    attachment: GLenum,
+    renderbuffertarget: GLenum,
+    renderbuffer: GLuint,
+) {
+    glFramebufferRenderbufferOES(env, target, attachment, renderbuffertarget, renderbuffer)
+}
+fn glFramebufferTexture2D(
+    env: &mut Environment,
+    target: GLenum,
+    attachment: GLenum,
+    textarget: GLenum,
+    texture: GLuint,
+    level: i32,
+) {
+    glFramebufferTexture2DOES(env, target, attachment, textarget, texture, level)
+}
+fn glGetRenderbufferParameteriv(
+    env: &mut Environment,
+    target: GLenum,
+    pname: GLenum,
+    params: MutPtr<GLint>,
+) {
+    glGetRenderbufferParameterivOES(env, target, pname, params)
+}
+fn glCheckFramebufferStatus(env: &mut Environment, target: GLenum) -> GLenum {
+    glCheckFramebufferStatusOES(env, target)
+}
+fn glDeleteFramebuffers(env: &mut Environment, n: GLsizei, framebuffers: ConstPtr<GLuint>) {
+    glDeleteFramebuffersOES(env, n, framebuffers)
+}
+fn glDeleteRenderbuffers(env: &mut Environment, n: GLsizei, renderbuffers: ConstPtr<GLuint>) {
+    glDeleteRenderbuffersOES(env, n, renderbuffers)
+}
+fn glGenerateMipmap(env: &mut Environment, target: GLenum) {
+    glGenerateMipmapOES(env, target)
 }
 
 pub const FUNCTIONS: FunctionExports = &[
@@ -1031,4 +1501,56 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glDeleteFramebuffersOES(_, _)),
     export_c_func!(glDeleteRenderbuffersOES(_, _)),
     export_c_func!(glGenerateMipmapOES(_)),
+    // OpenGL ES 2.0
+    export_c_func!(glCreateShader(_)),
+    export_c_func!(glDeleteShader(_)),
+    export_c_func!(glShaderSource(_, _, _, _)),
+    export_c_func!(glCompileShader(_)),
+    export_c_func!(glGetShaderiv(_, _, _)),
+    export_c_func!(glGetShaderInfoLog(_, _, _, _)),
+    export_c_func!(glCreateProgram()),
+    export_c_func!(glDeleteProgram(_)),
+    export_c_func!(glAttachShader(_, _)),
+    export_c_func!(glDetachShader(_, _)),
+    export_c_func!(glBindAttribLocation(_, _, _)),
+    export_c_func!(glLinkProgram(_)),
+    export_c_func!(glValidateProgram(_)),
+    export_c_func!(glUseProgram(_)),
+    export_c_func!(glGetProgramiv(_, _, _)),
+    export_c_func!(glGetProgramInfoLog(_, _, _, _)),
+    export_c_func!(glGetAttribLocation(_, _)),
+    export_c_func!(glGetUniformLocation(_, _)),
+    export_c_func!(glUniform1i(_, _)),
+    export_c_func!(glUniform1f(_, _)),
+    export_c_func!(glUniform2f(_, _, _)),
+    export_c_func!(glUniform3f(_, _, _, _)),
+    export_c_func!(glUniform4f(_, _, _, _, _)),
+    export_c_func!(glUniform1iv(_, _, _)),
+    export_c_func!(glUniform2iv(_, _, _)),
+    export_c_func!(glUniform3iv(_, _, _)),
+    export_c_func!(glUniform4iv(_, _, _)),
+    export_c_func!(glUniform1fv(_, _, _)),
+    export_c_func!(glUniform2fv(_, _, _)),
+    export_c_func!(glUniform3fv(_, _, _)),
+    export_c_func!(glUniform4fv(_, _, _)),
+    export_c_func!(glUniformMatrix2fv(_, _, _, _)),
+    export_c_func!(glUniformMatrix3fv(_, _, _, _)),
+    export_c_func!(glUniformMatrix4fv(_, _, _, _)),
+    export_c_func!(glVertexAttribPointer(_, _, _, _, _, _)),
+    export_c_func!(glEnableVertexAttribArray(_)),
+    export_c_func!(glDisableVertexAttribArray(_)),
+    export_c_func!(glVertexAttrib4f(_, _, _, _, _)),
+    export_c_func!(glVertexAttrib4fv(_, _)),
+    export_c_func!(glGenFramebuffers(_, _)),
+    export_c_func!(glGenRenderbuffers(_, _)),
+    export_c_func!(glBindFramebuffer(_, _)),
+    export_c_func!(glBindRenderbuffer(_, _)),
+    export_c_func!(glRenderbufferStorage(_, _, _, _)),
+    export_c_func!(glFramebufferRenderbuffer(_, _, _, _)),
+    export_c_func!(glFramebufferTexture2D(_, _, _, _, _)),
+    export_c_func!(glGetRenderbufferParameteriv(_, _, _)),
+    export_c_func!(glCheckFramebufferStatus(_)),
+    export_c_func!(glDeleteFramebuffers(_, _)),
+    export_c_func!(glDeleteRenderbuffers(_, _)),
+    export_c_func!(glGenerateMipmap(_)),
 ];
@@ -0,0 +1,152 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The OpenGL ES implementation details reported to the app.
+//!
+//! Apps use `glGetString()` and `glGetIntegerv()` to decide which features to
+//! use. Passing through the host's values would describe a desktop graphics
+//! driver with limits no iPhone had and extensions touchHLE doesn't implement,
+//! so by default a consistent set of values modelled on a real device is
+//! reported instead. See the `--gl-profile=` and `--gl-extensions=` options.
+//!
+//! The extension lists only contain the extensions of the real devices that
+//! touchHLE implements. The limits are never higher than the host's.
+
+use crate::gles::gles11_raw as gles11; // constants only
+use crate::gles::gles11_raw::types::{GLenum, GLint};
+use crate::gles::gles20_raw as gles20; // constants only
+
+/// Choice of values to report to the app, for the `--gl-profile=` option.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GLProfile {
+    /// PowerVR MBX, as in the original iPhone and the iPhone 3G.
+    Mbx,
+    /// PowerVR SGX 535, as in the iPhone 3GS.
+    Sgx,
+    /// Whatever the host's OpenGL (ES) implementation reports.
+    Host,
+}
+
+const VENDOR: &str = "Imagination Technologies";
+
+const GLES1_EXTENSIONS: &[&str] = &[
+    "GL_EXT_texture_filter_anisotropic",
+    "GL_EXT_texture_lod_bias",
+    "GL_IMG_texture_compression_pvrtc",
+    "GL_OES_compressed_paletted_texture",
+    "GL_OES_framebuffer_object",
+    "GL_OES_rgb8_rgba8",
+];
+const GLES2_EXTENSIONS: &[&str] = &["GL_IMG_texture_compression_pvrtc", "GL_OES_rgb8_rgba8"];
+
+/// The formats accepted by `glCompressedTexImage2D()`.
+pub const COMPRESSED_TEXTURE_FORMATS: &[GLenum] = &[
+    gles11::COMPRESSED_RGB_PVRTC_4BPPV1_IMG,
+    gles11::COMPRESSED_RGB_PVRTC_2BPPV1_IMG,
+    gles11::COMPRESSED_RGBA_PVRTC_4BPPV1_IMG,
+    gles11::COMPRESSED_RGBA_PVRTC_2BPPV1_IMG,
+    gles11::PALETTE4_RGB8_OES,
+    gles11::PALETTE4_RGBA8_OES,
+    gles11::PALETTE4_R5_G6_B5_OES,
+    gles11::PALETTE4_RGBA4_OES,
+    gles11::PALETTE4_RGB5_A1_OES,
+    gles11::PALETTE8_RGB8_OES,
+    gles11::PALETTE8_RGBA8_OES,
+    gles11::PALETTE8_R5_G6_B5_OES,
+    gles11::PALETTE8_RGBA4_OES,
+    gles11::PALETTE8_RGB5_A1_OES,
+];
+
+impl GLProfile {
+    /// The value to report for `glGetString(name)`, or [None] if the host's
+    /// value should be used. `extensions` replaces the profile's extension
+    /// list, if provided (see `--gl-extensions=`).
+    ///
+    /// OpenGL ES 2.0 contexts always get the SGX's values, since the MBX
+    /// doesn't support OpenGL ES 2.0.
+    pub fn get_string(
+        self,
+        is_gles2: bool,
+        name: GLenum,
+        extensions: Option<&[String]>,
+    ) -> Option<String> {
+        if name == gles11::EXTENSIONS {
+            if let Some(extensions) = extensions {
+                return Some(extensions.join(" "));
+            }
+        }
+        if self == GLProfile::Host {
+            return None;
+        }
+        let is_sgx = is_gles2 || self == GLProfile::Sgx;
+        match name {
+            gles11::VENDOR => Some(VENDOR.to_string()),
+            gles11::RENDERER if is_sgx => Some("PowerVR SGX 535".to_string()),
+            gles11::RENDERER => Some("PowerVR MBX".to_string()),
+            gles11::VERSION if is_gles2 => Some("OpenGL ES 2.0 IMGSGX535".to_string()),
+            gles11::VERSION => Some("OpenGL ES-CM 1.1 APPLE".to_string()),
+            gles20::SHADING_LANGUAGE_VERSION if is_gles2 => {
+                Some("OpenGL ES GLSL ES 1.00".to_string())
+            }
+            gles11::EXTENSIONS if is_gles2 => Some(GLES2_EXTENSIONS.join(" ")),
+            gles11::EXTENSIONS => Some(GLES1_EXTENSIONS.join(" ")),
+            _ => None,
+        }
+    }
+
+    /// The upper limit to report for a `glGetIntegerv()` parameter that is an
+    /// implementation limit, if any. The host's value is used if it's lower.
+    pub fn max_integer(self, is_gles2: bool, pname: GLenum) -> Option<GLint> {
+        if self == GLProfile::Host {
+            return None;
+        }
+        let is_sgx = is_gles2 || self == GLProfile::Sgx;
+        match pname {
+            gles11::MAX_TEXTURE_SIZE | gles11::MAX_RENDERBUFFER_SIZE_OES => {
+                Some(if is_sgx { 2048 } else { 1024 })
+            }
+            gles11::MAX_TEXTURE_UNITS if !is_gles2 => Some(if is_sgx { 8 } else { 2 }),
+            gles11::MAX_CLIP_PLANES if !is_gles2 => Some(if is_sgx { 6 } else { 1 }),
+            gles11::MAX_LIGHTS if !is_gles2 => Some(8),
+            gles20::MAX_TEXTURE_IMAGE_UNITS if is_gles2 => Some(8),
+            gles20::MAX_VERTEX_ATTRIBS if is_gles2 => Some(16),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_string() {
+        let string = |profile: GLProfile, is_gles2, name| profile.get_string(is_gles2, name, None);
+        assert_eq!(
+            string(GLProfile::Mbx, false, gles11::RENDERER).as_deref(),
+            Some("PowerVR MBX")
+        );
+        // The MBX has no OpenGL ES 2.0 support.
+        assert_eq!(
+            string(GLProfile::Mbx, true, gles11::RENDERER).as_deref(),
+            Some("PowerVR SGX 535")
+        );
+        assert_eq!(string(GLProfile::Host, false, gles11::RENDERER), None);
+        assert_eq!(
+            string(GLProfile::Mbx, false, gles20::SHADING_LANGUAGE_VERSION),
+            None
+        );
+
+        let extensions = ["GL_OES_foo".to_string(), "GL_OES_bar".to_string()];
+        for profile in [GLProfile::Mbx, GLProfile::Host] {
+            assert_eq!(
+                profile
+                    .get_string(false, gles11::EXTENSIONS, Some(&extensions))
+                    .as_deref(),
+                Some("GL_OES_foo GL_OES_bar")
+            );
+        }
+    }
+}
@@ -19,6 +19,7 @@ pub mod ui_device;
 pub mod ui_event;
 pub mod ui_font;
 pub mod ui_geometry;
+pub mod ui_gesture_recognizer;
 pub mod ui_graphics;
 pub mod ui_image;
 pub mod ui_image_picker_controller;
@@ -37,21 +38,26 @@ pub struct State {
     ui_device: ui_device::State,
     ui_font: ui_font::State,
     ui_graphics: ui_graphics::State,
+    ui_image: ui_image::State,
+    ui_responder: ui_responder::State,
     ui_screen: ui_screen::State,
     ui_touch: ui_touch::State,
     pub ui_view: ui_view::State,
+    ui_view_controller: ui_view_controller::State,
 }
 
 /// For use by `NSRunLoop`: handles any events that have queued up.
 ///
 /// Returns the next time this function must be called, if any, e.g. the next
-/// time an accelerometer input is due.
+/// time an accelerometer input is due, or the next frame of a scroll view's
+/// movement.
 pub fn handle_events(env: &mut Environment) -> Option<Instant> {
     use crate::window::Event;
 
     loop {
         // NSRunLoop will never call this function in headless mode.
-        let Some(event) = env.window.as_mut().unwrap().pop_event() else {
+        let uptime = env.clock.uptime();
+        let Some(event) = env.window.as_mut().unwrap().pop_event(uptime) else {
             break;
         };
 
@@ -63,6 +69,10 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
             Event::TouchDown(..) | Event::TouchMove(..) | Event::TouchUp(..) => {
                 ui_touch::handle_event(env, event)
             }
+            Event::TextInput(..) | Event::DeleteBackward => {
+                ui_responder::handle_text_input(env, event)
+            }
+            Event::RotateDevice { clockwise } => ui_device::handle_rotation(env, clockwise),
             Event::AppWillResignActive => {
                 // Getting this event means touchHLE is becoming inactive, e.g.
                 // due to switching apps. The obvious way to handle this would
@@ -89,5 +99,10 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
         }
     }
 
-    ui_accelerometer::handle_accelerometer(env)
+    let scroll_due = ui_view::ui_scroll_view::update_scroll_views(env);
+    let accelerometer_due = ui_accelerometer::handle_accelerometer(env);
+    match (scroll_due, accelerometer_due) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
 }
@@ -7,13 +7,12 @@
 
 use super::ui_device::*;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::foundation::ns_string;
+use crate::frameworks::foundation::{ns_string, ns_user_defaults, NSInteger};
 use crate::frameworks::uikit::ui_nib::load_main_nib_file;
 use crate::mem::MutPtr;
 use crate::objc::{
     id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
 };
-use crate::window::DeviceOrientation;
 use crate::Environment;
 
 #[derive(Default)]
@@ -21,6 +20,8 @@ pub struct State {
     /// [UIApplication sharedApplication]
     shared_application: Option<id>,
     pub(super) status_bar_hidden: bool,
+    status_bar_style: UIStatusBarStyle,
+    status_bar_orientation: UIInterfaceOrientation,
 }
 
 struct UIApplicationHostObject {
@@ -29,7 +30,64 @@ struct UIApplicationHostObject {
 }
 impl HostObject for UIApplicationHostObject {}
 
-type UIInterfaceOrientation = UIDeviceOrientation;
+pub type UIInterfaceOrientation = UIDeviceOrientation;
+pub const UIInterfaceOrientationPortrait: UIInterfaceOrientation = UIDeviceOrientationPortrait;
+pub const UIInterfaceOrientationPortraitUpsideDown: UIInterfaceOrientation =
+    UIDeviceOrientationPortraitUpsideDown;
+pub const UIInterfaceOrientationLandscapeLeft: UIInterfaceOrientation =
+    UIDeviceOrientationLandscapeRight;
+pub const UIInterfaceOrientationLandscapeRight: UIInterfaceOrientation =
+    UIDeviceOrientationLandscapeLeft;
+
+fn interface_orientation_from_name(name: &str) -> Option<UIInterfaceOrientation> {
+    match name {
+        "UIInterfaceOrientationPortrait" => Some(UIInterfaceOrientationPortrait),
+        "UIInterfaceOrientationPortraitUpsideDown" => {
+            Some(UIInterfaceOrientationPortraitUpsideDown)
+        }
+        "UIInterfaceOrientationLandscapeLeft" => Some(UIInterfaceOrientationLandscapeLeft),
+        "UIInterfaceOrientationLandscapeRight" => Some(UIInterfaceOrientationLandscapeRight),
+        _ => None,
+    }
+}
+
+/// Get the orientation of the interface, i.e. of the status bar.
+pub fn status_bar_orientation(env: &Environment) -> UIInterfaceOrientation {
+    env.framework_state
+        .uikit
+        .ui_application
+        .status_bar_orientation
+}
+
+/// Whether the app's Info.plist allows the interface to be rotated to an
+/// orientation. Apps that don't list their orientations allow any.
+pub(super) fn interface_orientation_is_supported(
+    env: &Environment,
+    orientation: UIInterfaceOrientation,
+) -> bool {
+    match env.bundle.supported_interface_orientations() {
+        Some(names) => names
+            .into_iter()
+            .any(|name| interface_orientation_from_name(name) == Some(orientation)),
+        None => true,
+    }
+}
+
+pub type UIStatusBarStyle = NSInteger;
+pub const UIStatusBarStyleDefault: UIStatusBarStyle = 0;
+pub const UIStatusBarStyleBlackTranslucent: UIStatusBarStyle = 1;
+pub const UIStatusBarStyleBlackOpaque: UIStatusBarStyle = 2;
+
+/// For use by the compositor: get the style of the status bar, if it is
+/// visible.
+pub fn visible_status_bar_style(env: &Environment) -> Option<UIStatusBarStyle> {
+    let state = &env.framework_state.uikit.ui_application;
+    if state.status_bar_hidden {
+        None
+    } else {
+        Some(state.status_bar_style)
+    }
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -54,6 +112,37 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (id)init {
     assert!(env.framework_state.uikit.ui_application.shared_application.is_none());
     env.framework_state.uikit.ui_application.shared_application = Some(this);
+
+    // The initial status bar state can be set in the Info.plist.
+    let status_bar_hidden = env.bundle.status_bar_hidden();
+    let status_bar_style = match env.bundle.status_bar_style() {
+        None | Some("UIStatusBarStyleDefault") => UIStatusBarStyleDefault,
+        Some("UIStatusBarStyleBlackTranslucent") => UIStatusBarStyleBlackTranslucent,
+        Some("UIStatusBarStyleBlackOpaque") => UIStatusBarStyleBlackOpaque,
+        Some(other) => {
+            log!("Warning: unknown status bar style {:?}, using default", other);
+            UIStatusBarStyleDefault
+        }
+    };
+    let state = &mut env.framework_state.uikit.ui_application;
+    state.status_bar_hidden = status_bar_hidden;
+    state.status_bar_style = status_bar_style;
+
+    // So can the initial orientation. Otherwise, the orientation the window
+    // was created with is used.
+    let orientation = env.bundle.interface_orientation().map(|name| {
+        interface_orientation_from_name(name).unwrap_or_else(|| {
+            log!("Warning: unknown interface orientation {:?}, using portrait", name);
+            UIInterfaceOrientationPortrait
+        })
+    });
+    let window_orientation = env.window().device_orientation();
+    env.framework_state.uikit.ui_application.status_bar_orientation =
+        from_device_orientation(window_orientation);
+    if let Some(orientation) = orientation {
+        () = msg![env; this setStatusBarOrientation:orientation];
+    }
+
     this
 }
 
@@ -75,7 +164,9 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
-// TODO: statusBarHidden getter
+- (bool)isStatusBarHidden {
+    env.framework_state.uikit.ui_application.status_bar_hidden
+}
 - (())setStatusBarHidden:(bool)hidden {
     env.framework_state.uikit.ui_application.status_bar_hidden = hidden;
 }
@@ -85,14 +176,27 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this setStatusBarHidden:hidden]
 }
 
-// TODO: statusBarOrientation getter
+- (UIStatusBarStyle)statusBarStyle {
+    env.framework_state.uikit.ui_application.status_bar_style
+}
+- (())setStatusBarStyle:(UIStatusBarStyle)style {
+    env.framework_state.uikit.ui_application.status_bar_style = style;
+}
+- (())setStatusBarStyle:(UIStatusBarStyle)style
+               animated:(bool)_animated {
+    // TODO: animation
+    msg![env; this setStatusBarStyle:style]
+}
+
+- (UIInterfaceOrientation)statusBarOrientation {
+    env.framework_state.uikit.ui_application.status_bar_orientation
+}
 - (())setStatusBarOrientation:(UIInterfaceOrientation)orientation {
-    env.window_mut().rotate_device(match orientation {
-        UIDeviceOrientationPortrait => DeviceOrientation::Portrait,
-        UIDeviceOrientationLandscapeLeft => DeviceOrientation::LandscapeLeft,
-        UIDeviceOrientationLandscapeRight => DeviceOrientation::LandscapeRight,
-        _ => unimplemented!("Orientation {} not handled yet", orientation),
-    });
+    let Some(window_orientation) = to_device_orientation(orientation) else {
+        unimplemented!("Orientation {} not handled yet", orientation);
+    };
+    env.framework_state.uikit.ui_application.status_bar_orientation = orientation;
+    env.window_mut().rotate_device(window_orientation);
 }
 - (())setStatusBarOrientation:(UIInterfaceOrientation)orientation
                      animated:(bool)_animated {
@@ -263,6 +367,14 @@ pub(super) fn exit(env: &mut Environment) {
         let _: () = msg![env; pool drain];
     };
 
+    // Preferences the app hasn't explicitly synchronized would otherwise be
+    // lost.
+    if !ns_user_defaults::synchronize_app_domain(env) {
+        log!("Warning: Couldn't save the app's preferences");
+    }
+
+    crate::frameworks::core_animation::free_gl_resources(env);
+
     std::process::exit(0);
 }
 
@@ -5,10 +5,12 @@
  */
 //! `UIColor`.
 
+use crate::frameworks::core_graphics::cg_color::{self, CGColorRef};
 use crate::frameworks::core_graphics::CGFloat;
 use crate::mem::MutPtr;
 use crate::objc::{
-    autorelease, id, msg, msg_class, objc_classes, ClassExports, HostObject, NSZonePtr, ObjC, SEL,
+    autorelease, id, msg, msg_class, nil, objc_classes, ClassExports, HostObject, NSZonePtr, ObjC,
+    SEL,
 };
 use crate::Environment;
 use std::collections::HashMap;
@@ -42,6 +44,9 @@ fn get_standard_color(
 
 struct UIColorHostObject {
     rgba: (CGFloat, CGFloat, CGFloat, CGFloat),
+    /// `CGColorRef`, created on demand by `CGColor`. This is a strong
+    /// reference.
+    cg_color: CGColorRef,
 }
 impl HostObject for UIColorHostObject {}
 
@@ -54,6 +59,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)allocWithZone:(NSZonePtr)_zone {
     let host_object = Box::new(UIColorHostObject {
         rgba: (0.0, 0.0, 0.0, 0.0),
+        cg_color: nil,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -73,6 +79,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)colorWithCGColor:(CGColorRef)cg_color {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCGColor:cg_color];
+    autorelease(env, new)
+}
+
 + (id)clearColor    { get_standard_color(env, _cmd, 0.0, 0.0, 0.0, 0.0) }
 + (id)blackColor    { get_standard_color(env, _cmd, 0.0, 0.0, 0.0, 1.0) }
 + (id)whiteColor    { get_standard_color(env, _cmd, 1.0, 1.0, 1.0, 1.0) }
@@ -114,6 +126,22 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithCGColor:(CGColorRef)cg_color {
+    let rgba = cg_color::get_rgba(&env.objc, cg_color).unwrap();
+    let cg_color = cg_color::CGColorRetain(env, cg_color);
+    *env.objc.borrow_mut::<UIColorHostObject>(this) = UIColorHostObject {
+        rgba,
+        cg_color,
+    };
+    this
+}
+
+- (())dealloc {
+    let cg_color = env.objc.borrow::<UIColorHostObject>(this).cg_color;
+    cg_color::CGColorRelease(env, cg_color);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
 - (bool)getRed:(MutPtr<CGFloat>)r
          green:(MutPtr<CGFloat>)g
           blue:(MutPtr<CGFloat>)b
@@ -126,12 +154,23 @@ pub const CLASSES: ClassExports = objc_classes! {
     true
 }
 
+// The result is owned by the UIColor, so it isn't autoreleased.
+- (CGColorRef)CGColor {
+    let &UIColorHostObject { rgba, cg_color } = env.objc.borrow(this);
+    if cg_color != nil {
+        return cg_color;
+    }
+    let cg_color = cg_color::new_rgba(env, rgba);
+    env.objc.borrow_mut::<UIColorHostObject>(this).cg_color = cg_color;
+    cg_color
+}
+
 @end
 
 };
 
 /// Shortcut for use in Core Animation's compositor: get the RGBA triple for a
-/// `UIColor*`.
-pub fn get_rgba(objc: &ObjC, ui_color: id) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
-    objc.borrow::<UIColorHostObject>(ui_color).rgba
+/// `UIColor*` or a `CGColorRef`.
+pub fn get_rgba(objc: &ObjC, color: id) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+    cg_color::get_rgba(objc, color).unwrap_or_else(|| objc.borrow::<UIColorHostObject>(color).rgba)
 }
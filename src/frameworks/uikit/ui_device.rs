@@ -5,15 +5,18 @@
  */
 //! `UIDevice`.
 
+use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::foundation::ns_string;
 use crate::frameworks::foundation::NSInteger;
-use crate::objc::{id, objc_classes, ClassExports, TrivialHostObject};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, ClassExports, TrivialHostObject,
+};
+use crate::window::DeviceOrientation;
+use crate::Environment;
 
 pub type UIDeviceOrientation = NSInteger;
-#[allow(dead_code)]
 pub const UIDeviceOrientationUnknown: UIDeviceOrientation = 0;
 pub const UIDeviceOrientationPortrait: UIDeviceOrientation = 1;
-#[allow(dead_code)]
 pub const UIDeviceOrientationPortraitUpsideDown: UIDeviceOrientation = 2;
 pub const UIDeviceOrientationLandscapeLeft: UIDeviceOrientation = 3;
 pub const UIDeviceOrientationLandscapeRight: UIDeviceOrientation = 4;
@@ -22,9 +25,102 @@ pub const UIDeviceOrientationFaceUp: UIDeviceOrientation = 5;
 #[allow(dead_code)]
 pub const UIDeviceOrientationFaceDown: UIDeviceOrientation = 6;
 
+pub type UIUserInterfaceIdiom = NSInteger;
+pub const UIUserInterfaceIdiomPhone: UIUserInterfaceIdiom = 0;
+pub const UIUserInterfaceIdiomPad: UIUserInterfaceIdiom = 1;
+
+pub const UIDeviceOrientationDidChangeNotification: &str =
+    "UIDeviceOrientationDidChangeNotification";
+
+/// `NSNotificationName` values.
+pub const CONSTANTS: ConstantExports = &[(
+    "_UIDeviceOrientationDidChangeNotification",
+    HostConstant::NSString(UIDeviceOrientationDidChangeNotification),
+)];
+
 #[derive(Default)]
 pub struct State {
     current_device: Option<id>,
+    /// Nesting count for `beginGeneratingDeviceOrientationNotifications`.
+    generating_orientation_notifications: u32,
+    /// The physical orientation of the emulated device, once the user has
+    /// rotated it (see [handle_rotation]). Until then, it is assumed to match
+    /// the window.
+    orientation: Option<DeviceOrientation>,
+}
+
+pub(super) fn from_device_orientation(orientation: DeviceOrientation) -> UIDeviceOrientation {
+    match orientation {
+        DeviceOrientation::Portrait => UIDeviceOrientationPortrait,
+        DeviceOrientation::LandscapeLeft => UIDeviceOrientationLandscapeLeft,
+        DeviceOrientation::LandscapeRight => UIDeviceOrientationLandscapeRight,
+    }
+}
+
+pub(super) fn to_device_orientation(orientation: UIDeviceOrientation) -> Option<DeviceOrientation> {
+    match orientation {
+        UIDeviceOrientationPortrait => Some(DeviceOrientation::Portrait),
+        UIDeviceOrientationLandscapeLeft => Some(DeviceOrientation::LandscapeLeft),
+        UIDeviceOrientationLandscapeRight => Some(DeviceOrientation::LandscapeRight),
+        _ => None,
+    }
+}
+
+fn physical_orientation(env: &Environment) -> DeviceOrientation {
+    if let Some(orientation) = env.framework_state.uikit.ui_device.orientation {
+        return orientation;
+    }
+    match &env.window {
+        Some(window) => window.device_orientation(),
+        None => env.options.initial_orientation,
+    }
+}
+
+/// Get the orientation the device is considered to be in. Like on a real
+/// device, there is no orientation before notifications have been requested.
+fn current_orientation(env: &Environment) -> UIDeviceOrientation {
+    if env
+        .framework_state
+        .uikit
+        .ui_device
+        .generating_orientation_notifications
+        == 0
+    {
+        return UIDeviceOrientationUnknown;
+    }
+    from_device_orientation(physical_orientation(env))
+}
+
+/// For use by [super::handle_events]: the user rotated the emulated device.
+pub(super) fn handle_rotation(env: &mut Environment, clockwise: bool) {
+    let new_orientation = match (physical_orientation(env), clockwise) {
+        (DeviceOrientation::Portrait, false) => DeviceOrientation::LandscapeLeft,
+        (DeviceOrientation::Portrait, true) => DeviceOrientation::LandscapeRight,
+        (DeviceOrientation::LandscapeLeft, true) | (DeviceOrientation::LandscapeRight, false) => {
+            DeviceOrientation::Portrait
+        }
+        // TODO: support upside-down portrait
+        _ => {
+            log!("Rotating the device upside-down is not supported, ignoring");
+            return;
+        }
+    };
+    env.framework_state.uikit.ui_device.orientation = Some(new_orientation);
+
+    if env
+        .framework_state
+        .uikit
+        .ui_device
+        .generating_orientation_notifications
+        > 0
+    {
+        let device: id = msg_class![env; UIDevice currentDevice];
+        () = msg![env; device _touchHLE_postOrientationNotification];
+    }
+
+    // Each interface orientation has the same value as the device orientation
+    // it corresponds to (despite left and right being swapped in the names).
+    super::ui_view_controller::autorotate(env, from_device_orientation(new_orientation));
 }
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -47,20 +143,82 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+
 - (())beginGeneratingDeviceOrientationNotifications {
-    log!("TODO: beginGeneratingDeviceOrientationNotifications");
+    let state = &mut env.framework_state.uikit.ui_device;
+    state.generating_orientation_notifications += 1;
+    if state.generating_orientation_notifications == 1 {
+        // The orientation has just become known, which counts as a change.
+        // The notification is posted from the run loop, so that observers
+        // added right after this call still get it.
+        let selector = env
+            .objc
+            .lookup_selector("_touchHLE_postOrientationNotification")
+            .unwrap();
+        () = msg![env; this performSelector:selector withObject:nil afterDelay:0.0];
+    }
 }
 - (())endGeneratingDeviceOrientationNotifications {
-    log!("TODO: endGeneratingDeviceOrientationNotifications");
+    let state = &mut env.framework_state.uikit.ui_device;
+    state.generating_orientation_notifications =
+        state.generating_orientation_notifications.saturating_sub(1);
+}
+- (bool)isGeneratingDeviceOrientationNotifications {
+    env.framework_state.uikit.ui_device.generating_orientation_notifications > 0
 }
+- (())_touchHLE_postOrientationNotification {
+    if current_orientation(env) == UIDeviceOrientationUnknown {
+        return;
+    }
+    let name = ns_string::get_static_str(env, UIDeviceOrientationDidChangeNotification);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    () = msg![env; center postNotificationName:name object:this];
+}
+
+- (UIDeviceOrientation)orientation {
+    current_orientation(env)
+}
+
 - (id)model {
-    // TODO: Hardcoded to iPhone for now
-    ns_string::get_static_str(env, "iPhone")
+    let model = env.options.device_model.clone();
+    let model = ns_string::from_rust_string(env, model);
+    autorelease(env, model)
+}
+- (id)localizedModel {
+    msg![env; this model]
+}
+
+- (id)name {
+    let name = env.options.device_name.clone();
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+
+- (id)systemName {
+    ns_string::get_static_str(env, "iPhone OS")
 }
 
-// NSString
 - (id)systemVersion {
-    ns_string::get_static_str(env, "2.0")
+    let version = env.options.system_version.clone();
+    let version = ns_string::from_rust_string(env, version);
+    autorelease(env, version)
+}
+
+- (UIUserInterfaceIdiom)userInterfaceIdiom {
+    if env.options.device_model == "iPad" {
+        UIUserInterfaceIdiomPad
+    } else {
+        UIUserInterfaceIdiomPhone
+    }
+}
+
+- (id)uniqueIdentifier {
+    // A real device has a random-looking 40-digit hexadecimal identifier. A
+    // fixed one keeps runs reproducible.
+    ns_string::get_static_str(env, "7f0c5ffb3d2b8f8a1d7f4de5a1b0e9c2c98e5d16")
 }
 
 - (bool)isMultitaskingSupported {
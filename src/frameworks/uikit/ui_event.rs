@@ -7,6 +7,7 @@
 
 use super::ui_touch::UITouchHostObject;
 use crate::frameworks::core_graphics::CGPoint;
+use crate::frameworks::foundation::ns_set;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
     NSZonePtr,
@@ -39,21 +40,23 @@ pub const CLASSES: ClassExports = objc_classes! {
     let &UIEventHostObject { touches, view } = env.objc.borrow(this);
     release(env, touches);
     release(env, view);
+    env.objc.dealloc_object(this, &mut env.mem)
 }
 
 - (id)touchesForView:(id)view {
     let &UIEventHostObject { touches, .. } = env.objc.borrow(this);
-    // TODO: broken for multi-touch
-    let touch: id = msg![env; touches anyObject];
-    let &UITouchHostObject { original_location, window, .. } = env.objc.borrow(touch);
-    // FIXME: handle non-zero-origin windows
-    let location_in_view: CGPoint = msg![env; window convertPoint:original_location toView:view];
-    if msg![env; view pointInside:location_in_view withEvent:this] {
-        msg_class![env; NSSet setWithObject:touch]
-    } else {
-        let empty_set: id = msg_class![env; NSSet new];
-        autorelease(env, empty_set)
+    let touches = ns_set::to_vec(env, touches);
+    let mut touches_in_view = Vec::new();
+    for touch in touches {
+        let &UITouchHostObject { original_location, window, .. } = env.objc.borrow(touch);
+        // FIXME: handle non-zero-origin windows
+        let location_in_view: CGPoint = msg![env; window convertPoint:original_location toView:view];
+        if msg![env; view pointInside:location_in_view withEvent:this] {
+            touches_in_view.push(touch);
+        }
     }
+    let set = ns_set::from_vec(env, touches_in_view);
+    autorelease(env, set)
 }
 
 - (id)allTouches {
@@ -67,8 +70,8 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
-/// For use by [super::ui_touch]: create a `UIEvent` with a set of `UITouch*`
-/// and the view it was originally sent to.
+/// For use by [super::ui_touch]: create a `UIEvent` with the set of all current
+/// `UITouch*` and the view it was originally sent to.
 pub(super) fn new_event(env: &mut Environment, touches: id, view: id) -> id {
     let event: id = msg_class![env; UIEvent alloc];
     retain(env, touches);
@@ -9,8 +9,8 @@ use super::ui_graphics::UIGraphicsGetCurrentContext;
 use crate::font::{Font, TextAlignment, WrapMode};
 use crate::frameworks::core_graphics::cg_bitmap_context::CGBitmapContextDrawer;
 use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
-use crate::frameworks::foundation::NSInteger;
-use crate::objc::{autorelease, id, objc_classes, ClassExports, HostObject};
+use crate::frameworks::foundation::{ns_string, NSInteger};
+use crate::objc::{autorelease, id, msg, objc_classes, Class, ClassExports, HostObject};
 use crate::Environment;
 
 #[derive(Default)]
@@ -66,39 +66,36 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation UIFont: NSObject
 
 + (id)systemFontOfSize:(CGFloat)size {
-    // Cache for later use
-    if env.framework_state.uikit.ui_font.regular.is_none() {
-        env.framework_state.uikit.ui_font.regular = Some(Font::sans_regular());
-    }
-    let host_object = UIFontHostObject {
-        size,
-        kind: FontKind::Regular,
-    };
-    let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
+    let new = new_font(env, this, size, FontKind::Regular);
     autorelease(env, new)
 }
 + (id)boldSystemFontOfSize:(CGFloat)size {
-    // Cache for later use
-    if env.framework_state.uikit.ui_font.bold.is_none() {
-        env.framework_state.uikit.ui_font.bold = Some(Font::sans_bold());
-    }
-    let host_object = UIFontHostObject {
-        size,
-        kind: FontKind::Bold,
-    };
-    let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
+    let new = new_font(env, this, size, FontKind::Bold);
     autorelease(env, new)
 }
 + (id)italicSystemFontOfSize:(CGFloat)size {
-    // Cache for later use
-    if env.framework_state.uikit.ui_font.italic.is_none() {
-        env.framework_state.uikit.ui_font.italic = Some(Font::sans_italic());
-    }
-    let host_object = UIFontHostObject {
-        size,
-        kind: FontKind::Italic,
-    };
-    let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
+    let new = new_font(env, this, size, FontKind::Italic);
+    autorelease(env, new)
+}
+
++ (id)fontWithName:(id)name // NSString*
+              size:(CGFloat)size {
+    // Only the system font is bundled, so all fonts are substituted with it,
+    // in the style that seems closest.
+    let name = ns_string::to_rust_string(env, name);
+    let kind = kind_for_font_name(&name);
+    let new = new_font(env, this, size, kind);
+    autorelease(env, new)
+}
+
+- (CGFloat)pointSize {
+    env.objc.borrow::<UIFontHostObject>(this).size
+}
+
+- (id)fontWithSize:(CGFloat)size {
+    let kind = env.objc.borrow::<UIFontHostObject>(this).kind;
+    let class = msg![env; this class];
+    let new = new_font(env, class, size, kind);
     autorelease(env, new)
 }
 
@@ -106,6 +103,58 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+fn kind_for_font_name(name: &str) -> FontKind {
+    log_dbg!("Substituting system font for {:?}", name);
+    if name.contains("Bold") {
+        FontKind::Bold
+    } else if name.contains("Italic") || name.contains("Oblique") {
+        FontKind::Italic
+    } else {
+        FontKind::Regular
+    }
+}
+
+fn new_font(env: &mut Environment, class: Class, size: CGFloat, kind: FontKind) -> id {
+    cache_font(&mut env.framework_state.uikit.ui_font, kind);
+    let host_object = UIFontHostObject { size, kind };
+    env.objc
+        .alloc_object(class, Box::new(host_object), &mut env.mem)
+}
+
+/// Load a font for later use by [get_font], if it hasn't been already.
+fn cache_font(state: &mut State, kind: FontKind) {
+    match kind {
+        FontKind::Regular => {
+            state.regular.get_or_insert_with(Font::sans_regular);
+        }
+        FontKind::Bold => {
+            state.bold.get_or_insert_with(Font::sans_bold);
+        }
+        FontKind::Italic => {
+            state.italic.get_or_insert_with(Font::sans_italic);
+        }
+    }
+}
+
+/// For use by Core Graphics' text drawing: get the bundled font that is
+/// substituted for a font name (see `fontWithName:size:`), for drawing some
+/// text.
+pub fn font_for_name<'a>(env: &'a mut Environment, name: &str, text: &str) -> &'a Font {
+    let kind = kind_for_font_name(name);
+    let state = &mut env.framework_state.uikit.ui_font;
+    cache_font(state, kind);
+    get_font(state, kind, text)
+}
+
+/// For use by `UILabel`: get the height of some number of lines of text.
+pub fn height_of_lines(env: &mut Environment, font: id, lines: NSInteger) -> CGFloat {
+    let host_object = env.objc.borrow::<UIFontHostObject>(font);
+    // This ignores the fallback font for CJK text, which may have slightly
+    // different metrics.
+    let font = get_font(&mut env.framework_state.uikit.ui_font, host_object.kind, "");
+    font.height_of_lines(host_object.size, lines.try_into().unwrap())
+}
+
 fn convert_line_break_mode(ui_mode: UILineBreakMode) -> WrapMode {
     match ui_mode {
         UILineBreakModeWordWrap => WrapMode::Word,
@@ -172,8 +221,10 @@ pub fn size_with_font(
     );
 
     let wrap = constrained.map(|(size, ui_mode)| (size.width, convert_line_break_mode(ui_mode)));
+    let max_lines =
+        constrained.map(|(size, _)| font.lines_fitting_height(host_object.size, size.height));
 
-    let (width, height) = font.calculate_text_size(host_object.size, text, wrap);
+    let (width, height) = font.calculate_text_size(host_object.size, text, wrap, max_lines);
 
     CGSize { width, height }
 }
@@ -198,8 +249,10 @@ pub fn draw_at_point(
 
     let width_and_line_break_mode =
         width_and_line_break_mode.map(|(width, ui_mode)| (width, convert_line_break_mode(ui_mode)));
+    // If a width is given, only a single line is drawn.
+    let max_lines = width_and_line_break_mode.map(|_| 1);
     let (width, height) =
-        font.calculate_text_size(host_object.size, text, width_and_line_break_mode);
+        font.calculate_text_size(host_object.size, text, width_and_line_break_mode, max_lines);
 
     let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
 
@@ -211,6 +264,7 @@ pub fn draw_at_point(
         text,
         (translation.0 + point.x, translation.1 + point.y),
         width_and_line_break_mode,
+        max_lines,
         TextAlignment::Left,
         |(x, y), coverage| {
             let (r, g, b, a) = fill_color;
@@ -263,6 +317,7 @@ pub fn draw_in_rect(
             translation.1 + rect.origin.y,
         ),
         Some((rect.size.width, convert_line_break_mode(line_break_mode))),
+        Some(font.lines_fitting_height(host_object.size, rect.size.height)),
         alignment,
         |(x, y), coverage| {
             let (r, g, b, a) = fill_color;
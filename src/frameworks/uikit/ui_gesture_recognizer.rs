@@ -0,0 +1,413 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIGestureRecognizer`.
+//!
+//! Touches are delivered to the gesture recognizers attached to the touched
+//! view and its superviews before they are delivered to the view itself (see
+//! [super::ui_touch]). Recognizers only ever see the touches that began in
+//! their view's hierarchy, one touch at a time, like views do.
+//!
+//! Useful resources:
+//! - The [Gesture Recognizers section](https://developer.apple.com/library/archive/documentation/EventHandling/Conceptual/EventHandlingiPhoneOS/GestureRecognizer_basics/GestureRecognizer_basics.html) of Apple's "Event Handling Guide for iOS".
+
+pub mod ui_pan_gesture_recognizer;
+pub mod ui_pinch_gesture_recognizer;
+pub mod ui_tap_gesture_recognizer;
+
+use super::ui_touch::{
+    UITouchPhase, UITouchPhaseBegan, UITouchPhaseCancelled, UITouchPhaseEnded, UITouchPhaseMoved,
+};
+use super::ui_view::UIViewHostObject;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint};
+use crate::frameworks::foundation::{NSInteger, NSUInteger};
+use crate::objc::{
+    id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr, SEL,
+};
+use crate::Environment;
+
+pub type UIGestureRecognizerState = NSInteger;
+pub const UIGestureRecognizerStatePossible: UIGestureRecognizerState = 0;
+pub const UIGestureRecognizerStateBegan: UIGestureRecognizerState = 1;
+pub const UIGestureRecognizerStateChanged: UIGestureRecognizerState = 2;
+pub const UIGestureRecognizerStateEnded: UIGestureRecognizerState = 3;
+pub const UIGestureRecognizerStateCancelled: UIGestureRecognizerState = 4;
+pub const UIGestureRecognizerStateFailed: UIGestureRecognizerState = 5;
+/// Discrete gestures go straight from possible to recognized.
+pub const UIGestureRecognizerStateRecognized: UIGestureRecognizerState =
+    UIGestureRecognizerStateEnded;
+
+pub struct UIGestureRecognizerHostObject {
+    /// See `addTarget:action:`. The targets are weak references!
+    targets: Vec<(id, SEL)>,
+    /// `UIView*` the recognizer is attached to. This is a weak reference.
+    view: id,
+    state: UIGestureRecognizerState,
+    enabled: bool,
+    cancels_touches_in_view: bool,
+    /// Weak reference.
+    delegate: id,
+    /// `UITouch*`es currently being tracked. These are strong references.
+    touches: Vec<id>,
+}
+impl HostObject for UIGestureRecognizerHostObject {}
+impl Default for UIGestureRecognizerHostObject {
+    fn default() -> Self {
+        // The Default trait is implemented so subclasses will get the same
+        // defaults.
+        UIGestureRecognizerHostObject {
+            targets: Vec::new(),
+            view: nil,
+            state: UIGestureRecognizerStatePossible,
+            enabled: true,
+            cancels_touches_in_view: true,
+            delegate: nil,
+            touches: Vec::new(),
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// abstract class
+@implementation UIGestureRecognizer: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIGestureRecognizerHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithTarget:(id)target
+              action:(SEL)action {
+    if target != nil {
+        () = msg![env; this addTarget:target action:action];
+    }
+    this
+}
+
+- (())dealloc {
+    let touches = std::mem::take(
+        &mut env.objc.borrow_mut::<UIGestureRecognizerHostObject>(this).touches
+    );
+    for touch in touches {
+        release(env, touch);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())addTarget:(id)target
+         action:(SEL)action {
+    let targets = &mut env.objc.borrow_mut::<UIGestureRecognizerHostObject>(this).targets;
+    if !targets.contains(&(target, action)) {
+        targets.push((target, action));
+    }
+}
+- (())removeTarget:(id)target
+            action:(SEL)action {
+    // A nil target matches any target.
+    // TODO: a NULL action should match any action.
+    env.objc.borrow_mut::<UIGestureRecognizerHostObject>(this).targets.retain(
+        |&(t, a)| !((target == nil || t == target) && a == action)
+    );
+}
+
+- (id)view {
+    env.objc.borrow::<UIGestureRecognizerHostObject>(this).view
+}
+
+- (UIGestureRecognizerState)state {
+    env.objc.borrow::<UIGestureRecognizerHostObject>(this).state
+}
+- (())setState:(UIGestureRecognizerState)state {
+    set_state(env, this, state);
+}
+
+- (bool)isEnabled {
+    env.objc.borrow::<UIGestureRecognizerHostObject>(this).enabled
+}
+- (())setEnabled:(bool)enabled {
+    env.objc.borrow_mut::<UIGestureRecognizerHostObject>(this).enabled = enabled;
+    if !enabled {
+        let state = env.objc.borrow::<UIGestureRecognizerHostObject>(this).state;
+        if matches!(state, UIGestureRecognizerStateBegan | UIGestureRecognizerStateChanged) {
+            set_state(env, this, UIGestureRecognizerStateCancelled);
+        }
+    }
+}
+
+- (bool)cancelsTouchesInView {
+    env.objc.borrow::<UIGestureRecognizerHostObject>(this).cancels_touches_in_view
+}
+- (())setCancelsTouchesInView:(bool)cancels {
+    env.objc.borrow_mut::<UIGestureRecognizerHostObject>(this).cancels_touches_in_view = cancels;
+}
+
+- (id)delegate {
+    env.objc.borrow::<UIGestureRecognizerHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate {
+    env.objc.borrow_mut::<UIGestureRecognizerHostObject>(this).delegate = delegate;
+}
+
+- (())requireGestureRecognizerToFail:(id)other { // UIGestureRecognizer*
+    log!(
+        "TODO: [{:?} requireGestureRecognizerToFail:{:?}] (ignored)",
+        this,
+        other
+    );
+}
+
+- (NSUInteger)numberOfTouches {
+    env.objc.borrow::<UIGestureRecognizerHostObject>(this).touches.len() as NSUInteger
+}
+- (CGPoint)locationOfTouch:(NSUInteger)index
+                    inView:(id)view { // UIView*
+    let touches = &env.objc.borrow::<UIGestureRecognizerHostObject>(this).touches;
+    let Some(&touch) = touches.get(index as usize) else {
+        panic!("Touch index {} out of range ({} touches)", index, touches.len());
+    };
+    msg![env; touch locationInView:view]
+}
+- (CGPoint)locationInView:(id)view { // UIView*
+    // The centroid of the touches.
+    let touches = env.objc.borrow::<UIGestureRecognizerHostObject>(this).touches.clone();
+    let points: Vec<CGPoint> = touches
+        .into_iter()
+        .map(|touch| msg![env; touch locationInView:view])
+        .collect();
+    centroid(&points)
+}
+
+// These are meant to be overridden by subclasses. Subclasses must call super.
+- (())reset {}
+- (())touchesBegan:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event {} // UIEvent*
+- (())touchesMoved:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event {} // UIEvent*
+- (())touchesEnded:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event {} // UIEvent*
+- (())touchesCancelled:(id)_touches // NSSet* of UITouch*
+             withEvent:(id)_event {} // UIEvent*
+
+@end
+
+};
+
+/// The average of some points, or the origin if there are none.
+fn centroid(points: &[CGPoint]) -> CGPoint {
+    if points.is_empty() {
+        return CGPoint { x: 0.0, y: 0.0 };
+    }
+    let count = points.len() as CGFloat;
+    CGPoint {
+        x: points.iter().map(|p| p.x).sum::<CGFloat>() / count,
+        y: points.iter().map(|p| p.y).sum::<CGFloat>() / count,
+    }
+}
+
+/// Change the state of a recognizer and send its actions if the new state
+/// calls for it.
+fn set_state(env: &mut Environment, this: id, state: UIGestureRecognizerState) {
+    let host_object = env.objc.borrow::<UIGestureRecognizerHostObject>(this);
+    let (old_state, delegate) = (host_object.state, host_object.delegate);
+
+    // The delegate can prevent a gesture from being recognized.
+    let state = if old_state == UIGestureRecognizerStatePossible
+        && matches!(
+            state,
+            UIGestureRecognizerStateBegan | UIGestureRecognizerStateRecognized
+        )
+        && delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, "gestureRecognizerShouldBegin:")
+        && !msg![env; delegate gestureRecognizerShouldBegin:this]
+    {
+        UIGestureRecognizerStateFailed
+    } else {
+        state
+    };
+
+    log_dbg!(
+        "Gesture recognizer {:?} state {} => {}",
+        this,
+        old_state,
+        state
+    );
+    env.objc
+        .borrow_mut::<UIGestureRecognizerHostObject>(this)
+        .state = state;
+
+    if !matches!(
+        state,
+        UIGestureRecognizerStateBegan
+            | UIGestureRecognizerStateChanged
+            | UIGestureRecognizerStateEnded
+            | UIGestureRecognizerStateCancelled
+    ) {
+        return;
+    }
+    let targets = env
+        .objc
+        .borrow::<UIGestureRecognizerHostObject>(this)
+        .targets
+        .clone();
+    for (target, action) in targets {
+        let sel_str = action.as_str(&env.mem);
+        log_dbg!(
+            "Sending {:?} to {:?} for gesture recognizer {:?}",
+            sel_str,
+            target,
+            this
+        );
+        match sel_str.bytes().filter(|&b| b == b':').count() {
+            // - (void)handleGesture;
+            0 => () = msg_send(env, (target, action)),
+            // - (void)handleGesture:(UIGestureRecognizer*)recognizer;
+            _ => () = msg_send(env, (target, action, this)),
+        }
+    }
+}
+
+fn is_finished(state: UIGestureRecognizerState) -> bool {
+    matches!(
+        state,
+        UIGestureRecognizerStateEnded
+            | UIGestureRecognizerStateCancelled
+            | UIGestureRecognizerStateFailed
+    )
+}
+
+/// Attach a recognizer to a view, for `[UIView addGestureRecognizer:]`.
+pub(super) fn set_view(env: &mut Environment, this: id, view: id) {
+    env.objc
+        .borrow_mut::<UIGestureRecognizerHostObject>(this)
+        .view = view;
+}
+
+/// Find the recognizers that should receive a new touch in a view: the ones
+/// attached to the view and to its superviews. The result is retained.
+pub(super) fn recognizers_for_touch(env: &mut Environment, view: id, touch: id) -> Vec<id> {
+    let mut recognizers = Vec::new();
+    let mut view = view;
+    while view != nil {
+        let attached = env
+            .objc
+            .borrow::<UIViewHostObject>(view)
+            .gesture_recognizers
+            .clone();
+        for recognizer in attached {
+            let &UIGestureRecognizerHostObject {
+                enabled, delegate, ..
+            } = env.objc.borrow(recognizer);
+            if !enabled {
+                continue;
+            }
+            if delegate != nil
+                && env.objc.object_has_method_named(
+                    &env.mem,
+                    delegate,
+                    "gestureRecognizer:shouldReceiveTouch:",
+                )
+                && !msg![env; delegate gestureRecognizer:recognizer shouldReceiveTouch:touch]
+            {
+                continue;
+            }
+            recognizers.push(retain(env, recognizer));
+        }
+        view = msg![env; view superview];
+    }
+    recognizers
+}
+
+/// Deliver a touch in the given phase to a recognizer that is tracking it.
+/// Returns the touches whose delivery to their views should be cancelled
+/// because the recognizer recognized its gesture.
+pub(super) fn handle_touch(
+    env: &mut Environment,
+    this: id,
+    touch: id,
+    phase: UITouchPhase,
+    event: id,
+) -> Vec<id> {
+    let touches: id = msg_class![env; NSSet setWithObject:touch];
+
+    if phase == UITouchPhaseBegan {
+        retain(env, touch);
+        env.objc
+            .borrow_mut::<UIGestureRecognizerHostObject>(this)
+            .touches
+            .push(touch);
+    } else if !env
+        .objc
+        .borrow::<UIGestureRecognizerHostObject>(this)
+        .touches
+        .contains(&touch)
+    {
+        return Vec::new();
+    }
+
+    let &UIGestureRecognizerHostObject { state, enabled, .. } = env.objc.borrow(this);
+    // Recognizers that have finished ignore their touches until they are all
+    // lifted and the recognizer is reset.
+    if enabled && !is_finished(state) {
+        match phase {
+            UITouchPhaseBegan => () = msg![env; this touchesBegan:touches withEvent:event],
+            UITouchPhaseMoved => () = msg![env; this touchesMoved:touches withEvent:event],
+            UITouchPhaseEnded => () = msg![env; this touchesEnded:touches withEvent:event],
+            UITouchPhaseCancelled => () = msg![env; this touchesCancelled:touches withEvent:event],
+            _ => unreachable!(),
+        }
+    }
+
+    let &UIGestureRecognizerHostObject {
+        state: new_state,
+        cancels_touches_in_view,
+        ..
+    } = env.objc.borrow(this);
+    let recognized = matches!(
+        new_state,
+        UIGestureRecognizerStateBegan
+            | UIGestureRecognizerStateChanged
+            | UIGestureRecognizerStateEnded
+    );
+    let cancelled_touches = if recognized && cancels_touches_in_view {
+        env.objc
+            .borrow::<UIGestureRecognizerHostObject>(this)
+            .touches
+            .clone()
+    } else {
+        Vec::new()
+    };
+
+    if matches!(phase, UITouchPhaseEnded | UITouchPhaseCancelled) {
+        let host_object = env.objc.borrow_mut::<UIGestureRecognizerHostObject>(this);
+        host_object.touches.retain(|&t| t != touch);
+        let no_touches_left = host_object.touches.is_empty();
+        // The caller still holds a reference to the touch.
+        release(env, touch);
+        if no_touches_left {
+            if matches!(
+                new_state,
+                UIGestureRecognizerStateBegan | UIGestureRecognizerStateChanged
+            ) {
+                // A continuous gesture must end with its touches.
+                set_state(env, this, UIGestureRecognizerStateCancelled);
+            }
+            let state = env.objc.borrow::<UIGestureRecognizerHostObject>(this).state;
+            if is_finished(state) {
+                env.objc
+                    .borrow_mut::<UIGestureRecognizerHostObject>(this)
+                    .state = UIGestureRecognizerStatePossible;
+                () = msg![env; this reset];
+            }
+        }
+    }
+
+    cancelled_touches
+}
@@ -0,0 +1,217 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIPanGestureRecognizer`.
+//!
+//! The translation and velocity are tracked relative to the screen, so views
+//! with a transform will see them unscaled and unrotated.
+
+use super::{
+    centroid, set_state, UIGestureRecognizerHostObject, UIGestureRecognizerState,
+    UIGestureRecognizerStateBegan, UIGestureRecognizerStateCancelled,
+    UIGestureRecognizerStateChanged, UIGestureRecognizerStateEnded, UIGestureRecognizerStateFailed,
+    UIGestureRecognizerStatePossible,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint};
+use crate::frameworks::foundation::{NSTimeInterval, NSUInteger};
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_super, nil, objc_classes, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+/// How far the touches have to move before the gesture begins.
+const HYSTERESIS: CGFloat = 10.0;
+
+pub struct UIPanGestureRecognizerHostObject {
+    superclass: super::UIGestureRecognizerHostObject,
+    minimum_number_of_touches: NSUInteger,
+    maximum_number_of_touches: NSUInteger,
+    translation: CGPoint,
+    velocity: CGPoint,
+    /// Centroid of the touches at the last event, relative to the screen.
+    last_location: Option<CGPoint>,
+    last_timestamp: NSTimeInterval,
+}
+impl_HostObject_with_superclass!(UIPanGestureRecognizerHostObject);
+impl Default for UIPanGestureRecognizerHostObject {
+    fn default() -> Self {
+        UIPanGestureRecognizerHostObject {
+            superclass: Default::default(),
+            minimum_number_of_touches: 1,
+            maximum_number_of_touches: NSUInteger::MAX,
+            translation: CGPoint { x: 0.0, y: 0.0 },
+            velocity: CGPoint { x: 0.0, y: 0.0 },
+            last_location: None,
+            last_timestamp: 0.0,
+        }
+    }
+}
+
+/// The centroid moves when touches are added or removed, which shouldn't
+/// count as movement. `lifted_touch` is a touch that is about to be removed,
+/// or [nil].
+fn touches_changed(env: &mut Environment, this: id, lifted_touch: id) {
+    let touches = env
+        .objc
+        .borrow::<UIGestureRecognizerHostObject>(this)
+        .touches
+        .clone();
+    let points: Vec<CGPoint> = touches
+        .into_iter()
+        .filter(|&touch| touch != lifted_touch)
+        .map(|touch| msg![env; touch locationInView:nil])
+        .collect();
+    let location = centroid(&points);
+    env.objc
+        .borrow_mut::<UIPanGestureRecognizerHostObject>(this)
+        .last_location = Some(location);
+}
+
+fn touch_count_allowed(env: &Environment, this: id) -> bool {
+    let count = env
+        .objc
+        .borrow::<UIGestureRecognizerHostObject>(this)
+        .touches
+        .len() as NSUInteger;
+    let host_object = env.objc.borrow::<UIPanGestureRecognizerHostObject>(this);
+    (host_object.minimum_number_of_touches..=host_object.maximum_number_of_touches).contains(&count)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIPanGestureRecognizer: UIGestureRecognizer
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIPanGestureRecognizerHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (NSUInteger)minimumNumberOfTouches {
+    env.objc.borrow::<UIPanGestureRecognizerHostObject>(this).minimum_number_of_touches
+}
+- (())setMinimumNumberOfTouches:(NSUInteger)number {
+    env.objc.borrow_mut::<UIPanGestureRecognizerHostObject>(this).minimum_number_of_touches =
+        number;
+}
+- (NSUInteger)maximumNumberOfTouches {
+    env.objc.borrow::<UIPanGestureRecognizerHostObject>(this).maximum_number_of_touches
+}
+- (())setMaximumNumberOfTouches:(NSUInteger)number {
+    env.objc.borrow_mut::<UIPanGestureRecognizerHostObject>(this).maximum_number_of_touches =
+        number;
+}
+
+- (CGPoint)translationInView:(id)_view { // UIView*
+    env.objc.borrow::<UIPanGestureRecognizerHostObject>(this).translation
+}
+- (())setTranslation:(CGPoint)translation
+              inView:(id)_view { // UIView*
+    let host_object = env.objc.borrow_mut::<UIPanGestureRecognizerHostObject>(this);
+    host_object.translation = translation;
+    host_object.velocity = CGPoint { x: 0.0, y: 0.0 };
+}
+- (CGPoint)velocityInView:(id)_view { // UIView*
+    env.objc.borrow::<UIPanGestureRecognizerHostObject>(this).velocity
+}
+
+- (())reset {
+    let host_object = env.objc.borrow_mut::<UIPanGestureRecognizerHostObject>(this);
+    host_object.translation = CGPoint { x: 0.0, y: 0.0 };
+    host_object.velocity = CGPoint { x: 0.0, y: 0.0 };
+    host_object.last_location = None;
+    msg_super![env; this reset]
+}
+
+- (())touchesBegan:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesBegan:touches withEvent:event];
+    touches_changed(env, this, nil);
+    let touch: id = msg![env; touches anyObject];
+    let timestamp: NSTimeInterval = msg![env; touch timestamp];
+    env.objc.borrow_mut::<UIPanGestureRecognizerHostObject>(this).last_timestamp = timestamp;
+}
+
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesMoved:touches withEvent:event];
+
+    let touch: id = msg![env; touches anyObject];
+    let timestamp: NSTimeInterval = msg![env; touch timestamp];
+    let location: CGPoint = msg![env; this locationInView:nil];
+    let host_object = env.objc.borrow_mut::<UIPanGestureRecognizerHostObject>(this);
+    let last_location = host_object.last_location.unwrap_or(location);
+    let delta = CGPoint {
+        x: location.x - last_location.x,
+        y: location.y - last_location.y,
+    };
+    host_object.translation.x += delta.x;
+    host_object.translation.y += delta.y;
+    let interval = timestamp - host_object.last_timestamp;
+    if interval > 0.0 {
+        // Smoothed, so one jittery event doesn't decide the velocity.
+        host_object.velocity = CGPoint {
+            x: (host_object.velocity.x + delta.x / interval as CGFloat) / 2.0,
+            y: (host_object.velocity.y + delta.y / interval as CGFloat) / 2.0,
+        };
+    }
+    host_object.last_location = Some(location);
+    host_object.last_timestamp = timestamp;
+    let translation = host_object.translation;
+
+    let state: UIGestureRecognizerState = msg![env; this state];
+    match state {
+        UIGestureRecognizerStatePossible => {
+            let distance = (translation.x * translation.x + translation.y * translation.y).sqrt();
+            if distance > HYSTERESIS && touch_count_allowed(env, this) {
+                set_state(env, this, UIGestureRecognizerStateBegan);
+            }
+        }
+        UIGestureRecognizerStateBegan | UIGestureRecognizerStateChanged => {
+            set_state(env, this, UIGestureRecognizerStateChanged);
+        }
+        _ => (),
+    }
+}
+
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesEnded:touches withEvent:event];
+
+    // The touch being lifted is still counted.
+    let touch: id = msg![env; touches anyObject];
+    let remaining = env.objc.borrow::<UIGestureRecognizerHostObject>(this).touches.len() - 1;
+    let minimum = env
+        .objc
+        .borrow::<UIPanGestureRecognizerHostObject>(this)
+        .minimum_number_of_touches;
+    if remaining > 0 && remaining as NSUInteger >= minimum {
+        return touches_changed(env, this, touch);
+    }
+    let state: UIGestureRecognizerState = msg![env; this state];
+    match state {
+        UIGestureRecognizerStatePossible => set_state(env, this, UIGestureRecognizerStateFailed),
+        UIGestureRecognizerStateBegan | UIGestureRecognizerStateChanged => {
+            set_state(env, this, UIGestureRecognizerStateEnded)
+        }
+        _ => (),
+    }
+}
+
+- (())touchesCancelled:(id)touches // NSSet* of UITouch*
+             withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesCancelled:touches withEvent:event];
+    let state: UIGestureRecognizerState = msg![env; this state];
+    if state == UIGestureRecognizerStatePossible {
+        set_state(env, this, UIGestureRecognizerStateFailed);
+    } else {
+        set_state(env, this, UIGestureRecognizerStateCancelled);
+    }
+}
+
+@end
+
+};
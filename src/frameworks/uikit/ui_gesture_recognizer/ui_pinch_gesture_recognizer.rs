@@ -0,0 +1,187 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIPinchGestureRecognizer`.
+
+use super::{
+    set_state, UIGestureRecognizerHostObject, UIGestureRecognizerState,
+    UIGestureRecognizerStateBegan, UIGestureRecognizerStateCancelled,
+    UIGestureRecognizerStateChanged, UIGestureRecognizerStateEnded, UIGestureRecognizerStateFailed,
+    UIGestureRecognizerStatePossible,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint};
+use crate::frameworks::foundation::NSTimeInterval;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_super, nil, objc_classes, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+/// How much the distance between the touches has to change before the
+/// gesture begins.
+const HYSTERESIS: CGFloat = 5.0;
+
+pub struct UIPinchGestureRecognizerHostObject {
+    superclass: super::UIGestureRecognizerHostObject,
+    scale: CGFloat,
+    /// Scale factor per second.
+    velocity: CGFloat,
+    /// Distance between the two touches when the second one began.
+    initial_distance: Option<CGFloat>,
+    last_distance: CGFloat,
+    last_timestamp: NSTimeInterval,
+}
+impl_HostObject_with_superclass!(UIPinchGestureRecognizerHostObject);
+impl Default for UIPinchGestureRecognizerHostObject {
+    fn default() -> Self {
+        UIPinchGestureRecognizerHostObject {
+            superclass: Default::default(),
+            scale: 1.0,
+            velocity: 0.0,
+            initial_distance: None,
+            last_distance: 0.0,
+            last_timestamp: 0.0,
+        }
+    }
+}
+
+/// Distance between the first two touches, if there are two.
+fn touch_distance(env: &mut Environment, this: id) -> Option<CGFloat> {
+    let touches = env
+        .objc
+        .borrow::<UIGestureRecognizerHostObject>(this)
+        .touches
+        .clone();
+    let [a, b, ..] = touches[..] else {
+        return None;
+    };
+    let a: CGPoint = msg![env; a locationInView:nil];
+    let b: CGPoint = msg![env; b locationInView:nil];
+    let (dx, dy) = (a.x - b.x, a.y - b.y);
+    Some((dx * dx + dy * dy).sqrt())
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIPinchGestureRecognizer: UIGestureRecognizer
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIPinchGestureRecognizerHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (CGFloat)scale {
+    env.objc.borrow::<UIPinchGestureRecognizerHostObject>(this).scale
+}
+- (())setScale:(CGFloat)scale {
+    let host_object = env.objc.borrow_mut::<UIPinchGestureRecognizerHostObject>(this);
+    host_object.scale = scale;
+    host_object.velocity = 0.0;
+}
+- (CGFloat)velocity {
+    env.objc.borrow::<UIPinchGestureRecognizerHostObject>(this).velocity
+}
+
+- (())reset {
+    let host_object = env.objc.borrow_mut::<UIPinchGestureRecognizerHostObject>(this);
+    host_object.scale = 1.0;
+    host_object.velocity = 0.0;
+    host_object.initial_distance = None;
+    msg_super![env; this reset]
+}
+
+- (())touchesBegan:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesBegan:touches withEvent:event];
+
+    if env.objc.borrow::<UIPinchGestureRecognizerHostObject>(this).initial_distance.is_some() {
+        return;
+    }
+    let Some(distance) = touch_distance(env, this) else {
+        return;
+    };
+    let touch: id = msg![env; touches anyObject];
+    let timestamp: NSTimeInterval = msg![env; touch timestamp];
+    let host_object = env.objc.borrow_mut::<UIPinchGestureRecognizerHostObject>(this);
+    host_object.initial_distance = Some(distance);
+    host_object.last_distance = distance;
+    host_object.last_timestamp = timestamp;
+}
+
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesMoved:touches withEvent:event];
+
+    let Some(distance) = touch_distance(env, this) else {
+        return;
+    };
+    let touch: id = msg![env; touches anyObject];
+    let timestamp: NSTimeInterval = msg![env; touch timestamp];
+    let state: UIGestureRecognizerState = msg![env; this state];
+    let host_object = env.objc.borrow_mut::<UIPinchGestureRecognizerHostObject>(this);
+    let Some(initial_distance) = host_object.initial_distance else {
+        return;
+    };
+    if state == UIGestureRecognizerStatePossible
+        && (distance - initial_distance).abs() <= HYSTERESIS
+    {
+        return;
+    }
+
+    if host_object.last_distance > 0.0 {
+        let old_scale = host_object.scale;
+        host_object.scale *= distance / host_object.last_distance;
+        let interval = timestamp - host_object.last_timestamp;
+        if interval > 0.0 {
+            host_object.velocity = (host_object.scale - old_scale) / interval as CGFloat;
+        }
+    }
+    host_object.last_distance = distance;
+    host_object.last_timestamp = timestamp;
+
+    set_state(env, this, match state {
+        UIGestureRecognizerStatePossible => UIGestureRecognizerStateBegan,
+        _ => UIGestureRecognizerStateChanged,
+    });
+}
+
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesEnded:touches withEvent:event];
+
+    let state: UIGestureRecognizerState = msg![env; this state];
+    match state {
+        // A pinch is over as soon as either touch is lifted.
+        UIGestureRecognizerStateBegan | UIGestureRecognizerStateChanged => {
+            set_state(env, this, UIGestureRecognizerStateEnded)
+        }
+        // Lifting a touch before the touches moved far enough means it
+        // wasn't a pinch. If there was only one touch, the second one might
+        // still be to come.
+        UIGestureRecognizerStatePossible => {
+            let host_object = env.objc.borrow::<UIPinchGestureRecognizerHostObject>(this);
+            if host_object.initial_distance.is_some() {
+                set_state(env, this, UIGestureRecognizerStateFailed);
+            }
+        }
+        _ => (),
+    }
+}
+
+- (())touchesCancelled:(id)touches // NSSet* of UITouch*
+             withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesCancelled:touches withEvent:event];
+    let state: UIGestureRecognizerState = msg![env; this state];
+    if state == UIGestureRecognizerStatePossible {
+        set_state(env, this, UIGestureRecognizerStateFailed);
+    } else {
+        set_state(env, this, UIGestureRecognizerStateCancelled);
+    }
+}
+
+@end
+
+};
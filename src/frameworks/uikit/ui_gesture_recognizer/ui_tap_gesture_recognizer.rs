@@ -0,0 +1,167 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITapGestureRecognizer`.
+
+use super::{
+    set_state, UIGestureRecognizerHostObject, UIGestureRecognizerStateFailed,
+    UIGestureRecognizerStateRecognized,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint};
+use crate::frameworks::foundation::{NSTimeInterval, NSUInteger};
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_super, nil, objc_classes, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+/// How far a touch can move before it's no longer a tap.
+const MAX_MOVEMENT: CGFloat = 10.0;
+/// How long there can be between the taps of a multi-tap.
+const MAX_TAP_INTERVAL: NSTimeInterval = 0.5;
+
+pub struct UITapGestureRecognizerHostObject {
+    superclass: super::UIGestureRecognizerHostObject,
+    number_of_taps_required: NSUInteger,
+    number_of_touches_required: NSUInteger,
+    /// Number of taps recognized so far.
+    taps: NSUInteger,
+    /// Largest number of touches that were down at once during this tap.
+    touches_in_tap: NSUInteger,
+    /// Where the first tap started, relative to the screen.
+    start_location: Option<CGPoint>,
+    /// When the last tap ended.
+    last_tap_timestamp: Option<NSTimeInterval>,
+}
+impl_HostObject_with_superclass!(UITapGestureRecognizerHostObject);
+impl Default for UITapGestureRecognizerHostObject {
+    fn default() -> Self {
+        UITapGestureRecognizerHostObject {
+            superclass: Default::default(),
+            number_of_taps_required: 1,
+            number_of_touches_required: 1,
+            taps: 0,
+            touches_in_tap: 0,
+            start_location: None,
+            last_tap_timestamp: None,
+        }
+    }
+}
+
+fn touch_count(env: &Environment, this: id) -> NSUInteger {
+    env.objc
+        .borrow::<UIGestureRecognizerHostObject>(this)
+        .touches
+        .len() as NSUInteger
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UITapGestureRecognizer: UIGestureRecognizer
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UITapGestureRecognizerHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (NSUInteger)numberOfTapsRequired {
+    env.objc.borrow::<UITapGestureRecognizerHostObject>(this).number_of_taps_required
+}
+- (())setNumberOfTapsRequired:(NSUInteger)number {
+    env.objc.borrow_mut::<UITapGestureRecognizerHostObject>(this).number_of_taps_required = number;
+}
+- (NSUInteger)numberOfTouchesRequired {
+    env.objc.borrow::<UITapGestureRecognizerHostObject>(this).number_of_touches_required
+}
+- (())setNumberOfTouchesRequired:(NSUInteger)number {
+    env.objc.borrow_mut::<UITapGestureRecognizerHostObject>(this).number_of_touches_required =
+        number;
+}
+
+- (())reset {
+    let host_object = env.objc.borrow_mut::<UITapGestureRecognizerHostObject>(this);
+    host_object.taps = 0;
+    host_object.touches_in_tap = 0;
+    host_object.start_location = None;
+    host_object.last_tap_timestamp = None;
+    msg_super![env; this reset]
+}
+
+- (())touchesBegan:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesBegan:touches withEvent:event];
+
+    let touch: id = msg![env; touches anyObject];
+    let timestamp: NSTimeInterval = msg![env; touch timestamp];
+    let count = touch_count(env, this);
+    let host_object = env.objc.borrow_mut::<UITapGestureRecognizerHostObject>(this);
+    if let Some(last) = host_object.last_tap_timestamp {
+        if timestamp - last > MAX_TAP_INTERVAL {
+            // Too slow for a multi-tap, so this is the first tap of a new one.
+            host_object.taps = 0;
+            host_object.start_location = None;
+            host_object.last_tap_timestamp = None;
+        }
+    }
+    host_object.touches_in_tap = host_object.touches_in_tap.max(count);
+    if host_object.touches_in_tap > host_object.number_of_touches_required {
+        return set_state(env, this, UIGestureRecognizerStateFailed);
+    }
+    if host_object.start_location.is_none() {
+        let location: CGPoint = msg![env; this locationInView:nil];
+        env.objc.borrow_mut::<UITapGestureRecognizerHostObject>(this).start_location =
+            Some(location);
+    }
+}
+
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesMoved:touches withEvent:event];
+
+    let touch: id = msg![env; touches anyObject];
+    let location: CGPoint = msg![env; touch locationInView:nil];
+    let Some(start) = env.objc.borrow::<UITapGestureRecognizerHostObject>(this).start_location
+    else {
+        return;
+    };
+    let (dx, dy) = (location.x - start.x, location.y - start.y);
+    if (dx * dx + dy * dy).sqrt() > MAX_MOVEMENT {
+        set_state(env, this, UIGestureRecognizerStateFailed);
+    }
+}
+
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesEnded:touches withEvent:event];
+
+    // The tap is over when its last touch is lifted. The touch being lifted
+    // is still counted.
+    if touch_count(env, this) > 1 {
+        return;
+    }
+    let touch: id = msg![env; touches anyObject];
+    let timestamp: NSTimeInterval = msg![env; touch timestamp];
+    let host_object = env.objc.borrow_mut::<UITapGestureRecognizerHostObject>(this);
+    if host_object.touches_in_tap != host_object.number_of_touches_required {
+        return set_state(env, this, UIGestureRecognizerStateFailed);
+    }
+    host_object.taps += 1;
+    host_object.touches_in_tap = 0;
+    host_object.last_tap_timestamp = Some(timestamp);
+    if host_object.taps >= host_object.number_of_taps_required {
+        set_state(env, this, UIGestureRecognizerStateRecognized);
+    }
+}
+
+- (())touchesCancelled:(id)touches // NSSet* of UITouch*
+             withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesCancelled:touches withEvent:event];
+    set_state(env, this, UIGestureRecognizerStateFailed);
+}
+
+@end
+
+};
@@ -6,7 +6,7 @@
 //! `UIImage`.
 
 use crate::frameworks::core_graphics::cg_image::{self, CGImageRef, CGImageRelease, CGImageRetain};
-use crate::frameworks::core_graphics::CGSize;
+use crate::frameworks::core_graphics::{CGFloat, CGSize};
 use crate::frameworks::foundation::{ns_data, ns_string, NSInteger};
 use crate::fs::GuestPath;
 use crate::image::Image;
@@ -14,9 +14,21 @@ use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
     NSZonePtr,
 };
+use crate::Environment;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub(super) struct State {
+    /// Images loaded by `imageNamed:`, by name. These are strong references
+    /// and are never released, like in Apple's implementation (which only
+    /// releases them when memory is low).
+    named_images: HashMap<String, id>,
+}
 
 struct UIImageHostObject {
     cg_image: CGImageRef,
+    /// Pixels per point.
+    scale: CGFloat,
 }
 impl HostObject for UIImageHostObject {}
 
@@ -27,7 +39,10 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation UIImage: NSObject
 
 + (id)allocWithZone:(NSZonePtr)_zone {
-    let host_object = Box::new(UIImageHostObject { cg_image: nil });
+    let host_object = Box::new(UIImageHostObject {
+        cg_image: nil,
+        scale: 1.0,
+    });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
@@ -38,10 +53,26 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 + (id)imageNamed:(id)name { // NSString*
-    // TODO: figure out whether this is actually correct in all cases
+    let name = ns_string::to_rust_string(env, name).into_owned();
+    if let Some(&image) = env.framework_state.uikit.ui_image.named_images.get(&name) {
+        return image;
+    }
+
     let bundle: id = msg_class![env; NSBundle mainBundle];
-    let path: id = msg![env; bundle pathForResource:name ofType:nil];
-    msg![env; this imageWithContentsOfFile:path]
+    let resource_path: id = msg![env; bundle resourcePath];
+    let resource_path = ns_string::to_rust_string(env, resource_path);
+    let Some(path) = find_named_image(env, &resource_path, &name) else {
+        log!("Warning: couldn't find image named {:?}, returning nil", name);
+        return nil;
+    };
+    let path = ns_string::from_rust_string(env, path);
+    let image: id = msg![env; this alloc];
+    let image: id = msg![env; image initWithContentsOfFile:path];
+    release(env, path);
+    if image != nil {
+        env.framework_state.uikit.ui_image.named_images.insert(name, image);
+    }
+    image
 }
 
 + (id)imageWithContentsOfFile:(id)path { // NSString*
@@ -52,16 +83,12 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 + (id)imageWithData:(id)data { // NSData*
     let new: id = msg![env; this alloc];
-    let slice = ns_data::to_rust_slice(env, data);
-    // TODO: refactor common parts
-    let image = Image::from_bytes(slice).unwrap();
-    let cg_image = cg_image::from_image(env, image);
-    env.objc.borrow_mut::<UIImageHostObject>(new).cg_image = cg_image;
+    let new: id = msg![env; new initWithData:data];
     autorelease(env, new)
 }
 
 - (())dealloc {
-    let &UIImageHostObject { cg_image } = env.objc.borrow(this);
+    let &UIImageHostObject { cg_image, .. } = env.objc.borrow(this);
     CGImageRelease(env, cg_image);
 
     env.objc.dealloc_object(this, &mut env.mem)
@@ -85,6 +112,25 @@ pub const CLASSES: ClassExports = objc_classes! {
     //       load a broken file, so panicking is most useful.
     let image = Image::from_bytes(&bytes).unwrap();
     let cg_image = cg_image::from_image(env, image);
+    let host_object = env.objc.borrow_mut::<UIImageHostObject>(this);
+    host_object.cg_image = cg_image;
+    host_object.scale = scale_for_path(&path);
+    this
+}
+
+- (id)initWithData:(id)data { // NSData*
+    let slice = ns_data::to_rust_slice(env, data);
+    // Unlike with files, apps may check for nil here to validate
+    // downloaded data etc, so this shouldn't panic.
+    let image = match Image::from_bytes(slice) {
+        Ok(image) => image,
+        Err(reason) => {
+            log!("Warning: couldn't decode image data ({}), returning nil", reason);
+            release(env, this);
+            return nil;
+        }
+    };
+    let cg_image = cg_image::from_image(env, image);
     env.objc.borrow_mut::<UIImageHostObject>(this).cg_image = cg_image;
     this
 }
@@ -102,15 +148,70 @@ pub const CLASSES: ClassExports = objc_classes! {
     0 // UIImageOrientationUp
 }
 
+- (CGFloat)scale {
+    env.objc.borrow::<UIImageHostObject>(this).scale
+}
+
 - (CGSize)size {
-    let image = env.objc.borrow::<UIImageHostObject>(this).cg_image;
-    let (width, height) = cg_image::borrow_image(&env.objc, image).dimensions();
+    let &UIImageHostObject { cg_image, scale } = env.objc.borrow(this);
+    let (width, height) = cg_image::borrow_image(&env.objc, cg_image).dimensions();
     CGSize {
-        width: width as _,
-        height: height as _,
+        width: width as CGFloat / scale,
+        height: height as CGFloat / scale,
     }
 }
 
 @end
 
 };
+
+/// Split a file name into the part before the extension, and the extension
+/// (including the dot), if any.
+fn split_extension(name: &str) -> (&str, &str) {
+    let file_name_start = name.rfind('/').map_or(0, |i| i + 1);
+    match name[file_name_start..].rfind('.') {
+        Some(i) if i > 0 => name.split_at(file_name_start + i),
+        _ => (name, ""),
+    }
+}
+
+/// Images with a name ending in `@2x` are for Retina displays and have two
+/// pixels per point.
+fn scale_for_path(path: &str) -> CGFloat {
+    let (stem, _) = split_extension(path);
+    if stem.ends_with("@2x") {
+        2.0
+    } else {
+        1.0
+    }
+}
+
+/// Find the file in the app bundle for `imageNamed:`. If the name has no
+/// extension, `.png` is assumed. The devices touchHLE emulates don't have
+/// Retina displays, so a `@2x` version is only used if there's no other.
+fn find_named_image(env: &Environment, resource_path: &str, name: &str) -> Option<String> {
+    let (stem, extension) = split_extension(name);
+    let extension = if extension.is_empty() {
+        ".png"
+    } else {
+        extension
+    };
+    [
+        format!("{}/{}{}", resource_path, stem, extension),
+        format!("{}/{}@2x{}", resource_path, stem, extension),
+    ]
+    .into_iter()
+    .find(|path| env.fs.exists(GuestPath::new(path)))
+}
+
+#[cfg(test)]
+#[test]
+fn test_split_extension() {
+    assert_eq!(split_extension("foo.png"), ("foo", ".png"));
+    assert_eq!(split_extension("foo"), ("foo", ""));
+    assert_eq!(split_extension("dir.d/foo"), ("dir.d/foo", ""));
+    assert_eq!(split_extension("dir/foo@2x.jpg"), ("dir/foo@2x", ".jpg"));
+    assert_eq!(split_extension(".hidden"), (".hidden", ""));
+    assert_eq!(scale_for_path("/a/b@2x.png"), 2.0);
+    assert_eq!(scale_for_path("/a/b.png"), 1.0);
+}
@@ -4,8 +4,75 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `UIResponder`.
+//!
+//! This also handles the keyboard, since it is shown whenever the first
+//! responder accepts text input (implements `UIKeyInput`). The host keyboard
+//! is used as-is, nothing is drawn.
 
-use crate::objc::{id, objc_classes, ClassExports};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{ns_dictionary, ns_string};
+use crate::objc::{id, msg, msg_class, nil, objc_classes, release, retain, ClassExports};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// The current first responder. This is a strong reference.
+    first_responder: Option<id>,
+    /// Whether the keyboard is currently "shown", i.e. whether host text input
+    /// is active.
+    keyboard_shown: bool,
+}
+
+// These are declared in UIWindow.h.
+pub const UIKeyboardWillShowNotification: &str = "UIKeyboardWillShowNotification";
+pub const UIKeyboardDidShowNotification: &str = "UIKeyboardDidShowNotification";
+pub const UIKeyboardWillHideNotification: &str = "UIKeyboardWillHideNotification";
+pub const UIKeyboardDidHideNotification: &str = "UIKeyboardDidHideNotification";
+pub const UIKeyboardBoundsUserInfoKey: &str = "UIKeyboardBoundsUserInfoKey";
+pub const UIKeyboardCenterBeginUserInfoKey: &str = "UIKeyboardCenterBeginUserInfoKey";
+pub const UIKeyboardCenterEndUserInfoKey: &str = "UIKeyboardCenterEndUserInfoKey";
+pub const UIKeyboardFrameBeginUserInfoKey: &str = "UIKeyboardFrameBeginUserInfoKey";
+pub const UIKeyboardFrameEndUserInfoKey: &str = "UIKeyboardFrameEndUserInfoKey";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIKeyboardWillShowNotification",
+        HostConstant::NSString(UIKeyboardWillShowNotification),
+    ),
+    (
+        "_UIKeyboardDidShowNotification",
+        HostConstant::NSString(UIKeyboardDidShowNotification),
+    ),
+    (
+        "_UIKeyboardWillHideNotification",
+        HostConstant::NSString(UIKeyboardWillHideNotification),
+    ),
+    (
+        "_UIKeyboardDidHideNotification",
+        HostConstant::NSString(UIKeyboardDidHideNotification),
+    ),
+    (
+        "_UIKeyboardBoundsUserInfoKey",
+        HostConstant::NSString(UIKeyboardBoundsUserInfoKey),
+    ),
+    (
+        "_UIKeyboardCenterBeginUserInfoKey",
+        HostConstant::NSString(UIKeyboardCenterBeginUserInfoKey),
+    ),
+    (
+        "_UIKeyboardCenterEndUserInfoKey",
+        HostConstant::NSString(UIKeyboardCenterEndUserInfoKey),
+    ),
+    (
+        "_UIKeyboardFrameBeginUserInfoKey",
+        HostConstant::NSString(UIKeyboardFrameBeginUserInfoKey),
+    ),
+    (
+        "_UIKeyboardFrameEndUserInfoKey",
+        HostConstant::NSString(UIKeyboardFrameEndUserInfoKey),
+    ),
+];
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -13,14 +80,21 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation UIResponder: NSObject
 
-// TODO: real responder implementation etc
+- (id)nextResponder {
+    // Subclasses override this to make up the responder chain.
+    nil
+}
 
-// These methods print debug logs because they are only likely to get called if
-// a subclass didn't override them, which might mean we delivered the event to
-// the wrong object or it is unhandled.
+// The default implementations pass touches up the responder chain. They print
+// debug logs when they reach the end of it, because that means the touch was
+// unhandled, or that we delivered it to the wrong object.
 
 - (())touchesBegan:(id)touches // NSSet* of UITouch*
          withEvent:(id)event { // UIEvent*
+    let next: id = msg![env; this nextResponder];
+    if next != nil {
+        return msg![env; next touchesBegan:touches withEvent:event];
+    }
     log_dbg!(
         "[{:?} touchesBegan:{:?} withEvent:{:?}] (probably unhandled)",
         this,
@@ -31,6 +105,10 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (())touchesMoved:(id)touches // NSSet* of UITouch*
          withEvent:(id)event { // UIEvent*
+    let next: id = msg![env; this nextResponder];
+    if next != nil {
+        return msg![env; next touchesMoved:touches withEvent:event];
+    }
     log_dbg!(
         "[{:?} touchesMoved:{:?} withEvent:{:?}] (probably unhandled)",
         this,
@@ -41,6 +119,10 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (())touchesEnded:(id)touches // NSSet* of UITouch*
          withEvent:(id)event { // UIEvent*
+    let next: id = msg![env; this nextResponder];
+    if next != nil {
+        return msg![env; next touchesEnded:touches withEvent:event];
+    }
     log_dbg!(
         "[{:?} touchesEnded:{:?} withEvent:{:?}] (probably unhandled)",
         this,
@@ -49,11 +131,180 @@ pub const CLASSES: ClassExports = objc_classes! {
     );
 }
 
+- (())touchesCancelled:(id)touches // NSSet* of UITouch*
+             withEvent:(id)event { // UIEvent*
+    let next: id = msg![env; this nextResponder];
+    if next != nil {
+        return msg![env; next touchesCancelled:touches withEvent:event];
+    }
+    log_dbg!(
+        "[{:?} touchesCancelled:{:?} withEvent:{:?}] (probably unhandled)",
+        this,
+        touches,
+        event,
+    );
+}
+
+- (bool)canBecomeFirstResponder {
+    // default implementation, subclasses can override this
+    false
+}
+- (bool)canResignFirstResponder {
+    // default implementation, subclasses can override this
+    true
+}
+- (bool)isFirstResponder {
+    env.framework_state.uikit.ui_responder.first_responder == Some(this)
+}
+
+- (bool)becomeFirstResponder {
+    let current = env.framework_state.uikit.ui_responder.first_responder;
+    if current == Some(this) {
+        return true;
+    }
+    if !msg![env; this canBecomeFirstResponder] {
+        return false;
+    }
+    if let Some(current) = current {
+        if !msg![env; current resignFirstResponder] {
+            return false;
+        }
+    }
+    log_dbg!("New first responder: {:?}", this);
+    retain(env, this);
+    env.framework_state.uikit.ui_responder.first_responder = Some(this);
+    update_keyboard(env);
+    true
+}
 - (bool)resignFirstResponder {
-    // TODO
+    if env.framework_state.uikit.ui_responder.first_responder != Some(this) {
+        return true;
+    }
+    if !msg![env; this canResignFirstResponder] {
+        return false;
+    }
+    log_dbg!("{:?} is no longer the first responder", this);
+    env.framework_state.uikit.ui_responder.first_responder = None;
+    update_keyboard(env);
+    release(env, this);
     true
 }
 
 @end
 
 };
+
+/// Show or hide the keyboard, depending on whether the first responder accepts
+/// text input, and send the appropriate notifications.
+fn update_keyboard(env: &mut Environment) {
+    let state = &env.framework_state.uikit.ui_responder;
+    let want_keyboard = state.first_responder.map_or(false, |responder| {
+        env.objc
+            .object_has_method_named(&env.mem, responder, "insertText:")
+    });
+    if want_keyboard == state.keyboard_shown {
+        return;
+    }
+    env.framework_state.uikit.ui_responder.keyboard_shown = want_keyboard;
+    if let Some(window) = env.window.as_mut() {
+        window.set_text_input(want_keyboard);
+    }
+
+    // There is no on-screen keyboard, but apps may still want to move their
+    // views out of the way of where it would be.
+    // TODO: handle landscape orientations
+    let shown_frame = CGRect {
+        origin: CGPoint { x: 0.0, y: 264.0 },
+        size: CGSize {
+            width: 320.0,
+            height: 216.0,
+        },
+    };
+    let hidden_frame = CGRect {
+        origin: CGPoint {
+            x: 0.0,
+            y: shown_frame.origin.y + shown_frame.size.height,
+        },
+        ..shown_frame
+    };
+    let (begin_frame, end_frame, will_name, did_name) = if want_keyboard {
+        (
+            hidden_frame,
+            shown_frame,
+            UIKeyboardWillShowNotification,
+            UIKeyboardDidShowNotification,
+        )
+    } else {
+        (
+            shown_frame,
+            hidden_frame,
+            UIKeyboardWillHideNotification,
+            UIKeyboardDidHideNotification,
+        )
+    };
+    let center = |frame: CGRect| CGPoint {
+        x: frame.origin.x + frame.size.width / 2.0,
+        y: frame.origin.y + frame.size.height / 2.0,
+    };
+    let bounds = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: shown_frame.size,
+    };
+
+    let bounds: id = msg_class![env; NSValue valueWithCGRect:bounds];
+    let center_begin: id = msg_class![env; NSValue valueWithCGPoint:(center(begin_frame))];
+    let center_end: id = msg_class![env; NSValue valueWithCGPoint:(center(end_frame))];
+    let begin_frame: id = msg_class![env; NSValue valueWithCGRect:begin_frame];
+    let end_frame: id = msg_class![env; NSValue valueWithCGRect:end_frame];
+    let keys_and_objects = [
+        (UIKeyboardBoundsUserInfoKey, bounds),
+        (UIKeyboardCenterBeginUserInfoKey, center_begin),
+        (UIKeyboardCenterEndUserInfoKey, center_end),
+        (UIKeyboardFrameBeginUserInfoKey, begin_frame),
+        (UIKeyboardFrameEndUserInfoKey, end_frame),
+    ]
+    .map(|(key, object)| (ns_string::get_static_str(env, key), object));
+    let user_info = ns_dictionary::dict_from_keys_and_objects(env, &keys_and_objects);
+
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    for name in [will_name, did_name] {
+        let name = ns_string::get_static_str(env, name);
+        let _: () = msg![env; center postNotificationName:name
+                                                   object:nil
+                                                 userInfo:user_info];
+    }
+    release(env, user_info);
+}
+
+/// For use by [super::handle_events]: deliver text input to the first
+/// responder, using the `UIKeyInput` protocol.
+pub fn handle_text_input(env: &mut Environment, event: crate::window::Event) {
+    use crate::window::Event;
+
+    let Some(responder) = env.framework_state.uikit.ui_responder.first_responder else {
+        log_dbg!("Ignoring text input event {:?}, no first responder", event);
+        return;
+    };
+    if !env
+        .objc
+        .object_has_method_named(&env.mem, responder, "insertText:")
+    {
+        log_dbg!(
+            "Ignoring text input event {:?}, first responder {:?} doesn't accept text",
+            event,
+            responder
+        );
+        return;
+    }
+    match event {
+        Event::TextInput(text) => {
+            let text = ns_string::from_rust_string(env, text);
+            let _: () = msg![env; responder insertText:text];
+            release(env, text);
+        }
+        Event::DeleteBackward => {
+            let _: () = msg![env; responder deleteBackward];
+        }
+        _ => unreachable!(),
+    }
+}
@@ -5,6 +5,10 @@
  */
 //! `UIScreen`.
 
+use super::ui_application::{
+    status_bar_orientation, UIInterfaceOrientationLandscapeLeft,
+    UIInterfaceOrientationLandscapeRight, UIInterfaceOrientationPortraitUpsideDown,
+};
 use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
 use crate::objc::{id, msg, objc_classes, ClassExports, TrivialHostObject};
 
@@ -41,7 +45,8 @@ pub const CLASSES: ClassExports = objc_classes! {
 // TODO: more accessors
 
 - (CGRect)bounds {
-    // TODO: once rotation is supported, this must change with the rotation!
+    // iPhone OS always reports the portrait size, even when the interface is
+    // rotated. Only the application frame takes the rotation into account.
     CGRect {
         origin: CGPoint { x: 0.0, y: 0.0 },
         size: CGSize { width: 320.0, height: 480.0 },
@@ -52,8 +57,23 @@ pub const CLASSES: ClassExports = objc_classes! {
     let mut bounds: CGRect = msg![env; this bounds];
     const STATUS_BAR_HEIGHT: f32 = 20.0;
     if !env.framework_state.uikit.ui_application.status_bar_hidden {
-        bounds.origin.y += STATUS_BAR_HEIGHT;
-        bounds.size.height -= STATUS_BAR_HEIGHT;
+        // The status bar is along the top edge of the rotated interface.
+        match status_bar_orientation(env) {
+            UIInterfaceOrientationPortraitUpsideDown => {
+                bounds.size.height -= STATUS_BAR_HEIGHT;
+            }
+            UIInterfaceOrientationLandscapeLeft => {
+                bounds.origin.x += STATUS_BAR_HEIGHT;
+                bounds.size.width -= STATUS_BAR_HEIGHT;
+            }
+            UIInterfaceOrientationLandscapeRight => {
+                bounds.size.width -= STATUS_BAR_HEIGHT;
+            }
+            _ => {
+                bounds.origin.y += STATUS_BAR_HEIGHT;
+                bounds.size.height -= STATUS_BAR_HEIGHT;
+            }
+        }
     }
     bounds
 }
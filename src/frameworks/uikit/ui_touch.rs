@@ -7,23 +7,30 @@
 
 use super::ui_event;
 use super::ui_event::UIEventHostObject;
+use super::ui_gesture_recognizer;
+use super::ui_view::ui_window;
+use super::ui_view_controller::status_bar_layout_offset;
 use crate::frameworks::core_graphics::{CGPoint, CGRect};
-use crate::frameworks::foundation::{NSInteger, NSTimeInterval, NSUInteger};
+use crate::frameworks::foundation::{ns_set, NSInteger, NSTimeInterval, NSUInteger};
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
     NSZonePtr,
 };
-use crate::window::Event;
+use crate::window::{Event, FingerId};
 use crate::Environment;
+use std::collections::HashMap;
 
 pub type UITouchPhase = NSInteger;
 pub const UITouchPhaseBegan: UITouchPhase = 0;
 pub const UITouchPhaseMoved: UITouchPhase = 1;
 pub const UITouchPhaseEnded: UITouchPhase = 3;
+pub const UITouchPhaseCancelled: UITouchPhase = 4;
 
 #[derive(Default)]
 pub struct State {
-    current_touch: Option<id>,
+    /// Touches that have begun but not yet ended, by the finger that started
+    /// them. Each is a strong reference.
+    current_touches: HashMap<FingerId, id>,
 }
 
 pub(super) struct UITouchHostObject {
@@ -40,6 +47,12 @@ pub(super) struct UITouchHostObject {
     pub(super) original_location: CGPoint,
     timestamp: NSTimeInterval,
     phase: UITouchPhase,
+    /// `UIGestureRecognizer*`s that get this touch before its view does.
+    /// These are strong references.
+    gesture_recognizers: Vec<id>,
+    /// Set once a gesture recognizer has cancelled this touch's delivery to
+    /// its view.
+    cancelled_in_view: bool,
 }
 impl HostObject for UITouchHostObject {}
 
@@ -58,14 +71,25 @@ pub const CLASSES: ClassExports = objc_classes! {
         original_location: CGPoint { x: 0.0, y: 0.0 },
         timestamp: 0.0,
         phase: UITouchPhaseBegan,
+        gesture_recognizers: Vec::new(),
+        cancelled_in_view: false,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
 - (())dealloc {
-    let &mut UITouchHostObject { view, window, .. } = env.objc.borrow_mut(this);
+    let UITouchHostObject {
+        view,
+        window,
+        ref mut gesture_recognizers,
+        ..
+    } = *env.objc.borrow_mut(this);
+    let gesture_recognizers = std::mem::take(gesture_recognizers);
     release(env, view);
     release(env, window);
+    for recognizer in gesture_recognizers {
+        release(env, recognizer);
+    }
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -106,25 +130,104 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// Create the `NSSet` returned by `[UIEvent allTouches]`: every current touch,
+/// plus `touch`, which may just have ended. The result is autoreleased.
+fn all_touches_set(env: &mut Environment, touch: id) -> id {
+    let mut touches: Vec<id> = env
+        .framework_state
+        .uikit
+        .ui_touch
+        .current_touches
+        .values()
+        .copied()
+        .collect();
+    if !touches.contains(&touch) {
+        touches.push(touch);
+    }
+    let set = ns_set::from_vec(env, touches);
+    autorelease(env, set)
+}
+
+/// Deliver a touch to the gesture recognizers that are tracking it, and
+/// cancel the delivery of touches to their views if one of the recognizers
+/// recognized its gesture. Returns [false] if the touch should not be
+/// delivered to its view.
+fn deliver_to_gesture_recognizers(env: &mut Environment, touch: id, event: id) -> bool {
+    let &UITouchHostObject {
+        ref gesture_recognizers,
+        phase,
+        ..
+    } = env.objc.borrow(touch);
+    for recognizer in gesture_recognizers.clone() {
+        let cancelled_touches =
+            ui_gesture_recognizer::handle_touch(env, recognizer, touch, phase, event);
+        for cancelled_touch in cancelled_touches {
+            let host_object = env.objc.borrow_mut::<UITouchHostObject>(cancelled_touch);
+            if std::mem::replace(&mut host_object.cancelled_in_view, true) {
+                continue;
+            }
+            // A touch that has just begun hasn't been delivered yet.
+            if cancelled_touch == touch && phase == UITouchPhaseBegan {
+                continue;
+            }
+            let view = host_object.view;
+            let touches: id = msg_class![env; NSSet setWithObject:cancelled_touch];
+            log_dbg!(
+                "Sending [{:?} touchesCancelled:{:?} withEvent:{:?}]",
+                view,
+                touches,
+                event
+            );
+            let _: () = msg![env; view touchesCancelled:touches withEvent:event];
+        }
+    }
+    !env.objc
+        .borrow::<UITouchHostObject>(touch)
+        .cancelled_in_view
+}
+
+/// Convert the screen co-ordinates of a touch to a window's co-ordinate space.
+/// This is only different if the window's contents have been moved for the
+/// status bar (see [status_bar_layout_offset]).
+fn location_in_window(env: &mut Environment, window: id, coords: (f32, f32)) -> CGPoint {
+    let offset = status_bar_layout_offset(env, window);
+    CGPoint {
+        x: coords.0 - offset.x,
+        y: coords.1 - offset.y,
+    }
+}
+
 /// [super::handle_events] will forward touch events to this function.
 pub fn handle_event(env: &mut Environment, event: Event) {
     match event {
-        Event::TouchDown(coords) => {
-            if env.framework_state.uikit.ui_touch.current_touch.is_some() {
-                log!("Warning: New touch initiated but current touch did not end yet, treating as movement.");
-                return handle_event(env, Event::TouchMove(coords));
+        Event::TouchDown(finger, coords) => {
+            if env
+                .framework_state
+                .uikit
+                .ui_touch
+                .current_touches
+                .contains_key(&finger)
+            {
+                log!("Warning: New touch initiated for {:?} but its current touch did not end yet, treating as movement.", finger);
+                return handle_event(env, Event::TouchMove(finger, coords));
             }
 
-            log_dbg!("Touch down: {:?}", coords);
-
-            let location = CGPoint {
-                x: coords.0,
-                y: coords.1,
-            };
+            log_dbg!("Touch down ({:?}): {:?}", finger, coords);
 
             // UIKit creates and drains autorelease pools when handling events.
             let pool: id = msg_class![env; NSAutoreleasePool new];
 
+            // FIXME: handle non-fullscreen windows in hit testing and
+            //        co-ordinate space translation.
+
+            let Some(top_window) = ui_window::top_visible_window(env) else {
+                log!("No visible window, touch event ignored");
+                release(env, pool);
+                return;
+            };
+
+            let location = location_in_window(env, top_window, coords);
+
             // Note: if the emulator is heavily lagging, this timestamp is going
             // to be far off from the truth, since it should represent the
             // time when the event actually happened, not the time when the
@@ -143,23 +246,16 @@ pub fn handle_event(env: &mut Environment, event: Event) {
                 original_location: location,
                 timestamp,
                 phase: UITouchPhaseBegan,
+                gesture_recognizers: Vec::new(),
+                cancelled_in_view: false,
             };
             autorelease(env, new_touch);
 
             let touches: id = msg_class![env; NSSet setWithObject:new_touch];
-            let event = ui_event::new_event(env, touches, nil);
+            let all_touches = all_touches_set(env, new_touch);
+            let event = ui_event::new_event(env, all_touches, nil);
             autorelease(env, event);
 
-            // FIXME: handle non-fullscreen windows in hit testing and
-            //        co-ordinate space translation.
-
-            // Assumes the last window in the list is the one on top.
-            // TODO: this is not correct once we support zPosition.
-            let Some(&top_window) = env.framework_state.uikit.ui_view.ui_window.visible_windows.last() else {
-                log!("No visible window, touch event ignored");
-                return;
-            };
-
             let view: id = msg![env; top_window hitTest:location withEvent:event];
             if view == nil {
                 log!(
@@ -167,6 +263,7 @@ pub fn handle_event(env: &mut Environment, event: Event) {
                     location,
                     top_window,
                 );
+                release(env, pool);
                 return;
             } else {
                 log_dbg!(
@@ -183,17 +280,29 @@ pub fn handle_event(env: &mut Environment, event: Event) {
 
             retain(env, view);
             retain(env, top_window);
+            let gesture_recognizers =
+                ui_gesture_recognizer::recognizers_for_touch(env, view, new_touch);
             {
                 let new_touch = env.objc.borrow_mut::<UITouchHostObject>(new_touch);
                 new_touch.view = view;
                 new_touch.window = top_window;
+                new_touch.gesture_recognizers = gesture_recognizers;
             }
 
             retain(env, view);
             env.objc.borrow_mut::<UIEventHostObject>(event).view = view;
 
-            env.framework_state.uikit.ui_touch.current_touch = Some(new_touch);
             retain(env, new_touch);
+            env.framework_state
+                .uikit
+                .ui_touch
+                .current_touches
+                .insert(finger, new_touch);
+
+            if !deliver_to_gesture_recognizers(env, new_touch, event) {
+                release(env, pool);
+                return;
+            }
 
             log_dbg!(
                 "Sending [{:?} touchesBegan:{:?} withEvent:{:?}]",
@@ -205,82 +314,84 @@ pub fn handle_event(env: &mut Environment, event: Event) {
 
             release(env, pool);
         }
-        Event::TouchMove(coords) => {
-            let Some(touch) = env.framework_state.uikit.ui_touch.current_touch else {
-                log!("Warning: Touch move event received but no current touch, ignoring.");
+        Event::TouchMove(finger, coords) | Event::TouchUp(finger, coords) => {
+            let ended = matches!(event, Event::TouchUp(..));
+
+            let Some(&touch) = env
+                .framework_state
+                .uikit
+                .ui_touch
+                .current_touches
+                .get(&finger)
+            else {
+                log!(
+                    "Warning: Touch {} event received for {:?} but it has no current touch, ignoring.",
+                    if ended { "up" } else { "move" },
+                    finger
+                );
                 return;
             };
 
-            log_dbg!("Touch move: {:?}", coords);
-
-            let location = CGPoint {
-                x: coords.0,
-                y: coords.1,
-            };
-
-            let timestamp: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
-
-            let view = env.objc.borrow::<UITouchHostObject>(touch).view;
-            let host_object = env.objc.borrow_mut::<UITouchHostObject>(touch);
-            host_object.previous_location = host_object.location;
-            host_object.location = location;
-            host_object.timestamp = timestamp;
-            host_object.phase = UITouchPhaseMoved;
-
-            let pool: id = msg_class![env; NSAutoreleasePool new];
-
-            let touches: id = msg_class![env; NSSet setWithObject:touch];
-            let event = ui_event::new_event(env, touches, view);
-            autorelease(env, event);
-
             log_dbg!(
-                "Sending [{:?} touchesMoved:{:?} withEvent:{:?}]",
-                view,
-                touches,
-                event
+                "Touch {} ({:?}): {:?}",
+                if ended { "up" } else { "move" },
+                finger,
+                coords
             );
-            let _: () = msg![env; view touchesMoved:touches withEvent:event];
-
-            release(env, pool);
-        }
-        Event::TouchUp(coords) => {
-            let Some(touch) = env.framework_state.uikit.ui_touch.current_touch else {
-                log!("Warning: Touch up event received but no current touch, ignoring.");
-                return;
-            };
 
-            log_dbg!("Touch up: {:?}", coords);
-
-            let location = CGPoint {
-                x: coords.0,
-                y: coords.1,
-            };
+            let &UITouchHostObject { view, window, .. } = env.objc.borrow(touch);
+            let location = location_in_window(env, window, coords);
 
             let timestamp: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
 
-            let view = env.objc.borrow::<UITouchHostObject>(touch).view;
             let host_object = env.objc.borrow_mut::<UITouchHostObject>(touch);
             host_object.previous_location = host_object.location;
             host_object.location = location;
             host_object.timestamp = timestamp;
-            host_object.phase = UITouchPhaseEnded;
+            host_object.phase = if ended {
+                UITouchPhaseEnded
+            } else {
+                UITouchPhaseMoved
+            };
 
             let pool: id = msg_class![env; NSAutoreleasePool new];
 
             let touches: id = msg_class![env; NSSet setWithObject:touch];
-            let event = ui_event::new_event(env, touches, view);
+            let all_touches = all_touches_set(env, touch);
+            let event = ui_event::new_event(env, all_touches, view);
             autorelease(env, event);
 
-            env.framework_state.uikit.ui_touch.current_touch = None;
-            release(env, touch); // only owner now should be the NSSet
+            if ended {
+                env.framework_state
+                    .uikit
+                    .ui_touch
+                    .current_touches
+                    .remove(&finger);
+                release(env, touch); // only owner now should be the NSSet
+            }
 
-            log_dbg!(
-                "Sending [{:?} touchesEnded:{:?} withEvent:{:?}]",
-                view,
-                touches,
-                event
-            );
-            let _: () = msg![env; view touchesEnded:touches withEvent:event];
+            if !deliver_to_gesture_recognizers(env, touch, event) {
+                release(env, pool);
+                return;
+            }
+
+            if ended {
+                log_dbg!(
+                    "Sending [{:?} touchesEnded:{:?} withEvent:{:?}]",
+                    view,
+                    touches,
+                    event
+                );
+                let _: () = msg![env; view touchesEnded:touches withEvent:event];
+            } else {
+                log_dbg!(
+                    "Sending [{:?} touchesMoved:{:?} withEvent:{:?}]",
+                    view,
+                    touches,
+                    event
+                );
+                let _: () = msg![env; view touchesMoved:touches withEvent:event];
+            }
 
             release(env, pool);
         }
@@ -12,15 +12,36 @@ pub mod ui_alert_view;
 pub mod ui_control;
 pub mod ui_image_view;
 pub mod ui_label;
+pub mod ui_scroll_view;
+pub mod ui_text_view;
 pub mod ui_window;
 
+use super::ui_gesture_recognizer;
 use super::ui_graphics::{UIGraphicsPopContext, UIGraphicsPushContext};
+use super::ui_view_controller::controller_for_view;
+use crate::abi::CallFromHost;
+use crate::frameworks::core_animation::ca_animation::{
+    kCAMediaTimingFunctionEaseIn, kCAMediaTimingFunctionEaseInEaseOut,
+    kCAMediaTimingFunctionEaseOut, kCAMediaTimingFunctionLinear,
+};
+use crate::frameworks::core_animation::ca_layer::{
+    kCAGravityBottom, kCAGravityBottomLeft, kCAGravityBottomRight, kCAGravityCenter,
+    kCAGravityLeft, kCAGravityResize, kCAGravityResizeAspect, kCAGravityResizeAspectFill,
+    kCAGravityRight, kCAGravityTop, kCAGravityTopLeft, kCAGravityTopRight,
+};
+use crate::frameworks::core_animation::ca_transaction;
+use crate::frameworks::core_graphics::cg_affine_transform::CGAffineTransform;
+use crate::frameworks::core_graphics::cg_color::CGColorRef;
 use crate::frameworks::core_graphics::cg_context::{CGContextClearRect, CGContextRef};
 use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::ns_array;
 use crate::frameworks::foundation::ns_string::get_static_str;
-use crate::frameworks::foundation::NSUInteger;
+use crate::frameworks::foundation::{NSInteger, NSTimeInterval, NSUInteger};
+use crate::libc::blocks;
+use crate::mem::MutVoidPtr;
 use crate::objc::{
-    id, msg, nil, objc_classes, release, retain, Class, ClassExports, HostObject, NSZonePtr,
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, Class,
+    ClassExports, HostObject, NSZonePtr, SEL,
 };
 use crate::Environment;
 
@@ -28,18 +49,42 @@ use crate::Environment;
 pub struct State {
     /// List of views for internal purposes. Non-retaining!
     pub(super) views: Vec<id>,
+    pub ui_scroll_view: ui_scroll_view::State,
     pub ui_window: ui_window::State,
+    /// Animation blocks begun with `beginAnimations:context:`, innermost last.
+    animation_blocks: Vec<AnimationBlock>,
 }
 
+/// Each animation block is also a `CATransaction`, which provides the
+/// animation settings.
+struct AnimationBlock {
+    /// `NSString*`, possibly nil. This is a weak reference.
+    animation_id: id,
+    context: MutVoidPtr,
+    /// Weak reference.
+    delegate: id,
+    did_stop_selector: Option<SEL>,
+}
+
+type UIViewAnimationCurve = NSInteger;
+const UIViewAnimationCurveEaseInOut: UIViewAnimationCurve = 0;
+const UIViewAnimationCurveEaseIn: UIViewAnimationCurve = 1;
+const UIViewAnimationCurveEaseOut: UIViewAnimationCurve = 2;
+const UIViewAnimationCurveLinear: UIViewAnimationCurve = 3;
+
 pub(super) struct UIViewHostObject {
     /// CALayer or subclass.
     layer: id,
     /// Subviews in back-to-front order. These are strong references.
-    subviews: Vec<id>,
+    pub(super) subviews: Vec<id>,
     /// The superview. This is a weak reference.
     superview: id,
     clears_context_before_drawing: bool,
     user_interaction_enabled: bool,
+    content_mode: UIViewContentMode,
+    /// `UIGestureRecognizer*`s attached to the view. These are strong
+    /// references.
+    pub(super) gesture_recognizers: Vec<id>,
 }
 impl HostObject for UIViewHostObject {}
 impl Default for UIViewHostObject {
@@ -52,6 +97,51 @@ impl Default for UIViewHostObject {
             superview: nil,
             clears_context_before_drawing: true,
             user_interaction_enabled: true,
+            content_mode: UIViewContentModeScaleToFill,
+            gesture_recognizers: Vec::new(),
+        }
+    }
+}
+
+pub type UIViewContentMode = NSInteger;
+pub const UIViewContentModeScaleToFill: UIViewContentMode = 0;
+pub const UIViewContentModeScaleAspectFit: UIViewContentMode = 1;
+pub const UIViewContentModeScaleAspectFill: UIViewContentMode = 2;
+pub const UIViewContentModeRedraw: UIViewContentMode = 3;
+pub const UIViewContentModeCenter: UIViewContentMode = 4;
+pub const UIViewContentModeTop: UIViewContentMode = 5;
+pub const UIViewContentModeBottom: UIViewContentMode = 6;
+pub const UIViewContentModeLeft: UIViewContentMode = 7;
+pub const UIViewContentModeRight: UIViewContentMode = 8;
+pub const UIViewContentModeTopLeft: UIViewContentMode = 9;
+pub const UIViewContentModeTopRight: UIViewContentMode = 10;
+pub const UIViewContentModeBottomLeft: UIViewContentMode = 11;
+pub const UIViewContentModeBottomRight: UIViewContentMode = 12;
+
+/// Get the `contentsGravity` of the layer that corresponds to a content mode.
+fn contents_gravity_for_content_mode(content_mode: UIViewContentMode) -> &'static str {
+    // Core Animation's gravity is defined as if y pointed up, so top and
+    // bottom are swapped.
+    match content_mode {
+        // TODO: redraw when the bounds change for UIViewContentModeRedraw
+        UIViewContentModeScaleToFill | UIViewContentModeRedraw => kCAGravityResize,
+        UIViewContentModeScaleAspectFit => kCAGravityResizeAspect,
+        UIViewContentModeScaleAspectFill => kCAGravityResizeAspectFill,
+        UIViewContentModeCenter => kCAGravityCenter,
+        UIViewContentModeTop => kCAGravityBottom,
+        UIViewContentModeBottom => kCAGravityTop,
+        UIViewContentModeLeft => kCAGravityLeft,
+        UIViewContentModeRight => kCAGravityRight,
+        UIViewContentModeTopLeft => kCAGravityBottomLeft,
+        UIViewContentModeTopRight => kCAGravityBottomRight,
+        UIViewContentModeBottomLeft => kCAGravityTopLeft,
+        UIViewContentModeBottomRight => kCAGravityTopRight,
+        _ => {
+            log!(
+                "Warning: unknown content mode {}, using scale to fill",
+                content_mode
+            );
+            kCAGravityResize
         }
     }
 }
@@ -92,6 +182,98 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.get_known_class("CALayer", &mut env.mem)
 }
 
++ (())beginAnimations:(id)animation_id // NSString*
+               context:(MutVoidPtr)context {
+    () = msg_class![env; CATransaction begin];
+    // UIView's defaults differ from CATransaction's.
+    let duration: NSTimeInterval = 0.2;
+    () = msg![env; this setAnimationDuration:duration];
+    env.framework_state.uikit.ui_view.animation_blocks.push(AnimationBlock {
+        animation_id,
+        context,
+        delegate: nil,
+        did_stop_selector: None,
+    });
+    () = msg![env; this setAnimationCurve:UIViewAnimationCurveEaseInOut];
+}
++ (())commitAnimations {
+    let Some(AnimationBlock {
+        animation_id,
+        context,
+        delegate,
+        did_stop_selector,
+    }) = env.framework_state.uikit.ui_view.animation_blocks.pop() else {
+        log!("Warning: [UIView commitAnimations] without matching begin, ignoring");
+        return;
+    };
+    if let Some(selector) = did_stop_selector.filter(|_| delegate != nil) {
+        ca_transaction::set_completion_handler(env, Box::new(move |env, finished| {
+            // The signature should be:
+            // - (void)animationDidStop:(NSString*)animationID
+            //                 finished:(NSNumber*)finished
+            //                  context:(void*)context;
+            let finished: id = msg_class![env; NSNumber numberWithBool:finished];
+            let _: () = msg_send(env, (delegate, selector, animation_id, finished, context));
+        }));
+    }
+    () = msg_class![env; CATransaction commit];
+}
+
++ (())setAnimationDuration:(NSTimeInterval)duration {
+    if env.framework_state.uikit.ui_view.animation_blocks.is_empty() {
+        return;
+    }
+    () = msg_class![env; CATransaction setAnimationDuration:duration];
+}
++ (())setAnimationCurve:(UIViewAnimationCurve)curve {
+    if env.framework_state.uikit.ui_view.animation_blocks.is_empty() {
+        return;
+    }
+    let name = match curve {
+        UIViewAnimationCurveEaseIn => kCAMediaTimingFunctionEaseIn,
+        UIViewAnimationCurveEaseOut => kCAMediaTimingFunctionEaseOut,
+        UIViewAnimationCurveLinear => kCAMediaTimingFunctionLinear,
+        _ => kCAMediaTimingFunctionEaseInEaseOut,
+    };
+    let name = get_static_str(env, name);
+    let function: id = msg_class![env; CAMediaTimingFunction functionWithName:name];
+    () = msg_class![env; CATransaction setAnimationTimingFunction:function];
+}
++ (())setAnimationDelegate:(id)delegate {
+    if let Some(block) = env.framework_state.uikit.ui_view.animation_blocks.last_mut() {
+        block.delegate = delegate;
+    }
+}
++ (())setAnimationDidStopSelector:(SEL)selector {
+    if let Some(block) = env.framework_state.uikit.ui_view.animation_blocks.last_mut() {
+        block.did_stop_selector = Some(selector);
+    }
+}
+
++ (())animateWithDuration:(NSTimeInterval)duration
+                animations:(id)animations {
+    () = msg![env; this animateWithDuration:duration
+                                 animations:animations
+                                 completion:nil];
+}
++ (())animateWithDuration:(NSTimeInterval)duration
+                animations:(id)animations
+                completion:(id)completion {
+    () = msg![env; this beginAnimations:nil context:(MutVoidPtr::null())];
+    () = msg![env; this setAnimationDuration:duration];
+    let invoke = blocks::get_invoke(env, animations);
+    let _: () = invoke.call_from_host(env, (animations,));
+    if completion != nil {
+        let completion = blocks::copy_block(env, completion);
+        ca_transaction::set_completion_handler(env, Box::new(move |env, finished| {
+            let invoke = blocks::get_invoke(env, completion);
+            let _: () = invoke.call_from_host(env, (completion, finished));
+            blocks::release_block(env, completion);
+        }));
+    }
+    () = msg![env; this commitAnimations];
+}
+
 // TODO: accessors etc
 
 // initWithCoder: and initWithFrame: are basically UIView's designated
@@ -136,6 +318,15 @@ pub const CLASSES: ClassExports = objc_classes! {
     let key_ns_string = get_static_str(env, "UIOpaque");
     let opaque: bool = msg![env; coder decodeBoolForKey:key_ns_string];
 
+    let key_ns_string = get_static_str(env, "UIUserInteractionDisabled");
+    let interaction_disabled: bool = msg![env; coder decodeBoolForKey:key_ns_string];
+
+    let key_ns_string = get_static_str(env, "UIContentMode");
+    if msg![env; coder containsValueForKey:key_ns_string] {
+        let content_mode: UIViewContentMode = msg![env; coder decodeIntegerForKey:key_ns_string];
+        () = msg![env; this setContentMode:content_mode];
+    }
+
     let key_ns_string = get_static_str(env, "UISubviews");
     let subviews: id = msg![env; coder decodeObjectForKey:key_ns_string];
     let subview_count: NSUInteger = msg![env; subviews count];
@@ -155,6 +346,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     () = msg![env; this setCenter:center];
     () = msg![env; this setHidden:hidden];
     () = msg![env; this setOpaque:opaque];
+    () = msg![env; this setUserInteractionEnabled:(!interaction_disabled)];
 
     for i in 0..subview_count {
         let subview: id = msg![env; subviews objectAtIndex:i];
@@ -176,6 +368,40 @@ pub const CLASSES: ClassExports = objc_classes! {
     // TODO: enable multitouch
 }
 
+- (id)gestureRecognizers {
+    let recognizers = env.objc.borrow::<UIViewHostObject>(this).gesture_recognizers.clone();
+    if recognizers.is_empty() {
+        return nil;
+    }
+    for &recognizer in &recognizers {
+        retain(env, recognizer);
+    }
+    let array = ns_array::from_vec(env, recognizers);
+    autorelease(env, array)
+}
+- (())addGestureRecognizer:(id)recognizer { // UIGestureRecognizer*
+    if env.objc.borrow::<UIViewHostObject>(this).gesture_recognizers.contains(&recognizer) {
+        return;
+    }
+    retain(env, recognizer);
+    // A recognizer can only be attached to one view at a time.
+    let old_view: id = msg![env; recognizer view];
+    if old_view != nil {
+        () = msg![env; old_view removeGestureRecognizer:recognizer];
+    }
+    env.objc.borrow_mut::<UIViewHostObject>(this).gesture_recognizers.push(recognizer);
+    ui_gesture_recognizer::set_view(env, recognizer, this);
+}
+- (())removeGestureRecognizer:(id)recognizer { // UIGestureRecognizer*
+    let recognizers = &mut env.objc.borrow_mut::<UIViewHostObject>(this).gesture_recognizers;
+    let Some(idx) = recognizers.iter().position(|&r| r == recognizer) else {
+        return;
+    };
+    recognizers.remove(idx);
+    ui_gesture_recognizer::set_view(env, recognizer, nil);
+    release(env, recognizer);
+}
+
 - (())layoutSubviews {
     // On iOS 5.1 and earlier, the default implementation of this method does nothing.
 }
@@ -251,9 +477,15 @@ pub const CLASSES: ClassExports = objc_classes! {
         subviews,
         clears_context_before_drawing: _,
         user_interaction_enabled: _,
+        content_mode: _,
+        gesture_recognizers,
     } = std::mem::take(env.objc.borrow_mut(this));
 
     release(env, layer);
+    for recognizer in gesture_recognizers {
+        ui_gesture_recognizer::set_view(env, recognizer, nil);
+        release(env, recognizer);
+    }
     assert!(superview == nil);
     for subview in subviews {
         env.objc.borrow_mut::<UIViewHostObject>(subview).superview = nil;
@@ -289,6 +521,15 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; layer setOpaque:opaque]
 }
 
+- (bool)clipsToBounds {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer masksToBounds]
+}
+- (())setClipsToBounds:(bool)clips_to_bounds {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer setMasksToBounds:clips_to_bounds]
+}
+
 - (CGFloat)alpha {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     msg![env; layer opacity]
@@ -298,24 +539,42 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; layer setOpacity:alpha]
 }
 
-// FIXME: CALayer's backgroundColor should be a CGColorRef, which is supposedly
-// a separate type from UIColor. For now we have not implemented it and treat
-// them as the same type (and it seems like UIKit itself maybe did this once),
-// but eventually we'll have to do this properly.
+// The layer's background color is a CGColorRef, so the UIColor has to be
+// converted in both directions.
 - (id)backgroundColor {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
-    msg![env; layer backgroundColor]
+    let cg_color: CGColorRef = msg![env; layer backgroundColor];
+    if cg_color == nil {
+        return nil;
+    }
+    msg_class![env; UIColor colorWithCGColor:cg_color]
 }
 - (())setBackgroundColor:(id)color { // UIColor*
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
-    msg![env; layer setBackgroundColor:color]
+    let cg_color: CGColorRef = msg![env; color CGColor];
+    msg![env; layer setBackgroundColor:cg_color]
 }
 
-// TODO: support setNeedsDisplayInRect:
 - (())setNeedsDisplay {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     msg![env; layer setNeedsDisplay]
 }
+- (())setNeedsDisplayInRect:(CGRect)rect {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer setNeedsDisplayInRect:rect]
+}
+
+// CALayerDelegate implementation
+- (id)actionForLayer:(id)_layer // CALayer*
+              forKey:(id)_key { // NSString*
+    // Changes to a view's layer are only animated inside an animation block,
+    // where the default action (an implicit animation) is used.
+    if env.framework_state.uikit.ui_view.animation_blocks.is_empty() {
+        msg_class![env; NSNull null]
+    } else {
+        nil
+    }
+}
 
 - (CGRect)bounds {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
@@ -330,7 +589,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     msg![env; layer position]
 }
-- (())setCenter:(CGRect)center {
+- (())setCenter:(CGPoint)center {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     msg![env; layer setPosition:center]
 }
@@ -342,6 +601,24 @@ pub const CLASSES: ClassExports = objc_classes! {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     msg![env; layer setFrame:frame]
 }
+- (CGAffineTransform)transform {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer affineTransform]
+}
+- (())setTransform:(CGAffineTransform)transform {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer setAffineTransform:transform]
+}
+
+- (UIViewContentMode)contentMode {
+    env.objc.borrow::<UIViewHostObject>(this).content_mode
+}
+- (())setContentMode:(UIViewContentMode)content_mode {
+    env.objc.borrow_mut::<UIViewHostObject>(this).content_mode = content_mode;
+    let gravity = get_static_str(env, contents_gravity_for_content_mode(content_mode));
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer setContentsGravity:gravity]
+}
 
 - (bool)clearsContextBeforeDrawing {
     env.objc.borrow::<UIViewHostObject>(this).clears_context_before_drawing
@@ -370,32 +647,38 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 // Event handling
 
-- (id)pointInside:(CGPoint)point
-        withEvent:(id)_event { // UIEvent* (possibly nil)
+- (id)nextResponder {
+    // A view controller comes between its view and the superview.
+    if let Some(controller) = controller_for_view(env, this) {
+        return controller;
+    }
+    env.objc.borrow::<UIViewHostObject>(this).superview
+}
+
+- (bool)pointInside:(CGPoint)point
+          withEvent:(id)_event { // UIEvent* (possibly nil)
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     msg![env; layer containsPoint:point]
 }
 
 - (id)hitTest:(CGPoint)point
     withEvent:(id)event { // UIEvent* (possibly nil)
-    if !msg![env; this pointInside:point withEvent:event] {
+    let hidden: bool = msg![env; this isHidden];
+    let alpha: CGFloat = msg![env; this alpha];
+    let interactible: bool = msg![env; this isUserInteractionEnabled];
+    if hidden || alpha < 0.01 || !interactible {
+        return nil;
+    }
+    // Apple's documentation says points outside the bounds are never hits,
+    // even if they are inside a subview that isn't clipped to the bounds.
+    let inside: bool = msg![env; this pointInside:point withEvent:event];
+    if !inside {
         return nil;
     }
     // TODO: avoid copy somehow?
     let subviews = env.objc.borrow::<UIViewHostObject>(this).subviews.clone();
     for subview in subviews.into_iter().rev() { // later views are on top
-        let hidden: bool = msg![env; subview isHidden];
-        let alpha: CGFloat = msg![env; subview alpha];
-        let interactible: bool = msg![env; this isUserInteractionEnabled];
-        if hidden || alpha < 0.01 || !interactible {
-           continue;
-        }
-        let frame: CGRect = msg![env; subview frame];
-        let bounds: CGRect = msg![env; subview bounds];
-        let point = CGPoint {
-            x: point.x - frame.origin.x + bounds.origin.x,
-            y: point.y - frame.origin.y + bounds.origin.y,
-        };
+        let point: CGPoint = msg![env; subview convertPoint:point fromView:this];
         let subview: id = msg![env; subview hitTest:point withEvent:event];
         if subview != nil {
             return subview;
@@ -4,9 +4,30 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `UITextField`.
+//!
+//! Text input comes from the host keyboard via the `UIKeyInput` methods, see
+//! [crate::frameworks::uikit::ui_responder]. The caret is always at the end of
+//! the text.
 
-use crate::frameworks::foundation::NSInteger;
-use crate::objc::{id, objc_classes, ClassExports};
+use super::{
+    send_actions, UIControlEventEditingChanged, UIControlEventEditingDidBegin,
+    UIControlEventEditingDidEnd, UIControlEventEditingDidEndOnExit, UIControlHostObject,
+};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::cg_context::{CGContextFillRect, CGContextSetRGBFillColor};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str, to_rust_string};
+use crate::frameworks::foundation::{NSInteger, NSRange, NSUInteger};
+use crate::frameworks::uikit::ui_color;
+use crate::frameworks::uikit::ui_font::{
+    self, UITextAlignment, UITextAlignmentCenter, UITextAlignmentLeft, UITextAlignmentRight,
+};
+use crate::frameworks::uikit::ui_graphics::UIGraphicsGetCurrentContext;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes, release,
+    retain, ClassExports, NSZonePtr,
+};
+use crate::Environment;
 
 type UIKeyboardAppearance = NSInteger;
 type UIKeyboardType = NSInteger;
@@ -14,29 +35,398 @@ type UIReturnKeyType = NSInteger;
 type UITextAutocapitalizationType = NSInteger;
 type UITextAutocorrectionType = NSInteger;
 
+pub const UITextFieldTextDidBeginEditingNotification: &str =
+    "UITextFieldTextDidBeginEditingNotification";
+pub const UITextFieldTextDidChangeNotification: &str = "UITextFieldTextDidChangeNotification";
+pub const UITextFieldTextDidEndEditingNotification: &str =
+    "UITextFieldTextDidEndEditingNotification";
+
+/// `NSNotificationName` values.
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UITextFieldTextDidBeginEditingNotification",
+        HostConstant::NSString(UITextFieldTextDidBeginEditingNotification),
+    ),
+    (
+        "_UITextFieldTextDidChangeNotification",
+        HostConstant::NSString(UITextFieldTextDidChangeNotification),
+    ),
+    (
+        "_UITextFieldTextDidEndEditingNotification",
+        HostConstant::NSString(UITextFieldTextDidEndEditingNotification),
+    ),
+];
+
+pub struct UITextFieldHostObject {
+    superclass: UIControlHostObject,
+    /// `NSString*`
+    text: id,
+    /// `NSString*`
+    placeholder: id,
+    /// `UIFont*`
+    font: id,
+    /// `UIColor*`
+    text_color: id,
+    text_alignment: UITextAlignment,
+    clears_on_begin_editing: bool,
+    /// Weak reference to something implementing `UITextFieldDelegate`.
+    delegate: id,
+}
+impl_HostObject_with_superclass!(UITextFieldHostObject);
+impl Default for UITextFieldHostObject {
+    fn default() -> Self {
+        UITextFieldHostObject {
+            superclass: Default::default(),
+            text: nil,
+            placeholder: nil,
+            font: nil,
+            text_color: nil,
+            text_alignment: UITextAlignmentLeft,
+            clears_on_begin_editing: false,
+            delegate: nil,
+        }
+    }
+}
+
+/// Replace a range (in UTF-16 code units) of `text` with `replacement`, like
+/// `[NSString stringByReplacingCharactersInRange:withString:]`.
+pub fn replace_range(text: &str, range: NSRange, replacement: &str) -> String {
+    let mut utf16: Vec<u16> = text.encode_utf16().collect();
+    let start = range.location as usize;
+    let end = start + range.length as usize;
+    utf16.splice(start..end, replacement.encode_utf16());
+    String::from_utf16_lossy(&utf16)
+}
+
+/// Get the range (in UTF-16 code units) covering the last character of `text`,
+/// which is what the backspace key deletes.
+pub fn last_character_range(text: &str) -> Option<NSRange> {
+    let last = text.chars().next_back()?;
+    let length = last.len_utf16() as NSUInteger;
+    Some(NSRange {
+        location: text.encode_utf16().count() as NSUInteger - length,
+        length,
+    })
+}
+
+fn delegate_responds(env: &mut Environment, delegate: id, selector: &str) -> bool {
+    delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, selector)
+}
+
+fn post_notification(env: &mut Environment, this: id, name: &'static str) {
+    let name = get_static_str(env, name);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    let _: () = msg![env; center postNotificationName:name object:this];
+}
+
+/// Replace part of the text for text input, if the delegate allows it.
+fn replace_text(env: &mut Environment, this: id, range: NSRange, replacement: id) {
+    let NSRange { location, length } = range;
+    let delegate = env.objc.borrow::<UITextFieldHostObject>(this).delegate;
+    if delegate_responds(
+        env,
+        delegate,
+        "textField:shouldChangeCharactersInRange:replacementString:",
+    ) {
+        let range = NSRange { location, length };
+        let allowed: bool = msg![env; delegate textField:this
+                                 shouldChangeCharactersInRange:range
+                                             replacementString:replacement];
+        if !allowed {
+            return;
+        }
+    }
+
+    let text: id = msg![env; this text];
+    let text = to_rust_string(env, text);
+    let replacement = to_rust_string(env, replacement);
+    let new_text = replace_range(&text, NSRange { location, length }, &replacement);
+    let new_text = from_rust_string(env, new_text);
+    () = msg![env; this setText:new_text];
+    release(env, new_text);
+
+    send_actions(env, this, nil, UIControlEventEditingChanged);
+    post_notification(env, this, UITextFieldTextDidChangeNotification);
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
 @implementation UITextField: UIControl
 
-// TODO: rendering
-// TODO: more properties
+// TODO: more properties (borderStyle, clear button, etc)
 
-- (())setText:(id)_text { // NSString*
-    // TODO
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UITextFieldHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
 }
-- (())setTextColor:(id)_color { // UIColor*
-    // TODO: implement this once views are actually rendered
+
+- (id)initWithFrame:(CGRect)frame {
+    let this: id = msg_super![env; this initWithFrame:frame];
+    // These aren't redundant, the setters fetch the real defaults.
+    () = msg![env; this setText:nil];
+    () = msg![env; this setFont:nil];
+    () = msg![env; this setTextColor:nil];
+    this
 }
 
-- (())setClearsOnBeginEditing:(bool)_clear {
-    // TODO
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    let this: id = msg_super![env; this initWithCoder:coder];
+    // TODO: decode the font and text color
+    () = msg![env; this setFont:nil];
+    () = msg![env; this setTextColor:nil];
+
+    let key_ns_string = get_static_str(env, "UIText");
+    let text: id = msg![env; coder decodeObjectForKey:key_ns_string];
+    () = msg![env; this setText:text];
+
+    let key_ns_string = get_static_str(env, "UIPlaceholder");
+    let placeholder: id = msg![env; coder decodeObjectForKey:key_ns_string];
+    () = msg![env; this setPlaceholder:placeholder];
+
+    let key_ns_string = get_static_str(env, "UITextAlignment");
+    let text_alignment: UITextAlignment = msg![env; coder decodeIntegerForKey:key_ns_string];
+    () = msg![env; this setTextAlignment:text_alignment];
+
+    this
+}
+
+- (())dealloc {
+    let &UITextFieldHostObject {
+        text,
+        placeholder,
+        font,
+        text_color,
+        ..
+    } = env.objc.borrow(this);
+    release(env, text);
+    release(env, placeholder);
+    release(env, font);
+    release(env, text_color);
+    msg_super![env; this dealloc]
+}
+
+- (id)text {
+    env.objc.borrow::<UITextFieldHostObject>(this).text
+}
+- (())setText:(id)new_text { // NSString*
+    // The text is never nil.
+    let new_text: id = if new_text == nil {
+        get_static_str(env, "")
+    } else {
+        new_text
+    };
+    let new_text: id = msg![env; new_text copy];
+    let old_text = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextFieldHostObject>(this).text,
+        new_text
+    );
+    release(env, old_text);
+
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (id)placeholder {
+    env.objc.borrow::<UITextFieldHostObject>(this).placeholder
+}
+- (())setPlaceholder:(id)new_placeholder { // NSString*
+    let new_placeholder: id = msg![env; new_placeholder copy];
+    let old_placeholder = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextFieldHostObject>(this).placeholder,
+        new_placeholder
+    );
+    release(env, old_placeholder);
+
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (id)font {
+    env.objc.borrow::<UITextFieldHostObject>(this).font
+}
+- (())setFont:(id)new_font { // UIFont*
+    let new_font: id = if new_font == nil {
+        // reset to default
+        let size: CGFloat = 12.0;
+        msg_class![env; UIFont systemFontOfSize:size]
+    } else {
+        new_font
+    };
+
+    let old_font = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextFieldHostObject>(this).font,
+        new_font
+    );
+    retain(env, new_font);
+    release(env, old_font);
+
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (id)textColor {
+    env.objc.borrow::<UITextFieldHostObject>(this).text_color
+}
+- (())setTextColor:(id)new_text_color { // UIColor*
+    let new_text_color: id = if new_text_color == nil {
+        msg_class![env; UIColor blackColor]
+    } else {
+        new_text_color
+    };
+
+    let old_text_color = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextFieldHostObject>(this).text_color,
+        new_text_color
+    );
+    retain(env, new_text_color);
+    release(env, old_text_color);
+
+    () = msg![env; this setNeedsDisplay];
 }
 
+- (UITextAlignment)textAlignment {
+    env.objc.borrow::<UITextFieldHostObject>(this).text_alignment
+}
+- (())setTextAlignment:(UITextAlignment)text_alignment {
+    env.objc.borrow_mut::<UITextFieldHostObject>(this).text_alignment = text_alignment;
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (bool)clearsOnBeginEditing {
+    env.objc.borrow::<UITextFieldHostObject>(this).clears_on_begin_editing
+}
+- (())setClearsOnBeginEditing:(bool)clear {
+    env.objc.borrow_mut::<UITextFieldHostObject>(this).clears_on_begin_editing = clear;
+}
+
+- (id)delegate {
+    env.objc.borrow::<UITextFieldHostObject>(this).delegate
+}
 // weak/non-retaining
-- (())setDelegate:(id)_delegate { // something implementing UITextFieldDelegate
-    // TODO
+- (())setDelegate:(id)delegate { // something implementing UITextFieldDelegate
+    env.objc.borrow_mut::<UITextFieldHostObject>(this).delegate = delegate;
+}
+
+- (bool)isEditing {
+    msg![env; this isFirstResponder]
+}
+
+// UIResponder overrides
+
+- (bool)canBecomeFirstResponder {
+    msg![env; this isEnabled]
+}
+
+- (bool)becomeFirstResponder {
+    if msg![env; this isFirstResponder] {
+        return true;
+    }
+    let delegate = env.objc.borrow::<UITextFieldHostObject>(this).delegate;
+    if delegate_responds(env, delegate, "textFieldShouldBeginEditing:") {
+        let should_begin: bool = msg![env; delegate textFieldShouldBeginEditing:this];
+        if !should_begin {
+            return false;
+        }
+    }
+    if !msg_super![env; this becomeFirstResponder] {
+        return false;
+    }
+
+    if env.objc.borrow::<UITextFieldHostObject>(this).clears_on_begin_editing {
+        () = msg![env; this setText:nil];
+    }
+    // Make the caret appear.
+    () = msg![env; this setNeedsDisplay];
+
+    send_actions(env, this, nil, UIControlEventEditingDidBegin);
+    if delegate_responds(env, delegate, "textFieldDidBeginEditing:") {
+        () = msg![env; delegate textFieldDidBeginEditing:this];
+    }
+    post_notification(env, this, UITextFieldTextDidBeginEditingNotification);
+    true
+}
+
+- (bool)resignFirstResponder {
+    if !msg![env; this isFirstResponder] {
+        return true;
+    }
+    let delegate = env.objc.borrow::<UITextFieldHostObject>(this).delegate;
+    if delegate_responds(env, delegate, "textFieldShouldEndEditing:") {
+        let should_end: bool = msg![env; delegate textFieldShouldEndEditing:this];
+        if !should_end {
+            return false;
+        }
+    }
+    if !msg_super![env; this resignFirstResponder] {
+        return false;
+    }
+
+    // Make the caret disappear.
+    () = msg![env; this setNeedsDisplay];
+
+    send_actions(env, this, nil, UIControlEventEditingDidEnd);
+    if delegate_responds(env, delegate, "textFieldDidEndEditing:") {
+        () = msg![env; delegate textFieldDidEndEditing:this];
+    }
+    post_notification(env, this, UITextFieldTextDidEndEditingNotification);
+    true
+}
+
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    () = msg_super![env; this touchesEnded:touches withEvent:event];
+    // Tapping a text field starts editing it.
+    let _: bool = msg![env; this becomeFirstResponder];
+}
+
+// UIKeyInput implementation
+
+- (bool)hasText {
+    let text: id = msg![env; this text];
+    let length: NSUInteger = msg![env; text length];
+    length != 0
+}
+
+- (())insertText:(id)new_text { // NSString*
+    if to_rust_string(env, new_text) == "\n" {
+        // Return key
+        let delegate = env.objc.borrow::<UITextFieldHostObject>(this).delegate;
+        if delegate_responds(env, delegate, "textFieldShouldReturn:") {
+            let should_return: bool = msg![env; delegate textFieldShouldReturn:this];
+            if !should_return {
+                return;
+            }
+        }
+        // Text fields are single-line, so return doesn't insert anything, but
+        // it can be used to dismiss the keyboard.
+        send_actions(env, this, nil, UIControlEventEditingDidEndOnExit);
+        let has_exit_target = env
+            .objc
+            .borrow::<UIControlHostObject>(this)
+            .action_targets
+            .iter()
+            .any(|&(_, _, events)| events & UIControlEventEditingDidEndOnExit != 0);
+        if has_exit_target {
+            let _: bool = msg![env; this resignFirstResponder];
+        }
+        return;
+    }
+
+    let text: id = msg![env; this text];
+    let length: NSUInteger = msg![env; text length];
+    replace_text(env, this, NSRange { location: length, length: 0 }, new_text);
+}
+
+- (())deleteBackward {
+    let text: id = msg![env; this text];
+    let Some(range) = last_character_range(&to_rust_string(env, text)) else {
+        return;
+    };
+    let empty = get_static_str(env, "");
+    replace_text(env, this, range, empty);
 }
 
 // UITextInputTraits implementation
@@ -56,6 +446,83 @@ pub const CLASSES: ClassExports = objc_classes! {
     // TODO
 }
 
+- (())drawRect:(CGRect)_rect {
+    let bounds: CGRect = msg![env; this bounds];
+    let editing: bool = msg![env; this isFirstResponder];
+    let context = UIGraphicsGetCurrentContext(env);
+
+    let &UITextFieldHostObject {
+        text,
+        placeholder,
+        font,
+        text_color,
+        text_alignment,
+        ..
+    } = env.objc.borrow(this);
+
+    let has_text: bool = msg![env; this hasText];
+    let (text, (r, g, b, a)) = if !has_text && placeholder != nil {
+        // TODO: is this the right color?
+        (placeholder, (0.7, 0.7, 0.7, 1.0))
+    } else {
+        (text, ui_color::get_rgba(&env.objc, text_color))
+    };
+    CGContextSetRGBFillColor(env, context, r, g, b, a);
+
+    // Text fields are single-line and vertically centered.
+    let line_height = ui_font::height_of_lines(env, font, 1);
+    let text_size: CGSize = msg![env; text sizeWithFont:font];
+    let x_offset = match text_alignment {
+        UITextAlignmentLeft => 0.0,
+        UITextAlignmentCenter => 0.5,
+        UITextAlignmentRight => 1.0,
+        _ => unimplemented!(),
+    };
+    let point = CGPoint {
+        x: bounds.origin.x + x_offset * (bounds.size.width - text_size.width).max(0.0),
+        y: bounds.origin.y + (bounds.size.height - line_height) / 2.0,
+    };
+    let text_size: CGSize = msg![env; text drawAtPoint:point
+                                            forWidth:(bounds.size.width)
+                                            withFont:font
+                                       lineBreakMode:(ui_font::UILineBreakModeClip)];
+
+    if editing {
+        // The caret goes after the text, or before the placeholder.
+        let caret_x = if has_text {
+            point.x + text_size.width
+        } else {
+            point.x
+        };
+        let (r, g, b, a) = ui_color::get_rgba(&env.objc, text_color);
+        CGContextSetRGBFillColor(env, context, r, g, b, a);
+        let caret = CGRect {
+            origin: CGPoint {
+                x: caret_x.min(bounds.origin.x + bounds.size.width - 2.0),
+                y: point.y,
+            },
+            size: CGSize {
+                width: 2.0,
+                height: line_height,
+            },
+        };
+        CGContextFillRect(env, context, caret);
+    }
+}
+
 @end
 
 };
+
+#[cfg(test)]
+#[test]
+fn test_replace_range() {
+    let range = |location, length| NSRange { location, length };
+    assert_eq!(replace_range("hello", range(5, 0), "!"), "hello!");
+    assert_eq!(replace_range("hello", range(0, 1), "J"), "Jello");
+    assert_eq!(replace_range("héllo", range(1, 4), ""), "h");
+    assert!(last_character_range("").is_none());
+    let emoji = last_character_range("a😀").unwrap();
+    assert_eq!((emoji.location, emoji.length), (1, 2));
+    assert_eq!(replace_range("a😀", emoji, ""), "a");
+}
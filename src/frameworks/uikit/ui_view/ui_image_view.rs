@@ -6,9 +6,9 @@
 //! `UIImageView`.
 
 use crate::frameworks::core_graphics::cg_image::CGImageRef;
-use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::objc::{
-    id, impl_HostObject_with_superclass, msg, msg_super, objc_classes, release, retain,
+    id, impl_HostObject_with_superclass, msg, msg_super, nil, objc_classes, release, retain,
     ClassExports, NSZonePtr,
 };
 
@@ -36,6 +36,9 @@ pub const CLASSES: ClassExports = objc_classes! {
     // Not sure if UIImageView does this unconditionally, or only for images
     // with alpha channels.
     () = msg![env; this setOpaque:false];
+    // Unlike most views, image views ignore touches by default, so they don't
+    // get in the way of the views they decorate.
+    () = msg![env; this setUserInteractionEnabled:false];
     this
 }
 
@@ -87,7 +90,9 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())displayLayer:(id)layer {
     let image: id = msg![env; this image];
     let cg_image: CGImageRef = msg![env; image CGImage];
+    let scale: CGFloat = if image == nil { 1.0 } else { msg![env; image scale] };
     () = msg![env; layer setContents:cg_image];
+    () = msg![env; layer setContentsScale:scale];
 }
 
 @end
@@ -7,10 +7,11 @@
 
 use crate::frameworks::core_graphics::cg_context::CGContextSetRGBFillColor;
 use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string::get_static_str;
 use crate::frameworks::foundation::NSInteger;
 use crate::frameworks::uikit::ui_color;
 use crate::frameworks::uikit::ui_font::{
-    UILineBreakMode, UILineBreakModeTailTruncation, UITextAlignment, UITextAlignmentCenter,
+    self, UILineBreakMode, UILineBreakModeTailTruncation, UITextAlignment, UITextAlignmentCenter,
     UITextAlignmentLeft, UITextAlignmentRight,
 };
 use crate::frameworks::uikit::ui_graphics::UIGraphicsGetCurrentContext;
@@ -65,6 +66,8 @@ pub const CLASSES: ClassExports = objc_classes! {
     () = msg![env; this setBackgroundColor:nil];
     // Built-in views don't have user-controlled opaqueness.
     () = msg_super![env; this setOpaque:false];
+    // Labels ignore touches by default.
+    () = msg![env; this setUserInteractionEnabled:false];
     this
 }
 
@@ -84,7 +87,37 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg_super![env; this dealloc]
 }
 
-// TODO: initWithCoder:
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    let this: id = msg_super![env; this initWithCoder:coder];
+    // TODO: decode the font and text color
+    () = msg![env; this setFont:nil];
+    () = msg![env; this setTextColor:nil];
+    () = msg![env; this setBackgroundColor:nil];
+    () = msg_super![env; this setOpaque:false];
+
+    let key_ns_string = get_static_str(env, "UIText");
+    let text: id = msg![env; coder decodeObjectForKey:key_ns_string];
+    () = msg![env; this setText:text];
+
+    let key_ns_string = get_static_str(env, "UITextAlignment");
+    let text_alignment: UITextAlignment = msg![env; coder decodeIntegerForKey:key_ns_string];
+    () = msg![env; this setTextAlignment:text_alignment];
+
+    let key_ns_string = get_static_str(env, "UILineBreakMode");
+    if msg![env; coder containsValueForKey:key_ns_string] {
+        let mode: UILineBreakMode = msg![env; coder decodeIntegerForKey:key_ns_string];
+        () = msg![env; this setLineBreakMode:mode];
+    }
+
+    let key_ns_string = get_static_str(env, "UINumberOfLines");
+    if msg![env; coder containsValueForKey:key_ns_string] {
+        let number: NSInteger = msg![env; coder decodeIntegerForKey:key_ns_string];
+        () = msg![env; this setNumberOfLines:number];
+    }
+
+    this
+}
 
 - (id)text {
     env.objc.borrow::<UILabelHostObject>(this).text
@@ -180,9 +213,6 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 - (())setNumberOfLines:(NSInteger)number {
     env.objc.borrow_mut::<UILabelHostObject>(this).number_of_lines = number;
-    if number != 0 && number != 1 {
-        log!("TODO: UILabel numberOfLines > 1 (label {:?})", this);
-    }
     () = msg![env; this setNeedsDisplay];
 }
 
@@ -203,15 +233,21 @@ pub const CLASSES: ClassExports = objc_classes! {
     let (r, g, b, a) = ui_color::get_rgba(&env.objc, text_color);
     CGContextSetRGBFillColor(env, context, r, g, b, a);
 
-    // TODO: handle line counts other than 0 and 1 properly. 0 = unlimited
-    // (note the log message in setNumberOfLines:)
     let single_line = number_of_lines == 1;
 
+    // A numberOfLines of 0 means there is no limit, other than the height of
+    // the label.
+    let mut constrained_size = bounds.size;
+    if number_of_lines > 1 {
+        let max_height = ui_font::height_of_lines(env, font, number_of_lines);
+        constrained_size.height = constrained_size.height.min(max_height);
+    }
+
     let calculated_size: CGSize = if single_line {
         msg![env; text sizeWithFont:font]
     } else {
         msg![env; text sizeWithFont:font
-                  constrainedToSize:(bounds.size)
+                  constrainedToSize:constrained_size
                       lineBreakMode:line_break_mode]
     };
 
@@ -223,7 +259,10 @@ pub const CLASSES: ClassExports = objc_classes! {
             x: bounds.origin.x,
             y: bounds.origin.y + (bounds.size.height - calculated_size.height) / 2.0,
         },
-        size: bounds.size,
+        size: CGSize {
+            width: bounds.size.width,
+            height: calculated_size.height,
+        },
     };
 
     let _size: CGSize = if single_line {
@@ -0,0 +1,656 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIScrollView`.
+//!
+//! The content offset is the origin of the view's bounds, so changing it moves
+//! the subviews. Dragging is done with the touches the scroll view receives,
+//! either directly or passed up the responder chain by its content. After the
+//! content is released, it keeps moving by itself (see [update_scroll_views]).
+//!
+//! Real scroll views delay touches to their content until they know whether
+//! the user is trying to scroll. That isn't done here.
+
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_send, msg_super, nil, objc_classes, release,
+    retain, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+/// Factor the velocity is multiplied by every millisecond when decelerating
+/// (`UIScrollViewDecelerationRateNormal`).
+const DECELERATION_RATE: CGFloat = 0.998;
+/// Velocity in points per second below which movement stops.
+const MIN_VELOCITY: CGFloat = 10.0;
+/// Velocity in points per second above which releasing a paging scroll view
+/// moves to the next page, even if it hasn't been dragged halfway.
+const PAGE_FLICK_VELOCITY: CGFloat = 200.0;
+/// How quickly the content springs back after bouncing, or moves to a page.
+/// Larger is faster.
+const SPRING_RATE: CGFloat = 12.0;
+/// How quickly the velocity is lost once the content has gone past an edge.
+const BOUNCE_DAMPING: CGFloat = 20.0;
+
+#[derive(Default)]
+pub struct State {
+    /// Scroll views that are moving by themselves. These are strong references.
+    moving: Vec<id>,
+}
+
+struct UIScrollViewHostObject {
+    superclass: super::UIViewHostObject,
+    content_size: CGSize,
+    /// Weak reference.
+    delegate: id,
+    scroll_enabled: bool,
+    bounces: bool,
+    always_bounce_horizontal: bool,
+    always_bounce_vertical: bool,
+    paging_enabled: bool,
+    shows_horizontal_scroll_indicator: bool,
+    shows_vertical_scroll_indicator: bool,
+    /// `UITouch*` of the touch dragging the content, [nil] if none. This is a
+    /// weak reference.
+    dragging_touch: id,
+    /// Whether the dragging touch has moved yet.
+    dragging: bool,
+    /// In points per second, estimated while dragging.
+    velocity: CGPoint,
+    /// Set when moving to a page boundary.
+    target_offset: Option<CGPoint>,
+    /// When the content last moved by itself, if it is moving.
+    last_step: Option<Instant>,
+}
+impl_HostObject_with_superclass!(UIScrollViewHostObject);
+impl Default for UIScrollViewHostObject {
+    fn default() -> Self {
+        UIScrollViewHostObject {
+            superclass: Default::default(),
+            content_size: CGSize {
+                width: 0.0,
+                height: 0.0,
+            },
+            delegate: nil,
+            scroll_enabled: true,
+            bounces: true,
+            always_bounce_horizontal: false,
+            always_bounce_vertical: false,
+            paging_enabled: false,
+            shows_horizontal_scroll_indicator: true,
+            shows_vertical_scroll_indicator: true,
+            dragging_touch: nil,
+            dragging: false,
+            velocity: CGPoint { x: 0.0, y: 0.0 },
+            target_offset: None,
+            last_step: None,
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIScrollView: UIView
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIScrollViewHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    let this: id = msg_super![env; this initWithCoder:coder];
+
+    let key_ns_string = get_static_str(env, "UIContentSize");
+    let content_size: CGSize = msg![env; coder decodeCGSizeForKey:key_ns_string];
+    let key_ns_string = get_static_str(env, "UIPagingEnabled");
+    let paging_enabled: bool = msg![env; coder decodeBoolForKey:key_ns_string];
+    // These are only present when they have been turned off.
+    let key_ns_string = get_static_str(env, "UIScrollDisabled");
+    let scroll_disabled: bool = msg![env; coder decodeBoolForKey:key_ns_string];
+    let key_ns_string = get_static_str(env, "UIBouncesZoom");
+    let _: bool = msg![env; coder decodeBoolForKey:key_ns_string];
+    let key_ns_string = get_static_str(env, "UIBounceDisabled");
+    let bounce_disabled: bool = msg![env; coder decodeBoolForKey:key_ns_string];
+
+    let host_object = env.objc.borrow_mut::<UIScrollViewHostObject>(this);
+    host_object.content_size = content_size;
+    host_object.paging_enabled = paging_enabled;
+    host_object.scroll_enabled = !scroll_disabled;
+    host_object.bounces = !bounce_disabled;
+
+    this
+}
+
+- (())dealloc {
+    let state = &mut env.framework_state.uikit.ui_view.ui_scroll_view;
+    // Scroll views that are moving are retained, so this one can't be.
+    assert!(!state.moving.contains(&this));
+    msg_super![env; this dealloc]
+}
+
+- (id)delegate {
+    env.objc.borrow::<UIScrollViewHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing UIScrollViewDelegate
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).delegate = delegate;
+}
+
+- (CGSize)contentSize {
+    env.objc.borrow::<UIScrollViewHostObject>(this).content_size
+}
+- (())setContentSize:(CGSize)size {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).content_size = size;
+}
+
+- (CGPoint)contentOffset {
+    let bounds: CGRect = msg![env; this bounds];
+    bounds.origin
+}
+- (())setContentOffset:(CGPoint)offset {
+    let mut bounds: CGRect = msg![env; this bounds];
+    if bounds.origin == offset {
+        return;
+    }
+    bounds.origin = offset;
+    () = msg![env; this setBounds:bounds];
+    let delegate = env.objc.borrow::<UIScrollViewHostObject>(this).delegate;
+    if delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, "scrollViewDidScroll:")
+    {
+        () = msg![env; delegate scrollViewDidScroll:this];
+    }
+}
+- (())setContentOffset:(CGPoint)offset
+              animated:(bool)animated {
+    stop_moving(env, this);
+    if animated {
+        env.objc.borrow_mut::<UIScrollViewHostObject>(this).target_offset = Some(offset);
+        start_moving(env, this);
+    } else {
+        () = msg![env; this setContentOffset:offset];
+    }
+}
+
+- (())scrollRectToVisible:(CGRect)rect
+                 animated:(bool)animated {
+    let bounds: CGRect = msg![env; this bounds];
+    let (max_x, max_y) = max_offset(env, this);
+    let axis = |offset: CGFloat, visible: CGFloat, start: CGFloat, length: CGFloat, max| {
+        let offset = if start < offset {
+            start
+        } else if start + length > offset + visible {
+            (start + length - visible).min(start)
+        } else {
+            offset
+        };
+        offset.clamp(0.0, max)
+    };
+    let offset = CGPoint {
+        x: axis(bounds.origin.x, bounds.size.width, rect.origin.x, rect.size.width, max_x),
+        y: axis(bounds.origin.y, bounds.size.height, rect.origin.y, rect.size.height, max_y),
+    };
+    msg![env; this setContentOffset:offset animated:animated]
+}
+
+- (bool)isScrollEnabled {
+    env.objc.borrow::<UIScrollViewHostObject>(this).scroll_enabled
+}
+- (())setScrollEnabled:(bool)enabled {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).scroll_enabled = enabled;
+}
+
+- (bool)bounces {
+    env.objc.borrow::<UIScrollViewHostObject>(this).bounces
+}
+- (())setBounces:(bool)bounces {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).bounces = bounces;
+}
+- (bool)alwaysBounceHorizontal {
+    env.objc.borrow::<UIScrollViewHostObject>(this).always_bounce_horizontal
+}
+- (())setAlwaysBounceHorizontal:(bool)value {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).always_bounce_horizontal = value;
+}
+- (bool)alwaysBounceVertical {
+    env.objc.borrow::<UIScrollViewHostObject>(this).always_bounce_vertical
+}
+- (())setAlwaysBounceVertical:(bool)value {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).always_bounce_vertical = value;
+}
+
+- (bool)isPagingEnabled {
+    env.objc.borrow::<UIScrollViewHostObject>(this).paging_enabled
+}
+- (())setPagingEnabled:(bool)enabled {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).paging_enabled = enabled;
+}
+
+// TODO: draw scroll indicators
+- (bool)showsHorizontalScrollIndicator {
+    env.objc.borrow::<UIScrollViewHostObject>(this).shows_horizontal_scroll_indicator
+}
+- (())setShowsHorizontalScrollIndicator:(bool)shows {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).shows_horizontal_scroll_indicator = shows;
+}
+- (bool)showsVerticalScrollIndicator {
+    env.objc.borrow::<UIScrollViewHostObject>(this).shows_vertical_scroll_indicator
+}
+- (())setShowsVerticalScrollIndicator:(bool)shows {
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).shows_vertical_scroll_indicator = shows;
+}
+
+- (bool)isTracking {
+    env.objc.borrow::<UIScrollViewHostObject>(this).dragging_touch != nil
+}
+- (bool)isDragging {
+    env.objc.borrow::<UIScrollViewHostObject>(this).dragging
+}
+- (bool)isDecelerating {
+    let host_object = env.objc.borrow::<UIScrollViewHostObject>(this);
+    host_object.last_step.is_some() && host_object.dragging_touch == nil
+}
+
+- (())touchesBegan:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    let host_object = env.objc.borrow::<UIScrollViewHostObject>(this);
+    if !host_object.scroll_enabled || host_object.dragging_touch != nil {
+        return msg_super![env; this touchesBegan:touches withEvent:event];
+    }
+    // Touching moving content stops it.
+    stop_moving(env, this);
+    let touch: id = msg![env; touches anyObject];
+    let host_object = env.objc.borrow_mut::<UIScrollViewHostObject>(this);
+    host_object.dragging_touch = touch;
+    host_object.dragging = false;
+    host_object.velocity = CGPoint { x: 0.0, y: 0.0 };
+}
+
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    // Touch events are delivered one touch at a time.
+    let touch: id = msg![env; touches anyObject];
+    if touch == nil || touch != env.objc.borrow::<UIScrollViewHostObject>(this).dragging_touch {
+        return msg_super![env; this touchesMoved:touches withEvent:event];
+    }
+
+    if !env.objc.borrow::<UIScrollViewHostObject>(this).dragging {
+        env.objc.borrow_mut::<UIScrollViewHostObject>(this).dragging = true;
+        send_to_delegate(env, this, "scrollViewWillBeginDragging:");
+    }
+
+    // The superview's co-ordinates are used, because this view's co-ordinates
+    // move with the content.
+    let superview: id = msg![env; this superview];
+    let location: CGPoint = msg![env; touch locationInView:superview];
+    let previous: CGPoint = msg![env; touch previousLocationInView:superview];
+    let offset: CGPoint = msg![env; this contentOffset];
+    let (max_x, max_y) = max_offset(env, this);
+    let &UIScrollViewHostObject {
+        bounces,
+        velocity,
+        ..
+    } = env.objc.borrow(this);
+    let (can_x, can_y) = scrollable_axes(env, this);
+    let axis = |offset: CGFloat, delta: CGFloat, max: CGFloat, can_scroll: bool| {
+        if !can_scroll {
+            return offset;
+        }
+        let new = offset - delta;
+        if (0.0..=max).contains(&new) {
+            new
+        } else if bounces {
+            // The content resists being dragged past the edge.
+            offset - delta / 2.0
+        } else {
+            new.clamp(0.0, max)
+        }
+    };
+    let new_offset = CGPoint {
+        x: axis(offset.x, location.x - previous.x, max_x, can_x),
+        y: axis(offset.y, location.y - previous.y, max_y, can_y),
+    };
+
+    // The touch timestamps aren't reliable enough for this, so the time
+    // between events is assumed to be one frame.
+    const FRAME: CGFloat = 1.0 / 60.0;
+    let new_velocity = CGPoint {
+        x: (offset.x - new_offset.x) / -FRAME,
+        y: (offset.y - new_offset.y) / -FRAME,
+    };
+    // Smoothed, so one jittery event doesn't decide the momentum.
+    env.objc.borrow_mut::<UIScrollViewHostObject>(this).velocity = CGPoint {
+        x: (velocity.x + new_velocity.x) / 2.0,
+        y: (velocity.y + new_velocity.y) / 2.0,
+    };
+
+    () = msg![env; this setContentOffset:new_offset];
+}
+
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    // Touch events are delivered one touch at a time.
+    let touch: id = msg![env; touches anyObject];
+    if touch == nil || touch != env.objc.borrow::<UIScrollViewHostObject>(this).dragging_touch {
+        return msg_super![env; this touchesEnded:touches withEvent:event];
+    }
+
+    let host_object = env.objc.borrow_mut::<UIScrollViewHostObject>(this);
+    host_object.dragging_touch = nil;
+    let was_dragging = std::mem::take(&mut host_object.dragging);
+    let paging_enabled = host_object.paging_enabled;
+    let velocity = host_object.velocity;
+    if !was_dragging {
+        // It was just a tap.
+        return msg_super![env; this touchesEnded:touches withEvent:event];
+    }
+
+    if paging_enabled {
+        let offset: CGPoint = msg![env; this contentOffset];
+        let bounds: CGRect = msg![env; this bounds];
+        let (max_x, max_y) = max_offset(env, this);
+        let target = CGPoint {
+            x: page_target(offset.x, velocity.x, bounds.size.width, max_x),
+            y: page_target(offset.y, velocity.y, bounds.size.height, max_y),
+        };
+        env.objc.borrow_mut::<UIScrollViewHostObject>(this).target_offset = Some(target);
+    }
+
+    // Even without any momentum, the content might need to bounce back.
+    start_moving(env, this);
+    let will_decelerate = env.objc.borrow::<UIScrollViewHostObject>(this).last_step.is_some();
+    let delegate = env.objc.borrow::<UIScrollViewHostObject>(this).delegate;
+    if delegate != nil
+        && env.objc.object_has_method_named(
+            &env.mem,
+            delegate,
+            "scrollViewDidEndDragging:willDecelerate:",
+        )
+    {
+        () = msg![env; delegate scrollViewDidEndDragging:this willDecelerate:will_decelerate];
+    }
+    if will_decelerate {
+        send_to_delegate(env, this, "scrollViewWillBeginDecelerating:");
+    }
+}
+
+- (())touchesCancelled:(id)touches // NSSet* of UITouch*
+             withEvent:(id)event { // UIEvent*
+    let touch: id = msg![env; touches anyObject];
+    let host_object = env.objc.borrow_mut::<UIScrollViewHostObject>(this);
+    if touch == nil || touch != host_object.dragging_touch || !host_object.dragging {
+        if touch != nil && touch == host_object.dragging_touch {
+            host_object.dragging_touch = nil;
+        }
+        return msg_super![env; this touchesCancelled:touches withEvent:event];
+    }
+    // A drag that is cancelled still lets go of the content.
+    msg![env; this touchesEnded:touches withEvent:event]
+}
+
+@end
+
+};
+
+fn send_to_delegate(env: &mut Environment, scroll_view: id, selector: &str) {
+    let delegate = env
+        .objc
+        .borrow::<UIScrollViewHostObject>(scroll_view)
+        .delegate;
+    if delegate == nil
+        || !env
+            .objc
+            .object_has_method_named(&env.mem, delegate, selector)
+    {
+        return;
+    }
+    let selector = env.objc.lookup_selector(selector).unwrap();
+    () = msg_send(env, (delegate, selector, scroll_view));
+}
+
+/// Get the largest content offset on each axis.
+fn max_offset(env: &mut Environment, scroll_view: id) -> (CGFloat, CGFloat) {
+    let bounds: CGRect = msg![env; scroll_view bounds];
+    let content_size = env
+        .objc
+        .borrow::<UIScrollViewHostObject>(scroll_view)
+        .content_size;
+    (
+        (content_size.width - bounds.size.width).max(0.0),
+        (content_size.height - bounds.size.height).max(0.0),
+    )
+}
+
+/// Get whether dragging can move the content on each axis.
+fn scrollable_axes(env: &mut Environment, scroll_view: id) -> (bool, bool) {
+    let (max_x, max_y) = max_offset(env, scroll_view);
+    let &UIScrollViewHostObject {
+        bounces,
+        always_bounce_horizontal,
+        always_bounce_vertical,
+        ..
+    } = env.objc.borrow(scroll_view);
+    (
+        max_x > 0.0 || (bounces && always_bounce_horizontal),
+        max_y > 0.0 || (bounces && always_bounce_vertical),
+    )
+}
+
+/// Get the offset of the page a paging scroll view should settle on.
+fn page_target(offset: CGFloat, velocity: CGFloat, page_size: CGFloat, max: CGFloat) -> CGFloat {
+    if page_size <= 0.0 {
+        return offset.clamp(0.0, max);
+    }
+    let page = offset / page_size;
+    let page = if velocity > PAGE_FLICK_VELOCITY {
+        page.ceil()
+    } else if velocity < -PAGE_FLICK_VELOCITY {
+        page.floor()
+    } else {
+        page.round()
+    };
+    (page * page_size).clamp(0.0, max)
+}
+
+/// Move part of the way from `offset` to `target`, for a spring-like motion.
+fn approach(offset: CGFloat, target: CGFloat, dt: CGFloat) -> CGFloat {
+    target + (offset - target) * (-dt * SPRING_RATE).exp()
+}
+
+/// Advance the motion of released content along one axis by `dt` seconds.
+/// Returns the new offset and velocity, and whether the motion has finished.
+fn step_axis(
+    offset: CGFloat,
+    velocity: CGFloat,
+    target: Option<CGFloat>,
+    max: CGFloat,
+    bounces: bool,
+    dt: CGFloat,
+) -> (CGFloat, CGFloat, bool) {
+    if let Some(target) = target {
+        let offset = approach(offset, target, dt);
+        return if (offset - target).abs() < 0.5 {
+            (target, 0.0, true)
+        } else {
+            (offset, 0.0, false)
+        };
+    }
+
+    let offset = offset + velocity * dt;
+    let velocity = velocity * DECELERATION_RATE.powf(dt * 1000.0);
+    let edge = offset.clamp(0.0, max);
+    if offset != edge {
+        if !bounces {
+            return (edge, 0.0, true);
+        }
+        // Past the edge, the content slows down quickly and springs back.
+        let velocity = velocity * (-dt * BOUNCE_DAMPING).exp();
+        let offset = approach(offset, edge, dt);
+        return if (offset - edge).abs() < 0.5 && velocity.abs() < MIN_VELOCITY {
+            (edge, 0.0, true)
+        } else {
+            (offset, velocity, false)
+        };
+    }
+    if velocity.abs() < MIN_VELOCITY {
+        (offset, 0.0, true)
+    } else {
+        (offset, velocity, false)
+    }
+}
+
+fn start_moving(env: &mut Environment, scroll_view: id) {
+    let now = env.clock.now();
+    env.objc
+        .borrow_mut::<UIScrollViewHostObject>(scroll_view)
+        .last_step = Some(now);
+    // Moving for one step can finish immediately, e.g. if there is nothing to
+    // do.
+    if step(env, scroll_view, now, 0.0) {
+        return;
+    }
+    retain(env, scroll_view);
+    env.framework_state
+        .uikit
+        .ui_view
+        .ui_scroll_view
+        .moving
+        .push(scroll_view);
+}
+
+fn stop_moving(env: &mut Environment, scroll_view: id) {
+    let host_object = env.objc.borrow_mut::<UIScrollViewHostObject>(scroll_view);
+    host_object.last_step = None;
+    host_object.target_offset = None;
+    host_object.velocity = CGPoint { x: 0.0, y: 0.0 };
+    let moving = &mut env.framework_state.uikit.ui_view.ui_scroll_view.moving;
+    if let Some(idx) = moving.iter().position(|&view| view == scroll_view) {
+        moving.remove(idx);
+        release(env, scroll_view);
+    }
+}
+
+/// Advance a scroll view's motion to `now`. Returns [true] if it has
+/// finished.
+fn step(env: &mut Environment, scroll_view: id, now: Instant, dt: CGFloat) -> bool {
+    let offset: CGPoint = msg![env; scroll_view contentOffset];
+    let (max_x, max_y) = max_offset(env, scroll_view);
+    let host_object = env.objc.borrow_mut::<UIScrollViewHostObject>(scroll_view);
+    let bounces = host_object.bounces;
+    let target = host_object.target_offset;
+    let (x, velocity_x, done_x) = step_axis(
+        offset.x,
+        host_object.velocity.x,
+        target.map(|target| target.x),
+        max_x,
+        bounces,
+        dt,
+    );
+    let (y, velocity_y, done_y) = step_axis(
+        offset.y,
+        host_object.velocity.y,
+        target.map(|target| target.y),
+        max_y,
+        bounces,
+        dt,
+    );
+    host_object.velocity = CGPoint {
+        x: velocity_x,
+        y: velocity_y,
+    };
+    let done = done_x && done_y;
+    if done {
+        host_object.last_step = None;
+        host_object.target_offset = None;
+    } else {
+        host_object.last_step = Some(now);
+    }
+    () = msg![env; scroll_view setContentOffset:(CGPoint { x, y })];
+    done
+}
+
+/// For use by [crate::frameworks::uikit::handle_events]: move the content of
+/// scroll views that were released while moving.
+///
+/// Returns the time this should be called again, if any.
+pub(in crate::frameworks::uikit) fn update_scroll_views(env: &mut Environment) -> Option<Instant> {
+    let moving = env
+        .framework_state
+        .uikit
+        .ui_view
+        .ui_scroll_view
+        .moving
+        .clone();
+    if moving.is_empty() {
+        return None;
+    }
+    let now = env.clock.now();
+    for scroll_view in moving {
+        let Some(last_step) = env
+            .objc
+            .borrow::<UIScrollViewHostObject>(scroll_view)
+            .last_step
+        else {
+            continue;
+        };
+        let dt = now.saturating_duration_since(last_step).as_secs_f32();
+        if step(env, scroll_view, now, dt) {
+            // The delegate might start another movement, so this one has to be
+            // over first. That could release the last reference though.
+            retain(env, scroll_view);
+            stop_moving(env, scroll_view);
+            send_to_delegate(env, scroll_view, "scrollViewDidEndDecelerating:");
+            release(env, scroll_view);
+        }
+    }
+    if env
+        .framework_state
+        .uikit
+        .ui_view
+        .ui_scroll_view
+        .moving
+        .is_empty()
+    {
+        None
+    } else {
+        Some(now + Duration::from_secs_f64(1.0 / 60.0))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_step_axis() {
+    // Momentum carries the content along and is gradually lost.
+    let (offset, velocity, done) = step_axis(100.0, 1000.0, None, 1000.0, true, 0.1);
+    assert!(offset > 100.0 && velocity < 1000.0 && velocity > 0.0 && !done);
+
+    // Without bouncing, the content stops at the edge.
+    assert_eq!(
+        step_axis(990.0, 1000.0, None, 1000.0, false, 0.1),
+        (1000.0, 0.0, true)
+    );
+
+    // With bouncing, it goes past the edge and eventually comes back.
+    let (mut offset, mut velocity, mut done) = step_axis(990.0, 1000.0, None, 1000.0, true, 0.1);
+    assert!(offset > 1000.0 && !done);
+    for _ in 0..100 {
+        (offset, velocity, done) = step_axis(offset, velocity, None, 1000.0, true, 1.0 / 60.0);
+        if done {
+            break;
+        }
+    }
+    assert_eq!((offset, velocity, done), (1000.0, 0.0, true));
+
+    // Paging snaps to the page boundary a flick is heading towards.
+    assert_eq!(page_target(330.0, 500.0, 320.0, 960.0), 640.0);
+    assert_eq!(page_target(330.0, 0.0, 320.0, 960.0), 320.0);
+    assert_eq!(page_target(950.0, 500.0, 320.0, 960.0), 960.0);
+}
@@ -0,0 +1,368 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITextView`.
+//!
+//! Text input works like for `UITextField`, see
+//! [super::ui_control::ui_text_field].
+
+use super::ui_control::ui_text_field::{last_character_range, replace_range};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::cg_context::CGContextSetRGBFillColor;
+use crate::frameworks::core_graphics::{CGFloat, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str, to_rust_string};
+use crate::frameworks::foundation::{NSRange, NSUInteger};
+use crate::frameworks::uikit::ui_color;
+use crate::frameworks::uikit::ui_font::{
+    UILineBreakModeWordWrap, UITextAlignment, UITextAlignmentLeft,
+};
+use crate::frameworks::uikit::ui_graphics::UIGraphicsGetCurrentContext;
+use crate::objc::{
+    id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes, release,
+    retain, ClassExports, NSZonePtr,
+};
+use crate::Environment;
+
+pub const UITextViewTextDidBeginEditingNotification: &str =
+    "UITextViewTextDidBeginEditingNotification";
+pub const UITextViewTextDidChangeNotification: &str = "UITextViewTextDidChangeNotification";
+pub const UITextViewTextDidEndEditingNotification: &str = "UITextViewTextDidEndEditingNotification";
+
+/// `NSNotificationName` values.
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UITextViewTextDidBeginEditingNotification",
+        HostConstant::NSString(UITextViewTextDidBeginEditingNotification),
+    ),
+    (
+        "_UITextViewTextDidChangeNotification",
+        HostConstant::NSString(UITextViewTextDidChangeNotification),
+    ),
+    (
+        "_UITextViewTextDidEndEditingNotification",
+        HostConstant::NSString(UITextViewTextDidEndEditingNotification),
+    ),
+];
+
+pub struct UITextViewHostObject {
+    superclass: super::UIViewHostObject,
+    /// `NSString*`
+    text: id,
+    /// `UIFont*`
+    font: id,
+    /// `UIColor*`
+    text_color: id,
+    text_alignment: UITextAlignment,
+    editable: bool,
+    /// Weak reference to something implementing `UITextViewDelegate`.
+    delegate: id,
+}
+impl_HostObject_with_superclass!(UITextViewHostObject);
+impl Default for UITextViewHostObject {
+    fn default() -> Self {
+        UITextViewHostObject {
+            superclass: Default::default(),
+            text: nil,
+            font: nil,
+            text_color: nil,
+            text_alignment: UITextAlignmentLeft,
+            editable: true,
+            delegate: nil,
+        }
+    }
+}
+
+fn delegate_responds(env: &mut Environment, delegate: id, selector: &str) -> bool {
+    delegate != nil
+        && env
+            .objc
+            .object_has_method_named(&env.mem, delegate, selector)
+}
+
+fn post_notification(env: &mut Environment, this: id, name: &'static str) {
+    let name = get_static_str(env, name);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    let _: () = msg![env; center postNotificationName:name object:this];
+}
+
+/// Replace part of the text for text input, if the delegate allows it.
+fn replace_text(env: &mut Environment, this: id, range: NSRange, replacement: id) {
+    let NSRange { location, length } = range;
+    let delegate = env.objc.borrow::<UITextViewHostObject>(this).delegate;
+    if delegate_responds(
+        env,
+        delegate,
+        "textView:shouldChangeTextInRange:replacementText:",
+    ) {
+        let range = NSRange { location, length };
+        let allowed: bool = msg![env; delegate textView:this
+                                 shouldChangeTextInRange:range
+                                         replacementText:replacement];
+        if !allowed {
+            return;
+        }
+    }
+
+    let text: id = msg![env; this text];
+    let text = to_rust_string(env, text);
+    let replacement = to_rust_string(env, replacement);
+    let new_text = replace_range(&text, NSRange { location, length }, &replacement);
+    let new_text = from_rust_string(env, new_text);
+    () = msg![env; this setText:new_text];
+    release(env, new_text);
+
+    if delegate_responds(env, delegate, "textViewDidChange:") {
+        () = msg![env; delegate textViewDidChange:this];
+    }
+    post_notification(env, this, UITextViewTextDidChangeNotification);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// TODO: This should be a subclass of UIScrollView, and support scrolling.
+@implementation UITextView: UIView
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UITextViewHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithFrame:(CGRect)frame {
+    let this: id = msg_super![env; this initWithFrame:frame];
+    // These aren't redundant, the setters fetch the real defaults.
+    () = msg![env; this setText:nil];
+    () = msg![env; this setFont:nil];
+    () = msg![env; this setTextColor:nil];
+    this
+}
+
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    let this: id = msg_super![env; this initWithCoder:coder];
+    // TODO: decode the font and text color
+    () = msg![env; this setFont:nil];
+    () = msg![env; this setTextColor:nil];
+
+    let key_ns_string = get_static_str(env, "UIText");
+    let text: id = msg![env; coder decodeObjectForKey:key_ns_string];
+    () = msg![env; this setText:text];
+
+    let key_ns_string = get_static_str(env, "UIEditable");
+    if msg![env; coder containsValueForKey:key_ns_string] {
+        let editable: bool = msg![env; coder decodeBoolForKey:key_ns_string];
+        () = msg![env; this setEditable:editable];
+    }
+
+    this
+}
+
+- (())dealloc {
+    let &UITextViewHostObject {
+        text,
+        font,
+        text_color,
+        ..
+    } = env.objc.borrow(this);
+    release(env, text);
+    release(env, font);
+    release(env, text_color);
+    msg_super![env; this dealloc]
+}
+
+- (id)text {
+    env.objc.borrow::<UITextViewHostObject>(this).text
+}
+- (())setText:(id)new_text { // NSString*
+    // The text is never nil.
+    let new_text: id = if new_text == nil {
+        get_static_str(env, "")
+    } else {
+        new_text
+    };
+    let new_text: id = msg![env; new_text copy];
+    let old_text = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextViewHostObject>(this).text,
+        new_text
+    );
+    release(env, old_text);
+
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (id)font {
+    env.objc.borrow::<UITextViewHostObject>(this).font
+}
+- (())setFont:(id)new_font { // UIFont*
+    let new_font: id = if new_font == nil {
+        // reset to default
+        let size: CGFloat = 12.0;
+        msg_class![env; UIFont systemFontOfSize:size]
+    } else {
+        new_font
+    };
+
+    let old_font = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextViewHostObject>(this).font,
+        new_font
+    );
+    retain(env, new_font);
+    release(env, old_font);
+
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (id)textColor {
+    env.objc.borrow::<UITextViewHostObject>(this).text_color
+}
+- (())setTextColor:(id)new_text_color { // UIColor*
+    let new_text_color: id = if new_text_color == nil {
+        msg_class![env; UIColor blackColor]
+    } else {
+        new_text_color
+    };
+
+    let old_text_color = std::mem::replace(
+        &mut env.objc.borrow_mut::<UITextViewHostObject>(this).text_color,
+        new_text_color
+    );
+    retain(env, new_text_color);
+    release(env, old_text_color);
+
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (UITextAlignment)textAlignment {
+    env.objc.borrow::<UITextViewHostObject>(this).text_alignment
+}
+- (())setTextAlignment:(UITextAlignment)text_alignment {
+    env.objc.borrow_mut::<UITextViewHostObject>(this).text_alignment = text_alignment;
+    () = msg![env; this setNeedsDisplay];
+}
+
+- (bool)isEditable {
+    env.objc.borrow::<UITextViewHostObject>(this).editable
+}
+- (())setEditable:(bool)editable {
+    env.objc.borrow_mut::<UITextViewHostObject>(this).editable = editable;
+    if !editable {
+        let _: bool = msg![env; this resignFirstResponder];
+    }
+}
+
+- (id)delegate {
+    env.objc.borrow::<UITextViewHostObject>(this).delegate
+}
+// weak/non-retaining
+- (())setDelegate:(id)delegate { // something implementing UITextViewDelegate
+    env.objc.borrow_mut::<UITextViewHostObject>(this).delegate = delegate;
+}
+
+// UIResponder overrides
+
+- (bool)canBecomeFirstResponder {
+    msg![env; this isEditable]
+}
+
+- (bool)becomeFirstResponder {
+    if msg![env; this isFirstResponder] {
+        return true;
+    }
+    let delegate = env.objc.borrow::<UITextViewHostObject>(this).delegate;
+    if delegate_responds(env, delegate, "textViewShouldBeginEditing:") {
+        let should_begin: bool = msg![env; delegate textViewShouldBeginEditing:this];
+        if !should_begin {
+            return false;
+        }
+    }
+    if !msg_super![env; this becomeFirstResponder] {
+        return false;
+    }
+
+    if delegate_responds(env, delegate, "textViewDidBeginEditing:") {
+        () = msg![env; delegate textViewDidBeginEditing:this];
+    }
+    post_notification(env, this, UITextViewTextDidBeginEditingNotification);
+    true
+}
+
+- (bool)resignFirstResponder {
+    if !msg![env; this isFirstResponder] {
+        return true;
+    }
+    let delegate = env.objc.borrow::<UITextViewHostObject>(this).delegate;
+    if delegate_responds(env, delegate, "textViewShouldEndEditing:") {
+        let should_end: bool = msg![env; delegate textViewShouldEndEditing:this];
+        if !should_end {
+            return false;
+        }
+    }
+    if !msg_super![env; this resignFirstResponder] {
+        return false;
+    }
+
+    if delegate_responds(env, delegate, "textViewDidEndEditing:") {
+        () = msg![env; delegate textViewDidEndEditing:this];
+    }
+    post_notification(env, this, UITextViewTextDidEndEditingNotification);
+    true
+}
+
+- (())touchesEnded:(id)_touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    // Tapping a text view starts editing it.
+    let _: bool = msg![env; this becomeFirstResponder];
+}
+
+// UIKeyInput implementation
+
+- (bool)hasText {
+    let text: id = msg![env; this text];
+    let length: NSUInteger = msg![env; text length];
+    length != 0
+}
+
+- (())insertText:(id)new_text { // NSString*
+    // Unlike for text fields, the return key just inserts a newline.
+    let text: id = msg![env; this text];
+    let length: NSUInteger = msg![env; text length];
+    replace_text(env, this, NSRange { location: length, length: 0 }, new_text);
+}
+
+- (())deleteBackward {
+    let text: id = msg![env; this text];
+    let Some(range) = last_character_range(&to_rust_string(env, text)) else {
+        return;
+    };
+    let empty = get_static_str(env, "");
+    replace_text(env, this, range, empty);
+}
+
+- (())drawRect:(CGRect)_rect {
+    // TODO: draw a caret while editing
+    let bounds: CGRect = msg![env; this bounds];
+    let context = UIGraphicsGetCurrentContext(env);
+
+    let &UITextViewHostObject {
+        text,
+        font,
+        text_color,
+        text_alignment,
+        ..
+    } = env.objc.borrow(this);
+
+    let (r, g, b, a) = ui_color::get_rgba(&env.objc, text_color);
+    CGContextSetRGBFillColor(env, context, r, g, b, a);
+
+    let _size: CGSize = msg![env; text drawInRect:bounds
+                                         withFont:font
+                                    lineBreakMode:UILineBreakModeWordWrap
+                                        alignment:text_alignment];
+}
+
+@end
+
+};
@@ -5,8 +5,13 @@
  */
 //! `UIWindow`.
 
-use crate::frameworks::core_graphics::CGRect;
-use crate::objc::{id, msg, msg_super, objc_classes, ClassExports};
+use crate::frameworks::core_graphics::{CGFloat, CGRect};
+use crate::objc::{id, msg, msg_class, msg_super, objc_classes, ClassExports};
+use crate::Environment;
+use std::collections::HashMap;
+
+#[allow(non_camel_case_types)]
+type UIWindowLevel = CGFloat;
 
 #[derive(Default)]
 pub struct State {
@@ -14,6 +19,23 @@ pub struct State {
     ///
     /// This is public because Core Animation also uses it.
     pub visible_windows: Vec<id>,
+    /// Window levels of windows that don't have the default level
+    /// (`UIWindowLevelNormal`, 0.0). Non-retaining!
+    window_levels: HashMap<id, UIWindowLevel>,
+}
+
+/// Get the visible window that is on top, i.e. the one with the highest window
+/// level. If several windows have the same level, the one most recently made
+/// visible is on top.
+pub fn top_visible_window(env: &Environment) -> Option<id> {
+    let state = &env.framework_state.uikit.ui_view.ui_window;
+    let level = |window: &id| state.window_levels.get(window).copied().unwrap_or(0.0);
+    // max_by() returns the last element if several are equally maximum.
+    state.visible_windows.iter().copied().max_by(|a, b| {
+        level(a)
+            .partial_cmp(&level(b))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    })
 }
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -64,9 +86,28 @@ pub const CLASSES: ClassExports = objc_classes! {
             visible_list,
         );
     }
+    env.framework_state
+        .uikit
+        .ui_view
+        .ui_window
+        .window_levels
+        .remove(&this);
     msg_super![env; this dealloc]
 }
 
+- (UIWindowLevel)windowLevel {
+    let levels = &env.framework_state.uikit.ui_view.ui_window.window_levels;
+    levels.get(&this).copied().unwrap_or(0.0)
+}
+- (())setWindowLevel:(UIWindowLevel)level {
+    let levels = &mut env.framework_state.uikit.ui_view.ui_window.window_levels;
+    if level == 0.0 {
+        levels.remove(&this);
+    } else {
+        levels.insert(this, level);
+    }
+}
+
 - (())setHidden:(bool)is_hidden {
     let was_hidden: bool = msg![env; this isHidden];
     () = msg_super![env; this setHidden:is_hidden];
@@ -90,6 +131,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
+- (id)nextResponder {
+    msg_class![env; UIApplication sharedApplication]
+}
+
 - (())makeKeyAndVisible {
     // TODO: Set the "key" window once it's relevant. We don't currently have
     // send any non-touch events to windows, so there's no meaning in it yet.
@@ -5,11 +5,45 @@
  */
 //! `UIViewController`.
 
-use crate::objc::{id, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr};
+use super::ui_application::{
+    interface_orientation_is_supported, status_bar_orientation, UIInterfaceOrientation,
+    UIInterfaceOrientationLandscapeLeft, UIInterfaceOrientationLandscapeRight,
+    UIInterfaceOrientationPortrait, UIInterfaceOrientationPortraitUpsideDown,
+};
+use super::ui_view::ui_window::top_visible_window;
+use super::ui_view::UIViewHostObject;
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    CGAffineTransform, CGAffineTransformIdentity,
+};
+use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::NSTimeInterval;
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::Environment;
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+#[derive(Default)]
+pub struct State {
+    /// The controller of each view that has one. Non-retaining!
+    controllers_by_view: HashMap<id, id>,
+}
+
+/// Get the view controller that manages a view, if any.
+pub(super) fn controller_for_view(env: &Environment, view: id) -> Option<id> {
+    env.framework_state
+        .uikit
+        .ui_view_controller
+        .controllers_by_view
+        .get(&view)
+        .copied()
+}
 
 #[derive(Default)]
 struct UIViewControllerHostObject {
     view: id,
+    wants_full_screen_layout: bool,
 }
 impl HostObject for UIViewControllerHostObject {}
 
@@ -25,8 +59,15 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (())dealloc {
-    let &UIViewControllerHostObject { view } = env.objc.borrow(this);
+    let &UIViewControllerHostObject { view, .. } = env.objc.borrow(this);
 
+    if view != nil {
+        env.framework_state
+            .uikit
+            .ui_view_controller
+            .controllers_by_view
+            .remove(&view);
+    }
     release(env, view);
 
     env.objc.dealloc_object(this, &mut env.mem);
@@ -35,6 +76,13 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())setView:(id)new_view { // UIView*
     let host_obj = env.objc.borrow_mut::<UIViewControllerHostObject>(this);
     let old_view = std::mem::replace(&mut host_obj.view, new_view);
+    let controllers = &mut env.framework_state.uikit.ui_view_controller.controllers_by_view;
+    if old_view != nil {
+        controllers.remove(&old_view);
+    }
+    if new_view != nil {
+        controllers.insert(new_view, this);
+    }
     retain(env, new_view);
     release(env, old_view);
 }
@@ -44,10 +92,158 @@ pub const CLASSES: ClassExports = objc_classes! {
     view
 }
 
+- (id)nextResponder {
+    let view = env.objc.borrow::<UIViewControllerHostObject>(this).view;
+    if view == nil {
+        nil
+    } else {
+        msg![env; view superview]
+    }
+}
+
+- (bool)wantsFullScreenLayout {
+    env.objc.borrow::<UIViewControllerHostObject>(this).wants_full_screen_layout
+}
+- (())setWantsFullScreenLayout:(bool)wants_full_screen_layout {
+    env.objc.borrow_mut::<UIViewControllerHostObject>(this).wants_full_screen_layout =
+        wants_full_screen_layout;
+}
+
 - (())setEditing:(bool)editing {
     log!("TODO: [(UIViewController*){:?} setEditing:{}]", this, editing); // TODO
 }
 
+- (UIInterfaceOrientation)interfaceOrientation {
+    status_bar_orientation(env)
+}
+- (bool)shouldAutorotateToInterfaceOrientation:(UIInterfaceOrientation)orientation {
+    orientation == UIInterfaceOrientationPortrait
+}
+- (())willRotateToInterfaceOrientation:(UIInterfaceOrientation)_orientation
+                              duration:(NSTimeInterval)_duration {
+}
+- (())didRotateFromInterfaceOrientation:(UIInterfaceOrientation)_orientation {
+}
+
 @end
 
 };
+
+/// Get the view controller in charge of a window, and its view. Like in
+/// iPhone OS 2 and 3, this is the one whose view is the backmost subview of the
+/// window.
+fn window_controller(env: &Environment, window: id) -> Option<(id, id)> {
+    let controllers = &env
+        .framework_state
+        .uikit
+        .ui_view_controller
+        .controllers_by_view;
+    env.objc
+        .borrow::<UIViewHostObject>(window)
+        .subviews
+        .iter()
+        .find_map(|view| controllers.get(view).map(|&controller| (*view, controller)))
+}
+
+/// For use by the compositor and touch handling: how far the contents of a
+/// window are moved to keep them clear of the status bar.
+///
+/// The real UIKit places the view of a view controller whose
+/// `wantsFullScreenLayout` is `NO` (the default) in the application frame,
+/// below the status bar. Here, the window's contents are moved instead, if the
+/// app left that view at the window's origin. Views the app positioned itself,
+/// e.g. using `applicationFrame`, aren't affected.
+pub fn status_bar_layout_offset(env: &mut Environment, window: id) -> CGPoint {
+    let no_offset = CGPoint { x: 0.0, y: 0.0 };
+    if env.framework_state.uikit.ui_application.status_bar_hidden {
+        return no_offset;
+    }
+    let Some((view, controller)) = window_controller(env, window) else {
+        return no_offset;
+    };
+    if env
+        .objc
+        .borrow::<UIViewControllerHostObject>(controller)
+        .wants_full_screen_layout
+    {
+        return no_offset;
+    }
+    let view_frame: CGRect = msg![env; view frame];
+    if view_frame.origin != no_offset {
+        return no_offset;
+    }
+    let screen: id = msg_class![env; UIScreen mainScreen];
+    let application_frame: CGRect = msg![env; screen applicationFrame];
+    application_frame.origin
+}
+
+/// For use by [super::ui_device::handle_rotation]: rotate the interface to a
+/// new orientation, if the app supports it.
+///
+/// The view controller that gets to decide is the one found by
+/// [window_controller].
+pub(super) fn autorotate(env: &mut Environment, orientation: UIInterfaceOrientation) {
+    let old_orientation = status_bar_orientation(env);
+    if orientation == old_orientation || !interface_orientation_is_supported(env, orientation) {
+        return;
+    }
+
+    let Some(window) = top_visible_window(env) else {
+        return;
+    };
+    let Some((view, controller)) = window_controller(env, window) else {
+        return;
+    };
+
+    let should_rotate: bool =
+        msg![env; controller shouldAutorotateToInterfaceOrientation:orientation];
+    if !should_rotate {
+        return;
+    }
+    log_dbg!(
+        "Rotating interface from orientation {} to {} for {:?}",
+        old_orientation,
+        orientation,
+        controller
+    );
+
+    // TODO: animate the rotation
+    let duration: NSTimeInterval = 0.0;
+    () = msg![env; controller willRotateToInterfaceOrientation:orientation
+                                                      duration:duration];
+
+    let app: id = msg_class![env; UIApplication sharedApplication];
+    () = msg![env; app setStatusBarOrientation:orientation];
+
+    // The window itself isn't rotated, so the view is rotated within it to
+    // fill the application frame.
+    let (transform, is_landscape) = match orientation {
+        UIInterfaceOrientationPortraitUpsideDown => (CGAffineTransform::rotation(PI), false),
+        UIInterfaceOrientationLandscapeLeft => (CGAffineTransform::rotation(-FRAC_PI_2), true),
+        UIInterfaceOrientationLandscapeRight => (CGAffineTransform::rotation(FRAC_PI_2), true),
+        _ => (CGAffineTransformIdentity, false),
+    };
+    let screen: id = msg_class![env; UIScreen mainScreen];
+    let frame: CGRect = msg![env; screen applicationFrame];
+    let size = if is_landscape {
+        CGSize {
+            width: frame.size.height,
+            height: frame.size.width,
+        }
+    } else {
+        frame.size
+    };
+    let bounds = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size,
+    };
+    let center = CGPoint {
+        x: frame.origin.x + frame.size.width / 2.0,
+        y: frame.origin.y + frame.size.height / 2.0,
+    };
+    () = msg![env; view setTransform:transform];
+    () = msg![env; view setBounds:bounds];
+    () = msg![env; view setCenter:center];
+
+    () = msg![env; controller didRotateFromInterfaceOrientation:old_orientation];
+}
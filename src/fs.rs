@@ -403,6 +403,16 @@ impl Seek for GuestFile {
     }
 }
 
+/// Like [std::fs::Metadata] but for the guest filesystem.
+#[derive(Debug)]
+pub struct GuestMetadata {
+    pub is_dir: bool,
+    pub size: u64,
+    /// Only known for files and directories that exist in the host
+    /// filesystem, i.e. not for the contents of a `.ipa` file.
+    pub modified: Option<std::time::SystemTime>,
+}
+
 /// The type that owns the guest filesystem and provides accessors for it.
 #[derive(Debug)]
 pub struct Fs {
@@ -423,8 +433,8 @@ impl Fs {
     ///
     /// The `bundle_id` argument should be some value that uniquely identifies
     /// the app. This will be used to construct the host path for the app's
-    /// sandbox directory, where documents can be stored. A directory will be
-    /// created at that path if it does not already exist.
+    /// sandbox directory, where documents and preferences can be stored.
+    /// Directories will be created at that path if they do not already exist.
     pub fn new(
         app_bundle: BundleData,
         bundle_dir_name: String,
@@ -437,16 +447,25 @@ impl Fs {
 
         let bundle_guest_path = home_directory.join(&bundle_dir_name);
 
-        let documents_host_path = paths::user_data_base_path()
+        let sandbox_host_path = paths::user_data_base_path()
             .join(paths::SANDBOX_DIR)
-            .join(bundle_id)
-            .join("Documents");
+            .join(bundle_id);
+        let documents_host_path = sandbox_host_path.join("Documents");
         if let Err(e) = std::fs::create_dir_all(&documents_host_path) {
             panic!(
                 "Could not create documents directory for app at {:?}: {:?}",
                 documents_host_path, e
             );
         }
+        // Library/Preferences is where CFPreferences and NSUserDefaults store
+        // the app's settings.
+        let library_host_path = sandbox_host_path.join("Library");
+        if let Err(e) = std::fs::create_dir_all(library_host_path.join("Preferences")) {
+            panic!(
+                "Could not create preferences directory for app at {:?}: {:?}",
+                library_host_path, e
+            );
+        }
 
         // Some Free Software libraries are bundled with touchHLE.
         use paths::DYLIBS_DIR;
@@ -482,6 +501,13 @@ impl Fs {
                                         /* writeable: */ true,
                                     ),
                                 ),
+                                (
+                                    "Library".to_string(),
+                                    FsNode::from_host_dir(
+                                        &library_host_path,
+                                        /* writeable: */ true,
+                                    ),
+                                ),
                             ]),
                             writeable: None,
                         },
@@ -557,7 +583,59 @@ impl Fs {
         matches!(self.lookup_node(path), Some(FsNode::File { .. }))
     }
 
-    #[allow(dead_code)]
+    /// Like [Path::is_dir] but for the guest filesystem.
+    pub fn is_dir(&self, path: &GuestPath) -> bool {
+        matches!(self.lookup_node(path), Some(FsNode::Directory { .. }))
+    }
+
+    /// Like [std::fs::metadata] but for the guest filesystem.
+    pub fn metadata<P: AsRef<GuestPath>>(&self, path: P) -> Result<GuestMetadata, ()> {
+        let path = path.as_ref();
+        let node = self.lookup_node(path).ok_or(())?;
+        let host_metadata = |host_path: &Path| match std::fs::metadata(host_path) {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+                log!(
+                    "Warning: couldn't get metadata for {:?} (host path: {:?}): {}",
+                    path,
+                    host_path,
+                    e
+                );
+                None
+            }
+        };
+        match node {
+            FsNode::File { location, .. } => {
+                let metadata = match location {
+                    FileLocation::Path(host_path) => host_metadata(host_path),
+                    FileLocation::IpaFileRef(_) | FileLocation::ResourceFilePath(_) => None,
+                };
+                let size = match metadata {
+                    Some(ref metadata) => metadata.len(),
+                    // Files inside a .ipa or bundled with touchHLE have no
+                    // host metadata, but their size can still be found.
+                    None => self
+                        .open(path)?
+                        .seek(std::io::SeekFrom::End(0))
+                        .map_err(|_| ())?,
+                };
+                Ok(GuestMetadata {
+                    is_dir: false,
+                    size,
+                    modified: metadata.and_then(|metadata| metadata.modified().ok()),
+                })
+            }
+            FsNode::Directory { writeable, .. } => {
+                let metadata = writeable.as_deref().and_then(host_metadata);
+                Ok(GuestMetadata {
+                    is_dir: true,
+                    size: metadata.as_ref().map_or(0, |metadata| metadata.len()),
+                    modified: metadata.and_then(|metadata| metadata.modified().ok()),
+                })
+            }
+        }
+    }
+
     /// Get an iterator over the names of files/directories in a directory.
     pub fn enumerate<P: AsRef<GuestPath>>(
         &self,
@@ -23,15 +23,20 @@
 //! - Various modules provide implementations:
 //!   - [gles1_native] passes through native OpenGL ES 1.1.
 //!   - [gles1_on_gl2] provides an implementation of OpenGL ES 1.1 using OpenGL
-//!     2.1 compatibility profile.
+//!     2.1 compatibility profile. It also provides OpenGL ES 2.0.
 //!   - There might be more in future.
 //! - [gles11_raw] provides raw bindings for OpenGL ES 1.1 generated from the
 //!   Khronos API headers. **The function bindings are only for use within this
 //!   module.** The constants and types can be used outside it, however.
 //!   - [gl21compat_raw] is the same thing, but for OpenGL 2.1 compatibility
 //!     profile, which can't be used outside this module at all.
+//!   - [gles20_raw] is the same thing, but for OpenGL ES 2.0. Only its
+//!     constants are used.
 //! - [present] provides utilities for presenting frames to the window using an
 //!   abstract OpenGL ES implementation.
+//! - [debug_overlay] draws performance statistics on top of presented frames.
+//! - [texture_memory] provides accounting of texture memory, so textures can
+//!   be evicted when there are too many.
 //!
 //! In contrast, [crate::frameworks::opengles] is a layer specific to OpenGL
 //! ES's role as a part of the iPhone OS API surface. It wraps [gles_generic] to
@@ -45,6 +50,11 @@
 //!   - [IMG_texture_compression_pvrtc](https://registry.khronos.org/OpenGL/extensions/IMG/IMG_texture_compression_pvrtc.txt)
 //!   - [OES_compressed_paletted_texture](https://registry.khronos.org/OpenGL/extensions/OES/OES_compressed_paletted_texture.txt) (also incorporated into the main spec)
 //!
+//! Useful resources for OpenGL ES 2.0:
+//! - [Reference pages](https://registry.khronos.org/OpenGL-Refpages/es2.0/xhtml/)
+//! - [Specification](https://registry.khronos.org/OpenGL/specs/es/2.0/es_full_spec_2.0.pdf)
+//! - [GLSL ES 1.00 specification](https://registry.khronos.org/OpenGL/specs/es/2.0/GLSL_ES_Specification_1.00.pdf)
+//!
 //! Useful resources for OpenGL 2.1:
 //! - [Reference pages](https://registry.khronos.org/OpenGL-Refpages/gl2.1/)
 //! - [Specification](https://registry.khronos.org/OpenGL/specs/gl/glspec21.pdf)
@@ -56,14 +66,17 @@
 //!   - [EXT_texture_filter_anisotropic](https://registry.khronos.org/OpenGL/extensions/EXT/EXT_texture_filter_anisotropic.txt)
 //!   - [EXT_texture_lod_bias](https://registry.khronos.org/OpenGL/extensions/EXT/EXT_texture_lod_bias.txt)
 
+pub mod debug_overlay;
 pub mod gles1_native;
 pub mod gles1_on_gl2;
 mod gles_generic;
 pub mod present;
+pub mod texture_memory;
 mod util;
 
 use touchHLE_gl_bindings::gl21compat as gl21compat_raw;
 pub use touchHLE_gl_bindings::gles11 as gles11_raw;
+pub use touchHLE_gl_bindings::gles20 as gles20_raw;
 
 use gles1_native::GLES1Native;
 use gles1_on_gl2::GLES1OnGL2;
@@ -80,6 +93,8 @@ pub enum GLESImplementation {
 impl GLESImplementation {
     /// List of OpenGL ES 1.1 implementations in order of preference.
     pub const GLES1_IMPLEMENTATIONS: &[Self] = &[Self::GLES1Native, Self::GLES1OnGL2];
+    /// List of OpenGL ES 2.0 implementations in order of preference.
+    pub const GLES2_IMPLEMENTATIONS: &[Self] = &[Self::GLES1OnGL2];
     /// Convert from short name used for command-line arguments. Returns [Err]
     /// if name is not recognized..
     pub fn from_short_name(name: &str) -> Result<Self, ()> {
@@ -120,19 +135,31 @@ pub fn create_gles1_ctx(
     } else {
         GLESImplementation::GLES1_IMPLEMENTATIONS
     };
-    let mut gles1_ctx = None;
+    try_implementations(window, list).expect("Couldn't create OpenGL ES 1.1 context!")
+}
+
+/// Try to create an OpenGL ES 2.0 context, panicking on failure.
+pub fn create_gles2_ctx(window: &mut crate::window::Window) -> Box<dyn GLES> {
+    log!("Creating an OpenGL ES 2.0 context:");
+    try_implementations(window, GLESImplementation::GLES2_IMPLEMENTATIONS)
+        .expect("Couldn't create OpenGL ES 2.0 context!")
+}
+
+fn try_implementations(
+    window: &mut crate::window::Window,
+    list: &[GLESImplementation],
+) -> Option<Box<dyn GLES>> {
     for implementation in list {
         log!("Trying: {}", implementation.description());
         match implementation.construct(window) {
             Ok(ctx) => {
                 log!("=> Success!");
-                gles1_ctx = Some(ctx);
-                break;
+                return Some(ctx);
             }
             Err(err) => {
                 log!("=> Failed: {}.", err);
             }
         }
     }
-    gles1_ctx.expect("Couldn't create OpenGL ES 1.1 context!")
+    None
 }
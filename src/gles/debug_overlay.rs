@@ -0,0 +1,242 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The debugging overlay (toggled with F11), which shows performance
+//! statistics on top of the app's output.
+//!
+//! The statistics are moving averages over the frames presented in the last
+//! second, so they don't jump around too much to be read.
+
+use super::gles11_raw as gles11; // constants and types only
+use super::GLES;
+use crate::font::{Font, TextAlignment};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The period statistics are averaged over.
+const SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+/// How often the text is updated.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+/// Font size in pixels (not points, the overlay isn't part of the app).
+const FONT_SIZE: f32 = 13.0;
+/// Space around the text, in pixels.
+const PADDING: u32 = 4;
+
+/// Statistics about a single presented frame.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameStats {
+    /// How long Core Animation composition took, or [None] if the frame
+    /// wasn't composited (e.g. the app presented directly, or the previous
+    /// frame was reused).
+    pub composite_time: Option<Duration>,
+    /// Number of Core Animation layers drawn by the compositor.
+    pub layers_drawn: u32,
+    /// Number of draw calls, including both the app's and the compositor's.
+    pub draw_calls: u32,
+    /// Estimated texture memory in use in bytes, including both the app's and
+    /// the compositor's textures.
+    pub texture_memory: usize,
+}
+
+#[derive(Default)]
+pub struct DebugOverlay {
+    samples: VecDeque<(Instant, FrameStats)>,
+    /// Loaded on first use, since the overlay may never be drawn.
+    font: Option<Font>,
+    /// The text last displayed, drawn as RGBA pixels with bottom-to-top rows,
+    /// and when it was drawn.
+    drawn: Option<(Instant, Vec<u8>, (u32, u32))>,
+}
+
+impl DebugOverlay {
+    /// Record a frame that has just been presented.
+    pub fn record_frame(&mut self, now: Instant, stats: FrameStats) {
+        self.samples.push_back((now, stats));
+        while let Some(&(time, _)) = self.samples.front() {
+            if now.duration_since(time) <= SAMPLE_PERIOD {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Produce the text to display from the recorded frames.
+    fn text(&self) -> String {
+        let frames = self.samples.len() as u32;
+        let per_frame = |total: u32| {
+            if frames == 0 {
+                0.0
+            } else {
+                total as f64 / frames as f64
+            }
+        };
+        let composite_times: Vec<Duration> = self
+            .samples
+            .iter()
+            .filter_map(|(_, stats)| stats.composite_time)
+            .collect();
+        let composite_time = if composite_times.is_empty() {
+            "n/a".to_string()
+        } else {
+            let total: Duration = composite_times.iter().sum();
+            let average = total / composite_times.len() as u32;
+            format!("{:.2} ms", average.as_secs_f64() * 1000.0)
+        };
+        let layers_drawn = self.samples.iter().map(|(_, s)| s.layers_drawn).sum();
+        let draw_calls = self.samples.iter().map(|(_, s)| s.draw_calls).sum();
+        // This isn't averaged: it changes rarely and the latest value matters.
+        let texture_memory = self.samples.back().map_or(0, |(_, s)| s.texture_memory);
+        format!(
+            "FPS: {}\nComposite: {}\nLayers: {:.1}\nDraw calls: {:.1}\nTextures: {:.1} MiB",
+            frames,
+            composite_time,
+            per_frame(layers_drawn),
+            per_frame(draw_calls),
+            texture_memory as f64 / (1024.0 * 1024.0)
+        )
+    }
+
+    /// Draw the overlay in the top-left corner of the viewport the frame was
+    /// presented in (see [super::present::present_frame]).
+    ///
+    /// The provided context must be current and the default framebuffer must
+    /// be bound. The texture binding, viewport, blending, color and vertex
+    /// arrays are modified, so the caller may need to restore them.
+    pub unsafe fn draw(&mut self, gles: &mut dyn GLES, viewport: (u32, u32, u32, u32)) {
+        use gles11::types::*;
+
+        let now = Instant::now();
+        let stale = match self.drawn {
+            Some((drawn_at, _, _)) => now.duration_since(drawn_at) >= REFRESH_INTERVAL,
+            None => true,
+        };
+        if stale {
+            let text = self.text();
+            let font = self.font.get_or_insert_with(Font::sans_bold);
+            let (pixels, size) = draw_text_pixels(font, &text);
+            self.drawn = Some((now, pixels, size));
+        }
+        let (_, ref pixels, (width, height)) = *self.drawn.as_ref().unwrap();
+
+        // The texture is only used for one frame, so it's not worth keeping
+        // around. That also means this works with any context.
+        let mut texture = 0;
+        gles.GenTextures(1, &mut texture);
+        gles.BindTexture(gles11::TEXTURE_2D, texture);
+        gles.TexImage2D(
+            gles11::TEXTURE_2D,
+            0,
+            gles11::RGBA as _,
+            width as _,
+            height as _,
+            0,
+            gles11::RGBA,
+            gles11::UNSIGNED_BYTE,
+            pixels.as_ptr() as *const _,
+        );
+        gles.TexParameteri(
+            gles11::TEXTURE_2D,
+            gles11::TEXTURE_MIN_FILTER,
+            gles11::NEAREST as _,
+        );
+        gles.TexParameteri(
+            gles11::TEXTURE_2D,
+            gles11::TEXTURE_MAG_FILTER,
+            gles11::NEAREST as _,
+        );
+
+        let (vx, vy, vw, vh) = viewport;
+        let (width, height) = (width.min(vw), height.min(vh));
+        gles.Viewport(vx as _, (vy + vh - height) as _, width as _, height as _);
+
+        gles.Color4f(1.0, 1.0, 1.0, 1.0);
+        gles.Enable(gles11::BLEND);
+        gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
+
+        gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+        let vertices: [f32; 8] = [-1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0];
+        gles.EnableClientState(gles11::VERTEX_ARRAY);
+        gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+        let tex_coords: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        gles.EnableClientState(gles11::TEXTURE_COORD_ARRAY);
+        gles.TexCoordPointer(2, gles11::FLOAT, 0, tex_coords.as_ptr() as *const GLvoid);
+        gles.Enable(gles11::TEXTURE_2D);
+        gles.DrawArrays(gles11::TRIANGLE_FAN, 0, 4);
+
+        gles.Disable(gles11::BLEND);
+        gles.DeleteTextures(1, &texture);
+    }
+}
+
+/// Draw white text on a translucent black background, producing premultiplied
+/// RGBA pixels with bottom-to-top rows, as OpenGL ES expects.
+fn draw_text_pixels(font: &Font, text: &str) -> (Vec<u8>, (u32, u32)) {
+    let (text_width, text_height) = font.calculate_text_size(FONT_SIZE, text, None, None);
+    let width = text_width.ceil() as u32 + PADDING * 2;
+    let height = text_height.ceil() as u32 + PADDING * 2;
+
+    let background = [0.0, 0.0, 0.0, 0.6];
+    let mut pixels: Vec<f32> = background
+        .iter()
+        .copied()
+        .cycle()
+        .take((width * height * 4) as usize)
+        .collect();
+    font.draw(
+        FONT_SIZE,
+        text,
+        (PADDING as f32, PADDING as f32),
+        None,
+        None,
+        TextAlignment::Left,
+        |(x, y), coverage| {
+            if x < 0 || y < 0 || x as u32 >= width || y as u32 >= height {
+                return;
+            }
+            // The font code's y co-ordinates point downwards.
+            let row = height - 1 - y as u32;
+            let idx = ((row * width + x as u32) * 4) as usize;
+            for pixel in &mut pixels[idx..idx + 4] {
+                *pixel = coverage + *pixel * (1.0 - coverage);
+            }
+        },
+    );
+
+    let pixels = pixels
+        .into_iter()
+        .map(|c| (c * 255.0).round() as u8)
+        .collect();
+    (pixels, (width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moving_average() {
+        let mut overlay = DebugOverlay::default();
+        let start = Instant::now();
+        // 90 frames at 50Hz, half of them composited.
+        for i in 0..90u32 {
+            let composited = i % 2 == 0;
+            overlay.record_frame(
+                start + Duration::from_millis(i as u64 * 20),
+                FrameStats {
+                    composite_time: composited.then_some(Duration::from_millis(2)),
+                    layers_drawn: if composited { 10 } else { 0 },
+                    draw_calls: 3,
+                    texture_memory: i as usize * 1024 * 1024,
+                },
+            );
+        }
+        // Only the last second's frames are kept.
+        assert_eq!(overlay.samples.len(), 51);
+        assert_eq!(
+            overlay.text(),
+            "FPS: 51\nComposite: 2.00 ms\nLayers: 4.9\nDraw calls: 3.0\nTextures: 89.0 MiB"
+        );
+    }
+}
@@ -14,13 +14,17 @@
 
 use super::gles11_raw as gles11;
 use super::gles11_raw::types::*;
-use super::util::{try_decode_pvrtc, PalettedTextureFormat};
+use super::util::{pvrtc_format_is_2bit, PalettedTextureFormat, PvrtcDecodeCache};
 use super::GLES;
 use crate::window::{GLContext, GLVersion, Window};
 use std::ffi::CStr;
 
 pub struct GLES1Native {
     gl_ctx: GLContext,
+    /// Whether the driver supports `IMG_texture_compression_pvrtc`. This is
+    /// checked the first time it's needed, because the context must be current.
+    supports_pvrtc: Option<bool>,
+    pvrtc_decode_cache: PvrtcDecodeCache,
 }
 impl GLES for GLES1Native {
     fn description() -> &'static str {
@@ -30,6 +34,8 @@ impl GLES for GLES1Native {
     fn new(window: &mut Window) -> Result<Self, String> {
         Ok(Self {
             gl_ctx: window.create_gl_context(GLVersion::GLES11)?,
+            supports_pvrtc: None,
+            pvrtc_decode_cache: PvrtcDecodeCache::default(),
         })
     }
 
@@ -388,25 +394,38 @@ impl GLES for GLES1Native {
     ) {
         let data = unsafe { std::slice::from_raw_parts(data.cast::<u8>(), image_size as usize) };
         // IMG_texture_compression_pvrtc (only on Imagination/Apple GPUs)
-        // TODO: It would be more efficient to use hardware decoding where
-        // available (I just don't have a suitable device to try this on)
-        if try_decode_pvrtc(
-            self,
-            target,
-            level,
-            internalformat,
-            width,
-            height,
-            border,
-            data,
-        ) {
+        // Hardware decoding is used where available, which is more efficient.
+        let is_pvrtc = pvrtc_format_is_2bit(internalformat).is_some();
+        let supports_pvrtc = *self.supports_pvrtc.get_or_insert_with(|| {
+            let extensions = CStr::from_ptr(gles11::GetString(gles11::EXTENSIONS) as *const _);
+            extensions
+                .to_string_lossy()
+                .split(' ')
+                .any(|name| name == "GL_IMG_texture_compression_pvrtc")
+        });
+        if is_pvrtc && !supports_pvrtc {
+            let pixels = self
+                .pvrtc_decode_cache
+                .try_decode(internalformat, width, height, border, data)
+                .unwrap();
             log_dbg!("Decoded PVRTC");
+            gles11::TexImage2D(
+                target,
+                level,
+                gles11::RGBA as _,
+                width,
+                height,
+                border,
+                gles11::RGBA,
+                gles11::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            );
             return;
         }
 
         // OES_compressed_paletted_texture is in the common profile of OpenGL ES
         // 1.1, so we can reasonably assume it's supported.
-        if PalettedTextureFormat::get_info(internalformat).is_none() {
+        if !is_pvrtc && PalettedTextureFormat::get_info(internalformat).is_none() {
             unimplemented!("CompressedTexImage2D internalformat: {:#x}", internalformat);
         }
         log_dbg!("Directly supported texture format: {:#x}", internalformat);
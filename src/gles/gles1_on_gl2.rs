@@ -17,17 +17,21 @@
 //! OpenGL 2.1 is the latest version that has a compatibility profile available
 //! on macOS. It's also a version supported on various other OSes.
 //! It is therefore a convenient target for our implementation.
+//!
+//! OpenGL 2.1 also has shaders, so this is the implementation used for OpenGL
+//! ES 2.0 contexts too. GLSL ES 1.00 shaders are mostly compatible with GLSL
+//! 1.20 and need only light translation (see [translate_shader_source]).
 
 use super::gl21compat_raw as gl21;
 use super::gl21compat_raw::types::*;
 use super::gles11_raw as gles11; // constants only
 use super::util::{
-    fixed_to_float, matrix_fixed_to_float, try_decode_pvrtc, PalettedTextureFormat, ParamTable,
-    ParamType,
+    fixed_to_float, matrix_fixed_to_float, PalettedTextureFormat, ParamTable, ParamType,
+    PvrtcDecodeCache,
 };
 use super::GLES;
 use crate::window::{GLContext, GLVersion, Window};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CStr;
 
 /// List of capabilities shared by OpenGL ES 1.1 and OpenGL 2.1.
@@ -90,6 +94,17 @@ struct ArrayStateBackup {
     pointer: *const GLvoid,
 }
 
+/// A generic vertex attribute array with fixed-point data, as specified by the
+/// app. OpenGL 2.1 doesn't have `GL_FIXED`, so translation is deferred until
+/// draw call.
+#[derive(Copy, Clone)]
+struct FixedPointAttrib {
+    size: GLint,
+    stride: GLsizei,
+    pointer: *const GLvoid,
+    buffer_binding: GLuint,
+}
+
 /// List of arrays shared by OpenGL ES 1.1 and OpenGL 2.1.
 ///
 /// TODO: GL_POINT_SIZE_ARRAY_OES?
@@ -264,6 +279,12 @@ const GET_PARAMS: ParamTable = ParamTable(&[
     (gl21::VERTEX_ARRAY_SIZE, ParamType::Int, 1),
     (gl21::VERTEX_ARRAY_STRIDE, ParamType::Int, 1),
     (gl21::VERTEX_ARRAY_TYPE, ParamType::Int, 1),
+    // OpenGL ES 2.0
+    (gl21::CURRENT_PROGRAM, ParamType::Int, 1),
+    (gl21::MAX_COMBINED_TEXTURE_IMAGE_UNITS, ParamType::Int, 1),
+    (gl21::MAX_TEXTURE_IMAGE_UNITS, ParamType::Int, 1),
+    (gl21::MAX_VERTEX_ATTRIBS, ParamType::Int, 1),
+    (gl21::MAX_VERTEX_TEXTURE_IMAGE_UNITS, ParamType::Int, 1),
     // OES_framebuffer_object -> EXT_framebuffer_object
     (gl21::FRAMEBUFFER_BINDING_EXT, ParamType::Int, 1),
     (gl21::RENDERBUFFER_BINDING_EXT, ParamType::Int, 1),
@@ -348,8 +369,15 @@ pub struct GLES1OnGL2 {
     pointer_is_fixed_point: [bool; ARRAYS.len()],
     fixed_point_texture_units: HashSet<GLenum>,
     fixed_point_translation_buffers: [Vec<GLfloat>; ARRAYS.len()],
+    fixed_point_attribs: HashMap<GLuint, FixedPointAttrib>,
+    fixed_point_attrib_translation_buffers: HashMap<GLuint, Vec<GLfloat>>,
+    pvrtc_decode_cache: PvrtcDecodeCache,
 }
 impl GLES1OnGL2 {
+    fn has_fixed_point_data(&self) -> bool {
+        self.pointer_is_fixed_point.iter().any(|&is_fixed| is_fixed)
+            || !self.fixed_point_attribs.is_empty()
+    }
     /// If any arrays with fixed-point data are in use at the time of a draw
     /// call, this function will convert the data to floating-point and
     /// replace the pointers. [Self::restore_fixed_point_arrays] can be called
@@ -520,6 +548,106 @@ impl GLES1OnGL2 {
             }
         }
     }
+    /// Like [Self::translate_fixed_point_arrays], but for generic vertex
+    /// attribute arrays. Returns the indices of the translated arrays, to be
+    /// passed to [Self::restore_fixed_point_attribs].
+    unsafe fn translate_fixed_point_attribs(
+        &mut self,
+        first: GLint,
+        count: GLsizei,
+    ) -> Vec<GLuint> {
+        assert!(first >= 0 && count >= 0);
+        let first = first as usize;
+        let count = count as usize;
+
+        let mut old_buffer_binding = 0;
+        gl21::GetIntegerv(gl21::ARRAY_BUFFER_BINDING, &mut old_buffer_binding);
+
+        let mut translated = Vec::new();
+        for (&index, attrib) in self.fixed_point_attribs.iter() {
+            let mut is_enabled = 0;
+            gl21::GetVertexAttribiv(index, gl21::VERTEX_ATTRIB_ARRAY_ENABLED, &mut is_enabled);
+            if is_enabled == 0 {
+                continue;
+            }
+
+            assert!(attrib.size >= 1 && attrib.size <= 4 && attrib.stride >= 0);
+            let size = attrib.size as usize;
+            let stride = if attrib.stride == 0 {
+                // tightly packed mode
+                size * 4 // sizeof(GLfixed)
+            } else {
+                attrib.stride as usize
+            };
+
+            // Data in a buffer object has to be read back first.
+            let mut buffer_data = Vec::<u8>::new();
+            let first_vector_ptr: *const GLvoid = if attrib.buffer_binding != 0 {
+                if count > 0 {
+                    let offset = attrib.pointer as usize + first * stride;
+                    let len = (count - 1) * stride + size * 4;
+                    buffer_data.resize(len, 0);
+                    gl21::BindBuffer(gl21::ARRAY_BUFFER, attrib.buffer_binding);
+                    gl21::GetBufferSubData(
+                        gl21::ARRAY_BUFFER,
+                        offset as GLintptr,
+                        len as GLsizeiptr,
+                        buffer_data.as_mut_ptr().cast(),
+                    );
+                }
+                buffer_data.as_ptr().cast()
+            } else {
+                attrib.pointer.add(first * stride)
+            };
+
+            let buffer = self
+                .fixed_point_attrib_translation_buffers
+                .entry(index)
+                .or_default();
+            buffer.clear();
+            buffer.resize((first + count) * size, 0.0);
+            for j in 0..count {
+                let vector_ptr: *const GLvoid = first_vector_ptr.add(j * stride);
+                let vector_ptr: *const GLfixed = vector_ptr.cast();
+                for k in 0..size {
+                    buffer[(first + j) * size + k] =
+                        fixed_to_float(vector_ptr.add(k).read_unaligned());
+                }
+            }
+
+            // Client-side arrays can only be specified with no buffer bound.
+            gl21::BindBuffer(gl21::ARRAY_BUFFER, 0);
+            gl21::VertexAttribPointer(
+                index,
+                attrib.size,
+                gl21::FLOAT,
+                gl21::FALSE,
+                0,
+                buffer.as_ptr().cast(),
+            );
+            translated.push(index);
+        }
+
+        gl21::BindBuffer(gl21::ARRAY_BUFFER, old_buffer_binding as GLuint);
+        translated
+    }
+    unsafe fn restore_fixed_point_attribs(&mut self, translated: Vec<GLuint>) {
+        let mut old_buffer_binding = 0;
+        gl21::GetIntegerv(gl21::ARRAY_BUFFER_BINDING, &mut old_buffer_binding);
+        for index in translated {
+            let attrib = self.fixed_point_attribs[&index];
+            gl21::BindBuffer(gl21::ARRAY_BUFFER, attrib.buffer_binding);
+            gl21::VertexAttribPointer(
+                index,
+                attrib.size,
+                gl21::FLOAT,
+                gl21::FALSE,
+                attrib.stride,
+                attrib.pointer,
+            );
+        }
+        gl21::BindBuffer(gl21::ARRAY_BUFFER, old_buffer_binding as GLuint);
+    }
 }
 impl GLES for GLES1OnGL2 {
     fn description() -> &'static str {
@@ -532,6 +660,9 @@ impl GLES for GLES1OnGL2 {
             pointer_is_fixed_point: [false; ARRAYS.len()],
             fixed_point_texture_units: HashSet::new(),
             fixed_point_translation_buffers: [Vec::new(), Vec::new(), Vec::new(), Vec::new()],
+            fixed_point_attribs: HashMap::new(),
+            fixed_point_attrib_translation_buffers: HashMap::new(),
+            pvrtc_decode_cache: PvrtcDecodeCache::default(),
         })
     }
 
@@ -924,10 +1055,12 @@ impl GLES for GLES1OnGL2 {
         .contains(&mode));
 
         let state_backup = self.translate_fixed_point_arrays(first, count);
+        let attribs_backup = self.translate_fixed_point_attribs(first, count);
 
         gl21::DrawArrays(mode, first, count);
 
         self.restore_fixed_point_arrays(state_backup);
+        self.restore_fixed_point_attribs(attribs_backup);
     }
     unsafe fn DrawElements(
         &mut self,
@@ -948,7 +1081,7 @@ impl GLES for GLES1OnGL2 {
         .contains(&mode));
         assert!(type_ == gl21::UNSIGNED_BYTE || type_ == gl21::UNSIGNED_SHORT);
 
-        let state_backup = if self.pointer_is_fixed_point.iter().any(|&is_fixed| is_fixed) {
+        let state_backup = if self.has_fixed_point_data() {
             // Scan the index buffer to find the range of data that may need
             // fixed-point translation.
             // TODO: Would it be more efficient to turn this into a non-indexed
@@ -995,15 +1128,19 @@ impl GLES for GLES1OnGL2 {
                 )
             };
 
-            Some(self.translate_fixed_point_arrays(first, count))
+            Some((
+                self.translate_fixed_point_arrays(first, count),
+                self.translate_fixed_point_attribs(first, count),
+            ))
         } else {
             None
         };
 
         gl21::DrawElements(mode, count, type_, indices);
 
-        if let Some(state_backup) = state_backup {
+        if let Some((state_backup, attribs_backup)) = state_backup {
             self.restore_fixed_point_arrays(state_backup);
+            self.restore_fixed_point_attribs(attribs_backup);
         }
     }
 
@@ -1208,19 +1345,22 @@ impl GLES for GLES1OnGL2 {
     ) {
         let data = unsafe { std::slice::from_raw_parts(data.cast::<u8>(), image_size as usize) };
         // IMG_texture_compression_pvrtc (only on Imagination/Apple GPUs)
-        // TODO: It would be more efficient to use hardware decoding where
-        // available (I just don't have a suitable device to try this on)
-        if try_decode_pvrtc(
-            self,
-            target,
-            level,
-            internalformat,
-            width,
-            height,
-            border,
-            data,
-        ) {
+        if let Some(pixels) = self
+            .pvrtc_decode_cache
+            .try_decode(internalformat, width, height, border, data)
+        {
             log_dbg!("Decoded PVRTC");
+            gl21::TexImage2D(
+                target,
+                level,
+                gl21::RGBA as _,
+                width,
+                height,
+                border,
+                gl21::RGBA,
+                gl21::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            )
         // OES_compressed_paletted_texture is only in OpenGL ES, so we'll need
         // to decompress those formats.
         } else if let Some(PalettedTextureFormat {
@@ -1587,4 +1727,265 @@ impl GLES for GLES1OnGL2 {
     unsafe fn GenerateMipmapOES(&mut self, target: GLenum) {
         gl21::GenerateMipmapEXT(target)
     }
+
+    // OpenGL ES 2.0
+    unsafe fn CreateShader(&mut self, type_: GLenum) -> GLuint {
+        assert!(type_ == gl21::VERTEX_SHADER || type_ == gl21::FRAGMENT_SHADER);
+        gl21::CreateShader(type_)
+    }
+    unsafe fn DeleteShader(&mut self, shader: GLuint) {
+        gl21::DeleteShader(shader)
+    }
+    unsafe fn ShaderSource(
+        &mut self,
+        shader: GLuint,
+        count: GLsizei,
+        string: *const *const GLchar,
+        length: *const GLint,
+    ) {
+        let mut source = Vec::new();
+        for i in 0..usize::try_from(count).unwrap() {
+            let string = *string.add(i);
+            let length = if length.is_null() { -1 } else { *length.add(i) };
+            if length < 0 {
+                source.extend_from_slice(CStr::from_ptr(string).to_bytes());
+            } else {
+                let string = std::slice::from_raw_parts(string as *const u8, length as usize);
+                source.extend_from_slice(string);
+            }
+        }
+        let source = translate_shader_source(&String::from_utf8_lossy(&source));
+        log_dbg!("Translated shader {} source: {:?}", shader, source);
+        let string = source.as_ptr() as *const GLchar;
+        let length = source.len() as GLint;
+        gl21::ShaderSource(shader, 1, &string, &length)
+    }
+    unsafe fn CompileShader(&mut self, shader: GLuint) {
+        gl21::CompileShader(shader)
+    }
+    unsafe fn GetShaderiv(&mut self, shader: GLuint, pname: GLenum, params: *mut GLint) {
+        assert!([
+            gl21::SHADER_TYPE,
+            gl21::DELETE_STATUS,
+            gl21::COMPILE_STATUS,
+            gl21::INFO_LOG_LENGTH,
+            gl21::SHADER_SOURCE_LENGTH,
+        ]
+        .contains(&pname));
+        gl21::GetShaderiv(shader, pname, params)
+    }
+    unsafe fn GetShaderInfoLog(
+        &mut self,
+        shader: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        gl21::GetShaderInfoLog(shader, buf_size, length, info_log)
+    }
+    unsafe fn CreateProgram(&mut self) -> GLuint {
+        gl21::CreateProgram()
+    }
+    unsafe fn DeleteProgram(&mut self, program: GLuint) {
+        gl21::DeleteProgram(program)
+    }
+    unsafe fn AttachShader(&mut self, program: GLuint, shader: GLuint) {
+        gl21::AttachShader(program, shader)
+    }
+    unsafe fn DetachShader(&mut self, program: GLuint, shader: GLuint) {
+        gl21::DetachShader(program, shader)
+    }
+    unsafe fn BindAttribLocation(&mut self, program: GLuint, index: GLuint, name: *const GLchar) {
+        gl21::BindAttribLocation(program, index, name)
+    }
+    unsafe fn LinkProgram(&mut self, program: GLuint) {
+        gl21::LinkProgram(program)
+    }
+    unsafe fn ValidateProgram(&mut self, program: GLuint) {
+        gl21::ValidateProgram(program)
+    }
+    unsafe fn UseProgram(&mut self, program: GLuint) {
+        gl21::UseProgram(program)
+    }
+    unsafe fn GetProgramiv(&mut self, program: GLuint, pname: GLenum, params: *mut GLint) {
+        assert!([
+            gl21::DELETE_STATUS,
+            gl21::LINK_STATUS,
+            gl21::VALIDATE_STATUS,
+            gl21::INFO_LOG_LENGTH,
+            gl21::ATTACHED_SHADERS,
+            gl21::ACTIVE_ATTRIBUTES,
+            gl21::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+            gl21::ACTIVE_UNIFORMS,
+            gl21::ACTIVE_UNIFORM_MAX_LENGTH,
+        ]
+        .contains(&pname));
+        gl21::GetProgramiv(program, pname, params)
+    }
+    unsafe fn GetProgramInfoLog(
+        &mut self,
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        gl21::GetProgramInfoLog(program, buf_size, length, info_log)
+    }
+    unsafe fn GetAttribLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        gl21::GetAttribLocation(program, name)
+    }
+    unsafe fn GetUniformLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        gl21::GetUniformLocation(program, name)
+    }
+    unsafe fn Uniform1i(&mut self, location: GLint, v0: GLint) {
+        gl21::Uniform1i(location, v0)
+    }
+    unsafe fn Uniform1f(&mut self, location: GLint, v0: GLfloat) {
+        gl21::Uniform1f(location, v0)
+    }
+    unsafe fn Uniform2f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat) {
+        gl21::Uniform2f(location, v0, v1)
+    }
+    unsafe fn Uniform3f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+        gl21::Uniform3f(location, v0, v1, v2)
+    }
+    unsafe fn Uniform4f(
+        &mut self,
+        location: GLint,
+        v0: GLfloat,
+        v1: GLfloat,
+        v2: GLfloat,
+        v3: GLfloat,
+    ) {
+        gl21::Uniform4f(location, v0, v1, v2, v3)
+    }
+    unsafe fn Uniformiv(
+        &mut self,
+        size: GLint,
+        location: GLint,
+        count: GLsizei,
+        value: *const GLint,
+    ) {
+        match size {
+            1 => gl21::Uniform1iv(location, count, value),
+            2 => gl21::Uniform2iv(location, count, value),
+            3 => gl21::Uniform3iv(location, count, value),
+            4 => gl21::Uniform4iv(location, count, value),
+            _ => unreachable!(),
+        }
+    }
+    unsafe fn Uniformfv(
+        &mut self,
+        size: GLint,
+        location: GLint,
+        count: GLsizei,
+        value: *const GLfloat,
+    ) {
+        match size {
+            1 => gl21::Uniform1fv(location, count, value),
+            2 => gl21::Uniform2fv(location, count, value),
+            3 => gl21::Uniform3fv(location, count, value),
+            4 => gl21::Uniform4fv(location, count, value),
+            _ => unreachable!(),
+        }
+    }
+    unsafe fn UniformMatrixfv(
+        &mut self,
+        size: GLint,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        match size {
+            2 => gl21::UniformMatrix2fv(location, count, transpose, value),
+            3 => gl21::UniformMatrix3fv(location, count, transpose, value),
+            4 => gl21::UniformMatrix4fv(location, count, transpose, value),
+            _ => unreachable!(),
+        }
+    }
+    unsafe fn VertexAttribPointer(
+        &mut self,
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: GLboolean,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    ) {
+        if type_ == gles11::FIXED {
+            // Translation deferred until draw call. Fixed-point data is never
+            // normalized.
+            let mut buffer_binding = 0;
+            gl21::GetIntegerv(gl21::ARRAY_BUFFER_BINDING, &mut buffer_binding);
+            self.fixed_point_attribs.insert(
+                index,
+                FixedPointAttrib {
+                    size,
+                    stride,
+                    pointer,
+                    buffer_binding: buffer_binding as GLuint,
+                },
+            );
+            gl21::VertexAttribPointer(index, size, gl21::FLOAT, gl21::FALSE, stride, pointer)
+        } else {
+            assert!([
+                gl21::BYTE,
+                gl21::UNSIGNED_BYTE,
+                gl21::SHORT,
+                gl21::UNSIGNED_SHORT,
+                gl21::FLOAT
+            ]
+            .contains(&type_));
+            self.fixed_point_attribs.remove(&index);
+            gl21::VertexAttribPointer(index, size, type_, normalized, stride, pointer)
+        }
+    }
+    unsafe fn EnableVertexAttribArray(&mut self, index: GLuint) {
+        gl21::EnableVertexAttribArray(index)
+    }
+    unsafe fn DisableVertexAttribArray(&mut self, index: GLuint) {
+        gl21::DisableVertexAttribArray(index)
+    }
+    unsafe fn VertexAttrib4f(
+        &mut self,
+        index: GLuint,
+        x: GLfloat,
+        y: GLfloat,
+        z: GLfloat,
+        w: GLfloat,
+    ) {
+        gl21::VertexAttrib4f(index, x, y, z, w)
+    }
+}
+
+/// Translate GLSL ES 1.00 source code to GLSL 1.20.
+///
+/// The languages are close enough that this only needs to replace the
+/// `#version` directive and remove precision statements and qualifiers, which
+/// GLSL 1.20 doesn't have. Removed lines are replaced with empty lines so that
+/// line numbers in the info log stay close to the original ones.
+fn translate_shader_source(source: &str) -> String {
+    let mut translated =
+        String::from("#version 120\n#define lowp\n#define mediump\n#define highp\n");
+    for line in source.lines() {
+        let first_word = line.split_whitespace().next().unwrap_or("");
+        let is_version = first_word.starts_with("#version");
+        let is_precision = first_word == "precision" && line.trim_end().ends_with(';');
+        if !is_version && !is_precision {
+            translated.push_str(line);
+        }
+        translated.push('\n');
+    }
+    translated
+}
+
+#[cfg(test)]
+#[test]
+fn test_translate_shader_source() {
+    let source = "#version 100\nprecision mediump float;\nvarying lowp vec4 color;\nvoid main() {\n    gl_FragColor = color;\n}";
+    assert_eq!(
+        translate_shader_source(source),
+        "#version 120\n#define lowp\n#define mediump\n#define highp\n\n\nvarying lowp vec4 color;\nvoid main() {\n    gl_FragColor = color;\n}\n"
+    );
 }
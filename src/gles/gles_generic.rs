@@ -3,11 +3,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Generic OpenGL ES 1.1 interface.
+//! Generic OpenGL ES 1.1 and 2.0 interface.
 //!
 //! Unfortunately this does not provide the types and constants, so the correct
 //! usage is to import `GLES` and `types` from this module, but get the
-//! constants from [super::gles11_raw].
+//! constants from [super::gles11_raw] (or [super::gles20_raw] for those only
+//! in OpenGL ES 2.0).
 
 use super::gles11_raw::types::*;
 
@@ -323,4 +324,172 @@ pub trait GLES {
     unsafe fn DeleteFramebuffersOES(&mut self, n: GLsizei, framebuffers: *const GLuint);
     unsafe fn DeleteRenderbuffersOES(&mut self, n: GLsizei, renderbuffers: *const GLuint);
     unsafe fn GenerateMipmapOES(&mut self, target: GLenum);
+
+    // OpenGL ES 2.0 (incomplete)
+    //
+    // Only implementations listed in
+    // [super::GLESImplementation::GLES2_IMPLEMENTATIONS] need to provide these.
+    // The functions OpenGL ES 2.0 shares with OpenGL ES 1.1 or its
+    // OES_framebuffer_object extension are provided by the methods above.
+    unsafe fn CreateShader(&mut self, _type: GLenum) -> GLuint {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn DeleteShader(&mut self, _shader: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn ShaderSource(
+        &mut self,
+        _shader: GLuint,
+        _count: GLsizei,
+        _string: *const *const GLchar,
+        _length: *const GLint,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn CompileShader(&mut self, _shader: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn GetShaderiv(&mut self, _shader: GLuint, _pname: GLenum, _params: *mut GLint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn GetShaderInfoLog(
+        &mut self,
+        _shader: GLuint,
+        _buf_size: GLsizei,
+        _length: *mut GLsizei,
+        _info_log: *mut GLchar,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn CreateProgram(&mut self) -> GLuint {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn DeleteProgram(&mut self, _program: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn AttachShader(&mut self, _program: GLuint, _shader: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn DetachShader(&mut self, _program: GLuint, _shader: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn BindAttribLocation(
+        &mut self,
+        _program: GLuint,
+        _index: GLuint,
+        _name: *const GLchar,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn LinkProgram(&mut self, _program: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn ValidateProgram(&mut self, _program: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn UseProgram(&mut self, _program: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn GetProgramiv(&mut self, _program: GLuint, _pname: GLenum, _params: *mut GLint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn GetProgramInfoLog(
+        &mut self,
+        _program: GLuint,
+        _buf_size: GLsizei,
+        _length: *mut GLsizei,
+        _info_log: *mut GLchar,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn GetAttribLocation(&mut self, _program: GLuint, _name: *const GLchar) -> GLint {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn GetUniformLocation(&mut self, _program: GLuint, _name: *const GLchar) -> GLint {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn Uniform1i(&mut self, _location: GLint, _v0: GLint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn Uniform1f(&mut self, _location: GLint, _v0: GLfloat) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn Uniform2f(&mut self, _location: GLint, _v0: GLfloat, _v1: GLfloat) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn Uniform3f(&mut self, _location: GLint, _v0: GLfloat, _v1: GLfloat, _v2: GLfloat) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn Uniform4f(
+        &mut self,
+        _location: GLint,
+        _v0: GLfloat,
+        _v1: GLfloat,
+        _v2: GLfloat,
+        _v3: GLfloat,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    /// `size` is the number of components (1 to 4) of each vector, so that
+    /// this can stand in for `glUniform1iv` through `glUniform4iv`.
+    unsafe fn Uniformiv(
+        &mut self,
+        _size: GLint,
+        _location: GLint,
+        _count: GLsizei,
+        _value: *const GLint,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    /// `size` is the number of components (1 to 4) of each vector, so that
+    /// this can stand in for `glUniform1fv` through `glUniform4fv`.
+    unsafe fn Uniformfv(
+        &mut self,
+        _size: GLint,
+        _location: GLint,
+        _count: GLsizei,
+        _value: *const GLfloat,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    /// `size` is the number of columns and rows (2 to 4) of each matrix, so
+    /// that this can stand in for `glUniformMatrix2fv` through
+    /// `glUniformMatrix4fv`.
+    unsafe fn UniformMatrixfv(
+        &mut self,
+        _size: GLint,
+        _location: GLint,
+        _count: GLsizei,
+        _transpose: GLboolean,
+        _value: *const GLfloat,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn VertexAttribPointer(
+        &mut self,
+        _index: GLuint,
+        _size: GLint,
+        _type: GLenum,
+        _normalized: GLboolean,
+        _stride: GLsizei,
+        _pointer: *const GLvoid,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn EnableVertexAttribArray(&mut self, _index: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn DisableVertexAttribArray(&mut self, _index: GLuint) {
+        unimplemented!("OpenGL ES 2.0")
+    }
+    unsafe fn VertexAttrib4f(
+        &mut self,
+        _index: GLuint,
+        _x: GLfloat,
+        _y: GLfloat,
+        _z: GLfloat,
+        _w: GLfloat,
+    ) {
+        unimplemented!("OpenGL ES 2.0")
+    }
 }
@@ -8,19 +8,27 @@
 
 use super::gles11_raw as gles11; // constants and types only
 use super::GLES;
+use crate::image::encode_png;
 use crate::matrix::Matrix;
+use crate::paths;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Present the the latest frame (e.g. the app's splash screen or rendering
 /// output), provided as a texture bound to `GL_TEXTURE_2D`, by drawing it on
 /// the window. It may be rotated, scaled and/or letterboxed as necessary. The
 /// virtual cursor is also drawn if it should be currently visible.
 ///
+/// `filter` is used as the texture's minification and magnification filter,
+/// so it should be `GL_LINEAR` or `GL_NEAREST` (see
+/// [crate::window::Window::present_filter]).
+///
 /// The provided context must be current.
 pub unsafe fn present_frame(
     gles: &mut dyn GLES,
     viewport: (u32, u32, u32, u32),
     output_rotation_matrix: Matrix<2>,
     virtual_cursor_visible_at: Option<(f32, f32, bool)>,
+    filter: gles11::types::GLenum,
 ) {
     // While this is a generic utility, it is closely tied to
     // crate::frameworks::opengles::eagl::present_renderbuffer, which handles
@@ -47,6 +55,10 @@ pub unsafe fn present_frame(
     let tex_coords: [f32; 12] = [0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
     gles.EnableClientState(gles11::TEXTURE_COORD_ARRAY);
     gles.TexCoordPointer(2, gles11::FLOAT, 0, tex_coords.as_ptr() as *const GLvoid);
+    // The texture will not have any mip levels, so the filter must not use
+    // them, else rendering will fail.
+    gles.TexParameteri(gles11::TEXTURE_2D, gles11::TEXTURE_MIN_FILTER, filter as _);
+    gles.TexParameteri(gles11::TEXTURE_2D, gles11::TEXTURE_MAG_FILTER, filter as _);
     let matrix = Matrix::<4>::from(&output_rotation_matrix);
     gles.MatrixMode(gles11::TEXTURE);
     gles.LoadMatrixf(matrix.columns().as_ptr() as *const _);
@@ -79,3 +91,62 @@ pub unsafe fn present_frame(
         gles.DrawArrays(gles11::TRIANGLES, 0, 6);
     }
 }
+
+/// Read back the part of the default framebuffer that [present_frame] drew the
+/// frame to, e.g. for taking a screenshot. The result is RGBA pixels (8 bits
+/// per channel) with rows in top-to-bottom order, and its size is the size of
+/// the viewport, so it includes any scaling (e.g. the scale hack).
+///
+/// The provided context must be current and the default framebuffer must be
+/// bound. This should be called after [present_frame] and before swapping.
+pub unsafe fn read_presented_frame(
+    gles: &mut dyn GLES,
+    viewport: (u32, u32, u32, u32),
+) -> (Vec<u8>, (u32, u32)) {
+    use gles11::types::*;
+
+    let (x, y, width, height) = viewport;
+    let row_size = width as usize * 4;
+    let mut pixels = vec![0u8; row_size * height as usize];
+    gles.ReadPixels(
+        x as _,
+        y as _,
+        width as _,
+        height as _,
+        gles11::RGBA,
+        gles11::UNSIGNED_BYTE,
+        pixels.as_mut_ptr() as *mut GLvoid,
+    );
+    // OpenGL ES's rows are bottom-to-top.
+    let pixels = pixels.chunks(row_size).rev().flatten().copied().collect();
+    (pixels, (width, height))
+}
+
+/// Save the frame drawn by [present_frame] to a PNG file named after the
+/// current time, e.g. `screenshot-1690000000000.png`. See
+/// [read_presented_frame] for the requirements.
+pub unsafe fn save_screenshot(gles: &mut dyn GLES, viewport: (u32, u32, u32, u32)) {
+    let (mut pixels, size) = read_presented_frame(gles, viewport);
+    // The window's alpha channel isn't meaningful.
+    for pixel in pixels.chunks_mut(4) {
+        pixel[3] = 255;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let path = paths::user_data_base_path().join(format!("screenshot-{}.png", timestamp));
+    match encode_png(&pixels, size)
+        .and_then(|png| std::fs::write(&path, png).map_err(|e| e.to_string()))
+    {
+        Ok(()) => echo!("Saved screenshot to {}", path.display()),
+        Err(e) => {
+            log!(
+                "Warning: couldn't save screenshot to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
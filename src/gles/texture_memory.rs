@@ -0,0 +1,120 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Accounting of texture memory, with least-recently-used eviction.
+//!
+//! This doesn't make any OpenGL ES calls itself. Whatever owns the textures
+//! records their allocation and use, and deletes the textures it is told to
+//! evict, which it must be able to regenerate later when they're next needed.
+//! The sizes are estimates, since the driver may pad or convert texture data.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+pub struct TextureMemory<K> {
+    /// Size in bytes of each texture, and the frame it was last used in.
+    textures: HashMap<K, (usize, u64)>,
+    bytes: usize,
+    frame: u64,
+}
+
+impl<K> Default for TextureMemory<K> {
+    fn default() -> Self {
+        TextureMemory {
+            textures: HashMap::new(),
+            bytes: 0,
+            frame: 0,
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> TextureMemory<K> {
+    /// Start a new frame. Textures used from now on won't be evicted until the
+    /// next frame.
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Record that a texture's contents have been (re-)uploaded, with a size
+    /// of `bytes`. This counts as a use.
+    pub fn record_upload(&mut self, key: K, bytes: usize) {
+        if let Some((old_bytes, _)) = self.textures.insert(key, (bytes, self.frame)) {
+            self.bytes -= old_bytes;
+        }
+        self.bytes += bytes;
+    }
+
+    /// Record that a texture has been used for drawing.
+    pub fn record_use(&mut self, key: K) {
+        if let Some((_, last_used)) = self.textures.get_mut(&key) {
+            *last_used = self.frame;
+        }
+    }
+
+    /// Stop tracking a texture, e.g. because it has been deleted.
+    pub fn forget(&mut self, key: K) {
+        if let Some((bytes, _)) = self.textures.remove(&key) {
+            self.bytes -= bytes;
+        }
+    }
+
+    /// Total size of the tracked textures in bytes.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Choose textures to evict, least recently used first, until the total
+    /// size is at most `budget` bytes. Textures used in the current frame are
+    /// never chosen, so the total may still be over budget afterwards. The
+    /// chosen textures are no longer tracked and must be deleted.
+    pub fn evict_to_fit(&mut self, budget: usize) -> Vec<K> {
+        if self.bytes <= budget {
+            return Vec::new();
+        }
+        let mut candidates: Vec<(K, usize, u64)> = self
+            .textures
+            .iter()
+            .filter(|&(_, &(_, last_used))| last_used < self.frame)
+            .map(|(&key, &(bytes, last_used))| (key, bytes, last_used))
+            .collect();
+        candidates.sort_by_key(|&(_, _, last_used)| last_used);
+
+        let mut evicted = Vec::new();
+        for (key, _, _) in candidates {
+            if self.bytes <= budget {
+                break;
+            }
+            self.forget(key);
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evict_to_fit() {
+        let mut memory = TextureMemory::default();
+        memory.record_upload('a', 100);
+        memory.next_frame();
+        memory.record_upload('b', 100);
+        memory.record_upload('c', 100);
+        memory.next_frame();
+        memory.record_use('a');
+        memory.record_upload('c', 50);
+        assert_eq!(memory.bytes(), 250);
+        memory.next_frame();
+        memory.record_use('c');
+
+        // 'b' is the least recently used, then 'a'. 'c' is in use.
+        assert_eq!(memory.evict_to_fit(200), vec!['b']);
+        assert_eq!(memory.bytes(), 150);
+        assert_eq!(memory.evict_to_fit(0), vec!['a']);
+        assert_eq!(memory.bytes(), 50);
+    }
+}
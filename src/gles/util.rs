@@ -7,7 +7,7 @@
 
 use super::gles11_raw as gles11; // constants only
 use super::gles11_raw::types::{GLenum, GLfixed, GLfloat, GLint, GLsizei};
-use super::GLES;
+use std::collections::HashMap;
 
 /// Convert a fixed-point scalar to a floating-point scalar.
 ///
@@ -136,49 +136,90 @@ impl ParamTable {
     }
 }
 
-/// Helper for implementing `glCompressedTexImage2D`: if `internalformat` is
-/// one of the `IMG_texture_compression_pvrtc` formats, decode it and call
-/// `glTexImage2D`. Returns `true` if this is done.
+/// Check if `internalformat` is one of the `IMG_texture_compression_pvrtc`
+/// formats. The result is [None] if it isn't, otherwise it says whether the
+/// format is 2bpp (rather than 4bpp).
+pub fn pvrtc_format_is_2bit(internalformat: GLenum) -> Option<bool> {
+    match internalformat {
+        gles11::COMPRESSED_RGB_PVRTC_4BPPV1_IMG | gles11::COMPRESSED_RGBA_PVRTC_4BPPV1_IMG => {
+            Some(false)
+        }
+        gles11::COMPRESSED_RGB_PVRTC_2BPPV1_IMG | gles11::COMPRESSED_RGBA_PVRTC_2BPPV1_IMG => {
+            Some(true)
+        }
+        _ => None,
+    }
+}
+
+/// Cache of decoded PVRTC textures, so that apps which upload the same
+/// texture repeatedly (e.g. when switching between scenes) don't pay for the
+/// decoding each time.
 ///
-/// Note that this panics rather than create GL errors for invalid use (TODO?)
-#[allow(clippy::too_many_arguments)]
-pub fn try_decode_pvrtc(
-    gles: &mut dyn GLES,
-    target: GLenum,
-    level: GLint,
-    internalformat: GLenum,
-    width: GLsizei,
-    height: GLsizei,
-    border: GLint,
-    pvrtc_data: &[u8],
-) -> bool {
-    let is_2bit = match internalformat {
-        gles11::COMPRESSED_RGB_PVRTC_4BPPV1_IMG | gles11::COMPRESSED_RGBA_PVRTC_4BPPV1_IMG => false,
-        gles11::COMPRESSED_RGB_PVRTC_2BPPV1_IMG | gles11::COMPRESSED_RGBA_PVRTC_2BPPV1_IMG => true,
-        _ => return false,
-    };
+/// Entries are keyed by the address and size of the compressed data, but a
+/// copy of the compressed data is also kept and compared on lookup, since the
+/// app may reuse the memory for something else.
+#[derive(Default)]
+pub struct PvrtcDecodeCache {
+    entries: HashMap<PvrtcDecodeCacheKey, (Box<[u8]>, Vec<u32>)>,
+    /// Total size in bytes of the decoded data in `entries`.
+    decoded_size: usize,
+}
+type PvrtcDecodeCacheKey = (usize, usize, bool, GLsizei, GLsizei);
+impl PvrtcDecodeCache {
+    /// Limit on [Self::decoded_size]. The whole cache is emptied if it would be
+    /// exceeded.
+    const MAX_DECODED_SIZE: usize = 64 * 1024 * 1024;
 
-    assert!(border == 0);
-    let pixels = crate::image::decode_pvrtc(
-        pvrtc_data,
-        is_2bit,
-        width.try_into().unwrap(),
-        height.try_into().unwrap(),
-    );
-    unsafe {
-        gles.TexImage2D(
-            target,
-            level,
-            gles11::RGBA as _,
+    /// Helper for implementing `glCompressedTexImage2D`: if `internalformat`
+    /// is one of the `IMG_texture_compression_pvrtc` formats, decode the data
+    /// (or get the result of a previous decoding) as RGBA8 pixels, suitable
+    /// for passing to `glTexImage2D`. Returns [None] for other formats.
+    ///
+    /// Note that this panics rather than create GL errors for invalid use
+    /// (TODO?)
+    pub fn try_decode(
+        &mut self,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        pvrtc_data: &[u8],
+    ) -> Option<&[u32]> {
+        let is_2bit = pvrtc_format_is_2bit(internalformat)?;
+        assert!(border == 0);
+
+        let key = (
+            pvrtc_data.as_ptr() as usize,
+            pvrtc_data.len(),
+            is_2bit,
             width,
             height,
-            border,
-            gles11::RGBA,
-            gles11::UNSIGNED_BYTE,
-            pixels.as_ptr() as *const _,
-        )
-    };
-    true
+        );
+        let is_cached = self
+            .entries
+            .get(&key)
+            .map_or(false, |(data, _)| **data == *pvrtc_data);
+        if is_cached {
+            log_dbg!("Using cached decoded PVRTC texture");
+        } else {
+            let pixels = crate::image::decode_pvrtc(
+                pvrtc_data,
+                is_2bit,
+                width.try_into().unwrap(),
+                height.try_into().unwrap(),
+            );
+            let size = pixels.len() * 4;
+            if self.decoded_size + size > Self::MAX_DECODED_SIZE {
+                self.entries.clear();
+                self.decoded_size = 0;
+            }
+            if let Some((_, old_pixels)) = self.entries.insert(key, (pvrtc_data.into(), pixels)) {
+                self.decoded_size -= old_pixels.len() * 4;
+            }
+            self.decoded_size += size;
+        }
+        Some(&self.entries[&key].1)
+    }
 }
 
 pub struct PalettedTextureFormat {
@@ -3,26 +3,35 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Image decoding. Currently only supports PNG files (treated as 8-bit sRGB).
+//! Image decoding. Currently only supports PNG and JPEG files (treated as 8-bit
+//! sRGB).
 //!
 //! Implemented as a wrapper around the C library stb_image, since it supports
 //! "CgBI" PNG files (an Apple proprietary extension used in iPhone OS apps).
+//! PNG encoding is also supported, using stb_image_write.
 //!
 //! This module also exposes decompression for Imagination Technologies' PVRTC
 //! format, implementing as a wrapper around their decoder from the PowerVR
 //! SDK.
 
-use std::ffi::{c_int, c_uchar, CStr};
+use std::ffi::{c_int, c_uchar, c_void, CStr};
 
 use touchHLE_pvrt_decompress_wrapper::*;
 use touchHLE_stb_image_wrapper::*;
 
 pub struct Image {
-    pixels: *mut c_uchar,
+    pixels: ImagePixels,
     dimensions: (u32, u32),
 }
 
+enum ImagePixels {
+    /// Allocated by stb_image.
+    Stb(*mut c_uchar),
+    Owned(Box<[u8]>),
+}
+
 impl Image {
+    /// Decode a PNG or JPEG file. The format is detected automatically.
     pub fn from_bytes(bytes: &[u8]) -> Result<Image, String> {
         let len: c_int = bytes.len().try_into().unwrap();
 
@@ -74,11 +83,24 @@ impl Image {
         }
 
         Ok(Image {
-            pixels,
+            pixels: ImagePixels::Stb(pixels),
             dimensions: (width, height),
         })
     }
 
+    /// Construct an image from existing pixel data, in the format returned by
+    /// [Image::pixels].
+    pub fn from_pixels(pixels: Vec<u8>, dimensions: (u32, u32)) -> Image {
+        assert_eq!(
+            pixels.len(),
+            dimensions.0 as usize * dimensions.1 as usize * 4
+        );
+        Image {
+            pixels: ImagePixels::Owned(pixels.into_boxed_slice()),
+            dimensions,
+        }
+    }
+
     pub fn dimensions(&self) -> (u32, u32) {
         self.dimensions
     }
@@ -86,11 +108,14 @@ impl Image {
     /// Get image data as bytes (8 bits per channel sRGB RGBA with premultiplied
     /// alpha). Rows are in top-to-bottom order.
     pub fn pixels(&self) -> &[u8] {
-        unsafe {
-            std::slice::from_raw_parts(
-                self.pixels,
-                self.dimensions.0 as usize * self.dimensions.1 as usize * 4,
-            )
+        match self.pixels {
+            ImagePixels::Stb(pixels) => unsafe {
+                std::slice::from_raw_parts(
+                    pixels,
+                    self.dimensions.0 as usize * self.dimensions.1 as usize * 4,
+                )
+            },
+            ImagePixels::Owned(ref pixels) => pixels,
         }
     }
 
@@ -120,8 +145,40 @@ impl Image {
 
 impl Drop for Image {
     fn drop(&mut self) {
-        unsafe { stbi_image_free(self.pixels.cast()) }
+        if let ImagePixels::Stb(pixels) = self.pixels {
+            unsafe { stbi_image_free(pixels.cast()) }
+        }
+    }
+}
+
+/// Encode RGBA pixels (8 bits per channel, rows in top-to-bottom order) as a
+/// PNG file.
+pub fn encode_png(pixels: &[u8], dimensions: (u32, u32)) -> Result<Vec<u8>, String> {
+    let (width, height) = dimensions;
+    assert_eq!(pixels.len(), width as usize * height as usize * 4);
+
+    unsafe extern "C" fn write(context: *mut c_void, data: *mut c_void, size: c_int) {
+        let output = &mut *(context as *mut Vec<u8>);
+        let data = std::slice::from_raw_parts(data as *const u8, size.try_into().unwrap());
+        output.extend_from_slice(data);
+    }
+
+    let mut output: Vec<u8> = Vec::new();
+    let success = unsafe {
+        stbi_write_png_to_func(
+            write,
+            &mut output as *mut Vec<u8> as *mut c_void,
+            width.try_into().unwrap(),
+            height.try_into().unwrap(),
+            4,
+            pixels.as_ptr() as *const c_void,
+            (width * 4).try_into().unwrap(),
+        )
+    };
+    if success == 0 {
+        return Err("stbi_write_png_to_func() failed".to_string());
     }
+    Ok(output)
 }
 
 /// Approximate implementation of sRGB gamma encoding.
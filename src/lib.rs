@@ -163,7 +163,7 @@ pub fn main<T: Iterator<Item = String>>(mut args: T) -> Result<(), String> {
             let parse_result = options.parse_argument(option_arg);
             assert!(parse_result == Ok(true));
         }
-        if options.headless {
+        if options.headless || options.offscreen {
             return Err(
                 "No app specified. Use the --help flag to see command-line usage.".to_string(),
             );
@@ -11,8 +11,10 @@
 
 mod generic_char;
 
+pub mod blocks;
 pub mod ctype;
 pub mod cxxabi;
+pub mod dispatch;
 pub mod dlfcn;
 pub mod errno;
 pub mod keymgr;
@@ -33,6 +35,8 @@ pub mod wchar;
 /// Container for state of various child modules
 #[derive(Default)]
 pub struct State {
+    dispatch: dispatch::State,
+    dlfcn: dlfcn::State,
     keymgr: keymgr::State,
     posix_io: posix_io::State,
     pthread: pthread::State,
@@ -0,0 +1,386 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Blocks runtime (`Block.h`, `_Block_copy()` and friends).
+//!
+//! Resources:
+//! - The [Block Implementation Specification](https://clang.llvm.org/docs/Block-ABI-Apple.html)
+//!   from Clang, which describes the memory layout of blocks and the helper
+//!   functions the compiler generates.
+//! - Apple's open-source `libclosure`, whose `runtime.c` is the original
+//!   implementation of these functions.
+//!
+//! A block is an Objective-C object whose `isa` is one of three classes:
+//! `__NSGlobalBlock__` for blocks that don't capture anything (these are
+//! constant data in the app binary), `__NSStackBlock__` for blocks created on
+//! the stack, and `__NSMallocBlock__` for blocks that have been copied to the
+//! heap by `_Block_copy()`. The app refers to these classes by the symbols
+//! `_NSConcreteGlobalBlock` etc. Blocks don't have host objects, so their
+//! reference count is stored in the `flags` field instead, like in Apple's
+//! implementation.
+//!
+//! Host code can call a block with [get_invoke].
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::NSUInteger;
+use crate::mem::{ConstPtr, ConstVoidPtr, MutPtr, MutVoidPtr, SafeRead};
+use crate::objc::{id, nil, objc_classes, release, retain, Class, ClassExports, NSZonePtr};
+use crate::Environment;
+
+/// Layout of every block object. Captured variables follow it.
+#[repr(C, packed)]
+struct BlockLiteral {
+    _isa: Class,
+    flags: i32,
+    _reserved: i32,
+    invoke: GuestFunction,
+    descriptor: ConstPtr<BlockDescriptor>,
+}
+unsafe impl SafeRead for BlockLiteral {}
+
+/// Layout of a block descriptor if [BLOCK_HAS_COPY_DISPOSE] is set. Otherwise,
+/// only the first two fields are present.
+#[repr(C, packed)]
+struct BlockDescriptor {
+    _reserved: u32,
+    /// Size of the block including captured variables. This is read
+    /// separately, since it's always present.
+    _size: u32,
+    copy_helper: GuestFunction,
+    dispose_helper: GuestFunction,
+}
+unsafe impl SafeRead for BlockDescriptor {}
+
+/// Layout of the storage for a `__block` variable. The variable follows it.
+#[repr(C, packed)]
+struct BlockByref {
+    _isa: id,
+    /// Points to the copy on the heap, once there is one, otherwise to itself.
+    forwarding: MutPtr<BlockByref>,
+    flags: i32,
+    /// Size of the storage including the variable.
+    size: u32,
+    /// Only present if [BLOCK_HAS_COPY_DISPOSE] is set.
+    keep_helper: GuestFunction,
+    /// Only present if [BLOCK_HAS_COPY_DISPOSE] is set.
+    destroy_helper: GuestFunction,
+}
+unsafe impl SafeRead for BlockByref {}
+
+/// Size of [BlockByref] without the helpers.
+const BLOCK_BYREF_HEADER_SIZE: u32 = 16;
+
+/// The reference count, which is in units of 2. If it reaches the maximum, it
+/// is stuck there and the block is leaked.
+const BLOCK_REFCOUNT_MASK: i32 = 0xfffe;
+const BLOCK_NEEDS_FREE: i32 = 1 << 24;
+const BLOCK_HAS_COPY_DISPOSE: i32 = 1 << 25;
+const BLOCK_IS_GLOBAL: i32 = 1 << 28;
+
+// Flags for _Block_object_assign() and _Block_object_dispose().
+const BLOCK_FIELD_IS_OBJECT: i32 = 3;
+const BLOCK_FIELD_IS_BLOCK: i32 = 7;
+const BLOCK_FIELD_IS_BYREF: i32 = 8;
+const BLOCK_FIELD_IS_WEAK: i32 = 16;
+const BLOCK_FIELD_IS_WEAK_BYREF: i32 = BLOCK_FIELD_IS_BYREF | BLOCK_FIELD_IS_WEAK;
+const BLOCK_BYREF_CALLER: i32 = 128;
+
+/// Increment the reference count in `flags`, unless it is stuck at the
+/// maximum.
+fn latching_incr(flags: i32) -> i32 {
+    if flags & BLOCK_REFCOUNT_MASK == BLOCK_REFCOUNT_MASK {
+        flags
+    } else {
+        flags + 2
+    }
+}
+
+/// Decrement the reference count in `flags`, unless it is stuck at the maximum
+/// or already zero.
+fn latching_decr(flags: i32) -> i32 {
+    let refcount = flags & BLOCK_REFCOUNT_MASK;
+    if refcount == BLOCK_REFCOUNT_MASK || refcount == 0 {
+        flags
+    } else {
+        flags - 2
+    }
+}
+
+/// Pointer to the `flags` field of a block or `__block` variable storage.
+fn flags_ptr(ptr: MutVoidPtr) -> MutPtr<i32> {
+    (ptr.cast::<u32>() + 1).cast()
+}
+
+fn read_descriptor(env: &Environment, block: MutVoidPtr) -> ConstPtr<BlockDescriptor> {
+    let literal: BlockLiteral = env.mem.read(block.cast());
+    literal.descriptor
+}
+
+fn _Block_copy(env: &mut Environment, block: MutVoidPtr) -> MutVoidPtr {
+    if block.is_null() {
+        return block;
+    }
+    let literal: BlockLiteral = env.mem.read(block.cast());
+    let flags = literal.flags;
+    if flags & BLOCK_NEEDS_FREE != 0 {
+        // Already on the heap.
+        env.mem.write(flags_ptr(block), latching_incr(flags));
+        return block;
+    }
+    if flags & BLOCK_IS_GLOBAL != 0 {
+        return block;
+    }
+
+    // It's a stack block, so a heap copy must be made.
+    let descriptor = literal.descriptor;
+    // The size is the second field, which is always present.
+    let size: u32 = env.mem.read(descriptor.cast::<u32>() + 1);
+    let new = env.mem.alloc(size);
+    env.mem.memmove(new, block.cast_const(), size);
+    let class = env.objc.get_known_class("__NSMallocBlock__", &mut env.mem);
+    env.mem.write(new.cast(), class);
+    // The new block has a reference count of 1.
+    let new_flags = (flags & !BLOCK_REFCOUNT_MASK) | BLOCK_NEEDS_FREE | 2;
+    env.mem.write(flags_ptr(new), new_flags);
+    if flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+        let copy_helper = env.mem.read(descriptor).copy_helper;
+        let _: () = copy_helper.call_from_host(env, (new, block));
+    }
+    log_dbg!("_Block_copy({:?}) => {:?}", block, new);
+    new
+}
+
+fn _Block_release(env: &mut Environment, block: MutVoidPtr) {
+    if block.is_null() {
+        return;
+    }
+    let flags: i32 = env.mem.read(flags_ptr(block));
+    if flags & BLOCK_IS_GLOBAL != 0 || flags & BLOCK_NEEDS_FREE == 0 {
+        // Global or stack blocks aren't reference-counted.
+        return;
+    }
+    if flags & BLOCK_REFCOUNT_MASK == BLOCK_REFCOUNT_MASK {
+        return;
+    }
+    let new_flags = latching_decr(flags);
+    env.mem.write(flags_ptr(block), new_flags);
+    if new_flags & BLOCK_REFCOUNT_MASK != 0 {
+        return;
+    }
+    log_dbg!("_Block_release({:?}): freeing", block);
+    if flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+        let descriptor = read_descriptor(env, block);
+        let dispose_helper = env.mem.read(descriptor).dispose_helper;
+        let _: () = dispose_helper.call_from_host(env, (block,));
+    }
+    env.mem.free(block);
+}
+
+/// Copy the storage of a `__block` variable to the heap, if that hasn't already
+/// happened, and return a pointer to the heap copy.
+fn byref_copy(env: &mut Environment, src: MutPtr<BlockByref>) -> MutPtr<BlockByref> {
+    let forwarding = env.mem.read(src).forwarding;
+    let forwarding_flags: i32 = env.mem.read(flags_ptr(forwarding.cast()));
+    if forwarding_flags & BLOCK_REFCOUNT_MASK == 0 {
+        let header = env.mem.read(src);
+        let size = header.size;
+        let copy: MutPtr<BlockByref> = env.mem.alloc(size).cast();
+        env.mem.memmove(copy.cast(), src.cast().cast_const(), size);
+        // One reference for the caller, and one for the stack.
+        env.mem
+            .write(flags_ptr(copy.cast()), header.flags | BLOCK_NEEDS_FREE | 4);
+        let forwarding_ptr: MutPtr<MutPtr<BlockByref>> = (src.cast::<u32>() + 1).cast();
+        let copy_forwarding_ptr: MutPtr<MutPtr<BlockByref>> = (copy.cast::<u32>() + 1).cast();
+        env.mem.write(copy_forwarding_ptr, copy);
+        env.mem.write(forwarding_ptr, copy);
+        if header.flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+            assert!(size >= BLOCK_BYREF_HEADER_SIZE + 8);
+            let keep_helper = header.keep_helper;
+            let _: () = keep_helper.call_from_host(env, (copy, src));
+        }
+    } else if forwarding_flags & BLOCK_NEEDS_FREE != 0 {
+        env.mem.write(
+            flags_ptr(forwarding.cast()),
+            latching_incr(forwarding_flags),
+        );
+    }
+    env.mem.read(src).forwarding
+}
+
+fn byref_release(env: &mut Environment, byref: MutPtr<BlockByref>) {
+    let byref = env.mem.read(byref).forwarding;
+    let flags: i32 = env.mem.read(flags_ptr(byref.cast()));
+    if flags & BLOCK_NEEDS_FREE == 0 {
+        // Still on the stack.
+        return;
+    }
+    if flags & BLOCK_REFCOUNT_MASK == BLOCK_REFCOUNT_MASK {
+        return;
+    }
+    let new_flags = latching_decr(flags);
+    env.mem.write(flags_ptr(byref.cast()), new_flags);
+    if new_flags & BLOCK_REFCOUNT_MASK != 0 {
+        return;
+    }
+    if flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+        let destroy_helper = env.mem.read(byref).destroy_helper;
+        let _: () = destroy_helper.call_from_host(env, (byref,));
+    }
+    env.mem.free(byref.cast());
+}
+
+/// Called by the compiler-generated copy helpers of blocks and `__block`
+/// variables for each captured variable that needs special handling.
+fn _Block_object_assign(
+    env: &mut Environment,
+    dest: MutPtr<MutVoidPtr>,
+    object: MutVoidPtr,
+    flags: i32,
+) {
+    let res = match flags {
+        BLOCK_FIELD_IS_OBJECT => retain(env, object.cast()).cast(),
+        BLOCK_FIELD_IS_BLOCK => _Block_copy(env, object),
+        BLOCK_FIELD_IS_BYREF | BLOCK_FIELD_IS_WEAK_BYREF => byref_copy(env, object.cast()).cast(),
+        _ if flags & BLOCK_BYREF_CALLER != 0 => {
+            // In the helpers for __block variables, objects and blocks are just
+            // assigned, because the variable owns them.
+            object
+        }
+        _ if flags & BLOCK_FIELD_IS_WEAK != 0 => object,
+        _ => panic!("_Block_object_assign(): unexpected flags {:#x}", flags),
+    };
+    env.mem.write(dest, res);
+}
+
+/// Counterpart to [_Block_object_assign], called by the compiler-generated
+/// dispose helpers.
+fn _Block_object_dispose(env: &mut Environment, object: MutVoidPtr, flags: i32) {
+    match flags {
+        BLOCK_FIELD_IS_OBJECT => release(env, object.cast()),
+        BLOCK_FIELD_IS_BLOCK => _Block_release(env, object),
+        BLOCK_FIELD_IS_BYREF | BLOCK_FIELD_IS_WEAK_BYREF => byref_release(env, object.cast()),
+        _ if flags & (BLOCK_BYREF_CALLER | BLOCK_FIELD_IS_WEAK) != 0 => (),
+        _ => panic!("_Block_object_dispose(): unexpected flags {:#x}", flags),
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// Common superclass of the block classes, as in Apple's implementation.
+@implementation NSBlock: NSObject
+
+- (id)copy {
+    _Block_copy(env, this.cast()).cast()
+}
+- (id)copyWithZone:(NSZonePtr)_zone {
+    _Block_copy(env, this.cast()).cast()
+}
+
+- (())invoke {
+    let invoke = get_invoke(env, this);
+    let _: () = invoke.call_from_host(env, (this,));
+}
+
+@end
+
+@implementation __NSGlobalBlock__: NSBlock
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+@end
+
+// Retaining a stack block doesn't copy it, as in Apple's implementation. It
+// must be copied explicitly.
+@implementation __NSStackBlock__: NSBlock
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+@end
+
+@implementation __NSMallocBlock__: NSBlock
+- (id)retain {
+    _Block_copy(env, this.cast()).cast()
+}
+- (())release {
+    _Block_release(env, this.cast())
+}
+- (NSUInteger)retainCount {
+    let flags: i32 = env.mem.read(flags_ptr(this.cast()));
+    ((flags & BLOCK_REFCOUNT_MASK) / 2) as NSUInteger
+}
+@end
+
+};
+
+/// The `_NSConcrete...Block` symbols are the classes themselves, not pointers
+/// to them.
+fn concrete_block_class(env: &mut Environment, name: &str) -> ConstVoidPtr {
+    let class = env.objc.get_known_class(name, &mut env.mem);
+    class.cast().cast_const()
+}
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "__NSConcreteGlobalBlock",
+        HostConstant::CustomWithEnv(|env| concrete_block_class(env, "__NSGlobalBlock__")),
+    ),
+    (
+        "__NSConcreteStackBlock",
+        HostConstant::CustomWithEnv(|env| concrete_block_class(env, "__NSStackBlock__")),
+    ),
+    (
+        "__NSConcreteMallocBlock",
+        HostConstant::CustomWithEnv(|env| concrete_block_class(env, "__NSMallocBlock__")),
+    ),
+];
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(_Block_copy(_)),
+    export_c_func!(_Block_release(_)),
+    export_c_func!(_Block_object_assign(_, _, _)),
+    export_c_func!(_Block_object_dispose(_, _)),
+];
+
+/// For use by host code: get the function that implements a block. To call
+/// the block, pass the block itself as the first argument, followed by the
+/// block's own arguments:
+///
+/// ```ignore
+/// let invoke = blocks::get_invoke(env, block);
+/// let _: () = invoke.call_from_host(env, (block, argument));
+/// ```
+pub fn get_invoke(env: &Environment, block: id) -> GuestFunction {
+    assert!(block != nil);
+    let literal: BlockLiteral = env.mem.read(block.cast());
+    literal.invoke
+}
+
+/// For use by host code: copy a block to the heap, like `Block_copy()`, so it
+/// can be kept after the function it was passed to returns. The copy must be
+/// released with [release_block].
+pub fn copy_block(env: &mut Environment, block: id) -> id {
+    _Block_copy(env, block.cast()).cast()
+}
+
+/// For use by host code: release a block copied with [copy_block].
+pub fn release_block(env: &mut Environment, block: id) {
+    _Block_release(env, block.cast())
+}
+
+/// For use by the dynamic linker: get the name of the class a
+/// `_NSConcrete...Block` symbol refers to. Global blocks in the app binary
+/// refer to these via external relocations rather than symbol pointers, so
+/// [CONSTANTS] isn't enough.
+pub fn class_for_symbol(symbol: &str) -> Option<&'static str> {
+    match symbol {
+        "__NSConcreteGlobalBlock" => Some("__NSGlobalBlock__"),
+        "__NSConcreteStackBlock" => Some("__NSStackBlock__"),
+        "__NSConcreteMallocBlock" => Some("__NSMallocBlock__"),
+        _ => None,
+    }
+}
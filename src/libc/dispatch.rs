@@ -0,0 +1,257 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Grand Central Dispatch (`dispatch/dispatch.h`).
+//!
+//! Only a small part of it is implemented. There are no worker threads: blocks
+//! submitted to any queue, even the global concurrent ones, are run one at a
+//! time on the main thread, by its run loop (see [handle_main_queue]). This is
+//! enough for the most common use of GCD, which is deferring work to the main
+//! queue.
+//!
+//! Resources:
+//! - Apple's [Dispatch Queues](https://developer.apple.com/library/archive/documentation/General/Conceptual/ConcurrencyProgrammingGuide/OperationQueues/OperationQueues.html) documentation
+
+use super::blocks;
+use crate::abi::CallFromHost;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::mem::{ConstPtr, ConstVoidPtr};
+use crate::objc::{id, objc_classes, release, retain, ClassExports, HostObject};
+use crate::Environment;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[allow(non_camel_case_types)]
+type dispatch_queue_t = id;
+#[allow(non_camel_case_types)]
+type dispatch_block_t = id;
+/// Nanoseconds on the same clock as `mach_absolute_time()`.
+#[allow(non_camel_case_types)]
+type dispatch_time_t = u64;
+
+const DISPATCH_TIME_NOW: dispatch_time_t = 0;
+const DISPATCH_TIME_FOREVER: dispatch_time_t = !0;
+
+#[derive(Default)]
+pub struct State {
+    /// Strong reference that is never released, so the queue lives forever.
+    main_queue: Option<dispatch_queue_t>,
+    /// Global queues, by priority. These are created on demand, and are never
+    /// released either.
+    global_queues: HashMap<i32, dispatch_queue_t>,
+    /// Blocks waiting to be run, in the order they were submitted.
+    pending: Vec<PendingBlock>,
+}
+
+struct PendingBlock {
+    /// The time the block should be run at, see [now].
+    due: dispatch_time_t,
+    /// Strong reference.
+    queue: dispatch_queue_t,
+    /// Strong reference to a copy of the block.
+    block: dispatch_block_t,
+}
+
+struct DispatchQueueHostObject {
+    label: String,
+}
+impl HostObject for DispatchQueueHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// On Apple's platforms, dispatch objects are only Objective-C objects on newer
+// OS versions, but it's convenient for them to always be objects here.
+@implementation OS_dispatch_queue: NSObject
+@end
+
+};
+
+fn new_queue(env: &mut Environment, label: String) -> dispatch_queue_t {
+    let host_object = Box::new(DispatchQueueHostObject { label });
+    let class = env.objc.get_known_class("OS_dispatch_queue", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+fn main_queue(env: &mut Environment) -> dispatch_queue_t {
+    if let Some(queue) = env.libc_state.dispatch.main_queue {
+        return queue;
+    }
+    let queue = new_queue(env, "com.apple.main-thread".to_string());
+    env.libc_state.dispatch.main_queue = Some(queue);
+    queue
+}
+
+/// Get the current time as a [dispatch_time_t].
+fn now(env: &Environment) -> dispatch_time_t {
+    env.clock.uptime().as_nanos().try_into().unwrap()
+}
+
+fn dispatch_get_main_queue(env: &mut Environment) -> dispatch_queue_t {
+    main_queue(env)
+}
+
+fn dispatch_get_global_queue(
+    env: &mut Environment,
+    priority: i32,
+    _flags: u32,
+) -> dispatch_queue_t {
+    if let Some(&queue) = env.libc_state.dispatch.global_queues.get(&priority) {
+        return queue;
+    }
+    let label = match priority {
+        2 => "com.apple.root.high-priority",
+        0 => "com.apple.root.default-priority",
+        -2 => "com.apple.root.low-priority",
+        _ => "com.apple.root.background-priority",
+    };
+    let queue = new_queue(env, label.to_string());
+    env.libc_state
+        .dispatch
+        .global_queues
+        .insert(priority, queue);
+    queue
+}
+
+fn dispatch_queue_create(
+    env: &mut Environment,
+    label: ConstPtr<u8>,
+    _attr: ConstVoidPtr,
+) -> dispatch_queue_t {
+    let label = if label.is_null() {
+        String::new()
+    } else {
+        String::from_utf8_lossy(env.mem.cstr_at(label)).into_owned()
+    };
+    // TODO: Queues created by the app are serial, unless DISPATCH_QUEUE_SERIAL
+    // is passed, but all queues are serial here anyway.
+    new_queue(env, label)
+}
+
+fn dispatch_retain(env: &mut Environment, object: id) {
+    retain(env, object);
+}
+
+fn dispatch_release(env: &mut Environment, object: id) {
+    release(env, object);
+}
+
+fn dispatch_time(env: &mut Environment, when: dispatch_time_t, delta: i64) -> dispatch_time_t {
+    if when == DISPATCH_TIME_FOREVER {
+        return DISPATCH_TIME_FOREVER;
+    }
+    let when = if when == DISPATCH_TIME_NOW {
+        now(env)
+    } else {
+        when
+    };
+    if delta >= 0 {
+        when.saturating_add(delta as u64)
+    } else {
+        when.saturating_sub(delta.unsigned_abs())
+    }
+}
+
+fn enqueue(env: &mut Environment, due: dispatch_time_t, queue: dispatch_queue_t, block: id) {
+    log_dbg!(
+        "Enqueuing block {:?} on queue {:?} ({:?}), due at {}",
+        block,
+        queue,
+        env.objc.borrow::<DispatchQueueHostObject>(queue).label,
+        due
+    );
+    retain(env, queue);
+    let block = blocks::copy_block(env, block);
+    env.libc_state
+        .dispatch
+        .pending
+        .push(PendingBlock { due, queue, block });
+}
+
+fn dispatch_async(env: &mut Environment, queue: dispatch_queue_t, block: dispatch_block_t) {
+    let due = now(env);
+    enqueue(env, due, queue, block);
+}
+
+fn dispatch_after(
+    env: &mut Environment,
+    when: dispatch_time_t,
+    queue: dispatch_queue_t,
+    block: dispatch_block_t,
+) {
+    if when == DISPATCH_TIME_FOREVER {
+        // The block would never run.
+        return;
+    }
+    let due = dispatch_time(env, when, 0);
+    enqueue(env, due, queue, block);
+}
+
+fn dispatch_sync(env: &mut Environment, queue: dispatch_queue_t, block: dispatch_block_t) {
+    // Apple's implementation runs the block on the calling thread where it can,
+    // so this is mostly accurate. Using the main queue from the main thread
+    // would deadlock there, but it's more useful to run the block immediately.
+    // On other threads, the block ought to run on the main thread (TODO).
+    if queue == main_queue(env) && env.current_thread != 0 {
+        log_dbg!(
+            "TODO: dispatch_sync() on main queue from thread {}, running block on the calling thread",
+            env.current_thread
+        );
+    }
+    // TODO: Blocks already enqueued for this queue should run first.
+    run_block(env, block);
+}
+
+fn run_block(env: &mut Environment, block: dispatch_block_t) {
+    let invoke = blocks::get_invoke(env, block);
+    let _: () = invoke.call_from_host(env, (block,));
+}
+
+/// For use by `NSRunLoop` on the main thread: run the blocks that are due.
+/// Returns the time the next block will be due, if there is one.
+pub fn handle_main_queue(env: &mut Environment) -> Option<Instant> {
+    if env.libc_state.dispatch.pending.is_empty() {
+        return None;
+    }
+
+    // Blocks enqueued while these run will be handled next time, so the run
+    // loop isn't starved.
+    let now_nanos = now(env);
+    let pending = std::mem::take(&mut env.libc_state.dispatch.pending);
+    let (due, not_due): (Vec<_>, Vec<_>) =
+        pending.into_iter().partition(|item| item.due <= now_nanos);
+    env.libc_state.dispatch.pending = not_due;
+
+    for PendingBlock { queue, block, .. } in due {
+        log_dbg!("Running block {:?} from queue {:?}", block, queue);
+        run_block(env, block);
+        blocks::release_block(env, block);
+        release(env, queue);
+    }
+
+    let pending = &env.libc_state.dispatch.pending;
+    let next_due = pending.iter().map(|item| item.due).min()?;
+    let now_nanos = now(env);
+    Some(env.clock.now() + Duration::from_nanos(next_due.saturating_sub(now_nanos)))
+}
+
+pub const CONSTANTS: ConstantExports = &[(
+    // dispatch_get_main_queue() is a macro that takes the address of this.
+    "__dispatch_main_q",
+    HostConstant::CustomWithEnv(|env| main_queue(env).cast().cast_const()),
+)];
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(dispatch_get_main_queue()),
+    export_c_func!(dispatch_get_global_queue(_, _)),
+    export_c_func!(dispatch_queue_create(_, _)),
+    export_c_func!(dispatch_retain(_)),
+    export_c_func!(dispatch_release(_)),
+    export_c_func!(dispatch_time(_, _)),
+    export_c_func!(dispatch_async(_, _)),
+    export_c_func!(dispatch_after(_, _, _)),
+    export_c_func!(dispatch_sync(_, _)),
+];
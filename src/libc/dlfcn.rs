@@ -5,46 +5,215 @@
  */
 //! `dlfcn.h` (`dlopen()` and friends)
 
-use crate::dyld::{export_c_func, FunctionExports};
-use crate::mem::{ConstPtr, MutVoidPtr, Ptr};
+use crate::dyld::{export_c_func, lookup_guest_symbol, Dyld, FunctionExports};
+use crate::mem::{ConstPtr, MutPtr, MutVoidPtr, Ptr};
 use crate::Environment;
+use std::collections::HashMap;
 
-const ALLOWED_LIBRARIES: [Result<&str, &[u8]>; 2] = [
-    Ok("/usr/lib/libSystem.B.dylib"),
-    Ok("/System/Library/Frameworks/OpenAL.framework/OpenAL"),
+/// Libraries that have host implementations, and can therefore always be
+/// opened, even though there is no binary for them.
+const HOST_LIBRARIES: &[&str] = &[
+    "/usr/lib/libSystem.B.dylib",
+    "/usr/lib/libobjc.A.dylib",
+    "/System/Library/Frameworks/AudioToolbox.framework/AudioToolbox",
+    "/System/Library/Frameworks/CoreFoundation.framework/CoreFoundation",
+    "/System/Library/Frameworks/CoreGraphics.framework/CoreGraphics",
+    "/System/Library/Frameworks/CoreServices.framework/CoreServices",
+    "/System/Library/Frameworks/Foundation.framework/Foundation",
+    "/System/Library/Frameworks/MediaPlayer.framework/MediaPlayer",
+    "/System/Library/Frameworks/OpenAL.framework/OpenAL",
+    "/System/Library/Frameworks/OpenGLES.framework/OpenGLES",
+    "/System/Library/Frameworks/QuartzCore.framework/QuartzCore",
+    "/System/Library/Frameworks/UIKit.framework/UIKit",
 ];
 
-fn dlopen(env: &mut Environment, path: ConstPtr<u8>, _mode: i32) -> MutVoidPtr {
-    // TODO: dlopen() support for real dynamic libraries, and support for all
-    // libraries with host implementations.
-    assert!(ALLOWED_LIBRARIES.contains(&env.mem.cstr_at_utf8(path)));
-    // For convenience, use the path as the handle.
-    // TODO: Find out whether the handle is truly opaque on iPhone OS, and if
-    // not, where it points.
-    path.cast_mut().cast()
+// Special handles from Apple's dlfcn.h.
+const RTLD_NEXT: u32 = -1i32 as u32;
+const RTLD_DEFAULT: u32 = -2i32 as u32;
+const RTLD_SELF: u32 = -3i32 as u32;
+const RTLD_MAIN_ONLY: u32 = -5i32 as u32;
+
+/// What a handle returned by `dlopen()` refers to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Library {
+    /// All loaded binaries and host libraries, i.e. `dlopen(NULL, ...)`.
+    Global,
+    /// A loaded binary, by its index in [Environment::bins].
+    Bin(usize),
+    /// A library with a host implementation (see [HOST_LIBRARIES]). These all
+    /// share a handle, since host functions aren't grouped by library.
+    Host,
+}
+
+#[derive(Default)]
+pub struct State {
+    /// Handles that have been returned by `dlopen()`. The same library always
+    /// gets the same handle. Each handle is a small guest allocation, so that
+    /// it is a unique, non-null pointer.
+    handles: HashMap<MutVoidPtr, Library>,
+    /// Symbols already looked up by `dlsym()`, so that repeated lookups of a
+    /// host function or constant don't leak memory.
+    symbols: HashMap<(Library, String), MutVoidPtr>,
+    /// Message for the last failure, to be returned by `dlerror()`.
+    ///
+    /// TODO: This should be per-thread.
+    last_error: Option<String>,
+    /// The string last returned by `dlerror()`.
+    error_string: Option<MutPtr<u8>>,
+}
+
+fn set_error(env: &mut Environment, message: String) {
+    log_dbg!("dlfcn error: {}", message);
+    env.libc_state.dlfcn.last_error = Some(message);
+}
+
+fn handle_for_library(env: &mut Environment, library: Library) -> MutVoidPtr {
+    if let Some((&handle, _)) = env
+        .libc_state
+        .dlfcn
+        .handles
+        .iter()
+        .find(|&(_, &existing)| existing == library)
+    {
+        return handle;
+    }
+    let handle = env.mem.alloc(4);
+    env.libc_state.dlfcn.handles.insert(handle, library);
+    handle
+}
+
+fn library_for_handle(env: &Environment, handle: MutVoidPtr) -> Option<Library> {
+    match handle.to_bits() {
+        RTLD_DEFAULT => Some(Library::Global),
+        RTLD_MAIN_ONLY => Some(Library::Bin(0)),
+        // These would need to know which binary called dlsym().
+        RTLD_NEXT | RTLD_SELF => {
+            log!("TODO: dlsym() with RTLD_NEXT or RTLD_SELF, treating like RTLD_DEFAULT");
+            Some(Library::Global)
+        }
+        _ => env.libc_state.dlfcn.handles.get(&handle).copied(),
+    }
+}
+
+fn dlopen(env: &mut Environment, path: ConstPtr<u8>, mode: i32) -> MutVoidPtr {
+    if path.is_null() {
+        return handle_for_library(env, Library::Global);
+    }
+
+    let path_str = env.mem.cstr_at_utf8(path).unwrap().to_string();
+    let file_name = path_str.rsplit('/').next().unwrap();
+    let library = if let Some(idx) = env
+        .bins
+        .iter()
+        .position(|bin| bin.install_name.as_deref() == Some(path_str.as_str()))
+        .or_else(|| env.bins.iter().position(|bin| bin.name == file_name))
+    {
+        Library::Bin(idx)
+    } else if HOST_LIBRARIES.contains(&path_str.as_str()) {
+        Library::Host
+    } else {
+        // TODO: load libraries that weren't loaded at launch.
+        log!("Warning: dlopen() for unknown library {:?}", path_str);
+        set_error(
+            env,
+            format!("dlopen({}, {}): image not found", path_str, mode),
+        );
+        return Ptr::null();
+    };
+
+    let handle = handle_for_library(env, library);
+    log_dbg!("dlopen({:?}) => {:?} ({:?})", path_str, handle, library);
+    handle
 }
 
 fn dlsym(env: &mut Environment, handle: MutVoidPtr, symbol: ConstPtr<u8>) -> MutVoidPtr {
-    assert!(ALLOWED_LIBRARIES.contains(&env.mem.cstr_at_utf8(handle.cast())));
+    let symbol_str = env.mem.cstr_at_utf8(symbol).unwrap().to_string();
+    let Some(library) = library_for_handle(env, handle) else {
+        set_error(
+            env,
+            format!("dlsym({:?}, {}): invalid handle", handle, symbol_str),
+        );
+        return Ptr::null();
+    };
+
     // For some reason, the symbols passed to dlsym() don't have the leading _.
-    let symbol = format!("_{}", env.mem.cstr_at_utf8(symbol).unwrap());
-    // TODO: error handling. dlsym() should just return NULL in this case, but
-    // currently it's probably more useful to have the emulator crash if there's
-    // no symbol found, since it most likely indicates a missing host function.
-    let addr = env
+    let mangled = format!("_{}", symbol_str);
+
+    let key = (library, mangled);
+    if let Some(&addr) = env.libc_state.dlfcn.symbols.get(&key) {
+        return addr;
+    }
+    let (_, mangled) = &key;
+
+    // Symbols from the guest binaries come first, like with the normal dynamic
+    // linker (see lookup_guest_symbol), then host functions, then host
+    // constants.
+    let guest_addr = match library {
+        Library::Global => lookup_guest_symbol(&env.bins, 0, mangled).map(|(_, addr)| addr),
+        Library::Bin(idx) => env.bins[idx].exported_symbols.get(mangled).copied(),
+        Library::Host => None,
+    };
+    let addr: Option<MutVoidPtr> = if let Some(addr) = guest_addr {
+        Some(Ptr::from_bits(addr))
+    } else if let Library::Bin(_) = library {
+        None
+    } else if let Ok(f) = env
         .dyld
-        .create_proc_address(&mut env.mem, &mut env.cpu, &symbol)
-        .unwrap_or_else(|_| panic!("dlsym() for unimplemented function {}", symbol));
-    Ptr::from_bits(addr.addr_with_thumb_bit())
+        .create_proc_address(&mut env.mem, &mut env.cpu, mangled)
+    {
+        // TODO: check the function belongs to the right host library?
+        Some(Ptr::from_bits(f.addr_with_thumb_bit()))
+    } else if let Ok(constant) = Dyld::create_constant_address(env, mangled) {
+        Some(constant.cast_mut())
+    } else {
+        None
+    };
+
+    let Some(addr) = addr else {
+        log!(
+            "Warning: dlsym() for unknown symbol {:?} in {:?}",
+            symbol_str,
+            library
+        );
+        set_error(
+            env,
+            format!("dlsym({:?}, {}): symbol not found", handle, symbol_str),
+        );
+        return Ptr::null();
+    };
+    log_dbg!("dlsym({:?}, {:?}) => {:?}", handle, symbol_str, addr);
+    env.libc_state.dlfcn.symbols.insert(key, addr);
+    addr
 }
 
 fn dlclose(env: &mut Environment, handle: MutVoidPtr) -> i32 {
-    assert!(ALLOWED_LIBRARIES.contains(&env.mem.cstr_at_utf8(handle.cast())));
-    0 // success
+    // Libraries are never unloaded: they are either built-in or were loaded
+    // at launch.
+    if library_for_handle(env, handle).is_some() {
+        0 // success
+    } else {
+        set_error(env, format!("dlclose({:?}): invalid handle", handle));
+        -1
+    }
+}
+
+fn dlerror(env: &mut Environment) -> ConstPtr<u8> {
+    let state = &mut env.libc_state.dlfcn;
+    // The previous string is only valid until the next call.
+    if let Some(old) = state.error_string.take() {
+        env.mem.free(old.cast());
+    }
+    let Some(message) = env.libc_state.dlfcn.last_error.take() else {
+        return Ptr::null();
+    };
+    let string = env.mem.alloc_and_write_cstr(message.as_bytes());
+    env.libc_state.dlfcn.error_string = Some(string);
+    string.cast_const()
 }
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(dlopen(_, _)),
     export_c_func!(dlsym(_, _)),
     export_c_func!(dlclose(_)),
+    export_c_func!(dlerror()),
 ];
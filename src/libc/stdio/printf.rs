@@ -8,12 +8,479 @@
 use crate::abi::{DotDotDot, VaList};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::foundation::ns_string;
-use crate::mem::{ConstPtr, GuestUSize, Mem, MutPtr};
-use crate::objc::{id, msg};
+use crate::mem::{ConstPtr, GuestUSize, Mem, MutPtr, Ptr};
+use crate::objc::{id, msg, nil};
 use crate::Environment;
 use std::io::Write;
 
-const INTEGER_SPECIFIERS: [u8; 6] = [b'd', b'i', b'o', b'u', b'x', b'X'];
+/// Length modifier of a conversion specification, e.g. the `ll` in `%lld`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Length {
+    Default,
+    /// `hh`
+    Char,
+    /// `h`
+    Short,
+    /// `l`. On 32-bit ARM, `long` is the same size as `int`.
+    Long,
+    /// `ll`, `q`, `j`
+    LongLong,
+}
+
+/// A width or precision, e.g. the `10` or `*` in `%10.*f`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Count {
+    Fixed(usize),
+    /// `*` or `*n$`: the value is taken from an `int` argument. The number is
+    /// the index of that argument.
+    Arg(usize),
+}
+
+/// A parsed conversion specification (e.g. `%-10.3f`), excluding `%%`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Spec {
+    /// `-`
+    left_justify: bool,
+    /// `+`
+    plus_sign: bool,
+    /// ` `
+    space_sign: bool,
+    /// `#`
+    alternate_form: bool,
+    /// `0`
+    zero_pad: bool,
+    width: Option<Count>,
+    precision: Option<Count>,
+    length: Length,
+    conversion: u8,
+    /// Index of the argument to be formatted.
+    arg: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Literal(Vec<u8>),
+    Spec(Spec),
+}
+
+/// Type of an argument as passed in the variable arguments list. Types smaller
+/// than `int` are promoted to `int`, and `float` is promoted to `double`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ArgType {
+    /// `int`, `long` or any pointer.
+    Word,
+    /// `long long`
+    DoubleWord,
+    Double,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ArgValue {
+    Word(u32),
+    DoubleWord(u64),
+    Double(f64),
+}
+impl ArgValue {
+    fn read(args: &mut VaList, env: &mut Environment, type_: ArgType) -> ArgValue {
+        match type_ {
+            ArgType::Word => ArgValue::Word(args.next(env)),
+            ArgType::DoubleWord => ArgValue::DoubleWord(args.next(env)),
+            ArgType::Double => ArgValue::Double(args.next(env)),
+        }
+    }
+    /// Get the value as a signed integer, sign-extending if appropriate.
+    fn to_i64(self) -> i64 {
+        match self {
+            ArgValue::Word(word) => word as i32 as i64,
+            ArgValue::DoubleWord(double_word) => double_word as i64,
+            ArgValue::Double(double) => double as i64,
+        }
+    }
+    /// Get the value as an unsigned integer, zero-extending if appropriate.
+    fn to_u64(self) -> u64 {
+        match self {
+            ArgValue::Word(word) => word.into(),
+            ArgValue::DoubleWord(double_word) => double_word,
+            ArgValue::Double(double) => double as u64,
+        }
+    }
+    fn to_f64(self) -> f64 {
+        match self {
+            ArgValue::Word(word) => word as i32 as f64,
+            ArgValue::DoubleWord(double_word) => double_word as i64 as f64,
+            ArgValue::Double(double) => double,
+        }
+    }
+}
+
+impl Spec {
+    fn arg_type(&self) -> ArgType {
+        match self.conversion {
+            b'd' | b'i' | b'o' | b'u' | b'x' | b'X' if self.length == Length::LongLong => {
+                ArgType::DoubleWord
+            }
+            b'f' | b'F' | b'e' | b'E' | b'g' | b'G' | b'a' | b'A' => ArgType::Double,
+            _ => ArgType::Word,
+        }
+    }
+}
+
+/// Parse a format string into literal text and conversion specifications.
+/// Also returns the types of the arguments, in order.
+///
+/// Arguments are consumed one after the other, unless positional arguments
+/// (e.g. `%2$d`) are used. Unused positions are assumed to be `int`s.
+fn parse_format(format: &[u8]) -> (Vec<Piece>, Vec<ArgType>) {
+    let mut pieces = Vec::new();
+    let mut arg_types: Vec<Option<ArgType>> = Vec::new();
+    let mut next_arg = 0;
+    let mut use_arg = |arg_types: &mut Vec<Option<ArgType>>, position: Option<usize>, type_| {
+        let idx = position.unwrap_or_else(|| {
+            next_arg += 1;
+            next_arg - 1
+        });
+        if arg_types.len() <= idx {
+            arg_types.resize(idx + 1, None);
+        }
+        // If the same argument is used twice with different types, the first
+        // one wins.
+        arg_types[idx].get_or_insert(type_);
+        idx
+    };
+
+    let mut literal = Vec::new();
+    let mut i = 0;
+    while i < format.len() {
+        let c = format[i];
+        i += 1;
+        if c != b'%' {
+            literal.push(c);
+            continue;
+        }
+        if format.get(i) == Some(&b'%') {
+            i += 1;
+            literal.push(b'%');
+            continue;
+        }
+        let spec_start = i - 1;
+
+        // Parses a decimal number followed by `$`, as used for positional
+        // arguments. The result is the zero-based index.
+        let parse_position = |i: &mut usize| -> Option<usize> {
+            let mut j = *i;
+            let mut position: usize = 0;
+            while let Some(&c @ b'0'..=b'9') = format.get(j) {
+                position = position
+                    .saturating_mul(10)
+                    .saturating_add((c - b'0').into());
+                j += 1;
+            }
+            if j > *i && position > 0 && format.get(j) == Some(&b'$') {
+                *i = j + 1;
+                Some(position - 1)
+            } else {
+                None
+            }
+        };
+        let parse_number = |i: &mut usize| -> usize {
+            let mut number: usize = 0;
+            while let Some(&c @ b'0'..=b'9') = format.get(*i) {
+                number = number.saturating_mul(10).saturating_add((c - b'0').into());
+                *i += 1;
+            }
+            number
+        };
+
+        let position = parse_position(&mut i);
+
+        let mut spec = Spec {
+            left_justify: false,
+            plus_sign: false,
+            space_sign: false,
+            alternate_form: false,
+            zero_pad: false,
+            width: None,
+            precision: None,
+            length: Length::Default,
+            conversion: 0,
+            arg: 0,
+        };
+        loop {
+            match format.get(i) {
+                Some(b'-') => spec.left_justify = true,
+                Some(b'+') => spec.plus_sign = true,
+                Some(b' ') => spec.space_sign = true,
+                Some(b'#') => spec.alternate_form = true,
+                Some(b'0') => spec.zero_pad = true,
+                // Thousands grouping, not meaningful in the C locale.
+                Some(b'\'') => (),
+                _ => break,
+            }
+            i += 1;
+        }
+        if format.get(i) == Some(&b'*') {
+            i += 1;
+            let position = parse_position(&mut i);
+            let idx = use_arg(&mut arg_types, position, ArgType::Word);
+            spec.width = Some(Count::Arg(idx));
+        } else if let Some(b'1'..=b'9') = format.get(i) {
+            spec.width = Some(Count::Fixed(parse_number(&mut i)));
+        }
+        if format.get(i) == Some(&b'.') {
+            i += 1;
+            if format.get(i) == Some(&b'*') {
+                i += 1;
+                let position = parse_position(&mut i);
+                let idx = use_arg(&mut arg_types, position, ArgType::Word);
+                spec.precision = Some(Count::Arg(idx));
+            } else {
+                spec.precision = Some(Count::Fixed(parse_number(&mut i)));
+            }
+        }
+        spec.length = match format.get(i) {
+            Some(b'h') if format.get(i + 1) == Some(&b'h') => {
+                i += 2;
+                Length::Char
+            }
+            Some(b'h') => {
+                i += 1;
+                Length::Short
+            }
+            Some(b'l') if format.get(i + 1) == Some(&b'l') => {
+                i += 2;
+                Length::LongLong
+            }
+            Some(b'q' | b'j') => {
+                i += 1;
+                Length::LongLong
+            }
+            // size_t, ptrdiff_t, long double (which is the same as double on
+            // iPhone OS) are the same size as the default.
+            Some(b'l' | b'z' | b't' | b'L') => {
+                i += 1;
+                Length::Long
+            }
+            _ => Length::Default,
+        };
+
+        let Some(&conversion) = format.get(i) else {
+            log!(
+                "Warning: incomplete format specification {:?}",
+                String::from_utf8_lossy(&format[spec_start..])
+            );
+            literal.extend_from_slice(&format[spec_start..]);
+            break;
+        };
+        i += 1;
+        if !b"diouxXfFeEgGaAcCsSp@n".contains(&conversion) {
+            log!(
+                "Warning: unsupported format specification {:?}",
+                String::from_utf8_lossy(&format[spec_start..i])
+            );
+            literal.extend_from_slice(&format[spec_start..i]);
+            continue;
+        }
+        spec.conversion = conversion;
+        spec.arg = use_arg(&mut arg_types, position, spec.arg_type());
+
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+        }
+        pieces.push(Piece::Spec(spec));
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+
+    let arg_types = arg_types
+        .into_iter()
+        .map(|type_| type_.unwrap_or(ArgType::Word))
+        .collect();
+    (pieces, arg_types)
+}
+
+/// Pad `body` to `width` (in bytes). `prefix` (sign and/or radix prefix) is
+/// kept before any zero-padding.
+fn pad(res: &mut Vec<u8>, spec: &Spec, width: usize, prefix: &[u8], body: &[u8], zero_pad: bool) {
+    let padding = width.saturating_sub(prefix.len() + body.len());
+    if spec.left_justify {
+        res.extend_from_slice(prefix);
+        res.extend_from_slice(body);
+        res.extend(std::iter::repeat(b' ').take(padding));
+    } else if zero_pad {
+        res.extend_from_slice(prefix);
+        res.extend(std::iter::repeat(b'0').take(padding));
+        res.extend_from_slice(body);
+    } else {
+        res.extend(std::iter::repeat(b' ').take(padding));
+        res.extend_from_slice(prefix);
+        res.extend_from_slice(body);
+    }
+}
+
+fn sign_prefix(spec: &Spec, negative: bool) -> &'static [u8] {
+    if negative {
+        b"-"
+    } else if spec.plus_sign {
+        b"+"
+    } else if spec.space_sign {
+        b" "
+    } else {
+        b""
+    }
+}
+
+/// Format an integer conversion (`d`, `i`, `o`, `u`, `x`, `X`).
+fn format_int(
+    res: &mut Vec<u8>,
+    spec: &Spec,
+    width: usize,
+    precision: Option<usize>,
+    value: ArgValue,
+) {
+    let signed = matches!(spec.conversion, b'd' | b'i');
+    // Truncate to the size given by the length modifier.
+    let (negative, magnitude) = if signed {
+        let value = match spec.length {
+            Length::Char => value.to_i64() as i8 as i64,
+            Length::Short => value.to_i64() as i16 as i64,
+            Length::Default | Length::Long => value.to_i64() as i32 as i64,
+            Length::LongLong => value.to_i64(),
+        };
+        (value < 0, value.unsigned_abs())
+    } else {
+        let value = match spec.length {
+            Length::Char => value.to_u64() as u8 as u64,
+            Length::Short => value.to_u64() as u16 as u64,
+            Length::Default | Length::Long => value.to_u64() as u32 as u64,
+            Length::LongLong => value.to_u64(),
+        };
+        (false, value)
+    };
+
+    let mut digits = match spec.conversion {
+        b'o' => format!("{:o}", magnitude),
+        b'x' => format!("{:x}", magnitude),
+        b'X' => format!("{:X}", magnitude),
+        _ => format!("{}", magnitude),
+    };
+    // A precision is the minimum number of digits. Zero with a precision of
+    // zero has no digits at all.
+    if let Some(precision) = precision {
+        if precision == 0 && magnitude == 0 {
+            digits.clear();
+        } else if digits.len() < precision {
+            digits.insert_str(0, &"0".repeat(precision - digits.len()));
+        }
+    }
+    let prefix: &[u8] = match spec.conversion {
+        b'o' if spec.alternate_form && !digits.starts_with('0') => {
+            digits.insert(0, '0');
+            b""
+        }
+        b'x' if spec.alternate_form && magnitude != 0 => b"0x",
+        b'X' if spec.alternate_form && magnitude != 0 => b"0X",
+        b'd' | b'i' => sign_prefix(spec, negative),
+        _ => b"",
+    };
+    let zero_pad = spec.zero_pad && precision.is_none();
+    pad(res, spec, width, prefix, digits.as_bytes(), zero_pad);
+}
+
+/// Write a float in exponential notation, like C's `%e`.
+fn format_exponential(value: f64, precision: usize, upper: bool) -> String {
+    // Rust's exponential notation is like "1.5e3", but C's is like "1.5e+03".
+    let formatted = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let e = if upper { 'E' } else { 'e' };
+    format!("{}{}{}{:02}", mantissa, e, sign, exponent.unsigned_abs())
+}
+
+/// Format a floating-point conversion (`f`, `F`, `e`, `E`, `g`, `G`, `a`,
+/// `A`).
+fn format_float(
+    res: &mut Vec<u8>,
+    spec: &Spec,
+    width: usize,
+    precision: Option<usize>,
+    value: f64,
+) {
+    let upper = spec.conversion.is_ascii_uppercase();
+    let prefix = sign_prefix(spec, value.is_sign_negative() && !value.is_nan());
+    let value = value.abs();
+
+    if !value.is_finite() {
+        let body = match (value.is_nan(), upper) {
+            (true, false) => "nan",
+            (true, true) => "NAN",
+            (false, false) => "inf",
+            (false, true) => "INF",
+        };
+        pad(res, spec, width, prefix, body.as_bytes(), false);
+        return;
+    }
+
+    let precision = precision.unwrap_or(6);
+    let mut body = match spec.conversion {
+        b'f' | b'F' => format!("{:.*}", precision, value),
+        b'e' | b'E' => format_exponential(value, precision, upper),
+        b'g' | b'G' => {
+            // The precision is the number of significant digits. Which
+            // notation to use depends on the exponent after rounding.
+            let precision = precision.max(1);
+            let exponent: i32 = format!("{:.*e}", precision - 1, value)
+                .split_once('e')
+                .unwrap()
+                .1
+                .parse()
+                .unwrap();
+            let mut body = if exponent < -4 || exponent >= precision as i32 {
+                format_exponential(value, precision - 1, upper)
+            } else {
+                let decimals = (precision as i32 - 1 - exponent) as usize;
+                format!("{:.*}", decimals, value)
+            };
+            // Trailing zeros are removed unless the # flag is used.
+            if !spec.alternate_form {
+                let (number, exponent) = match body.find(['e', 'E']) {
+                    Some(idx) => body.split_at(idx),
+                    None => (body.as_str(), ""),
+                };
+                if number.contains('.') {
+                    let number = number.trim_end_matches('0').trim_end_matches('.');
+                    body = format!("{}{}", number, exponent);
+                }
+            }
+            body
+        }
+        // Hexadecimal floats are rarely used, so use a simpler notation.
+        // TODO: Follow the C standard exactly.
+        _ => {
+            let e = if upper { 'P' } else { 'p' };
+            let x = if upper { "0X" } else { "0x" };
+            if value == 0.0 {
+                format!("{}0{}+0", x, e)
+            } else {
+                let exponent = value.log2().floor() as i32;
+                let mantissa = value / 2f64.powi(exponent);
+                let fraction = ((mantissa - 1.0) * (1u64 << 52) as f64) as u64;
+                let fraction = format!("{:013x}", fraction);
+                let fraction = fraction.trim_end_matches('0');
+                let dot = if fraction.is_empty() { "" } else { "." };
+                format!("{}1{}{}{}{:+}", x, dot, fraction, e, exponent)
+            }
+        }
+    };
+    if spec.alternate_form && !body.contains('.') && matches!(spec.conversion, b'f' | b'F') {
+        body.push('.');
+    }
+    if upper {
+        body.make_ascii_uppercase();
+    }
+    pad(res, spec, width, prefix, body.as_bytes(), spec.zero_pad);
+}
 
 /// String formatting implementation for `printf` and `NSLog` function families.
 ///
@@ -22,117 +489,151 @@ const INTEGER_SPECIFIERS: [u8; 6] = [b'd', b'i', b'o', b'u', b'x', b'X'];
 ///
 /// `get_format_char` is a callback that returns the byte at a given index in
 /// the format string, or `'\0'` if the index is one past the last byte.
+///
+/// There is no way to know how many arguments were actually passed, so if the
+/// format string asks for more than that, garbage is read, as on a real device.
+/// Null pointers for `%s` and `%@` are printed as `(null)`.
 pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
     env: &mut Environment,
     get_format_char: F,
     mut args: VaList,
 ) -> Vec<u8> {
-    let mut res = Vec::<u8>::new();
-
-    let mut format_char_idx = 0;
-
+    let mut format = Vec::new();
     loop {
-        let c = get_format_char(&env.mem, format_char_idx);
-        format_char_idx += 1;
-
+        let c = get_format_char(&env.mem, format.len().try_into().unwrap());
         if c == b'\0' {
             break;
         }
-        if c != b'%' {
-            res.push(c);
-            continue;
-        }
+        format.push(c);
+    }
 
-        let mut pad_char = if get_format_char(&env.mem, format_char_idx) == b'0' {
-            format_char_idx += 1;
-            '0'
-        } else {
-            ' '
-        };
-        let mut has_precision = false;
-        if get_format_char(&env.mem, format_char_idx) == b'.' {
-            has_precision = true;
-            format_char_idx += 1;
-            pad_char = '0';
-        }
-        let pad_width = {
-            let mut pad_width = 0;
-            while let c @ b'0'..=b'9' = get_format_char(&env.mem, format_char_idx) {
-                pad_width = pad_width * 10 + (c - b'0') as usize;
-                format_char_idx += 1;
-            }
-            pad_width
-        };
+    let (pieces, arg_types) = parse_format(&format);
+    let arg_values: Vec<ArgValue> = arg_types
+        .into_iter()
+        .map(|type_| ArgValue::read(&mut args, env, type_))
+        .collect();
 
-        let specifier = get_format_char(&env.mem, format_char_idx);
-        format_char_idx += 1;
+    let mut res = Vec::<u8>::new();
 
-        assert!(specifier != b'\0');
-        if specifier == b'%' {
-            res.push(b'%');
-            continue;
-        }
+    for piece in pieces {
+        let spec = match piece {
+            Piece::Literal(literal) => {
+                res.extend_from_slice(&literal);
+                continue;
+            }
+            Piece::Spec(spec) => spec,
+        };
 
-        if has_precision {
-            assert!(INTEGER_SPECIFIERS.contains(&specifier))
-        }
+        let (width, left_justify) = match spec.width {
+            None => (0, false),
+            Some(Count::Fixed(width)) => (width, false),
+            // A negative width means left-justification.
+            Some(Count::Arg(idx)) => {
+                let width = arg_values[idx].to_i64() as i32;
+                (width.unsigned_abs() as usize, width < 0)
+            }
+        };
+        let precision = match spec.precision {
+            None => None,
+            Some(Count::Fixed(precision)) => Some(precision),
+            // A negative precision is treated as if it were omitted.
+            Some(Count::Arg(idx)) => usize::try_from(arg_values[idx].to_i64() as i32).ok(),
+        };
+        let spec = Spec {
+            left_justify: spec.left_justify || left_justify,
+            ..spec
+        };
+        let value = arg_values[spec.arg];
 
-        match specifier {
+        match spec.conversion {
+            b'd' | b'i' | b'o' | b'u' | b'x' | b'X' => {
+                format_int(&mut res, &spec, width, precision, value);
+            }
+            b'f' | b'F' | b'e' | b'E' | b'g' | b'G' | b'a' | b'A' => {
+                format_float(&mut res, &spec, width, precision, value.to_f64());
+            }
             b'c' => {
-                let c: u8 = args.next(env);
-                assert!(pad_char == ' ' && pad_width == 0); // TODO
-                res.push(c);
+                let c = value.to_u64() as u8;
+                pad(&mut res, &spec, width, b"", &[c], false);
             }
-            b's' => {
-                let c_string: ConstPtr<u8> = args.next(env);
-                assert!(pad_char == ' ' && pad_width == 0); // TODO
-                res.extend_from_slice(env.mem.cstr_at(c_string));
+            b'C' => {
+                // unichar (NSString) or wint_t (C), both are UTF-16 here.
+                let c = value.to_u64() as u16;
+                let c = char::decode_utf16([c])
+                    .next()
+                    .unwrap()
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                pad(&mut res, &spec, width, b"", c.to_string().as_bytes(), false);
             }
-            b'd' | b'i' | b'u' => {
-                let int: i64 = if specifier == b'u' {
-                    let uint: u32 = args.next(env);
-                    uint.into()
-                } else {
-                    let int: i32 = args.next(env);
-                    int.into()
-                };
-                if pad_width > 0 {
-                    if pad_char == '0' {
-                        write!(&mut res, "{:01$}", int, pad_width).unwrap();
-                    } else {
-                        write!(&mut res, "{:1$}", int, pad_width).unwrap();
+            b's' => {
+                let c_string: ConstPtr<u8> = Ptr::from_bits(value.to_u64() as u32);
+                let bytes: &[u8] = if c_string.is_null() {
+                    b"(null)"
+                } else if let Some(precision) = precision {
+                    // The string doesn't have to be null-terminated if there is
+                    // a precision.
+                    let mut len = 0;
+                    while len < precision && env.mem.read(c_string + len as GuestUSize) != b'\0' {
+                        len += 1;
                     }
+                    env.mem.bytes_at(c_string, len as GuestUSize)
                 } else {
-                    write!(&mut res, "{}", int).unwrap();
-                }
+                    env.mem.cstr_at(c_string)
+                };
+                let bytes = bytes.to_vec();
+                pad(&mut res, &spec, width, b"", &bytes, false);
             }
-            b'f' => {
-                let float: f64 = args.next(env);
-                if pad_width > 0 {
-                    if pad_char == '0' {
-                        write!(&mut res, "{:01$}", float, pad_width).unwrap();
-                    } else {
-                        write!(&mut res, "{:1$}", float, pad_width).unwrap();
-                    }
+            b'S' => {
+                // Null-terminated UTF-16 string (unichar*).
+                let string: ConstPtr<u16> = Ptr::from_bits(value.to_u64() as u32);
+                let string = if string.is_null() {
+                    "(null)".to_string()
                 } else {
-                    write!(&mut res, "{}", float).unwrap();
-                }
+                    let mut code_units = Vec::new();
+                    loop {
+                        if precision.map_or(false, |precision| code_units.len() >= precision) {
+                            break;
+                        }
+                        let code_unit = env.mem.read(string + code_units.len() as GuestUSize);
+                        if code_unit == 0 {
+                            break;
+                        }
+                        code_units.push(code_unit);
+                    }
+                    String::from_utf16_lossy(&code_units)
+                };
+                pad(&mut res, &spec, width, b"", string.as_bytes(), false);
+            }
+            b'p' => {
+                let pointer = format!("{:#x}", value.to_u64() as u32);
+                pad(&mut res, &spec, width, b"", pointer.as_bytes(), false);
             }
             b'@' if NS_LOG => {
-                let object: id = args.next(env);
-                // TODO: use localized description if available?
-                let description: id = msg![env; object description];
-                // TODO: avoid copy
-                // TODO: what if the description isn't valid UTF-16?
-                let description = ns_string::to_rust_string(env, description);
-                write!(&mut res, "{}", description).unwrap();
+                let object: id = Ptr::from_bits(value.to_u64() as u32);
+                let description = if object == nil {
+                    "(null)".into()
+                } else {
+                    // TODO: use localized description if available?
+                    let description: id = msg![env; object description];
+                    // TODO: avoid copy
+                    // TODO: what if the description isn't valid UTF-16?
+                    ns_string::to_rust_string(env, description)
+                };
+                pad(&mut res, &spec, width, b"", description.as_bytes(), false);
             }
-            b'x' => {
-                let int: i32 = args.next(env);
-                res.extend_from_slice(format!("{:x}", int).as_bytes());
+            b'n' => {
+                let count_ptr: MutPtr<i32> = Ptr::from_bits(value.to_u64() as u32);
+                if !count_ptr.is_null() {
+                    env.mem.write(count_ptr, res.len().try_into().unwrap());
+                }
+            }
+            _ => {
+                // %@ in a C format string.
+                log!(
+                    "Warning: format character '{}' is not valid here",
+                    spec.conversion as char
+                );
             }
-            // TODO: more specifiers
-            _ => unimplemented!("Format character '{}'", specifier as char),
         }
     }
 
@@ -286,3 +787,102 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(sprintf(_, _, _)),
     export_c_func!(printf(_, _)),
 ];
+
+#[cfg(test)]
+#[test]
+fn test_format_numbers() {
+    let format = |format: &str, value: ArgValue| -> String {
+        let (pieces, _) = parse_format(format.as_bytes());
+        let [Piece::Spec(spec)] = &pieces[..] else {
+            panic!();
+        };
+        let width = match spec.width {
+            Some(Count::Fixed(width)) => width,
+            _ => 0,
+        };
+        let precision = match spec.precision {
+            Some(Count::Fixed(precision)) => Some(precision),
+            _ => None,
+        };
+        let mut res = Vec::new();
+        if spec.arg_type() == ArgType::Double {
+            format_float(&mut res, spec, width, precision, value.to_f64());
+        } else {
+            format_int(&mut res, spec, width, precision, value);
+        }
+        String::from_utf8(res).unwrap()
+    };
+    let int = |int: i32| ArgValue::Word(int as u32);
+    let double = ArgValue::Double;
+
+    assert_eq!(format("%d", int(-42)), "-42");
+    assert_eq!(format("%u", int(-1)), "4294967295");
+    assert_eq!(format("%-10d", int(42)), "42        ");
+    assert_eq!(format("%+5d", int(42)), "  +42");
+    assert_eq!(format("%05d", int(-42)), "-0042");
+    assert_eq!(format("%.3d", int(7)), "007");
+    assert_eq!(format("%08x", int(0xbeef)), "0000beef");
+    assert_eq!(format("%#X", int(255)), "0XFF");
+    assert_eq!(format("%#o", int(8)), "010");
+    assert_eq!(format("%hhd", int(255)), "-1");
+    assert_eq!(format("%lld", ArgValue::DoubleWord(-5i64 as u64)), "-5");
+
+    assert_eq!(format("%f", double(1.5)), "1.500000");
+    assert_eq!(format("%5.2f", double(1.23456)), " 1.23");
+    assert_eq!(format("%08.3f", double(-2.5)), "-002.500");
+    assert_eq!(format("%e", double(12345.678)), "1.234568e+04");
+    assert_eq!(format("%E", double(0.00012)), "1.200000E-04");
+    assert_eq!(format("%g", double(0.0001)), "0.0001");
+    assert_eq!(format("%g", double(100000.0)), "100000");
+    assert_eq!(format("%g", double(1000000.0)), "1e+06");
+    assert_eq!(format("%g", double(1.5)), "1.5");
+    assert_eq!(format("%G", double(f64::INFINITY)), "INF");
+    assert_eq!(format("%f", double(-f64::INFINITY)), "-inf");
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_format() {
+    let spec = |piece: &Piece| -> Spec {
+        let Piece::Spec(spec) = piece else {
+            panic!();
+        };
+        spec.clone()
+    };
+
+    let (pieces, arg_types) = parse_format(b"%2$s is %1$d%%");
+    assert_eq!(arg_types, [ArgType::Word, ArgType::Word]);
+    assert_eq!(pieces.len(), 4);
+    assert_eq!(
+        (spec(&pieces[0]).conversion, spec(&pieces[0]).arg),
+        (b's', 1)
+    );
+    assert_eq!(pieces[1], Piece::Literal(b" is ".to_vec()));
+    assert_eq!(
+        (spec(&pieces[2]).conversion, spec(&pieces[2]).arg),
+        (b'd', 0)
+    );
+    assert_eq!(pieces[3], Piece::Literal(b"%".to_vec()));
+
+    let (pieces, arg_types) = parse_format(b"%*.*f %lld");
+    assert_eq!(
+        arg_types,
+        [
+            ArgType::Word,
+            ArgType::Word,
+            ArgType::Double,
+            ArgType::DoubleWord
+        ]
+    );
+    let float = spec(&pieces[0]);
+    assert_eq!(float.width, Some(Count::Arg(0)));
+    assert_eq!(float.precision, Some(Count::Arg(1)));
+    assert_eq!(float.arg, 2);
+    assert_eq!(spec(&pieces[2]).length, Length::LongLong);
+
+    // Unsupported or incomplete specifications are output verbatim.
+    assert_eq!(
+        parse_format(b"%y 100%").0,
+        [Piece::Literal(b"%y 100%".to_vec())]
+    );
+}
@@ -9,7 +9,9 @@ use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr};
 use crate::Environment;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
 
 pub mod qsort;
 
@@ -17,6 +19,9 @@ pub mod qsort;
 pub struct State {
     rand: u32,
     random: u32,
+    /// State for `arc4random()` and friends. This is seeded on first use,
+    /// see [arc4random_state].
+    arc4random: Option<u32>,
     env: HashMap<Vec<u8>, MutPtr<u8>>,
 }
 
@@ -135,8 +140,20 @@ fn prng(state: u32) -> u32 {
 
 const RAND_MAX: i32 = i32::MAX;
 
+/// If the user has asked for a fixed seed with `--random-seed=`, this
+/// overrides whatever seed the app chooses, so that runs are reproducible even
+/// if the app seeds with the current time.
+fn override_seed(env: &mut Environment, seed: u32) -> u32 {
+    if let Some(fixed_seed) = env.options.random_seed {
+        log_dbg!("Replacing seed {} with fixed seed {}", seed, fixed_seed);
+        fixed_seed
+    } else {
+        seed
+    }
+}
+
 fn srand(env: &mut Environment, seed: u32) {
-    env.libc_state.stdlib.rand = seed;
+    env.libc_state.stdlib.rand = override_seed(env, seed);
 }
 fn rand(env: &mut Environment) -> i32 {
     env.libc_state.stdlib.rand = prng(env.libc_state.stdlib.rand);
@@ -146,13 +163,49 @@ fn rand(env: &mut Environment) -> i32 {
 // BSD's "better" random number generator, with an implementation that is not
 // actually better.
 fn srandom(env: &mut Environment, seed: u32) {
-    env.libc_state.stdlib.random = seed;
+    env.libc_state.stdlib.random = override_seed(env, seed);
 }
 fn random(env: &mut Environment) -> i32 {
     env.libc_state.stdlib.random = prng(env.libc_state.stdlib.random);
     (env.libc_state.stdlib.random as i32) & RAND_MAX
 }
 
+/// Get a mutable reference to the `arc4random()` state, seeding it if this is
+/// the first use. Real `arc4random()` can't be seeded by the app and is
+/// different every run, so the host's randomness is used, unless the user has
+/// asked for a fixed seed with `--random-seed=`.
+fn arc4random_state(env: &mut Environment) -> &mut u32 {
+    let fixed_seed = env.options.random_seed;
+    env.libc_state.stdlib.arc4random.get_or_insert_with(|| {
+        fixed_seed.unwrap_or_else(|| RandomState::new().build_hasher().finish() as u32)
+    })
+}
+fn arc4random(env: &mut Environment) -> u32 {
+    let state = arc4random_state(env);
+    *state = prng(*state);
+    *state
+}
+fn arc4random_uniform(env: &mut Environment, upper_bound: u32) -> u32 {
+    if upper_bound < 2 {
+        return 0;
+    }
+    // Avoid modulo bias by rejecting values below 2**32 % upper_bound.
+    let min = upper_bound.wrapping_neg() % upper_bound;
+    loop {
+        let value = arc4random(env);
+        if value >= min {
+            return value % upper_bound;
+        }
+    }
+}
+fn arc4random_buf(env: &mut Environment, buf: MutVoidPtr, nbytes: GuestUSize) {
+    let buf: MutPtr<u8> = buf.cast();
+    for i in 0..nbytes {
+        let byte = arc4random(env) as u8;
+        env.mem.write(buf + i, byte);
+    }
+}
+
 fn getenv(env: &mut Environment, name: ConstPtr<u8>) -> MutPtr<u8> {
     let name_cstr = env.mem.cstr_at(name);
     // TODO: Provide all the system environment variables an app might expect to
@@ -245,6 +298,9 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(rand()),
     export_c_func!(srandom(_)),
     export_c_func!(random()),
+    export_c_func!(arc4random()),
+    export_c_func!(arc4random_uniform(_)),
+    export_c_func!(arc4random_buf(_, _)),
     export_c_func!(getenv(_)),
     export_c_func!(setenv(_, _, _)),
     export_c_func!(exit(_)),
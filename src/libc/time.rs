@@ -25,7 +25,9 @@ pub struct State {
 type time_t = i32;
 
 fn time(env: &mut Environment, out: MutPtr<time_t>) -> time_t {
-    let time64 = SystemTime::now()
+    let time64 = env
+        .clock
+        .system_now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
@@ -245,14 +247,29 @@ fn gmtime(env: &mut Environment, timestamp: ConstPtr<time_t>) -> MutPtr<tm> {
 }
 
 fn localtime_r(env: &mut Environment, timestamp: ConstPtr<time_t>, res: MutPtr<tm>) -> MutPtr<tm> {
-    // TODO: don't assume local time is UTC?
-    gmtime_r(env, timestamp, res)
+    // The local time zone is a fixed offset from UTC, chosen by the user (see
+    // --time-zone-offset=).
+    let offset = env.options.time_zone_offset;
+    let timestamp = env.mem.read(timestamp);
+    let calendar_date = timestamp_to_calendar_date(timestamp.wrapping_add(offset));
+    env.mem.write(
+        res,
+        tm {
+            tm_gmtoff: offset,
+            ..calendar_date
+        },
+    );
+    res
 }
 fn localtime(env: &mut Environment, timestamp: ConstPtr<time_t>) -> MutPtr<tm> {
-    // TODO: don't assume local time is UTC?
     // This doesn't have to be a unique temporary, gmtime and localtime are
     // allowed to share it.
-    gmtime(env, timestamp)
+    let tmp = *env
+        .libc_state
+        .time
+        .gmtime_tmp
+        .get_or_insert_with(|| env.mem.alloc(guest_size_of::<tm>()).cast());
+    localtime_r(env, timestamp, tmp)
 }
 
 // sys/time.h (POSIX)
@@ -295,7 +312,9 @@ fn gettimeofday(
         return 0; // success
     }
 
-    let time = SystemTime::now()
+    let time = env
+        .clock
+        .system_now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
 
@@ -4,7 +4,7 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! Reading of Mach-O files, the executable and library format on iPhone OS.
-//! Currently only handles executables.
+//! Handles executables and dynamic libraries.
 //!
 //! Implemented using the mach_object crate. All usage of that crate should be
 //! confined to this module. The goal is to read the Mach-O binary exactly once,
@@ -22,8 +22,9 @@ use crate::abi::GuestFunction;
 use crate::fs::{Fs, GuestPath};
 use crate::mem::{Mem, Ptr};
 use mach_object::{
-    DyLib, LoadCommand, MachCommand, OFile, Symbol, SymbolIter, ThreadState, N_ARM_THUMB_DEF,
-    S_LAZY_SYMBOL_POINTERS, S_MOD_INIT_FUNC_POINTERS, S_NON_LAZY_SYMBOL_POINTERS, S_SYMBOL_STUBS,
+    DyLib, LoadCommand, MachCommand, OFile, Symbol, SymbolIter, ThreadState, MH_DYLIB,
+    MH_SPLIT_SEGS, MH_TWOLEVEL, N_ARM_THUMB_DEF, S_LAZY_SYMBOL_POINTERS, S_MOD_INIT_FUNC_POINTERS,
+    S_NON_LAZY_SYMBOL_POINTERS, S_SYMBOL_STUBS,
 };
 use std::collections::HashMap;
 use std::io::{Cursor, Seek, SeekFrom};
@@ -32,8 +33,18 @@ use std::io::{Cursor, Seek, SeekFrom};
 pub struct MachO {
     /// Name (for debugging purposes)
     pub name: String,
-    /// Paths of dynamic libraries referenced by the binary.
+    /// For a dynamic library, the install name other binaries refer to it by
+    /// (see [Self::dynamic_libraries]).
+    pub install_name: Option<String>,
+    /// Paths of dynamic libraries referenced by the binary. The order matters
+    /// for [Self::library_ordinals].
     pub dynamic_libraries: Vec<String>,
+    /// If the binary uses a two-level namespace, this maps the names of the
+    /// symbols it imports to the libraries they were found in at build time.
+    /// These are 1-based indices into [Self::dynamic_libraries], or one of the
+    /// special values [SELF_LIBRARY_ORDINAL], [EXECUTABLE_ORDINAL] and
+    /// [DYNAMIC_LOOKUP_ORDINAL].
+    pub library_ordinals: HashMap<String, u8>,
     /// Metadata related to sections.
     pub sections: Vec<Section>,
     /// Symbols exported by the binary. This is a hashmap so the dynamic linker
@@ -45,8 +56,22 @@ pub struct MachO {
     pub external_relocations: Vec<(u32, String)>,
     /// Address/program counter value for the entry point.
     pub entry_point_pc: Option<u32>,
+    /// Difference between the address the binary was loaded at and the
+    /// address it was linked for. This is only ever non-zero for dynamic
+    /// libraries, which can be moved if their preferred address is taken.
+    pub slide: u32,
 }
 
+/// Special value in [MachO::library_ordinals]: the symbol is in the same
+/// binary.
+pub const SELF_LIBRARY_ORDINAL: u8 = 0;
+/// Special value in [MachO::library_ordinals]: the symbol is in the main
+/// executable.
+pub const EXECUTABLE_ORDINAL: u8 = 0xfe;
+/// Special value in [MachO::library_ordinals]: the symbol should be looked up
+/// in all loaded binaries, like with a flat namespace.
+pub const DYNAMIC_LOOKUP_ORDINAL: u8 = 0xff;
+
 #[derive(Debug)]
 pub struct Section {
     /// Section name.
@@ -136,7 +161,6 @@ enum Reloc {
         size: u32,
         type_: u32,
     },
-    #[allow(dead_code)]
     Local {
         addr: u32,
         section_idx: u32,
@@ -144,7 +168,6 @@ enum Reloc {
         size: u32,
         type_: u32,
     },
-    #[allow(dead_code)]
     Scattered {
         offset: u32,
         value: u32,
@@ -245,13 +268,75 @@ impl MachO {
         }
         // TODO: Check cpusubtype (should be some flavour of ARMv6/ARMv7)
 
+        let is_dylib = header.filetype == MH_DYLIB;
+        let is_two_level = (header.flags & MH_TWOLEVEL) != 0;
+
+        // Find where the binary wants to be loaded. Relocation addresses are
+        // relative to the first segment, or the first writeable segment if
+        // they are split.
+        let mut preferred_ranges = Vec::new();
+        let mut reloc_base: Option<u32> = None;
+        for MachCommand(command, _size) in &commands {
+            if let LoadCommand::Segment {
+                segname,
+                vmaddr,
+                vmsize,
+                ..
+            } = command
+            {
+                let vmaddr: u32 = (*vmaddr).try_into().unwrap();
+                let vmsize: u32 = (*vmsize).try_into().unwrap();
+                if (header.flags & MH_SPLIT_SEGS) == 0 || segname == "__DATA" {
+                    reloc_base.get_or_insert(vmaddr);
+                }
+                if segname != "__PAGEZERO" && segname != "__LINKEDIT" && vmsize > 0 {
+                    preferred_ranges.push((vmaddr, vmsize));
+                }
+            }
+        }
+        let reloc_base = reloc_base.unwrap_or(0);
+
+        // Executables must be loaded where they want to be, but a dynamic
+        // library can be moved (its "slide") if that space is already taken.
+        let slide = if !is_dylib
+            || preferred_ranges
+                .iter()
+                .all(|&(vmaddr, vmsize)| into_mem.can_reserve(vmaddr, vmsize))
+        {
+            0
+        } else {
+            let start = preferred_ranges
+                .iter()
+                .map(|&(addr, _)| addr)
+                .min()
+                .unwrap();
+            let end = preferred_ranges
+                .iter()
+                .map(|&(addr, size)| addr + size)
+                .max()
+                .unwrap();
+            // Segments must be page-aligned.
+            const PAGE_SIZE: u32 = 0x1000;
+            let alloc_base = into_mem.alloc(end - start + PAGE_SIZE).to_bits();
+            let base = (alloc_base + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+            log!(
+                "{:?} can't be loaded at its preferred address {:#x}, moving it to {:#x}",
+                name,
+                start,
+                base
+            );
+            base.wrapping_sub(start)
+        };
+
         // Info used while parsing file
         let mut text_segment_base: Option<u32> = None;
         let mut all_sections = Vec::new();
         let mut sym_tab_info: Option<(u32, u32, u32, u32)> = None;
 
         // Info used for the result
+        let mut install_name = None;
         let mut dynamic_libraries = Vec::new();
+        let mut library_ordinals = HashMap::new();
         let mut exported_symbols = HashMap::new();
         let mut indirect_undef_symbols: Vec<Option<String>> = Vec::new();
         let mut external_relocations: Vec<(u32, String)> = Vec::new();
@@ -269,6 +354,7 @@ impl MachO {
                     ..
                 } => {
                     let vmaddr: u32 = vmaddr.try_into().unwrap();
+                    let vmaddr = vmaddr.wrapping_add(slide);
                     let vmsize: u32 = vmsize.try_into().unwrap();
                     let filesize: u32 = filesize.try_into().unwrap();
 
@@ -295,8 +381,16 @@ impl MachO {
                         }
                     };
 
-                    if load_me {
-                        into_mem.reserve(vmaddr, vmsize);
+                    if load_me && vmsize > 0 {
+                        if slide == 0 {
+                            into_mem.reserve(vmaddr, vmsize);
+                        } else {
+                            // The memory was allocated rather than reserved,
+                            // so it might not have been zeroed.
+                            into_mem
+                                .bytes_at_mut(Ptr::from_bits(vmaddr), vmsize)
+                                .fill(0);
+                        }
 
                         // If filesize is less than vmsize, the rest of the
                         // segment should be filled with zeroes. We are assuming
@@ -343,6 +437,7 @@ impl MachO {
                             } = symbol
                             {
                                 let entry: u32 = entry.try_into().unwrap();
+                                let entry = entry.wrapping_add(slide);
                                 let entry = if desc & N_ARM_THUMB_DEF != 0 {
                                     entry | GuestFunction::THUMB_BIT
                                 } else {
@@ -350,6 +445,23 @@ impl MachO {
                                 };
                                 exported_symbols.insert(name.to_string(), entry);
                             };
+                            // The library ordinal is in the high byte of
+                            // n_desc for an undefined symbol.
+                            if let Symbol::Undefined {
+                                name: Some(name),
+                                desc,
+                                ..
+                            }
+                            | Symbol::Prebound {
+                                name: Some(name),
+                                desc,
+                                ..
+                            } = symbol
+                            {
+                                if is_two_level {
+                                    library_ordinals.insert(name.to_string(), (desc >> 8) as u8);
+                                }
+                            }
                         }
                     }
                 }
@@ -358,6 +470,8 @@ impl MachO {
                     nindirectsyms,
                     extreloff,
                     nextrel,
+                    locreloff,
+                    nlocrel,
                     ..
                 } => {
                     let indirectsyms =
@@ -412,8 +526,50 @@ impl MachO {
                         let Some(Symbol::Undefined { name: Some(n), .. }) = sym else {
                             continue;
                         };
+                        let addr = reloc_base.wrapping_add(slide).wrapping_add(addr);
                         external_relocations.push((addr, String::from(n)));
                     }
+
+                    // Local relocations are only needed to "rebase" a binary
+                    // that wasn't loaded at its preferred address. Pointers
+                    // within the binary need to be moved by the same amount.
+                    let locrels = &bytes[locreloff as usize..][..nlocrel as usize * 8];
+                    for entry in locrels.chunks(8) {
+                        if slide == 0 {
+                            break;
+                        }
+                        let reloc = Reloc::parse(is_bigend, entry.try_into().unwrap());
+                        let addr = match reloc {
+                            Reloc::Local {
+                                section_idx: 0, // R_ABS, not affected by sliding
+                                ..
+                            } => continue,
+                            Reloc::Local {
+                                addr,
+                                is_pc_relative: false,
+                                size: 4,
+                                type_: 0, // generic
+                                ..
+                            } => addr,
+                            Reloc::Scattered {
+                                offset,
+                                is_pc_relative: false,
+                                size: 4,
+                                // generic, or ARM_RELOC_PB_LA_PTR (prebound
+                                // lazy pointer)
+                                type_: 0 | 7,
+                                ..
+                            } => offset,
+                            _ => {
+                                log!("Warning: unhandled local relocation {:?}", reloc);
+                                continue;
+                            }
+                        };
+                        let ptr: crate::mem::MutPtr<u32> =
+                            Ptr::from_bits(reloc_base.wrapping_add(slide).wrapping_add(addr));
+                        let value = into_mem.read(ptr);
+                        into_mem.write(ptr, value.wrapping_add(slide));
+                    }
                 }
                 LoadCommand::EncryptionInfo { id, .. } => {
                     if id != 0 {
@@ -422,7 +578,12 @@ impl MachO {
                         );
                     }
                 }
-                LoadCommand::LoadDyLib(DyLib { name, .. }) => {
+                LoadCommand::IdDyLib(DyLib { name, .. }) => {
+                    install_name = Some(String::from(&*name));
+                }
+                // Weak libraries count for the library ordinals too.
+                LoadCommand::LoadDyLib(DyLib { name, .. })
+                | LoadCommand::LoadWeakDyLib(DyLib { name, .. }) => {
                     dynamic_libraries.push(String::from(&*name));
                 }
                 // Old-style entry point PC command
@@ -438,7 +599,7 @@ impl MachO {
                     };
                     // There should only be a single initial thread state.
                     assert!(entry_point_pc.is_none());
-                    entry_point_pc = Some(pc);
+                    entry_point_pc = Some(pc.wrapping_add(slide));
                 }
                 // New-style entry point PC command
                 LoadCommand::EntryPoint {
@@ -470,6 +631,7 @@ impl MachO {
 
                 let name = section.sectname.clone();
                 let addr: u32 = section.addr.try_into().unwrap();
+                let addr = addr.wrapping_add(slide);
                 let size: u32 = section.size.try_into().unwrap();
                 let type_ = section.flags.sect_type();
 
@@ -516,11 +678,14 @@ impl MachO {
 
         Ok(MachO {
             name,
+            install_name,
             dynamic_libraries,
+            library_ordinals,
             sections,
             exported_symbols,
             external_relocations,
             entry_point_pc,
+            slide,
         })
     }
 
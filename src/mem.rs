@@ -219,6 +219,9 @@ pub struct Mem {
     bytes: *mut Bytes,
 
     allocator: allocator::Allocator,
+
+    /// See [Self::set_bounds_checking].
+    bounds_checking: bool,
 }
 
 impl Drop for Mem {
@@ -263,7 +266,11 @@ impl Mem {
 
         let allocator = allocator::Allocator::new();
 
-        Mem { bytes, allocator }
+        Mem {
+            bytes,
+            allocator,
+            bounds_checking: false,
+        }
     }
 
     /// Take an existing instance of [Mem], but free and zero all the
@@ -275,6 +282,7 @@ impl Mem {
         let Mem {
             bytes: _,
             ref mut allocator,
+            bounds_checking: _,
         } = mem;
         let used_chunks = allocator.reset_and_drain_used_chunks();
         for allocator::Chunk { base, size } in used_chunks {
@@ -283,6 +291,18 @@ impl Mem {
         mem
     }
 
+    /// Enable or disable bounds checking. When it is enabled, every access
+    /// made through this type is checked against the allocations and reserved
+    /// regions (binary segments, stacks) known to the allocator, and a panic
+    /// occurs if any byte is outside of them. This is very slow and only meant
+    /// for debugging.
+    ///
+    /// Note that accesses made by the CPU emulation only go through this type
+    /// when direct memory access is disabled.
+    pub fn set_bounds_checking(&mut self, enabled: bool) {
+        self.bounds_checking = enabled;
+    }
+
     /// Get a pointer to the full 4GiB of memory. This is only for use when
     /// setting up the CPU, never call this otherwise.
     ///
@@ -310,10 +330,28 @@ impl Mem {
         )
     }
 
+    #[cold]
+    fn bounds_check_fail(at: VAddr, size: GuestUSize) {
+        panic!(
+            "Attempted access to unmapped memory at {:#x} ({:#x} bytes)",
+            at, size
+        )
+    }
+
+    /// Panic if bounds checking is enabled and the range is not entirely in
+    /// use. Empty ranges are never checked.
+    fn bounds_check(&self, at: VAddr, size: GuestUSize) {
+        if self.bounds_checking && size > 0 && !self.allocator.is_in_use(at, size) {
+            Self::bounds_check_fail(at, size)
+        }
+    }
+
     /// Special version of [Self::bytes_at] that returns [None] rather than
     /// panicking on failure. Only for use by [crate::gdb::GdbServer].
     pub fn get_bytes_fallible(&self, addr: ConstVoidPtr, count: GuestUSize) -> Option<&[u8]> {
-        if addr.to_bits() < Self::NULL_PAGE_SIZE {
+        if addr.to_bits() < Self::NULL_PAGE_SIZE
+            || (self.bounds_checking && !self.allocator.is_in_use(addr.to_bits(), count))
+        {
             return None;
         }
         self.bytes()
@@ -327,7 +365,9 @@ impl Mem {
         addr: ConstVoidPtr,
         count: GuestUSize,
     ) -> Option<&mut [u8]> {
-        if addr.to_bits() < Self::NULL_PAGE_SIZE {
+        if addr.to_bits() < Self::NULL_PAGE_SIZE
+            || (self.bounds_checking && !self.allocator.is_in_use(addr.to_bits(), count))
+        {
             return None;
         }
         self.bytes_mut()
@@ -346,6 +386,7 @@ impl Mem {
         if ptr.to_bits() < Self::NULL_PAGE_SIZE {
             Self::null_check_fail(ptr.to_bits(), count)
         }
+        self.bounds_check(ptr.to_bits(), count);
         &self.bytes()[ptr.to_bits() as usize..][..count as usize]
     }
     /// Get a slice for reading or writing `count` bytes. This is the basic
@@ -359,6 +400,7 @@ impl Mem {
         if ptr.to_bits() < Self::NULL_PAGE_SIZE {
             Self::null_check_fail(ptr.to_bits(), count)
         }
+        self.bounds_check(ptr.to_bits(), count);
         &mut self.bytes_mut()[ptr.to_bits() as usize..][..count as usize]
     }
 
@@ -428,6 +470,8 @@ impl Mem {
 
     /// C-style `memmove`.
     pub fn memmove(&mut self, dest: MutVoidPtr, src: ConstVoidPtr, size: GuestUSize) {
+        self.bounds_check(src.to_bits(), size);
+        self.bounds_check(dest.to_bits(), size);
         let src = src.to_bits() as usize;
         let dest = dest.to_bits() as usize;
         let size = size as usize;
@@ -458,7 +502,8 @@ impl Mem {
     /// Free an allocation made with one of the `alloc` methods on this type.
     pub fn free(&mut self, ptr: MutVoidPtr) {
         let size = self.allocator.free(ptr.to_bits());
-        self.bytes_at_mut(ptr.cast(), size).fill(0);
+        // The allocation is no longer in use, so this can't be bounds-checked.
+        self.bytes_mut()[ptr.to_bits() as usize..][..size as usize].fill(0);
         log_dbg!("Freed {:?} ({:#x} bytes)", ptr, size);
     }
 
@@ -506,4 +551,11 @@ impl Mem {
     pub fn reserve(&mut self, base: VAddr, size: GuestUSize) {
         self.allocator.reserve(allocator::Chunk::new(base, size));
     }
+
+    /// Check whether a region of address space is entirely unused, so that
+    /// [Self::reserve] would succeed.
+    pub fn can_reserve(&self, base: VAddr, size: GuestUSize) -> bool {
+        self.allocator
+            .can_reserve(allocator::Chunk::new(base, size))
+    }
 }
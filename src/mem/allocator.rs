@@ -129,6 +129,14 @@ impl Allocator {
         panic!("Could not reserve chunk {:?}!", chunk);
     }
 
+    /// Check whether [Self::reserve] would succeed for a chunk, i.e. whether
+    /// none of it is in use.
+    pub fn can_reserve(&self, chunk: Chunk) -> bool {
+        self.unused_chunks
+            .iter()
+            .any(|unused_chunk| unused_chunk.trisect_by(chunk).is_some())
+    }
+
     pub fn alloc(&mut self, size: GuestUSize) -> VAddr {
         // TODO: use a better allocation strategy, probably using buckets.
 
@@ -224,9 +232,64 @@ impl Allocator {
         size
     }
 
+    /// Check that every byte in a range of address space is in use, i.e. that
+    /// it belongs to an allocation or a reserved region. This is slow and is
+    /// only used when bounds checking is enabled (see [Mem::set_bounds_checking]).
+    pub fn is_in_use(&self, base: VAddr, size: GuestUSize) -> bool {
+        // The range may span several adjacent chunks.
+        let end = base as u64 + size as u64;
+        let mut addr = base as u64;
+        while addr < end {
+            let Some(chunk) = self
+                .used_chunks
+                .iter()
+                .find(|chunk| chunk.contains(addr as VAddr))
+            else {
+                return false;
+            };
+            addr = chunk.last_byte() as u64 + 1;
+        }
+        true
+    }
+
     pub(super) fn reset_and_drain_used_chunks(&mut self) -> Vec<Chunk> {
         let chunks = std::mem::take(&mut self.used_chunks);
         *self = Allocator::new();
         chunks
     }
 }
+
+#[cfg(test)]
+#[test]
+fn test_is_in_use() {
+    let mut allocator = Allocator::new();
+    allocator.reserve(Chunk::new(0x2000, 0x100));
+    allocator.reserve(Chunk::new(0x2100, 0x100));
+    let alloc = allocator.alloc(0x20);
+
+    assert!(allocator.is_in_use(0x2000, 0x100));
+    // Spanning two adjacent chunks
+    assert!(allocator.is_in_use(0x20f0, 0x20));
+    assert!(!allocator.is_in_use(0x21f0, 0x20));
+    assert!(allocator.is_in_use(alloc, 0x20));
+    assert!(!allocator.is_in_use(alloc + 0x10, 0x20));
+
+    let _ = allocator.free(alloc);
+    assert!(!allocator.is_in_use(alloc, 1));
+    // The main thread stack is always in use
+    assert!(allocator.is_in_use(u32::MAX, 1));
+}
+
+#[cfg(test)]
+#[test]
+fn test_can_reserve() {
+    let mut allocator = Allocator::new();
+    assert!(allocator.can_reserve(Chunk::new(0x2000, 0x1000)));
+    allocator.reserve(Chunk::new(0x2000, 0x1000));
+    assert!(!allocator.can_reserve(Chunk::new(0x2000, 0x1000)));
+    // Partially overlapping
+    assert!(!allocator.can_reserve(Chunk::new(0x1800, 0x1000)));
+    assert!(allocator.can_reserve(Chunk::new(0x3000, 0x1000)));
+    // The null page is always in use
+    assert!(!allocator.can_reserve(Chunk::new(0, 0x1000)));
+}
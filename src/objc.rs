@@ -19,20 +19,28 @@
 //! categories and dynamic class editing).
 
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::MutexId;
-use std::collections::HashMap;
+use crate::mem::{ConstPtr, MutPtr};
+use crate::{MutexId, ThreadId};
+use std::collections::{HashMap, HashSet};
 
+mod arc;
+mod associated_objects;
 mod classes;
+mod ivars;
 mod messages;
 mod methods;
 mod objects;
 mod properties;
+mod protocols;
 mod selectors;
 mod synchronization;
+mod weak_references;
 
+pub use associated_objects::release_orphaned_associated_objects;
 pub use classes::{objc_classes, Class, ClassExports, ClassTemplate};
 pub use messages::{
-    autorelease, msg, msg_class, msg_send, msg_send_super2, msg_super, objc_super, release, retain,
+    autorelease, msg, msg_class, msg_send, msg_send_super2, msg_send_with_raw_args, msg_super,
+    objc_super, release, retain,
 };
 pub use methods::{GuestIMP, HostIMP, IMP};
 pub use objects::{
@@ -40,13 +48,40 @@ pub use objects::{
 };
 pub use selectors::{selector, SEL};
 
-use classes::{ClassHostObject, FakeClass, UnimplementedClass, CLASS_LISTS};
-use messages::{objc_msgSend, objc_msgSendSuper2, objc_msgSend_stret};
-use methods::method_list_t;
+use arc::{
+    objc_autorelease, objc_autoreleaseReturnValue, objc_release, objc_retain,
+    objc_retainAutorelease, objc_retainAutoreleaseReturnValue, objc_retainAutoreleasedReturnValue,
+    objc_retainBlock, objc_storeStrong,
+};
+use associated_objects::{
+    objc_getAssociatedObject, objc_removeAssociatedObjects, objc_setAssociatedObject, Associations,
+};
+use classes::{
+    class_getName, class_getSuperclass, class_isMetaClass, objc_allocateClassPair, objc_getClass,
+    objc_lookUpClass, objc_registerClassPair, object_getClass, object_getClassName,
+    ClassHostObject, FakeClass, UnimplementedClass, CLASS_LISTS,
+};
+use ivars::{
+    class_addIvar, class_getInstanceVariable, ivar_getName, ivar_getOffset, ivar_getTypeEncoding,
+    object_getInstanceVariable, object_getIvar, object_setIvar,
+};
+use messages::{
+    objc_msgSend, objc_msgSendSuper, objc_msgSendSuper2, objc_msgSendSuper2_stret,
+    objc_msgSendSuper_stret, objc_msgSend_stret,
+};
+use methods::{
+    class_addMethod, class_getClassMethod, class_getInstanceMethod, class_replaceMethod,
+    method_exchangeImplementations, method_getImplementation, method_list_t,
+    method_setImplementation, Method,
+};
 use objects::{objc_object, HostObjectEntry};
 use properties::{objc_copyStruct, objc_setProperty};
 use selectors::sel_registerName;
 use synchronization::{objc_sync_enter, objc_sync_exit};
+use weak_references::{
+    objc_copyWeak, objc_destroyWeak, objc_initWeak, objc_loadWeak, objc_loadWeakRetained,
+    objc_moveWeak, objc_storeWeak,
+};
 
 /// Typedef for `NSZone *`. This is a [fossil type] found in the signature of
 /// `allocWithZone:` and similar methods. Its value is always ignored.
@@ -69,8 +104,55 @@ pub struct ObjC {
     /// Look at the `isa` to get the metaclass for a class.
     classes: HashMap<String, Class>,
 
+    /// Names of classes created with `objc_allocateClassPair` that have yet to
+    /// be registered. These names can't be reused.
+    pending_class_pairs: HashSet<String>,
+
+    /// Cache of method implementations found by `objc_msgSend`, indexed by the
+    /// class or metaclass of the receiver and the selector. This includes
+    /// inherited methods, so it must be flushed (see
+    /// [ObjC::flush_method_cache]) whenever any class's methods change.
+    method_cache: HashMap<(Class, SEL), IMP>,
+
+    /// Handles returned by `class_getInstanceMethod` and similar, indexed by
+    /// the class that has the method and the selector.
+    method_handles: HashMap<(Class, SEL), Method>,
+
+    /// Reverse mapping for [Self::method_handles].
+    method_handle_targets: HashMap<Method, (Class, SEL)>,
+
+    /// Guest functions created so that host method implementations can be
+    /// returned by `method_getImplementation` and similar. The key is the
+    /// address of the [HostIMP].
+    host_imp_trampolines: HashMap<usize, GuestIMP>,
+
+    /// C strings for class names, allocated on demand by functions like
+    /// `object_getClassName` and never freed.
+    class_name_cstrs: HashMap<String, ConstPtr<u8>>,
+
     /// Mutexes used in @synchronized blocks (objc_sync_enter/exit).
     sync_mutexes: HashMap<id, MutexId>,
+
+    /// Associated objects (objc_setAssociatedObject etc), indexed by the
+    /// object they're associated with.
+    associated_objects: HashMap<id, Associations>,
+
+    /// Associated objects that need to be released because the object that
+    /// owned them was deallocated.
+    orphaned_associated_objects: Vec<id>,
+
+    /// Locations of zeroing weak references (objc_storeWeak etc), indexed by
+    /// the object they refer to.
+    weak_references: HashMap<id, HashSet<MutPtr<id>>>,
+
+    /// Object returned by `objc_autoreleaseReturnValue` without being
+    /// autoreleased, and the thread it was returned on, so that the following
+    /// `objc_retainAutoreleasedReturnValue` knows not to retain it.
+    retained_return_value: Option<(ThreadId, id)>,
+
+    /// +load methods from the app binary that have yet to be called, in the
+    /// order they should be called in. See [ObjC::call_load_methods].
+    load_methods: Vec<(Class, GuestIMP)>,
 }
 
 impl ObjC {
@@ -79,7 +161,18 @@ impl ObjC {
             selectors: HashMap::new(),
             objects: HashMap::new(),
             classes: HashMap::new(),
+            pending_class_pairs: HashSet::new(),
+            method_cache: HashMap::new(),
+            method_handles: HashMap::new(),
+            method_handle_targets: HashMap::new(),
+            host_imp_trampolines: HashMap::new(),
+            class_name_cstrs: HashMap::new(),
             sync_mutexes: HashMap::new(),
+            associated_objects: HashMap::new(),
+            orphaned_associated_objects: Vec::new(),
+            weak_references: HashMap::new(),
+            retained_return_value: None,
+            load_methods: Vec::new(),
         }
     }
 }
@@ -87,10 +180,56 @@ impl ObjC {
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(objc_msgSend(_, _)),
     export_c_func!(objc_msgSend_stret(_, _, _)),
+    export_c_func!(objc_msgSendSuper(_, _)),
     export_c_func!(objc_msgSendSuper2(_, _)),
+    export_c_func!(objc_msgSendSuper_stret(_, _, _)),
+    export_c_func!(objc_msgSendSuper2_stret(_, _, _)),
     export_c_func!(objc_setProperty(_, _, _, _, _, _)),
     export_c_func!(objc_copyStruct(_, _, _, _, _)),
     export_c_func!(objc_sync_enter(_)),
     export_c_func!(objc_sync_exit(_)),
     export_c_func!(sel_registerName(_)),
+    export_c_func!(objc_setAssociatedObject(_, _, _, _)),
+    export_c_func!(objc_getAssociatedObject(_, _)),
+    export_c_func!(objc_removeAssociatedObjects(_)),
+    export_c_func!(objc_retain(_)),
+    export_c_func!(objc_release(_)),
+    export_c_func!(objc_autorelease(_)),
+    export_c_func!(objc_retainAutorelease(_)),
+    export_c_func!(objc_autoreleaseReturnValue(_)),
+    export_c_func!(objc_retainAutoreleaseReturnValue(_)),
+    export_c_func!(objc_retainAutoreleasedReturnValue(_)),
+    export_c_func!(objc_storeStrong(_, _)),
+    export_c_func!(objc_retainBlock(_)),
+    export_c_func!(objc_initWeak(_, _)),
+    export_c_func!(objc_storeWeak(_, _)),
+    export_c_func!(objc_destroyWeak(_)),
+    export_c_func!(objc_loadWeak(_)),
+    export_c_func!(objc_loadWeakRetained(_)),
+    export_c_func!(objc_copyWeak(_, _)),
+    export_c_func!(objc_moveWeak(_, _)),
+    export_c_func!(objc_allocateClassPair(_, _, _)),
+    export_c_func!(objc_registerClassPair(_)),
+    export_c_func!(objc_getClass(_)),
+    export_c_func!(objc_lookUpClass(_)),
+    export_c_func!(object_getClass(_)),
+    export_c_func!(object_getClassName(_)),
+    export_c_func!(class_getSuperclass(_)),
+    export_c_func!(class_getName(_)),
+    export_c_func!(class_isMetaClass(_)),
+    export_c_func!(class_addMethod(_, _, _, _)),
+    export_c_func!(class_getInstanceMethod(_, _)),
+    export_c_func!(class_getClassMethod(_, _)),
+    export_c_func!(class_replaceMethod(_, _, _, _)),
+    export_c_func!(method_getImplementation(_)),
+    export_c_func!(method_setImplementation(_, _)),
+    export_c_func!(method_exchangeImplementations(_, _)),
+    export_c_func!(class_addIvar(_, _, _, _, _)),
+    export_c_func!(class_getInstanceVariable(_, _)),
+    export_c_func!(object_getIvar(_, _)),
+    export_c_func!(object_setIvar(_, _, _)),
+    export_c_func!(object_getInstanceVariable(_, _, _)),
+    export_c_func!(ivar_getName(_)),
+    export_c_func!(ivar_getOffset(_)),
+    export_c_func!(ivar_getTypeEncoding(_)),
 ];
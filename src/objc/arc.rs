@@ -0,0 +1,102 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Runtime support for Automatic Reference Counting (`objc_retain` etc).
+//!
+//! Code compiled with ARC calls these functions instead of sending `retain`,
+//! `release` and `autorelease` messages itself. See also
+//! [super::weak_references].
+//!
+//! Resources:
+//! - [Clang's documentation of the ARC runtime functions](https://clang.llvm.org/docs/AutomaticReferenceCounting.html#runtime-support)
+
+use super::{autorelease, id, nil, release, retain};
+use crate::abi::GuestFunction;
+use crate::cpu::Cpu;
+use crate::libc::blocks;
+use crate::mem::{ConstPtr, MutPtr, Ptr};
+use crate::Environment;
+
+/// `mov r7, r7` in A32, which the compiler places right after a call that
+/// will be followed by `objc_retainAutoreleasedReturnValue`.
+const RETURN_VALUE_MARKER_A32: u32 = 0xe1a07007;
+/// `mov r7, r7` in T32.
+const RETURN_VALUE_MARKER_T32: u16 = 0x463f;
+
+pub(super) fn objc_retain(env: &mut Environment, object: id) -> id {
+    retain(env, object)
+}
+
+pub(super) fn objc_release(env: &mut Environment, object: id) {
+    release(env, object)
+}
+
+pub(super) fn objc_autorelease(env: &mut Environment, object: id) -> id {
+    autorelease(env, object)
+}
+
+pub(super) fn objc_retainAutorelease(env: &mut Environment, object: id) -> id {
+    let object = retain(env, object);
+    autorelease(env, object)
+}
+
+/// Check whether the code the current host function will return to is about
+/// to call `objc_retainAutoreleasedReturnValue`.
+fn caller_accepts_retained_return_value(env: &Environment) -> bool {
+    let return_addr = GuestFunction::from_addr_with_thumb_bit(env.cpu.regs()[Cpu::LR]);
+    let addr = return_addr.addr_without_thumb_bit();
+    if return_addr.is_thumb() {
+        let ptr: ConstPtr<u16> = Ptr::from_bits(addr);
+        env.mem.read(ptr) == RETURN_VALUE_MARKER_T32
+    } else {
+        let ptr: ConstPtr<u32> = Ptr::from_bits(addr);
+        env.mem.read(ptr) == RETURN_VALUE_MARKER_A32
+    }
+}
+
+/// Autorelease an object that is about to be returned, unless the caller will
+/// immediately retain it again, in which case both steps are skipped and the
+/// reference is handed over directly.
+pub(super) fn objc_autoreleaseReturnValue(env: &mut Environment, object: id) -> id {
+    if object != nil && caller_accepts_retained_return_value(env) {
+        log_dbg!(
+            "objc_autoreleaseReturnValue({:?}): eliding autorelease",
+            object
+        );
+        env.objc.retained_return_value = Some((env.current_thread, object));
+        object
+    } else {
+        autorelease(env, object)
+    }
+}
+
+pub(super) fn objc_retainAutoreleaseReturnValue(env: &mut Environment, object: id) -> id {
+    let object = retain(env, object);
+    objc_autoreleaseReturnValue(env, object)
+}
+
+/// Retain an object that was just returned, unless
+/// [objc_autoreleaseReturnValue] already handed over the reference.
+pub(super) fn objc_retainAutoreleasedReturnValue(env: &mut Environment, object: id) -> id {
+    let handed_over = env.objc.retained_return_value.take();
+    if object != nil && handed_over == Some((env.current_thread, object)) {
+        object
+    } else {
+        retain(env, object)
+    }
+}
+
+pub(super) fn objc_storeStrong(env: &mut Environment, location: MutPtr<id>, object: id) {
+    // The new value must be retained before the old one is released, in case
+    // they're the same object.
+    let object = retain(env, object);
+    let old_object = env.mem.read(location);
+    env.mem.write(location, object);
+    release(env, old_object);
+}
+
+pub(super) fn objc_retainBlock(env: &mut Environment, block: id) -> id {
+    blocks::copy_block(env, block)
+}
@@ -0,0 +1,150 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Handling of associated objects (`objc_setAssociatedObject` and friends).
+//!
+//! These let an app attach arbitrary objects to any other object, using a
+//! pointer as a key. This is often used by categories to add state to classes
+//! they don't own.
+//!
+//! Resources:
+//! - [Apple's documentation of `objc_setAssociatedObject`](https://developer.apple.com/documentation/objectivec/1418509-objc_setassociatedobject?language=objc)
+//! - [Apple's documentation of `objc_AssociationPolicy`](https://developer.apple.com/documentation/objectivec/objc_associationpolicy?language=objc)
+
+use super::{id, msg, nil, release, retain, ObjC};
+use crate::mem::{ConstVoidPtr, MutVoidPtr, Ptr};
+use crate::Environment;
+use std::collections::HashMap;
+
+/// The name is standard Objective-C. The values are from Apple's headers.
+#[allow(non_camel_case_types)]
+type objc_AssociationPolicy = u32;
+const OBJC_ASSOCIATION_ASSIGN: objc_AssociationPolicy = 0;
+const OBJC_ASSOCIATION_RETAIN_NONATOMIC: objc_AssociationPolicy = 1;
+const OBJC_ASSOCIATION_COPY_NONATOMIC: objc_AssociationPolicy = 3;
+const OBJC_ASSOCIATION_RETAIN: objc_AssociationPolicy = 0o1401;
+const OBJC_ASSOCIATION_COPY: objc_AssociationPolicy = 0o1403;
+
+/// Whether an associated object is owned by the object it's associated with.
+/// The distinction between retaining and copying only matters when setting the
+/// association, and atomicity doesn't matter to us.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(super) enum AssociationOwnership {
+    Assigned,
+    Owned,
+}
+
+/// Associated objects for a particular object, indexed by key.
+pub(super) type Associations = HashMap<ConstVoidPtr, (id, AssociationOwnership)>;
+
+impl ObjC {
+    /// For use by [ObjC::dealloc_object]: remove the associated objects of an
+    /// object that is being deallocated. Associated objects that were owned by
+    /// it can't be released immediately, because that requires sending
+    /// messages, so they're queued for [release_orphaned_associated_objects].
+    pub(super) fn orphan_associated_objects(&mut self, object: id) {
+        let Some(associations) = self.associated_objects.remove(&object) else {
+            return;
+        };
+        self.orphaned_associated_objects.extend(
+            associations
+                .into_values()
+                .filter(|&(_, ownership)| ownership == AssociationOwnership::Owned)
+                .map(|(value, _)| value),
+        );
+    }
+}
+
+/// Release associated objects whose owners have been deallocated. See
+/// [ObjC::orphan_associated_objects]. This should be called after sending the
+/// `dealloc` message.
+pub fn release_orphaned_associated_objects(env: &mut Environment) {
+    // Releasing an object can cause it to be deallocated, which can orphan
+    // more associated objects.
+    while let Some(value) = env.objc.orphaned_associated_objects.pop() {
+        release(env, value);
+    }
+}
+
+/// Remove an association, releasing the value if it was owned.
+fn remove_association(env: &mut Environment, object: id, key: ConstVoidPtr) {
+    let Some(associations) = env.objc.associated_objects.get_mut(&object) else {
+        return;
+    };
+    let Some((old_value, ownership)) = associations.remove(&key) else {
+        return;
+    };
+    if associations.is_empty() {
+        env.objc.associated_objects.remove(&object);
+    }
+    if ownership == AssociationOwnership::Owned {
+        release(env, old_value);
+    }
+}
+
+pub(super) fn objc_setAssociatedObject(
+    env: &mut Environment,
+    object: id,
+    key: ConstVoidPtr,
+    value: id,
+    policy: objc_AssociationPolicy,
+) {
+    assert!(object != nil);
+
+    // The new value must be retained before the old one is released, in case
+    // they're the same object.
+    let (value, ownership) = if value == nil {
+        (nil, AssociationOwnership::Assigned)
+    } else {
+        match policy {
+            OBJC_ASSOCIATION_ASSIGN => (value, AssociationOwnership::Assigned),
+            OBJC_ASSOCIATION_RETAIN_NONATOMIC | OBJC_ASSOCIATION_RETAIN => {
+                (retain(env, value), AssociationOwnership::Owned)
+            }
+            OBJC_ASSOCIATION_COPY_NONATOMIC | OBJC_ASSOCIATION_COPY => {
+                let zone: MutVoidPtr = Ptr::null();
+                let copy: id = msg![env; value copyWithZone:zone];
+                (copy, AssociationOwnership::Owned)
+            }
+            _ => {
+                log!(
+                    "Warning: Unknown association policy {:#o}, treating as OBJC_ASSOCIATION_ASSIGN",
+                    policy
+                );
+                (value, AssociationOwnership::Assigned)
+            }
+        }
+    };
+
+    remove_association(env, object, key);
+
+    // Setting nil is how an app removes a single association.
+    if value != nil {
+        env.objc
+            .associated_objects
+            .entry(object)
+            .or_default()
+            .insert(key, (value, ownership));
+    }
+}
+
+pub(super) fn objc_getAssociatedObject(env: &mut Environment, object: id, key: ConstVoidPtr) -> id {
+    env.objc
+        .associated_objects
+        .get(&object)
+        .and_then(|associations| associations.get(&key))
+        .map_or(nil, |&(value, _)| value)
+}
+
+pub(super) fn objc_removeAssociatedObjects(env: &mut Environment, object: id) {
+    let Some(associations) = env.objc.associated_objects.remove(&object) else {
+        return;
+    };
+    for (value, ownership) in associations.into_values() {
+        if ownership == AssociationOwnership::Owned {
+            release(env, value);
+        }
+    }
+}
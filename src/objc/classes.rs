@@ -13,12 +13,16 @@
 mod class_lists;
 pub(super) use class_lists::CLASS_LISTS;
 
+use super::ivars::{ivar_list_t, Ivar};
+use super::protocols::{add_protocol_names_from_bin, protocol_list_t};
 use super::{
     id, method_list_t, nil, objc_object, AnyHostObject, HostIMP, HostObject, ObjC, IMP, SEL,
 };
+use crate::abi::CallFromHost;
 use crate::mach_o::MachO;
 use crate::mem::{guest_size_of, ConstPtr, ConstVoidPtr, GuestUSize, Mem, Ptr, SafeRead};
-use std::collections::HashMap;
+use crate::Environment;
+use std::collections::{HashMap, HashSet};
 
 /// Generic pointer to an Objective-C class or metaclass.
 ///
@@ -38,6 +42,16 @@ pub(super) struct ClassHostObject {
     pub(super) is_metaclass: bool,
     pub(super) superclass: Class,
     pub(super) methods: HashMap<SEL, IMP>,
+    /// Names of the protocols this class conforms to, including protocols
+    /// adopted by those protocols, but excluding protocols only conformed to by
+    /// a superclass.
+    pub(super) protocols: HashSet<String>,
+    /// Instance variables declared by this class (not its superclasses),
+    /// indexed by name.
+    pub(super) ivars: HashMap<String, Ivar>,
+    /// Whether `+initialize` has been sent to this class yet (or is being sent
+    /// right now). This is meaningless for metaclasses.
+    pub(super) initialized: bool,
     /// Offset into the allocated memory for the object where the ivars of
     /// instances of this class or metaclass (respectively: normal objects or
     /// classes) should live. This is always >= the value in the superclass.
@@ -93,8 +107,8 @@ struct class_rw_t {
     _reserved: u32,
     name: ConstPtr<u8>,
     base_methods: ConstPtr<method_list_t>,
-    _base_protocols: ConstVoidPtr, // protocol list (TODO)
-    _ivars: ConstVoidPtr,          // ivar list (TODO)
+    base_protocols: ConstPtr<protocol_list_t>,
+    ivars: ConstPtr<ivar_list_t>,
     _weak_ivar_layout: u32,
     _base_properties: ConstVoidPtr, // property list (TODO)
 }
@@ -109,7 +123,7 @@ struct category_t {
     class: Class,
     instance_methods: ConstPtr<method_list_t>,
     class_methods: ConstPtr<method_list_t>,
-    _protocols: ConstVoidPtr,     // protocol list (TODO)
+    protocols: ConstPtr<protocol_list_t>,
     _property_list: ConstVoidPtr, // property list (TODO)
 }
 unsafe impl SafeRead for category_t {}
@@ -325,9 +339,14 @@ impl ClassHostObject {
         objc: &ObjC,
     ) -> Self {
         // For our host implementations we store all data in host objects, so
-        // there are no ivars and the size is always just the isa pointer.
+        // there are no ivars of their own and the size is just that of the
+        // superclass, which is ultimately just the isa pointer.
         // This is true for both classes and normal objects.
-        let size = guest_size_of::<objc_object>();
+        let size = if superclass == nil {
+            guest_size_of::<objc_object>()
+        } else {
+            objc.borrow::<ClassHostObject>(superclass).instance_size
+        };
         ClassHostObject {
             name: template.name.to_string(),
             is_metaclass,
@@ -346,6 +365,9 @@ impl ClassHostObject {
                     (objc.selectors[name], IMP::Host(host_imp))
                 }),
             ),
+            protocols: HashSet::new(),
+            ivars: HashMap::new(),
+            initialized: false,
             // maybe this should be 0 for NSObject? does it matter?
             _instance_start: size,
             instance_size: size,
@@ -361,6 +383,8 @@ impl ClassHostObject {
             instance_size,
             name,
             base_methods,
+            base_protocols,
+            ivars,
             ..
         } = mem.read(data);
 
@@ -371,6 +395,9 @@ impl ClassHostObject {
             is_metaclass,
             superclass,
             methods: HashMap::new(),
+            protocols: HashSet::new(),
+            ivars: HashMap::new(),
+            initialized: false,
             _instance_start: instance_start,
             instance_size,
         };
@@ -379,9 +406,36 @@ impl ClassHostObject {
             host_object.add_methods_from_bin(base_methods, mem, objc);
         }
 
+        add_protocol_names_from_bin(base_protocols, mem, &mut host_object.protocols);
+
+        if !ivars.is_null() {
+            host_object.add_ivars_from_bin(ivars, mem);
+        }
+
         host_object
     }
 
+    /// Create a class or metaclass with no methods or ivars of its own, for
+    /// [objc_allocateClassPair].
+    fn new_for_runtime(
+        name: String,
+        is_metaclass: bool,
+        superclass: Class,
+        instance_size: GuestUSize,
+    ) -> Self {
+        ClassHostObject {
+            name,
+            is_metaclass,
+            superclass,
+            methods: HashMap::new(),
+            protocols: HashSet::new(),
+            ivars: HashMap::new(),
+            initialized: false,
+            _instance_start: instance_size,
+            instance_size,
+        }
+    }
+
     // See methods.rs for binary method parsing
 }
 
@@ -565,9 +619,11 @@ impl ObjC {
 
         assert!(list.size % 4 == 0);
         let base: ConstPtr<Class> = Ptr::from_bits(list.addr);
+        let mut bin_classes = Vec::new();
         for i in 0..(list.size / 4) {
             let class = mem.read(base + i);
             let metaclass = Self::read_isa(class, mem);
+            bin_classes.push(class);
 
             let name = if let Some(fakes) = substitute_classes(mem, class, metaclass) {
                 let (class_host_object, metaclass_host_object) = fakes;
@@ -596,6 +652,65 @@ impl ObjC {
 
             self.classes.insert(name.to_string(), class);
         }
+
+        // This must be done before categories are registered, because a
+        // category's +load method would replace the class's own.
+        if let Some(load_sel) = self.lookup_selector("load") {
+            let mut scheduled = HashSet::new();
+            for class in bin_classes {
+                self.schedule_load_method(class, load_sel, mem, &mut scheduled);
+            }
+        }
+    }
+
+    /// Add a class's own +load method, if it has one, to the list of +load
+    /// methods to call, after doing the same for its superclasses.
+    fn schedule_load_method(
+        &mut self,
+        class: Class,
+        load_sel: SEL,
+        mem: &Mem,
+        scheduled: &mut HashSet<Class>,
+    ) {
+        if !scheduled.insert(class) {
+            return;
+        }
+
+        // Fake and placeholder classes have no methods to call.
+        let Some(&ClassHostObject { superclass, .. }) =
+            self.get_host_object(class).unwrap().as_any().downcast_ref()
+        else {
+            return;
+        };
+        if superclass != nil {
+            self.schedule_load_method(superclass, load_sel, mem, scheduled);
+        }
+
+        let metaclass = Self::read_isa(class, mem);
+        let ClassHostObject { methods, .. } = self.borrow(metaclass);
+        if let Some(&IMP::Guest(imp)) = methods.get(&load_sel) {
+            self.load_methods.push((class, imp));
+        }
+    }
+
+    /// Call all the +load methods of classes and categories from the app
+    /// binary, in the order Apple's runtime would: superclasses before
+    /// subclasses, and classes before categories. This needs to be done before
+    /// static initializers are run.
+    pub fn call_load_methods(env: &mut Environment) {
+        let load_methods = std::mem::take(&mut env.objc.load_methods);
+        if load_methods.is_empty() {
+            return;
+        }
+        let load_sel = env.objc.lookup_selector("load").unwrap();
+        for (class, imp) in load_methods {
+            log_dbg!(
+                "Calling +load for class \"{}\" ({:?})",
+                env.objc.get_class_name(class),
+                class
+            );
+            () = imp.call_from_host(env, (class, load_sel));
+        }
     }
 
     /// For use by [crate::dyld]: register all the categories from the
@@ -613,6 +728,14 @@ impl ObjC {
             let class = data.class;
             let metaclass = Self::read_isa(class, mem);
 
+            if !data.protocols.is_null() {
+                let any = self.get_host_object(class).unwrap().as_any();
+                if !(any.is::<FakeClass>() || any.is::<UnimplementedClass>()) {
+                    let host_obj = self.borrow_mut::<ClassHostObject>(class);
+                    add_protocol_names_from_bin(data.protocols, mem, &mut host_obj.protocols);
+                }
+            }
+
             for (class, methods) in [
                 (class, data.instance_methods),
                 (metaclass, data.class_methods),
@@ -626,21 +749,23 @@ impl ObjC {
                     continue;
                 }
 
-                // Horrible workaround to avoid double-borrowing self:
-                // temporarily replace the class object.
-                let mut host_obj = std::mem::replace(
-                    self.borrow_mut::<ClassHostObject>(class),
-                    ClassHostObject {
-                        name: Default::default(),
-                        is_metaclass: Default::default(),
-                        superclass: nil,
-                        methods: Default::default(),
-                        _instance_start: Default::default(),
-                        instance_size: Default::default(),
-                    },
-                );
+                // The method list must be read before borrowing the class's
+                // host object, because reading it needs to register selectors.
+                let methods = self.read_methods_from_bin(methods, mem);
+
+                if class == metaclass {
+                    let load_sel = self.lookup_selector("load");
+                    if let Some((_, IMP::Guest(imp))) =
+                        methods.iter().find(|&&(sel, _)| Some(sel) == load_sel)
+                    {
+                        self.load_methods.push((data.class, *imp));
+                    }
+                }
+
+                let host_obj = self.borrow_mut::<ClassHostObject>(class);
                 log_dbg!(
-                    "Adding {} methods from guest app category \"{}\" {:?} to {} \"{}\" {:?}",
+                    "Adding {} {} methods from guest app category \"{}\" {:?} to {} \"{}\" {:?}",
+                    methods.len(),
                     if host_obj.is_metaclass {
                         "class"
                     } else {
@@ -656,10 +781,88 @@ impl ObjC {
                     host_obj.name,
                     class,
                 );
-                host_obj.add_methods_from_bin(methods, mem, self);
-                *self.borrow_mut::<ClassHostObject>(class) = host_obj;
+                // Like in Apple's runtime, category methods replace any
+                // existing methods with the same selector, and the category
+                // loaded last wins.
+                host_obj.methods.extend(methods);
             }
         }
+        self.flush_method_cache();
+    }
+
+    /// Create a new class and metaclass at runtime (see
+    /// [objc_allocateClassPair]). Returns [nil] if the name is already in use,
+    /// including by a class that has been allocated but not yet registered.
+    pub fn allocate_class_pair(&mut self, superclass: Class, name: String, mem: &mut Mem) -> Class {
+        if self.classes.contains_key(&name) || self.pending_class_pairs.contains(&name) {
+            log!(
+                "Can't allocate class pair {:?}, the name is already in use",
+                name
+            );
+            return nil;
+        }
+        self.pending_class_pairs.insert(name.clone());
+
+        let class_size = guest_size_of::<objc_object>();
+
+        let metaclass = if superclass == nil {
+            // This is a new root class, so the metaclass is its own metaclass,
+            // like for NSObject (see link_class_inner). Its superclass is set
+            // once the class itself exists.
+            let metaclass_host_object =
+                ClassHostObject::new_for_runtime(name.clone(), true, nil, class_size);
+            let metaclass = mem.alloc_and_write(objc_object { isa: nil });
+            mem.write(metaclass, objc_object { isa: metaclass });
+            self.register_static_object(metaclass, Box::new(metaclass_host_object));
+            metaclass
+        } else {
+            let super_metaclass = Self::read_isa(superclass, mem);
+            // The isa of any metaclass is the root metaclass.
+            let root_metaclass = Self::read_isa(super_metaclass, mem);
+            let metaclass_host_object =
+                ClassHostObject::new_for_runtime(name.clone(), true, super_metaclass, class_size);
+            self.alloc_static_object(root_metaclass, Box::new(metaclass_host_object), mem)
+        };
+
+        // Fake and unimplemented classes have no instance size, so the base
+        // object size is used for them too.
+        let instance_size = self
+            .try_borrow::<ClassHostObject>(superclass)
+            .map_or(guest_size_of::<objc_object>(), |superclass| {
+                superclass.instance_size
+            });
+        let class_host_object =
+            ClassHostObject::new_for_runtime(name, false, superclass, instance_size);
+        let class = self.alloc_static_object(metaclass, Box::new(class_host_object), mem);
+
+        if superclass == nil {
+            // The superclass of a root metaclass is the root class itself.
+            self.borrow_mut::<ClassHostObject>(metaclass).superclass = class;
+        }
+
+        class
+    }
+
+    /// Make a class created by [Self::allocate_class_pair] usable. Classes that
+    /// weren't allocated that way, or were already registered, are ignored.
+    pub fn register_class_pair(&mut self, class: Class) {
+        let name = self.get_class_name(class).to_string();
+        if self.classes.get(&name) == Some(&class) || !self.pending_class_pairs.remove(&name) {
+            log!(
+                "Class {:?} ({:?}) is already registered or wasn't allocated with objc_allocateClassPair, ignoring",
+                name,
+                class
+            );
+            return;
+        }
+        self.classes.insert(name, class);
+    }
+
+    /// Checks if a class is known by name, i.e. it is not a class created at
+    /// runtime that has yet to be registered with [Self::register_class_pair].
+    pub fn class_is_registered(&self, class: Class) -> bool {
+        let name = self.get_class_name(class);
+        self.classes.get(name) == Some(&class)
     }
 
     pub fn class_is_subclass_of(&self, class: Class, superclass: Class) -> bool {
@@ -682,6 +885,48 @@ impl ObjC {
         }
     }
 
+    /// Get the superclass of a class or metaclass. This is [nil] for a root
+    /// class, and also for placeholder classes, whose superclass is unknown.
+    pub fn get_superclass(&self, class: Class) -> Class {
+        let host_object = self.get_host_object(class).unwrap();
+        if let Some(&ClassHostObject { superclass, .. }) = host_object.as_any().downcast_ref() {
+            superclass
+        } else {
+            nil
+        }
+    }
+
+    pub fn class_is_metaclass(&self, class: Class) -> bool {
+        let host_object = self.get_host_object(class).unwrap();
+        if let Some(&ClassHostObject { is_metaclass, .. }) = host_object.as_any().downcast_ref() {
+            is_metaclass
+        } else if let Some(&UnimplementedClass { is_metaclass, .. }) =
+            host_object.as_any().downcast_ref()
+        {
+            is_metaclass
+        } else if let Some(&FakeClass { is_metaclass, .. }) = host_object.as_any().downcast_ref() {
+            is_metaclass
+        } else {
+            panic!();
+        }
+    }
+
+    /// Get the name of a class as a C string, for use by runtime functions
+    /// like [object_getClassName]. The string is allocated the first time it
+    /// is needed and is then never freed. Like in Apple's runtime, the name
+    /// of [nil] is `"nil"`.
+    fn get_class_name_cstr(&mut self, class: Class, mem: &mut Mem) -> ConstPtr<u8> {
+        let name = if class == nil {
+            "nil".to_string()
+        } else {
+            self.get_class_name(class).to_string()
+        };
+        *self
+            .class_name_cstrs
+            .entry(name)
+            .or_insert_with_key(|name| mem.alloc_and_write_cstr(name.as_bytes()).cast_const())
+    }
+
     pub fn get_class_name(&self, class: Class) -> &str {
         let host_object = self.get_host_object(class).unwrap();
         if let Some(ClassHostObject { name, .. }) = host_object.as_any().downcast_ref() {
@@ -695,3 +940,128 @@ impl ObjC {
         }
     }
 }
+
+/// Standard Objective-C runtime function for creating a class at runtime.
+///
+/// The new class can have methods and ivars added to it with
+/// [super::methods::class_addMethod] and [super::ivars::class_addIvar], but
+/// it can't be used until it is registered with [objc_registerClassPair].
+pub(super) fn objc_allocateClassPair(
+    env: &mut Environment,
+    superclass: Class,
+    name: ConstPtr<u8>,
+    extra_bytes: GuestUSize,
+) -> Class {
+    // Indexed ivars in class objects are a very obscure feature, we don't
+    // support them.
+    if extra_bytes != 0 {
+        log!(
+            "TODO: objc_allocateClassPair() with extra_bytes = {}, ignoring",
+            extra_bytes
+        );
+    }
+
+    let name = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    log_dbg!(
+        "objc_allocateClassPair({:?}, {:?}, {})",
+        superclass,
+        name,
+        extra_bytes
+    );
+    env.objc.allocate_class_pair(superclass, name, &mut env.mem)
+}
+
+/// Standard Objective-C runtime function for registering a class created with
+/// [objc_allocateClassPair].
+pub(super) fn objc_registerClassPair(env: &mut Environment, class: Class) {
+    env.objc.register_class_pair(class);
+}
+
+/// Standard Objective-C runtime function for looking up a class by name.
+/// Unlike [objc_lookUpClass], this can make a host implementation of a class
+/// available if it hasn't been used yet.
+pub(super) fn objc_getClass(env: &mut Environment, name: ConstPtr<u8>) -> Class {
+    if name.is_null() {
+        return nil;
+    }
+    let name = env.mem.cstr_at_utf8(name).unwrap();
+    // Apple's runtime would call the class handler here (TODO?)
+    if env.objc.classes.contains_key(name) || ObjC::find_template(name).is_some() {
+        let name = name.to_string();
+        env.objc.get_known_class(&name, &mut env.mem)
+    } else {
+        log!("objc_getClass(): no class named {:?}, returning nil", name);
+        nil
+    }
+}
+
+/// Standard Objective-C runtime function for looking up a class by name.
+pub(super) fn objc_lookUpClass(env: &mut Environment, name: ConstPtr<u8>) -> Class {
+    if name.is_null() {
+        return nil;
+    }
+    let name = env.mem.cstr_at_utf8(name).unwrap();
+    env.objc.classes.get(name).copied().unwrap_or(nil)
+}
+
+/// Standard Objective-C runtime function for getting the class of an object.
+/// For a class, this returns the metaclass.
+pub(super) fn object_getClass(env: &mut Environment, object: id) -> Class {
+    if object == nil {
+        return nil;
+    }
+    ObjC::read_isa(object, &env.mem)
+}
+
+/// Standard Objective-C runtime function for getting the name of the class of
+/// an object.
+pub(super) fn object_getClassName(env: &mut Environment, object: id) -> ConstPtr<u8> {
+    let class = object_getClass(env, object);
+    env.objc.get_class_name_cstr(class, &mut env.mem)
+}
+
+/// Standard Objective-C runtime function for getting the superclass of a class.
+pub(super) fn class_getSuperclass(env: &mut Environment, class: Class) -> Class {
+    if class == nil {
+        return nil;
+    }
+    env.objc.get_superclass(class)
+}
+
+/// Standard Objective-C runtime function for getting the name of a class.
+pub(super) fn class_getName(env: &mut Environment, class: Class) -> ConstPtr<u8> {
+    env.objc.get_class_name_cstr(class, &mut env.mem)
+}
+
+/// Standard Objective-C runtime function for checking if a class is a
+/// metaclass.
+pub(super) fn class_isMetaClass(env: &mut Environment, class: Class) -> bool {
+    if class == nil {
+        return false;
+    }
+    env.objc.class_is_metaclass(class)
+}
+
+#[cfg(test)]
+#[test]
+fn test_superclass_chain() {
+    let mut mem = Mem::new();
+    let mut objc = ObjC::new();
+    objc.register_host_selectors(&mut mem);
+
+    let mut class = objc.get_known_class("NSMutableArray", &mut mem);
+    let mut names = Vec::new();
+    while class != nil {
+        assert!(!objc.class_is_metaclass(class));
+        names.push(objc.get_class_name(class).to_string());
+        class = objc.get_superclass(class);
+    }
+    assert_eq!(names.first().unwrap(), "NSMutableArray");
+    assert!(names.iter().any(|name| name == "NSArray"));
+    assert_eq!(names.last().unwrap(), "NSObject");
+
+    let class = objc.get_known_class("NSMutableArray", &mut mem);
+    let metaclass = ObjC::read_isa(class, &mem);
+    assert!(objc.class_is_metaclass(metaclass));
+    assert_eq!(objc.get_class_name(metaclass), "NSMutableArray");
+}
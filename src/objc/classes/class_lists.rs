@@ -6,39 +6,63 @@
 //! Separate module just for the class lists, since this will probably be a
 //! very long and frequently-updated list.
 
-use crate::frameworks::{core_animation, core_graphics, foundation, media_player, opengles, uikit};
+use crate::frameworks::{
+    core_animation, core_foundation, core_graphics, foundation, media_player, opengles, uikit,
+};
 
 /// All the lists of classes that the runtime should search through.
 pub const CLASS_LISTS: &[super::ClassExports] = &[
-    crate::app_picker::CLASSES, // Not a framework! Special internal classes.
+    crate::app_picker::CLASSES,   // Not a framework! Special internal classes.
+    crate::libc::blocks::CLASSES, // Not a framework! Part of libSystem.
+    crate::libc::dispatch::CLASSES, // Not a framework! Part of libSystem.
+    core_animation::ca_animation::CLASSES,
+    core_animation::ca_display_link::CLASSES,
     core_animation::ca_eagl_layer::CLASSES,
+    core_animation::ca_gradient_layer::CLASSES,
     core_animation::ca_layer::CLASSES,
+    core_animation::ca_shape_layer::CLASSES,
+    core_animation::ca_transaction::CLASSES,
+    core_foundation::cf_run_loop::CLASSES,
     core_graphics::cg_data_provider::CLASSES,
+    core_graphics::cg_color::CLASSES,
     core_graphics::cg_color_space::CLASSES,
     core_graphics::cg_context::CLASSES,
     core_graphics::cg_image::CLASSES,
+    core_graphics::cg_path::CLASSES,
     foundation::ns_array::CLASSES,
     foundation::ns_autorelease_pool::CLASSES,
     foundation::ns_bundle::CLASSES,
     foundation::ns_character_set::CLASSES,
     foundation::ns_coder::CLASSES,
     foundation::ns_data::CLASSES,
+    foundation::ns_date::CLASSES,
+    foundation::ns_date_formatter::CLASSES,
     foundation::ns_dictionary::CLASSES,
     foundation::ns_enumerator::CLASSES,
+    foundation::ns_error::CLASSES,
     foundation::ns_file_manager::CLASSES,
+    foundation::ns_invocation::CLASSES,
     foundation::ns_keyed_unarchiver::CLASSES,
     foundation::ns_locale::CLASSES,
+    foundation::ns_method_signature::CLASSES,
     foundation::ns_notification::CLASSES,
     foundation::ns_notification_center::CLASSES,
     foundation::ns_null::CLASSES,
+    foundation::ns_number_formatter::CLASSES,
     foundation::ns_object::CLASSES,
+    foundation::ns_predicate::CLASSES,
     foundation::ns_process_info::CLASSES,
+    foundation::ns_property_list_serialization::CLASSES,
     foundation::ns_run_loop::CLASSES,
     foundation::ns_set::CLASSES,
     foundation::ns_string::CLASSES,
     foundation::ns_thread::CLASSES,
+    foundation::ns_time_zone::CLASSES,
     foundation::ns_timer::CLASSES,
     foundation::ns_url::CLASSES,
+    foundation::ns_url_connection::CLASSES,
+    foundation::ns_url_request::CLASSES,
+    foundation::ns_url_response::CLASSES,
     foundation::ns_user_defaults::CLASSES,
     foundation::ns_value::CLASSES,
     media_player::movie_player::CLASSES,
@@ -49,6 +73,10 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     uikit::ui_device::CLASSES,
     uikit::ui_event::CLASSES,
     uikit::ui_font::CLASSES,
+    uikit::ui_gesture_recognizer::CLASSES,
+    uikit::ui_gesture_recognizer::ui_pan_gesture_recognizer::CLASSES,
+    uikit::ui_gesture_recognizer::ui_pinch_gesture_recognizer::CLASSES,
+    uikit::ui_gesture_recognizer::ui_tap_gesture_recognizer::CLASSES,
     uikit::ui_image::CLASSES,
     uikit::ui_image_picker_controller::CLASSES,
     uikit::ui_nib::CLASSES,
@@ -62,6 +90,8 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     uikit::ui_view::ui_control::ui_text_field::CLASSES,
     uikit::ui_view::ui_image_view::CLASSES,
     uikit::ui_view::ui_label::CLASSES,
+    uikit::ui_view::ui_scroll_view::CLASSES,
+    uikit::ui_view::ui_text_view::CLASSES,
     uikit::ui_view::ui_window::CLASSES,
     uikit::ui_view_controller::CLASSES,
 ];
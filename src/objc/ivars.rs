@@ -0,0 +1,267 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Handling of Objective-C instance variables (ivars).
+//!
+//! Our host classes don't use ivars (all their data lives in host objects), so
+//! this is only relevant to classes from the guest app.
+//!
+//! Resources:
+//! - [Apple's documentation of `class_getInstanceVariable`](https://developer.apple.com/documentation/objectivec/1418643-class_getinstancevariable?language=objc)
+//! - [Apple's documentation of `object_getIvar`](https://developer.apple.com/documentation/objectivec/1418580-object_getivar?language=objc)
+//!
+//! See also: [super::properties].
+
+use super::{id, nil, Class, ClassHostObject, ObjC};
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead};
+use crate::Environment;
+
+/// The layout of an ivar list in an app binary.
+///
+/// The name, field names and field layout are based on what Ghidra outputs.
+#[repr(C, packed)]
+pub(super) struct ivar_list_t {
+    entsize: GuestUSize,
+    count: GuestUSize,
+    // entries follow the struct
+}
+unsafe impl SafeRead for ivar_list_t {}
+
+/// The layout of an ivar in an app binary.
+///
+/// The name, field names and field layout are based on what Ghidra outputs.
+#[repr(C, packed)]
+pub struct ivar_t {
+    /// Note that this is a pointer to the offset, not the offset itself! This
+    /// indirection lets Apple's runtime move ivars when a superclass grows.
+    offset: ConstPtr<GuestUSize>,
+    name: ConstPtr<u8>,
+    type_: ConstPtr<u8>,
+    _alignment: u32,
+    _size: u32,
+}
+unsafe impl SafeRead for ivar_t {}
+
+/// Opaque type used for ivars. In our implementation, this is a pointer to an
+/// [ivar_t] in guest memory.
+///
+/// The name is standard Objective-C.
+pub type Ivar = ConstPtr<ivar_t>;
+
+impl ClassHostObject {
+    pub(super) fn add_ivars_from_bin(&mut self, ivar_list_ptr: ConstPtr<ivar_list_t>, mem: &Mem) {
+        let ivar_list_t { entsize, count } = mem.read(ivar_list_ptr);
+        assert!(entsize >= guest_size_of::<ivar_t>());
+
+        let ivars_base_ptr: ConstPtr<ivar_t> = (ivar_list_ptr + 1).cast();
+
+        for i in 0..count {
+            let ivar: Ivar = Ptr::from_bits(ivars_base_ptr.to_bits() + i * entsize);
+            let name = ivar_get_name(ivar, mem).to_string();
+            self.ivars.insert(name, ivar);
+        }
+    }
+
+    /// Add a new ivar after all the existing ones, growing the instance size
+    /// accordingly. This is for classes created at runtime, see
+    /// [class_addIvar]. Returns [false] if there's already an ivar with the
+    /// same name.
+    fn add_ivar(
+        &mut self,
+        name: &str,
+        size: GuestUSize,
+        log2_alignment: u8,
+        type_: &[u8],
+        mem: &mut Mem,
+    ) -> bool {
+        if self.ivars.contains_key(name) {
+            return false;
+        }
+
+        let alignment: GuestUSize = 1 << log2_alignment;
+        let offset = self.instance_size;
+        let offset = if offset % alignment != 0 {
+            offset + alignment - (offset % alignment)
+        } else {
+            offset
+        };
+        self.instance_size = offset.checked_add(size).unwrap();
+
+        // These need to live in guest memory so that the Ivar can be passed to
+        // the guest app.
+        let offset_ptr = mem.alloc_and_write(offset).cast_const();
+        let name_ptr = mem.alloc_and_write_cstr(name.as_bytes()).cast_const();
+        let type_ptr = mem.alloc_and_write_cstr(type_).cast_const();
+        let ivar = mem.alloc_and_write(ivar_t {
+            offset: offset_ptr,
+            name: name_ptr,
+            type_: type_ptr,
+            _alignment: log2_alignment.into(),
+            _size: size,
+        });
+        self.ivars.insert(name.to_string(), ivar.cast_const());
+        true
+    }
+}
+
+/// Get the name of an ivar.
+pub fn ivar_get_name(ivar: Ivar, mem: &Mem) -> &str {
+    let ivar_t { name, .. } = mem.read(ivar);
+    mem.cstr_at_utf8(name).unwrap()
+}
+
+/// Get the offset of an ivar within instances of the class it belongs to.
+pub fn ivar_get_offset(ivar: Ivar, mem: &Mem) -> GuestUSize {
+    let ivar_t { offset, .. } = mem.read(ivar);
+    mem.read(offset)
+}
+
+impl ObjC {
+    /// Look up an ivar by name in a class or its superclasses.
+    pub fn class_get_instance_variable(&self, class: Class, name: &str) -> Option<Ivar> {
+        let mut class = class;
+        while class != nil {
+            // Placeholder and fake classes have no ivar information.
+            let Some(&ClassHostObject {
+                superclass,
+                ref ivars,
+                ..
+            }) = self.get_host_object(class).unwrap().as_any().downcast_ref()
+            else {
+                return None;
+            };
+            if let Some(&ivar) = ivars.get(name) {
+                return Some(ivar);
+            }
+            class = superclass;
+        }
+        None
+    }
+}
+
+fn ivar_ptr_in_object<T>(object: id, ivar: Ivar, mem: &Mem) -> MutPtr<T> {
+    let offset = ivar_get_offset(ivar, mem);
+    // Any real ivar offset will be after the isa pointer.
+    assert!(offset >= 4);
+    Ptr::from_bits(object.to_bits().checked_add(offset).unwrap())
+}
+
+/// Standard Objective-C runtime function for looking up an ivar.
+pub(super) fn class_getInstanceVariable(
+    env: &mut Environment,
+    class: Class,
+    name: ConstPtr<u8>,
+) -> Ivar {
+    if class == nil || name.is_null() {
+        return Ptr::null();
+    }
+    let name = env.mem.cstr_at_utf8(name).unwrap();
+    env.objc
+        .class_get_instance_variable(class, name)
+        .unwrap_or(Ptr::null())
+}
+
+/// Standard Objective-C runtime function for adding an ivar to a class created
+/// with [super::classes::objc_allocateClassPair]. This is only allowed before
+/// the class is registered.
+pub(super) fn class_addIvar(
+    env: &mut Environment,
+    class: Class,
+    name: ConstPtr<u8>,
+    size: GuestUSize,
+    log2_alignment: u8,
+    types: ConstPtr<u8>,
+) -> bool {
+    if class == nil || name.is_null() {
+        return false;
+    }
+    let name = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    let types = if types.is_null() {
+        Vec::new()
+    } else {
+        env.mem.cstr_at(types).to_vec()
+    };
+
+    // Adding ivars to a class that is already in use would break the layout
+    // of existing instances.
+    if env.objc.class_is_registered(class) {
+        log!(
+            "Warning: class_addIvar() called for already-registered class {:?}, ignoring",
+            class
+        );
+        return false;
+    }
+
+    let host_object = env.objc.borrow_mut::<ClassHostObject>(class);
+    // Metaclasses can't have ivars added.
+    if host_object.is_metaclass {
+        return false;
+    }
+    host_object.add_ivar(&name, size, log2_alignment, &types, &mut env.mem)
+}
+
+/// Standard Objective-C runtime function for reading an object-typed ivar.
+pub(super) fn object_getIvar(env: &mut Environment, object: id, ivar: Ivar) -> id {
+    if object == nil || ivar.is_null() {
+        return nil;
+    }
+    env.mem.read(ivar_ptr_in_object(object, ivar, &env.mem))
+}
+
+/// Standard Objective-C runtime function for writing an object-typed ivar.
+/// Like in Apple's runtime, no reference counting is done.
+pub(super) fn object_setIvar(env: &mut Environment, object: id, ivar: Ivar, value: id) {
+    if object == nil || ivar.is_null() {
+        return;
+    }
+    let ptr = ivar_ptr_in_object(object, ivar, &env.mem);
+    env.mem.write(ptr, value);
+}
+
+/// Standard Objective-C runtime function for reading a pointer-sized ivar by
+/// name.
+pub(super) fn object_getInstanceVariable(
+    env: &mut Environment,
+    object: id,
+    name: ConstPtr<u8>,
+    out_value: MutPtr<MutVoidPtr>,
+) -> Ivar {
+    if object == nil {
+        return Ptr::null();
+    }
+    let class = ObjC::read_isa(object, &env.mem);
+    let ivar = class_getInstanceVariable(env, class, name);
+    if !ivar.is_null() && !out_value.is_null() {
+        let value: MutVoidPtr = env.mem.read(ivar_ptr_in_object(object, ivar, &env.mem));
+        env.mem.write(out_value, value);
+    }
+    ivar
+}
+
+/// Standard Objective-C runtime function for getting an ivar's name.
+pub(super) fn ivar_getName(env: &mut Environment, ivar: Ivar) -> ConstPtr<u8> {
+    if ivar.is_null() {
+        return Ptr::null();
+    }
+    let ivar_t { name, .. } = env.mem.read(ivar);
+    name
+}
+
+/// Standard Objective-C runtime function for getting an ivar's offset.
+pub(super) fn ivar_getOffset(env: &mut Environment, ivar: Ivar) -> GuestUSize {
+    if ivar.is_null() {
+        return 0;
+    }
+    ivar_get_offset(ivar, &env.mem)
+}
+
+/// Standard Objective-C runtime function for getting an ivar's type encoding.
+pub(super) fn ivar_getTypeEncoding(env: &mut Environment, ivar: Ivar) -> ConstPtr<u8> {
+    if ivar.is_null() {
+        return Ptr::null();
+    }
+    let ivar_t { type_, .. } = env.mem.read(ivar);
+    type_
+}
@@ -11,9 +11,11 @@
 //! - Mike Ash's [objc_msgSend's New Prototype](https://www.mikeash.com/pyblog/objc_msgsends-new-prototype.html)
 //! - Peter Steinberger's [Calling Super at Runtime in Swift](https://steipete.com/posts/calling-super-at-runtime/) explains `objc_msgSendSuper2`
 
-use super::{id, nil, Class, ObjC, IMP, SEL};
-use crate::abi::{CallFromHost, GuestRet};
-use crate::mem::{ConstPtr, MutVoidPtr, SafeRead};
+use super::{id, nil, Class, ClassHostObject, ObjC, IMP, SEL};
+use crate::abi::{extend_stack_for_args, write_next_arg, CallFromHost, GuestRet};
+use crate::cpu::Cpu;
+use crate::frameworks::foundation::ns_invocation;
+use crate::mem::{ConstPtr, MutVoidPtr, Ptr, SafeRead};
 use crate::Environment;
 
 /// The core implementation of `objc_msgSend`, the main function of Objective-C.
@@ -28,8 +30,21 @@ use crate::Environment;
 /// Similarly, the return value of `objc_msgSend` is whatever value is returned
 /// by the method implementation. We are relying on CallFromGuest not
 /// overwriting it.
+///
+/// If `super_class` is provided, this is a super-call and method lookup starts
+/// at that class, rather than the class of the receiver.
+///
+/// `stret` must be [true] if this was reached from one of the `_stret`
+/// variants, in which case `r0` is the struct return pointer and the receiver
+/// is in `r1`.
 #[allow(non_snake_case)]
-fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2: Option<Class>) {
+fn objc_msgSend_inner(
+    env: &mut Environment,
+    receiver: id,
+    selector: SEL,
+    super_class: Option<Class>,
+    stret: bool,
+) {
     if receiver == nil {
         // https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjectiveC/Chapters/ocObjectsClasses.html#//apple_ref/doc/uid/TP30001163-CH11-SW7
         log_dbg!("[nil {}]", selector.as_str(&env.mem));
@@ -37,104 +52,254 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
         return;
     }
 
-    let orig_class = super2.unwrap_or_else(|| ObjC::read_isa(receiver, &env.mem));
+    let orig_class = super_class.unwrap_or_else(|| ObjC::read_isa(receiver, &env.mem));
     assert!(orig_class != nil);
 
-    // Traverse the chain of superclasses to find the method implementation.
+    // A super-call can only happen within a method of an initialized class.
+    if super_class.is_none() {
+        initialize_receiver_class(env, receiver, orig_class);
+    }
 
-    let mut class = orig_class;
-    loop {
-        if class == nil {
-            assert!(class != orig_class);
-
-            let class_host_object = env.objc.get_host_object(orig_class).unwrap();
-            let &super::ClassHostObject {
-                ref name,
-                is_metaclass,
-                ..
-            } = class_host_object.as_any().downcast_ref().unwrap();
-
-            panic!(
-                "{} {:?} ({}class \"{}\", {:?}){} does not respond to selector \"{}\"!",
-                if is_metaclass { "Class" } else { "Object" },
-                receiver,
-                if is_metaclass { "meta" } else { "" },
-                name,
-                orig_class,
-                if super2.is_some() {
-                    "'s superclass"
-                } else {
-                    ""
-                },
-                selector.as_str(&env.mem),
-            );
+    let class = match env.objc.lookup_method_cached(orig_class, selector) {
+        Ok(imp) => {
+            call_imp(env, imp);
+            return;
         }
+        Err(class) => class,
+    };
 
-        let host_object = env.objc.get_host_object(class).unwrap();
+    if class == nil {
+        if forward_message(env, receiver, selector, stret) {
+            return;
+        }
 
-        if let Some(&super::ClassHostObject {
-            superclass,
-            ref methods,
+        let class_host_object = env.objc.get_host_object(orig_class).unwrap();
+        let &super::ClassHostObject {
+            ref name,
+            is_metaclass,
             ..
-        }) = host_object.as_any().downcast_ref()
-        {
-            // Skip method lookup on first iteration if this is the super-call
-            // variant of objc_msgSend (look up the superclass first)
-            if super2.is_some() && class == orig_class {
-                class = superclass;
-                continue;
-            }
+        } = class_host_object.as_any().downcast_ref().unwrap();
 
-            if let Some(imp) = methods.get(&selector) {
-                match imp {
-                    IMP::Host(host_imp) => host_imp.call_from_guest(env),
-                    // We can't create a new stack frame, because that would
-                    // interfere with pass-through of stack arguments.
-                    IMP::Guest(guest_imp) => guest_imp.call_without_pushing_stack_frame(env),
-                }
-                return;
+        panic!(
+            "{} {:?} ({}class \"{}\", {:?}){} does not respond to selector \"{}\"!",
+            if is_metaclass { "Class" } else { "Object" },
+            receiver,
+            if is_metaclass { "meta" } else { "" },
+            name,
+            orig_class,
+            if super_class.is_some() {
+                " (super-call)"
             } else {
-                class = superclass;
+                ""
+            },
+            selector.as_str(&env.mem),
+        );
+    }
+
+    // The search was ended by a class that isn't a normal class.
+    let host_object = env.objc.get_host_object(class).unwrap();
+    if let Some(&super::UnimplementedClass {
+        ref name,
+        is_metaclass,
+    }) = host_object.as_any().downcast_ref()
+    {
+        panic!(
+            "Class \"{}\" ({:?}) is unimplemented. Call to {} method \"{}\".",
+            name,
+            class,
+            if is_metaclass { "class" } else { "instance" },
+            selector.as_str(&env.mem),
+        );
+    } else if let Some(&super::FakeClass {
+        ref name,
+        is_metaclass,
+    }) = host_object.as_any().downcast_ref()
+    {
+        log!(
+            "Call to faked class \"{}\" ({:?}) {} method \"{}\". Behaving as if message was sent to nil.",
+            name,
+            class,
+            if is_metaclass { "class" } else { "instance" },
+            selector.as_str(&env.mem),
+        );
+        env.cpu.regs_mut()[0..2].fill(0);
+    } else {
+        panic!(
+            "Item {:?} in superclass chain of object {:?}'s class {:?} has an unexpected host object type.",
+            class, receiver, orig_class
+        );
+    }
+}
+
+/// Give the receiver of a message it has no method for a chance to handle it,
+/// like Apple's runtime does: first `forwardingTargetForSelector:` is tried,
+/// then `forwardInvocation:`, and finally `doesNotRecognizeSelector:`.
+/// Returns [false] if none of these are implemented.
+///
+/// The arguments of the message must still be in the registers and on the
+/// stack. `stret` has the same meaning as for [objc_msgSend_inner].
+fn forward_message(env: &mut Environment, receiver: id, selector: SEL, stret: bool) -> bool {
+    // Even for a super-call, forwarding uses the receiver's own class.
+    let isa = ObjC::read_isa(receiver, &env.mem);
+
+    // The messages sent here must not clobber the arguments.
+    let saved_regs: [u32; 4] = env.cpu.regs()[0..4].try_into().unwrap();
+
+    if let Some(sel) = env.objc.lookup_selector("forwardingTargetForSelector:") {
+        if selector != sel && env.objc.class_has_method(isa, sel) {
+            let target: id = msg_send(env, (receiver, sel, selector));
+            env.cpu.regs_mut()[0..4].copy_from_slice(&saved_regs);
+            if target != nil && target != receiver {
+                log_dbg!(
+                    "Forwarding \"{}\" from {:?} to {:?}",
+                    selector.as_str(&env.mem),
+                    receiver,
+                    target
+                );
+                env.cpu.regs_mut()[usize::from(stret)] = target.to_bits();
+                objc_msgSend_inner(env, target, selector, /* super_class: */ None, stret);
+                return true;
             }
-        } else if let Some(&super::UnimplementedClass {
-            ref name,
-            is_metaclass,
-        }) = host_object.as_any().downcast_ref()
-        {
-            panic!(
-                "Class \"{}\" ({:?}) is unimplemented. Call to {} method \"{}\".",
-                name,
-                class,
-                if is_metaclass { "class" } else { "instance" },
-                selector.as_str(&env.mem),
-            );
-        } else if let Some(&super::FakeClass {
-            ref name,
-            is_metaclass,
+        }
+    }
+
+    // The root class's implementation (NSObject's) only calls
+    // doesNotRecognizeSelector:, so it is skipped.
+    // TODO: Struct returns. NSInvocation's raw messaging only supports return
+    // values in r0 and r1, so there's no way to fill in the struct.
+    if let Some(sel) = env
+        .objc
+        .lookup_selector("forwardInvocation:")
+        .filter(|_| !stret)
+    {
+        let implementor = env.objc.find_class_with_method(isa, sel);
+        if let Some(implementor) = implementor {
+            let &ClassHostObject { superclass, .. } = env.objc.borrow(implementor);
+            if superclass != nil {
+                // TODO: Arguments larger than 32 bits. We don't have method
+                // signatures, so each argument is assumed to be a single word.
+                let arg_count = selector.as_str(&env.mem).matches(':').count();
+                let stack_ptr: ConstPtr<u32> = Ptr::from_bits(env.cpu.regs()[Cpu::SP]);
+                let arguments = (0..arg_count)
+                    .map(|i| {
+                        if i < 2 {
+                            saved_regs[2 + i]
+                        } else {
+                            env.mem.read(stack_ptr + (i - 2).try_into().unwrap())
+                        }
+                    })
+                    .collect();
+                let invocation =
+                    ns_invocation::new_for_forwarding(env, receiver, selector, arguments);
+                () = msg_send(env, (receiver, sel, invocation));
+                let return_value = ns_invocation::raw_return_value(env, invocation);
+                release(env, invocation);
+                env.cpu.regs_mut()[0..2].copy_from_slice(&return_value);
+                return true;
+            }
+        }
+    }
+
+    if let Some(sel) = env.objc.lookup_selector("doesNotRecognizeSelector:") {
+        if env.objc.class_has_method(isa, sel) {
+            () = msg_send(env, (receiver, sel, selector));
+            // Apple's runtime aborts if this returns. The caller will panic.
+            env.cpu.regs_mut()[0..4].copy_from_slice(&saved_regs);
+        }
+    }
+
+    false
+}
+
+fn call_imp(env: &mut Environment, imp: IMP) {
+    match imp {
+        IMP::Host(host_imp) => host_imp.call_from_guest(env),
+        // We can't create a new stack frame, because that would interfere with
+        // pass-through of stack arguments.
+        IMP::Guest(guest_imp) => guest_imp.call_without_pushing_stack_frame(env),
+    }
+}
+
+/// Make sure `+initialize` has been sent to the class of the receiver (or the
+/// receiver itself, if it is a class) before it receives any other message.
+///
+/// `isa` must be the class of the receiver.
+fn initialize_receiver_class(env: &mut Environment, receiver: id, isa: Class) {
+    // Fake and placeholder classes don't need initializing.
+    let host_object = env.objc.get_host_object(isa).unwrap();
+    let Some(&ClassHostObject { is_metaclass, .. }) = host_object.as_any().downcast_ref() else {
+        return;
+    };
+    let class = if is_metaclass { receiver } else { isa };
+    initialize_class(env, class);
+}
+
+/// Send `+initialize` to a class if this hasn't been done already, after doing
+/// the same for its superclasses.
+fn initialize_class(env: &mut Environment, class: Class) {
+    let host_object = env.objc.get_host_object(class).unwrap();
+    let Some(&ClassHostObject {
+        is_metaclass: false,
+        initialized: false,
+        superclass,
+        ..
+    }) = host_object.as_any().downcast_ref()
+    else {
+        return;
+    };
+
+    // This must be set before sending any messages, so that messages sent to
+    // the class while it's being initialized don't cause infinite recursion.
+    env.objc.borrow_mut::<ClassHostObject>(class).initialized = true;
+
+    if superclass != nil {
+        initialize_class(env, superclass);
+    }
+
+    let Some(sel) = env.objc.lookup_selector("initialize") else {
+        // If nothing has this selector, nothing implements +initialize.
+        return;
+    };
+
+    // Host classes don't implement +initialize, so often nothing in the
+    // superclass chain will respond to it. Like in Apple's runtime, a class
+    // that doesn't implement +initialize itself will get its superclass's
+    // implementation called again.
+    let mut metaclass = ObjC::read_isa(class, &env.mem);
+    loop {
+        let host_object = env.objc.get_host_object(metaclass).unwrap();
+        let Some(&ClassHostObject {
+            superclass,
+            ref methods,
+            ..
         }) = host_object.as_any().downcast_ref()
-        {
-            log!(
-                "Call to faked class \"{}\" ({:?}) {} method \"{}\". Behaving as if message was sent to nil.",
-                name,
-                class,
-                if is_metaclass { "class" } else { "instance" },
-                selector.as_str(&env.mem),
-            );
-            env.cpu.regs_mut()[0..2].fill(0);
+        else {
+            return;
+        };
+        if methods.contains_key(&sel) {
+            break;
+        } else if superclass == nil {
             return;
         } else {
-            panic!(
-                "Item {:?} in superclass chain of object {:?}'s class {:?} has an unexpected host object type.",
-                class, receiver, orig_class
-            );
+            metaclass = superclass;
         }
     }
+
+    log_dbg!("Initializing class {:?}", class);
+
+    // The message is sent from within another message send, so the registers
+    // containing that message's arguments need to be preserved.
+    let saved_regs: [u32; 4] = env.cpu.regs()[0..4].try_into().unwrap();
+    () = msg_send(env, (class, sel));
+    env.cpu.regs_mut()[0..4].copy_from_slice(&saved_regs);
 }
 
 /// Standard variant of `objc_msgSend`. See [objc_msgSend_inner].
 #[allow(non_snake_case)]
 pub(super) fn objc_msgSend(env: &mut Environment, receiver: id, selector: SEL) {
-    objc_msgSend_inner(env, receiver, selector, /* super2: */ None)
+    objc_msgSend_inner(
+        env, receiver, selector, /* super_class: */ None, /* stret: */ false,
+    )
 }
 
 /// Variant of `objc_msgSend` for methods that return a struct via a pointer.
@@ -152,16 +317,18 @@ pub(super) fn objc_msgSend_stret(
     receiver: id,
     selector: SEL,
 ) {
-    objc_msgSend_inner(env, receiver, selector, /* super2: */ None)
+    objc_msgSend_inner(
+        env, receiver, selector, /* super_class: */ None, /* stret: */ true,
+    )
 }
 
 #[repr(C, packed)]
 /// A pointer to this struct replaces the normal receiver parameter for
-/// `objc_msgSendSuper2` and [msg_send_super2].
+/// `objc_msgSendSuper`, `objc_msgSendSuper2` and [msg_send_super2].
 pub struct objc_super {
     pub receiver: id,
-    /// If this is used with `objc_msgSendSuper` (not implemented here, TODO),
-    /// this is a pointer to the superclass to look up the method on.
+    /// If this is used with `objc_msgSendSuper`, this is a pointer to the
+    /// superclass to look up the method on.
     /// If this is used with `objc_msgSendSuper2`, this is a pointer to a class
     /// and the superclass will be looked up from it.
     pub class: Class,
@@ -175,18 +342,98 @@ unsafe impl SafeRead for objc_super {}
 /// extra parameter, because that would take one of the argument slots reserved
 /// for arguments passed onto the method implementation. Hence the [objc_super]
 /// pointer in place of the normal [id].
+///
+/// The class in the [objc_super] is where method lookup starts. Compare
+/// [objc_msgSendSuper2].
+#[allow(non_snake_case)]
+pub(super) fn objc_msgSendSuper(
+    env: &mut Environment,
+    super_ptr: ConstPtr<objc_super>,
+    selector: SEL,
+) {
+    objc_msgSendSuper_inner(env, super_ptr, selector, /* reg_offset: */ 0, false)
+}
+
+/// Variant of `objc_msgSend` for supercalls. See [objc_msgSendSuper].
+///
+/// Unlike [objc_msgSendSuper], the class in the [objc_super] is the class the
+/// calling method belongs to, and method lookup starts at its superclass.
+/// This is what binaries using the non-fragile ABI use.
 #[allow(non_snake_case)]
 pub(super) fn objc_msgSendSuper2(
     env: &mut Environment,
     super_ptr: ConstPtr<objc_super>,
     selector: SEL,
+) {
+    objc_msgSendSuper_inner(env, super_ptr, selector, /* reg_offset: */ 0, true)
+}
+
+/// Variant of [objc_msgSendSuper] for methods that return a struct via a
+/// pointer. See [objc_msgSend_stret].
+#[allow(non_snake_case)]
+pub(super) fn objc_msgSendSuper_stret(
+    env: &mut Environment,
+    _stret: MutVoidPtr,
+    super_ptr: ConstPtr<objc_super>,
+    selector: SEL,
+) {
+    objc_msgSendSuper_inner(env, super_ptr, selector, /* reg_offset: */ 1, false)
+}
+
+/// Variant of [objc_msgSendSuper2] for methods that return a struct via a
+/// pointer. See [objc_msgSend_stret].
+#[allow(non_snake_case)]
+pub(super) fn objc_msgSendSuper2_stret(
+    env: &mut Environment,
+    _stret: MutVoidPtr,
+    super_ptr: ConstPtr<objc_super>,
+    selector: SEL,
+) {
+    objc_msgSendSuper_inner(env, super_ptr, selector, /* reg_offset: */ 1, true)
+}
+
+/// Shared implementation of the `objc_msgSendSuper` variants. `reg_offset` is
+/// the register containing the [objc_super] pointer (1 if there's a struct
+/// return pointer, otherwise 0), and `is_super2` selects the
+/// [objc_msgSendSuper2] behaviour.
+#[allow(non_snake_case)]
+fn objc_msgSendSuper_inner(
+    env: &mut Environment,
+    super_ptr: ConstPtr<objc_super>,
+    selector: SEL,
+    mut reg_offset: usize,
+    is_super2: bool,
 ) {
     let objc_super { receiver, class } = env.mem.read(super_ptr);
+    let stret = reg_offset == 1;
+
+    // Rewrite the receiver argument to match the normal ABI.
+    crate::abi::write_next_arg(&mut reg_offset, env.cpu.regs_mut(), &mut env.mem, receiver);
 
-    // Rewrite first argument to match the normal ABI.
-    crate::abi::write_next_arg(&mut 0, env.cpu.regs_mut(), &mut env.mem, receiver);
+    let super_class = if is_super2 {
+        let host_object = env.objc.get_host_object(class).unwrap();
+        if let Some(&super::ClassHostObject { superclass, .. }) =
+            host_object.as_any().downcast_ref()
+        {
+            assert!(superclass != nil);
+            superclass
+        } else {
+            // Fake and unimplemented classes have no superclass to look up.
+            // objc_msgSend_inner will handle them when it finds them at the
+            // start of the lookup.
+            class
+        }
+    } else {
+        class
+    };
 
-    objc_msgSend_inner(env, receiver, selector, /* super2: */ Some(class))
+    objc_msgSend_inner(
+        env,
+        receiver,
+        selector,
+        /* super_class: */ Some(super_class),
+        stret,
+    )
 }
 
 /// Wrapper around [objc_msgSend] which, together with [msg], makes it easy to
@@ -210,6 +457,34 @@ where
     }
 }
 
+/// Send a message with arguments that are already in their raw form, i.e. the
+/// 32-bit words that would be in registers or on the stack, starting after
+/// `_cmd`. Returns the raw contents of `r0` and `r1` afterwards. This is
+/// useful when the types aren't known statically, e.g. for `NSInvocation`.
+///
+/// Methods returning structs via a pointer are not supported.
+pub fn msg_send_with_raw_args(
+    env: &mut Environment,
+    receiver: id,
+    selector: SEL,
+    args: &[u32],
+) -> [u32; 2] {
+    let regs = env.cpu.regs_mut();
+    let old_sp = extend_stack_for_args(2 + args.len(), regs);
+    let mut reg_offset = 0;
+    write_next_arg(&mut reg_offset, regs, &mut env.mem, receiver);
+    write_next_arg(&mut reg_offset, regs, &mut env.mem, selector);
+    for &arg in args {
+        write_next_arg(&mut reg_offset, regs, &mut env.mem, arg);
+    }
+    objc_msgSend_inner(
+        env, receiver, selector, /* super_class: */ None, /* stret: */ false,
+    );
+    let regs = env.cpu.regs_mut();
+    regs[Cpu::SP] = old_sp;
+    [regs[0], regs[1]]
+}
+
 /// [msg_send] but for super-calls (calls [objc_msgSendSuper2]). You probably
 /// want to use [msg_super] rather than calling this directly.
 pub fn msg_send_super2<R, P>(env: &mut Environment, args: P) -> R
@@ -219,7 +494,8 @@ where
     R: GuestRet,
 {
     if R::SIZE_IN_MEM.is_some() {
-        todo!() // no stret yet
+        (objc_msgSendSuper2_stret as fn(&mut Environment, MutVoidPtr, ConstPtr<objc_super>, SEL))
+            .call_from_host(env, args)
     } else {
         (objc_msgSendSuper2 as fn(&mut Environment, ConstPtr<objc_super>, SEL))
             .call_from_host(env, args)
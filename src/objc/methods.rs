@@ -10,7 +10,7 @@
 
 use super::{id, nil, Class, ClassHostObject, ObjC, SEL};
 use crate::abi::{CallFromGuest, DotDotDot, GuestArg, GuestFunction, GuestRet};
-use crate::mem::{guest_size_of, ConstPtr, GuestUSize, Mem, Ptr, SafeRead};
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, Mem, MutPtr, Ptr, SafeRead};
 use crate::Environment;
 
 /// Type for any function implementating a method.
@@ -21,13 +21,26 @@ use crate::Environment;
 /// "guest methods" (functions in the guest app). Either way, the function needs
 /// to conform to the same ABI: [id] and [SEL] must be its first two parameters.
 #[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone)]
 pub enum IMP {
     Host(&'static dyn HostIMP),
     Guest(GuestIMP),
 }
 
 /// Type for any host function implementing a method (see also [IMP]).
-pub trait HostIMP: CallFromGuest {}
+pub trait HostIMP: CallFromGuest + AsCallFromGuest {}
+
+/// Helper trait for turning a [HostIMP] into a plain [CallFromGuest], e.g. so
+/// that a guest function can be created for it with
+/// [crate::dyld::Dyld::create_guest_function].
+pub trait AsCallFromGuest {
+    fn as_call_from_guest(&'static self) -> &'static dyn CallFromGuest;
+}
+impl<T: CallFromGuest> AsCallFromGuest for T {
+    fn as_call_from_guest(&'static self) -> &'static dyn CallFromGuest {
+        self
+    }
+}
 
 impl<R> HostIMP for fn(&mut Environment, id, SEL) -> R where R: GuestRet {}
 impl<R, P1> HostIMP for fn(&mut Environment, id, SEL, P1) -> R
@@ -126,17 +139,27 @@ pub(super) struct method_list_t {
 }
 unsafe impl SafeRead for method_list_t {}
 
-/// The layout of a method in an app binary.
+/// The layout of a method in an app binary. This is also used for [Method].
 ///
 /// The name, field names and field layout are based on what Ghidra outputs.
 #[repr(C, packed)]
-struct method_t {
+pub(super) struct method_t {
     name: ConstPtr<u8>,
     types: ConstPtr<u8>,
     imp: GuestIMP,
 }
 unsafe impl SafeRead for method_t {}
 
+/// Opaque handle for a method, used by runtime functions like
+/// [class_getInstanceMethod].
+///
+/// The name is standard Objective-C. In our implementation, this points to a
+/// [method_t] with the method's name. The other fields are not filled in,
+/// because the implementation is always looked up from the class, which can
+/// change. Apple's runtime doesn't guarantee anything about the contents
+/// anyway.
+pub type Method = MutPtr<method_t>;
+
 impl ClassHostObject {
     // See classes.rs for host method parsing
 
@@ -146,49 +169,70 @@ impl ClassHostObject {
         mem: &Mem,
         objc: &mut ObjC,
     ) {
+        self.methods
+            .extend(objc.read_methods_from_bin(method_list_ptr, mem));
+    }
+}
+
+impl ObjC {
+    /// Read a method list from the application binary, registering the
+    /// selectors it uses. This doesn't need a [ClassHostObject], which makes it
+    /// useful when the class's host object is already owned by [ObjC].
+    pub(super) fn read_methods_from_bin(
+        &mut self,
+        method_list_ptr: ConstPtr<method_list_t>,
+        mem: &Mem,
+    ) -> Vec<(SEL, IMP)> {
         let method_list_t { entsize, count } = mem.read(method_list_ptr);
         assert!(entsize >= guest_size_of::<method_t>());
 
         let methods_base_ptr: ConstPtr<method_t> = (method_list_ptr + 1).cast();
 
-        for i in 0..count {
-            let method_ptr: ConstPtr<method_t> =
-                Ptr::from_bits(methods_base_ptr.to_bits() + i * entsize);
-
-            // TODO: support type strings
-            let method_t {
-                name,
-                types: _,
-                imp,
-            } = mem.read(method_ptr);
-
-            // There is no guarantee this string is unique or known.
-            // We must deduplicate it like any other.
-            let sel = objc.register_bin_selector(name, mem);
-            self.methods.insert(sel, IMP::Guest(imp));
-        }
+        (0..count)
+            .map(|i| {
+                let method_ptr: ConstPtr<method_t> =
+                    Ptr::from_bits(methods_base_ptr.to_bits() + i * entsize);
+
+                // TODO: support type strings
+                let method_t {
+                    name,
+                    types: _,
+                    imp,
+                } = mem.read(method_ptr);
+
+                // There is no guarantee this string is unique or known.
+                // We must deduplicate it like any other.
+                let sel = self.register_bin_selector(name, mem);
+                (sel, IMP::Guest(imp))
+            })
+            .collect()
     }
 }
 
 impl ObjC {
     /// Checks if the provided class has a method in it's class chain (that is to say, objects of
-    /// the given class respond to a selector).
+    /// the given class respond to a selector). To check for a class method, pass the metaclass.
+    ///
+    /// Placeholder and fake classes have no methods, so if one is encountered
+    /// in the chain, the result is [false].
     pub fn class_has_method(&self, class: Class, sel: SEL) -> bool {
         let mut class = class;
-        loop {
-            let &ClassHostObject {
+        while class != nil {
+            let host_object = self.get_host_object(class).unwrap();
+            let Some(&ClassHostObject {
                 superclass,
                 ref methods,
                 ..
-            } = self.borrow(class);
+            }) = host_object.as_any().downcast_ref()
+            else {
+                return false;
+            };
             if methods.contains_key(&sel) {
                 return true;
-            } else if superclass == nil {
-                return false;
-            } else {
-                class = superclass;
             }
+            class = superclass;
         }
+        false
     }
 
     /// Same as [Self::class_has_method], but using a named selector (rather than a pointer).
@@ -247,3 +291,312 @@ impl ObjC {
         }
     }
 }
+
+/// Standard Objective-C runtime function for adding a method to a class.
+///
+/// Returns [false] if the class already has its own implementation of the
+/// method (implementations inherited from superclasses don't count).
+pub(super) fn class_addMethod(
+    env: &mut Environment,
+    class: Class,
+    selector: SEL,
+    imp: GuestIMP,
+    _types: ConstPtr<u8>,
+) -> bool {
+    // TODO: support type strings
+    let Some(host_object) = env.objc.get_host_object(class) else {
+        return false;
+    };
+    if !host_object.as_any().is::<ClassHostObject>() {
+        return false;
+    }
+
+    let host_object = env.objc.borrow_mut::<ClassHostObject>(class);
+    if host_object.methods.contains_key(&selector) {
+        return false;
+    }
+    log_dbg!(
+        "Adding method \"{}\" ({:?}) to {} \"{}\" {:?}",
+        selector.as_str(&env.mem),
+        imp,
+        if host_object.is_metaclass {
+            "metaclass"
+        } else {
+            "class"
+        },
+        host_object.name,
+        class,
+    );
+    host_object.methods.insert(selector, IMP::Guest(imp));
+    env.objc.flush_method_cache();
+    true
+}
+
+impl ObjC {
+    /// Flush the cache used by `objc_msgSend`. This must be called whenever
+    /// methods are added to a class or replaced.
+    pub(super) fn flush_method_cache(&mut self) {
+        self.method_cache.clear();
+    }
+
+    /// Look up the method implementation `objc_msgSend` calls when a message
+    /// is sent to an instance of `class`, by walking the superclass chain. If
+    /// there is none, the error is the placeholder or fake class that ended the
+    /// search, or [nil] if the whole chain was searched.
+    pub(super) fn lookup_method(&self, class: Class, sel: SEL) -> Result<IMP, Class> {
+        let mut class = class;
+        while class != nil {
+            let host_object = self.get_host_object(class).unwrap();
+            let Some(&ClassHostObject {
+                superclass,
+                ref methods,
+                ..
+            }) = host_object.as_any().downcast_ref()
+            else {
+                return Err(class);
+            };
+            if let Some(&imp) = methods.get(&sel) {
+                return Ok(imp);
+            }
+            class = superclass;
+        }
+        Err(nil)
+    }
+
+    /// Like [Self::lookup_method], but using the method cache.
+    pub(super) fn lookup_method_cached(&mut self, class: Class, sel: SEL) -> Result<IMP, Class> {
+        if let Some(&imp) = self.method_cache.get(&(class, sel)) {
+            return Ok(imp);
+        }
+        let imp = self.lookup_method(class, sel)?;
+        // Only successful lookups are cached, so that unrecognized selectors
+        // always take the slow path.
+        self.method_cache.insert((class, sel), imp);
+        Ok(imp)
+    }
+
+    /// Find the class in the superclass chain that has its own implementation
+    /// of a method. Placeholder and fake classes have no methods.
+    pub(super) fn find_class_with_method(&self, class: Class, sel: SEL) -> Option<Class> {
+        let mut class = class;
+        while class != nil {
+            let host_object = self.get_host_object(class).unwrap();
+            let Some(&ClassHostObject {
+                superclass,
+                ref methods,
+                ..
+            }) = host_object.as_any().downcast_ref()
+            else {
+                return None;
+            };
+            if methods.contains_key(&sel) {
+                return Some(class);
+            }
+            class = superclass;
+        }
+        None
+    }
+
+    /// Get the [Method] handle for a class's own method, creating it if
+    /// necessary.
+    fn get_method_handle(&mut self, class: Class, sel: SEL, mem: &mut Mem) -> Method {
+        if let Some(&method) = self.method_handles.get(&(class, sel)) {
+            return method;
+        }
+        let method = mem.alloc_and_write(method_t {
+            name: sel.to_ptr(),
+            types: Ptr::null(),
+            imp: GuestFunction::from_addr_with_thumb_bit(0),
+        });
+        self.method_handles.insert((class, sel), method);
+        self.method_handle_targets.insert(method, (class, sel));
+        method
+    }
+}
+
+/// Get the implementation of a class's own method as a guest function, or
+/// NULL if the class has no such method. A guest function is created for host
+/// implementations.
+fn get_guest_imp(env: &mut Environment, class: Class, sel: SEL) -> GuestIMP {
+    let host_imp = match env.objc.borrow::<ClassHostObject>(class).methods.get(&sel) {
+        None => return GuestFunction::from_addr_with_thumb_bit(0),
+        Some(&IMP::Guest(guest_imp)) => return guest_imp,
+        Some(&IMP::Host(host_imp)) => host_imp,
+    };
+    let key = host_imp as *const dyn HostIMP as *const () as usize;
+    if let Some(&guest_imp) = env.objc.host_imp_trampolines.get(&key) {
+        return guest_imp;
+    }
+    let guest_imp = env.dyld.create_guest_function(
+        &mut env.mem,
+        &mut env.cpu,
+        "[host method implementation]",
+        host_imp.as_call_from_guest(),
+    );
+    env.objc.host_imp_trampolines.insert(key, guest_imp);
+    guest_imp
+}
+
+/// Replace the implementation of a class's own method (or add it), returning
+/// the old implementation or NULL.
+fn set_guest_imp(env: &mut Environment, class: Class, sel: SEL, imp: GuestIMP) -> GuestIMP {
+    let old_imp = get_guest_imp(env, class, sel);
+    let host_object = env.objc.borrow_mut::<ClassHostObject>(class);
+    log_dbg!(
+        "Setting implementation of method \"{}\" of \"{}\" {:?} to {:?}",
+        sel.as_str(&env.mem),
+        host_object.name,
+        class,
+        imp,
+    );
+    host_object.methods.insert(sel, IMP::Guest(imp));
+    env.objc.flush_method_cache();
+    old_imp
+}
+
+/// Standard Objective-C runtime function for looking up an instance method.
+/// The method may be inherited from a superclass.
+pub(super) fn class_getInstanceMethod(env: &mut Environment, class: Class, sel: SEL) -> Method {
+    if class == nil {
+        return Ptr::null();
+    }
+    let Some(class) = env.objc.find_class_with_method(class, sel) else {
+        return Ptr::null();
+    };
+    env.objc.get_method_handle(class, sel, &mut env.mem)
+}
+
+/// Standard Objective-C runtime function for looking up a class method.
+pub(super) fn class_getClassMethod(env: &mut Environment, class: Class, sel: SEL) -> Method {
+    if class == nil {
+        return Ptr::null();
+    }
+    let metaclass = ObjC::read_isa(class, &env.mem);
+    class_getInstanceMethod(env, metaclass, sel)
+}
+
+/// Standard Objective-C runtime function for getting the implementation of a
+/// method.
+pub(super) fn method_getImplementation(env: &mut Environment, method: Method) -> GuestIMP {
+    if method.is_null() {
+        return GuestFunction::from_addr_with_thumb_bit(0);
+    }
+    let (class, sel) = env.objc.method_handle_targets[&method];
+    get_guest_imp(env, class, sel)
+}
+
+/// Standard Objective-C runtime function for replacing the implementation of a
+/// method. Returns the old implementation.
+pub(super) fn method_setImplementation(
+    env: &mut Environment,
+    method: Method,
+    imp: GuestIMP,
+) -> GuestIMP {
+    if method.is_null() {
+        return GuestFunction::from_addr_with_thumb_bit(0);
+    }
+    let (class, sel) = env.objc.method_handle_targets[&method];
+    set_guest_imp(env, class, sel, imp)
+}
+
+/// Standard Objective-C runtime function for swapping the implementations of
+/// two methods, i.e. "swizzling".
+pub(super) fn method_exchangeImplementations(env: &mut Environment, m1: Method, m2: Method) {
+    if m1.is_null() || m2.is_null() || m1 == m2 {
+        return;
+    }
+    let (class1, sel1) = env.objc.method_handle_targets[&m1];
+    let (class2, sel2) = env.objc.method_handle_targets[&m2];
+    log_dbg!(
+        "Exchanging implementations of \"{}\" ({:?}) and \"{}\" ({:?})",
+        sel1.as_str(&env.mem),
+        class1,
+        sel2.as_str(&env.mem),
+        class2,
+    );
+    // The IMPs are moved rather than converted to guest functions, so that
+    // host implementations can still be called directly.
+    let imp1 = env
+        .objc
+        .borrow_mut::<ClassHostObject>(class1)
+        .methods
+        .remove(&sel1)
+        .unwrap();
+    let imp2 = env
+        .objc
+        .borrow_mut::<ClassHostObject>(class2)
+        .methods
+        .insert(sel2, imp1)
+        .unwrap();
+    env.objc
+        .borrow_mut::<ClassHostObject>(class1)
+        .methods
+        .insert(sel1, imp2);
+    env.objc.flush_method_cache();
+}
+
+/// Standard Objective-C runtime function for replacing a class's own method
+/// implementation, or adding it if the class doesn't have its own
+/// implementation. Returns the old implementation, or NULL if there was none.
+pub(super) fn class_replaceMethod(
+    env: &mut Environment,
+    class: Class,
+    selector: SEL,
+    imp: GuestIMP,
+    _types: ConstPtr<u8>,
+) -> GuestIMP {
+    // TODO: support type strings
+    let Some(host_object) = env.objc.get_host_object(class) else {
+        return GuestFunction::from_addr_with_thumb_bit(0);
+    };
+    if !host_object.as_any().is::<ClassHostObject>() {
+        return GuestFunction::from_addr_with_thumb_bit(0);
+    }
+    set_guest_imp(env, class, selector, imp)
+}
+
+/// Compares method lookup with and without the method cache, for a selector
+/// implemented by the root of a deep class hierarchy. This is ignored by
+/// default because it only prints timings. Run it with:
+///
+/// `cargo test --release --lib bench_method_lookup -- --ignored --nocapture`
+#[cfg(test)]
+#[test]
+#[ignore]
+fn bench_method_lookup() {
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    const DEPTH: usize = 20;
+    const LOOKUPS: u32 = 1_000_000;
+
+    let mut mem = Mem::new();
+    let mut objc = ObjC::new();
+    objc.register_host_selectors(&mut mem);
+
+    let mut class = objc.get_known_class("NSObject", &mut mem);
+    for i in 0..DEPTH {
+        class = objc.allocate_class_pair(class, format!("_touchHLE_Deep{}", i), &mut mem);
+        objc.register_class_pair(class);
+    }
+    let sel = objc.lookup_selector("init").unwrap();
+
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        assert!(objc.lookup_method(black_box(class), black_box(sel)).is_ok());
+    }
+    let uncached = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        assert!(objc
+            .lookup_method_cached(black_box(class), black_box(sel))
+            .is_ok());
+    }
+    let cached = start.elapsed();
+
+    println!(
+        "{} lookups of -init through {} subclasses of NSObject: {:?} uncached, {:?} cached",
+        LOOKUPS, DEPTH, uncached, cached
+    );
+}
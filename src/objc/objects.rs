@@ -165,6 +165,8 @@ impl super::ObjC {
         assert!(instance_size >= guest_size_of::<objc_object>());
 
         let ptr: MutPtr<objc_object> = mem.alloc(instance_size).cast();
+        // Guest code expects the ivars to start out zeroed.
+        mem.bytes_at_mut(ptr.cast(), instance_size).fill(0);
         mem.write(ptr, guest_object);
         assert!(!self.objects.contains_key(&ptr));
         self.objects.insert(
@@ -240,16 +242,20 @@ impl super::ObjC {
     /// Get a reference to a host object and downcast it. Panics if there is
     /// no such object, or if downcasting fails.
     pub fn borrow<T: AnyHostObject + 'static>(&self, object: id) -> &T {
+        self.try_borrow(object).unwrap()
+    }
+
+    /// Get a reference to a host object and downcast it, if the object exists
+    /// and has a host object of that type. This is useful for host code that
+    /// accepts objects of more than one type.
+    pub fn try_borrow<T: AnyHostObject + 'static>(&self, object: id) -> Option<&T> {
         let mut host_object: &(dyn AnyHostObject + 'static) =
-            &*self.objects.get(&object).unwrap().host_object;
+            &*self.objects.get(&object)?.host_object;
         loop {
             if let Some(res) = host_object.as_any().downcast_ref() {
-                return res;
-            } else if let Some(next) = host_object.as_superclass() {
-                host_object = next;
-            } else {
-                panic!();
+                return Some(res);
             }
+            host_object = host_object.as_superclass()?;
         }
     }
 
@@ -334,6 +340,9 @@ impl super::ObjC {
 
         std::mem::drop(host_object);
 
+        self.orphan_associated_objects(object);
+        self.clear_weak_references(object, mem);
+
         mem.free(object.cast());
     }
 }
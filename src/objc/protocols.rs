@@ -0,0 +1,110 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Handling of Objective-C protocols.
+//!
+//! We don't currently do anything with the methods a protocol declares, we
+//! only keep track of which protocols each class conforms to, so that
+//! `conformsToProtocol:` works.
+//!
+//! Resources:
+//! - Apple's [The Objective-C Programming Language](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjectiveC/Chapters/ocProtocols.html)
+//! - [Apple's documentation of `conformsToProtocol:`](https://developer.apple.com/documentation/objectivec/1418893-conformstoprotocol?language=objc)
+
+use super::{id, method_list_t, nil, Class, ClassHostObject, ObjC};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, SafeRead};
+use std::collections::HashSet;
+
+/// The layout of a protocol list in an app binary.
+///
+/// The name, field names and field layout are based on what Ghidra outputs.
+#[repr(C, packed)]
+pub(super) struct protocol_list_t {
+    count: GuestUSize,
+    // entries (pointers to protocol_t) follow the struct
+}
+unsafe impl SafeRead for protocol_list_t {}
+
+/// The layout of a protocol in an app binary.
+///
+/// The name, field names and field layout are based on what Ghidra outputs.
+#[repr(C, packed)]
+pub(super) struct protocol_t {
+    _isa: id,
+    name: ConstPtr<u8>,
+    protocols: ConstPtr<protocol_list_t>,
+    _instance_methods: ConstPtr<method_list_t>,
+    _class_methods: ConstPtr<method_list_t>,
+    _optional_instance_methods: ConstPtr<method_list_t>,
+    _optional_class_methods: ConstPtr<method_list_t>,
+    _instance_properties: ConstVoidPtr, // property list (TODO)
+}
+unsafe impl SafeRead for protocol_t {}
+
+/// Read the names of the protocols in a protocol list from the app binary,
+/// including the names of all protocols those protocols adopt, and add them to
+/// `names`.
+pub(super) fn add_protocol_names_from_bin(
+    protocol_list_ptr: ConstPtr<protocol_list_t>,
+    mem: &Mem,
+    names: &mut HashSet<String>,
+) {
+    if protocol_list_ptr.is_null() {
+        return;
+    }
+
+    let protocol_list_t { count } = mem.read(protocol_list_ptr);
+    let protocols_base_ptr: ConstPtr<ConstPtr<protocol_t>> = (protocol_list_ptr + 1).cast();
+
+    for i in 0..count {
+        let protocol_ptr = mem.read(protocols_base_ptr + i);
+        let protocol_t {
+            name, protocols, ..
+        } = mem.read(protocol_ptr);
+
+        let name = mem.cstr_at_utf8(name).unwrap();
+        // Checking for duplicates isn't just an optimisation: it also prevents
+        // infinite recursion if there's somehow a cycle of protocols.
+        if names.insert(name.to_string()) {
+            add_protocol_names_from_bin(protocols, mem, names);
+        }
+    }
+}
+
+impl ObjC {
+    /// Get the name of a protocol object (e.g. the result of a `@protocol()`
+    /// expression in the app).
+    pub fn get_protocol_name(protocol: id, mem: &Mem) -> &str {
+        let protocol_t { name, .. } = mem.read(protocol.cast::<protocol_t>());
+        mem.cstr_at_utf8(name).unwrap()
+    }
+
+    /// Checks if a class or any of its superclasses conforms to a protocol.
+    /// Nothing conforms to a [nil] protocol.
+    pub fn class_conforms_to_protocol(&self, class: Class, protocol: id, mem: &Mem) -> bool {
+        if protocol == nil {
+            return false;
+        }
+        let protocol_name = Self::get_protocol_name(protocol, mem);
+
+        let mut class = class;
+        while class != nil {
+            // Placeholder and fake classes have no protocol information.
+            let Some(&ClassHostObject {
+                superclass,
+                ref protocols,
+                ..
+            }) = self.get_host_object(class).unwrap().as_any().downcast_ref()
+            else {
+                return false;
+            };
+            if protocols.contains(protocol_name) {
+                return true;
+            }
+            class = superclass;
+        }
+        false
+    }
+}
@@ -17,7 +17,7 @@
 use super::ObjC;
 use crate::abi::{GuestArg, GuestRet};
 use crate::mach_o::MachO;
-use crate::mem::{ConstPtr, Mem, MutPtr, Ptr};
+use crate::mem::{ConstPtr, Mem, MutPtr, Ptr, SafeRead};
 use crate::Environment;
 
 /// Create a string literal for a selector from Objective-C message syntax
@@ -38,6 +38,8 @@ pub use crate::selector; // #[macro_export] is weird...
 #[repr(transparent)]
 #[allow(clippy::upper_case_acronyms)] // silly clippit, this isn't an acronym!
 pub struct SEL(ConstPtr<u8>);
+// All bit patterns are valid, as for pointers.
+unsafe impl SafeRead for SEL {}
 
 impl GuestArg for SEL {
     const REG_COUNT: usize = <ConstPtr<u8> as GuestArg>::REG_COUNT;
@@ -58,6 +60,11 @@ impl GuestRet for SEL {
 }
 
 impl SEL {
+    /// Get the pointer to the selector's C string.
+    pub(super) fn to_ptr(self) -> ConstPtr<u8> {
+        self.0
+    }
+
     pub fn as_str(self, mem: &Mem) -> &str {
         // selectors are probably always UTF-8 but this hasn't been verified
         mem.cstr_at_utf8(self.0).unwrap()
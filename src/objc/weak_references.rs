@@ -0,0 +1,142 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Handling of zeroing weak references (`objc_storeWeak` and friends).
+//!
+//! Apps compiled with ARC call these functions for `__weak` variables and
+//! `weak` properties. A weak reference doesn't keep the object alive, but it is
+//! set to `nil` when the object is deallocated, so the runtime has to know
+//! where all the weak references to an object are stored.
+//!
+//! Resources:
+//! - [Clang's documentation of the ARC runtime functions](https://clang.llvm.org/docs/AutomaticReferenceCounting.html#runtime-support)
+
+use super::{autorelease, id, nil, retain, ObjC};
+use crate::mem::{Mem, MutPtr};
+use crate::Environment;
+
+impl ObjC {
+    /// Make `location` no longer count as a weak reference to `object`.
+    fn unregister_weak_reference(&mut self, object: id, location: MutPtr<id>) {
+        let Some(locations) = self.weak_references.get_mut(&object) else {
+            return;
+        };
+        locations.remove(&location);
+        if locations.is_empty() {
+            self.weak_references.remove(&object);
+        }
+    }
+
+    /// Store a weak reference to `new_object` (possibly [nil]) at `location`,
+    /// where `old_object` is the weak reference currently stored there.
+    fn store_weak(
+        &mut self,
+        mem: &mut Mem,
+        location: MutPtr<id>,
+        old_object: id,
+        new_object: id,
+    ) -> id {
+        if old_object != nil {
+            self.unregister_weak_reference(old_object, location);
+        }
+
+        // A weak reference to an object that doesn't exist (anymore) would
+        // never be cleared, so it is stored as nil instead.
+        let new_object = if new_object != nil && self.get_host_object(new_object).is_some() {
+            self.weak_references
+                .entry(new_object)
+                .or_default()
+                .insert(location);
+            new_object
+        } else {
+            nil
+        };
+
+        mem.write(location, new_object);
+        new_object
+    }
+
+    /// For use by [ObjC::dealloc_object]: set all the weak references to an
+    /// object that is being deallocated to [nil].
+    pub(super) fn clear_weak_references(&mut self, object: id, mem: &mut Mem) {
+        let Some(locations) = self.weak_references.remove(&object) else {
+            return;
+        };
+        for location in locations {
+            // Non-ARC code might have overwritten the variable without telling
+            // us, in which case it's no longer ours to clear.
+            if mem.read(location) == object {
+                mem.write(location, nil);
+            }
+        }
+    }
+}
+
+pub(super) fn objc_initWeak(env: &mut Environment, location: MutPtr<id>, object: id) -> id {
+    // The location is uninitialized, so whatever it contains is ignored.
+    env.objc.store_weak(&mut env.mem, location, nil, object)
+}
+
+pub(super) fn objc_storeWeak(env: &mut Environment, location: MutPtr<id>, object: id) -> id {
+    let old_object = env.mem.read(location);
+    env.objc
+        .store_weak(&mut env.mem, location, old_object, object)
+}
+
+pub(super) fn objc_destroyWeak(env: &mut Environment, location: MutPtr<id>) {
+    let old_object = env.mem.read(location);
+    env.objc.store_weak(&mut env.mem, location, old_object, nil);
+}
+
+/// Returns the object with a +1 reference count, or [nil].
+pub(super) fn objc_loadWeakRetained(env: &mut Environment, location: MutPtr<id>) -> id {
+    let object = env.mem.read(location);
+    retain(env, object)
+}
+
+/// Like [objc_loadWeakRetained], but the object is autoreleased, so the caller
+/// doesn't own it.
+pub(super) fn objc_loadWeak(env: &mut Environment, location: MutPtr<id>) -> id {
+    let object = objc_loadWeakRetained(env, location);
+    autorelease(env, object)
+}
+
+pub(super) fn objc_copyWeak(env: &mut Environment, to: MutPtr<id>, from: MutPtr<id>) {
+    let object = env.mem.read(from);
+    env.objc.store_weak(&mut env.mem, to, nil, object);
+}
+
+pub(super) fn objc_moveWeak(env: &mut Environment, to: MutPtr<id>, from: MutPtr<id>) {
+    objc_copyWeak(env, to, from);
+    objc_destroyWeak(env, from);
+}
+
+#[cfg(test)]
+#[test]
+fn test_weak_reference_cleared_on_dealloc() {
+    let mut mem = Mem::new();
+    let mut objc = ObjC::new();
+    objc.register_host_selectors(&mut mem);
+
+    let class = objc.get_known_class("NSObject", &mut mem);
+    let object = objc.alloc_object(class, Box::new(super::TrivialHostObject), &mut mem);
+    let location: MutPtr<id> = mem.alloc_and_write(nil);
+    let other_location: MutPtr<id> = mem.alloc_and_write(nil);
+
+    assert_eq!(objc.store_weak(&mut mem, location, nil, object), object);
+    assert_eq!(
+        objc.store_weak(&mut mem, other_location, nil, object),
+        object
+    );
+    // Overwriting a weak reference unregisters it.
+    objc.store_weak(&mut mem, other_location, object, nil);
+    mem.write(other_location, object);
+
+    objc.dealloc_object(object, &mut mem);
+    assert_eq!(mem.read(location), nil);
+    // This location wasn't a weak reference anymore, so it wasn't touched.
+    assert_eq!(mem.read(other_location), object);
+    assert!(objc.weak_references.is_empty());
+}
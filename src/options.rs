@@ -5,40 +5,106 @@
  */
 //! Parsing and management of user-configurable options, e.g. for input methods.
 
+use crate::frameworks::opengles::profile::GLProfile;
 use crate::gles::GLESImplementation;
-use crate::window::DeviceOrientation;
+use crate::window::{input_recording, input_script, DeviceOrientation, PresentScaling};
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Read};
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::num::NonZeroU32;
+use std::time::Duration;
 
 pub const DOCUMENTATION: &str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/OPTIONS_HELP.txt"));
 
 /// Game controller button for `--button-to-touch=` option.
-#[derive(Hash, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum Button {
     A,
     B,
     X,
     Y,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    LeftShoulder,
+    Start,
+    Back,
+}
+impl Button {
+    /// Look up a button by the name used for it in `--button-to-touch=`, which
+    /// is the same as its [Debug] representation.
+    pub fn from_name(name: &str) -> Option<Button> {
+        match name {
+            "A" => Some(Button::A),
+            "B" => Some(Button::B),
+            "X" => Some(Button::X),
+            "Y" => Some(Button::Y),
+            "DPadUp" => Some(Button::DPadUp),
+            "DPadDown" => Some(Button::DPadDown),
+            "DPadLeft" => Some(Button::DPadLeft),
+            "DPadRight" => Some(Button::DPadRight),
+            "LeftShoulder" => Some(Button::LeftShoulder),
+            "Start" => Some(Button::Start),
+            "Back" => Some(Button::Back),
+            _ => None,
+        }
+    }
+}
+
+/// Source of simulated accelerometer input for `--accelerometer=` option.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccelerometerSource {
+    /// Use a game controller if one is connected, otherwise the device's real
+    /// accelerometer if it has one, otherwise the keyboard.
+    Auto,
+    Device,
+    Controller,
+    Keyboard,
 }
 
 /// Struct containing all user-configurable options.
 pub struct Options {
     pub fullscreen: bool,
     pub initial_orientation: DeviceOrientation,
+    pub device_model: String,
+    pub device_name: String,
+    pub system_version: String,
+    /// Offset of the device's time zone from UTC, in seconds.
+    pub time_zone_offset: i32,
+    /// Identifier of the device's locale, e.g. `en_US`.
+    pub locale: String,
     pub scale_hack: NonZeroU32,
+    pub present_scaling: PresentScaling,
     pub deadzone: f32,
     pub x_tilt_range: f32,
     pub y_tilt_range: f32,
     pub x_tilt_offset: f32,
     pub y_tilt_offset: f32,
     pub button_to_touch: HashMap<Button, (f32, f32)>,
+    pub accelerometer_source: AccelerometerSource,
     pub gles1_implementation: Option<GLESImplementation>,
+    pub gl_profile: GLProfile,
+    /// Replacement for the profile's OpenGL ES extension list.
+    pub gl_extensions: Option<Vec<String>>,
+    /// Limit in bytes on the estimated texture memory use, above which the
+    /// compositor evicts textures it can regenerate.
+    pub texture_memory_budget: Option<usize>,
+    /// Skip drawing Core Animation layers hidden behind opaque layers.
+    pub occlusion_culling: bool,
     pub direct_memory_access: bool,
+    pub check_memory_bounds: bool,
     pub gdb_listen_addrs: Option<Vec<SocketAddr>>,
     pub headless: bool,
+    pub offscreen: bool,
+    pub input_script: Option<Vec<input_script::ScriptedEvent>>,
+    pub record_input: Option<String>,
+    pub replay_input: Option<Vec<(Duration, input_recording::RecordedInput)>>,
+    pub network_access: bool,
+    pub random_seed: Option<u32>,
+    pub virtual_clock_step: Option<Duration>,
+    pub virtual_clock_frames: Option<u64>,
 }
 
 impl Default for Options {
@@ -46,17 +112,37 @@ impl Default for Options {
         Options {
             fullscreen: false,
             initial_orientation: DeviceOrientation::Portrait,
+            device_model: "iPhone".to_string(),
+            device_name: "iPhone".to_string(),
+            system_version: "2.0".to_string(),
+            time_zone_offset: 0,
+            locale: "en_US".to_string(),
             scale_hack: NonZeroU32::new(1).unwrap(),
+            present_scaling: PresentScaling::Linear,
             deadzone: 0.1,
             x_tilt_range: 60.0,
             y_tilt_range: 60.0,
             x_tilt_offset: 0.0,
             y_tilt_offset: 0.0,
             button_to_touch: HashMap::new(),
+            accelerometer_source: AccelerometerSource::Auto,
             gles1_implementation: None,
+            gl_profile: GLProfile::Mbx,
+            gl_extensions: None,
+            texture_memory_budget: None,
+            occlusion_culling: false,
             direct_memory_access: true,
+            check_memory_bounds: false,
             gdb_listen_addrs: None,
             headless: false,
+            offscreen: false,
+            input_script: None,
+            record_input: None,
+            replay_input: None,
+            network_access: false,
+            random_seed: None,
+            virtual_clock_step: None,
+            virtual_clock_frames: None,
         }
     }
 }
@@ -82,10 +168,63 @@ impl Options {
             self.initial_orientation = DeviceOrientation::LandscapeLeft;
         } else if arg == "--landscape-right" {
             self.initial_orientation = DeviceOrientation::LandscapeRight;
+        } else if let Some(value) = arg.strip_prefix("--device-model=") {
+            if !["iPhone", "iPod touch", "iPad"].contains(&value) {
+                return Err("Unrecognized --device-model= value".to_string());
+            }
+            self.device_model = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--device-name=") {
+            self.device_name = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--system-version=") {
+            let components: Vec<&str> = value.split('.').collect();
+            if components.len() > 3
+                || components
+                    .iter()
+                    .any(|c| c.is_empty() || !c.bytes().all(|b| b.is_ascii_digit()))
+            {
+                return Err("Invalid value for --system-version=".to_string());
+            }
+            self.system_version = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--time-zone-offset=") {
+            let hours: f64 = value
+                .parse()
+                .map_err(|_| "Invalid value for --time-zone-offset=".to_string())?;
+            if !(-12.0..=14.0).contains(&hours) {
+                return Err("--time-zone-offset= must be between -12 and 14".to_string());
+            }
+            self.time_zone_offset = (hours * 3600.0).round() as i32;
+        } else if let Some(value) = arg.strip_prefix("--locale=") {
+            let identifier = if value == "host" {
+                // Turn e.g. "sv_SE.UTF-8" into just "sv_SE".
+                let lang = std::env::var("LANG").unwrap_or_default();
+                let lang = lang.split(['.', '@']).next().unwrap().to_string();
+                if lang.is_empty() || lang == "C" || lang == "POSIX" {
+                    "en_US".to_string()
+                } else {
+                    lang
+                }
+            } else {
+                value.to_string()
+            };
+            if identifier.is_empty()
+                || !identifier
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+            {
+                return Err("Invalid value for --locale=".to_string());
+            }
+            self.locale = identifier;
         } else if let Some(value) = arg.strip_prefix("--scale-hack=") {
             self.scale_hack = value
                 .parse()
                 .map_err(|_| "Invalid scale hack factor".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--scaling=") {
+            self.present_scaling = match value {
+                "linear" => PresentScaling::Linear,
+                "nearest" => PresentScaling::Nearest,
+                "integer" => PresentScaling::Integer,
+                _ => return Err("Unrecognized --scaling= value".to_string()),
+            };
         } else if let Some(value) = arg.strip_prefix("--deadzone=") {
             self.deadzone = parse_degrees(value, "deadzone")?;
         } else if let Some(value) = arg.strip_prefix("--x-tilt-range=") {
@@ -96,6 +235,14 @@ impl Options {
             self.x_tilt_offset = parse_degrees(value, "X tilt offset")?;
         } else if let Some(value) = arg.strip_prefix("--y-tilt-offset=") {
             self.y_tilt_offset = parse_degrees(value, "Y tilt offset")?;
+        } else if let Some(value) = arg.strip_prefix("--accelerometer=") {
+            self.accelerometer_source = match value {
+                "auto" => AccelerometerSource::Auto,
+                "device" => AccelerometerSource::Device,
+                "controller" => AccelerometerSource::Controller,
+                "keyboard" => AccelerometerSource::Keyboard,
+                _ => return Err("Unrecognized --accelerometer= value".to_string()),
+            };
         } else if let Some(values) = arg.strip_prefix("--button-to-touch=") {
             let (button, coords) = values
                 .split_once(',')
@@ -103,13 +250,8 @@ impl Options {
             let (x, y) = coords
                 .split_once(',')
                 .ok_or_else(|| "--button-to-touch= requires three values".to_string())?;
-            let button = match button {
-                "A" => Ok(Button::A),
-                "B" => Ok(Button::B),
-                "X" => Ok(Button::X),
-                "Y" => Ok(Button::Y),
-                _ => Err("Invalid button for --button-to-touch=".to_string()),
-            }?;
+            let button = Button::from_name(button)
+                .ok_or_else(|| "Invalid button for --button-to-touch=".to_string())?;
             let x: f32 = x
                 .parse()
                 .map_err(|_| "Invalid X co-ordinate for --button-to-touch=".to_string())?;
@@ -122,8 +264,35 @@ impl Options {
                 GLESImplementation::from_short_name(value)
                     .map_err(|_| "Unrecognized --gles1= value".to_string())?,
             );
+        } else if let Some(value) = arg.strip_prefix("--gl-profile=") {
+            self.gl_profile = match value {
+                "mbx" => GLProfile::Mbx,
+                "sgx" => GLProfile::Sgx,
+                "host" => GLProfile::Host,
+                _ => return Err("Unrecognized --gl-profile= value".to_string()),
+            };
+        } else if let Some(value) = arg.strip_prefix("--gl-extensions=") {
+            self.gl_extensions = Some(
+                value
+                    .split(',')
+                    .filter(|extension| !extension.is_empty())
+                    .map(|extension| extension.to_string())
+                    .collect(),
+            );
+        } else if let Some(value) = arg.strip_prefix("--texture-memory-budget=") {
+            let mebibytes: usize = value
+                .parse()
+                .map_err(|_| "Invalid value for --texture-memory-budget=".to_string())?;
+            self.texture_memory_budget = Some(mebibytes * 1024 * 1024);
+        } else if arg == "--occlusion-culling" {
+            self.occlusion_culling = true;
         } else if arg == "--disable-direct-memory-access" {
             self.direct_memory_access = false;
+        } else if arg == "--check-memory-bounds" {
+            // The CPU's memory accesses can only be checked if they go through
+            // the memory access callbacks.
+            self.check_memory_bounds = true;
+            self.direct_memory_access = false;
         } else if let Some(address) = arg.strip_prefix("--gdb=") {
             let addrs = address
                 .to_socket_addrs()
@@ -132,6 +301,44 @@ impl Options {
             self.gdb_listen_addrs = Some(addrs);
         } else if arg == "--headless" {
             self.headless = true;
+        } else if arg == "--offscreen" {
+            self.offscreen = true;
+        } else if let Some(path) = arg.strip_prefix("--input-script=") {
+            let script = std::fs::read_to_string(path)
+                .map_err(|e| format!("Could not read input script {:?}: {}", path, e))?;
+            let events = input_script::parse(&script)
+                .map_err(|e| format!("Could not parse input script {:?}: {}", path, e))?;
+            self.input_script = Some(events);
+        } else if let Some(path) = arg.strip_prefix("--record-input=") {
+            self.record_input = Some(path.to_string());
+        } else if let Some(path) = arg.strip_prefix("--replay-input=") {
+            let recording = std::fs::read_to_string(path)
+                .map_err(|e| format!("Could not read input recording {:?}: {}", path, e))?;
+            let inputs = input_recording::parse(&recording)
+                .map_err(|e| format!("Could not parse input recording {:?}: {}", path, e))?;
+            self.replay_input = Some(inputs);
+        } else if arg == "--allow-network-access" {
+            self.network_access = true;
+        } else if let Some(value) = arg.strip_prefix("--random-seed=") {
+            self.random_seed = Some(
+                value
+                    .parse()
+                    .map_err(|_| "Invalid value for --random-seed=".to_string())?,
+            );
+        } else if let Some(value) = arg.strip_prefix("--virtual-clock=") {
+            let frame_rate: f64 = value
+                .parse()
+                .map_err(|_| "Invalid value for --virtual-clock=".to_string())?;
+            if !(frame_rate.is_finite() && frame_rate > 0.0) {
+                return Err("--virtual-clock= must be a positive number".to_string());
+            }
+            self.virtual_clock_step = Some(Duration::from_secs_f64(1.0 / frame_rate));
+        } else if let Some(value) = arg.strip_prefix("--virtual-clock-frames=") {
+            self.virtual_clock_frames = Some(
+                value
+                    .parse()
+                    .map_err(|_| "Invalid value for --virtual-clock-frames=".to_string())?,
+            );
         } else {
             return Ok(false);
         };
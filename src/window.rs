@@ -12,11 +12,18 @@
 //! window system interaction in general, because it is assumed only one window
 //! will be needed for the runtime of the app.
 
+pub mod input_recording;
+pub mod input_script;
+
+use crate::gles::debug_overlay::DebugOverlay;
 use crate::gles::present::present_frame;
 use crate::gles::{create_gles1_ctx, GLES};
 use crate::image::Image;
 use crate::matrix::Matrix;
-use crate::options::Options;
+use crate::options::{AccelerometerSource, Options};
+use input_recording::{RecordedInput, Recorder, Replay};
+use input_script::{ScriptedEvent, ScriptedEventKind};
+use sdl2::keyboard::{Keycode, Scancode};
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::surface::Surface;
@@ -32,6 +39,18 @@ pub enum DeviceOrientation {
     LandscapeLeft,
     LandscapeRight,
 }
+
+/// How the app's output is scaled to fit the window (`--scaling=` option).
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum PresentScaling {
+    /// Scale to fit with linear filtering.
+    Linear,
+    /// Scale to fit with nearest-neighbor filtering.
+    Nearest,
+    /// Scale by the largest whole number that fits, with nearest-neighbor
+    /// filtering, letterboxing the rest.
+    Integer,
+}
 fn size_for_orientation(orientation: DeviceOrientation, scale_hack: NonZeroU32) -> (u32, u32) {
     let scale_hack = scale_hack.get();
     match orientation {
@@ -68,7 +87,25 @@ fn set_sdl2_orientation(orientation: DeviceOrientation) {
     );
 }
 
-#[derive(Debug)]
+/// Identifies something that can touch the simulated touch screen, so that
+/// several concurrent touches can be told apart.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FingerId {
+    /// The left mouse button.
+    Mouse,
+    /// The right mouse button, which simulates a second finger.
+    SecondMouse,
+    /// A finger on a real touch screen, as identified by SDL2.
+    Touch(i64),
+    /// A game controller button mapped with `--button-to-touch=`.
+    Button(crate::options::Button),
+    /// The analog stick-controlled virtual cursor.
+    VirtualCursor,
+    /// Touches from `--input-script=`.
+    Script,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Event {
     /// User requested quit.
     Quit,
@@ -78,9 +115,19 @@ pub enum Event {
     /// OS has informed touchHLE it will soon terminate.
     /// (iOS `applicationWillTerminate:`, Android `onDestroy()`)
     AppWillTerminate,
-    TouchDown((f32, f32)),
-    TouchMove((f32, f32)),
-    TouchUp((f32, f32)),
+    TouchDown(FingerId, (f32, f32)),
+    TouchMove(FingerId, (f32, f32)),
+    TouchUp(FingerId, (f32, f32)),
+    /// Text typed while text input is active (see [Window::set_text_input]).
+    /// The return key is reported as `"\n"`.
+    TextInput(String),
+    /// The backspace key was pressed while text input is active.
+    DeleteBackward,
+    /// The user asked for the emulated device to be rotated by 90° (F9 for
+    /// counterclockwise, F10 for clockwise).
+    RotateDevice {
+        clockwise: bool,
+    },
 }
 
 pub enum GLVersion {
@@ -124,6 +171,22 @@ pub struct Window {
     /// terminate).
     high_priority_event: Option<Event>,
     enable_event_polling: bool,
+    /// Set when the user presses the screenshot key, see
+    /// [Window::take_screenshot_request].
+    screenshot_requested: bool,
+    /// Set while the debugging overlay is visible (toggled with F11), see
+    /// [Window::debug_overlay_mut].
+    debug_overlay: Option<DebugOverlay>,
+    /// Copy of `offscreen` on [Options]. When this is set, the window is never
+    /// shown and input from the OS is ignored.
+    offscreen: bool,
+    /// Events from `--input-script=` that haven't been delivered yet.
+    input_script: VecDeque<ScriptedEvent>,
+    /// Set when using `--record-input=`.
+    input_recorder: Option<Recorder>,
+    /// Set when using `--replay-input=`. Input from the OS is then ignored,
+    /// other than requests to quit.
+    input_replay: Option<Replay>,
     #[cfg(target_os = "macos")]
     max_height: u32,
     #[cfg(target_os = "macos")]
@@ -132,6 +195,8 @@ pub struct Window {
     /// [Self::rotatable_fullscreen] returns [true].
     fullscreen: bool,
     scale_hack: NonZeroU32,
+    /// Copy of `present_scaling` on [Options].
+    present_scaling: PresentScaling,
     internal_gl_ctx: Option<Box<dyn GLES>>,
     splash_image: Option<Image>,
     device_orientation: DeviceOrientation,
@@ -156,8 +221,27 @@ impl Window {
         launch_image: Option<Image>,
         options: &Options,
     ) -> Window {
+        let offscreen = options.offscreen;
+        if offscreen {
+            // This driver renders to EGL pbuffers, so it works even if there is
+            // no display (e.g. on a CI server).
+            sdl2::hint::set("SDL_VIDEODRIVER", "offscreen");
+        }
         let sdl_ctx = sdl2::init().unwrap();
-        let video_ctx = sdl_ctx.video().unwrap();
+        let video_ctx = sdl_ctx
+            .video()
+            .or_else(|e| {
+                if !offscreen {
+                    return Err(e);
+                }
+                log!(
+                    "Warning: SDL2's offscreen video driver is unavailable ({}), using a hidden window instead.",
+                    e
+                );
+                sdl2::hint::set("SDL_VIDEODRIVER", "");
+                sdl_ctx.video()
+            })
+            .unwrap();
 
         // The "hidapi" feature of rust-sdl2 is enabled so that sdl2::sensor
         // is available, but we don't want to enable SDL's HIDAPI controller
@@ -183,12 +267,22 @@ impl Window {
         video_ctx.enable_screen_saver();
 
         let scale_hack = options.scale_hack;
-        // TODO: some apps specify their orientation in Info.plist, we could use
-        // that here.
+        // Apps that specify their orientation in Info.plist get rotated into
+        // it by UIApplication later.
+        // TODO: use it here too, so the window doesn't visibly rotate?
         let device_orientation = options.initial_orientation;
-        let fullscreen = options.fullscreen;
+        let fullscreen = options.fullscreen && !offscreen;
 
-        let mut window = if Self::rotatable_fullscreen() {
+        let mut window = if offscreen {
+            let (width, height) = size_for_orientation(device_orientation, scale_hack);
+            let window = video_ctx
+                .window(title, width, height)
+                .hidden()
+                .opengl()
+                .build()
+                .unwrap();
+            window
+        } else if Self::rotatable_fullscreen() {
             // Without this, SDL will force fullscreen mode to be portrait.
             set_sdl2_orientation(device_orientation);
             let screen_size = video_ctx.display_bounds(0).unwrap().size();
@@ -233,6 +327,21 @@ impl Window {
 
         let event_pump = sdl_ctx.event_pump().unwrap();
 
+        let input_recorder = options
+            .record_input
+            .as_ref()
+            .and_then(|path| match Recorder::create(path) {
+                Ok(recorder) => {
+                    log!("Recording input to {:?}.", path);
+                    Some(recorder)
+                }
+                Err(e) => {
+                    log!("Warning: couldn't create input recording {:?}: {}", path, e);
+                    None
+                }
+            });
+        let input_replay = options.replay_input.clone().map(Replay::new);
+
         let controller_ctx = sdl_ctx.game_controller().unwrap();
 
         let sensor_ctx = sdl_ctx.sensor().unwrap();
@@ -261,12 +370,19 @@ impl Window {
             last_polled: Instant::now() - Duration::from_secs(1),
             high_priority_event: None,
             enable_event_polling: true,
+            screenshot_requested: false,
+            debug_overlay: None,
+            offscreen,
+            input_script: options.input_script.clone().unwrap_or_default().into(),
+            input_recorder,
+            input_replay,
             #[cfg(target_os = "macos")]
             max_height,
             #[cfg(target_os = "macos")]
             viewport_y_offset: 0,
             fullscreen,
             scale_hack,
+            present_scaling: options.present_scaling,
             internal_gl_ctx: None,
             splash_image: launch_image,
             device_orientation,
@@ -278,6 +394,10 @@ impl Window {
             virtual_cursor_last: None,
         };
 
+        // SDL2 enables text input by default on some platforms, but it should
+        // only be active while the app is expecting text.
+        window.set_text_input(false);
+
         // Set up OpenGL ES context used for splash screen and app UI rendering
         // (see src/frameworks/core_animation/composition.rs). OpenGL ES is used
         // because SDL2 won't let us use more than one graphics API in the same
@@ -301,7 +421,42 @@ impl Window {
     ///
     /// Since polling can be quite expensive, this function will skip it if it
     /// was called too recently.
-    pub fn poll_for_events(&mut self, options: &Options) {
+    ///
+    /// `uptime` is the time since startup as seen by the app, which is used to
+    /// deliver events from `--input-script=` at the right time.
+    pub fn poll_for_events(&mut self, options: &Options, uptime: Duration) {
+        // Scripted events should arrive at exactly the scheduled time, so they
+        // are checked before deciding whether to skip polling.
+        while self
+            .input_script
+            .front()
+            .map_or(false, |event| event.time <= uptime)
+        {
+            let ScriptedEvent { time, kind } = self.input_script.pop_front().unwrap();
+            log_dbg!("Scripted event at {:?}: {:?}", time, kind);
+            let finger = FingerId::Script;
+            self.event_queue.push_back(match kind {
+                ScriptedEventKind::TouchDown(coords) => {
+                    Event::TouchDown(finger, transform_input_coords(self, coords, true))
+                }
+                ScriptedEventKind::TouchMove(coords) => {
+                    Event::TouchMove(finger, transform_input_coords(self, coords, true))
+                }
+                ScriptedEventKind::TouchUp(coords) => {
+                    Event::TouchUp(finger, transform_input_coords(self, coords, true))
+                }
+                ScriptedEventKind::Screenshot => {
+                    self.screenshot_requested = true;
+                    continue;
+                }
+                ScriptedEventKind::Quit => Event::Quit,
+            });
+        }
+
+        if self.offscreen {
+            return;
+        }
+
         let now = Instant::now();
         // poll roughly twice per frame to try to avoid missing frames sometimes
         if now.duration_since(self.last_polled) < Duration::from_secs_f64(1.0 / 120.0) {
@@ -333,12 +488,38 @@ impl Window {
             let out_y = (y + 0.5) * out_h as f32;
             (out_x, out_y)
         }
+        /// Touch screen co-ordinates are normalized, unlike mouse co-ordinates.
+        fn transform_finger_coords(window: &Window, (x, y): (f32, f32)) -> (f32, f32) {
+            let (width, height) = window.window.size();
+            let coords = (x * width as f32, y * height as f32);
+            transform_input_coords(window, coords, false)
+        }
+        fn translate_mouse_button(button: MouseButton) -> Option<FingerId> {
+            match button {
+                MouseButton::Left => Some(FingerId::Mouse),
+                MouseButton::Right => Some(FingerId::SecondMouse),
+                _ => None,
+            }
+        }
+        // Values used by SDL2 for events generated from other kinds of input.
+        const SDL_TOUCH_MOUSEID: u32 = u32::MAX;
+        const SDL_MOUSE_TOUCHID: i64 = -1;
+
         fn translate_button(button: sdl2::controller::Button) -> Option<crate::options::Button> {
             match button {
                 sdl2::controller::Button::A => Some(crate::options::Button::A),
                 sdl2::controller::Button::B => Some(crate::options::Button::B),
                 sdl2::controller::Button::X => Some(crate::options::Button::X),
                 sdl2::controller::Button::Y => Some(crate::options::Button::Y),
+                sdl2::controller::Button::DPadUp => Some(crate::options::Button::DPadUp),
+                sdl2::controller::Button::DPadDown => Some(crate::options::Button::DPadDown),
+                sdl2::controller::Button::DPadLeft => Some(crate::options::Button::DPadLeft),
+                sdl2::controller::Button::DPadRight => Some(crate::options::Button::DPadRight),
+                sdl2::controller::Button::LeftShoulder => {
+                    Some(crate::options::Button::LeftShoulder)
+                }
+                sdl2::controller::Button::Start => Some(crate::options::Button::Start),
+                sdl2::controller::Button::Back => Some(crate::options::Button::Back),
                 _ => None,
             }
         }
@@ -349,26 +530,123 @@ impl Window {
                 break;
             };
             use sdl2::event::Event as E;
+            // Right-button mouse motion produces a second touch event.
+            let mut extra_event = None;
             self.event_queue.push_back(match event {
                 E::Quit { .. } => Event::Quit,
-                // TODO: support for multi-touch
+                // SDL2 generates mouse events for touch screen input and vice
+                // versa. Those are ignored, since both kinds are handled.
                 E::MouseButtonDown {
+                    which,
                     x,
                     y,
-                    mouse_btn: MouseButton::Left,
+                    mouse_btn,
                     ..
-                } => Event::TouchDown(transform_input_coords(self, (x as f32, y as f32), false)),
+                } if which != SDL_TOUCH_MOUSEID => {
+                    let Some(finger) = translate_mouse_button(mouse_btn) else {
+                        continue;
+                    };
+                    let coords = transform_input_coords(self, (x as f32, y as f32), false);
+                    Event::TouchDown(finger, coords)
+                }
                 E::MouseMotion {
-                    x, y, mousestate, ..
-                } if mousestate.left() => {
-                    Event::TouchMove(transform_input_coords(self, (x as f32, y as f32), false))
+                    which,
+                    x,
+                    y,
+                    mousestate,
+                    ..
+                } if which != SDL_TOUCH_MOUSEID => {
+                    let coords = transform_input_coords(self, (x as f32, y as f32), false);
+                    if mousestate.right() {
+                        extra_event = Some(Event::TouchMove(FingerId::SecondMouse, coords));
+                    }
+                    if mousestate.left() {
+                        Event::TouchMove(FingerId::Mouse, coords)
+                    } else if let Some(event) = extra_event.take() {
+                        event
+                    } else {
+                        continue;
+                    }
                 }
                 E::MouseButtonUp {
+                    which,
+                    x,
+                    y,
+                    mouse_btn,
+                    ..
+                } if which != SDL_TOUCH_MOUSEID => {
+                    let Some(finger) = translate_mouse_button(mouse_btn) else {
+                        continue;
+                    };
+                    let coords = transform_input_coords(self, (x as f32, y as f32), false);
+                    Event::TouchUp(finger, coords)
+                }
+                E::FingerDown {
+                    touch_id,
+                    finger_id,
+                    x,
+                    y,
+                    ..
+                } if touch_id != SDL_MOUSE_TOUCHID => {
+                    let coords = transform_finger_coords(self, (x, y));
+                    Event::TouchDown(FingerId::Touch(finger_id), coords)
+                }
+                E::FingerMotion {
+                    touch_id,
+                    finger_id,
+                    x,
+                    y,
+                    ..
+                } if touch_id != SDL_MOUSE_TOUCHID => {
+                    let coords = transform_finger_coords(self, (x, y));
+                    Event::TouchMove(FingerId::Touch(finger_id), coords)
+                }
+                E::FingerUp {
+                    touch_id,
+                    finger_id,
                     x,
                     y,
-                    mouse_btn: MouseButton::Left,
                     ..
-                } => Event::TouchUp(transform_input_coords(self, (x as f32, y as f32), false)),
+                } if touch_id != SDL_MOUSE_TOUCHID => {
+                    let coords = transform_finger_coords(self, (x, y));
+                    Event::TouchUp(FingerId::Touch(finger_id), coords)
+                }
+                E::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => {
+                    self.screenshot_requested = true;
+                    continue;
+                }
+                E::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => {
+                    self.debug_overlay = match self.debug_overlay {
+                        Some(_) => None,
+                        None => Some(DebugOverlay::default()),
+                    };
+                    continue;
+                }
+                E::KeyDown {
+                    keycode: Some(keycode @ (Keycode::F9 | Keycode::F10)),
+                    repeat: false,
+                    ..
+                } => Event::RotateDevice {
+                    clockwise: keycode == Keycode::F10,
+                },
+                E::TextInput { text, .. } => Event::TextInput(text),
+                // SDL2 doesn't generate text input events for these keys.
+                E::KeyDown {
+                    keycode:
+                        Some(keycode @ (Keycode::Backspace | Keycode::Return | Keycode::KpEnter)),
+                    ..
+                } if self.video_ctx.text_input().is_active() => match keycode {
+                    Keycode::Backspace => Event::DeleteBackward,
+                    _ => Event::TextInput("\n".to_string()),
+                },
                 E::ControllerDeviceAdded { which, .. } => {
                     self.controller_added(which);
                     continue;
@@ -387,13 +665,11 @@ impl Window {
                     let Some(&(x, y)) = options.button_to_touch.get(&button) else {
                         continue;
                     };
+                    let finger = FingerId::Button(button);
+                    let coords = transform_input_coords(self, (x, y), true);
                     match event {
-                        E::ControllerButtonUp { .. } => {
-                            Event::TouchUp(transform_input_coords(self, (x, y), true))
-                        }
-                        E::ControllerButtonDown { .. } => {
-                            Event::TouchDown(transform_input_coords(self, (x, y), true))
-                        }
+                        E::ControllerButtonUp { .. } => Event::TouchUp(finger, coords),
+                        E::ControllerButtonDown { .. } => Event::TouchDown(finger, coords),
                         _ => unreachable!(),
                     }
                 }
@@ -420,35 +696,68 @@ impl Window {
                     continue;
                 }
                 _ => continue,
-            })
+            });
+            self.event_queue.extend(extra_event);
         }
 
         if controller_updated {
             let (new_x, new_y, new_pressed, visible) = self.get_virtual_cursor(options);
             let (old_x, old_y, old_pressed, _) = self.virtual_cursor_last.unwrap_or_default();
             self.virtual_cursor_last = Some((new_x, new_y, new_pressed, visible));
+            let finger = FingerId::VirtualCursor;
+            let coords = transform_input_coords(self, (new_x, new_y), false);
             self.event_queue
                 .push_back(match (old_pressed, new_pressed) {
-                    (false, true) => {
-                        Event::TouchDown(transform_input_coords(self, (new_x, new_y), false))
-                    }
-                    (true, false) => {
-                        Event::TouchUp(transform_input_coords(self, (new_x, new_y), false))
-                    }
+                    (false, true) => Event::TouchDown(finger, coords),
+                    (true, false) => Event::TouchUp(finger, coords),
                     _ if (new_x, new_y) != (old_x, old_y) && new_pressed => {
-                        Event::TouchMove(transform_input_coords(self, (new_x, new_y), false))
+                        Event::TouchMove(finger, coords)
                     }
                     _ => return,
                 });
         }
     }
 
+    /// Returns [true] if the user has pressed the screenshot key (F12) since
+    /// the last call. Whatever presents the next frame should call this and
+    /// then use [crate::gles::present::save_screenshot].
+    pub fn take_screenshot_request(&mut self) -> bool {
+        std::mem::take(&mut self.screenshot_requested)
+    }
+
+    /// Returns the debugging overlay if the user has toggled it on (F11).
+    /// Whatever presents a frame should record it with
+    /// [DebugOverlay::record_frame] and then draw the overlay on top of it.
+    pub fn debug_overlay_mut(&mut self) -> Option<&mut DebugOverlay> {
+        self.debug_overlay.as_mut()
+    }
+
     /// Pop an event from the queue (in FIFO order, except for high priority
     /// events)
-    pub fn pop_event(&mut self) -> Option<Event> {
-        self.high_priority_event
-            .take()
-            .or_else(|| self.event_queue.pop_front())
+    ///
+    /// `uptime` is the time since startup as seen by the app, which is used
+    /// for recording and replaying input.
+    pub fn pop_event(&mut self, uptime: Duration) -> Option<Event> {
+        let event = if let Some(ref mut replay) = self.input_replay {
+            // Input from the OS is discarded, but the user should still be
+            // able to quit.
+            while let Some(event) = self.event_queue.pop_front() {
+                if event == Event::Quit {
+                    self.high_priority_event.get_or_insert(event);
+                }
+            }
+            self.high_priority_event
+                .take()
+                .or_else(|| replay.pop_event(uptime))
+        } else {
+            self.high_priority_event
+                .take()
+                .or_else(|| self.event_queue.pop_front())
+        };
+        if let (Some(recorder), Some(event)) = (&mut self.input_recorder, &event) {
+            recorder.record(uptime, &RecordedInput::Event(event.clone()));
+        }
+        event
     }
 
     fn controller_added(&mut self, joystick_idx: u32) {
@@ -469,8 +778,23 @@ impl Window {
         let controller = self.controllers.remove(idx);
         log!("Warning: Controller disconnected: {}", controller.name());
     }
-    pub fn print_accelerometer_notice(&self) {
+    pub fn print_accelerometer_notice(&self, options: &Options) {
         log!("This app uses the accelerometer.");
+        match options.accelerometer_source {
+            AccelerometerSource::Auto => (),
+            AccelerometerSource::Device => {
+                log!("Your device's accelerometer will be used for accelerometer simulation.");
+                return;
+            }
+            AccelerometerSource::Controller => {
+                log!("Your controller's left analog stick will be used for accelerometer simulation.");
+                return;
+            }
+            AccelerometerSource::Keyboard => {
+                log!("The arrow keys will be used for accelerometer simulation.");
+                return;
+            }
+        }
         if !self.controllers.is_empty() {
             log!("Your connected controller's left analog stick will be used for accelerometer simulation.");
             if self.accelerometer.is_some() {
@@ -480,14 +804,40 @@ impl Window {
             log!("Your device's accelerometer will be used for accelerometer simulation.");
             log!("Connect a controller if you would prefer to use an analog stick.");
         } else if self.controllers.is_empty() {
-            log!("Connect a controller to get accelerometer simulation.");
+            log!("Use the arrow keys or connect a controller to get accelerometer simulation.");
         }
     }
 
     /// Get the real or simulated accelerometer output.
     /// See also [crate::frameworks::uikit::ui_accelerometer].
-    pub fn get_acceleration(&self, options: &Options) -> (f32, f32, f32) {
-        if self.controllers.is_empty() {
+    ///
+    /// `uptime` is the time since startup as seen by the app, which is used
+    /// for recording and replaying input.
+    pub fn get_acceleration(&mut self, options: &Options, uptime: Duration) -> (f32, f32, f32) {
+        let acceleration = if let Some(ref mut replay) = self.input_replay {
+            replay.acceleration(uptime)
+        } else {
+            self.get_host_acceleration(options)
+        };
+        if let Some(ref mut recorder) = self.input_recorder {
+            recorder.record(uptime, &RecordedInput::Acceleration(acceleration));
+        }
+        acceleration
+    }
+
+    fn get_host_acceleration(&self, options: &Options) -> (f32, f32, f32) {
+        let source = match options.accelerometer_source {
+            AccelerometerSource::Auto if !self.controllers.is_empty() => {
+                AccelerometerSource::Controller
+            }
+            AccelerometerSource::Auto if self.accelerometer.is_some() => {
+                AccelerometerSource::Device
+            }
+            AccelerometerSource::Auto => AccelerometerSource::Keyboard,
+            source => source,
+        };
+
+        if source == AccelerometerSource::Device {
             if let Some(ref accelerometer) = self.accelerometer {
                 let data = accelerometer.get_data().unwrap();
                 let sdl2::sensor::SensorData::Accel(data) = data else { panic!(); };
@@ -503,8 +853,17 @@ impl Window {
             }
         }
 
-        // Get left analog stick input. The range is [-1, 1] on each axis.
-        let (x, y, _) = self.get_controller_stick(options, true);
+        // Get left analog stick or keyboard input. The range is [-1, 1] on
+        // each axis. If the device has no accelerometer, it is simulated as
+        // being level with the ground.
+        let (x, y) = match source {
+            AccelerometerSource::Controller => {
+                let (x, y, _) = self.get_controller_stick(options, true);
+                (x, y)
+            }
+            AccelerometerSource::Keyboard => self.get_keyboard_arrows(),
+            _ => (0.0, 0.0),
+        };
 
         // Correct for window rotation
         let [x, y] = self.input_rotation_matrix().transform([x, y]);
@@ -586,6 +945,24 @@ impl Window {
         (x, y, pressed, visible)
     }
 
+    /// Get the X and Y directions of the currently held arrow keys, in the same
+    /// form as an analog stick (see [Self::get_controller_stick]).
+    fn get_keyboard_arrows(&self) -> (f32, f32) {
+        let keyboard = self.event_pump.keyboard_state();
+        let axis = |negative, positive| match (
+            keyboard.is_scancode_pressed(negative),
+            keyboard.is_scancode_pressed(positive),
+        ) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => 0.0,
+        };
+        (
+            axis(Scancode::Left, Scancode::Right),
+            axis(Scancode::Up, Scancode::Down),
+        )
+    }
+
     /// Get the summed X and Y positions and button state of the left or right
     /// analog stick of the game controllers. Each axis value is in the range
     /// [-1, 1].
@@ -678,6 +1055,18 @@ impl Window {
         self.internal_gl_ctx.as_deref_mut().unwrap()
     }
 
+    /// Like [Self::get_internal_gl_ctx], but also returns the debugging
+    /// overlay (see [Self::debug_overlay_mut]), so it can be drawn with the
+    /// internal context.
+    pub fn get_internal_gl_ctx_and_debug_overlay(
+        &mut self,
+    ) -> (&mut dyn GLES, Option<&mut DebugOverlay>) {
+        (
+            self.internal_gl_ctx.as_deref_mut().unwrap(),
+            self.debug_overlay.as_mut(),
+        )
+    }
+
     fn display_splash(&mut self) {
         assert!(self.splash_image.is_some());
 
@@ -686,6 +1075,7 @@ impl Window {
         let matrix = self.output_rotation_matrix().multiply(&Matrix::y_flip());
         let (vx, vy, vw, vh) = self.viewport();
         let viewport = (vx, vy + self.viewport_y_offset(), vw, vh);
+        let filter = self.present_filter();
 
         self.make_internal_gl_ctx_current();
 
@@ -710,34 +1100,33 @@ impl Window {
                 gles11::UNSIGNED_BYTE,
                 image.pixels().as_ptr() as *const _,
             );
-            gl_ctx.TexParameteri(
-                gles11::TEXTURE_2D,
-                gles11::TEXTURE_MIN_FILTER,
-                gles11::LINEAR as _,
-            );
-            gl_ctx.TexParameteri(
-                gles11::TEXTURE_2D,
-                gles11::TEXTURE_MAG_FILTER,
-                gles11::LINEAR as _,
-            );
 
             present_frame(
-                gl_ctx, viewport, matrix, /* virtual_cursor_visible_at: */ None,
+                gl_ctx, viewport, matrix, /* virtual_cursor_visible_at: */ None, filter,
             );
 
             gl_ctx.DeleteTextures(1, &texture);
         };
 
-        self.window.gl_swap_window();
+        self.swap_window();
 
         // hold onto GL context so the image doesn't disappear, and hold
         // onto image so we can rotate later if necessary
     }
 
     /// Swap front-buffer and back-buffer so the result of OpenGL rendering is
-    /// presented.
+    /// presented. In offscreen mode, this does nothing, so the frame can still
+    /// be read back afterwards (see [crate::gles::present::read_presented_frame]).
     pub fn swap_window(&self) {
-        self.window.gl_swap_window();
+        if !self.offscreen {
+            self.window.gl_swap_window();
+        }
+    }
+
+    /// The orientation the emulated device is currently considered to be in.
+    /// See [Self::rotate_device].
+    pub fn device_orientation(&self) -> DeviceOrientation {
+        self.device_orientation
     }
 
     /// Consider the emulated device to be rotated to a particular orientation.
@@ -834,6 +1223,20 @@ impl Window {
 
         let (screen_width, screen_height) = self.window.drawable_size();
 
+        if self.present_scaling == PresentScaling::Integer {
+            let (logical_width, logical_height) =
+                size_for_orientation(self.device_orientation, NonZeroU32::new(1).unwrap());
+            let factor = (screen_width / logical_width).min(screen_height / logical_height);
+            // If even 1× doesn't fit, fall back to scaling down.
+            if factor > 0 {
+                let (scaled_width, scaled_height) =
+                    (logical_width * factor, logical_height * factor);
+                let x = (screen_width - scaled_width) / 2;
+                let y = (screen_height - scaled_height) / 2;
+                return (x, y, scaled_width, scaled_height);
+            }
+        }
+
         let app_aspect = app_width as f32 / app_height as f32;
         let screen_aspect = screen_width as f32 / screen_height as f32;
         let (scaled_width, scaled_height) = if app_aspect < screen_aspect {
@@ -852,6 +1255,16 @@ impl Window {
         (x, y, scaled_width, scaled_height)
     }
 
+    /// Get the texture filter (`GL_LINEAR` or `GL_NEAREST`) to use when scaling
+    /// the app's output to the [Self::viewport].
+    pub fn present_filter(&self) -> crate::gles::gles11_raw::types::GLenum {
+        use crate::gles::gles11_raw as gles11; // constants only
+        match self.present_scaling {
+            PresentScaling::Linear => gles11::LINEAR,
+            PresentScaling::Nearest | PresentScaling::Integer => gles11::NEAREST,
+        }
+    }
+
     /// Special offset to add to y co-ordinates, only when drawing to screen.
     pub fn viewport_y_offset(&self) -> u32 {
         #[cfg(target_os = "macos")]
@@ -881,6 +1294,17 @@ impl Window {
         }
     }
 
+    /// Start or stop receiving [Event::TextInput] and [Event::DeleteBackward]
+    /// events. On platforms without a physical keyboard, this also shows or
+    /// hides the OS's on-screen keyboard.
+    pub fn set_text_input(&mut self, active: bool) {
+        let text_input = self.video_ctx.text_input();
+        match active {
+            true => text_input.start(),
+            false => text_input.stop(),
+        }
+    }
+
     pub fn is_screen_saver_enabled(&self) -> bool {
         self.video_ctx.is_screen_saver_enabled()
     }
@@ -0,0 +1,323 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Recording and replay of input for the `--record-input=` and
+//! `--replay-input=` options.
+//!
+//! A recording is a text file. The first line is [HEADER] followed by the
+//! format version, so that recordings made by an incompatible version of
+//! touchHLE can be detected. Each further line is an input: the time it was
+//! delivered to the app in nanoseconds since startup, its kind, and its
+//! arguments, separated by spaces. For example:
+//!
+//! ```text
+//! touchHLE input recording 1
+//! 1500000000 touch-down mouse 160 240
+//! 1516666666 accelerometer 0 0 -1
+//! 1600000000 touch-up mouse 160 240
+//! ```
+//!
+//! The recorded events are those that have already been translated from OS
+//! events, so replaying doesn't depend on the window size and so on. For exact
+//! reproduction, the virtual clock (`--virtual-clock=`) should be used both
+//! when recording and replaying.
+
+use super::{Event, FingerId};
+use crate::options::Button;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::time::Duration;
+
+pub const HEADER: &str = "touchHLE input recording";
+/// Increment this when making incompatible changes to the format.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedInput {
+    Event(Event),
+    /// New accelerometer reading.
+    Acceleration((f32, f32, f32)),
+}
+
+fn format_finger(finger: FingerId) -> String {
+    match finger {
+        FingerId::Mouse => "mouse".to_string(),
+        FingerId::SecondMouse => "second-mouse".to_string(),
+        FingerId::Touch(id) => format!("touch:{}", id),
+        FingerId::Button(button) => format!("button:{:?}", button),
+        FingerId::VirtualCursor => "virtual-cursor".to_string(),
+        FingerId::Script => "script".to_string(),
+    }
+}
+
+fn parse_finger(finger: &str) -> Option<FingerId> {
+    match finger {
+        "mouse" => Some(FingerId::Mouse),
+        "second-mouse" => Some(FingerId::SecondMouse),
+        "virtual-cursor" => Some(FingerId::VirtualCursor),
+        "script" => Some(FingerId::Script),
+        _ => {
+            if let Some(id) = finger.strip_prefix("touch:") {
+                id.parse().ok().map(FingerId::Touch)
+            } else if let Some(name) = finger.strip_prefix("button:") {
+                Button::from_name(name).map(FingerId::Button)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn format_line(time: Duration, input: &RecordedInput) -> String {
+    let time = time.as_nanos();
+    // Rust's formatting of floats is round-trip safe, so no precision is lost.
+    match *input {
+        RecordedInput::Event(Event::Quit) => format!("{} quit", time),
+        RecordedInput::Event(Event::AppWillResignActive) => format!("{} resign-active", time),
+        RecordedInput::Event(Event::AppWillTerminate) => format!("{} terminate", time),
+        RecordedInput::Event(Event::TouchDown(finger, (x, y))) => {
+            format!("{} touch-down {} {} {}", time, format_finger(finger), x, y)
+        }
+        RecordedInput::Event(Event::TouchMove(finger, (x, y))) => {
+            format!("{} touch-move {} {} {}", time, format_finger(finger), x, y)
+        }
+        RecordedInput::Event(Event::TouchUp(finger, (x, y))) => {
+            format!("{} touch-up {} {} {}", time, format_finger(finger), x, y)
+        }
+        // The text is hex-encoded UTF-8, so it can't contain spaces.
+        RecordedInput::Event(Event::TextInput(ref text)) => {
+            let hex: String = text.bytes().map(|b| format!("{:02x}", b)).collect();
+            format!("{} text-input {}", time, hex)
+        }
+        RecordedInput::Event(Event::DeleteBackward) => format!("{} delete-backward", time),
+        RecordedInput::Event(Event::RotateDevice { clockwise }) => format!(
+            "{} rotate-device {}",
+            time,
+            if clockwise { "cw" } else { "ccw" }
+        ),
+        RecordedInput::Acceleration((x, y, z)) => {
+            format!("{} accelerometer {} {} {}", time, x, y, z)
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Result<(Duration, RecordedInput), String> {
+    let parts: Vec<&str> = line.split(' ').collect();
+    let time: u64 = parts[0]
+        .parse()
+        .map_err(|_| format!("invalid time {:?}", parts[0]))?;
+    let time = Duration::from_nanos(time);
+    let float = |part: &str| -> Result<f32, String> {
+        part.parse()
+            .map_err(|_| format!("invalid number {:?}", part))
+    };
+    let input = match parts[1..] {
+        ["quit"] => RecordedInput::Event(Event::Quit),
+        ["resign-active"] => RecordedInput::Event(Event::AppWillResignActive),
+        ["terminate"] => RecordedInput::Event(Event::AppWillTerminate),
+        [kind @ ("touch-down" | "touch-move" | "touch-up"), finger, x, y] => {
+            let finger =
+                parse_finger(finger).ok_or_else(|| format!("invalid finger {:?}", finger))?;
+            let coords = (float(x)?, float(y)?);
+            RecordedInput::Event(match kind {
+                "touch-down" => Event::TouchDown(finger, coords),
+                "touch-move" => Event::TouchMove(finger, coords),
+                "touch-up" => Event::TouchUp(finger, coords),
+                _ => unreachable!(),
+            })
+        }
+        ["text-input", hex] => {
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| {
+                    hex.get(i..i + 2)
+                        .and_then(|b| u8::from_str_radix(b, 16).ok())
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| format!("invalid text {:?}", hex))?;
+            let text = String::from_utf8(bytes).map_err(|_| format!("invalid text {:?}", hex))?;
+            RecordedInput::Event(Event::TextInput(text))
+        }
+        ["delete-backward"] => RecordedInput::Event(Event::DeleteBackward),
+        ["rotate-device", direction @ ("cw" | "ccw")] => {
+            RecordedInput::Event(Event::RotateDevice {
+                clockwise: direction == "cw",
+            })
+        }
+        ["accelerometer", x, y, z] => {
+            RecordedInput::Acceleration((float(x)?, float(y)?, float(z)?))
+        }
+        _ => return Err("invalid input".to_string()),
+    };
+    Ok((time, input))
+}
+
+/// Parse a recording. The inputs are returned in chronological order.
+pub fn parse(recording: &str) -> Result<Vec<(Duration, RecordedInput)>, String> {
+    let mut lines = recording.lines();
+    let version = lines
+        .next()
+        .and_then(|header| header.strip_prefix(HEADER))
+        .and_then(|version| version.trim().parse::<u32>().ok())
+        .ok_or_else(|| "Not a touchHLE input recording".to_string())?;
+    if version != VERSION {
+        return Err(format!(
+            "The recording is in format version {}, but this version of touchHLE only supports version {}",
+            version, VERSION
+        ));
+    }
+
+    let mut inputs: Vec<(Duration, RecordedInput)> = Vec::new();
+    for (line_idx, line) in lines.enumerate() {
+        // The header is line 1.
+        let line_no = line_idx + 2;
+        let (time, input) = parse_line(line).map_err(|e| format!("Line {}: {}", line_no, e))?;
+        if inputs
+            .last()
+            .map_or(false, |&(last_time, _)| last_time > time)
+        {
+            return Err(format!(
+                "Line {}: inputs must be in chronological order",
+                line_no
+            ));
+        }
+        inputs.push((time, input));
+    }
+    Ok(inputs)
+}
+
+/// Writes inputs to a recording as they happen.
+pub struct Recorder {
+    /// This isn't buffered, so that nothing is lost if touchHLE exits abruptly.
+    file: File,
+    last_acceleration: Option<(f32, f32, f32)>,
+}
+impl Recorder {
+    pub fn create(path: &str) -> Result<Recorder, String> {
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
+        writeln!(file, "{} {}", HEADER, VERSION).map_err(|e| e.to_string())?;
+        Ok(Recorder {
+            file,
+            last_acceleration: None,
+        })
+    }
+
+    pub fn record(&mut self, time: Duration, input: &RecordedInput) {
+        // The accelerometer is polled, so only changes are recorded.
+        if let &RecordedInput::Acceleration(acceleration) = input {
+            if self.last_acceleration == Some(acceleration) {
+                return;
+            }
+            self.last_acceleration = Some(acceleration);
+        }
+        let line = format_line(time, input) + "\n";
+        if let Err(e) = self.file.write_all(line.as_bytes()) {
+            log!("Warning: couldn't write to input recording: {}", e);
+        }
+    }
+}
+
+/// Provides the inputs from a recording at the times they were recorded.
+pub struct Replay {
+    events: VecDeque<(Duration, Event)>,
+    accelerations: VecDeque<(Duration, (f32, f32, f32))>,
+    /// The latest accelerometer reading. Until there is one, the device is
+    /// level with the ground.
+    acceleration: (f32, f32, f32),
+}
+impl Replay {
+    pub fn new(inputs: Vec<(Duration, RecordedInput)>) -> Replay {
+        let mut events = VecDeque::new();
+        let mut accelerations = VecDeque::new();
+        for (time, input) in inputs {
+            match input {
+                RecordedInput::Event(event) => events.push_back((time, event)),
+                RecordedInput::Acceleration(acceleration) => {
+                    accelerations.push_back((time, acceleration))
+                }
+            }
+        }
+        Replay {
+            events,
+            accelerations,
+            acceleration: (0.0, 0.0, -1.0),
+        }
+    }
+
+    /// Get the next event, if it is due by `time`.
+    pub fn pop_event(&mut self, time: Duration) -> Option<Event> {
+        if self.events.front()?.0 > time {
+            return None;
+        }
+        let (_, event) = self.events.pop_front().unwrap();
+        if self.events.is_empty() {
+            log!("Input replay: last event replayed.");
+        }
+        Some(event)
+    }
+
+    /// Get the accelerometer reading at `time`.
+    pub fn acceleration(&mut self, time: Duration) -> (f32, f32, f32) {
+        while self
+            .accelerations
+            .front()
+            .map_or(false, |&(due, _)| due <= time)
+        {
+            self.acceleration = self.accelerations.pop_front().unwrap().1;
+        }
+        self.acceleration
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_round_trip() {
+    let inputs = vec![
+        (
+            Duration::from_nanos(1),
+            RecordedInput::Event(Event::TouchDown(FingerId::Mouse, (0.1, 479.5))),
+        ),
+        (
+            Duration::from_nanos(2),
+            RecordedInput::Event(Event::TouchMove(FingerId::Touch(-3), (1.0, 2.0))),
+        ),
+        (
+            Duration::from_nanos(2),
+            RecordedInput::Event(Event::TouchUp(
+                FingerId::Button(Button::DPadLeft),
+                (3.0, 4.0),
+            )),
+        ),
+        (
+            Duration::from_secs(3),
+            RecordedInput::Acceleration((0.25, -0.5, -1.0 / 3.0)),
+        ),
+        (
+            Duration::from_secs(4),
+            RecordedInput::Event(Event::TextInput("a b\né".to_string())),
+        ),
+        (
+            Duration::from_secs(4),
+            RecordedInput::Event(Event::DeleteBackward),
+        ),
+        (
+            Duration::from_secs(4),
+            RecordedInput::Event(Event::RotateDevice { clockwise: true }),
+        ),
+        (Duration::from_secs(4), RecordedInput::Event(Event::Quit)),
+    ];
+    let mut recording = format!("{} {}\n", HEADER, VERSION);
+    for (time, input) in &inputs {
+        recording += &format_line(*time, input);
+        recording += "\n";
+    }
+    assert_eq!(parse(&recording), Ok(inputs));
+
+    assert!(parse("").is_err());
+    assert!(parse("touchHLE input recording 0\n").is_err());
+    assert!(parse("touchHLE input recording 1\n2 quit\n1 quit").is_err());
+    assert!(parse("touchHLE input recording 1\n1 text-input 6").is_err());
+}
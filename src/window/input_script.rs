@@ -0,0 +1,144 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Scripted input for the `--input-script=` option.
+//!
+//! A script is a text file with one event per line, each line being a time in
+//! seconds since startup (as seen by the app, see also `--virtual-clock=`), the
+//! name of an event and its arguments, separated by spaces. Lines that are
+//! empty or start with `#` are ignored. For example:
+//!
+//! ```text
+//! # Tap the middle of the screen after two seconds.
+//! 2.0 touch-down 160,240
+//! 2.1 touch-up 160,240
+//! 3.0 screenshot
+//! 3.0 quit
+//! ```
+//!
+//! Co-ordinates are in points, with 0,0 being the top-left corner of the
+//! screen in the current orientation, like for `--button-to-touch=`.
+
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptedEventKind {
+    TouchDown((f32, f32)),
+    TouchMove((f32, f32)),
+    TouchUp((f32, f32)),
+    /// Save a screenshot of the next frame, like pressing F12.
+    Screenshot,
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptedEvent {
+    pub time: Duration,
+    pub kind: ScriptedEventKind,
+}
+
+fn parse_coords(coords: Option<&str>) -> Result<(f32, f32), String> {
+    let coords = coords.ok_or_else(|| "missing co-ordinates".to_string())?;
+    let (x, y) = coords
+        .split_once(',')
+        .ok_or_else(|| format!("invalid co-ordinates {:?}", coords))?;
+    let x: f32 = x
+        .parse()
+        .map_err(|_| format!("invalid X co-ordinate {:?}", x))?;
+    let y: f32 = y
+        .parse()
+        .map_err(|_| format!("invalid Y co-ordinate {:?}", y))?;
+    Ok((x, y))
+}
+
+fn parse_line(line: &str) -> Result<ScriptedEvent, String> {
+    let mut parts = line.split_whitespace();
+    let time = parts.next().unwrap();
+    let time: f64 = time
+        .parse()
+        .ok()
+        .filter(|time: &f64| time.is_finite() && *time >= 0.0)
+        .ok_or_else(|| format!("invalid time {:?}", time))?;
+    let kind = match parts.next() {
+        Some("touch-down") => ScriptedEventKind::TouchDown(parse_coords(parts.next())?),
+        Some("touch-move") => ScriptedEventKind::TouchMove(parse_coords(parts.next())?),
+        Some("touch-up") => ScriptedEventKind::TouchUp(parse_coords(parts.next())?),
+        Some("screenshot") => ScriptedEventKind::Screenshot,
+        Some("quit") => ScriptedEventKind::Quit,
+        Some(other) => return Err(format!("unknown event {:?}", other)),
+        None => return Err("missing event".to_string()),
+    };
+    if let Some(extra) = parts.next() {
+        return Err(format!("unexpected {:?}", extra));
+    }
+    Ok(ScriptedEvent {
+        time: Duration::from_secs_f64(time),
+        kind,
+    })
+}
+
+/// Parse an input script. The events are returned in chronological order.
+pub fn parse(script: &str) -> Result<Vec<ScriptedEvent>, String> {
+    let mut events: Vec<ScriptedEvent> = Vec::new();
+    for (line_idx, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let event = parse_line(line).map_err(|e| format!("Line {}: {}", line_idx + 1, e))?;
+        if events.last().map_or(false, |last| last.time > event.time) {
+            return Err(format!(
+                "Line {}: events must be in chronological order",
+                line_idx + 1
+            ));
+        }
+        events.push(event);
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse() {
+    let script = "
+        # comment
+        0 touch-down 1,2
+        0.5 touch-move 3.5,4
+
+        1.25 touch-up 5,6
+        2 screenshot
+        2 quit
+    ";
+    assert_eq!(
+        parse(script),
+        Ok(vec![
+            ScriptedEvent {
+                time: Duration::from_secs(0),
+                kind: ScriptedEventKind::TouchDown((1.0, 2.0)),
+            },
+            ScriptedEvent {
+                time: Duration::from_millis(500),
+                kind: ScriptedEventKind::TouchMove((3.5, 4.0)),
+            },
+            ScriptedEvent {
+                time: Duration::from_millis(1250),
+                kind: ScriptedEventKind::TouchUp((5.0, 6.0)),
+            },
+            ScriptedEvent {
+                time: Duration::from_secs(2),
+                kind: ScriptedEventKind::Screenshot,
+            },
+            ScriptedEvent {
+                time: Duration::from_secs(2),
+                kind: ScriptedEventKind::Quit,
+            },
+        ])
+    );
+    assert!(parse("1 touch-down").is_err());
+    assert!(parse("1 touch-down 1,2 3").is_err());
+    assert!(parse("1 jump").is_err());
+    assert!(parse("-1 quit").is_err());
+    assert!(parse("2 quit\n1 quit").is_err());
+}